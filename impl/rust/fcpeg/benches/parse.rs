@@ -0,0 +1,90 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use fcpeg::parser::*;
+
+use criterion::*;
+
+use rustnutlib::console::*;
+
+mod scenarios;
+
+use scenarios::*;
+
+// spec: BENCH_SCENARIOS の各場面を BENCH_INPUT_LENS のうち場面の上限以下の各大きさの入力で解析する
+// note: 規則マップと入力は計測の外で生成する; 入力の複製と構文木の破棄も計測に含めない
+fn bench_parse(c: &mut Criterion) {
+    let cons = match Console::load(None, ConsoleLogLimit::NoLimit) {
+        Ok(v) => Rc::new(RefCell::new(v)),
+        Err(_) => panic!("failed to load console"),
+    };
+
+    for each_scenario in BENCH_SCENARIOS {
+        let rule_map = each_scenario.load_rule_map(&cons);
+        let mut group = c.benchmark_group(each_scenario.name);
+        // note: 1 MB の入力では 1 回の解析に時間がかかるため標本数を最小とする
+        group.sample_size(10);
+
+        for each_input_len in each_scenario.get_input_lens() {
+            let input = (each_scenario.generate_input)(each_input_len);
+            let config = each_scenario.get_config(input.len());
+            group.throughput(Throughput::Bytes(input.len() as u64));
+
+            group.bench_with_input(BenchmarkId::from_parameter(format!("{}KB", each_input_len / 1024)), &input, |b, input| {
+                b.iter_batched(|| Box::new(input.clone()), |src_content| {
+                    match SyntaxParser::parse(cons.clone(), rule_map.clone(), BENCH_INPUT_FILE_PATH.to_string(), src_content, config.clone()) {
+                        Ok(tree) => tree,
+                        Err(()) => {
+                            cons.borrow().print_all();
+                            panic!("failed to parse the input of scenario '{}'", each_scenario.name);
+                        },
+                    }
+                }, BatchSize::LargeInput);
+            });
+        }
+
+        group.finish();
+    }
+}
+
+// spec: BENCH_SHORT_GROUP_SKIP_SCENARIO_NAME の場面の最小の大きさの入力を行ごとに分けて、最小一致長に満たないグループの検査を省く場合と省かない場合で解析する
+fn bench_short_group_skip(c: &mut Criterion) {
+    let cons = match Console::load(None, ConsoleLogLimit::NoLimit) {
+        Ok(v) => Rc::new(RefCell::new(v)),
+        Err(_) => panic!("failed to load console"),
+    };
+
+    let scenario = match BENCH_SCENARIOS.iter().find(|each_scenario| each_scenario.name == BENCH_SHORT_GROUP_SKIP_SCENARIO_NAME) {
+        Some(v) => v,
+        None => panic!("scenario '{}' is not found", BENCH_SHORT_GROUP_SKIP_SCENARIO_NAME),
+    };
+
+    let rule_map = scenario.load_rule_map(&cons);
+    let input = (scenario.generate_input)(BENCH_INPUT_LENS[0]);
+    let lines = split_input_lines(&input);
+    let mut group = c.benchmark_group("short_group_skip");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+
+    for skip_short_groups in vec![true, false] {
+        let config = scenario.get_config(input.len()).with_short_group_skip(skip_short_groups);
+
+        group.bench_function(BenchmarkId::from_parameter(if skip_short_groups { "skipped" } else { "unskipped" }), |b| {
+            b.iter(|| {
+                for each_line in &lines {
+                    match SyntaxParser::parse(cons.clone(), rule_map.clone(), BENCH_INPUT_FILE_PATH.to_string(), Box::new(each_line.clone()), config.clone()) {
+                        Ok(_) => (),
+                        Err(()) => {
+                            cons.borrow().print_all();
+                            panic!("failed to parse the line {:?}", each_line);
+                        },
+                    }
+                }
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse, bench_short_group_skip);
+criterion_main!(benches);
@@ -0,0 +1,210 @@
+// spec: ベンチマーク (benches/parse.rs) と examples/bench_corpus.rs, tests/bench_scenarios.rs で共有する解析の場面
+// note: 読み込む側によって用いない要素があるため dead_code を許可する
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use fcpeg::*;
+use fcpeg::parser::*;
+use fcpeg::rule::*;
+
+use rustnutlib::console::*;
+
+pub const BENCH_GRAMMAR_FILE_PATH: &'static str = "<bench grammar>";
+pub const BENCH_INPUT_FILE_PATH: &'static str = "<bench input>";
+
+// note: ベンチマークで解析する入力のおおよその大きさ (バイト)
+pub const BENCH_INPUT_LENS: &[usize] = &[10 * 1024, 100 * 1024, 1024 * 1024];
+
+// spec: ベンチマークの場面; 文法と入力の生成関数の組
+// note: 大きな入力ファイルをリポジトリに含めないよう、入力は実行時に生成する
+pub struct BenchScenario {
+    pub name: &'static str,
+    // note: 開始規則は .Main.Main とする
+    pub grammar_src: &'static str,
+    pub enable_memoization: bool,
+    // note: 解析する入力の大きさの上限 (バイト); BENCH_INPUT_LENS のうちこれを超えるものは用いない
+    pub max_input_len: usize,
+    // note: 指定の大きさ (バイト) 以上の入力を生成する; 同じ大きさに対しては常に同じ入力を返す
+    pub generate_input: fn(usize) -> String,
+}
+
+impl BenchScenario {
+    pub fn load_rule_map(&self, cons: &Rc<RefCell<Console>>) -> Arc<Box<RuleMap>> {
+        return match FCPEGGrammar::from_str(cons.clone(), BENCH_GRAMMAR_FILE_PATH, self.grammar_src) {
+            Ok(v) => {
+                cons.borrow_mut().clear_log();
+                v
+            },
+            Err(()) => {
+                cons.borrow().print_all();
+                panic!("failed to load the grammar of scenario '{}'", self.name);
+            },
+        };
+    }
+
+    pub fn get_input_lens(&self) -> Vec<usize> {
+        return BENCH_INPUT_LENS.iter().cloned().filter(|each_len| *each_len <= self.max_input_len).collect();
+    }
+
+    // note: 入力全体を 1 つの繰り返しで読むため、繰り返しの上限は入力の大きさに合わせて引き上げる
+    pub fn get_config(&self, input_len: usize) -> ParseConfig {
+        return ParseConfig::default().with_memoization(self.enable_memoization).with_loop_limit(input_len + 1);
+    }
+}
+
+// spec: 文字列の一致が大半を占める文法; 選択肢がすべて文字列の選択は AnyOf に置き換えられる
+const BENCH_LITERAL_GRAMMAR_SRC: &'static str = r##"[Main]{
+    Main <- Entry* "\z"#,
+    Entry <- "<entry>"# "<key>"# Key "</key>"# "<value>"# Value "</value>"# "</entry>"# "\n"#,
+    Key <- "alpha" : "beta" : "gamma" : "delta" : "epsilon",
+    Value <- "true" : "false" : "null",
+}"##;
+
+// spec: 文字クラスの一致 (正規表現) が大半を占める文法
+const BENCH_CHAR_CLASS_GRAMMAR_SRC: &'static str = r##"[Main]{
+    Main <- Token* "\z"#,
+    Token <- Ident : Number : Space#,
+    Ident <- [a-zA-Z_] [a-zA-Z0-9_]*,
+    Number <- [0-9]+ ("." [0-9]+)?,
+    Space <- [ \t\n]+,
+}"##;
+
+// spec: 入れ子の深い式の文法; Call と Var は同じ Ident から始まるため、メモ化の有無で Ident の再解析の回数が変わる
+const BENCH_EXPR_GRAMMAR_SRC: &'static str = r##"[Main]{
+    Main <- Line* "\z"#,
+    Line <- Expr ";"# "\n"#,
+    Expr <- Term (("+" : "-") Term)*##,
+    Term <- Factor (("*" : "/") Factor)*##,
+    Factor <- Num : Call : Var : "("# Expr ")"#,
+    Call <- Ident "("# Expr ")"#,
+    Var <- Ident,
+    Num <- JOIN<[0-9]+>,
+    Ident <- JOIN<[a-z]+>,
+}"##;
+
+// spec: 選択肢の多いキーワードの文法; 短いキーワードを先に置くため、長いキーワードは前方一致した選択肢の後戻りを経て一致する
+const BENCH_KEYWORD_GRAMMAR_SRC: &'static str = r##"[Main]{
+    Main <- Stmt* "\z"#,
+    Stmt <- "as" Body : "async" Body : "await" Body : "break" Body : "case" Body : "catch" Body : "class" Body : "const" Body : "continue" Body : "default" Body : "do" Body : "double" Body : "else" Body : "enum" Body : "extends" Body : "for" Body : "foreach" Body : "if" Body : "impl" Body : "import" Body : "in" Body : "int" Body : "interface" Body : "return" Body,
+    Body <- " "# Ident ";"# "\n"#,
+    Ident <- JOIN<[a-z]+>,
+}"##;
+
+const BENCH_KEYWORDS: &[&'static str] = &["as", "async", "await", "break", "case", "catch", "class", "const", "continue", "default", "do", "double", "else", "enum", "extends", "for", "foreach", "if", "impl", "import", "in", "int", "interface", "return"];
+
+// note: 式の括弧の入れ子の深さの上限; デバッグビルドのテストのスレッド (スタック 2 MB) でも溢れない範囲とする
+const BENCH_EXPR_MAX_NESTING: usize = 10;
+
+pub const BENCH_SCENARIOS: &[BenchScenario] = &[
+    BenchScenario {
+        name: "literal",
+        grammar_src: BENCH_LITERAL_GRAMMAR_SRC,
+        enable_memoization: true,
+        max_input_len: 1024 * 1024,
+        generate_input: generate_bench_literal_input,
+    },
+    BenchScenario {
+        name: "char_class",
+        grammar_src: BENCH_CHAR_CLASS_GRAMMAR_SRC,
+        enable_memoization: true,
+        max_input_len: 1024 * 1024,
+        generate_input: generate_bench_char_class_input,
+    },
+    BenchScenario {
+        name: "expr_memoized",
+        grammar_src: BENCH_EXPR_GRAMMAR_SRC,
+        enable_memoization: true,
+        // note: 1 MB の入力では 1 回の解析に数十秒かかり、メモ化データも数 GB となるため 100 KB までとする
+        max_input_len: 100 * 1024,
+        generate_input: generate_bench_expr_input,
+    },
+    BenchScenario {
+        name: "expr_unmemoized",
+        grammar_src: BENCH_EXPR_GRAMMAR_SRC,
+        enable_memoization: false,
+        max_input_len: 100 * 1024,
+        generate_input: generate_bench_expr_input,
+    },
+    BenchScenario {
+        name: "keyword",
+        grammar_src: BENCH_KEYWORD_GRAMMAR_SRC,
+        enable_memoization: true,
+        max_input_len: 1024 * 1024,
+        generate_input: generate_bench_keyword_input,
+    },
+];
+
+// spec: 最小一致長による検査の省略を計測する場面の名前; 入力を行ごとに分けて解析すると、入力の末尾で省かれる検査の割合が大きくなる
+pub const BENCH_SHORT_GROUP_SKIP_SCENARIO_NAME: &'static str = "keyword";
+
+// ret: 行末の改行を含む各行
+pub fn split_input_lines(input: &str) -> Vec<String> {
+    return input.split_inclusive('\n').map(|each_line| each_line.to_string()).collect();
+}
+
+fn generate_bench_literal_input(input_len: usize) -> String {
+    let keys = ["alpha", "beta", "gamma", "delta", "epsilon"];
+    let values = ["true", "false", "null"];
+    let mut input = String::new();
+    let mut i = 0usize;
+
+    while input.len() < input_len {
+        input += &format!("<entry><key>{}</key><value>{}</value></entry>\n", keys[i % keys.len()], values[i % values.len()]);
+        i += 1;
+    }
+
+    return input;
+}
+
+fn generate_bench_char_class_input(input_len: usize) -> String {
+    let mut input = String::new();
+    let mut i = 0usize;
+
+    while input.len() < input_len {
+        input += &format!("ident_{} {}.{} Word{}\t{}\n", i, i, i % 100, i % 17, i * 31);
+        i += 1;
+    }
+
+    return input;
+}
+
+fn generate_bench_expr_input(input_len: usize) -> String {
+    let var_names = ["x", "y", "z", "n"];
+    let mut input = String::new();
+    let mut i = 0usize;
+
+    while input.len() < input_len {
+        let nesting = i % BENCH_EXPR_MAX_NESTING + 1;
+        let mut line = var_names[i % var_names.len()].to_string();
+
+        for each_depth in 0..nesting {
+            line = match each_depth % 3 {
+                0 => format!("f({}+{})", line, each_depth),
+                1 => format!("({}*y-{})", line, each_depth),
+                _ => format!("g({})/{}", line, each_depth + 1),
+            };
+        }
+
+        input += &line;
+        input += ";\n";
+        i += 1;
+    }
+
+    return input;
+}
+
+fn generate_bench_keyword_input(input_len: usize) -> String {
+    let mut input = String::new();
+    let mut i = 0usize;
+
+    while input.len() < input_len {
+        // note: 7 は BENCH_KEYWORDS の要素数と互いに素であるため、すべてのキーワードが順に現れる
+        input += &format!("{} name;\n", BENCH_KEYWORDS[i * 7 % BENCH_KEYWORDS.len()]);
+        i += 1;
+    }
+
+    return input;
+}
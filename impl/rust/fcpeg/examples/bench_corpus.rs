@@ -0,0 +1,106 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use fcpeg::*;
+use fcpeg::parser::*;
+use fcpeg::rule::*;
+
+use rustnutlib::console::*;
+
+#[path = "../benches/scenarios/mod.rs"]
+mod scenarios;
+
+use scenarios::*;
+
+// note: 計測の回数; 最も速かった回の結果を表示する
+const MEASUREMENT_COUNT: usize = 3;
+
+// spec: 引数がなければ BENCH_SCENARIOS の各場面を、文法ファイルと入力ファイルを指定すればその組を解析し、スループット (MB/s) と解析の統計情報を表示する
+// note: 開始規則 ID を省略した場合は文法の開始規則を用いる
+// usage: cargo run --release --example bench_corpus [-- <文法ファイル> <入力ファイル> [開始規則 ID]]
+fn main() {
+    let cons = match Console::load(None, ConsoleLogLimit::NoLimit) {
+        Ok(v) => Rc::new(RefCell::new(v)),
+        Err(_) => panic!("failed to load console"),
+    };
+
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+
+    match args.as_slice() {
+        [] => {
+            for each_scenario in BENCH_SCENARIOS {
+                let rule_map = each_scenario.load_rule_map(&cons);
+
+                for each_input_len in each_scenario.get_input_lens() {
+                    let input = (each_scenario.generate_input)(each_input_len);
+                    let config = each_scenario.get_config(input.len());
+                    measure(&cons, &format!("{}/{}KB", each_scenario.name, each_input_len / 1024), rule_map.clone(), &input, config);
+                }
+            }
+        },
+        [grammar_file_path, input_file_path] | [grammar_file_path, input_file_path, _] => {
+            let rule_map = match FCPEGGrammar::load(cons.clone(), grammar_file_path.clone(), HashMap::new()) {
+                Ok(v) => v,
+                Err(()) => {
+                    cons.borrow().print_all();
+                    std::process::exit(1);
+                },
+            };
+
+            let input = match std::fs::read_to_string(input_file_path) {
+                Ok(v) => v,
+                Err(e) => {
+                    eprintln!("failed to read input file '{}': {}", input_file_path, e);
+                    std::process::exit(1);
+                },
+            };
+
+            let mut config = ParseConfig::default().with_loop_limit(std::cmp::max(DEFAULT_LOOP_LIMIT, input.len() + 1));
+
+            match args.get(2) {
+                Some(start_rule_id) => config = config.with_start_rule_id(start_rule_id.clone()),
+                None => (),
+            }
+
+            measure(&cons, input_file_path, rule_map, &input, config);
+        },
+        _ => {
+            eprintln!("usage: bench_corpus [<grammar file> <input file> [<start rule ID>]]");
+            std::process::exit(1);
+        },
+    }
+}
+
+fn measure(cons: &Rc<RefCell<Console>>, name: &str, rule_map: Arc<Box<RuleMap>>, input: &str, config: ParseConfig) {
+    let mut fastest_elapsed_time = Duration::MAX;
+    let mut stats = ParseStats::default();
+
+    for _ in 0..MEASUREMENT_COUNT {
+        let src_content = Box::new(input.to_string());
+        let started_at = Instant::now();
+
+        let output = match SyntaxParser::parse_with_stats(cons.clone(), rule_map.clone(), name.to_string(), src_content, config.clone()) {
+            Ok(v) => v,
+            Err(()) => {
+                cons.borrow().print_all();
+                std::process::exit(1);
+            },
+        };
+
+        let elapsed_time = started_at.elapsed();
+
+        if elapsed_time < fastest_elapsed_time {
+            fastest_elapsed_time = elapsed_time;
+        }
+
+        stats = output.stats;
+    }
+
+    let throughput = input.len() as f64 / (1024.0 * 1024.0) / fastest_elapsed_time.as_secs_f64();
+    println!("{}: {} bytes in {:.2} ms ({:.2} MB/s)", name, input.len(), fastest_elapsed_time.as_secs_f64() * 1000.0, throughput);
+    println!("    {}", stats);
+}
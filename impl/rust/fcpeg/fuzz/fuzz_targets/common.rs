@@ -0,0 +1,96 @@
+// spec: fuzz_targets の各ターゲットで共有するファジングの処理
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use fcpeg::*;
+use fcpeg::parser::*;
+use fcpeg::rule::*;
+
+use rustnutlib::console::*;
+
+pub const FUZZ_GRAMMAR_FILE_PATH: &'static str = "<fuzz grammar>";
+pub const FUZZ_INPUT_FILE_PATH: &'static str = "<fuzz input>";
+
+// spec: ファジングで入力を解析する代表的な文法; 選択, 繰り返し, 区切り付きの繰り返し, 先読み, ワイルドカード, JOIN, 多バイト文字の文字クラスを含む
+// note: 後方参照を含む文法はメモ化が無効になるため含めない (後方参照は MEMOIZATION_TEST_CORPUS で扱う)
+pub const FUZZ_GRAMMAR_SRC: &'static str = r##"[Main]{
+    Main <- Space# (Stmt Space#)* "\z"#,
+    Stmt <- (Let : Expr) Space# ";"#,
+    Let <- "let"# Space# Ident Space# "="# Space# Expr,
+    Expr <- Prod (Space# ("+" : "-") Space# Prod)*##,
+    Prod <- Unary (Space# ("*" : "/") Space# Unary)*##,
+    Unary <- "-"? Atom,
+    Atom <- Num : Str : Call : Ident : List : "("# Space# Expr Space# ")"#,
+    Call <- Ident "("# Space# (Expr (Space# ","# Space# Expr)*##)?## Space# ")"#,
+    List <- "["# Item ** "," "]"#,
+    Item <- Space# Expr Space#,
+    Num <- JOIN<[0-9]+ ("." [0-9]+)?>,
+    Str <- "\""# JOIN<(!"\"" .)*> "\""#,
+    Ident <- !Keyword JOIN<[a-zA-Z_ぁ-ん] [a-zA-Z0-9_ぁ-ん]*>,
+    Keyword <- "let" ![a-zA-Z0-9_],
+    Space <- (" " : "\n" : "\t" : "#" (!"\n" .)*)*,
+}"##;
+
+// note: ファジングでは無限ループや深い再帰を早く打ち切るため既定値より小さくする
+pub const FUZZ_LOOP_LIMIT: usize = 256;
+pub const FUZZ_MAX_DEPTH: usize = 64;
+
+// note: 文法のファジングで解析する固定の入力
+pub const FUZZ_INPUT: &'static str = "let x = (1 + 2) * [a, \"b\"];\nf(x);";
+
+// spec: 任意のバイト列を文字列に変換して FUZZ_GRAMMAR_SRC で解析し、パニックせず、成功するか失敗のログを伴って失敗することを確認する
+// note: cargo-fuzz のターゲットから呼び出す; メモ化の有無の両方で解析する
+pub fn fuzz_parse_input(data: &[u8]) {
+    let cons = new_console();
+
+    let rule_map = match FCPEGGrammar::from_str(cons.clone(), FUZZ_GRAMMAR_FILE_PATH, FUZZ_GRAMMAR_SRC) {
+        Ok(v) => v,
+        Err(()) => {
+            cons.borrow().print_all();
+            panic!("failed to load the fuzzing grammar");
+        },
+    };
+
+    let input = String::from_utf8_lossy(data).to_string();
+
+    for enable_memoization in vec![true, false] {
+        assert_parses_or_logs(&cons, rule_map.clone(), &input, enable_memoization);
+    }
+}
+
+// spec: 任意のバイト列を文字列に変換して文法として読み込み、読み込めれば FUZZ_INPUT を解析してパニックしないことを確認する
+// note: 読み込みの失敗はコンソールへのログ出力のみを伴うため、失敗したこと自体は検査しない; 文法の構文解析は GRAMMAR_LOOP_LIMIT と GRAMMAR_MAX_DEPTH で打ち切られる
+pub fn fuzz_load_grammar(data: &[u8]) {
+    let cons = new_console();
+    let grammar_src = String::from_utf8_lossy(data).to_string();
+
+    let rule_map = match FCPEGGrammar::from_str(cons.clone(), FUZZ_GRAMMAR_FILE_PATH, &grammar_src) {
+        Ok(v) => v,
+        Err(()) => return,
+    };
+
+    for enable_memoization in vec![true, false] {
+        assert_parses_or_logs(&cons, rule_map.clone(), FUZZ_INPUT, enable_memoization);
+    }
+}
+
+fn assert_parses_or_logs(cons: &Rc<RefCell<Console>>, rule_map: Arc<Box<RuleMap>>, input: &str, enable_memoization: bool) {
+    let mut parser = SyntaxParser::new(cons.clone(), rule_map, FUZZ_INPUT_FILE_PATH.to_string(), Box::new(input.to_string()), enable_memoization).with_loop_limit(FUZZ_LOOP_LIMIT).with_max_depth(FUZZ_MAX_DEPTH);
+
+    match parser.run() {
+        Ok(_) => (),
+        Err(()) if parser.get_logs().len() != 0 => (),
+        Err(()) => panic!("parsing failed without logs (memoization: {})\ninput:\t{:?}", enable_memoization, input),
+    }
+
+    cons.borrow_mut().clear_log();
+}
+
+fn new_console() -> Rc<RefCell<Console>> {
+    return match Console::load(None, ConsoleLogLimit::NoLimit) {
+        Ok(v) => Rc::new(RefCell::new(v)),
+        Err(_) => panic!("failed to load console"),
+    };
+}
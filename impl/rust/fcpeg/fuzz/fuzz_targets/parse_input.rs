@@ -0,0 +1,11 @@
+#![no_main]
+
+mod common;
+
+use common::*;
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    fuzz_parse_input(data);
+});
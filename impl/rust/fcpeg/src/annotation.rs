@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use crate::tree::*;
+
+use uuid::Uuid;
+
+// spec: 構文木と同じ構造を持ち、各ノード・リーフに注釈を付加した木; 型・スコープ・評価結果などの属性を要素に対応づける
+// note: 注釈は要素と UUID の索引の双方に保持するため A: Clone とする
+pub struct AnnotatedTree<A: Clone> {
+    child: AnnotatedNodeElement<A>,
+    annotation_map: HashMap<Uuid, A>,
+}
+
+impl<A: Clone> AnnotatedTree<A> {
+    // spec: 構文木のすべての要素 (非反映的な要素を含む) について行きがけ順に f を呼び出し、その結果を注釈とする
+    // note: UUID が重複する場合、索引には先に訪れた要素の注釈が残る
+    pub fn from_tree<F: Fn(&SyntaxNodeElement) -> A>(tree: &SyntaxTree, f: &F) -> AnnotatedTree<A> {
+        let mut annotation_map = HashMap::<Uuid, A>::new();
+        let child = AnnotatedTree::to_annotated_elem(tree.get_child_ref(), f, &mut annotation_map);
+
+        return AnnotatedTree {
+            child: child,
+            annotation_map: annotation_map,
+        };
+    }
+
+    fn to_annotated_elem<F: Fn(&SyntaxNodeElement) -> A>(elem: &SyntaxNodeElement, f: &F, annotation_map: &mut HashMap<Uuid, A>) -> AnnotatedNodeElement<A> {
+        let annotation = f(elem);
+
+        return match elem {
+            SyntaxNodeElement::Node(node) => {
+                annotation_map.entry(node.uuid).or_insert(annotation.clone());
+                let sub_elems = node.sub_elems.iter().map(|each_elem| AnnotatedTree::to_annotated_elem(each_elem, f, annotation_map)).collect::<Vec<AnnotatedNodeElement<A>>>();
+
+                AnnotatedNodeElement::Node(Box::new(AnnotatedNode {
+                    sub_elems: sub_elems,
+                    ast_reflection_style: node.ast_reflection_style.clone(),
+                    uuid: node.uuid,
+                    is_presence_marker: node.is_presence_marker,
+                    src_range: node.src_range,
+                    annotation: annotation,
+                }))
+            },
+            SyntaxNodeElement::Leaf(leaf) => {
+                annotation_map.entry(leaf.uuid).or_insert(annotation.clone());
+
+                AnnotatedNodeElement::Leaf(Box::new(AnnotatedLeaf {
+                    pos: leaf.pos.clone(),
+                    value: leaf.value.clone(),
+                    raw_value: leaf.raw_value.clone(),
+                    ast_reflection_style: leaf.ast_reflection_style.clone(),
+                    uuid: leaf.uuid,
+                    annotation: annotation,
+                }))
+            },
+        };
+    }
+
+    pub fn get_child_ref(&self) -> &AnnotatedNodeElement<A> {
+        return &self.child;
+    }
+
+    // ret: UUID が uuid である要素の注釈
+    // spec: 木に存在しない UUID を指定した場合は panic する; 存在しない場合を扱う場合は get_annotation_checked() を用いる
+    pub fn get_annotation(&self, uuid: &Uuid) -> &A {
+        return match self.annotation_map.get(uuid) {
+            Some(v) => v,
+            None => panic!("element {} not found in annotated tree", uuid),
+        };
+    }
+
+    // spec: get_annotation() と同様だが、存在しなければ None を返す
+    pub fn get_annotation_checked(&self, uuid: &Uuid) -> Option<&A> {
+        return self.annotation_map.get(uuid);
+    }
+
+    // ret: 注釈を持つ要素の数 (UUID の重複を除く)
+    pub fn len(&self) -> usize {
+        return self.annotation_map.len();
+    }
+}
+
+pub enum AnnotatedNodeElement<A> {
+    Node(Box<AnnotatedNode<A>>),
+    Leaf(Box<AnnotatedLeaf<A>>),
+}
+
+impl<A> AnnotatedNodeElement<A> {
+    pub fn get_annotation(&self) -> &A {
+        return match self {
+            AnnotatedNodeElement::Node(node) => &node.annotation,
+            AnnotatedNodeElement::Leaf(leaf) => &leaf.annotation,
+        };
+    }
+
+    pub fn get_uuid(&self) -> &Uuid {
+        return match self {
+            AnnotatedNodeElement::Node(node) => &node.uuid,
+            AnnotatedNodeElement::Leaf(leaf) => &leaf.uuid,
+        };
+    }
+}
+
+pub struct AnnotatedNode<A> {
+    pub sub_elems: Vec<AnnotatedNodeElement<A>>,
+    pub ast_reflection_style: ASTReflectionStyle,
+    pub uuid: Uuid,
+    pub is_presence_marker: bool,
+    pub src_range: Option<(usize, usize)>,
+    pub annotation: A,
+}
+
+pub struct AnnotatedLeaf<A> {
+    pub pos: CharacterPosition,
+    pub value: String,
+    pub raw_value: Option<Vec<u8>>,
+    pub ast_reflection_style: ASTReflectionStyle,
+    pub uuid: Uuid,
+    pub annotation: A,
+}
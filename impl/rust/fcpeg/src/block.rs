@@ -4,12 +4,12 @@ use std::cell::RefCell;
 use std::sync::Arc;
 
 use crate::*;
+use crate::diagnostic::*;
 use crate::parser::*;
 use crate::rule::*;
+use crate::terminal::*;
 use crate::tree::*;
 
-use colored::*;
-
 use rustnutlib::*;
 use rustnutlib::console::*;
 
@@ -46,7 +46,7 @@ macro_rules! rule {
 
             let mut root_group = Box::new(RuleGroup::new(RuleGroupKind::Choice));
             root_group.sub_elems = sub_elems;
-            root_group.ast_reflection_style = ASTReflectionStyle::Expansion;
+            root_group.ast_reflection_style = ASTReflectionStyle::expansion();
 
             let rule = Rule::new(CharacterPosition::get_empty(), $rule_name.to_string(), String::new(), Vec::new(), Vec::new(), root_group);
             BlockCommand::Define { pos: CharacterPosition::get_empty(), rule: rule }
@@ -67,17 +67,17 @@ macro_rules! group {
         {
             let mut group = RuleGroup::new(RuleGroupKind::Sequence);
             group.sub_elems = vec![$($sub_elem,)*];
-            group.ast_reflection_style = ASTReflectionStyle::Reflection(String::new());
+            group.ast_reflection_style = ASTReflectionStyle::reflection(String::new());
 
             for opt in $options {
                 match opt {
                     "&" | "!" => group.lookahead_kind = RuleElementLookaheadKind::new(opt),
                     "?" | "*" | "+" => group.loop_range = RuleElementLoopRange::from(opt),
-                    "#" => group.ast_reflection_style = ASTReflectionStyle::NoReflection,
-                    "##" => group.ast_reflection_style = ASTReflectionStyle::Expansion,
+                    "#" => group.ast_reflection_style = ASTReflectionStyle::no_reflection(),
+                    "##" => group.ast_reflection_style = ASTReflectionStyle::expansion(),
                     ":" => group.kind = RuleGroupKind::Choice,
                     _ if opt.len() >= 2 && opt.starts_with("#") =>
-                        group.ast_reflection_style = ASTReflectionStyle::Reflection(opt[1..].to_string()),
+                        group.ast_reflection_style = ASTReflectionStyle::reflection(opt[1..].to_string()),
                     _ => panic!(),
                 }
             }
@@ -103,16 +103,16 @@ macro_rules! expr {
                 _ => String::new(),
             };
 
-            expr.ast_reflection_style = ASTReflectionStyle::Reflection(leaf_name);
+            expr.ast_reflection_style = ASTReflectionStyle::reflection(leaf_name);
 
             $(
                 match $option {
                     "&" | "!" => expr.lookahead_kind = RuleElementLookaheadKind::new($option),
                     "?" | "*" | "+" => expr.loop_range = RuleElementLoopRange::from($option),
-                    "#" => expr.ast_reflection_style = ASTReflectionStyle::NoReflection,
-                    "##" => expr.ast_reflection_style = ASTReflectionStyle::Expansion,
+                    "#" => expr.ast_reflection_style = ASTReflectionStyle::no_reflection(),
+                    "##" => expr.ast_reflection_style = ASTReflectionStyle::expansion(),
                     _ if $option.len() >= 2 && $option.starts_with("#") =>
-                        expr.ast_reflection_style = ASTReflectionStyle::Reflection($option[1..].to_string()),
+                        expr.ast_reflection_style = ASTReflectionStyle::reflection($option[1..].to_string()),
                     _ => panic!(),
                 }
             )*
@@ -128,19 +128,27 @@ pub enum BlockParsingLog {
     AttemptToAccessPrivateItem { pos: CharacterPosition, item_id: String },
     BlockAliasNotFoundOrUsed { pos: CharacterPosition, block_alias_name: String },
     // ChildElementNotMatched { parent_uuid: Uuid, expected: String, },
+    CyclicConstantReference { pos: CharacterPosition, const_names: Vec<String> },
     DuplicateBlockName { pos: CharacterPosition, block_name: String },
+    DuplicateConstantName { pos: CharacterPosition, const_name: String },
+    DuplicateEntryCommand { pos: CharacterPosition, rule_id: String },
     DuplicateArgumentID { pos: CharacterPosition, arg_id: String },
-    DuplicateRuleName { pos: CharacterPosition, rule_name: String },
+    DuplicateRuleName { pos: CharacterPosition, original_pos: CharacterPosition, rule_name: String },
     DuplicateStartCommand { pos: CharacterPosition },
+    EntryCommandOutsideMainBlock { pos: CharacterPosition },
+    InvalidEscapeSequence { pos: CharacterPosition, esc_seq: String, msg: String },
     InvalidID { pos: CharacterPosition, id: String },
+    InvalidLongestMatch { pos: CharacterPosition, msg: String },
     InvalidLoopRange { pos: CharacterPosition, msg: String },
+    InvalidPresenceMarker { pos: CharacterPosition, msg: String },
+    InvalidSeparator { pos: CharacterPosition, msg: String },
     NamingRuleViolation { pos: CharacterPosition, id: String },
     RandomOrderInExpression { pos: CharacterPosition },
     StartCommandOutsideMainBlock { pos: CharacterPosition },
     UnexpectedChildName { parent_uuid: Uuid, unexpected: String, expected: String },
     UnexpectedNodeName { uuid: Uuid, unexpected: String, expected: String },
-    UnknownEscapeSequenceCharacter { pos: CharacterPosition },
     UnknownBlockID { pos: CharacterPosition, block_id: String },
+    UnknownConstantName { pos: CharacterPosition, const_name: String },
     UnknownRuleID { pos: CharacterPosition, rule_id: String },
     UnnecessaryBlockAliasName { pos: CharacterPosition, alias_name: String, },
     UnnecessaryStartCommand { pos: CharacterPosition, msg: String },
@@ -154,24 +162,32 @@ impl ConsoleLogger for BlockParsingLog {
             BlockParsingLog::AttemptToAccessPrivateItem { pos, item_id } => log!(Warning, "attempt to access private item", format!("at:\t{}", pos), format!("id:\t{}", item_id)),
             BlockParsingLog::BlockAliasNotFoundOrUsed { pos, block_alias_name } => log!(Error, format!("block alias '{}' not found or used", block_alias_name), format!("at:\t{}", pos)),
             // BlockParsingLog::ChildElementNotMatched { parent_uuid, expected } => log!(Error, format!("child element not matched"), format!("parent:\t{}", parent_uuid), format!("expected:\t{}", expected)),
+            BlockParsingLog::CyclicConstantReference { pos, const_names } => log!(Error, "cyclic constant reference", format!("at:\t{}", pos), format!("cycle:\t{}", const_names.join(" -> "))),
             BlockParsingLog::DuplicateBlockName { pos, block_name } => log!(Error, format!("duplicate block name '{}'", block_name), format!("at:\t{}", pos)),
+            BlockParsingLog::DuplicateConstantName { pos, const_name } => log!(Error, format!("duplicate constant name '{}'", const_name), format!("at:\t{}", pos)),
+            BlockParsingLog::DuplicateEntryCommand { pos, rule_id } => log!(Error, format!("duplicate entry command '{}'", rule_id), format!("at:\t{}", pos)),
             BlockParsingLog::DuplicateArgumentID { pos, arg_id } => log!(Error, format!("duplicate argument id '{}'", arg_id), format!("at:\t{}", pos)),
-            BlockParsingLog::DuplicateRuleName { pos, rule_name } => log!(Error, format!("duplicate rule name '{}'", rule_name), format!("at:\t{}", pos)),
+            BlockParsingLog::DuplicateRuleName { pos, original_pos, rule_name } => log!(Error, format!("duplicate rule name '{}'", rule_name), format!("at:\t{}", pos), format!("original:\t{}", original_pos)),
             BlockParsingLog::DuplicateStartCommand { pos } => log!(Error, "duplicate start command", format!("at:\t{}", pos)),
+            BlockParsingLog::EntryCommandOutsideMainBlock { pos } => log!(Error, "entry command outside main block", format!("at:\t{}", pos)),
+            BlockParsingLog::InvalidEscapeSequence { pos, esc_seq, msg } => log!(Error, format!("invalid escape sequence '\\{}'", esc_seq), format!("at:\t{}", pos), to_secondary_text(msg)),
             BlockParsingLog::InvalidID { pos, id } => log!(Error, format!("invalid id '{}'", id), format!("at:\t{}", pos)),
-            BlockParsingLog::InvalidLoopRange { pos, msg } => log!(Error, format!("invalid loop range"), format!("at:\t{}", pos), format!("{}", msg.bright_black())),
+            BlockParsingLog::InvalidLongestMatch { pos, msg } => log!(Error, format!("invalid longest match"), format!("at:\t{}", pos), to_secondary_text(msg)),
+            BlockParsingLog::InvalidLoopRange { pos, msg } => log!(Error, format!("invalid loop range"), format!("at:\t{}", pos), to_secondary_text(msg)),
+            BlockParsingLog::InvalidPresenceMarker { pos, msg } => log!(Error, format!("invalid presence marker"), format!("at:\t{}", pos), to_secondary_text(msg)),
+            BlockParsingLog::InvalidSeparator { pos, msg } => log!(Error, format!("invalid separator"), format!("at:\t{}", pos), to_secondary_text(msg)),
             BlockParsingLog::NamingRuleViolation { pos, id } => log!(Warning, "naming rule violation", format!("at:\t{}", pos), format!("id:\t{}", id)),
-            BlockParsingLog::RandomOrderInExpression { pos } => log!(Error, "random order in expression", format!("at:\t{}", pos), format!("{}", "cannot specify random order symbol to expression".bright_black())),
+            BlockParsingLog::RandomOrderInExpression { pos } => log!(Error, "random order in expression", format!("at:\t{}", pos), to_secondary_text("cannot specify random order symbol to expression")),
             BlockParsingLog::StartCommandOutsideMainBlock { pos } => log!(Error, "start command outside main block", format!("at:\t{}", pos)),
             BlockParsingLog::UnexpectedChildName { parent_uuid, unexpected, expected } => log!(Error, format!("unknown node name {}, expected {}", unexpected, expected), format!("parent uuid:\t{}", parent_uuid)),
             BlockParsingLog::UnexpectedNodeName { uuid, unexpected, expected } => log!(Error, format!("unknown node name {}, expected {}", unexpected, expected), format!("uuid:\t{}", uuid)),
-            BlockParsingLog::UnknownEscapeSequenceCharacter { pos } => log!(Error, "unknown escape sequence character", format!("at:\t{}", pos)),
             BlockParsingLog::UnknownBlockID { pos, block_id } => log!(Error, format!("unknown block id '{}'", block_id), format!("at:\t{}", pos)),
+            BlockParsingLog::UnknownConstantName { pos, const_name } => log!(Error, format!("unknown constant name '{}'", const_name), format!("at:\t{}", pos)),
             BlockParsingLog::UnknownRuleID { pos, rule_id } => log!(Error, format!("unknown rule id '{}'", rule_id), format!("at:\t{}", pos)),
             BlockParsingLog::UnnecessaryBlockAliasName { pos, alias_name } => log!(Warning, format!("unnecessary block alias name"), format!("at:\t{}", pos), format!("alias name:\t{}", alias_name)),
-            BlockParsingLog::UnnecessaryStartCommand { pos, msg } => log!(Warning, format!("unnecessary start command"), format!("at:\t{}", pos), format!("{}", msg.bright_black())),
-            BlockParsingLog::UnnecessaryUseCommand { pos, msg } => log!(Warning, format!("unnecessary use command"), format!("at:\t{}", pos), format!("{}", msg.bright_black())),
-            BlockParsingLog::UnrecommendedLoopRange { pos, msg } => log!(Warning, format!("unrecommended loop range"), format!("at:\t{}", pos), format!("{}", msg.bright_black())),
+            BlockParsingLog::UnnecessaryStartCommand { pos, msg } => log!(Warning, format!("unnecessary start command"), format!("at:\t{}", pos), to_secondary_text(msg)),
+            BlockParsingLog::UnnecessaryUseCommand { pos, msg } => log!(Warning, format!("unnecessary use command"), format!("at:\t{}", pos), to_secondary_text(msg)),
+            BlockParsingLog::UnrecommendedLoopRange { pos, msg } => log!(Warning, format!("unrecommended loop range"), format!("at:\t{}", pos), to_secondary_text(msg)),
         }
     }
 }
@@ -186,14 +202,41 @@ pub struct RawRange {
     pub max_num_pos: Option<CharacterPosition>,
 }
 
+// note: 区切り付き繰り返しの指定
+pub struct RawSeparator {
+    pub pos: CharacterPosition,
+    pub loop_range: RuleElementLoopRange,
+    pub elem: RuleElement,
+    pub allows_trailing_separator: bool,
+}
+
+// note: 展開前の定数値の要素
+enum RawConstValueElement {
+    ConstID { pos: CharacterPosition, name: String },
+    String(String),
+}
+
 // note: プリミティブ規則名の一覧
-pub const PRIMITIVE_RULE_NAMES: &[&'static str] = &["JOIN"];
+pub const PRIMITIVE_RULE_NAMES: &[&'static str] = &["CAPTURE", "EPSILON", "INTO", "JOIN", "MATCH_CAPTURED", "SCAN"];
+// note: 引数なしで記述すると RuleExpressionKind::Epsilon に置き換えるプリミティブ規則名
+pub const EPSILON_RULE_NAME: &'static str = "EPSILON";
 // note: デフォルトの開始規則 ID
 pub const DEFAULT_START_RULE_ID: &'static str = ".Main.Main";
+// note: 反映名のない反映方式を明示する反映名 (#_)
+pub const ANONYMOUS_REFLECTION_NAME: &'static str = "_";
+// note: 文字列中の \z を表す文字; 内部用の非文字 U+FDD0 を用いて \0 (U+0000) と区別する
+pub const EOF_ESCAPE_CHAR: char = '\u{FDD0}';
+// note: FCPEG コードの構文解析での繰り返し回数・規則の呼び出しの入れ子の深さの上限; 任意の文法を読み込む場合もスタックを溢れさせずにエラーとする
+pub const GRAMMAR_LOOP_LIMIT: usize = DEFAULT_LOOP_LIMIT;
+pub const GRAMMAR_MAX_DEPTH: usize = DEFAULT_MAX_DEPTH;
 
 pub struct BlockParser {
     cons: Rc<RefCell<Console>>,
     start_rule_id: Option<String>,
+    // note: start 命令の位置; 命令がなければ空の位置
+    start_cmd_pos: CharacterPosition,
+    // note: entry 命令で宣言された規則 ID; 記述順
+    entry_rule_ids: Vec<String>,
     file_alias_name: String,
     replaced_file_alias_names: Arc<HashMap<String, String>>,
     used_block_ids: Box<HashMap<String, CharacterPosition>>,
@@ -202,15 +245,24 @@ pub struct BlockParser {
     // note: <ブロックエイリアス名, ブロック ID>
     block_alias_map: HashMap<String, String>,
     block_id_map: Vec::<String>,
+    // note: <定数名, 展開済みの値>; ブロックごとにクリアする
+    const_map: HashMap<String, String>,
     file_path: String,
     file_content: Box<String>,
+    // note: 構成ファイルの ASTReflection が reversed であれば指定のない要素を反映しない
+    reverse_ast_reflection_style: bool,
 }
 
 impl BlockParser {
     // note: FileMap から最終的な RuleMap を取得する
     pub fn get_rule_map(cons: Rc<RefCell<Console>>, fcpeg_file_map: &mut FCPEGFileMap, enable_memoization: bool) -> ConsoleResult<Arc<Box<RuleMap>>> {
+        return BlockParser::get_rule_map_with_diagnostics(cons, fcpeg_file_map, enable_memoization, &mut Vec::new());
+    }
+
+    // spec: 文法の構文解析に失敗した場合は解析器のログを診断情報として diagnostics に追加する; ブロック・規則の検査のエラーはコンソールにのみ出力する
+    pub fn get_rule_map_with_diagnostics(cons: Rc<RefCell<Console>>, fcpeg_file_map: &mut FCPEGFileMap, enable_memoization: bool, diagnostics: &mut Vec<Diagnostic>) -> ConsoleResult<Arc<Box<RuleMap>>> {
         let block_map = FCPEGBlock::get_block_map();
-        let rule_map = Arc::new(Box::new(RuleMap::new(vec![block_map], ".Syntax.FCPEG".to_string())?));
+        let rule_map = Arc::new(Box::new(RuleMap::new(&cons, vec![block_map], ".Syntax.FCPEG".to_string())?));
         let mut block_maps = Vec::<BlockMap>::new();
 
         let mut used_block_ids = Box::new(HashMap::<String, CharacterPosition>::new());
@@ -218,11 +270,15 @@ impl BlockParser {
         let mut block_id_map = Vec::<String>::new();
 
         let mut start_rule_id = Option::<String>::None;
+        let mut start_cmd_pos = CharacterPosition::get_empty();
+        let mut entry_rule_ids = Vec::<String>::new();
 
         for (file_alias_name, fcpeg_file) in fcpeg_file_map.iter() {
             let mut block_parser = BlockParser {
                 cons: cons.clone(),
                 start_rule_id: None,
+                start_cmd_pos: CharacterPosition::get_empty(),
+                entry_rule_ids: Vec::new(),
                 file_alias_name: file_alias_name.clone(),
                 replaced_file_alias_names: fcpeg_file_map.replaced_file_alias_names.clone(),
                 used_block_ids: used_block_ids,
@@ -230,15 +286,19 @@ impl BlockParser {
                 block_name: String::new(),
                 block_alias_map: HashMap::new(),
                 block_id_map: block_id_map,
+                const_map: HashMap::new(),
                 file_path: fcpeg_file.file_path.clone(),
                 file_content: fcpeg_file.file_content.clone(),
+                reverse_ast_reflection_style: fcpeg_file.config.reverse_ast_reflection_style,
             };
 
-            let tree = Box::new(block_parser.to_syntax_tree(rule_map.clone(), enable_memoization)?);
+            let tree = Box::new(block_parser.to_syntax_tree(rule_map.clone(), enable_memoization, diagnostics)?);
             block_maps.push(block_parser.to_block_map(tree)?);
 
             if block_parser.file_alias_name == "" {
                 start_rule_id = block_parser.start_rule_id.clone();
+                start_cmd_pos = block_parser.start_cmd_pos.clone();
+                entry_rule_ids = block_parser.entry_rule_ids.clone();
             }
 
             used_block_ids = block_parser.used_block_ids;
@@ -251,7 +311,10 @@ impl BlockParser {
             None => DEFAULT_START_RULE_ID.to_string(),
         };
 
-        let rule_map = Arc::new(Box::new(RuleMap::new(block_maps, start_rule_id_str)?));
+        let mut rule_map = RuleMap::new(&cons, block_maps, start_rule_id_str)?;
+        rule_map.start_cmd_pos = start_cmd_pos;
+        rule_map.entry_rule_ids = entry_rule_ids;
+        let rule_map = Arc::new(Box::new(rule_map));
 
         let mut has_id_error = false;
 
@@ -284,21 +347,26 @@ impl BlockParser {
         };
     }
 
-    fn to_syntax_tree(&mut self, rule_map: Arc<Box<RuleMap>>, enable_memoization: bool) -> ConsoleResult<SyntaxTree> {
-        let tree = SyntaxParser::parse(self.cons.clone(), rule_map, self.file_path.clone(), self.file_content.clone(), enable_memoization)?;
-        return Ok(tree);
+    fn to_syntax_tree(&mut self, rule_map: Arc<Box<RuleMap>>, enable_memoization: bool, diagnostics: &mut Vec<Diagnostic>) -> ConsoleResult<SyntaxTree> {
+        let config = ParseConfig::default().with_memoization(enable_memoization).with_loop_limit(GRAMMAR_LOOP_LIMIT).with_max_depth(GRAMMAR_MAX_DEPTH);
+        let mut parser = SyntaxParser::new(self.cons.clone(), rule_map, self.file_path.clone(), self.file_content.clone(), enable_memoization).with_config(&config);
+
+        return match parser.run() {
+            Ok(tree) => Ok(tree),
+            Err(()) => {
+                diagnostics.append(&mut parser.drain_diagnostics());
+                Err(())
+            },
+        };
     }
 
     // note: FCPEG コードの構文木 → ブロックマップの変換
     fn to_block_map(&mut self, tree: Box<SyntaxTree>) -> ConsoleResult<BlockMap> {
         let mut block_map = BlockMap::new();
         let root = tree.get_child_ref();
-        let block_nodes = match root.get_node(&self.cons)?.get_node_child_at(&self.cons, 0) {
+        let block_nodes = match root.get_node(&self.cons)?.try_get_node_child_at(0) {
             Ok(v) => v.get_reflectable_children(),
-            Err(()) => {
-                self.cons.borrow_mut().pop_log();
-                return Ok(block_map)
-            },
+            Err(_) => return Ok(block_map),
         };
 
         for each_block_elem in &block_nodes {
@@ -324,41 +392,67 @@ impl BlockParser {
             }
 
             let mut cmds = Vec::<BlockCommand>::new();
-            let mut rule_names = Vec::<String>::new();
+            // note: <規則名, 定義位置>
+            let mut rule_names = Vec::<(String, CharacterPosition)>::new();
+            // note: 規則定義の直前に連続するコメント命令を規則の説明とする
+            let mut doc_lines = Vec::<String>::new();
 
-            match each_block_node.get_node_child_at(&self.cons, 1) {
+            match each_block_node.try_get_node_child_at(1) {
                 Ok(cmd_elems) => {
+                    // note: 定数は定義位置に関わらず参照できるよう規則より先に解決する
+                    self.const_map = self.to_const_map(cmd_elems)?;
+
                     for each_cmd_elem in &cmd_elems.get_reflectable_children() {
                         let each_cmd_node = each_cmd_elem.get_node(&self.cons)?.get_node_child_at(&self.cons, 0)?;
-                        let new_cmd = self.to_block_cmd(each_cmd_node)?;
+                        let mut new_cmd = self.to_block_cmd(each_cmd_node)?;
 
-                        // ルール名の重複チェック
-                        match &new_cmd {
+                        // ルール名の重複チェックと説明の付与
+                        match &mut new_cmd {
+                            BlockCommand::Comment { pos: _, value } => {
+                                doc_lines.push(value.replace(",,", ",").trim().to_string());
+                            },
                             BlockCommand::Define { pos: _, rule } => {
-                                if rule_names.contains(&rule.name) {
-                                    self.cons.borrow_mut().append_log(BlockParsingLog::DuplicateRuleName {
-                                        pos: rule.pos.clone(),
-                                        rule_name: rule.name.clone(),
-                                    }.get_log());
+                                // note: override は他のブロックの定義を置き換えるためのものであり、同じブロック内の重複は許さない
+                                // note: 別名も規則名と同じ名前空間に属する
+                                let mut new_names = vec![(rule.name.clone(), rule.pos.clone())];
+                                new_names.extend(rule.aliases.iter().map(|each_alias| (each_alias.name.clone(), each_alias.pos.clone())));
+
+                                for (each_new_name, each_new_pos) in new_names {
+                                    match rule_names.iter().find(|(each_rule_name, _)| *each_rule_name == each_new_name) {
+                                        Some((_, original_pos)) => {
+                                            self.cons.borrow_mut().append_log(BlockParsingLog::DuplicateRuleName {
+                                                pos: each_new_pos,
+                                                original_pos: original_pos.clone(),
+                                                rule_name: each_new_name,
+                                            }.get_log());
 
-                                    return Err(());
+                                            return Err(());
+                                        },
+                                        None => (),
+                                    }
+
+                                    rule_names.push((each_new_name, each_new_pos));
                                 }
 
-                                rule_names.push(rule.name.clone())
+                                if doc_lines.len() != 0 {
+                                    rule.doc = Some(doc_lines.join("\n"));
+                                    doc_lines.clear();
+                                }
                             },
-                            _ => (),
+                            _ => doc_lines.clear(),
                         }
 
                         cmds.push(new_cmd);
                     }
                 },
-                Err(()) => self.cons.borrow_mut().pop_log(),
+                Err(_) => (),
             }
 
             self.block_id_map.push(BlockParser::to_block_id_from_elements(&self.replaced_file_alias_names, &self.file_alias_name, &self.block_name));
             block_map.insert(self.block_name.clone(), Box::new(Block::new(self.block_name.clone(), cmds)));
             // note: ファイルを抜けるためクリア
             self.block_alias_map.clear();
+            self.const_map.clear();
         }
 
         block_map.insert(String::new(), Box::new(Block::new("Main".to_string(), Vec::new())));
@@ -366,10 +460,12 @@ impl BlockParser {
     }
 
     fn to_block_cmd(&mut self, cmd_node: &SyntaxNode) -> ConsoleResult<BlockCommand> {
-        return match &cmd_node.ast_reflection_style {
-            ASTReflectionStyle::Reflection(node_name) => match node_name.as_str() {
+        return match (&cmd_node.ast_reflection_style.visibility, cmd_node.ast_reflection_style.get_name()) {
+            (ASTVisibility::Reflect, node_name) => match node_name {
                 ".Block.CommentCmd" => self.to_comment_cmd(cmd_node),
+                ".Block.ConstCmd" => self.to_const_cmd(cmd_node),
                 ".Block.DefineCmd" => self.to_define_cmd(cmd_node),
+                ".Block.TestCmd" => self.to_test_cmd(cmd_node),
                 ".Block.StartCmd" => {
                     let start_cmd = self.to_start_cmd(cmd_node)?;
 
@@ -399,6 +495,7 @@ impl BlockParser {
                                 }
 
                                 self.start_rule_id = Some(rule_id);
+                                self.start_cmd_pos = pos;
                             }
                         }
                         _ => (),
@@ -406,6 +503,44 @@ impl BlockParser {
 
                     Ok(start_cmd)
                 },
+                ".Block.EntryCmd" => {
+                    let entry_cmd = self.to_entry_cmd(cmd_node)?;
+
+                    match &entry_cmd {
+                        BlockCommand::Entry { pos, file_alias_name, block_name, rule_name } => {
+                            if self.block_name != "Main" {
+                                self.cons.borrow_mut().append_log(BlockParsingLog::EntryCommandOutsideMainBlock {
+                                    pos: pos.clone(),
+                                }.get_log());
+
+                                return Err(());
+                            }
+
+                            // note: 開始規則と同様にメインファイルの宣言のみを用いる
+                            if self.file_alias_name == "" {
+                                let rule_id = BlockParser::to_rule_id_from_elements(&self.replaced_file_alias_names, file_alias_name, block_name, rule_name);
+
+                                if self.entry_rule_ids.contains(&rule_id) {
+                                    self.cons.borrow_mut().append_log(BlockParsingLog::DuplicateEntryCommand {
+                                        pos: pos.clone(),
+                                        rule_id: rule_id,
+                                    }.get_log());
+
+                                    return Err(());
+                                }
+
+                                if !self.used_rule_ids.contains_key(&rule_id) {
+                                    self.used_rule_ids.insert(rule_id.clone(), pos.clone());
+                                }
+
+                                self.entry_rule_ids.push(rule_id);
+                            }
+                        },
+                        _ => (),
+                    }
+
+                    Ok(entry_cmd)
+                },
                 ".Block.UseCmd" => {
                     let use_cmd = self.to_use_cmd(cmd_node)?;
 
@@ -483,8 +618,137 @@ impl BlockParser {
         return Ok(BlockCommand::Comment { pos: cmd_node.get_position(&self.cons)?, value: cmd_node.join_child_leaf_values() });
     }
 
+    fn to_const_cmd(&mut self, cmd_node: &SyntaxNode) -> ConsoleResult<BlockCommand> {
+        let const_name_node = cmd_node.get_node_child_at(&self.cons, 0)?;
+        let const_name = const_name_node.join_child_leaf_values();
+        let const_id = BlockParser::to_rule_id_from_elements(&self.replaced_file_alias_names, &self.file_alias_name, &self.block_name, &const_name);
+
+        // note: to_const_map() で解決済み
+        let value = match self.const_map.get(&const_name) {
+            Some(v) => v.clone(),
+            None => String::new(),
+        };
+
+        return Ok(BlockCommand::Const { pos: const_name_node.get_position(&self.cons)?, id: const_id, value: value });
+    }
+
+    // note: ブロック内のすべての定数を展開する
+    fn to_const_map(&mut self, cmd_elems: &SyntaxNode) -> ConsoleResult<HashMap<String, String>> {
+        // note: <定数名, (定義位置, 展開前の値)>
+        let mut raw_const_map = HashMap::<String, (CharacterPosition, Vec<RawConstValueElement>)>::new();
+        let mut const_names = Vec::<String>::new();
+
+        for each_cmd_elem in &cmd_elems.get_reflectable_children() {
+            let each_cmd_node = each_cmd_elem.get_node(&self.cons)?.get_node_child_at(&self.cons, 0)?;
+
+            if each_cmd_node.ast_reflection_style != ASTReflectionStyle::reflection(".Block.ConstCmd".to_string()) {
+                continue;
+            }
+
+            let const_name_node = each_cmd_node.get_node_child_at(&self.cons, 0)?;
+            let const_pos = const_name_node.get_position(&self.cons)?;
+            let const_name = const_name_node.join_child_leaf_values();
+
+            if raw_const_map.contains_key(&const_name) {
+                self.cons.borrow_mut().append_log(BlockParsingLog::DuplicateConstantName {
+                    pos: const_pos,
+                    const_name: const_name,
+                }.get_log());
+
+                return Err(());
+            }
+
+            let mut raw_value = Vec::<RawConstValueElement>::new();
+
+            for each_value_node in each_cmd_node.find_child_nodes(vec![".Block.ConstValue"]) {
+                let value_elem_node = each_value_node.get_node_child_at(&self.cons, 0)?;
+
+                let new_elem = match &value_elem_node.ast_reflection_style {
+                    style if style.is_reflected_as(".Rule.ConstID") => RawConstValueElement::ConstID {
+                        pos: value_elem_node.get_position(&self.cons)?,
+                        name: value_elem_node.join_child_leaf_values(),
+                    },
+                    _ => RawConstValueElement::String(self.to_string_value(value_elem_node)?),
+                };
+
+                raw_value.push(new_elem);
+            }
+
+            const_names.push(const_name.clone());
+            raw_const_map.insert(const_name, (const_pos, raw_value));
+        }
+
+        let mut const_map = HashMap::<String, String>::new();
+
+        for each_const_name in &const_names {
+            self.resolve_const_value(each_const_name, &raw_const_map, &mut const_map, &mut Vec::new())?;
+        }
+
+        return Ok(const_map);
+    }
+
+    // ret: 展開済みの定数値
+    fn resolve_const_value(&mut self, const_name: &String, raw_const_map: &HashMap<String, (CharacterPosition, Vec<RawConstValueElement>)>, const_map: &mut HashMap<String, String>, const_name_stack: &mut Vec<String>) -> ConsoleResult<String> {
+        match const_map.get(const_name) {
+            Some(v) => return Ok(v.clone()),
+            None => (),
+        }
+
+        let (const_pos, raw_value) = raw_const_map.get(const_name).unwrap();
+
+        if const_name_stack.contains(const_name) {
+            let mut cycle_const_names = const_name_stack.clone();
+            cycle_const_names.push(const_name.clone());
+
+            self.cons.borrow_mut().append_log(BlockParsingLog::CyclicConstantReference {
+                pos: const_pos.clone(),
+                const_names: cycle_const_names,
+            }.get_log());
+
+            return Err(());
+        }
+
+        const_name_stack.push(const_name.clone());
+        let mut value = String::new();
+
+        for each_elem in raw_value {
+            match each_elem {
+                RawConstValueElement::ConstID { pos, name } => {
+                    if !raw_const_map.contains_key(name) {
+                        self.cons.borrow_mut().append_log(BlockParsingLog::UnknownConstantName {
+                            pos: pos.clone(),
+                            const_name: name.clone(),
+                        }.get_log());
+
+                        return Err(());
+                    }
+
+                    value += &self.resolve_const_value(name, raw_const_map, const_map, const_name_stack)?;
+                },
+                RawConstValueElement::String(s) => value += s,
+            }
+        }
+
+        const_name_stack.pop();
+        const_map.insert(const_name.clone(), value.clone());
+
+        return Ok(value);
+    }
+
     fn to_define_cmd(&mut self, cmd_node: &SyntaxNode) -> ConsoleResult<BlockCommand> {
-        let rule_name_node = cmd_node.get_node_child_at(&self.cons, 0)?;
+        let rule_name_node = match cmd_node.find_first_child_node(vec![".Misc.SingleID"]) {
+            Some(v) => v,
+            None => {
+                self.cons.borrow_mut().append_log(BlockParsingLog::UnexpectedChildName {
+                    parent_uuid: cmd_node.uuid.clone(),
+                    unexpected: "unknown".to_string(),
+                    expected: "rule name node".to_string(),
+                }.get_log());
+
+                return Err(());
+            },
+        };
+
         let rule_pos = rule_name_node.get_position(&self.cons)?;
         let rule_name = rule_name_node.join_child_leaf_values();
 
@@ -519,17 +783,85 @@ impl BlockParser {
         };
 
         let rule_id = BlockParser::to_rule_id_from_elements(&self.replaced_file_alias_names, &self.file_alias_name, &self.block_name, &rule_name);
-        let rule = Rule::new(rule_pos.clone(), rule_id, rule_name, generics_args, template_args, new_choice);
+        let mut rule = Rule::new(rule_pos.clone(), rule_id, rule_name, generics_args, template_args, new_choice);
+        rule.memoization = BlockParser::to_define_cmd_memoization(cmd_node);
+        rule.is_override = cmd_node.exists_child_node(vec![".Block.DefineCmdOverride"]);
+        rule.deprecation = self.to_define_cmd_deprecation(cmd_node)?;
+
+        for each_alias_node in cmd_node.find_child_nodes(vec![".Block.DefineCmdAlias"]) {
+            rule.aliases.push(self.to_define_cmd_alias(each_alias_node)?);
+        }
+
         return Ok(BlockCommand::Define { pos: rule_pos, rule: rule });
     }
 
+    // ret: 直下の %memo 指定であれば Some(true), %nomemo 指定であれば Some(false); 指定がなければ None
+    fn to_define_cmd_memoization(parent_node: &SyntaxNode) -> Option<bool> {
+        let memo_node = parent_node.find_first_child_node(vec![".Block.DefineCmdMemo"])?;
+
+        return match memo_node.find_first_child_node(vec![".Block.DefineCmdMemoKind"]) {
+            Some(kind_node) => Some(kind_node.join_child_leaf_values() == "%memo"),
+            None => None,
+        };
+    }
+
+    // ret: 直下の deprecated 指定の説明; 指定がなければ None
+    fn to_define_cmd_deprecation(&mut self, parent_node: &SyntaxNode) -> ConsoleResult<Option<String>> {
+        let deprecated_node = match parent_node.find_first_child_node(vec![".Block.DefineCmdDeprecated"]) {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+
+        return match deprecated_node.find_first_child_node(vec![".Rule.Str"]) {
+            Some(str_node) => Ok(Some(self.to_string_value(str_node)?)),
+            None => {
+                self.cons.borrow_mut().append_log(BlockParsingLog::UnexpectedChildName {
+                    parent_uuid: deprecated_node.uuid.clone(),
+                    unexpected: "unknown".to_string(),
+                    expected: "string node".to_string(),
+                }.get_log());
+
+                Err(())
+            },
+        };
+    }
+
+    fn to_define_cmd_alias(&mut self, alias_node: &SyntaxNode) -> ConsoleResult<RuleAlias> {
+        let alias_name_node = match alias_node.find_first_child_node(vec![".Misc.SingleID"]) {
+            Some(v) => v,
+            None => {
+                self.cons.borrow_mut().append_log(BlockParsingLog::UnexpectedChildName {
+                    parent_uuid: alias_node.uuid.clone(),
+                    unexpected: "unknown".to_string(),
+                    expected: "alias name node".to_string(),
+                }.get_log());
+
+                return Err(());
+            },
+        };
+
+        let alias_pos = alias_name_node.get_position(&self.cons)?;
+        let alias_name = alias_name_node.join_child_leaf_values();
+
+        if !BlockParser::is_pascal_case(&alias_name) {
+            self.cons.borrow_mut().append_log(BlockParsingLog::NamingRuleViolation {
+                pos: alias_pos.clone(),
+                id: alias_name.clone(),
+            }.get_log());
+        }
+
+        let alias_id = BlockParser::to_rule_id_from_elements(&self.replaced_file_alias_names, &self.file_alias_name, &self.block_name, &alias_name);
+        let deprecation = self.to_define_cmd_deprecation(alias_node)?;
+        return Ok(RuleAlias::new(alias_pos, alias_id, alias_name, deprecation));
+    }
+
     fn to_define_cmd_arg_ids(&mut self, cmd_node: &SyntaxNode) -> ConsoleResult<Vec<String>> {
         let mut args = Vec::<String>::new();
 
         for each_elem in &cmd_node.sub_elems {
             match each_elem {
                 SyntaxNodeElement::Node(each_node) => {
-                    if each_node.ast_reflection_style == ASTReflectionStyle::Reflection(".Rule.ArgID".to_string()) {
+                    if each_node.ast_reflection_style == ASTReflectionStyle::reflection(".Rule.ArgID".to_string()) {
                         let new_arg = each_node.join_child_leaf_values();
 
                         if args.contains(&new_arg) {
@@ -550,36 +882,81 @@ impl BlockParser {
     }
 
     fn to_start_cmd(&mut self, cmd_node: &SyntaxNode) -> ConsoleResult<BlockCommand> {
+        let (file_alias_name, block_name, rule_name) = self.to_cmd_rule_id_elements(cmd_node)?;
+
+        // note: ブロック ID がデフォルトと同じであれば警告
+        if DEFAULT_START_RULE_ID == BlockParser::to_rule_id_from_elements(&self.replaced_file_alias_names, &file_alias_name, &block_name, &rule_name) {
+            self.cons.borrow_mut().append_log(BlockParsingLog::UnnecessaryStartCommand {
+                pos: cmd_node.get_position(&self.cons)?,
+                msg: format!("rule '{}' is the same as the default", DEFAULT_START_RULE_ID),
+            }.get_log());
+        }
+
+        let cmd = BlockCommand::Start {
+            pos: cmd_node.get_position(&self.cons)?,
+            file_alias_name: file_alias_name,
+            block_name: block_name,
+            rule_name: rule_name,
+        };
+
+        return Ok(cmd);
+    }
+
+    fn to_entry_cmd(&mut self, cmd_node: &SyntaxNode) -> ConsoleResult<BlockCommand> {
+        let (file_alias_name, block_name, rule_name) = self.to_cmd_rule_id_elements(cmd_node)?;
+
+        let cmd = BlockCommand::Entry {
+            pos: cmd_node.get_position(&self.cons)?,
+            file_alias_name: file_alias_name,
+            block_name: block_name,
+            rule_name: rule_name,
+        };
+
+        return Ok(cmd);
+    }
+
+    // ret: 命令の引数 (ブロック名.規則名 もしくは ファイルエイリアス名.ブロック名.規則名) の (ファイルエイリアス名, ブロック名, 規則名)
+    fn to_cmd_rule_id_elements(&mut self, cmd_node: &SyntaxNode) -> ConsoleResult<(String, String, String)> {
         let raw_id_node = cmd_node.get_node_child_at(&self.cons, 0)?;
         let raw_id = self.to_chain_id(raw_id_node)?;
         let divided_raw_id = raw_id.split(".").collect::<Vec<&str>>();
 
-        let (file_alias_name, block_name, rule_name) = match divided_raw_id.len() {
-            2 => (String::new(), divided_raw_id.get(0).unwrap().to_string(), divided_raw_id.get(1).unwrap().to_string()),
-            3 => (divided_raw_id.get(0).unwrap().to_string(), divided_raw_id.get(1).unwrap().to_string(), divided_raw_id.get(2).unwrap().to_string()),
+        return match divided_raw_id.len() {
+            2 => Ok((String::new(), divided_raw_id.get(0).unwrap().to_string(), divided_raw_id.get(1).unwrap().to_string())),
+            3 => Ok((divided_raw_id.get(0).unwrap().to_string(), divided_raw_id.get(1).unwrap().to_string(), divided_raw_id.get(2).unwrap().to_string())),
             _ => {
                 self.cons.borrow_mut().append_log(BlockParsingLog::InvalidID {
                     pos: raw_id_node.get_position(&self.cons)?,
                     id: raw_id,
                 }.get_log());
 
-                return Err(());
+                Err(())
             },
         };
+    }
 
-        // note: ブロック ID がデフォルトと同じであれば警告
-        if DEFAULT_START_RULE_ID == BlockParser::to_rule_id_from_elements(&self.replaced_file_alias_names, &file_alias_name, &block_name, &rule_name) {
-            self.cons.borrow_mut().append_log(BlockParsingLog::UnnecessaryStartCommand {
-                pos: cmd_node.get_position(&self.cons)?,
-                msg: format!("rule '{}' is the same as the default", DEFAULT_START_RULE_ID),
-            }.get_log());
+    // spec: 規則 ID は式中の参照と同様に解決し、未定義であれば UnknownRuleID とする
+    fn to_test_cmd(&mut self, cmd_node: &SyntaxNode) -> ConsoleResult<BlockCommand> {
+        let pos = cmd_node.get_position(&self.cons)?;
+        let chain_id_node = cmd_node.get_node_child_at(&self.cons, 0)?;
+        let rule_id = BlockParser::to_rule_id(&self.cons, &pos, &BlockParser::to_string_vec(&self.cons, chain_id_node)?, &self.block_alias_map, &self.file_alias_name, &self.block_name, &self.replaced_file_alias_names)?;
+
+        if !self.used_rule_ids.contains_key(&rule_id) {
+            self.used_rule_ids.insert(rule_id.clone(), pos.clone());
         }
 
-        let cmd = BlockCommand::Start {
-            pos: cmd_node.get_position(&self.cons)?,
-            file_alias_name: file_alias_name,
-            block_name: block_name,
-            rule_name: rule_name,
+        let expectation = match cmd_node.get_node_child_at(&self.cons, 1)?.join_child_leaf_values().as_str() {
+            "accepts" => EmbeddedTestExpectation::Accept,
+            _ => EmbeddedTestExpectation::Reject,
+        };
+
+        let input = self.to_string_value(cmd_node.get_node_child_at(&self.cons, 2)?)?;
+
+        let cmd = BlockCommand::Test {
+            pos: pos,
+            rule_id: rule_id,
+            expectation: expectation,
+            input: input,
         };
 
         return Ok(cmd);
@@ -782,6 +1159,15 @@ impl BlockParser {
                 None => RuleElementLoopRange::get_single_loop(),
             };
 
+            // note: Separator ノード; 繰り返し範囲は区切りの種別で決まる
+            let (loop_range, separator) = match each_seq_elem_node.find_first_child_node(vec![".Rule.Separator"]) {
+                Some(separator_node) => {
+                    let raw_separator = self.to_raw_separator(separator_node, generics_args)?;
+                    (raw_separator.loop_range.clone(), Some(raw_separator))
+                },
+                None => (loop_range, None),
+            };
+
             // note: RandomOrder ノード
             let (elem_order, random_order_node_pos) = match each_seq_elem_node.find_first_child_node(vec![".Rule.RandomOrder"]) {
                 Some(random_order_node) => {
@@ -881,25 +1267,56 @@ impl BlockParser {
                 None => (RuleElementOrder::Sequential, CharacterPosition::get_empty()),
             };
 
+            // note: LongestMatch ノード
+            let longest_match_pos = match each_seq_elem_node.find_first_child_node(vec![".Rule.LongestMatch"]) {
+                Some(longest_match_node) => Some(longest_match_node.get_position(&self.cons)?),
+                None => None,
+            };
+
+            // note: PresenceMarker ノード
+            let presence_marker_pos = match each_seq_elem_node.find_first_child_node(vec![".Rule.PresenceMarker"]) {
+                Some(presence_marker_node) => Some(presence_marker_node.get_position(&self.cons)?),
+                None => None,
+            };
+
             // note: ASTReflectionStyle ノード
-            // todo: 構成ファイルによって切り替える
-            let ast_reflection_style = match each_seq_elem_node.find_first_child_node(vec![".Rule.ASTReflectionStyle"]) {
+            // spec: 構成ファイルによる反転は指定のない要素にのみ適用する
+            let (ast_reflection_style, is_ast_reflection_specified) = match each_seq_elem_node.find_first_child_node(vec![".Rule.ASTReflectionStyle"]) {
                 Some(style_node) => {
-                    match style_node.get_leaf_child_at(&self.cons, 0) {
+                    let ast_reflection_style = match style_node.try_get_leaf_child_at(0) {
                         Ok(leaf) => {
                             if leaf.value == "##" {
-                                ASTReflectionStyle::Expansion
+                                // note: ##Name の場合は展開された子要素の反映名を置き換える
+                                let elem_name = style_node.join_child_leaf_values()[2..].to_string();
+
+                                if elem_name == "" {
+                                    ASTReflectionStyle::expansion()
+                                } else {
+                                    ASTReflectionStyle::new(ASTVisibility::Expand, Some(elem_name))
+                                }
                             } else {
-                                ASTReflectionStyle::Reflection(style_node.join_child_leaf_values())
+                                let elem_name = style_node.join_child_leaf_values();
+
+                                // spec: #_ は反映名のない反映方式を明示する; 規則 ID 表現字句でも規則名で命名しない
+                                if elem_name == ANONYMOUS_REFLECTION_NAME {
+                                    ASTReflectionStyle::reflection(String::new())
+                                } else {
+                                    ASTReflectionStyle::reflection(elem_name)
+                                }
                             }
                         },
-                        Err(()) => {
-                            self.cons.borrow_mut().pop_log();
-                            ASTReflectionStyle::from_config(false, true, String::new())
-                        },
-                    }
+                        Err(_) => ASTReflectionStyle::no_reflection(),
+                    };
+
+                    (ast_reflection_style, true)
                 },
-                None => ASTReflectionStyle::from_config(false, false, String::new()),
+                None => (ASTReflectionStyle::get_default(self.reverse_ast_reflection_style), false),
+            };
+
+            // note: Capture ノード
+            let capture_name = match each_seq_elem_node.find_first_child_node(vec![".Rule.Capture"]) {
+                Some(capture_node) => Some(capture_node.join_child_leaf_values()),
+                None => None,
             };
 
             // note: Choice または Expr ノード
@@ -916,15 +1333,71 @@ impl BlockParser {
                 },
             };
 
-            match &choice_or_expr_node.ast_reflection_style {
-                ASTReflectionStyle::Reflection(name) => {
-                    let new_elem = match name.as_str() {
+            match (&choice_or_expr_node.ast_reflection_style.visibility, choice_or_expr_node.ast_reflection_style.get_name()) {
+                (ASTVisibility::Reflect, name) => {
+                    let new_elem = match name {
                         ".Rule.Choice" => {
                             let mut new_choice = Box::new(self.to_rule_choice_elem(choice_or_expr_node.get_node_child_at(&self.cons, 0)?, generics_args)?);
                             new_choice.ast_reflection_style = ast_reflection_style;
+                            new_choice.is_ast_reflection_specified = is_ast_reflection_specified;
                             new_choice.lookahead_kind = lookahead_kind;
                             new_choice.loop_range = loop_range;
                             new_choice.elem_order = elem_order;
+                            new_choice.capture_name = capture_name;
+
+                            match separator {
+                                Some(raw_separator) => {
+                                    if new_choice.elem_order.is_random() {
+                                        self.cons.borrow_mut().append_log(BlockParsingLog::InvalidSeparator {
+                                            pos: raw_separator.pos,
+                                            msg: "cannot specify separator to random order group".to_string(),
+                                        }.get_log());
+
+                                        return Err(());
+                                    }
+
+                                    new_choice.separator = Some(raw_separator.elem);
+                                    new_choice.allows_trailing_separator = raw_separator.allows_trailing_separator;
+                                },
+                                None => (),
+                            }
+
+                            // note: 最長一致は括弧の中の選択グループに指定する
+                            match longest_match_pos {
+                                Some(pos) => {
+                                    if new_choice.elem_order.is_random() {
+                                        self.cons.borrow_mut().append_log(BlockParsingLog::InvalidLongestMatch {
+                                            pos: pos,
+                                            msg: "cannot specify longest match to random order group".to_string(),
+                                        }.get_log());
+
+                                        return Err(());
+                                    }
+
+                                    match new_choice.sub_elems.get_mut(0) {
+                                        Some(RuleElement::Group(choice)) => choice.choice_mode = RuleChoiceMode::LongestMatch,
+                                        _ => (),
+                                    }
+                                },
+                                None => (),
+                            }
+
+                            match presence_marker_pos {
+                                Some(pos) => {
+                                    if new_choice.ast_reflection_style.is_expandable() {
+                                        self.cons.borrow_mut().append_log(BlockParsingLog::InvalidPresenceMarker {
+                                            pos: pos,
+                                            msg: "cannot specify presence marker to expandable group".to_string(),
+                                        }.get_log());
+
+                                        return Err(());
+                                    }
+
+                                    new_choice.has_presence_marker = true;
+                                },
+                                None => (),
+                            }
+
                             RuleElement::Group(new_choice)
                         },
                         ".Rule.Expr" => {
@@ -936,11 +1409,54 @@ impl BlockParser {
                                 return Err(());
                             }
 
+                            match presence_marker_pos {
+                                Some(pos) => {
+                                    self.cons.borrow_mut().append_log(BlockParsingLog::InvalidPresenceMarker {
+                                        pos: pos,
+                                        msg: "cannot specify presence marker to expression; enclose it in a group".to_string(),
+                                    }.get_log());
+
+                                    return Err(());
+                                },
+                                None => (),
+                            }
+
+                            match longest_match_pos {
+                                Some(pos) => {
+                                    self.cons.borrow_mut().append_log(BlockParsingLog::InvalidLongestMatch {
+                                        pos: pos,
+                                        msg: "cannot specify longest match to expression; enclose choices in a group".to_string(),
+                                    }.get_log());
+
+                                    return Err(());
+                                },
+                                None => (),
+                            }
+
                             let mut new_expr = Box::new(self.to_rule_expr_elem(choice_or_expr_node, generics_args)?);
                             new_expr.ast_reflection_style = ast_reflection_style;
-                            new_expr.lookahead_kind = lookahead_kind;
-                            new_expr.loop_range = loop_range;
-                            RuleElement::Expression(new_expr)
+                            new_expr.is_ast_reflection_specified = is_ast_reflection_specified;
+
+                            match separator {
+                                // note: 区切りはグループでのみ扱うため、表現を展開方式のグループで囲む; 表現の反映方式は各繰り返しの子要素に適用される
+                                Some(raw_separator) => {
+                                    let mut new_group = Box::new(RuleGroup::new(RuleGroupKind::Sequence));
+                                    new_group.ast_reflection_style = ASTReflectionStyle::expansion();
+                                    new_group.lookahead_kind = lookahead_kind;
+                                    new_group.loop_range = loop_range;
+                                    new_group.capture_name = capture_name;
+                                    new_group.separator = Some(raw_separator.elem);
+                                    new_group.allows_trailing_separator = raw_separator.allows_trailing_separator;
+                                    new_group.sub_elems = vec![RuleElement::Expression(new_expr)];
+                                    RuleElement::Group(new_group)
+                                },
+                                None => {
+                                    new_expr.lookahead_kind = lookahead_kind;
+                                    new_expr.loop_range = loop_range;
+                                    new_expr.capture_name = capture_name;
+                                    RuleElement::Expression(new_expr)
+                                },
+                            }
                         },
                         _ => {
                             self.cons.borrow_mut().append_log(BlockParsingLog::UnexpectedNodeName {
@@ -1047,6 +1563,70 @@ impl BlockParser {
         return Ok(raw_range);
     }
 
+    // note: Rule.Separator ノードの解析
+    // spec: 表現の区切りは AST に反映しない; グループの区切りは展開方式とし、グループ内の要素の反映方式に従う
+    fn to_raw_separator(&mut self, separator_node: &SyntaxNode, generics_args: &Vec<String>) -> ConsoleResult<RawSeparator> {
+        let kind_node = match separator_node.find_first_child_node(vec![".Rule.SeparatorKind"]) {
+            Some(v) => v,
+            None => {
+                self.cons.borrow_mut().append_log(BlockParsingLog::UnexpectedChildName {
+                    parent_uuid: separator_node.uuid.clone(),
+                    unexpected: "unknown".to_string(),
+                    expected: "separator kind node".to_string(),
+                }.get_log());
+
+                return Err(());
+            },
+        };
+
+        let pos = kind_node.get_position(&self.cons)?;
+        let kind_leaf = kind_node.get_leaf_child_at(&self.cons, 0)?;
+
+        let loop_range = match kind_leaf.value.as_str() {
+            "**" => RuleElementLoopRange::from("*"),
+            "++" => RuleElementLoopRange::from("+"),
+            _ => {
+                self.cons.borrow_mut().append_log(BlockParsingLog::InvalidSeparator {
+                    pos: pos,
+                    msg: format!("unknown separator kind '{}'", kind_leaf.value),
+                }.get_log());
+
+                return Err(());
+            },
+        };
+
+        let elem = match separator_node.find_first_child_node(vec![".Rule.Choice", ".Rule.Expr"]) {
+            Some(choice_or_expr_node) if choice_or_expr_node.ast_reflection_style.is_reflected_as(".Rule.Choice") => {
+                let mut new_choice = Box::new(self.to_rule_choice_elem(choice_or_expr_node.get_node_child_at(&self.cons, 0)?, generics_args)?);
+                new_choice.ast_reflection_style = ASTReflectionStyle::expansion();
+                RuleElement::Group(new_choice)
+            },
+            Some(choice_or_expr_node) => {
+                let mut new_expr = Box::new(self.to_rule_expr_elem(choice_or_expr_node, generics_args)?);
+                new_expr.ast_reflection_style = ASTReflectionStyle::no_reflection();
+                RuleElement::Expression(new_expr)
+            },
+            None => {
+                self.cons.borrow_mut().append_log(BlockParsingLog::UnexpectedChildName {
+                    parent_uuid: separator_node.uuid.clone(),
+                    unexpected: "unknown".to_string(),
+                    expected: "choice or expression node".to_string(),
+                }.get_log());
+
+                return Err(());
+            },
+        };
+
+        let raw_separator = RawSeparator {
+            pos: pos,
+            loop_range: loop_range,
+            elem: elem,
+            allows_trailing_separator: separator_node.find_first_child_node(vec![".Rule.TrailingSeparator"]).is_some(),
+        };
+
+        return Ok(raw_separator);
+    }
+
     // note: Rule.PureChoice ノードの解析
     fn to_rule_choice_elem(&mut self, choice_node: &SyntaxNode, generics_args: &Vec<String>) -> ConsoleResult<RuleGroup> {
         let mut children = Vec::<RuleElement>::new();
@@ -1056,12 +1636,9 @@ impl BlockParser {
         for seq_elem in &choice_node.get_reflectable_children() {
             match &seq_elem {
                 SyntaxNodeElement::Node(node) => {
-                    match &seq_elem.get_ast_reflection_style() {
-                        ASTReflectionStyle::Reflection(name) => if name == ".Rule.Seq" {
-                            let new_child = self.to_seq_elem(node, generics_args)?;
-                            children.push(new_child);
-                        },
-                        _ => (),
+                    if seq_elem.get_ast_reflection_style().is_reflected_as(".Rule.Seq") {
+                        let new_child = self.to_seq_elem(node, generics_args)?;
+                        children.push(new_child);
                     }
                 },
                 SyntaxNodeElement::Leaf(leaf) => {
@@ -1084,11 +1661,30 @@ impl BlockParser {
 
     fn to_rule_expr_elem(&mut self, expr_node: &SyntaxNode, generics_args: &Vec<String>) -> ConsoleResult<RuleExpression> {
         let expr_child_node = expr_node.get_node_child_at(&self.cons, 0)?;
-        let (pos, kind, value) = match &expr_child_node.ast_reflection_style {
-            ASTReflectionStyle::Reflection(name) => {
-                match name.as_str() {
+        let (pos, kind, value) = match (&expr_child_node.ast_reflection_style.visibility, expr_child_node.ast_reflection_style.get_name()) {
+            (ASTVisibility::Reflect, name) => {
+                match name {
                     ".Rule.ArgID" => (expr_child_node.get_position(&self.cons)?, RuleExpressionKind::ArgId, expr_child_node.join_child_leaf_values()),
                     ".Rule.CharClass" => (expr_child_node.get_position(&self.cons)?, RuleExpressionKind::CharClass, format!("[{}]", expr_child_node.join_child_leaf_values())),
+                    // note: 定数は読み込み時に文字列表現字句へ置換する
+                    ".Rule.ConstID" => {
+                        let pos = expr_child_node.get_position(&self.cons)?;
+                        let const_name = expr_child_node.join_child_leaf_values();
+
+                        let value = match self.const_map.get(&const_name) {
+                            Some(v) => v.clone(),
+                            None => {
+                                self.cons.borrow_mut().append_log(BlockParsingLog::UnknownConstantName {
+                                    pos: pos,
+                                    const_name: const_name,
+                                }.get_log());
+
+                                return Err(());
+                            },
+                        };
+
+                        (pos, RuleExpressionKind::String, value)
+                    },
                     ".Rule.ID" => {
                         let chain_id_node = expr_child_node.get_node_child_at(&self.cons, 0)?;
                         let parent_node = chain_id_node.get_node_child_at(&self.cons, 0)?;
@@ -1132,15 +1728,21 @@ impl BlockParser {
                             self.used_rule_ids.insert(id.clone(), pos.clone());
                         }
 
-                        let id_expr_kind = RuleExpressionKind::IdWithArgs {
-                            generics_args: new_generics_args,
-                            template_args: new_template_args,
+                        // note: 引数を持つ EPSILON は置き換えず、解析時に引数の数の誤りとする
+                        let id_expr_kind = if id == EPSILON_RULE_NAME && new_generics_args.len() == 0 && new_template_args.len() == 0 {
+                            RuleExpressionKind::Epsilon
+                        } else {
+                            RuleExpressionKind::IdWithArgs {
+                                generics_args: new_generics_args,
+                                template_args: new_template_args,
+                            }
                         };
 
                         (pos, id_expr_kind, id)
                     },
                     ".Rule.Str" => (expr_child_node.get_position(&self.cons)?, RuleExpressionKind::String, self.to_string_value(expr_child_node)?),
                     ".Rule.Wildcard" => (expr_child_node.get_position(&self.cons)?, RuleExpressionKind::Wildcard, ".".to_string()),
+                    ".Rule.CaptureRef" => (expr_child_node.get_position(&self.cons)?, RuleExpressionKind::CaptureRef, expr_child_node.join_child_leaf_values()),
                     _ => {
                         self.cons.borrow_mut().append_log(BlockParsingLog::UnexpectedChildName {
                             parent_uuid: expr_child_node.uuid.clone(),
@@ -1236,42 +1838,92 @@ impl BlockParser {
         return Ok(new_id);
     }
 
+    // spec: 不正なエスケープシーケンスはすべてログに出力してから Err を返す; ログの位置は文字列リテラルの位置とする
     fn to_string_value(&mut self, str_node: &SyntaxNode) -> ConsoleResult<String> {
         let mut s = String::new();
+        let mut has_invalid_esc_seq = false;
 
         for each_elem in &str_node.sub_elems {
             match each_elem {
                 SyntaxNodeElement::Node(node) => {
-                    match node.ast_reflection_style {
-                        ASTReflectionStyle::Reflection(_) => {
-                            s += match node.get_leaf_child_at(&self.cons, 0)?.value.as_str() {
-                                "\\" => "\\",
-                                "\"" => "\"",
-                                "n" => "\n",
-                                "t" => "\t",
-                                "z" => "\0",
-                                _ => {
-                                    self.cons.borrow_mut().append_log(BlockParsingLog::UnknownEscapeSequenceCharacter {
-                                        pos: node.get_position(&self.cons)?,
+                    match node.ast_reflection_style.visibility {
+                        ASTVisibility::Reflect => {
+                            let esc_seq = node.join_child_leaf_values();
+
+                            match BlockParser::to_escaped_char(&esc_seq) {
+                                Ok(v) => s.push(v),
+                                Err(msg) => {
+                                    self.cons.borrow_mut().append_log(BlockParsingLog::InvalidEscapeSequence {
+                                        pos: str_node.get_position(&self.cons)?,
+                                        esc_seq: esc_seq,
+                                        msg: msg,
                                     }.get_log());
 
-                                    return Err(());
+                                    has_invalid_esc_seq = true;
                                 },
-                            };
+                            }
                         },
                         _ => (),
                     }
                 },
                 SyntaxNodeElement::Leaf(leaf) => {
-                    match leaf.ast_reflection_style {
-                        ASTReflectionStyle::Reflection(_) => s += leaf.value.as_ref(),
+                    match leaf.ast_reflection_style.visibility {
+                        ASTVisibility::Reflect => s += leaf.value.as_ref(),
                         _ => (),
                     }
                 },
             }
         }
 
-        return Ok(s);
+        return if has_invalid_esc_seq {
+            Err(())
+        } else {
+            Ok(s)
+        };
+    }
+
+    // ret: エスケープシーケンス (先頭の \ を除く) が表す文字; 不正であれば Err(理由)
+    pub(crate) fn to_escaped_char(esc_seq: &str) -> Result<char, String> {
+        return match esc_seq {
+            "\\" => Ok('\\'),
+            "\"" => Ok('"'),
+            "n" => Ok('\n'),
+            "r" => Ok('\r'),
+            "t" => Ok('\t'),
+            // note: 入力の末尾には EOF 用のヌル文字が付加される; 文字列モードでは \z と \0 はいずれも入力の終端とも一致し、バイト列モードでは \z のみが一致する
+            "0" => Ok('\0'),
+            "z" => Ok(EOF_ESCAPE_CHAR),
+            // note: \xNN は U+0000 ~ U+00FF の文字となる; バイト列モードでは同じ値の 1 バイトと一致する
+            _ if esc_seq.starts_with("x") => match u8::from_str_radix(&esc_seq[1..], 16) {
+                Ok(v) if esc_seq.len() == 3 => Ok(v as char),
+                _ => Err("\\x must be followed by 2 hexadecimal digits".to_string()),
+            },
+            _ if esc_seq.starts_with("u") => BlockParser::to_unicode_escaped_char(&esc_seq[1..]),
+            _ => Err(format!("unknown escape sequence character '{}'", esc_seq)),
+        };
+    }
+
+    // spec: braced_code_point は \u に続く {XXXX} の部分 (閉じ括弧がなければそれを除いたもの)
+    // note: 符号位置は UTF-16 の符号単位ではなく Unicode スカラー値として扱うため、BMP 外の文字もサロゲートペアなしで記述できる
+    fn to_unicode_escaped_char(braced_code_point: &str) -> Result<char, String> {
+        if !braced_code_point.starts_with("{") {
+            return Err("\\u must be followed by a code point in braces like \\u{1F600}".to_string());
+        }
+
+        if braced_code_point.len() < 2 || !braced_code_point.ends_with("}") {
+            return Err("unterminated unicode escape sequence; expected '}'".to_string());
+        }
+
+        let hex_digits = &braced_code_point[1..braced_code_point.len() - 1];
+
+        if hex_digits.len() == 0 || hex_digits.len() > 6 || !hex_digits.chars().all(|each_char| each_char.is_ascii_hexdigit()) {
+            return Err("unicode escape sequence must have 1 to 6 hexadecimal digits".to_string());
+        }
+
+        return match u32::from_str_radix(hex_digits, 16).ok().and_then(char::from_u32) {
+            Some(v) => Ok(v),
+            None => Err(format!("U+{} is not a unicode scalar value; code point must be at most U+10FFFF and not a surrogate", hex_digits.to_uppercase())),
+        };
     }
 
     fn to_chain_id(&mut self, chain_id_node: &SyntaxNode) -> ConsoleResult<String> {
@@ -1346,13 +1998,18 @@ impl FCPEGBlock {
     }
 
     fn get_syntax_block() -> Block {
-        // code: FCPEG <- Symbol.Space*# Symbol.LineEnd*# (Block.Block Symbol.Div*#)* "\z"#,
+        // code: FCPEG <- Symbol.Space*# Symbol.LineEnd*# ("#pragma once" Symbol.Div*)?# (Block.Block Symbol.Div*#)* "\z"#,
         let fcpeg_rule = rule!{
             ".Syntax.FCPEG",
             group!{
                 vec![],
                 expr!(Id, ".Symbol.Space", "*", "#"),
                 expr!(Id, ".Symbol.LineEnd", "*", "#"),
+                group!{
+                    vec!["?", "#"],
+                    expr!(String, "#pragma once"),
+                    expr!(Id, ".Symbol.Div", "*"),
+                },
                 group!{
                     vec!["*"],
                     expr!(Id, ".Block.Block"),
@@ -1497,27 +2154,39 @@ impl FCPEGBlock {
             },
         };
 
-        // code: Cmd <- CommentCmd : DefineCmd : StartCmd : UseCmd,
+        // code: Cmd <- TestCmd : DefineCmd : CommentCmd : StartCmd : EntryCmd : UseCmd : ConstCmd,
         let cmd_rule = rule!{
             ".Block.Cmd",
             group!{
                 vec![":"],
                 group!{
                     vec![],
-                    expr!(Id, ".Block.CommentCmd"),
+                    expr!(Id, ".Block.TestCmd"),
                 },
                 group!{
                     vec![],
                     expr!(Id, ".Block.DefineCmd"),
                 },
+                group!{
+                    vec![],
+                    expr!(Id, ".Block.CommentCmd"),
+                },
                 group!{
                     vec![],
                     expr!(Id, ".Block.StartCmd"),
                 },
+                group!{
+                    vec![],
+                    expr!(Id, ".Block.EntryCmd"),
+                },
                 group!{
                     vec![],
                     expr!(Id, ".Block.UseCmd"),
                 },
+                group!{
+                    vec![],
+                    expr!(Id, ".Block.ConstCmd"),
+                },
             },
         };
 
@@ -1546,14 +2215,18 @@ impl FCPEGBlock {
             },
         };
 
-        // code: DefineCmd <- Misc.SingleID DefineCmdGenerics? DefineCmdTemplate? Symbol.Div*# "<-"# Symbol.Div*# Rule.PureChoice Symbol.Div*# ","#,
+        // code: DefineCmd <- DefineCmdMemo? DefineCmdOverride? DefineCmdDeprecated? Misc.SingleID DefineCmdGenerics? DefineCmdTemplate? DefineCmdAlias* Symbol.Div*# "<-"# Symbol.Div*# Rule.PureChoice Symbol.Div*# ","#,
         let define_cmd_rule = rule!{
             ".Block.DefineCmd",
             group!{
                 vec![],
+                expr!(Id, ".Block.DefineCmdMemo", "?"),
+                expr!(Id, ".Block.DefineCmdOverride", "?"),
+                expr!(Id, ".Block.DefineCmdDeprecated", "?"),
                 expr!(Id, ".Misc.SingleID"),
                 expr!(Id, ".Block.DefineCmdGenerics", "?"),
                 expr!(Id, ".Block.DefineCmdTemplate", "?"),
+                expr!(Id, ".Block.DefineCmdAlias", "*"),
                 expr!(Id, ".Symbol.Div", "*", "#"),
                 expr!(String, "<-", "#"),
                 expr!(Id, ".Symbol.Div", "*", "#"),
@@ -1563,6 +2236,84 @@ impl FCPEGBlock {
             },
         };
 
+        // code: DefineCmdMemo <- DefineCmdMemoKind Symbol.Div+#,
+        let define_cmd_memo_rule = rule!{
+            ".Block.DefineCmdMemo",
+            group!{
+                vec![],
+                expr!(Id, ".Block.DefineCmdMemoKind"),
+                expr!(Id, ".Symbol.Div", "+", "#"),
+            },
+        };
+
+        // code: DefineCmdMemoKind <- "%memo" : "%nomemo",
+        let define_cmd_memo_kind_rule = rule!{
+            ".Block.DefineCmdMemoKind",
+            group!{
+                vec![],
+                group!{
+                    vec![":"],
+                    group!{
+                        vec![],
+                        expr!(String, "%memo"),
+                    },
+                    group!{
+                        vec![],
+                        expr!(String, "%nomemo"),
+                    },
+                },
+            },
+        };
+
+        // code: DefineCmdOverride <- "override" Symbol.Div+#,
+        let define_cmd_override_rule = rule!{
+            ".Block.DefineCmdOverride",
+            group!{
+                vec![],
+                expr!(String, "override"),
+                expr!(Id, ".Symbol.Div", "+", "#"),
+            },
+        };
+
+        // code: DefineCmdDeprecated <- "deprecated"# Symbol.Div*# "("# Symbol.Div*# Rule.Str Symbol.Div*# ")"# Symbol.Div*#,
+        let define_cmd_deprecated_rule = rule!{
+            ".Block.DefineCmdDeprecated",
+            group!{
+                vec![],
+                expr!(String, "deprecated", "#"),
+                expr!(Id, ".Symbol.Div", "*", "#"),
+                expr!(String, "(", "#"),
+                expr!(Id, ".Symbol.Div", "*", "#"),
+                expr!(Id, ".Rule.Str"),
+                expr!(Id, ".Symbol.Div", "*", "#"),
+                expr!(String, ")", "#"),
+                expr!(Id, ".Symbol.Div", "*", "#"),
+            },
+        };
+
+        // code: DefineCmdAlias <- Symbol.Div*# "alias"# Symbol.Div+# Misc.SingleID (Symbol.Div+# DefineCmdDeprecated)?##,
+        let define_cmd_alias_rule = rule!{
+            ".Block.DefineCmdAlias",
+            group!{
+                vec![],
+                expr!(Id, ".Symbol.Div", "*", "#"),
+                expr!(String, "alias", "#"),
+                expr!(Id, ".Symbol.Div", "+", "#"),
+                expr!(Id, ".Misc.SingleID"),
+                group!{
+                    vec!["?", "##"],
+                    group!{
+                        vec![":"],
+                        group!{
+                            vec![],
+                            expr!(Id, ".Symbol.Div", "+", "#"),
+                            expr!(Id, ".Block.DefineCmdDeprecated"),
+                        },
+                    },
+                },
+            },
+        };
+
         // code: DefineCmdGenerics <- Symbol.Div*# "<"# Symbol.Div*# Rule.ArgID (Symbol.Div*# ","# Symbol.Div*# Rule.ArgID)*## Symbol.Div*# ">"# Symbol.Div*#,
         let define_cmd_generics_rule = rule!{
             ".Block.DefineCmdGenerics",
@@ -1622,6 +2373,21 @@ impl FCPEGBlock {
             },
         };
 
+        // code: EntryCmd <- "+"# Symbol.Div*# "entry"# Symbol.Div+# Misc.ChainID Symbol.Div*# ","#,
+        let entry_cmd_rule = rule!{
+            ".Block.EntryCmd",
+            group!{
+                vec![],
+                expr!(String, "+", "#"),
+                expr!(Id, ".Symbol.Div", "*", "#"),
+                expr!(String, "entry", "#"),
+                expr!(Id, ".Symbol.Div", "+", "#"),
+                expr!(Id, ".Misc.ChainID"),
+                expr!(Id, ".Symbol.Div", "*", "#"),
+                expr!(String, ",", "#"),
+            },
+        };
+
         // code: UseCmd <- "+"# Symbol.Div*# "use"# Symbol.Div+# Misc.ChainID UseCmdBlockAlias? Symbol.Div*# ","#,
         let use_cmd_rule = rule!{
             ".Block.UseCmd",
@@ -1650,7 +2416,86 @@ impl FCPEGBlock {
             },
         };
 
-        return block!(".Block", vec![block_rule, cmd_rule, comment_rule, define_cmd_rule, define_cmd_generics_rule, define_cmd_template_rule, start_cmd_rule, use_cmd_rule, use_cmd_block_alias_rule]);
+        // code: ConstCmd <- "+"# Symbol.Div*# "const"# Symbol.Div+# Misc.SingleID Symbol.Div*# "="# Symbol.Div*# ConstValue (Symbol.Div+# ConstValue)*## Symbol.Div*# ","#,
+        let const_cmd_rule = rule!{
+            ".Block.ConstCmd",
+            group!{
+                vec![],
+                expr!(String, "+", "#"),
+                expr!(Id, ".Symbol.Div", "*", "#"),
+                expr!(String, "const", "#"),
+                expr!(Id, ".Symbol.Div", "+", "#"),
+                expr!(Id, ".Misc.SingleID"),
+                expr!(Id, ".Symbol.Div", "*", "#"),
+                expr!(String, "=", "#"),
+                expr!(Id, ".Symbol.Div", "*", "#"),
+                expr!(Id, ".Block.ConstValue"),
+                group!{
+                    vec!["*", "##"],
+                    expr!(Id, ".Symbol.Div", "+", "#"),
+                    expr!(Id, ".Block.ConstValue"),
+                },
+                expr!(Id, ".Symbol.Div", "*", "#"),
+                expr!(String, ",", "#"),
+            },
+        };
+
+        // code: ConstValue <- Rule.Str : Rule.ConstID,
+        let const_value_rule = rule!{
+            ".Block.ConstValue",
+            group!{
+                vec![],
+                group!{
+                    vec![":"],
+                    group!{
+                        vec![],
+                        expr!(Id, ".Rule.Str"),
+                    },
+                    group!{
+                        vec![],
+                        expr!(Id, ".Rule.ConstID"),
+                    },
+                },
+            },
+        };
+
+        // code: TestCmd <- "%test"# Symbol.Div+# Misc.ChainID Symbol.Div+# TestCmdExpectation Symbol.Div+# Rule.Str Symbol.Div*# ","#,
+        let test_cmd_rule = rule!{
+            ".Block.TestCmd",
+            group!{
+                vec![],
+                expr!(String, "%test", "#"),
+                expr!(Id, ".Symbol.Div", "+", "#"),
+                expr!(Id, ".Misc.ChainID"),
+                expr!(Id, ".Symbol.Div", "+", "#"),
+                expr!(Id, ".Block.TestCmdExpectation"),
+                expr!(Id, ".Symbol.Div", "+", "#"),
+                expr!(Id, ".Rule.Str"),
+                expr!(Id, ".Symbol.Div", "*", "#"),
+                expr!(String, ",", "#"),
+            },
+        };
+
+        // code: TestCmdExpectation <- "accepts" : "rejects",
+        let test_cmd_expectation_rule = rule!{
+            ".Block.TestCmdExpectation",
+            group!{
+                vec![],
+                group!{
+                    vec![":"],
+                    group!{
+                        vec![],
+                        expr!(String, "accepts"),
+                    },
+                    group!{
+                        vec![],
+                        expr!(String, "rejects"),
+                    },
+                },
+            },
+        };
+
+        return block!(".Block", vec![block_rule, cmd_rule, comment_rule, define_cmd_rule, define_cmd_memo_rule, define_cmd_memo_kind_rule, define_cmd_override_rule, define_cmd_deprecated_rule, define_cmd_alias_rule, define_cmd_generics_rule, define_cmd_template_rule, start_cmd_rule, entry_cmd_rule, use_cmd_rule, use_cmd_block_alias_rule, const_cmd_rule, const_value_rule, test_cmd_rule, test_cmd_expectation_rule]);
     }
 
     fn get_rule_block() -> Block {
@@ -1715,7 +2560,7 @@ impl FCPEGBlock {
             },
         };
 
-        // code: SeqElem <- Lookahead? (Choice : Expr) Loop? RandomOrder? ASTReflectionStyle?,
+        // code: SeqElem <- Lookahead? (Choice : Expr) (Loop : Separator)?## RandomOrder? LongestMatch? PresenceMarker? ASTReflectionStyle? Capture?,
         let seq_elem_rule = rule!{
             ".Rule.SeqElem",
             group!{
@@ -1735,13 +2580,29 @@ impl FCPEGBlock {
                         },
                     },
                 },
-                expr!(Id, ".Rule.Loop", "?"),
+                group!{
+                    vec!["?", "##"],
+                    group!{
+                        vec![":"],
+                        group!{
+                            vec![],
+                            expr!(Id, ".Rule.Loop"),
+                        },
+                        group!{
+                            vec![],
+                            expr!(Id, ".Rule.Separator"),
+                        },
+                    },
+                },
                 expr!(Id, ".Rule.RandomOrder", "?"),
+                expr!(Id, ".Rule.LongestMatch", "?"),
+                expr!(Id, ".Rule.PresenceMarker", "?"),
                 expr!(Id, ".Rule.ASTReflectionStyle", "?"),
+                expr!(Id, ".Rule.Capture", "?"),
             },
         };
 
-        // code: Expr <- ArgID : ID : Str : CharClass : Wildcard,
+        // code: Expr <- ArgID : ConstID : CaptureRef : ID : Str : CharClass : Wildcard,
         let expr_rule = rule!{
             ".Rule.Expr",
             group!{
@@ -1752,6 +2613,14 @@ impl FCPEGBlock {
                         vec![],
                         expr!(Id, ".Rule.ArgID"),
                     },
+                    group!{
+                        vec![],
+                        expr!(Id, ".Rule.ConstID"),
+                    },
+                    group!{
+                        vec![],
+                        expr!(Id, ".Rule.CaptureRef"),
+                    },
                     group!{
                         vec![],
                         expr!(Id, ".Rule.ID"),
@@ -1836,6 +2705,60 @@ impl FCPEGBlock {
             },
         };
 
+        // code: Separator <- Symbol.Div+# SeparatorKind Symbol.Div+# (Choice : Expr) TrailingSeparator?,
+        let separator_rule = rule!{
+            ".Rule.Separator",
+            group!{
+                vec![],
+                expr!(Id, ".Symbol.Div", "+", "#"),
+                expr!(Id, ".Rule.SeparatorKind"),
+                expr!(Id, ".Symbol.Div", "+", "#"),
+                group!{
+                    vec!["##"],
+                    group!{
+                        vec![":"],
+                        group!{
+                            vec![],
+                            expr!(Id, ".Rule.Choice"),
+                        },
+                        group!{
+                            vec![],
+                            expr!(Id, ".Rule.Expr"),
+                        },
+                    },
+                },
+                expr!(Id, ".Rule.TrailingSeparator", "?"),
+            },
+        };
+
+        // code: SeparatorKind <- "**" : "++",
+        let separator_kind_rule = rule!{
+            ".Rule.SeparatorKind",
+            group!{
+                vec![],
+                group!{
+                    vec![":"],
+                    group!{
+                        vec![],
+                        expr!(String, "**"),
+                    },
+                    group!{
+                        vec![],
+                        expr!(String, "++"),
+                    },
+                },
+            },
+        };
+
+        // code: TrailingSeparator <- "?",
+        let trailing_separator_rule = rule!{
+            ".Rule.TrailingSeparator",
+            group!{
+                vec![],
+                expr!(String, "?"),
+            },
+        };
+
         // expr: RandomOrder <- "^"# RandomOrderRange?,
         let random_order_rule = rule!{
             ".Rule.RandomOrder",
@@ -1846,6 +2769,24 @@ impl FCPEGBlock {
             },
         };
 
+        // code: LongestMatch <- "|",
+        let longest_match_rule = rule!{
+            ".Rule.LongestMatch",
+            group!{
+                vec![],
+                expr!(String, "|"),
+            },
+        };
+
+        // code: PresenceMarker <- "~",
+        let presence_marker_rule = rule!{
+            ".Rule.PresenceMarker",
+            group!{
+                vec![],
+                expr!(String, "~"),
+            },
+        };
+
         // code: RandomOrderRange <- "["# Symbol.Div*# Num?#MinNum (Symbol.Div*# "-"# Symbol.Div*# Num?#MaxNum)?#MaxNumGroup Symbol.Div*# "]"#,
         let random_order_range_rule = rule!{
             ".Rule.RandomOrderRange",
@@ -1866,7 +2807,7 @@ impl FCPEGBlock {
             },
         };
 
-        // code: ASTReflectionStyle <- "##" : "#"# Misc.SingleID?##,
+        // code: ASTReflectionStyle <- "##" Misc.SingleID?## : "#"# Misc.SingleID?##,
         let ast_reflection_rule = rule!{
             ".Rule.ASTReflectionStyle",
             group!{
@@ -1876,6 +2817,7 @@ impl FCPEGBlock {
                     group!{
                         vec![],
                         expr!(String, "##"),
+                        expr!(Id, ".Misc.SingleID", "?", "##"),
                     },
                     group!{
                         vec![],
@@ -1916,6 +2858,36 @@ impl FCPEGBlock {
             },
         };
 
+        // code: ConstID <- "@"# Misc.SingleID##,
+        let const_id_rule = rule!{
+            ".Rule.ConstID",
+            group!{
+                vec![],
+                expr!(String, "@", "#"),
+                expr!(Id, ".Misc.SingleID", "##"),
+            },
+        };
+
+        // code: Capture <- "->"# Misc.SingleID##,
+        let capture_rule = rule!{
+            ".Rule.Capture",
+            group!{
+                vec![],
+                expr!(String, "->", "#"),
+                expr!(Id, ".Misc.SingleID", "##"),
+            },
+        };
+
+        // code: CaptureRef <- "="# Misc.SingleID##,
+        let capture_ref_rule = rule!{
+            ".Rule.CaptureRef",
+            group!{
+                vec![],
+                expr!(String, "=", "#"),
+                expr!(Id, ".Misc.SingleID", "##"),
+            },
+        };
+
         // code: Generics <- "<"# Symbol.Div*# Seq (Symbol.Div*# ","# Symbol.Div*# Seq)*## Symbol.Div*# ">"#,
         let generics_rule = rule!{
             ".Rule.Generics",
@@ -1962,7 +2934,7 @@ impl FCPEGBlock {
             },
         };
 
-        // code: EscSeq <- "\\"# ("\\" : "\"" : "n" : "t" : "z")##,
+        // code: EscSeq <- "\\"# ("x" [0-9a-fA-F] [0-9a-fA-F] : "u" ("{" (!"}" !"\"" !"\\" .)*## "}"?)?## : .)##,
         let esc_seq_rule = rule!{
             ".Rule.EscSeq",
             group!{
@@ -1974,23 +2946,29 @@ impl FCPEGBlock {
                         vec![":"],
                         group!{
                             vec![],
-                            expr!(String, "\\"),
-                        },
-                        group!{
-                            vec![],
-                            expr!(String, "\""),
+                            expr!(String, "x"),
+                            expr!(CharClass, "[0-9a-fA-F]"),
+                            expr!(CharClass, "[0-9a-fA-F]"),
                         },
                         group!{
                             vec![],
-                            expr!(String, "n"),
-                        },
-                        group!{
-                            vec![],
-                            expr!(String, "t"),
+                            expr!(String, "u"),
+                            group!{
+                                vec!["?", "##"],
+                                expr!(String, "{"),
+                                group!{
+                                    vec!["*", "##"],
+                                    expr!(String, "}", "!"),
+                                    expr!(String, "\"", "!"),
+                                    expr!(String, "\\", "!"),
+                                    expr!(Wildcard, "."),
+                                },
+                                expr!(String, "}", "?"),
+                            },
                         },
                         group!{
                             vec![],
-                            expr!(String, "z"),
+                            expr!(Wildcard, "."),
                         },
                     },
                 },
@@ -2082,6 +3060,6 @@ impl FCPEGBlock {
             },
         };
 
-        return block!(".Rule", vec![pure_choice_rule, choice_rule, seq_rule, seq_elem_rule, expr_rule, lookahead_rule, loop_rule, loop_range_rule, random_order_rule, random_order_range_rule, ast_reflection_rule, num_rule, id_rule, arg_id_rule, generics_rule, template_rule, esc_seq_rule, str_rule, char_class_rule, wildcard_rule]);
+        return block!(".Rule", vec![pure_choice_rule, choice_rule, seq_rule, seq_elem_rule, expr_rule, lookahead_rule, loop_rule, loop_range_rule, separator_rule, separator_kind_rule, trailing_separator_rule, random_order_rule, random_order_range_rule, longest_match_rule, presence_marker_rule, ast_reflection_rule, num_rule, id_rule, arg_id_rule, const_id_rule, capture_rule, capture_ref_rule, generics_rule, template_rule, esc_seq_rule, str_rule, char_class_rule, wildcard_rule]);
     }
 }
@@ -158,15 +158,28 @@ pub struct Configuration {
 }
 
 impl Configuration {
+    pub fn new() -> Configuration {
+        return Configuration {
+            file_alias_map: HashMap::new(),
+            regex_mode: RegexMode::get_default_mode(),
+            reverse_ast_reflection_style: false,
+        };
+    }
+
     pub fn load(cons: Rc<RefCell<Console>>, file_path: &String) -> ConsoleResult<Configuration> {
         let file_content = match FileMan::read_all(file_path) {
-            Ok(v) => Box::new(v),
+            Ok(v) => v,
             Err(e) => {
                 cons.borrow_mut().append_log(e.get_log());
                 return Err(());
             },
         };
 
+        return Configuration::load_from_str(cons, file_path, file_content);
+    }
+
+    pub fn load_from_str(cons: Rc<RefCell<Console>>, file_path: &String, file_content: String) -> ConsoleResult<Configuration> {
+        let file_content = Box::new(file_content);
         let mut file_alias_map = HashMap::<String, String>::new();
         let mut reverse_ast_reflection_style = false;
         let mut regex_mode = RegexMode::get_default_mode();
@@ -287,8 +300,9 @@ struct ConfigurationParser {
 impl ConfigurationParser {
     fn parse(cons: Rc<RefCell<Console>>, src_path: String, src_content: Box<String>) -> ConsoleResult<Box<PropertyMap>> {
         let block_map = ConfigurationBlock::get_block_map();
-        let rule_map = Arc::new(Box::new(RuleMap::new(vec![block_map], DEFAULT_START_RULE_ID.to_string())?));
-        let tree = SyntaxParser::parse(cons.clone(), rule_map, src_path, src_content, true)?;
+        let rule_map = Arc::new(Box::new(RuleMap::new(&cons, vec![block_map], DEFAULT_START_RULE_ID.to_string())?));
+        let tree = SyntaxParser::parse(cons.clone(), rule_map, src_path, src_content, ParseConfig::default())?;
+        #[cfg(feature = "terminal")]
         tree.print(true);
 
         let mut config_parser = ConfigurationParser {
@@ -307,9 +321,9 @@ impl ConfigurationParser {
         for prop_item_node in tree.get_child_ref().get_node(&self.cons)?.find_child_nodes(vec![".Prop.Item"]) {
             let sub_item = prop_item_node.get_node_child_at(&self.cons, 0)?;
 
-            match &sub_item.ast_reflection_style {
-                ASTReflectionStyle::Reflection(name) => {
-                    match name.as_str() {
+            match (&sub_item.ast_reflection_style.visibility, sub_item.ast_reflection_style.get_name()) {
+                (ASTVisibility::Reflect, name) => {
+                    match name {
                         ".Prop.ChildItem" => {
                             let (key_stack, key, values) = self.to_child_property(sub_item)?;
                             root_item.add_values(&self.cons, key_stack, 0, key, values)?;
@@ -0,0 +1,248 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::rule::*;
+use crate::tree::*;
+
+use rustnutlib::*;
+use rustnutlib::console::*;
+
+use uuid::Uuid;
+
+pub enum CoverageLog {
+    CoverageSummary { exercised_rule_count: usize, rule_count: usize, matched_alternative_count: usize, alternative_count: usize },
+    UnexercisedRule { entry: CoverageEntry },
+    UnmatchedAlternative { entry: CoverageEntry },
+}
+
+impl ConsoleLogger for CoverageLog {
+    fn get_log(&self) -> ConsoleLog {
+        return match self {
+            CoverageLog::CoverageSummary { exercised_rule_count, rule_count, matched_alternative_count, alternative_count } => log!(Note, "grammar coverage", format!("exercised rules:\t{}/{}", exercised_rule_count, rule_count), format!("matched alternatives:\t{}/{}", matched_alternative_count, alternative_count)),
+            CoverageLog::UnexercisedRule { entry } => log!(Warning, format!("rule '{}' is never exercised", entry.rule_id), format!("at:\t{}", entry.pos)),
+            CoverageLog::UnmatchedAlternative { entry } => {
+                let alternative_i = match &entry.kind {
+                    CoverageEntryKind::Alternative { choice_uuid: _, alternative_i } => *alternative_i,
+                    _ => 0,
+                };
+
+                if entry.is_attempted {
+                    log!(Warning, format!("alternative #{} in rule '{}' never matches", alternative_i + 1, entry.rule_id), format!("at:\t{}", entry.pos))
+                } else {
+                    log!(Warning, format!("alternative #{} in rule '{}' is never tried", alternative_i + 1, entry.rule_id), format!("at:\t{}", entry.pos))
+                }
+            },
+        };
+    }
+}
+
+// spec: 規則グループの連番 (RuleGroup::index) の集合
+#[derive(Clone, Default)]
+pub struct GroupIndexSet {
+    words: Vec<u64>,
+}
+
+impl GroupIndexSet {
+    pub fn new(group_count: usize) -> GroupIndexSet {
+        return GroupIndexSet {
+            words: vec![0; (group_count + 63) / 64],
+        };
+    }
+
+    // note: 規則マップ外で生成されたグループの番号にも対応するため、範囲外であれば領域を広げる
+    pub fn insert(&mut self, index: usize) {
+        let word_i = index / 64;
+
+        if word_i >= self.words.len() {
+            self.words.resize(word_i + 1, 0);
+        }
+
+        self.words[word_i] |= 1 << (index % 64);
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        return match self.words.get(index / 64) {
+            Some(word) => word & (1 << (index % 64)) != 0,
+            None => false,
+        };
+    }
+}
+
+// spec: 解析中に記録する網羅状況; 解析間で累積する
+#[derive(Clone, Default)]
+pub struct CoverageRecord {
+    pub attempted: GroupIndexSet,
+    pub matched: GroupIndexSet,
+}
+
+impl CoverageRecord {
+    pub fn new(group_count: usize) -> CoverageRecord {
+        return CoverageRecord {
+            attempted: GroupIndexSet::new(group_count),
+            matched: GroupIndexSet::new(group_count),
+        };
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CoverageEntryKind {
+    // note: 規則のグループ; 規則の呼び出しごとに記録される
+    Rule,
+    // note: 選択肢を 2 つ以上持つ選択グループの選択肢; alternative_i は選択肢のインデックス
+    Alternative { choice_uuid: Uuid, alternative_i: usize },
+    // note: それ以外のグループ (括弧・引数・区切り・選択肢が 1 つのみの選択グループの選択肢)
+    Group,
+}
+
+#[derive(Clone)]
+pub struct CoverageEntry {
+    pub uuid: Uuid,
+    pub index: usize,
+    pub rule_id: String,
+    // note: 規則であれば定義の位置; それ以外はグループの先頭の表現字句の位置 (表現字句がなければ規則の位置)
+    pub pos: CharacterPosition,
+    pub kind: CoverageEntryKind,
+    pub is_attempted: bool,
+    pub is_matched: bool,
+}
+
+// spec: 網羅状況を規則マップのグループに対応づけたもの; 規則 ID 順で、規則内ではグループの記述順
+// note: 別名の項目は参照先の規則と同じ番号を持つため含めない; 他規則の別名にすぎない規則 (A <- B) は規則のみを含める
+#[derive(Clone)]
+pub struct GrammarCoverage {
+    pub entries: Vec<CoverageEntry>,
+}
+
+impl GrammarCoverage {
+    pub fn new(rule_map: &RuleMap, record: &CoverageRecord) -> GrammarCoverage {
+        let mut entries = Vec::<CoverageEntry>::new();
+
+        for each_rule_id in rule_map.get_defined_rule_ids() {
+            let rule = &rule_map.rule_map[each_rule_id];
+            entries.push(GrammarCoverage::to_entry(record, &rule.group, each_rule_id, rule.pos.clone(), CoverageEntryKind::Rule));
+
+            let is_alias_only = rule.generics_arg_ids.len() == 0 && rule.template_arg_ids.len() == 0 && rule.group.get_alias_rule_id().is_some();
+
+            if !is_alias_only {
+                GrammarCoverage::collect_sub_entries(record, &rule.group, rule, &mut entries);
+            }
+        }
+
+        return GrammarCoverage {
+            entries: entries,
+        };
+    }
+
+    // note: 選択グループは選択肢ごとに記録されるため自身は含めない
+    fn collect_sub_entries(record: &CoverageRecord, group: &RuleGroup, rule: &Rule, entries: &mut Vec<CoverageEntry>) {
+        let alternative_count = group.sub_elems.iter().filter(|each_elem| match each_elem {
+            RuleElement::Group(_) => true,
+            RuleElement::Expression(_) => false,
+        }).count();
+
+        let mut alternative_i = 0usize;
+
+        for each_elem in group.sub_elems.iter().chain(group.separator.iter()) {
+            match each_elem {
+                RuleElement::Group(each_group) => {
+                    let kind = if group.kind == RuleGroupKind::Choice && alternative_count >= 2 && alternative_i < alternative_count {
+                        CoverageEntryKind::Alternative { choice_uuid: group.uuid, alternative_i: alternative_i }
+                    } else {
+                        CoverageEntryKind::Group
+                    };
+
+                    alternative_i += 1;
+
+                    if each_group.kind != RuleGroupKind::Choice {
+                        let pos = GrammarCoverage::get_first_expr_pos(each_group).unwrap_or(rule.pos.clone());
+                        entries.push(GrammarCoverage::to_entry(record, each_group, &rule.id, pos, kind));
+                    }
+
+                    GrammarCoverage::collect_sub_entries(record, each_group, rule, entries);
+                },
+                RuleElement::Expression(each_expr) => {
+                    match &each_expr.kind {
+                        RuleExpressionKind::IdWithArgs { generics_args, template_args } => {
+                            for each_arg in generics_args.iter().chain(template_args.iter()) {
+                                let pos = GrammarCoverage::get_first_expr_pos(each_arg).unwrap_or(each_expr.pos.clone());
+                                entries.push(GrammarCoverage::to_entry(record, each_arg, &rule.id, pos, CoverageEntryKind::Group));
+                                GrammarCoverage::collect_sub_entries(record, each_arg, rule, entries);
+                            }
+                        },
+                        _ => (),
+                    }
+                },
+            }
+        }
+    }
+
+    fn to_entry(record: &CoverageRecord, group: &RuleGroup, rule_id: &String, pos: CharacterPosition, kind: CoverageEntryKind) -> CoverageEntry {
+        return CoverageEntry {
+            uuid: group.uuid,
+            index: group.index,
+            rule_id: rule_id.clone(),
+            pos: pos,
+            kind: kind,
+            is_attempted: record.attempted.contains(group.index),
+            is_matched: record.matched.contains(group.index),
+        };
+    }
+
+    fn get_first_expr_pos(group: &RuleGroup) -> Option<CharacterPosition> {
+        for each_elem in &group.sub_elems {
+            let pos = match each_elem {
+                RuleElement::Group(each_group) => GrammarCoverage::get_first_expr_pos(each_group),
+                RuleElement::Expression(each_expr) => Some(each_expr.pos.clone()),
+            };
+
+            if pos.is_some() {
+                return pos;
+            }
+        }
+
+        return None;
+    }
+
+    pub fn get_rule_entries(&self) -> Vec<&CoverageEntry> {
+        return self.entries.iter().filter(|each_entry| each_entry.kind == CoverageEntryKind::Rule).collect::<Vec<&CoverageEntry>>();
+    }
+
+    // ret: 一度も呼び出されなかった規則
+    pub fn get_unexercised_rules(&self) -> Vec<&CoverageEntry> {
+        return self.get_rule_entries().into_iter().filter(|each_entry| !each_entry.is_attempted).collect::<Vec<&CoverageEntry>>();
+    }
+
+    pub fn get_alternative_entries(&self) -> Vec<&CoverageEntry> {
+        return self.entries.iter().filter(|each_entry| match each_entry.kind {
+            CoverageEntryKind::Alternative { choice_uuid: _, alternative_i: _ } => true,
+            _ => false,
+        }).collect::<Vec<&CoverageEntry>>();
+    }
+
+    // ret: 一度も一致しなかった選択肢 (先行する選択肢が常に一致して検査されなかったものを含む); 呼び出されなかった規則の選択肢は含めない
+    pub fn get_unmatched_alternatives(&self) -> Vec<&CoverageEntry> {
+        let unexercised_rule_ids = self.get_unexercised_rules().iter().map(|each_entry| &each_entry.rule_id).collect::<Vec<&String>>();
+        return self.get_alternative_entries().into_iter().filter(|each_entry| !each_entry.is_matched && !unexercised_rule_ids.contains(&&each_entry.rule_id)).collect::<Vec<&CoverageEntry>>();
+    }
+
+    // spec: 呼び出されなかった規則と一致しなかった選択肢、件数をコンソールに追加する; 出力は呼び出し元が行う
+    pub fn print_report(&self, cons: &Rc<RefCell<Console>>) {
+        for each_entry in self.get_unexercised_rules() {
+            cons.borrow_mut().append_log(CoverageLog::UnexercisedRule { entry: each_entry.clone() }.get_log());
+        }
+
+        for each_entry in self.get_unmatched_alternatives() {
+            cons.borrow_mut().append_log(CoverageLog::UnmatchedAlternative { entry: each_entry.clone() }.get_log());
+        }
+
+        let rule_entries = self.get_rule_entries();
+        let alternative_entries = self.get_alternative_entries();
+
+        cons.borrow_mut().append_log(CoverageLog::CoverageSummary {
+            exercised_rule_count: rule_entries.iter().filter(|each_entry| each_entry.is_attempted).count(),
+            rule_count: rule_entries.len(),
+            matched_alternative_count: alternative_entries.iter().filter(|each_entry| each_entry.is_matched).count(),
+            alternative_count: alternative_entries.len(),
+        }.get_log());
+    }
+}
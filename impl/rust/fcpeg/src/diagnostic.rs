@@ -0,0 +1,160 @@
+use crate::parser::*;
+use crate::tree::*;
+
+// note: LSP の DiagnosticSeverity と同じ値
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DiagnosticSeverity {
+    Error = 1,
+    Warning = 2,
+    Information = 3,
+    Hint = 4,
+}
+
+// spec: 列数 (DiagnosticPosition::character) の単位; 値は LSP の PositionEncodingKind と対応する
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DiagnosticPositionEncoding {
+    // note: UTF-16 のコード単位; LSP の既定の単位
+    Utf16,
+    // note: 文字 (Unicode スカラー値) 単位; CharacterPosition の列数と同じ
+    Utf32,
+}
+
+impl DiagnosticPositionEncoding {
+    // ret: LSP の PositionEncodingKind の値
+    pub fn get_lsp_name(&self) -> &'static str {
+        return match self {
+            DiagnosticPositionEncoding::Utf16 => "utf-16",
+            DiagnosticPositionEncoding::Utf32 => "utf-32",
+        };
+    }
+}
+
+// note: line, character はともに 0 始まり; character の単位は Diagnostic::position_encoding による
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DiagnosticPosition {
+    pub line: usize,
+    pub character: usize,
+}
+
+impl DiagnosticPosition {
+    pub fn new(line: usize, character: usize) -> DiagnosticPosition {
+        return DiagnosticPosition {
+            line: line,
+            character: character,
+        };
+    }
+
+    pub fn from_char_position(pos: &CharacterPosition) -> DiagnosticPosition {
+        return DiagnosticPosition::new(pos.line, pos.column);
+    }
+
+    // spec: 文字単位の列数を、src の同じ行の先頭から数えた UTF-16 のコード単位の列数に変換する
+    // note: 行末を超える列数は行末以降の文字をそれぞれ 1 コード単位として数える
+    pub fn to_utf16(&self, src: &str) -> DiagnosticPosition {
+        let line_str = match src.split('\n').nth(self.line) {
+            Some(v) => v,
+            None => "",
+        };
+
+        let line_len = line_str.chars().count();
+        let utf16_len = line_str.chars().take(self.character).map(|each_char| each_char.len_utf16()).sum::<usize>();
+        let character = utf16_len + self.character.saturating_sub(line_len);
+        return DiagnosticPosition::new(self.line, character);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DiagnosticRange {
+    pub start: DiagnosticPosition,
+    pub end: DiagnosticPosition,
+}
+
+impl DiagnosticRange {
+    pub fn new(start: DiagnosticPosition, end: DiagnosticPosition) -> DiagnosticRange {
+        return DiagnosticRange {
+            start: start,
+            end: end,
+        };
+    }
+
+    pub fn to_utf16(&self, src: &str) -> DiagnosticRange {
+        return DiagnosticRange::new(self.start.to_utf16(src), self.end.to_utf16(src));
+    }
+}
+
+// spec: LSP の Diagnostic に対応する診断情報; コンソールログの文字列を解析せずに言語サーバへ渡すために用いる
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub range: DiagnosticRange,
+    pub severity: DiagnosticSeverity,
+    pub message: String,
+    pub code: String,
+    pub source: String,
+    // note: From<&SyntaxParsingLog> で変換した直後は Utf32
+    pub position_encoding: DiagnosticPositionEncoding,
+}
+
+impl Diagnostic {
+    pub fn new(range: DiagnosticRange, severity: DiagnosticSeverity, message: String, code: String) -> Diagnostic {
+        return Diagnostic {
+            range: range,
+            severity: severity,
+            message: message,
+            code: code,
+            source: "fcpeg".to_string(),
+            position_encoding: DiagnosticPositionEncoding::Utf32,
+        };
+    }
+
+    // spec: 範囲の列数を UTF-16 のコード単位に変換した診断情報を返す; src はログを出力した解析の入力とする
+    // note: LSP の既定の単位であるため、位置の単位を取り決めない言語サーバへ渡す前に用いる; 変換済みであればそのまま返す
+    pub fn to_utf16(&self, src: &str) -> Diagnostic {
+        let mut diagnostic = self.clone();
+
+        if self.position_encoding == DiagnosticPositionEncoding::Utf32 {
+            diagnostic.range = self.range.to_utf16(src);
+            diagnostic.position_encoding = DiagnosticPositionEncoding::Utf16;
+        }
+
+        return diagnostic;
+    }
+
+    // spec: LSP の Diagnostic の JSON 表現を返す
+    #[cfg(feature = "lsp")]
+    pub fn to_lsp_json(&self) -> serde_json::Value {
+        return serde_json::json!({
+            "range": {
+                "start": {
+                    "line": self.range.start.line,
+                    "character": self.range.start.character,
+                },
+                "end": {
+                    "line": self.range.end.line,
+                    "character": self.range.end.character,
+                },
+            },
+            "severity": self.severity as u8,
+            "code": self.code,
+            "source": self.source,
+            "message": self.message,
+        });
+    }
+}
+
+// spec: 位置を持たないログは文書の先頭を範囲とする; 終了位置が不明なログは開始位置を終了位置とする
+impl From<&SyntaxParsingLog> for Diagnostic {
+    fn from(log: &SyntaxParsingLog) -> Diagnostic {
+        let range = match log.get_range() {
+            Some((start_pos, end_pos)) => DiagnosticRange::new(DiagnosticPosition::from_char_position(&start_pos), DiagnosticPosition::from_char_position(&end_pos)),
+            None => DiagnosticRange::new(DiagnosticPosition::new(0, 0), DiagnosticPosition::new(0, 0)),
+        };
+
+        let severity = match log {
+            SyntaxParsingLog::DeprecatedRuleUse { pos: _, rule_id: _, alias_of: _, msg: _ } => DiagnosticSeverity::Warning,
+            SyntaxParsingLog::PositionMismatch { pos: _, value: _, found: _ } => DiagnosticSeverity::Warning,
+            _ => DiagnosticSeverity::Error,
+        };
+
+        return Diagnostic::new(range, severity, log.to_string(), log.get_code().to_string());
+    }
+}
@@ -1,13 +1,27 @@
 use std::cell::RefCell;
 use std::collections::*;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::Arc;
 
 use crate::config::*;
 
+use rustnutlib::*;
 use rustnutlib::console::*;
 use rustnutlib::file::*;
 
+pub enum FCPEGFileLog {
+    SourceNotFound { file_path: String },
+}
+
+impl ConsoleLogger for FCPEGFileLog {
+    fn get_log(&self) -> ConsoleLog {
+        return match self {
+            FCPEGFileLog::SourceNotFound { file_path } => log!(Error, format!("source '{}' not found", file_path)),
+        };
+    }
+}
+
 pub struct FCPEGFileMap {
     pub file_map: HashMap<String, FCPEGFile>,
     // spec: メインファイルを参照するエイリアス名; ID 変換時にエイリアスを空文字に置換する
@@ -15,10 +29,28 @@ pub struct FCPEGFileMap {
 }
 
 impl FCPEGFileMap {
-    // todo: config 読んでサブファイル対応
+    // note: ファイルシステムからファイルを読み込む
     pub fn load(cons: Rc<RefCell<Console>>, fcpeg_file_path: String, lib_fcpeg_file_map: HashMap<String, String>) -> ConsoleResult<FCPEGFileMap> {
+        return FCPEGFileMap::load_from_source_map(cons, fcpeg_file_path, HashMap::new(), lib_fcpeg_file_map, true);
+    }
+
+    // note: ファイルを介さずにメインファイルの内容を直接読み込む; 構成はデフォルト値を使用する
+    pub fn load_from_str(cons: Rc<RefCell<Console>>, file_path: String, file_content: String) -> ConsoleResult<FCPEGFileMap> {
+        let mut src_map = HashMap::<String, String>::new();
+        src_map.insert(file_path.clone(), file_content);
+
+        return FCPEGFileMap::load_from_str_map(cons, file_path, src_map, HashMap::new());
+    }
+
+    // spec: src_map は <仮想ファイルパス, ファイル内容>; 構成ファイルのエイリアスもマップ内のパスとして解決する
+    // spec: 構成ファイル (拡張子 cfg) がマップに存在しない場合はデフォルト値を使用する
+    pub fn load_from_str_map(cons: Rc<RefCell<Console>>, fcpeg_file_path: String, src_map: HashMap<String, String>, lib_fcpeg_file_map: HashMap<String, String>) -> ConsoleResult<FCPEGFileMap> {
+        return FCPEGFileMap::load_from_source_map(cons, fcpeg_file_path, src_map, lib_fcpeg_file_map, false);
+    }
+
+    fn load_from_source_map(cons: Rc<RefCell<Console>>, fcpeg_file_path: String, src_map: HashMap<String, String>, lib_fcpeg_file_map: HashMap<String, String>, use_file_system: bool) -> ConsoleResult<FCPEGFileMap> {
         // note: ルートファイルのエイリアス名は空文字; 除外エイリアスなし
-        let (file_map, replaced_file_alias_names) = FCPEGFileLoader::load(cons, fcpeg_file_path, lib_fcpeg_file_map)?;
+        let (file_map, replaced_file_alias_names) = FCPEGFileLoader::load(cons, fcpeg_file_path, src_map, lib_fcpeg_file_map, use_file_system)?;
 
         let file_map_wrapper = FCPEGFileMap {
             replaced_file_alias_names: Arc::new(replaced_file_alias_names),
@@ -49,22 +81,31 @@ struct FCPEGFileLoader {
     // spec: すでにロードされているファイルではエイリアス名をロード済みのものに置換する
     // note: <replace_from, replace_to>
     replaced_file_alias_names: HashMap<String, String>,
+    // spec: インクルードガード (#pragma once) を持つロード済みファイルの正規化されたパス
+    included_files: HashSet<PathBuf>,
+    // note: <file_path, file_content>; ファイルシステムから読み込んだ内容もここに格納する
+    src_map: HashMap<String, String>,
+    // spec: false の場合は src_map に存在しないファイルを読み込まない
+    use_file_system: bool,
 }
 
 impl FCPEGFileLoader {
-    pub fn load(cons: Rc<RefCell<Console>>, fcpeg_file_path: String, lib_fcpeg_file_map: HashMap<String, String>) -> ConsoleResult<(HashMap<String, FCPEGFile>, HashMap<String, String>)> {
+    pub fn load(cons: Rc<RefCell<Console>>, fcpeg_file_path: String, src_map: HashMap<String, String>, lib_fcpeg_file_map: HashMap<String, String>, use_file_system: bool) -> ConsoleResult<(HashMap<String, FCPEGFile>, HashMap<String, String>)> {
         let mut loader = FCPEGFileLoader {
             cons: cons,
             file_map_result: HashMap::new(),
             loaded_fcpeg_files: HashMap::new(),
             replaced_file_alias_names: HashMap::new(),
+            included_files: HashSet::new(),
+            src_map: src_map,
+            use_file_system: use_file_system,
         };
 
         // note: メインファイルのエイリアス名は空文字
         loader.load_file(String::new(), fcpeg_file_path)?;
 
         for (each_alias_name, each_fcpeg_file_path) in lib_fcpeg_file_map {
-            loader.load_file(each_alias_name, each_fcpeg_file_path)?;
+            loader.load_file_once(each_alias_name, each_fcpeg_file_path)?;
         }
 
         return Ok((loader.file_map_result, loader.replaced_file_alias_names));
@@ -72,16 +113,29 @@ impl FCPEGFileLoader {
 
     // ret: サブファイルのマップ
     fn load_file(&mut self, alias_name: String, fcpeg_file_path: String) -> ConsoleResult<()> {
-        let file_content = match FileMan::read_all(&fcpeg_file_path) {
-            Ok(v) => v,
-            Err(e) => {
-                self.cons.borrow_mut().append_log(e.get_log());
+        let file_content = match self.read_source(&fcpeg_file_path)? {
+            Some(v) => v,
+            None => {
+                self.cons.borrow_mut().append_log(FCPEGFileLog::SourceNotFound {
+                    file_path: fcpeg_file_path.clone(),
+                }.get_log());
+
                 return Err(());
             },
         };
 
+        if FCPEGFileLoader::has_include_guard(&file_content) {
+            let canonical_path = self.to_canonical_path(&fcpeg_file_path);
+            self.included_files.insert(canonical_path);
+        }
+
         let config_file_path = FileMan::rename_ext(&fcpeg_file_path, "cfg");
-        let config = Configuration::load(self.cons.clone(), &config_file_path)?;
+
+        let config = match self.read_source(&config_file_path)? {
+            Some(v) => Configuration::load_from_str(self.cons.clone(), &config_file_path, v)?,
+            None => Configuration::new(),
+        };
+
         let sub_file_alias_map = config.file_alias_map.clone();
 
         let new_file = FCPEGFile {
@@ -95,35 +149,103 @@ impl FCPEGFileLoader {
         // note: 無限再帰防止; 現在ロード中のエイリアスをロード対象から除外する
         self.loaded_fcpeg_files.insert(alias_name.clone(), fcpeg_file_path.clone());
 
-        'map_loop: for (sub_alias_name, sub_file_path) in sub_file_alias_map {
-            // note: エイリアス名の重複チェック
-            if self.loaded_fcpeg_files.contains_key(&sub_alias_name) || self.replaced_file_alias_names.contains_key(&sub_alias_name) {
+        for (sub_alias_name, sub_file_path) in sub_file_alias_map {
+            self.load_file_once(sub_alias_name, sub_file_path)?;
+        }
+
+        return Ok(());
+    }
+
+    // spec: ロード済みのファイルは再度ロードせず、エイリアス名をロード済みのものに置換する
+    // spec: インクルードガードを持つファイルは複数のファイルから同じエイリアス名で参照できる; それ以外のエイリアス名の重複はエラー
+    fn load_file_once(&mut self, alias_name: String, fcpeg_file_path: String) -> ConsoleResult<()> {
+        let loaded_alias_name = match self.replaced_file_alias_names.get(&alias_name) {
+            Some(v) => Some(v.clone()),
+            None if self.loaded_fcpeg_files.contains_key(&alias_name) => Some(alias_name.clone()),
+            None => None,
+        };
+
+        // note: エイリアス名の重複チェック
+        match loaded_alias_name {
+            Some(loaded_alias_name) => {
+                let loaded_file_path = self.loaded_fcpeg_files.get(&loaded_alias_name).unwrap().clone();
+
+                if self.included_files.contains(&self.to_canonical_path(&fcpeg_file_path)) && self.is_same_file(&loaded_file_path, &fcpeg_file_path)? {
+                    return Ok(());
+                }
+
                 self.cons.borrow_mut().append_log(ConfigurationLog::DuplicateFileAliasName {
-                    alias_name: sub_alias_name.clone(),
+                    alias_name: alias_name.clone(),
                 }.get_log());
 
                 return Err(());
-            }
+            },
+            None => (),
+        }
 
-            // note: ロード済みであれば無視
-            for (loaded_alias_name, loaded_file_path) in &self.loaded_fcpeg_files {
-                match FileMan::is_same(loaded_file_path, &sub_file_path) {
-                    Ok(is_same_path) => {
-                        if is_same_path {
-                            self.replaced_file_alias_names.insert(sub_alias_name.clone(), loaded_alias_name.clone());
-                            continue 'map_loop;
-                        }
-                    },
-                    Err(e) => {
-                        self.cons.borrow_mut().append_log(e.get_log());
-                        return Err(());
-                    },
-                }
+        // note: 別のエイリアス名でロード済みであれば置換する
+        for (loaded_alias_name, loaded_file_path) in &self.loaded_fcpeg_files {
+            if self.is_same_file(loaded_file_path, &fcpeg_file_path)? {
+                self.replaced_file_alias_names.insert(alias_name.clone(), loaded_alias_name.clone());
+                return Ok(());
             }
+        }
+
+        return self.load_file(alias_name, fcpeg_file_path);
+    }
+
+    // spec: 先頭の空白・改行を除いたファイル内容が #pragma once で始まればインクルードガードを持つ
+    fn has_include_guard(file_content: &String) -> bool {
+        return file_content.trim_start_matches(|each_char: char| each_char == ' ' || each_char == '\n').starts_with("#pragma once");
+    }
 
-            self.load_file(sub_alias_name, sub_file_path)?;
+    // note: 仮想ファイルパスはパス文字列をそのまま用いる; 正規化できないパスもそのまま用いる
+    fn to_canonical_path(&self, file_path: &String) -> PathBuf {
+        if !self.use_file_system {
+            return PathBuf::from(file_path);
         }
 
-        return Ok(());
+        return match std::fs::canonicalize(file_path) {
+            Ok(v) => v,
+            Err(_) => PathBuf::from(file_path),
+        };
+    }
+
+    // ret: ソースが見つからなければ None
+    fn read_source(&mut self, file_path: &String) -> ConsoleResult<Option<String>> {
+        match self.src_map.get(file_path) {
+            Some(v) => return Ok(Some(v.clone())),
+            None => (),
+        }
+
+        if !self.use_file_system {
+            return Ok(None);
+        }
+
+        let file_content = match FileMan::read_all(file_path) {
+            Ok(v) => v,
+            Err(e) => {
+                self.cons.borrow_mut().append_log(e.get_log());
+                return Err(());
+            },
+        };
+
+        self.src_map.insert(file_path.clone(), file_content.clone());
+        return Ok(Some(file_content));
+    }
+
+    fn is_same_file(&self, file_path: &String, other_file_path: &String) -> ConsoleResult<bool> {
+        // note: 仮想ファイルパスはパス文字列で比較する
+        if !self.use_file_system {
+            return Ok(file_path == other_file_path);
+        }
+
+        return match FileMan::is_same(file_path, other_file_path) {
+            Ok(v) => Ok(v),
+            Err(e) => {
+                self.cons.borrow_mut().append_log(e.get_log());
+                Err(())
+            },
+        };
     }
 }
@@ -1,8 +1,16 @@
+pub mod annotation;
 pub mod block;
 pub mod config;
+pub mod coverage;
+pub mod diagnostic;
 pub mod file;
+pub mod logging;
 pub mod parser;
+pub mod pattern;
 pub mod rule;
+pub mod span;
+pub mod terminal;
+pub mod testing;
 pub mod tree;
 
 use std::cell::RefCell;
@@ -11,6 +19,7 @@ use std::rc::Rc;
 use std::sync::Arc;
 
 use crate::block::*;
+use crate::diagnostic::*;
 use crate::file::*;
 use crate::parser::*;
 use crate::rule::*;
@@ -19,6 +28,33 @@ use crate::tree::*;
 use rustnutlib::console::*;
 use rustnutlib::file::*;
 
+pub struct FCPEGGrammar {}
+
+impl FCPEGGrammar {
+    pub fn load(cons: Rc<RefCell<Console>>, fcpeg_file_path: String, lib_fcpeg_file_map: HashMap<String, String>) -> ConsoleResult<Arc<Box<RuleMap>>> {
+        let mut fcpeg_file_map = FCPEGFileMap::load(cons.clone(), fcpeg_file_path, lib_fcpeg_file_map)?;
+        return BlockParser::get_rule_map(cons, &mut fcpeg_file_map, true);
+    }
+
+    // spec: name はログ出力時の仮想ファイルパスとして使用される
+    pub fn from_str(cons: Rc<RefCell<Console>>, name: &str, fcpeg_src: &str) -> ConsoleResult<Arc<Box<RuleMap>>> {
+        let mut fcpeg_file_map = FCPEGFileMap::load_from_str(cons.clone(), name.to_string(), fcpeg_src.to_string())?;
+        return BlockParser::get_rule_map(cons, &mut fcpeg_file_map, true);
+    }
+
+    // spec: from_str() と同じく読み込み、文法の構文解析に失敗した場合は解析器のログを診断情報として diagnostics に追加する
+    pub fn from_str_with_diagnostics(cons: Rc<RefCell<Console>>, name: &str, fcpeg_src: &str, diagnostics: &mut Vec<Diagnostic>) -> ConsoleResult<Arc<Box<RuleMap>>> {
+        let mut fcpeg_file_map = FCPEGFileMap::load_from_str(cons.clone(), name.to_string(), fcpeg_src.to_string())?;
+        return BlockParser::get_rule_map_with_diagnostics(cons, &mut fcpeg_file_map, true, diagnostics);
+    }
+
+    // spec: fcpeg_src_map は <仮想ファイルパス, ソース>; 構成ファイルのエイリアスはマップ内から解決される
+    pub fn from_str_map(cons: Rc<RefCell<Console>>, main_name: &str, fcpeg_src_map: HashMap<String, String>) -> ConsoleResult<Arc<Box<RuleMap>>> {
+        let mut fcpeg_file_map = FCPEGFileMap::load_from_str_map(cons.clone(), main_name.to_string(), fcpeg_src_map, HashMap::new())?;
+        return BlockParser::get_rule_map(cons, &mut fcpeg_file_map, true);
+    }
+}
+
 pub struct FCPEGParser {
     cons: Rc<RefCell<Console>>,
     rule_map: Arc<Box<RuleMap>>,
@@ -26,17 +62,17 @@ pub struct FCPEGParser {
 }
 
 impl FCPEGParser {
-    pub fn load(cons: Rc<RefCell<Console>>, fcpeg_file_path: String, lib_fcpeg_file_map: HashMap<String, String>, enable_memoization: bool) -> ConsoleResult<FCPEGParser> {
-        let mut fcpeg_file_map = FCPEGFileMap::load(cons.clone(), fcpeg_file_path, lib_fcpeg_file_map)?;
-        let rule_map = BlockParser::get_rule_map(cons.clone(), &mut fcpeg_file_map, true)?;
-
-        let parser = FCPEGParser {
+    pub fn new(cons: Rc<RefCell<Console>>, rule_map: Arc<Box<RuleMap>>, enable_memoization: bool) -> FCPEGParser {
+        return FCPEGParser {
             cons: cons,
             rule_map: rule_map,
             enable_memoization: enable_memoization,
         };
+    }
 
-        return Ok(parser);
+    pub fn load(cons: Rc<RefCell<Console>>, fcpeg_file_path: String, lib_fcpeg_file_map: HashMap<String, String>, enable_memoization: bool) -> ConsoleResult<FCPEGParser> {
+        let rule_map = FCPEGGrammar::load(cons.clone(), fcpeg_file_path, lib_fcpeg_file_map)?;
+        return Ok(FCPEGParser::new(cons, rule_map, enable_memoization));
     }
 
     pub fn parse(&mut self, input_file_path: String) -> ConsoleResult<SyntaxTree> {
@@ -48,7 +84,12 @@ impl FCPEGParser {
             },
         };
 
-        let tree = SyntaxParser::parse(self.cons.clone(), self.rule_map.clone(), input_file_path, input_file_content, self.enable_memoization)?;
+        let tree = SyntaxParser::parse(self.cons.clone(), self.rule_map.clone(), input_file_path, input_file_content, ParseConfig::default().with_memoization(self.enable_memoization))?;
         return Ok(tree);
     }
+
+    // spec: src_path はログ出力時のファイルパスとして使用される
+    pub fn parse_bytes(&mut self, src_path: String, src_bytes: &[u8], encoding: Encoding) -> ConsoleResult<SyntaxTree> {
+        return SyntaxParser::parse_bytes(self.cons.clone(), self.rule_map.clone(), src_path, src_bytes, encoding, ParseConfig::default().with_memoization(self.enable_memoization));
+    }
 }
@@ -0,0 +1,144 @@
+use std::fmt::*;
+use std::sync::{Arc, RwLock};
+
+use crate::parser::*;
+use crate::tree::*;
+
+// spec: ログを出力した処理の段階
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogPhase {
+    // note: 構文解析器による入力の解析
+    Parsing,
+    // note: 構文木の要素の取得
+    TreeAccess,
+}
+
+impl Display for LogPhase {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let s = match self {
+            LogPhase::Parsing => "parsing",
+            LogPhase::TreeAccess => "tree access",
+        };
+
+        return write!(f, "{}", s);
+    }
+}
+
+// spec: ログの出力元; src_path は対象の入力のパスで、構文木の要素から特定できなければ None
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogScope {
+    pub phase: LogPhase,
+    pub src_path: Option<String>,
+}
+
+impl LogScope {
+    pub fn new(phase: LogPhase, src_path: Option<String>) -> LogScope {
+        return LogScope {
+            phase: phase,
+            src_path: src_path,
+        };
+    }
+
+    // ret: phase と src_path のうち指定されたものがすべて一致するか
+    pub fn matches(&self, phase: Option<LogPhase>, src_path: Option<&str>) -> bool {
+        let is_phase_matched = match phase {
+            Some(v) => self.phase == v,
+            None => true,
+        };
+
+        let is_src_path_matched = match src_path {
+            Some(v) => self.src_path.as_deref() == Some(v),
+            None => true,
+        };
+
+        return is_phase_matched && is_src_path_matched;
+    }
+}
+
+impl Display for LogScope {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        return match &self.src_path {
+            Some(src_path) => write!(f, "{} {}", self.phase, src_path),
+            None => write!(f, "{}", self.phase),
+        };
+    }
+}
+
+#[derive(Clone)]
+pub enum ScopedLogKind {
+    Parsing(SyntaxParsingLog),
+    Tree(TreeLog),
+}
+
+impl Display for ScopedLogKind {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        return match self {
+            ScopedLogKind::Parsing(log) => write!(f, "{}", log),
+            ScopedLogKind::Tree(log) => write!(f, "{}", log),
+        };
+    }
+}
+
+#[derive(Clone)]
+pub struct ScopedLog {
+    pub scope: LogScope,
+    pub log: ScopedLogKind,
+}
+
+// spec: 複数の解析・構文木の操作で共有するログの記録; コンソールに追加したログを出力元のスコープとともに保持し、スコープごとに取り出す
+// note: 複製したハンドルは同じ記録を参照する; コンソールへの出力は従来どおり行われる
+#[derive(Clone)]
+pub struct LogRecorder {
+    logs: Arc<RwLock<Vec<ScopedLog>>>,
+}
+
+impl LogRecorder {
+    pub fn new() -> LogRecorder {
+        return LogRecorder {
+            logs: Arc::new(RwLock::new(Vec::new())),
+        };
+    }
+
+    pub fn record(&self, scope: LogScope, log: ScopedLogKind) {
+        self.logs.write().unwrap_or_else(|e| e.into_inner()).push(ScopedLog {
+            scope: scope,
+            log: log,
+        });
+    }
+
+    // spec: try_ 版の取得関数が返したエラーを、要素から特定した入力のパスとともに記録する
+    pub fn record_tree_log(&self, log: &TreeLog) {
+        self.record(LogScope::new(LogPhase::TreeAccess, log.get_src_path().cloned()), ScopedLogKind::Tree(log.clone()));
+    }
+
+    pub fn clear(&self) {
+        self.logs.write().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.logs.read().unwrap_or_else(|e| e.into_inner()).len();
+    }
+
+    // ret: 記録したすべてのログ (記録順)
+    pub fn get_logs(&self) -> Vec<ScopedLog> {
+        return self.logs.read().unwrap_or_else(|e| e.into_inner()).clone();
+    }
+
+    // ret: スコープが LogScope::matches() に一致するログ (記録順)
+    pub fn get_logs_in(&self, phase: Option<LogPhase>, src_path: Option<&str>) -> Vec<ScopedLog> {
+        return self.logs.read().unwrap_or_else(|e| e.into_inner()).iter().filter(|each_log| each_log.scope.matches(phase, src_path)).cloned().collect::<Vec<ScopedLog>>();
+    }
+}
+
+impl Debug for LogRecorder {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        return f.debug_struct("LogRecorder").field("len", &self.len()).finish();
+    }
+}
+
+// note: 同じ記録を参照するハンドルであれば等しい
+impl PartialEq for LogRecorder {
+    fn eq(&self, other: &LogRecorder) -> bool {
+        return Arc::ptr_eq(&self.logs, &other.logs);
+    }
+}
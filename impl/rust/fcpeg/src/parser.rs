@@ -1,14 +1,19 @@
 use std::cell::RefCell;
 use std::collections::*;
+use std::fmt::*;
+use std::hash::{Hash, Hasher};
 use std::rc::Rc;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 use crate::block::*;
+use crate::coverage::*;
+use crate::diagnostic::*;
+use crate::logging::*;
 use crate::rule::*;
+use crate::span::*;
+use crate::terminal::*;
 use crate::tree::*;
 
-use colored::*;
-
 use regex::*;
 
 use rustnutlib::*;
@@ -16,17 +21,44 @@ use rustnutlib::console::*;
 
 use uuid::Uuid;
 
+#[derive(Clone)]
 pub enum SyntaxParsingLog {
-    InvalidCharClassFormat { value: String },
-    InvalidGenericsArgumentLength { pos: CharacterPosition, expected_arg_len: usize },
-    InvalidTemplateArgumentLength { pos: CharacterPosition, expected_arg_len: usize },
+    // note: alias_of は rule_id が別名であれば参照先の規則 ID; pos は規則を参照した表現字句の位置
+    DeprecatedRuleUse { pos: CharacterPosition, rule_id: String, alias_of: Option<String>, msg: String },
+    InvalidCharClassFormat { pos: CharacterPosition, value: String, msg: String },
+    // note: decl は呼び出された規則の宣言; プリミティブ規則であれば None
+    InvalidGenericsArgumentLength { pos: CharacterPosition, expected_arg_len: usize, decl: Option<RuleDeclaration> },
+    // note: pos は変換できた部分の末尾; 不正なバイト列の先頭
+    InvalidInputEncoding { pos: CharacterPosition, encoding: Encoding },
+    // note: decl は InvalidGenericsArgumentLength と同様
+    InvalidTemplateArgumentLength { pos: CharacterPosition, expected_arg_len: usize, decl: Option<RuleDeclaration> },
+    // note: pos は文字クラス中のエスケープシーケンスの位置; sequence は記述どおりのエスケープシーケンス
+    InvalidUnicodeEscape { pos: CharacterPosition, sequence: String },
     InvalidLoopRange { msg: String },
     InvalidRuleElementStructure { uuid: Uuid, msg: String },
-    NoSucceededRule { pos: CharacterPosition, rule_id: String, rule_stack: Vec<(CharacterPosition, String)> },
-    TooLongRepetition { loop_limit: usize },
+    // note: rule_doc は規則スタックの先頭の (規則名, 説明); 説明の出力が有効な場合のみ Some
+    // note: expected は最も先の失敗位置で期待された終端記号; 失敗した表現字句がなければ None
+    NoSucceededRule { pos: CharacterPosition, rule_id: String, rule_stack: Vec<(CharacterPosition, String)>, rule_doc: Option<(String, String)>, expected: Option<ExpectedTokens> },
+    // note: pos はリーフの位置; value はリーフの値、found は pos の文字インデックスから同じ文字数だけ取り出した入力
+    PositionMismatch { pos: CharacterPosition, value: String, found: String },
+    TemplateArgumentTypeMismatch { pos: CharacterPosition, arg_id: String, expected_kind: RuleArgumentKind, actual_kind: RuleArgumentKind },
+    TooDeepRuleNesting { max_depth: usize },
+    // note: rule_id は規則スタックの先頭の規則; group_uuid は表現字句の繰り返しであれば None
+    TooLongRepetition { pos: CharacterPosition, rule_id: String, group_uuid: Option<Uuid>, loop_range: RuleElementLoopRange, loop_limit: usize },
     UncoveredPrimitiveRule { pos: CharacterPosition, rule_name: String },
+    // note: pos は開始規則を宣言した start 命令の位置; 命令がない場合や設定で開始規則を指定した場合は空の位置
+    StartRuleNotFound { pos: CharacterPosition, start_rule_id: String },
+    // note: entry_rule_ids は指定可能な規則 ID の一覧
+    // note: end_pos は未消費の入力の末尾; remaining_preview はエスケープ・省略した未消費の入力の先頭部分、remaining_len は EOF 用のヌル文字を除いた未消費の入力の文字数
+    UnconsumedInput { pos: CharacterPosition, end_pos: CharacterPosition, remaining_preview: String, remaining_len: usize },
+    UndeclaredEntryRule { rule_id: String, entry_rule_ids: Vec<String> },
+    // note: pos は入力の末尾; expected_rule は EOF に達した表現字句を含む規則
+    UnexpectedEOF { pos: CharacterPosition, expected_rule: String },
     UnknownGenericsArgumentID { arg_id: String },
     UnknownTemplateArgumentID { arg_id: String },
+    UnknownContextName { pos: CharacterPosition, name: String },
+    UnknownScannerName { pos: CharacterPosition, name: String },
+    UnknownTransformName { pos: CharacterPosition, name: String },
     UnknownLookaheadKind { uuid: Uuid, kind: String },
     UnknownRuleID { pos: CharacterPosition, rule_id: String },
 }
@@ -34,19 +66,204 @@ pub enum SyntaxParsingLog {
 impl ConsoleLogger for SyntaxParsingLog {
     fn get_log(&self) -> ConsoleLog {
         return match self {
-            SyntaxParsingLog::InvalidCharClassFormat { value } => log!(Error, format!("invalid character class format '{}'", value)),
-            SyntaxParsingLog::InvalidGenericsArgumentLength { pos, expected_arg_len } => log!(Error, format!("invalid generics argument length; expected {} argument(s)", expected_arg_len), format!("pos:\t{}", pos)),
-            SyntaxParsingLog::InvalidTemplateArgumentLength { pos, expected_arg_len } => log!(Error, format!("invalid template argument length; expected {} argument(s)", expected_arg_len), format!("pos:\t{}", pos)),
-            SyntaxParsingLog::InvalidLoopRange { msg } => log!(Error, format!("invalid loop range"), format!("{}", msg.bright_black())),
-            SyntaxParsingLog::InvalidRuleElementStructure { uuid, msg } => log!(Error, format!("invalid rule element structure"), format!("uuid:\t{}", uuid), format!("{}", msg.bright_black())),
-            SyntaxParsingLog::NoSucceededRule { pos, rule_id, rule_stack } => log!(Error, format!("no succeeded rule '{}'", rule_id), format!("at:\t{}", pos), format!("rule stack:\t{}", rule_stack.iter().map(|(each_pos, each_rule_id)| format!("\n\t\t{} at {}", each_rule_id, each_pos)).collect::<Vec<String>>().join(""))),
-            SyntaxParsingLog::TooLongRepetition { loop_limit } => log!(Error, format!("too long repetition over {}", loop_limit)),
-            SyntaxParsingLog::UncoveredPrimitiveRule { pos, rule_name } => log!(Error, format!("uncovered primitive rule '{}'", rule_name), format!("pos:\t{}", pos)),
-            SyntaxParsingLog::UnknownGenericsArgumentID { arg_id } => log!(Error, format!("unknown generics argument id '{}'", arg_id)),
-            SyntaxParsingLog::UnknownTemplateArgumentID { arg_id } => log!(Error, format!("unknown template argument id '{}'", arg_id)),
-            SyntaxParsingLog::UnknownLookaheadKind { uuid, kind } => log!(Error, format!("unknown lookahead kind '{}'", kind), format!("uuid:\t{}", uuid)),
-            SyntaxParsingLog::UnknownRuleID { pos, rule_id } => log!(Error, format!("unknown rule id '{}'", rule_id), format!("at:\t{}", pos)),
+            SyntaxParsingLog::DeprecatedRuleUse { pos, rule_id: _, alias_of, msg } => {
+                match alias_of {
+                    Some(canonical_rule_id) => log!(Warning, self.to_string(), format!("at:\t{}", pos), to_secondary_text(msg), format!("help:\tuse '{}' instead", canonical_rule_id)),
+                    None => log!(Warning, self.to_string(), format!("at:\t{}", pos), to_secondary_text(msg)),
+                }
+            },
+            SyntaxParsingLog::InvalidCharClassFormat { pos, value: _, msg } => log!(Error, self.to_string(), format!("at:\t{}", pos), to_secondary_text(msg)),
+            SyntaxParsingLog::InvalidGenericsArgumentLength { pos, expected_arg_len: _, decl } => {
+                match decl {
+                    Some(decl) => log!(Error, self.to_string(), format!("pos:\t{}", pos), format!("declared at:\t{}", decl.pos), decl.to_note_text("generics", "<", ">")),
+                    None => log!(Error, self.to_string(), format!("pos:\t{}", pos)),
+                }
+            },
+            SyntaxParsingLog::InvalidInputEncoding { pos, encoding: _ } => log!(Error, self.to_string(), format!("at:\t{}", pos)),
+            SyntaxParsingLog::InvalidTemplateArgumentLength { pos, expected_arg_len: _, decl } => {
+                match decl {
+                    Some(decl) => log!(Error, self.to_string(), format!("pos:\t{}", pos), format!("declared at:\t{}", decl.pos), decl.to_note_text("template", "(", ")")),
+                    None => log!(Error, self.to_string(), format!("pos:\t{}", pos)),
+                }
+            },
+            SyntaxParsingLog::InvalidUnicodeEscape { pos, sequence: _ } => log!(Error, self.to_string(), format!("at:\t{}", pos), "help:\tescape a Unicode scalar value (U+0000 to U+D7FF or U+E000 to U+10FFFF); in byte mode, a value up to U+00FF"),
+            SyntaxParsingLog::InvalidLoopRange { msg } => log!(Error, self.to_string(), to_secondary_text(msg)),
+            SyntaxParsingLog::InvalidRuleElementStructure { uuid, msg } => log!(Error, self.to_string(), format!("uuid:\t{}", uuid), to_secondary_text(msg)),
+            SyntaxParsingLog::NoSucceededRule { pos, rule_id: _, rule_stack, rule_doc, expected } => {
+                let rule_stack_text = rule_stack.iter().map(|(each_pos, each_rule_id)| format!("\n\t\t{} at {}", each_rule_id, each_pos)).collect::<Vec<String>>().join("");
+                let rule_doc_text = match rule_doc {
+                    Some((rule_name, doc)) => Some(format!("while parsing {} \u{2014} {}", rule_name, doc.replace("\n", " "))),
+                    None => None,
+                };
+
+                match (rule_doc_text, expected) {
+                    (Some(rule_doc_text), Some(expected_tokens)) => log!(Error, self.to_string(), format!("at:\t{}", pos), format!("rule stack:\t{}", rule_stack_text), expected_tokens.to_string(), rule_doc_text),
+                    (Some(rule_doc_text), None) => log!(Error, self.to_string(), format!("at:\t{}", pos), format!("rule stack:\t{}", rule_stack_text), rule_doc_text),
+                    (None, Some(expected_tokens)) => log!(Error, self.to_string(), format!("at:\t{}", pos), format!("rule stack:\t{}", rule_stack_text), expected_tokens.to_string()),
+                    (None, None) => log!(Error, self.to_string(), format!("at:\t{}", pos), format!("rule stack:\t{}", rule_stack_text)),
+                }
+            },
+            SyntaxParsingLog::PositionMismatch { pos, value: _, found: _ } => log!(Warning, self.to_string(), format!("at:\t{}", pos)),
+            SyntaxParsingLog::StartRuleNotFound { pos, start_rule_id: _ } => log!(Error, self.to_string(), format!("at:\t{}", pos), "help:\tdefine the rule or declare an existing rule with '+ start' in the Main block"),
+            SyntaxParsingLog::TemplateArgumentTypeMismatch { pos, arg_id: _, expected_kind: _, actual_kind: _ } => log!(Error, self.to_string(), format!("at:\t{}", pos)),
+            SyntaxParsingLog::TooDeepRuleNesting { max_depth: _ } => log!(Error, self.to_string()),
+            SyntaxParsingLog::TooLongRepetition { pos, rule_id: _, group_uuid, loop_range: _, loop_limit: _ } => {
+                match group_uuid {
+                    Some(uuid) => log!(Error, self.to_string(), format!("at:\t{}", pos), format!("uuid:\t{}", uuid)),
+                    None => log!(Error, self.to_string(), format!("at:\t{}", pos)),
+                }
+            },
+            SyntaxParsingLog::UncoveredPrimitiveRule { pos, rule_name: _ } => log!(Error, self.to_string(), format!("pos:\t{}", pos)),
+            SyntaxParsingLog::UnconsumedInput { pos, end_pos: _, remaining_preview: _, remaining_len } => {
+                if *remaining_len == 0 {
+                    log!(Error, self.to_string(), format!("at:\t{}", pos), "help:\tend the start rule with \"\\z\"# to consume the end of input")
+                } else {
+                    log!(Error, self.to_string(), format!("at:\t{}", pos))
+                }
+            },
+            SyntaxParsingLog::UndeclaredEntryRule { rule_id: _, entry_rule_ids } => log!(Error, self.to_string(), format!("valid entries:\t{}", entry_rule_ids.join(", "))),
+            SyntaxParsingLog::UnexpectedEOF { pos, expected_rule: _ } => log!(Error, self.to_string(), format!("at:\t{}", pos)),
+            SyntaxParsingLog::UnknownGenericsArgumentID { arg_id: _ } => log!(Error, self.to_string()),
+            SyntaxParsingLog::UnknownTemplateArgumentID { arg_id: _ } => log!(Error, self.to_string()),
+            SyntaxParsingLog::UnknownContextName { pos, name: _ } => log!(Error, self.to_string(), format!("at:\t{}", pos)),
+            SyntaxParsingLog::UnknownScannerName { pos, name: _ } => log!(Error, self.to_string(), format!("at:\t{}", pos), "help:\tregister the scanner to PrimitiveRegistry passed with ParseConfig"),
+            SyntaxParsingLog::UnknownTransformName { pos, name: _ } => log!(Error, self.to_string(), format!("at:\t{}", pos), "help:\tregister the transform to PrimitiveRegistry passed with ParseConfig"),
+            SyntaxParsingLog::UnknownLookaheadKind { uuid, kind: _ } => log!(Error, self.to_string(), format!("uuid:\t{}", uuid)),
+            SyntaxParsingLog::UnknownRuleID { pos, rule_id: _ } => log!(Error, self.to_string(), format!("at:\t{}", pos)),
+        };
+    }
+}
+
+impl SyntaxParsingLog {
+    pub fn get_position(&self) -> Option<&CharacterPosition> {
+        return match self {
+            SyntaxParsingLog::DeprecatedRuleUse { pos, rule_id: _, alias_of: _, msg: _ } => Some(pos),
+            SyntaxParsingLog::InvalidCharClassFormat { pos, value: _, msg: _ } => Some(pos),
+            SyntaxParsingLog::InvalidGenericsArgumentLength { pos, expected_arg_len: _, decl: _ } => Some(pos),
+            SyntaxParsingLog::InvalidInputEncoding { pos, encoding: _ } => Some(pos),
+            SyntaxParsingLog::InvalidTemplateArgumentLength { pos, expected_arg_len: _, decl: _ } => Some(pos),
+            SyntaxParsingLog::InvalidUnicodeEscape { pos, sequence: _ } => Some(pos),
+            SyntaxParsingLog::NoSucceededRule { pos, rule_id: _, rule_stack: _, rule_doc: _, expected: _ } => Some(pos),
+            SyntaxParsingLog::PositionMismatch { pos, value: _, found: _ } => Some(pos),
+            SyntaxParsingLog::StartRuleNotFound { pos, start_rule_id: _ } => Some(pos),
+            SyntaxParsingLog::TemplateArgumentTypeMismatch { pos, arg_id: _, expected_kind: _, actual_kind: _ } => Some(pos),
+            SyntaxParsingLog::TooLongRepetition { pos, rule_id: _, group_uuid: _, loop_range: _, loop_limit: _ } => Some(pos),
+            SyntaxParsingLog::UncoveredPrimitiveRule { pos, rule_name: _ } => Some(pos),
+            SyntaxParsingLog::UnconsumedInput { pos, end_pos: _, remaining_preview: _, remaining_len: _ } => Some(pos),
+            SyntaxParsingLog::UnexpectedEOF { pos, expected_rule: _ } => Some(pos),
+            SyntaxParsingLog::UnknownContextName { pos, name: _ } => Some(pos),
+            SyntaxParsingLog::UnknownScannerName { pos, name: _ } => Some(pos),
+            SyntaxParsingLog::UnknownTransformName { pos, name: _ } => Some(pos),
+            SyntaxParsingLog::UnknownRuleID { pos, rule_id: _ } => Some(pos),
+            _ => None,
+        };
+    }
+
+    // ret: NoSucceededRule であれば最も先の失敗位置で期待された終端記号; それ以外は None
+    pub fn get_expected_tokens(&self) -> Option<&ExpectedTokens> {
+        return match self {
+            SyntaxParsingLog::NoSucceededRule { pos: _, rule_id: _, rule_stack: _, rule_doc: _, expected } => expected.as_ref(),
+            _ => None,
+        };
+    }
+
+    // ret: (開始位置, 終了位置); 終了位置が不明であれば開始位置と同じ
+    pub fn get_range(&self) -> Option<(CharacterPosition, CharacterPosition)> {
+        let start_pos = self.get_position()?.clone();
+
+        let end_pos = match self {
+            SyntaxParsingLog::UnconsumedInput { pos: _, end_pos, remaining_preview: _, remaining_len: _ } => end_pos.clone(),
+            // note: 文字クラスのパターン・エスケープシーケンス・プリミティブ規則名は記述どおりの文字列であるため長さから終了位置がわかる
+            SyntaxParsingLog::InvalidCharClassFormat { pos, value: written_str, msg: _ } | SyntaxParsingLog::InvalidUnicodeEscape { pos, sequence: written_str } | SyntaxParsingLog::UncoveredPrimitiveRule { pos, rule_name: written_str } => {
+                let mut end_pos = pos.clone();
+                end_pos.index += written_str.chars().count();
+                end_pos.column += written_str.chars().count();
+                end_pos
+            },
+            _ => start_pos.clone(),
+        };
+
+        return Some((start_pos, end_pos));
+    }
+
+    // ret: 診断情報のコードとして用いるバリアント名
+    pub fn get_code(&self) -> &'static str {
+        return match self {
+            SyntaxParsingLog::DeprecatedRuleUse { pos: _, rule_id: _, alias_of: _, msg: _ } => "DeprecatedRuleUse",
+            SyntaxParsingLog::InvalidCharClassFormat { pos: _, value: _, msg: _ } => "InvalidCharClassFormat",
+            SyntaxParsingLog::InvalidGenericsArgumentLength { pos: _, expected_arg_len: _, decl: _ } => "InvalidGenericsArgumentLength",
+            SyntaxParsingLog::InvalidInputEncoding { pos: _, encoding: _ } => "InvalidInputEncoding",
+            SyntaxParsingLog::InvalidTemplateArgumentLength { pos: _, expected_arg_len: _, decl: _ } => "InvalidTemplateArgumentLength",
+            SyntaxParsingLog::InvalidUnicodeEscape { pos: _, sequence: _ } => "InvalidUnicodeEscape",
+            SyntaxParsingLog::InvalidLoopRange { msg: _ } => "InvalidLoopRange",
+            SyntaxParsingLog::InvalidRuleElementStructure { uuid: _, msg: _ } => "InvalidRuleElementStructure",
+            SyntaxParsingLog::NoSucceededRule { pos: _, rule_id: _, rule_stack: _, rule_doc: _, expected: _ } => "NoSucceededRule",
+            SyntaxParsingLog::PositionMismatch { pos: _, value: _, found: _ } => "PositionMismatch",
+            SyntaxParsingLog::StartRuleNotFound { pos: _, start_rule_id: _ } => "StartRuleNotFound",
+            SyntaxParsingLog::TemplateArgumentTypeMismatch { pos: _, arg_id: _, expected_kind: _, actual_kind: _ } => "TemplateArgumentTypeMismatch",
+            SyntaxParsingLog::TooDeepRuleNesting { max_depth: _ } => "TooDeepRuleNesting",
+            SyntaxParsingLog::TooLongRepetition { pos: _, rule_id: _, group_uuid: _, loop_range: _, loop_limit: _ } => "TooLongRepetition",
+            SyntaxParsingLog::UncoveredPrimitiveRule { pos: _, rule_name: _ } => "UncoveredPrimitiveRule",
+            SyntaxParsingLog::UnconsumedInput { pos: _, end_pos: _, remaining_preview: _, remaining_len: _ } => "UnconsumedInput",
+            SyntaxParsingLog::UndeclaredEntryRule { rule_id: _, entry_rule_ids: _ } => "UndeclaredEntryRule",
+            SyntaxParsingLog::UnexpectedEOF { pos: _, expected_rule: _ } => "UnexpectedEOF",
+            SyntaxParsingLog::UnknownGenericsArgumentID { arg_id: _ } => "UnknownGenericsArgumentID",
+            SyntaxParsingLog::UnknownTemplateArgumentID { arg_id: _ } => "UnknownTemplateArgumentID",
+            SyntaxParsingLog::UnknownContextName { pos: _, name: _ } => "UnknownContextName",
+            SyntaxParsingLog::UnknownScannerName { pos: _, name: _ } => "UnknownScannerName",
+            SyntaxParsingLog::UnknownTransformName { pos: _, name: _ } => "UnknownTransformName",
+            SyntaxParsingLog::UnknownLookaheadKind { uuid: _, kind: _ } => "UnknownLookaheadKind",
+            SyntaxParsingLog::UnknownRuleID { pos: _, rule_id: _ } => "UnknownRuleID",
+        };
+    }
+}
+
+#[cfg(feature = "lsp")]
+impl SyntaxParsingLog {
+    // spec: LSP の Diagnostic オブジェクトを返す
+    pub fn to_lsp_diagnostic(&self) -> serde_json::Value {
+        return Diagnostic::from(self).to_lsp_json();
+    }
+}
+
+// note: ログの見出し部分; 詳細情報は get_log() で付加する
+impl Display for SyntaxParsingLog {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let s = match self {
+            SyntaxParsingLog::DeprecatedRuleUse { pos: _, rule_id, alias_of, msg: _ } => match alias_of {
+                Some(canonical_rule_id) => format!("use of deprecated alias '{}' of rule '{}'", rule_id, canonical_rule_id),
+                None => format!("use of deprecated rule '{}'", rule_id),
+            },
+            SyntaxParsingLog::InvalidCharClassFormat { pos: _, value, msg: _ } => format!("invalid character class format '{}'", value),
+            SyntaxParsingLog::InvalidGenericsArgumentLength { pos: _, expected_arg_len, decl: _ } => format!("invalid generics argument length; expected {} argument(s)", expected_arg_len),
+            SyntaxParsingLog::InvalidInputEncoding { pos: _, encoding } => format!("invalid {} byte sequence in input", encoding),
+            SyntaxParsingLog::InvalidTemplateArgumentLength { pos: _, expected_arg_len, decl: _ } => format!("invalid template argument length; expected {} argument(s)", expected_arg_len),
+            SyntaxParsingLog::InvalidUnicodeEscape { pos: _, sequence } => match to_escaped_code_point(sequence) {
+                Some(code_point) => format!("invalid Unicode escape sequence '{}' (U+{:04X}) in character class", sequence, code_point),
+                None => format!("invalid Unicode escape sequence '{}' in character class", sequence),
+            },
+            SyntaxParsingLog::InvalidLoopRange { msg: _ } => "invalid loop range".to_string(),
+            SyntaxParsingLog::InvalidRuleElementStructure { uuid: _, msg: _ } => "invalid rule element structure".to_string(),
+            SyntaxParsingLog::NoSucceededRule { pos: _, rule_id, rule_stack: _, rule_doc: _, expected: _ } => format!("no succeeded rule '{}'", rule_id),
+            SyntaxParsingLog::PositionMismatch { pos: _, value, found } => format!("position of leaf \"{}\" mismatches the source \"{}\"", escape_source_text(value), escape_source_text(found)),
+            SyntaxParsingLog::StartRuleNotFound { pos: _, start_rule_id } => format!("start rule '{}' is not found in the grammar", start_rule_id),
+            SyntaxParsingLog::TemplateArgumentTypeMismatch { pos: _, arg_id, expected_kind, actual_kind } => format!("argument '${}' expects {} expression but {} expression is passed", arg_id, expected_kind, actual_kind),
+            SyntaxParsingLog::TooDeepRuleNesting { max_depth } => format!("too deep rule nesting over {}", max_depth),
+            SyntaxParsingLog::TooLongRepetition { pos: _, rule_id, group_uuid: _, loop_range, loop_limit } => format!("too long repetition over {} in rule '{}' (loop range {})", loop_limit, rule_id, loop_range.to_string(true, "", "{", ",", "}")),
+            SyntaxParsingLog::UncoveredPrimitiveRule { pos: _, rule_name } => format!("uncovered primitive rule '{}'", rule_name),
+            SyntaxParsingLog::UnconsumedInput { pos: _, end_pos: _, remaining_preview, remaining_len } => format!("unconsumed input \"{}\" ({} character(s))", remaining_preview, remaining_len),
+            SyntaxParsingLog::UndeclaredEntryRule { rule_id, entry_rule_ids: _ } => format!("undeclared entry rule '{}'", rule_id),
+            SyntaxParsingLog::UnexpectedEOF { pos: _, expected_rule } => format!("unexpected EOF while parsing rule '{}'", expected_rule),
+            SyntaxParsingLog::UnknownGenericsArgumentID { arg_id } => format!("unknown generics argument id '{}'", arg_id),
+            SyntaxParsingLog::UnknownTemplateArgumentID { arg_id } => format!("unknown template argument id '{}'", arg_id),
+            SyntaxParsingLog::UnknownContextName { pos: _, name } => format!("unknown context name '{}'", name),
+            SyntaxParsingLog::UnknownScannerName { pos: _, name } => format!("unknown scanner name '{}'", name),
+            SyntaxParsingLog::UnknownTransformName { pos: _, name } => format!("unknown transform name '{}'", name),
+            SyntaxParsingLog::UnknownLookaheadKind { uuid: _, kind } => format!("unknown lookahead kind '{}'", kind),
+            SyntaxParsingLog::UnknownRuleID { pos: _, rule_id } => format!("unknown rule id '{}'", rule_id),
         };
+
+        return write!(f, "{}", s);
     }
 }
 
@@ -64,6 +281,49 @@ impl ArgumentMap {
     }
 }
 
+// spec: 捕捉した入力文字列を捕捉順に保持する; 同名の捕捉は後のものが優先される
+// note: 失敗した分岐の捕捉はバックトラック時に長さを戻して破棄する
+pub struct CaptureMap {
+    // note: Vec<(捕捉名, 文字列)>
+    entries: Vec<(String, String)>,
+}
+
+impl CaptureMap {
+    pub fn new() -> CaptureMap {
+        return CaptureMap {
+            entries: Vec::new(),
+        };
+    }
+
+    pub fn push(&mut self, name: String, value: String) {
+        self.entries.push((name, value));
+    }
+
+    pub fn find(&self, name: &str) -> Option<&String> {
+        return match self.entries.iter().rev().find(|(each_name, _)| each_name == name) {
+            Some((_, value)) => Some(value),
+            None => None,
+        };
+    }
+
+    pub fn len(&self) -> usize {
+        return self.entries.len();
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.entries.truncate(len);
+    }
+
+    // ret: len 番目以降の捕捉; 自身からは取り除かれる
+    pub fn split_off(&mut self, len: usize) -> Vec<(String, String)> {
+        return self.entries.split_off(len);
+    }
+
+    pub fn append(&mut self, mut entries: Vec<(String, String)>) {
+        self.entries.append(&mut entries);
+    }
+}
+
 pub struct MemoizationMap {
     // note: HashMap<(group_uuid, src_i), (src_len, result)>
     map: HashMap<(Uuid, usize), (usize, Option<Vec<SyntaxNodeElement>>)>,
@@ -86,848 +346,4405 @@ impl MemoizationMap {
             None => None,
         };
     }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.map.is_empty();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.map.len();
+    }
+
+    // note: 同じキーの結果は other のものが優先される
+    pub fn extend(&mut self, other: MemoizationMap) {
+        self.map.extend(other.map);
+    }
 }
 
-pub struct SyntaxParser {
-    cons: Rc<RefCell<Console>>,
-    rule_map: Arc<Box<RuleMap>>,
-    src_i: usize,
-    src_line: usize,
-    src_latest_line_i: usize,
-    src_path: String,
-    src_content: Box<String>,
-    loop_limit: usize,
-    arg_maps: Box<Vec<ArgumentMap>>,
-    rule_stack: Box<Vec<(CharacterPosition, String)>>,
-    regex_map: Box<HashMap<String, Regex>>,
-    memoized_map: Box<MemoizationMap>,
-    enable_memoization: bool,
+struct SharedMemoizationStore {
+    // note: 結果を記録した解析の入力キー; SyntaxParser::get_input_key() を参照
+    input_key: Option<u64>,
+    map: MemoizationMap,
 }
 
-impl SyntaxParser {
-    pub fn parse(cons: Rc<RefCell<Console>>, rule_map: Arc<Box<RuleMap>>, src_path: String, src_content: Box<String>, enable_memoization: bool) -> ConsoleResult<SyntaxTree> {
-        let mut parser = SyntaxParser {
-            cons: cons,
-            rule_map: rule_map,
-            src_i: 0,
-            src_line: 0,
-            src_latest_line_i: 0,
-            src_path: src_path,
-            src_content: src_content,
-            loop_limit: 65536,
-            arg_maps: Box::new(Vec::new()),
-            rule_stack: Box::new(Vec::new()),
-            regex_map: Box::new(HashMap::new()),
-            memoized_map: Box::new(MemoizationMap::new()),
-            enable_memoization: enable_memoization,
+// spec: 同じ入力・規則マップに対する複数回の解析 (異なる開始規則での解析等) でメモ化結果を共有する; 複製したハンドルは同じ結果を参照し、スレッド間で共有できる
+// note: 入力キーが異なる解析に渡された場合は以前の結果を破棄するため、異なる入力に対して結果が再利用されることはない
+#[derive(Clone)]
+pub struct SharedMemoization {
+    store: Arc<RwLock<SharedMemoizationStore>>,
+}
+
+impl SharedMemoization {
+    pub fn new() -> SharedMemoization {
+        return SharedMemoization {
+            store: Arc::new(RwLock::new(SharedMemoizationStore {
+                input_key: None,
+                map: MemoizationMap::new(),
+            })),
         };
+    }
 
-        // note: 余分な改行コード 0x0d を排除する
-        loop {
-            match parser.src_content.find(0x0d as char) {
-                Some(v) => {
-                    let _ = parser.src_content.remove(v);
-                },
-                None => break,
-            }
+    pub fn clear(&self) {
+        let mut store = self.store.write().unwrap_or_else(|e| e.into_inner());
+        store.input_key = None;
+        store.map.clear();
+    }
+
+    // ret: 保持している結果の数
+    pub fn len(&self) -> usize {
+        return self.store.read().unwrap_or_else(|e| e.into_inner()).map.len();
+    }
+
+    // spec: 入力キーに対応する結果を取り出す; 入力キーが異なれば以前の結果を破棄する
+    // note: 解析中はロックを保持しないため、同時に解析する他のハンドルは結果を共有せずに解析する
+    fn take_map(&self, input_key: u64) -> MemoizationMap {
+        let mut store = self.store.write().unwrap_or_else(|e| e.into_inner());
+
+        if store.input_key != Some(input_key) {
+            store.input_key = Some(input_key);
+            store.map.clear();
         }
 
-        // EOF 用のヌル文字
-        *parser.src_content += "\0";
+        return std::mem::replace(&mut store.map, MemoizationMap::new());
+    }
 
-        let start_rule_id = parser.rule_map.start_rule_id.clone();
+    // spec: 取り出した結果に解析中の結果を加えて戻す; 解析中に他の入力で使用された場合は破棄する
+    fn restore_map(&self, input_key: u64, map: MemoizationMap) {
+        let mut store = self.store.write().unwrap_or_else(|e| e.into_inner());
 
-        if parser.src_content.chars().count() == 0 {
-            return Ok(SyntaxTree::from_node_args(Vec::new(), ASTReflectionStyle::Reflection(String::new())));
+        if store.input_key == Some(input_key) {
+            store.map.extend(map);
         }
+    }
+}
 
-        let start_rule_pos = parser.rule_map.start_rule_pos.clone();
-        let mut root_node = match parser.parse_rule(&start_rule_id, &start_rule_pos)? {
-            Some(v) => v,
-            None => {
-                parser.cons.borrow_mut().append_log(SyntaxParsingLog::NoSucceededRule {
-                    rule_id: start_rule_id.clone(),
-                    pos: parser.get_char_position(),
-                    rule_stack: *parser.rule_stack.clone(),
-                }.get_log());
+impl Debug for SharedMemoization {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let store = self.store.read().unwrap_or_else(|e| e.into_inner());
+        return f.debug_struct("SharedMemoization").field("input_key", &store.input_key).field("len", &store.map.len()).finish();
+    }
+}
 
-                return Err(());
-            },
-        };
+// note: 同じ結果を参照するハンドルであれば等しい
+impl PartialEq for SharedMemoization {
+    fn eq(&self, other: &SharedMemoization) -> bool {
+        return Arc::ptr_eq(&self.store, &other.store);
+    }
+}
 
-        // note: ルートは常に Reflectable
-        root_node.set_ast_reflection_style(ASTReflectionStyle::Reflection(start_rule_id.clone()));
+pub const DEFAULT_LOOP_LIMIT: usize = 65536;
+// note: 既定のスタックサイズ (2 MiB) のスレッドでデバッグビルドの再帰型の解析器がスタックを溢れさせない深さとする
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+// note: regex クレートの既定値と同じ
+pub const DEFAULT_REGEX_SIZE_LIMIT: usize = 10 * (1 << 20);
+pub const DEFAULT_REGEX_DFA_SIZE_LIMIT: usize = 2 * (1 << 20);
+
+// note: UnconsumedInput のログに含める未消費の入力の最大文字数
+const UNCONSUMED_INPUT_PREVIEW_MAX_LEN: usize = 40;
+
+// note: NoSucceededRule のログに列挙する期待された終端記号の最大数; 超過分は件数のみを表示する
+pub const EXPECTED_TOKENS_MAX_LEN: usize = 8;
+
+// spec: 解析に失敗した際に、最も先の失敗位置で期待された終端記号と実際の文字
+#[derive(Clone, PartialEq)]
+pub struct ExpectedTokens {
+    pub pos: CharacterPosition,
+    // note: RuleMap::get_expected_terminals() による説明; 重複なしで失敗した順
+    pub tokens: Vec<String>,
+    // note: 失敗位置の文字 (エスケープ済み); 入力の終端であれば None
+    pub found: Option<String>,
+}
 
-        // note: 入力位置が length を超えると失敗
-        if parser.src_i < parser.src_content.chars().count() {
-            parser.cons.borrow_mut().append_log(SyntaxParsingLog::NoSucceededRule {
-                rule_id: start_rule_id.clone(),
-                pos: parser.get_char_position(),
-                rule_stack: *parser.rule_stack.clone(),
-            }.get_log());
+impl Display for ExpectedTokens {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let mut tokens_text = self.tokens.iter().take(EXPECTED_TOKENS_MAX_LEN).map(|each_token| each_token.as_str()).collect::<Vec<&str>>().join(", ");
 
-            return Err(());
+        if self.tokens.len() > EXPECTED_TOKENS_MAX_LEN {
+            tokens_text += &format!(" and {} more", self.tokens.len() - EXPECTED_TOKENS_MAX_LEN);
         }
 
-        return Ok(SyntaxTree::from_node(root_node));
+        let found_text = match &self.found {
+            Some(v) => format!("'{}'", v),
+            None => "end of input".to_string(),
+        };
+
+        return write!(f, "expected: {}, found: {} at {}", tokens_text, found_text, self.pos);
     }
+}
 
-    fn parse_rule(&mut self, rule_id: &String, pos: &CharacterPosition) -> ConsoleResult<Option<SyntaxNodeElement>> {
-        let rule_group = match self.rule_map.rule_map.get(rule_id) {
-            Some(rule) => rule.group.clone(),
-            None => {
-                self.cons.borrow_mut().append_log(SyntaxParsingLog::UnknownRuleID {
-                    pos: pos.clone(),
-                    rule_id: rule_id.clone(),
-                }.get_log());
+// spec: プリミティブ規則 INTO<グループ, "変換名"> が呼び出す変換; グループの結果の要素を受け取り、新たなリーフの値を返す
+pub type PrimitiveTransform = Arc<dyn Fn(Vec<SyntaxNodeElement>) -> String>;
 
-                return Err(());
-            },
+// spec: プリミティブ規則 SCAN<"走査名"> が呼び出す走査; 構文解析器の入力を直接読み進め、一致すれば新たなリーフの値を返す
+// spec: None を返した場合は失敗とし、走査前の入力位置に戻す
+// note: 走査の中での後戻りは SyntaxParser::checkpoint() と restore() で行う
+pub type PrimitiveScanner = Arc<dyn Fn(&mut SyntaxParser) -> Option<String>>;
+
+// spec: 変換名から INTO の変換へ、走査名から SCAN の走査へのハッシュマップ; 同名の変換・走査は後に登録したものが優先される
+#[derive(Clone)]
+pub struct PrimitiveRegistry {
+    transforms: HashMap<String, PrimitiveTransform>,
+    scanners: HashMap<String, PrimitiveScanner>,
+}
+
+impl PrimitiveRegistry {
+    pub fn new() -> PrimitiveRegistry {
+        return PrimitiveRegistry {
+            transforms: HashMap::new(),
+            scanners: HashMap::new(),
         };
+    }
 
-        self.rule_stack.push((self.get_char_position(), rule_id.clone()));
+    pub fn register(&mut self, name: &str, f: PrimitiveTransform) {
+        self.transforms.insert(name.to_string(), f);
+    }
 
-        return match self.parse_group(&rule_group.elem_order, &rule_group)? {
-            Some(v) => {
-                let mut ast_reflection_style = match &rule_group.sub_elems.get(0) {
-                    Some(v) => {
-                        match v {
-                            RuleElement::Group(sub_choice) => sub_choice.ast_reflection_style.clone(),
-                            RuleElement::Expression(_) => rule_group.ast_reflection_style.clone(),
-                        }
-                    },
-                    _ => rule_group.ast_reflection_style.clone(),
-                };
+    pub fn get(&self, name: &str) -> Option<&PrimitiveTransform> {
+        return self.transforms.get(name);
+    }
 
-                match &ast_reflection_style {
-                    ASTReflectionStyle::Reflection(elem_name) if *elem_name == String::new() => {
-                        // todo: 構成ファイルを ASTReflection に反映
-                        ast_reflection_style = ASTReflectionStyle::from_config(false, true, rule_id.clone());
-                    },
-                    _ => (),
-                };
+    // ret: 登録された変換名の一覧 (名前順)
+    pub fn get_names(&self) -> Vec<&String> {
+        let mut names = self.transforms.keys().collect::<Vec<&String>>();
+        names.sort();
+        return names;
+    }
 
-                self.rule_stack.pop().unwrap();
-                let new_node = SyntaxNodeElement::from_node_args(v, ast_reflection_style);
-                Ok(Some(new_node))
-            },
-            None => {
-                Ok(None)
-            },
-        }
+    pub fn register_scanner(&mut self, name: &str, f: PrimitiveScanner) {
+        self.scanners.insert(name.to_string(), f);
     }
 
-    fn parse_group(&mut self, parent_elem_order: &RuleElementOrder, group: &Box<RuleGroup>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
-        if self.enable_memoization {
-            match self.memoized_map.find(&group.uuid, self.src_i) {
-                Some((src_len, result)) => {
-                    self.src_i += src_len;
-                    return Ok(result);
-                },
-                None => (),
-            }
-        }
+    pub fn get_scanner(&self, name: &str) -> Option<&PrimitiveScanner> {
+        return self.scanners.get(name);
+    }
 
-        let tmp_i = self.src_i;
-        let result = self.parse_lookahead_group(parent_elem_order, group)?;
+    // ret: 登録された走査名の一覧 (名前順)
+    pub fn get_scanner_names(&self) -> Vec<&String> {
+        let mut names = self.scanners.keys().collect::<Vec<&String>>();
+        names.sort();
+        return names;
+    }
+}
+
+// note: クロージャは比較・表示できないため変換名と参照先で扱う
+impl Debug for PrimitiveRegistry {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        return f.debug_struct("PrimitiveRegistry").field("transforms", &self.get_names()).field("scanners", &self.get_scanner_names()).finish();
+    }
+}
 
-        if self.enable_memoization {
-            if self.src_i != tmp_i {
-                self.memoized_map.push(group.uuid.clone(), tmp_i, self.src_i - tmp_i, result.clone());
+impl PartialEq for PrimitiveRegistry {
+    fn eq(&self, other: &PrimitiveRegistry) -> bool {
+        let are_transforms_eq = self.transforms.len() == other.transforms.len() && self.transforms.iter().all(|(each_name, each_transform)| {
+            match other.transforms.get(each_name) {
+                Some(other_transform) => Arc::ptr_eq(each_transform, other_transform),
+                None => false,
             }
-        }
+        });
 
-        return Ok(result);
+        let are_scanners_eq = self.scanners.len() == other.scanners.len() && self.scanners.iter().all(|(each_name, each_scanner)| {
+            match other.scanners.get(each_name) {
+                Some(other_scanner) => Arc::ptr_eq(each_scanner, other_scanner),
+                None => false,
+            }
+        });
+
+        return are_transforms_eq && are_scanners_eq;
     }
+}
 
-    fn parse_lookahead_group(&mut self, parent_elem_order: &RuleElementOrder, group: &Box<RuleGroup>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
-        return if group.lookahead_kind.is_none() {
-            self.parse_loop_group(parent_elem_order, group)
-        } else {
-            let start_src_i = self.src_i;
-            let is_lookahead_positive = group.lookahead_kind == RuleElementLookaheadKind::Positive;
+// spec: 入力から得られない文法外部の情報 (文字コード, 方言の版等); 後方参照は入力から捕捉された名前がなければこれを参照する
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParseContext {
+    // note: <捕捉名, 値>
+    pub extern_captures: HashMap<String, String>,
+}
 
-            let result = self.parse_loop_group(parent_elem_order, group)?;
-            self.src_i = start_src_i;
+impl ParseContext {
+    pub fn new() -> ParseContext {
+        return ParseContext::default();
+    }
 
-            if result.is_some() == is_lookahead_positive {
-                Ok(Some(Vec::new()))
-            } else {
-                Ok(None)
-            }
-        };
+    pub fn with_extern_capture(mut self, name: &str, value: &str) -> ParseContext {
+        self.extern_captures.insert(name.to_string(), value.to_string());
+        return self;
     }
+}
 
-    fn parse_loop_group(&mut self, parent_elem_order: &RuleElementOrder, group: &Box<RuleGroup>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
-        let (min_count, max_count) = group.loop_range.to_tuple();
+// spec: 解析時のオプション; 未指定の項目は既定値となる
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseConfig {
+    // note: 文法に後方参照が含まれる場合は真でもメモ化を行わない; %memo / %nomemo 指定のない規則の既定値となる
+    pub enable_memoization: bool,
+    // note: 1 つの要素の繰り返し回数の上限; 超過すると TooLongRepetition エラーとなる
+    pub loop_limit: usize,
+    // note: 規則の呼び出しの入れ子の深さの上限; 超過すると TooDeepRuleNesting エラーとなる
+    pub max_depth: usize,
+    // note: 真であれば反映名のない規則ノードを規則 ID (ファイルエイリアス名.ブロック名.規則名) で命名する; 偽であれば規則名のみとする
+    pub qualify_rule_names: bool,
+    // note: 規則 ID で指定する開始規則; None であれば規則マップの開始規則を用いる
+    pub start_rule_id: Option<String>,
+    // note: 真であれば NoSucceededRule のログに規則スタックの先頭の規則の説明を付記する
+    pub include_rule_docs: bool,
+    // note: 文字クラスの正規表現のコンパイル後のサイズと DFA のキャッシュサイズの上限 (バイト)
+    pub regex_size_limit: usize,
+    pub regex_dfa_size_limit: usize,
+    // note: INTO で参照する変換
+    pub primitive_registry: PrimitiveRegistry,
+    // note: 解析間で共有するメモ化結果; None であれば解析ごとに破棄する
+    pub shared_memoization: Option<SharedMemoization>,
+    pub context: ParseContext,
+    // note: 真であれば規則ごとのメモ化の統計情報 (RuleMemoStats) を収集する
+    pub collect_rule_memo_stats: bool,
+    // note: 真であれば文字クラス・文字列の表現字句の繰り返しで連続するリーフを 1 つのリーフにまとめる
+    pub coalesce_repeated_leaves: bool,
+    // note: 真であれば残りの入力が最小一致長に満たないグループを検査せずに失敗とする; 解析の成否と構文木は変わらない
+    pub skip_short_groups: bool,
+    // note: 開始規則からの解析に用いる解析器; 結果とログはいずれも同じとなる
+    pub engine: ParseEngine,
+    // note: 真であれば規則グループごとの網羅状況 (CoverageRecord) を記録する
+    pub collect_coverage: bool,
+    // note: 出力したログを入力のパスとともに記録する; None であればコンソールにのみ出力する
+    pub log_recorder: Option<LogRecorder>,
+}
 
-        if max_count != -1 && min_count as isize > max_count {
-            self.cons.borrow_mut().append_log(SyntaxParsingLog::InvalidLoopRange {
-                msg: format!("invalid loop range {{{},{}}} was detected", min_count, max_count),
-            }.get_log());
+impl ParseConfig {
+    pub fn with_memoization(mut self, enable_memoization: bool) -> ParseConfig {
+        self.enable_memoization = enable_memoization;
+        return self;
+    }
 
-            return Err(());
-        }
+    pub fn with_loop_limit(mut self, limit: usize) -> ParseConfig {
+        self.loop_limit = limit;
+        return self;
+    }
 
-        let mut children = Vec::<SyntaxNodeElement>::new();
-        let mut loop_count = 0isize;
+    pub fn with_max_depth(mut self, max_depth: usize) -> ParseConfig {
+        self.max_depth = max_depth;
+        return self;
+    }
 
-        while self.src_i < self.src_content.chars().count() {
-            if loop_count > self.loop_limit as isize {
-                self.cons.borrow_mut().append_log(SyntaxParsingLog::TooLongRepetition {
-                    loop_limit: self.loop_limit as usize,
-                }.get_log());
+    pub fn with_qualified_rule_names(mut self, qualify_rule_names: bool) -> ParseConfig {
+        self.qualify_rule_names = qualify_rule_names;
+        return self;
+    }
 
-                return Err(());
-            }
+    pub fn with_start_rule_id(mut self, start_rule_id: String) -> ParseConfig {
+        self.start_rule_id = Some(start_rule_id);
+        return self;
+    }
 
-            match self.parse_element_order_group(parent_elem_order, group)? {
-                Some(node_elems) => {
-                    for each_elem in node_elems {
-                        match &each_elem {
-                            SyntaxNodeElement::Node(node) => {
-                                if node.sub_elems.len() != 0 {
-                                    children.push(each_elem);
-                                }
-                            },
-                            _ => children.push(each_elem),
-                        }
-                    }
+    pub fn with_rule_docs(mut self, include_rule_docs: bool) -> ParseConfig {
+        self.include_rule_docs = include_rule_docs;
+        return self;
+    }
 
-                    loop_count += 1;
+    pub fn with_regex_size_limits(mut self, size_limit: usize, dfa_size_limit: usize) -> ParseConfig {
+        self.regex_size_limit = size_limit;
+        self.regex_dfa_size_limit = dfa_size_limit;
+        return self;
+    }
 
-                    if max_count != -1 && loop_count == max_count {
-                        return Ok(Some(children));
-                    }
-                },
-                None => {
-                    if loop_count >= min_count as isize && (max_count == -1 || loop_count <= max_count) {
-                        return Ok(Some(children));
-                    } else {
-                        return Ok(None);
-                    }
-                },
-            }
-        }
+    pub fn with_primitive_registry(mut self, primitive_registry: PrimitiveRegistry) -> ParseConfig {
+        self.primitive_registry = primitive_registry;
+        return self;
+    }
 
-        if loop_count >= min_count as isize && (max_count == -1 || loop_count <= max_count) {
-            return Ok(Some(children));
-        } else {
-            return Ok(None);
-        }
+    pub fn with_shared_memoization(mut self, shared_memoization: SharedMemoization) -> ParseConfig {
+        self.shared_memoization = Some(shared_memoization);
+        return self;
     }
 
-    fn parse_element_order_group(&mut self, parent_elem_order: &RuleElementOrder, group: &Box<RuleGroup>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
-        let mut children = Vec::<SyntaxNodeElement>::new();
+    pub fn with_context(mut self, context: ParseContext) -> ParseConfig {
+        self.context = context;
+        return self;
+    }
 
-        return match parent_elem_order {
-            RuleElementOrder::Random(random_order_loop_range) => {
-                let tar_elems = match group.sub_elems.get(0) {
-                    Some(tar_parent_elem) => {
-                        match tar_parent_elem {
-                            RuleElement::Group(tar_parent_group) => &tar_parent_group.sub_elems,
-                            _ => {
-                                self.cons.borrow_mut().append_log(SyntaxParsingLog::InvalidRuleElementStructure {
-                                    uuid: group.uuid.clone(),
-                                    msg: "child element of random order group must be a group".to_string(),
-                                }.get_log());
-
-                                return Err(());
-                            },
-                        }
-                    },
-                    None => {
-                        self.cons.borrow_mut().append_log(SyntaxParsingLog::InvalidRuleElementStructure {
-                            uuid: group.uuid.clone(),
-                            msg: "random order group must have a child group".to_string(),
-                        }.get_log());
+    pub fn with_rule_memo_stats(mut self, collect_rule_memo_stats: bool) -> ParseConfig {
+        self.collect_rule_memo_stats = collect_rule_memo_stats;
+        return self;
+    }
 
-                        return Err(());
-                    },
-                };
+    pub fn with_coalesced_leaves(mut self, coalesce_repeated_leaves: bool) -> ParseConfig {
+        self.coalesce_repeated_leaves = coalesce_repeated_leaves;
+        return self;
+    }
 
-                let random_order_start_src_i = self.src_i;
-                let mut is_each_subgroup_matched = vec![false; tar_elems.len()];
-                let mut subgroup_i = 0usize;
+    pub fn with_short_group_skip(mut self, skip_short_groups: bool) -> ParseConfig {
+        self.skip_short_groups = skip_short_groups;
+        return self;
+    }
 
-                for _ in 0..tar_elems.len() {
-                    let elem_start_src_i = self.src_i;
-                    for subelem in tar_elems {
-                        match subelem {
-                            RuleElement::Group(subgroup) => {
-                                let mut conved_subgroup = subgroup.clone();
-                                conved_subgroup.loop_range = random_order_loop_range.clone();
+    pub fn with_engine(mut self, engine: ParseEngine) -> ParseConfig {
+        self.engine = engine;
+        return self;
+    }
 
-                                match self.parse_group(&RuleElementOrder::Sequential, &conved_subgroup)? {
-                                    Some(node_elems) => {
+    pub fn with_coverage(mut self, collect_coverage: bool) -> ParseConfig {
+        self.collect_coverage = collect_coverage;
+        return self;
+    }
+
+    pub fn with_log_recorder(mut self, log_recorder: LogRecorder) -> ParseConfig {
+        self.log_recorder = Some(log_recorder);
+        return self;
+    }
+}
+
+impl Default for ParseConfig {
+    fn default() -> ParseConfig {
+        return ParseConfig {
+            enable_memoization: true,
+            loop_limit: DEFAULT_LOOP_LIMIT,
+            max_depth: DEFAULT_MAX_DEPTH,
+            qualify_rule_names: false,
+            start_rule_id: None,
+            include_rule_docs: false,
+            regex_size_limit: DEFAULT_REGEX_SIZE_LIMIT,
+            regex_dfa_size_limit: DEFAULT_REGEX_DFA_SIZE_LIMIT,
+            primitive_registry: PrimitiveRegistry::new(),
+            shared_memoization: None,
+            context: ParseContext::new(),
+            collect_rule_memo_stats: false,
+            coalesce_repeated_leaves: false,
+            skip_short_groups: true,
+            engine: ParseEngine::default(),
+            collect_coverage: false,
+            log_recorder: None,
+        };
+    }
+}
+
+// spec: 開始規則からの解析に用いる解析器
+// note: Iterative は規則の入れ子をヒープ上のフレームで辿るため、入れ子の深さがスレッドのスタックサイズに制限されない
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParseEngine {
+    Recursive,
+    Iterative,
+}
+
+impl Default for ParseEngine {
+    fn default() -> ParseEngine {
+        return ParseEngine::Recursive;
+    }
+}
+
+// spec: SyntaxParser::parse_bytes() に渡すバイト列の文字コード
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    // note: 文字コードとして変換せず、バイト列モードで解析する
+    Binary,
+    // note: 各バイトを U+0000 ~ U+00FF の 1 文字とする; 不正なバイト列はない
+    Latin1,
+    Utf8,
+}
+
+impl Encoding {
+    // ret: 変換後の文字列; 変換できなければ不正なバイト列の先頭までを変換した文字列
+    // note: Binary はバイト列モードの内部表現と同じく Latin1 として変換する
+    pub fn decode(&self, src_bytes: &[u8]) -> std::result::Result<String, String> {
+        return match self {
+            Encoding::Binary | Encoding::Latin1 => Ok(src_bytes.iter().map(|each_byte| *each_byte as char).collect::<String>()),
+            Encoding::Utf8 => match std::str::from_utf8(src_bytes) {
+                Ok(v) => Ok(v.to_string()),
+                Err(e) => Err(String::from_utf8_lossy(&src_bytes[..e.valid_up_to()]).to_string()),
+            },
+        };
+    }
+}
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let s = match self {
+            Encoding::Binary => "binary",
+            Encoding::Latin1 => "Latin-1",
+            Encoding::Utf8 => "UTF-8",
+        };
+
+        return write!(f, "{}", s);
+    }
+}
+
+// spec: 解析中の統計情報; 解析の成否に関わらず取得できる
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ParseStats {
+    pub expr_eval_count: usize,
+    pub rule_invocation_count: usize,
+    // note: メモ化の対象となるグループがなければいずれも 0
+    pub memo_hit_count: usize,
+    pub memo_miss_count: usize,
+    pub peak_rule_depth: usize,
+    // note: EOF 用のヌル文字を含まない
+    pub consumed_char_count: usize,
+}
+
+impl Display for ParseStats {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        return write!(f, "exprs={} rules={} memo_hits={} memo_misses={} peak_rule_depth={} consumed_chars={}", self.expr_eval_count, self.rule_invocation_count, self.memo_hit_count, self.memo_miss_count, self.peak_rule_depth, self.consumed_char_count);
+    }
+}
+
+// spec: 規則ごとのメモ化の統計情報; グループを含む規則 (規則スタックの先頭) ごとに数える
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RuleMemoStats {
+    pub hit_count: usize,
+    pub miss_count: usize,
+    // note: メモ化の表に追加した結果の数; 入力を消費しなかった結果は追加しない
+    pub entry_count: usize,
+}
+
+pub struct ParseOutput {
+    pub tree: SyntaxTree,
+    pub stats: ParseStats,
+}
+
+// spec: 複数の入力に対して同じ文法で解析を繰り返すためのセッション; 正規表現キャッシュを解析間で保持する
+pub struct ParserSession {
+    cons: Rc<RefCell<Console>>,
+    rule_map: Arc<Box<RuleMap>>,
+    regex_map: Box<HashMap<String, Regex>>,
+    // note: メモ化結果は入力に依存するため解析ごとに空にする; 領域のみ再利用する
+    memoized_map: Box<MemoizationMap>,
+    config: ParseConfig,
+    // note: 網羅状況の記録が有効であれば解析間で累積する
+    coverage: CoverageRecord,
+}
+
+impl ParserSession {
+    pub fn new(cons: Rc<RefCell<Console>>, rule_map: Arc<Box<RuleMap>>) -> ParserSession {
+        return ParserSession {
+            cons: cons,
+            rule_map: rule_map,
+            regex_map: Box::new(HashMap::new()),
+            memoized_map: Box::new(MemoizationMap::new()),
+            config: ParseConfig::default(),
+            coverage: CoverageRecord::default(),
+        };
+    }
+
+    pub fn with_config(mut self, config: ParseConfig) -> ParserSession {
+        self.config = config;
+        return self;
+    }
+
+    pub fn get_rule_map(&self) -> &Arc<Box<RuleMap>> {
+        return &self.rule_map;
+    }
+
+    pub fn parse(&mut self, src_path: String, src_content: Box<String>) -> ConsoleResult<SyntaxTree> {
+        // note: 以前の入力や文法のメモ化結果が残っていると誤った結果を返すため
+        debug_assert!(self.memoized_map.is_empty(), "memoization map must be empty before parsing");
+
+        let mut parser = SyntaxParser::new(self.cons.clone(), self.rule_map.clone(), src_path, src_content, self.config.enable_memoization).with_config(&self.config);
+        std::mem::swap(&mut parser.regex_map, &mut self.regex_map);
+        std::mem::swap(&mut parser.memoized_map, &mut self.memoized_map);
+
+        if parser.coverage.is_some() {
+            parser.coverage = Some(std::mem::take(&mut self.coverage));
+        }
+
+        let result = parser.run();
+
+        std::mem::swap(&mut parser.regex_map, &mut self.regex_map);
+        std::mem::swap(&mut parser.memoized_map, &mut self.memoized_map);
+        self.memoized_map.clear();
+
+        match parser.coverage.take() {
+            Some(v) => self.coverage = v,
+            None => (),
+        }
+
+        return result;
+    }
+
+    // ret: これまでの解析で累積した網羅状況; 取り出した網羅状況は破棄する
+    // note: 網羅状況の記録が有効でなければ、すべての規則とグループが検査されていないものとなる
+    pub fn take_coverage(&mut self) -> GrammarCoverage {
+        let coverage = std::mem::take(&mut self.coverage);
+        return GrammarCoverage::new(&self.rule_map, &coverage);
+    }
+
+    // ret: 正規表現をキャッシュしている文字クラスのパターン (名前順)
+    pub fn get_cached_char_class_patterns(&self) -> Vec<&String> {
+        let mut patterns = self.regex_map.keys().collect::<Vec<&String>>();
+        patterns.sort();
+        return patterns;
+    }
+
+    // spec: 新しい文法に存在しない文字クラスの正規表現のみをキャッシュから破棄する
+    pub fn reload_grammar(&mut self, new_rule_map: Arc<Box<RuleMap>>) {
+        let char_class_patterns = new_rule_map.get_char_class_patterns();
+        self.regex_map.retain(|each_pattern, _| char_class_patterns.contains(each_pattern));
+        // note: 規則グループの UUID は読み込みごとに異なるため古い結果は再利用できない
+        self.memoized_map.clear();
+        // note: グループの番号も規則マップごとに異なる
+        self.coverage = CoverageRecord::default();
+        self.rule_map = new_rule_map;
+    }
+}
+
+// spec: 繰り返しの 1 回分の検査結果
+enum LoopIteration {
+    // note: 区切りと繰り返しの要素の結果
+    Matched(Vec<SyntaxNodeElement>),
+    // note: 末尾の区切りの結果; 繰り返しはここで終わる
+    TrailingSeparator(Vec<SyntaxNodeElement>),
+    Unmatched,
+}
+
+// spec: グループの検査を始める前の処理の結果
+enum GroupStart {
+    // note: 最小一致長もしくはメモ化された結果により検査せずに定まった結果
+    Finished(Option<Vec<SyntaxNodeElement>>),
+    Started(GroupState),
+}
+
+// spec: SyntaxParser::end_group() に渡すグループの検査開始時の状態
+struct GroupState {
+    is_memoizable: bool,
+    start_src_i: usize,
+    capture_lens: (usize, usize),
+}
+
+// spec: 繰り返しの 1 回分の検査中の状態; 区切りの検査の前に保存する
+struct LoopIterationState {
+    separator_snapshot: ParserCheckpoint,
+    separator_capture_lens: (usize, usize),
+    separator_elems: Vec<SyntaxNodeElement>,
+    is_separator_matched: bool,
+    // note: 区切りの検査後、繰り返しの要素の検査前の状態
+    item_snapshot: ParserCheckpoint,
+    item_capture_lens: (usize, usize),
+}
+
+// note: (選択肢, 結果, 終了時点の状態, 選択肢内での捕捉, 選択肢内で記録された文脈変数)
+type LongestMatch<'a> = (&'a Box<RuleGroup>, Vec<SyntaxNodeElement>, ParserCheckpoint, Vec<(String, String)>, Vec<(String, String)>);
+
+// spec: 反復型の解析器 (ParseEngine::Iterative) のフレーム; 再帰型の解析器の parse_* の呼び出し 1 回分に対応する
+// note: フレームは Vec に直接格納し、フレームごとにヒープを確保しない
+enum ParseFrame<'a> {
+    Rule(RuleFrame<'a>),
+    Group(GroupFrame<'a>),
+    LoopIteration(LoopIterationFrame<'a>),
+    RandomOrder(RandomOrderFrame<'a>),
+    RawGroup(RawGroupFrame<'a>),
+    SequenceElem(SequenceElemFrame<'a>),
+    Choice(ChoiceFrame<'a>),
+    Expr(ExprFrame<'a>),
+    NestedExpr(NestedExprFrame<'a>),
+}
+
+// spec: フレームを 1 段階進めた結果
+enum FrameStep<'a> {
+    // note: 子フレームを積み、その戻り値を受け取って再開する
+    Call(ParseFrame<'a>),
+    Return(FrameOutput<'a>),
+}
+
+// spec: フレームの戻り値; 対応する再帰型の関数の戻り値と同じ
+enum FrameOutput<'a> {
+    Node(Option<SyntaxNodeElement>),
+    Elems(Option<Vec<SyntaxNodeElement>>),
+    Iteration(LoopIteration),
+    Choice(Option<(&'a Box<RuleGroup>, Vec<SyntaxNodeElement>)>),
+}
+
+// note: 子フレームの種類から戻り値の種類が定まるため、一致しなければ解析器の不具合となる
+impl<'a> FrameOutput<'a> {
+    fn into_node(self) -> Option<SyntaxNodeElement> {
+        return match self {
+            FrameOutput::Node(v) => v,
+            _ => panic!("unexpected frame output"),
+        };
+    }
+
+    fn into_elems(self) -> Option<Vec<SyntaxNodeElement>> {
+        return match self {
+            FrameOutput::Elems(v) => v,
+            _ => panic!("unexpected frame output"),
+        };
+    }
+
+    fn into_iteration(self) -> LoopIteration {
+        return match self {
+            FrameOutput::Iteration(v) => v,
+            _ => panic!("unexpected frame output"),
+        };
+    }
+
+    fn into_choice(self) -> Option<(&'a Box<RuleGroup>, Vec<SyntaxNodeElement>)> {
+        return match self {
+            FrameOutput::Choice(v) => v,
+            _ => panic!("unexpected frame output"),
+        };
+    }
+}
+
+// spec: 反復型の解析器で呼び出し中の規則に渡された総称引数; 再帰型の解析器の arg_maps に対応する
+// note: 引数 ID の解決では arg_maps と同じく外側の呼び出しの引数から順に探す
+struct ArgumentStack<'a> {
+    // note: (引数 ID, 引数グループ)
+    generics_args: Vec<(&'a String, &'a Box<RuleGroup>)>,
+    // note: 引数付きで呼び出し中の規則の数; メモ化の可否に用いる
+    map_len: usize,
+}
+
+// spec: parse_rule() と parse_raw_rule()
+struct RuleFrame<'a> {
+    rule_id: &'a String,
+    pos: &'a CharacterPosition,
+    context_len: usize,
+    state: RuleFrameState<'a>,
+}
+
+enum RuleFrameState<'a> {
+    Start,
+    // note: 別名の参照先の規則を検査中
+    Alias {
+        alias_rule_id: &'a String,
+    },
+    Group {
+        rule_group: &'a Box<RuleGroup>,
+        start_src_i: usize,
+    },
+}
+
+// spec: parse_group(), parse_lookahead_group() と parse_loop_group()
+// note: 順不同グループの子要素は複製せず、loop_range で繰り返し範囲を置き換える
+struct GroupFrame<'a> {
+    parent_elem_order: &'a RuleElementOrder,
+    group: &'a Box<RuleGroup>,
+    loop_range: &'a RuleElementLoopRange,
+    // note: 検査を始めるまでは None
+    state: Option<GroupState>,
+    lookahead_snapshot: ParserCheckpoint,
+    loop_counts: (usize, isize),
+    children: Vec<SyntaxNodeElement>,
+    loop_count: isize,
+}
+
+// spec: parse_loop_group_iteration()
+struct LoopIterationFrame<'a> {
+    parent_elem_order: &'a RuleElementOrder,
+    group: &'a Box<RuleGroup>,
+    loop_count: isize,
+    state: Option<LoopIterationState>,
+    is_item_started: bool,
+}
+
+// spec: parse_element_order_group() の順不同グループ
+// note: 一致済みの選択肢が再び一致した場合に入力位置を戻さない点も含めて再帰型の解析器と同じ手順で検査する
+struct RandomOrderFrame<'a> {
+    tar_elems: &'a Vec<RuleElement>,
+    loop_range: &'a RuleElementLoopRange,
+    random_order_snapshot: ParserCheckpoint,
+    elem_snapshot: ParserCheckpoint,
+    is_each_subgroup_matched: Vec<bool>,
+    children: Vec<SyntaxNodeElement>,
+    round_i: usize,
+    subgroup_i: usize,
+}
+
+// spec: parse_raw_group()
+struct RawGroupFrame<'a> {
+    group: &'a Box<RuleGroup>,
+    elem_i: usize,
+    elem_src_i: usize,
+    children: Vec<SyntaxNodeElement>,
+}
+
+// spec: parse_sequence_elem()
+struct SequenceElemFrame<'a> {
+    elem: &'a RuleElement,
+    is_single_elem: bool,
+    snapshot: ParserCheckpoint,
+}
+
+// spec: parse_ordered_choice() と parse_longest_match_choice()
+struct ChoiceFrame<'a> {
+    choice: &'a Box<RuleGroup>,
+    snapshot: ParserCheckpoint,
+    capture_lens: (usize, usize),
+    sub_elem_i: usize,
+    // note: 検査中の選択肢
+    sub_group: Option<&'a Box<RuleGroup>>,
+    longest_match: Option<LongestMatch<'a>>,
+}
+
+// spec: parse_expr(), parse_lookahead_expr() と parse_loop_expr()
+// note: 子フレームを要しない表現字句は parse_raw_expr() でその場で検査する
+struct ExprFrame<'a> {
+    expr: &'a Box<RuleExpression>,
+    start_src_i: usize,
+    capture_lens: (usize, usize),
+    lookahead_snapshot: ParserCheckpoint,
+    loop_counts: (usize, isize),
+    children: Vec<SyntaxNodeElement>,
+    loop_count: usize,
+    is_coalescible: bool,
+}
+
+// spec: parse_raw_expr() のうち規則もしくはグループを検査する表現字句
+struct NestedExprFrame<'a> {
+    expr: &'a Box<RuleExpression>,
+    state: NestedExprState,
+}
+
+enum NestedExprState {
+    Start,
+    ArgId,
+    Id,
+    Capture {
+        name: String,
+        start_pos: CharacterPosition,
+    },
+    Into {
+        transform: PrimitiveTransform,
+        start_pos: CharacterPosition,
+    },
+    Join {
+        start_pos: CharacterPosition,
+    },
+    // note: 引数付きの規則の呼び出し; 呼び出し前の総称引数の数
+    RuleCall {
+        generics_arg_len: usize,
+        has_args: bool,
+    },
+}
+
+impl<'a> ArgumentStack<'a> {
+    fn new() -> ArgumentStack<'a> {
+        return ArgumentStack {
+            generics_args: Vec::new(),
+            map_len: 0,
+        };
+    }
+}
+
+impl<'a> RuleFrame<'a> {
+    fn new(rule_id: &'a String, pos: &'a CharacterPosition) -> RuleFrame<'a> {
+        return RuleFrame {
+            rule_id: rule_id,
+            pos: pos,
+            context_len: 0,
+            state: RuleFrameState::Start,
+        };
+    }
+}
+
+impl<'a> GroupFrame<'a> {
+    fn new(parent_elem_order: &'a RuleElementOrder, group: &'a Box<RuleGroup>, loop_range: &'a RuleElementLoopRange) -> GroupFrame<'a> {
+        return GroupFrame {
+            parent_elem_order: parent_elem_order,
+            group: group,
+            loop_range: loop_range,
+            state: None,
+            lookahead_snapshot: ParserCheckpoint::default(),
+            loop_counts: (0, -1),
+            children: Vec::new(),
+            loop_count: 0,
+        };
+    }
+}
+
+impl<'a> LoopIterationFrame<'a> {
+    fn new(parent_elem_order: &'a RuleElementOrder, group: &'a Box<RuleGroup>, loop_count: isize) -> LoopIterationFrame<'a> {
+        return LoopIterationFrame {
+            parent_elem_order: parent_elem_order,
+            group: group,
+            loop_count: loop_count,
+            state: None,
+            is_item_started: false,
+        };
+    }
+}
+
+impl<'a> RandomOrderFrame<'a> {
+    fn new(tar_elems: &'a Vec<RuleElement>, loop_range: &'a RuleElementLoopRange, random_order_snapshot: ParserCheckpoint) -> RandomOrderFrame<'a> {
+        return RandomOrderFrame {
+            tar_elems: tar_elems,
+            loop_range: loop_range,
+            random_order_snapshot: random_order_snapshot,
+            elem_snapshot: random_order_snapshot,
+            is_each_subgroup_matched: vec![false; tar_elems.len()],
+            children: Vec::new(),
+            round_i: 0,
+            subgroup_i: 0,
+        };
+    }
+}
+
+impl<'a> RawGroupFrame<'a> {
+    fn new(group: &'a Box<RuleGroup>) -> RawGroupFrame<'a> {
+        return RawGroupFrame {
+            group: group,
+            elem_i: 0,
+            elem_src_i: 0,
+            children: Vec::new(),
+        };
+    }
+}
+
+impl<'a> SequenceElemFrame<'a> {
+    fn new(elem: &'a RuleElement, is_single_elem: bool, snapshot: ParserCheckpoint) -> SequenceElemFrame<'a> {
+        return SequenceElemFrame {
+            elem: elem,
+            is_single_elem: is_single_elem,
+            snapshot: snapshot,
+        };
+    }
+}
+
+impl<'a> ChoiceFrame<'a> {
+    fn new(choice: &'a Box<RuleGroup>, snapshot: ParserCheckpoint, capture_lens: (usize, usize)) -> ChoiceFrame<'a> {
+        return ChoiceFrame {
+            choice: choice,
+            snapshot: snapshot,
+            capture_lens: capture_lens,
+            sub_elem_i: 0,
+            sub_group: None,
+            longest_match: None,
+        };
+    }
+}
+
+impl<'a> ExprFrame<'a> {
+    fn new(expr: &'a Box<RuleExpression>) -> ExprFrame<'a> {
+        return ExprFrame {
+            expr: expr,
+            start_src_i: 0,
+            capture_lens: (0, 0),
+            lookahead_snapshot: ParserCheckpoint::default(),
+            loop_counts: (0, -1),
+            children: Vec::new(),
+            loop_count: 0,
+            is_coalescible: false,
+        };
+    }
+}
+
+impl<'a> NestedExprFrame<'a> {
+    fn new(expr: &'a Box<RuleExpression>) -> NestedExprFrame<'a> {
+        return NestedExprFrame {
+            expr: expr,
+            state: NestedExprState::Start,
+        };
+    }
+}
+
+// spec: 後戻りのために SyntaxParser::checkpoint() で保存し、restore() で戻す構文解析器の状態; SCAN の走査からも用いる
+// note: 行と列は line_start_indexes と src_i から求めるため入力位置のみを保存すればよい
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ParserCheckpoint {
+    src_i: usize,
+    rule_stack_len: usize,
+}
+
+pub struct SyntaxParser {
+    cons: Rc<RefCell<Console>>,
+    rule_map: Arc<Box<RuleMap>>,
+    src_i: usize,
+    // note: 各行の先頭の文字インデックス; 位置は後戻りに影響されないよう src_i から求める
+    line_start_indexes: Vec<usize>,
+    src_path: String,
+    src_content: Box<String>,
+    // note: EOF 用のヌル文字を含む入力の文字数
+    src_len: usize,
+    // note: 入力が ASCII のみであれば文字インデックスとバイトインデックスが一致するため部分文字列を直接切り出せる
+    is_ascii_src: bool,
+    loop_limit: usize,
+    max_depth: usize,
+    qualify_rule_names: bool,
+    start_rule_id: Option<String>,
+    include_rule_docs: bool,
+    regex_size_limit: usize,
+    regex_dfa_size_limit: usize,
+    primitive_registry: PrimitiveRegistry,
+    shared_memoization: Option<SharedMemoization>,
+    log_recorder: Option<LogRecorder>,
+    context: ParseContext,
+    arg_maps: Box<Vec<ArgumentMap>>,
+    rule_stack: Box<Vec<(CharacterPosition, String)>>,
+    regex_map: Box<HashMap<String, Regex>>,
+    memoized_map: Box<MemoizationMap>,
+    enable_memoization: bool,
+    collect_rule_memo_stats: bool,
+    rule_memo_stats: HashMap<String, RuleMemoStats>,
+    coalesce_repeated_leaves: bool,
+    skip_short_groups: bool,
+    engine: ParseEngine,
+    // note: 記録が有効でなければ None
+    coverage: Option<CoverageRecord>,
+    capture_map: CaptureMap,
+    // note: CAPTURE で記録された文脈変数; 規則の呼び出しごとにスコープを持つ
+    context_map: CaptureMap,
+    // note: バイト列モードでは入力の各バイトを U+0000 ~ U+00FF の 1 文字として src_content に保持する
+    is_byte_mode: bool,
+    byte_regex_map: Box<HashMap<String, bytes::Regex>>,
+    // note: 解析中に出力したログ; コンソールへの出力とは別に保持する
+    logs: Vec<SyntaxParsingLog>,
+    // note: 分岐を失敗させて続行したエラーがあれば解析全体を失敗とする
+    has_branch_error: bool,
+    branch_error_keys: HashSet<String>,
+    // note: 非推奨の警告を出力済みの規則 ID (別名を含む); 解析ごとに 1 度のみ警告する
+    deprecated_rule_ids: HashSet<String>,
+    // note: 失敗時に rule_stack は戻されないため規則の深さは別に数える
+    rule_depth: usize,
+    // note: 入力をすべて消費した位置で最初に失敗した要素を含む規則; 解析の失敗時に UnexpectedEOF とするかの判定に用いる
+    eof_rule_id: Option<String>,
+    // note: 先読みの外で表現字句が失敗した最も先の入力位置と、その位置で失敗した (表現字句, 表現字句を含む規則 ID)
+    expected_src_i: usize,
+    expected_exprs: Vec<(Box<RuleExpression>, String)>,
+    // note: 最も先の失敗位置で最初に失敗した時点の規則スタック; 後戻りで規則スタックは戻されるため失敗時のログ用に保持する
+    expected_rule_stack: Vec<(CharacterPosition, String)>,
+    // note: 先読みの中で失敗した表現字句は期待された終端記号として記録しない
+    lookahead_depth: usize,
+    partial_tree: Option<SyntaxTree>,
+    // note: 開始規則の直下で要素が失敗した最も先の入力位置と、その位置までに一致した子要素; 開始規則が一致しなかった場合の partial_tree に用いる
+    partial_src_i: usize,
+    partial_elems: Vec<SyntaxNodeElement>,
+    stats: ParseStats,
+}
+
+impl SyntaxParser {
+    pub fn new(cons: Rc<RefCell<Console>>, rule_map: Arc<Box<RuleMap>>, src_path: String, mut src_content: Box<String>, enable_memoization: bool) -> SyntaxParser {
+        // note: 余分な改行コード 0x0d を排除する
+        loop {
+            match src_content.find(0x0d as char) {
+                Some(v) => {
+                    let _ = src_content.remove(v);
+                },
+                None => break,
+            }
+        }
+
+        return SyntaxParser::from_source(cons, rule_map, src_path, src_content, enable_memoization, false);
+    }
+
+    // spec: 入力をバイト列として解析する; 位置はバイト単位で数え、0x0d は取り除かない
+    pub fn new_bytes(cons: Rc<RefCell<Console>>, rule_map: Arc<Box<RuleMap>>, src_path: String, src_bytes: Vec<u8>, enable_memoization: bool) -> SyntaxParser {
+        let src_content = src_bytes.into_iter().map(|each_byte| each_byte as char).collect::<String>();
+        return SyntaxParser::from_source(cons, rule_map, src_path, Box::new(src_content), enable_memoization, true);
+    }
+
+    fn from_source(cons: Rc<RefCell<Console>>, rule_map: Arc<Box<RuleMap>>, src_path: String, src_content: Box<String>, enable_memoization: bool, is_byte_mode: bool) -> SyntaxParser {
+        // note: メモ化された結果を再利用すると捕捉が記録されず後方参照の結果も変わるため
+        let enable_memoization = enable_memoization && !rule_map.has_capture_refs;
+
+        let mut parser = SyntaxParser {
+            cons: cons,
+            rule_map: rule_map,
+            src_i: 0,
+            line_start_indexes: Vec::new(),
+            src_path: src_path,
+            src_content: src_content,
+            src_len: 0,
+            is_ascii_src: false,
+            loop_limit: DEFAULT_LOOP_LIMIT,
+            max_depth: DEFAULT_MAX_DEPTH,
+            qualify_rule_names: false,
+            start_rule_id: None,
+            include_rule_docs: false,
+            regex_size_limit: DEFAULT_REGEX_SIZE_LIMIT,
+            regex_dfa_size_limit: DEFAULT_REGEX_DFA_SIZE_LIMIT,
+            primitive_registry: PrimitiveRegistry::new(),
+            shared_memoization: None,
+            log_recorder: None,
+            context: ParseContext::new(),
+            arg_maps: Box::new(Vec::new()),
+            rule_stack: Box::new(Vec::new()),
+            regex_map: Box::new(HashMap::new()),
+            memoized_map: Box::new(MemoizationMap::new()),
+            enable_memoization: enable_memoization,
+            collect_rule_memo_stats: false,
+            rule_memo_stats: HashMap::new(),
+            coalesce_repeated_leaves: false,
+            skip_short_groups: true,
+            engine: ParseEngine::default(),
+            coverage: None,
+            capture_map: CaptureMap::new(),
+            context_map: CaptureMap::new(),
+            is_byte_mode: is_byte_mode,
+            byte_regex_map: Box::new(HashMap::new()),
+            logs: Vec::new(),
+            has_branch_error: false,
+            branch_error_keys: HashSet::new(),
+            deprecated_rule_ids: HashSet::new(),
+            rule_depth: 0,
+            eof_rule_id: None,
+            expected_src_i: 0,
+            expected_exprs: Vec::new(),
+            expected_rule_stack: Vec::new(),
+            lookahead_depth: 0,
+            partial_tree: None,
+            partial_src_i: 0,
+            partial_elems: Vec::new(),
+            stats: ParseStats::default(),
+        };
+
+        // EOF 用のヌル文字
+        *parser.src_content += "\0";
+        parser.src_len = parser.src_content.chars().count();
+        parser.is_ascii_src = parser.src_content.is_ascii();
+        parser.line_start_indexes = SyntaxParser::get_line_start_indexes(&parser.src_content);
+
+        return parser;
+    }
+
+    pub fn with_loop_limit(mut self, limit: usize) -> SyntaxParser {
+        self.loop_limit = limit;
+        return self;
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> SyntaxParser {
+        self.max_depth = max_depth;
+        return self;
+    }
+
+    pub fn with_qualified_rule_names(mut self, qualify_rule_names: bool) -> SyntaxParser {
+        self.qualify_rule_names = qualify_rule_names;
+        return self;
+    }
+
+    // spec: 規則マップの開始規則の代わりに指定された規則 ID から解析する
+    pub fn with_start_rule_id(mut self, start_rule_id: String) -> SyntaxParser {
+        self.start_rule_id = Some(start_rule_id);
+        return self;
+    }
+
+    pub fn with_rule_docs(mut self, include_rule_docs: bool) -> SyntaxParser {
+        self.include_rule_docs = include_rule_docs;
+        return self;
+    }
+
+    pub fn with_regex_size_limits(mut self, size_limit: usize, dfa_size_limit: usize) -> SyntaxParser {
+        self.regex_size_limit = size_limit;
+        self.regex_dfa_size_limit = dfa_size_limit;
+        return self;
+    }
+
+    pub fn with_primitive_registry(mut self, primitive_registry: PrimitiveRegistry) -> SyntaxParser {
+        self.primitive_registry = primitive_registry;
+        return self;
+    }
+
+    pub fn with_shared_memoization(mut self, shared_memoization: SharedMemoization) -> SyntaxParser {
+        self.shared_memoization = Some(shared_memoization);
+        return self;
+    }
+
+    pub fn with_log_recorder(mut self, log_recorder: LogRecorder) -> SyntaxParser {
+        self.log_recorder = Some(log_recorder);
+        return self;
+    }
+
+    pub fn with_context(mut self, context: ParseContext) -> SyntaxParser {
+        self.context = context;
+        return self;
+    }
+
+    pub fn with_rule_memo_stats(mut self, collect_rule_memo_stats: bool) -> SyntaxParser {
+        self.collect_rule_memo_stats = collect_rule_memo_stats;
+        return self;
+    }
+
+    pub fn with_coalesced_leaves(mut self, coalesce_repeated_leaves: bool) -> SyntaxParser {
+        self.coalesce_repeated_leaves = coalesce_repeated_leaves;
+        return self;
+    }
+
+    pub fn with_short_group_skip(mut self, skip_short_groups: bool) -> SyntaxParser {
+        self.skip_short_groups = skip_short_groups;
+        return self;
+    }
+
+    pub fn with_engine(mut self, engine: ParseEngine) -> SyntaxParser {
+        self.engine = engine;
+        return self;
+    }
+
+    // spec: 後方参照を含む文法では指定によらずメモ化しない
+    pub fn with_memoization(mut self, enable_memoization: bool) -> SyntaxParser {
+        self.enable_memoization = enable_memoization && !self.rule_map.has_capture_refs;
+        return self;
+    }
+
+    pub fn with_coverage(mut self, collect_coverage: bool) -> SyntaxParser {
+        self.coverage = if collect_coverage {
+            Some(CoverageRecord::new(self.rule_map.group_count))
+        } else {
+            None
+        };
+
+        return self;
+    }
+
+    pub fn with_config(self, config: &ParseConfig) -> SyntaxParser {
+        let parser = self.with_memoization(config.enable_memoization).with_loop_limit(config.loop_limit).with_max_depth(config.max_depth).with_qualified_rule_names(config.qualify_rule_names).with_rule_docs(config.include_rule_docs).with_regex_size_limits(config.regex_size_limit, config.regex_dfa_size_limit).with_primitive_registry(config.primitive_registry.clone()).with_context(config.context.clone()).with_rule_memo_stats(config.collect_rule_memo_stats).with_coalesced_leaves(config.coalesce_repeated_leaves).with_short_group_skip(config.skip_short_groups).with_engine(config.engine).with_coverage(config.collect_coverage);
+
+        let parser = match &config.start_rule_id {
+            Some(v) => parser.with_start_rule_id(v.clone()),
+            None => parser,
+        };
+
+        let parser = match &config.shared_memoization {
+            Some(v) => parser.with_shared_memoization(v.clone()),
+            None => parser,
+        };
+
+        return match &config.log_recorder {
+            Some(v) => parser.with_log_recorder(v.clone()),
+            None => parser,
+        };
+    }
+
+    // spec: 既定の設定で解析する場合は ParseConfig::default() を渡す
+    pub fn parse(cons: Rc<RefCell<Console>>, rule_map: Arc<Box<RuleMap>>, src_path: String, src_content: Box<String>, config: ParseConfig) -> ConsoleResult<SyntaxTree> {
+        let mut parser = SyntaxParser::new(cons, rule_map, src_path, src_content, config.enable_memoization).with_config(&config);
+        return parser.run();
+    }
+
+    // spec: entry は規則 ID で指定し、開始規則もしくは entry 命令で宣言された規則のみを受け付ける
+    pub fn parse_with_entry(cons: Rc<RefCell<Console>>, rule_map: Arc<Box<RuleMap>>, entry: &str, src_path: String, src_content: Box<String>, config: ParseConfig) -> ConsoleResult<SyntaxTree> {
+        let entry_rule_ids = rule_map.get_entry_rule_ids();
+
+        if !entry_rule_ids.iter().any(|each_rule_id| each_rule_id == entry) {
+            SyntaxParser::append_log_without_parser(&cons, &config, &src_path, SyntaxParsingLog::UndeclaredEntryRule {
+                rule_id: entry.to_string(),
+                entry_rule_ids: entry_rule_ids,
+            });
+
+            return Err(());
+        }
+
+        return SyntaxParser::parse(cons, rule_map, src_path, src_content, config.with_start_rule_id(entry.to_string()));
+    }
+
+    pub fn parse_with_stats(cons: Rc<RefCell<Console>>, rule_map: Arc<Box<RuleMap>>, src_path: String, src_content: Box<String>, config: ParseConfig) -> ConsoleResult<ParseOutput> {
+        let mut parser = SyntaxParser::new(cons, rule_map, src_path, src_content, config.enable_memoization).with_config(&config);
+        let tree = parser.run()?;
+
+        return Ok(ParseOutput {
+            tree: tree,
+            stats: parser.get_stats(),
+        });
+    }
+
+    // spec: リーフの値を入力の範囲として保持する構文木を返す; 解析後に SpanTree::from_tree() で変換する
+    pub fn parse_spans<'src>(cons: Rc<RefCell<Console>>, rule_map: Arc<Box<RuleMap>>, src_path: String, src: &'src str, config: ParseConfig) -> ConsoleResult<SpanTree<'src>> {
+        let tree = SyntaxParser::parse(cons, rule_map, src_path, Box::new(src.to_string()), config)?;
+        return Ok(SpanTree::from_tree(src, tree));
+    }
+
+    // spec: 入力を encoding に従って文字列に変換してから文字列の入力と同様に解析する; 変換できなければ InvalidInputEncoding を出力して失敗する
+    // spec: Encoding::Binary であればバイト列モードで解析し、リーフの値は UTF-8 として不正なバイトを置換した文字列となり、元のバイト列は SyntaxLeaf::raw_value に保持される
+    pub fn parse_bytes(cons: Rc<RefCell<Console>>, rule_map: Arc<Box<RuleMap>>, src_path: String, src_bytes: &[u8], encoding: Encoding, config: ParseConfig) -> ConsoleResult<SyntaxTree> {
+        let parser = match encoding {
+            Encoding::Binary => SyntaxParser::new_bytes(cons, rule_map, src_path, src_bytes.to_vec(), config.enable_memoization),
+            Encoding::Latin1 | Encoding::Utf8 => match encoding.decode(src_bytes) {
+                Ok(src_content) => SyntaxParser::new(cons, rule_map, src_path, Box::new(src_content), config.enable_memoization),
+                Err(decoded_src) => {
+                    let log = SyntaxParsingLog::InvalidInputEncoding {
+                        pos: SyntaxParser::get_end_position_of(src_path.clone(), &decoded_src),
+                        encoding: encoding,
+                    };
+
+                    SyntaxParser::append_log_without_parser(&cons, &config, &src_path, log);
+
+                    return Err(());
+                },
+            },
+        };
+
+        let mut parser = parser.with_config(&config);
+        return parser.run();
+    }
+
+    // ret: 文字列の入力として解析する場合の src の末尾の位置; 0x0d は取り除かれるため数えない
+    fn get_end_position_of(src_path: String, src: &str) -> CharacterPosition {
+        let chars = src.chars().filter(|each_char| *each_char != 0x0d as char).collect::<Vec<char>>();
+        let line = chars.iter().filter(|each_char| **each_char == '\n').count();
+
+        let column = match chars.iter().rposition(|each_char| *each_char == '\n') {
+            Some(v) => chars.len() - v - 1,
+            None => chars.len(),
+        };
+
+        return CharacterPosition::new(Some(src_path), chars.len(), line, column);
+    }
+
+    // spec: 入力全体を解析せず、開始規則のノードの子要素を完成した順に返すイテレータを生成する
+    // note: 開始規則の直下の繰り返しのみを 1 回ずつ返し、それ以外の要素は一致した時点でまとめて返す
+    pub fn parse_iter(cons: Rc<RefCell<Console>>, rule_map: Arc<Box<RuleMap>>, src_path: String, src_content: Box<String>, config: ParseConfig) -> SyntaxNodeIter {
+        let parser = SyntaxParser::new(cons, rule_map, src_path, src_content, config.enable_memoization).with_config(&config);
+        return SyntaxNodeIter::new(parser);
+    }
+
+    // ret: 解析を開始する規則の (規則 ID, 定義位置)
+    // spec: 入力を検査する前に開始規則を検証し、存在しなければ StartRuleNotFound, 要素を持たなければ InvalidRuleElementStructure を出力して失敗する
+    fn get_start_rule(&mut self) -> ConsoleResult<(String, CharacterPosition)> {
+        // note: 設定で指定された開始規則には宣言の位置がない
+        let (start_rule_id, decl_pos) = match &self.start_rule_id {
+            Some(v) => (v.clone(), CharacterPosition::get_empty()),
+            None => (self.rule_map.start_rule_id.clone(), self.rule_map.start_cmd_pos.clone()),
+        };
+
+        let (start_rule_pos, start_group_uuid, is_empty_rule) = match self.rule_map.rule_map.get(&start_rule_id) {
+            Some(rule) => (rule.pos.clone(), rule.group.uuid, rule.group.sub_elems.len() == 0),
+            None => {
+                self.append_log(SyntaxParsingLog::StartRuleNotFound {
+                    pos: decl_pos,
+                    start_rule_id: start_rule_id,
+                });
+
+                return Err(());
+            },
+        };
+
+        if is_empty_rule {
+            self.append_log(SyntaxParsingLog::InvalidRuleElementStructure {
+                uuid: start_group_uuid,
+                msg: format!("start rule '{}' has no elements", start_rule_id),
+            });
+
+            return Err(());
+        }
+
+        return Ok((start_rule_id, start_rule_pos));
+    }
+
+    pub fn run(&mut self) -> ConsoleResult<SyntaxTree> {
+        let shared_memoization = match &self.shared_memoization {
+            Some(v) if self.enable_memoization => v.clone(),
+            _ => return self.parse_input(),
+        };
+
+        let input_key = self.get_input_key();
+        let shared_map = shared_memoization.take_map(input_key);
+        let local_map = std::mem::replace(&mut self.memoized_map, Box::new(shared_map));
+
+        let result = self.parse_input();
+
+        let shared_map = std::mem::replace(&mut self.memoized_map, local_map);
+        shared_memoization.restore_map(input_key, *shared_map);
+        return result;
+    }
+
+    // ret: メモ化結果を共有できる解析を識別するハッシュ値
+    // note: 結果のノードやリーフに影響する入力・ファイルパス・規則マップ・設定を含める; 開始規則は含めない
+    fn get_input_key(&self) -> u64 {
+        let mut hasher = hash_map::DefaultHasher::new();
+        self.src_path.hash(&mut hasher);
+        self.src_content.hash(&mut hasher);
+        self.is_byte_mode.hash(&mut hasher);
+        self.qualify_rule_names.hash(&mut hasher);
+        self.coalesce_repeated_leaves.hash(&mut hasher);
+        (Arc::as_ptr(&self.rule_map) as usize).hash(&mut hasher);
+
+        for each_name in self.primitive_registry.get_names() {
+            each_name.hash(&mut hasher);
+
+            match self.primitive_registry.get(each_name) {
+                Some(transform) => (Arc::as_ptr(transform) as *const () as usize).hash(&mut hasher),
+                None => (),
+            }
+        }
+
+        for each_name in self.primitive_registry.get_scanner_names() {
+            each_name.hash(&mut hasher);
+
+            match self.primitive_registry.get_scanner(each_name) {
+                Some(scanner) => (Arc::as_ptr(scanner) as *const () as usize).hash(&mut hasher),
+                None => (),
+            }
+        }
+
+        return hasher.finish();
+    }
+
+    fn parse_input(&mut self) -> ConsoleResult<SyntaxTree> {
+        let (start_rule_id, start_rule_pos) = self.get_start_rule()?;
+
+        if !self.precompile_char_classes() {
+            return Err(());
+        }
+
+        // note: 空の入力も開始規則で検査し、受理するかは文法に委ねる
+        let root_node = match self.engine {
+            ParseEngine::Recursive => self.parse_rule(&start_rule_id, &start_rule_pos)?,
+            ParseEngine::Iterative => self.parse_rule_iteratively(&start_rule_id, &start_rule_pos)?,
+        };
+
+        // note: 分岐内で記録したエラーがあれば構文木の成否に関わらず失敗とする
+        if self.has_branch_error {
+            return Err(());
+        }
+
+        let mut root_node = match root_node {
+            Some(v) => v,
+            None => {
+                self.append_unmatched_log(&start_rule_id);
+                self.partial_tree = self.to_partial_tree(&start_rule_id);
+                return Err(());
+            },
+        };
+
+        // note: ルートは常に Reflectable
+        root_node.set_ast_reflection_style(ASTReflectionStyle::reflection(self.to_rule_reflection_name(&start_rule_id)));
+
+        let tree = SyntaxTree::from_node(root_node);
+
+        let tree = if self.is_byte_mode {
+            tree.decode_byte_leaves()
+        } else {
+            tree
+        };
+
+        // note: 入力位置が length を超えると失敗
+        if self.src_i < self.src_len {
+            self.append_unconsumed_input_log();
+            self.partial_tree = Some(tree);
+            return Err(());
+        }
+
+        return Ok(tree);
+    }
+
+    // spec: 開始規則が入力の途中までしか一致しなかったことを記録する; 範囲は未消費の入力全体
+    fn append_unconsumed_input_log(&mut self) {
+        // note: EOF 用のヌル文字は数えない
+        let remaining_len = self.src_len.saturating_sub(self.src_i + 1);
+        let remaining_text = self.substring_src_content(self.src_i, remaining_len.min(UNCONSUMED_INPUT_PREVIEW_MAX_LEN));
+        let mut remaining_preview = escape_source_text(&remaining_text);
+
+        if remaining_len > UNCONSUMED_INPUT_PREVIEW_MAX_LEN {
+            remaining_preview += "…";
+        }
+
+        self.append_log(SyntaxParsingLog::UnconsumedInput {
+            pos: self.get_char_position(),
+            end_pos: self.get_end_of_input_position(),
+            remaining_preview: remaining_preview,
+            remaining_len: remaining_len,
+        });
+    }
+
+    // ret: 解析に失敗した場合の、失敗した位置の手前までに一致した部分の構文木; 成功した場合や一致した部分がなければ None
+    // spec: 開始規則が入力の途中までしか一致しなかった場合はその構文木、開始規則が一致しなかった場合は開始規則の直下で最も先まで一致した子要素を持つ構文木とする
+    pub fn get_partial_tree(&self) -> Option<&SyntaxTree> {
+        return self.partial_tree.as_ref();
+    }
+
+    // spec: 開始規則の直下の連接で要素が失敗した際に、それまでに一致した子要素を記録する; 記録済みの位置より先で失敗した場合のみ複製する
+    // note: 先読みの中の失敗は入力を消費しないため対象外
+    fn record_partial_elems(&mut self, elem_src_i: usize, children: &Vec<SyntaxNodeElement>) {
+        if self.rule_depth == 1 && self.lookahead_depth == 0 && children.len() != 0 && elem_src_i > self.partial_src_i {
+            self.partial_src_i = elem_src_i;
+            self.partial_elems = children.clone();
+        }
+    }
+
+    // ret: record_partial_elems() で記録した子要素を持つ開始規則のノードの構文木; 記録がなければ None
+    fn to_partial_tree(&mut self, start_rule_id: &String) -> Option<SyntaxTree> {
+        if self.partial_elems.len() == 0 {
+            return None;
+        }
+
+        let partial_elems = std::mem::replace(&mut self.partial_elems, Vec::new());
+        let mut root_node = SyntaxNodeElement::from_node_args(partial_elems, ASTReflectionStyle::reflection(self.to_rule_reflection_name(start_rule_id)));
+        root_node.set_src_range(Some((0, self.partial_src_i)));
+        let tree = SyntaxTree::from_node(root_node);
+
+        return if self.is_byte_mode {
+            Some(tree.decode_byte_leaves())
+        } else {
+            Some(tree)
+        };
+    }
+
+    // spec: 開始規則が一致しなかったことを記録する; 入力をすべて消費した位置で失敗した要素があれば入力が途中で終わったとみなす
+    fn append_unmatched_log(&mut self, start_rule_id: &String) {
+        let log = match &self.eof_rule_id {
+            Some(expected_rule) => SyntaxParsingLog::UnexpectedEOF {
+                pos: self.get_end_of_input_position(),
+                expected_rule: expected_rule.clone(),
+            },
+            None => SyntaxParsingLog::NoSucceededRule {
+                rule_id: start_rule_id.clone(),
+                pos: self.get_char_position(),
+                rule_stack: self.get_failure_rule_stack().clone(),
+                rule_doc: self.get_rule_stack_doc(),
+                expected: self.get_expected_tokens(),
+            },
+        };
+
+        self.append_log(log);
+    }
+
+    // ret: 最も先の失敗位置で期待された終端記号; 失敗した表現字句がなければ None
+    fn get_expected_tokens(&self) -> Option<ExpectedTokens> {
+        if self.expected_exprs.len() == 0 {
+            return None;
+        }
+
+        let mut tokens = Vec::<String>::new();
+
+        for (each_expr, each_rule_id) in &self.expected_exprs {
+            for each_token in self.rule_map.get_expected_terminals(each_expr, each_rule_id) {
+                if !tokens.contains(&each_token) {
+                    tokens.push(each_token);
+                }
+            }
+        }
+
+        // note: EOF 用のヌル文字の位置は入力の終端とする
+        let found = if self.expected_src_i + 1 >= self.src_len {
+            None
+        } else {
+            Some(escape_source_text(&self.substring_src_content(self.expected_src_i, 1)))
+        };
+
+        return Some(ExpectedTokens {
+            pos: self.get_char_position_at(self.expected_src_i),
+            tokens: tokens,
+            found: found,
+        });
+    }
+
+    // ret: 失敗時のログに含める規則スタック; 失敗した表現字句がなければ現在の規則スタック
+    fn get_failure_rule_stack(&self) -> &Vec<(CharacterPosition, String)> {
+        return if self.expected_rule_stack.len() != 0 {
+            &self.expected_rule_stack
+        } else {
+            &self.rule_stack
+        };
+    }
+
+    // ret: 規則スタックの先頭の (規則名, 説明); 説明の出力が無効であるか説明がなければ None
+    fn get_rule_stack_doc(&self) -> Option<(String, String)> {
+        if !self.include_rule_docs {
+            return None;
+        }
+
+        let rule_id = match self.get_failure_rule_stack().last() {
+            Some((_, v)) => v,
+            None => return None,
+        };
+
+        return match self.rule_map.get_rule_doc(rule_id) {
+            Some(doc) => Some((self.to_rule_reflection_name(rule_id), doc.to_string())),
+            None => None,
+        };
+    }
+
+    pub fn get_logs(&self) -> &Vec<SyntaxParsingLog> {
+        return &self.logs;
+    }
+
+    // spec: 解析中に出力したログを LSP の Diagnostic の JSON 配列として出力する
+    #[cfg(feature = "lsp")]
+    pub fn write_lsp_diagnostics<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let diagnostics = self.logs.iter().map(|each_log| each_log.to_lsp_diagnostic()).collect::<Vec<serde_json::Value>>();
+        return serde_json::to_writer(writer, &diagnostics).map_err(|e| e.into());
+    }
+
+    // spec: 解析中に出力したログを診断情報に変換して取り出す; 取り出したログは get_logs() に残らない
+    pub fn drain_diagnostics(&mut self) -> Vec<Diagnostic> {
+        return self.logs.drain(..).map(|each_log| Diagnostic::from(&each_log)).collect::<Vec<Diagnostic>>();
+    }
+
+    // ret: 規則 ID ごとのメモ化の統計情報; 収集が有効でなければ空
+    pub fn get_rule_memo_stats(&self) -> &HashMap<String, RuleMemoStats> {
+        return &self.rule_memo_stats;
+    }
+
+    // ret: 記録した網羅状況; 取り出した網羅状況は破棄する
+    // note: 網羅状況の記録が有効でなければ、すべての規則とグループが検査されていないものとなる
+    pub fn take_coverage(&mut self) -> GrammarCoverage {
+        let coverage = match &mut self.coverage {
+            Some(v) => std::mem::take(v),
+            None => CoverageRecord::default(),
+        };
+
+        return GrammarCoverage::new(&self.rule_map, &coverage);
+    }
+
+    pub fn get_stats(&self) -> ParseStats {
+        let mut stats = self.stats;
+        stats.consumed_char_count = self.src_i.min(self.src_len.saturating_sub(1));
+        return stats;
+    }
+
+    // spec: tree のすべてのリーフ (非反映的なものを含む) について、位置の文字インデックスから始まる入力がリーフの値と一致するかを検査し、一致しなければ PositionMismatch を返す
+    // spec: バイト列モードではリーフの元のバイト列を入力と比較する; 返したログは get_logs() に追加しない
+    // note: 入力位置の加算 (add_source_index_by_string() など) の不具合を検出するためのもので、入力の文字列を 1 度だけ展開して走査する
+    // note: 引数の先頭に非反映的な要素を含む JOIN や INTO で値を変換したリーフは入力と一致しないため報告される
+    pub fn validate_positions(&self, tree: &SyntaxTree) -> Vec<SyntaxParsingLog> {
+        let src_chars = self.src_content.chars().collect::<Vec<char>>();
+
+        return tree.fold(Vec::<SyntaxParsingLog>::new(), TraversalOrder::PreOrder, |mut logs, each_elem| {
+            let leaf = match each_elem {
+                SyntaxNodeElement::Node(_) => return logs,
+                SyntaxNodeElement::Leaf(leaf) => leaf,
+            };
+
+            let leaf_chars = if self.is_byte_mode {
+                leaf.get_bytes().iter().map(|each_byte| *each_byte as char).collect::<Vec<char>>()
+            } else {
+                leaf.value.chars().collect::<Vec<char>>()
+            };
+
+            let start_i = leaf.pos.index.min(src_chars.len());
+            let end_i = leaf.pos.index.saturating_add(leaf_chars.len()).min(src_chars.len());
+
+            if src_chars[start_i..end_i] != leaf_chars[..] {
+                logs.push(SyntaxParsingLog::PositionMismatch {
+                    pos: leaf.pos.clone(),
+                    value: leaf_chars.iter().collect::<String>(),
+                    found: src_chars[start_i..end_i].iter().collect::<String>(),
+                });
+            }
+
+            return logs;
+        });
+    }
+
+    // ret: 解析中に出力したログのスコープ
+    pub fn get_log_scope(&self) -> LogScope {
+        return LogScope::new(LogPhase::Parsing, Some(self.src_path.clone()));
+    }
+
+    fn append_log(&mut self, log: SyntaxParsingLog) {
+        self.cons.borrow_mut().append_log(log.get_log());
+
+        match &self.log_recorder {
+            Some(log_recorder) => log_recorder.record(self.get_log_scope(), ScopedLogKind::Parsing(log.clone())),
+            None => (),
+        }
+
+        self.logs.push(log);
+    }
+
+    // spec: 構文解析器を生成する前に失敗した場合のログを出力し、設定に記録があれば src_path のスコープで記録する
+    fn append_log_without_parser(cons: &Rc<RefCell<Console>>, config: &ParseConfig, src_path: &String, log: SyntaxParsingLog) {
+        cons.borrow_mut().append_log(log.get_log());
+
+        match &config.log_recorder {
+            Some(log_recorder) => log_recorder.record(LogScope::new(LogPhase::Parsing, Some(src_path.clone())), ScopedLogKind::Parsing(log)),
+            None => (),
+        }
+    }
+
+    // spec: 文法の使い方に関するエラーを記録して解析全体を失敗扱いにする; 呼び出し元は当該の分岐のみを失敗させて解析を続行する
+    // note: メモ化されない分岐では同じエラーが繰り返し発生するため重複を除く
+    fn append_branch_error(&mut self, log: SyntaxParsingLog) {
+        self.has_branch_error = true;
+
+        let log_key = match log.get_position() {
+            Some(pos) => format!("{} at {}", log, pos),
+            None => log.to_string(),
+        };
+
+        if self.branch_error_keys.insert(log_key) {
+            self.append_log(log);
+        }
+    }
+
+    // note: 位置は上限を超えた時点の入力位置であり、繰り返された範囲の内側を指す
+    fn append_too_long_repetition_log(&mut self, loop_range: &RuleElementLoopRange, group_uuid: Option<Uuid>) {
+        let rule_id = match self.rule_stack.last() {
+            Some((_, v)) => v.clone(),
+            None => String::new(),
+        };
+
+        self.append_log(SyntaxParsingLog::TooLongRepetition {
+            pos: self.get_char_position(),
+            rule_id: rule_id,
+            group_uuid: group_uuid,
+            loop_range: loop_range.clone(),
+            loop_limit: self.loop_limit,
+        });
+    }
+
+    fn parse_rule(&mut self, rule_id: &String, pos: &CharacterPosition) -> ConsoleResult<Option<SyntaxNodeElement>> {
+        let context_len = self.enter_rule()?;
+        let result = self.parse_raw_rule(rule_id, pos);
+
+        let is_matched = match &result {
+            Ok(Some(_)) => true,
+            _ => false,
+        };
+
+        self.leave_rule(rule_id, pos, context_len, is_matched);
+        return result;
+    }
+
+    // ret: 規則に入る時点の文脈変数の数
+    fn enter_rule(&mut self) -> ConsoleResult<usize> {
+        self.stats.rule_invocation_count += 1;
+        self.rule_depth += 1;
+
+        if self.rule_depth > self.stats.peak_rule_depth {
+            self.stats.peak_rule_depth = self.rule_depth;
+        }
+
+        // note: 左再帰や深い入れ子によるスタックオーバーフローを防ぐ
+        if self.rule_depth > self.max_depth {
+            self.append_log(SyntaxParsingLog::TooDeepRuleNesting {
+                max_depth: self.max_depth,
+            });
+
+            self.rule_depth -= 1;
+            return Err(());
+        }
+
+        return Ok(self.context_map.len());
+    }
+
+    // note: 規則内で記録された文脈変数は規則から戻る際に破棄する; 解析がエラーで終わる場合も呼び出す
+    fn leave_rule(&mut self, rule_id: &String, pos: &CharacterPosition, context_len: usize, is_matched: bool) {
+        self.context_map.truncate(context_len);
+        self.rule_depth -= 1;
+
+        // note: 他規則の別名にすぎない規則はグループを検査しないため、規則の呼び出しとしても記録する
+        if self.coverage.is_some() {
+            match self.rule_map.rule_map.get(rule_id) {
+                Some(rule) => {
+                    let group_index = rule.group.index;
+                    self.record_coverage(group_index, is_matched);
+                },
+                None => (),
+            }
+        }
+
+        if is_matched && self.rule_map.has_deprecations {
+            self.warn_deprecated_rule_use(rule_id, pos);
+        }
+    }
+
+    // spec: 一致した規則もしくは別名に deprecated 指定があれば、参照元の表現字句の位置を示して警告する; 同じ規則 ID については解析ごとに最初の 1 度のみ警告する
+    // note: 後戻りで破棄される分岐内での一致も使用とみなす
+    fn warn_deprecated_rule_use(&mut self, rule_id: &String, pos: &CharacterPosition) {
+        if self.deprecated_rule_ids.contains(rule_id) {
+            return;
+        }
+
+        let (alias_of, msg) = match self.rule_map.rule_map.get(rule_id) {
+            Some(rule) => match &rule.deprecation {
+                Some(msg) => (rule.alias_of.clone(), msg.clone()),
+                None => return,
+            },
+            None => return,
+        };
+
+        self.deprecated_rule_ids.insert(rule_id.clone());
+
+        self.append_log(SyntaxParsingLog::DeprecatedRuleUse {
+            pos: pos.clone(),
+            rule_id: rule_id.clone(),
+            alias_of: alias_of,
+            msg: msg,
+        });
+    }
+
+    fn parse_raw_rule(&mut self, rule_id: &String, pos: &CharacterPosition) -> ConsoleResult<Option<SyntaxNodeElement>> {
+        let rule_map = self.rule_map.clone();
+
+        let (rule_group, alias_rule_id) = match self.begin_raw_rule(&rule_map, rule_id, pos) {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+
+        // note: 他規則の別名にすぎない規則 (A <- B) は中間ノードを生成せず被参照規則のノードを別名の規則名で返す
+        match alias_rule_id {
+            Some(alias_rule_id) => {
+                let result = self.parse_rule(alias_rule_id, pos)?;
+                return Ok(self.to_alias_rule_node(rule_id, alias_rule_id, result));
+            },
+            None => (),
+        }
+
+        let start_src_i = self.src_i;
+
+        return match self.parse_group(&rule_group.elem_order, rule_group)? {
+            Some(v) => Ok(Some(self.to_rule_node(rule_id, rule_group, start_src_i, v))),
+            None => Ok(None),
+        };
+    }
+
+    // ret: 規則のグループと、別名にすぎない規則であれば参照先の規則 ID; 規則が存在しなければエラーを記録して None
+    // spec: 規則が存在すれば規則スタックに積む
+    fn begin_raw_rule<'a>(&mut self, rule_map: &'a RuleMap, rule_id: &String, pos: &CharacterPosition) -> Option<(&'a Box<RuleGroup>, Option<&'a String>)> {
+        return match rule_map.rule_map.get(rule_id) {
+            Some(rule) => {
+                let alias_rule_id = if rule.generics_arg_ids.len() == 0 && rule.template_arg_ids.len() == 0 {
+                    rule.group.get_alias_rule_id()
+                } else {
+                    None
+                };
+
+                self.rule_stack.push((self.get_char_position(), rule_id.clone()));
+                Some((&rule.group, alias_rule_id))
+            },
+            None => {
+                self.append_branch_error(SyntaxParsingLog::UnknownRuleID {
+                    pos: pos.clone(),
+                    rule_id: rule_id.clone(),
+                });
+
+                None
+            },
+        };
+    }
+
+    // spec: 一致した規則のグループの結果から規則ノードを生成し、規則スタックから取り除く
+    fn to_rule_node(&mut self, rule_id: &String, rule_group: &RuleGroup, start_src_i: usize, children: Vec<SyntaxNodeElement>) -> SyntaxNodeElement {
+        let (mut ast_reflection_style, is_ast_reflection_specified) = match &rule_group.sub_elems.get(0) {
+            Some(v) => {
+                match v {
+                    RuleElement::Group(sub_choice) => (sub_choice.ast_reflection_style.clone(), sub_choice.is_ast_reflection_specified),
+                    RuleElement::Expression(_) => (rule_group.ast_reflection_style.clone(), rule_group.is_ast_reflection_specified),
+                }
+            },
+            _ => (rule_group.ast_reflection_style.clone(), rule_group.is_ast_reflection_specified),
+        };
+
+        // note: 反映方式の指定がない規則ノードは規則名で命名する; 構成ファイルによる反転は読み込み時に各要素へ適用済み
+        // spec: 反映名のない反映方式が明示されていれば (#_) 無名のノードとする
+        if ast_reflection_style.visibility == ASTVisibility::Reflect && ast_reflection_style.name_override.is_none() && !is_ast_reflection_specified {
+            ast_reflection_style = ASTReflectionStyle::reflection(self.to_rule_reflection_name(rule_id));
+        }
+
+        self.rule_stack.pop().unwrap();
+        let mut new_node = SyntaxNodeElement::from_node_args(children, ast_reflection_style);
+        new_node.set_src_range(Some((start_src_i, self.src_i - start_src_i)));
+        return new_node;
+    }
+
+    // spec: 別名にすぎない規則の結果として参照先の規則ノードを別名の規則名で命名し直し、規則スタックから取り除く
+    // note: 参照先の規則で反映方式が明示されたノードやリーフはそのまま返す
+    fn to_alias_rule_node(&mut self, rule_id: &String, alias_rule_id: &String, result: Option<SyntaxNodeElement>) -> Option<SyntaxNodeElement> {
+        let mut node_elem = match result {
+            Some(v) => v,
+            None => return None,
+        };
+
+        self.rule_stack.pop().unwrap();
+
+        if node_elem.is_node() && node_elem.get_ast_reflection_style().is_reflected_as(&self.to_rule_reflection_name(alias_rule_id)) {
+            node_elem.set_ast_reflection_style(ASTReflectionStyle::reflection(self.to_rule_reflection_name(rule_id)));
+        }
+
+        return Some(node_elem);
+    }
+
+    fn parse_group(&mut self, parent_elem_order: &RuleElementOrder, group: &Box<RuleGroup>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
+        let state = match self.begin_group(parent_elem_order, group, &group.loop_range, self.arg_maps.len()) {
+            GroupStart::Started(v) => v,
+            GroupStart::Finished(result) => return Ok(result),
+        };
+
+        let result = self.parse_lookahead_group(parent_elem_order, group)?;
+        self.end_group(group, state, &result);
+        return Ok(result);
+    }
+
+    // spec: 最小一致長とメモ化された結果を調べ、グループを検査せずに結果が定まれば GroupStart::Finished を返す
+    // note: 順不同グループの子要素は loop_range で繰り返し範囲を置き換えて検査するため、最小一致長もそれに従う
+    fn begin_group(&mut self, parent_elem_order: &RuleElementOrder, group: &RuleGroup, loop_range: &RuleElementLoopRange, arg_map_len: usize) -> GroupStart {
+        self.record_coverage(group.index, false);
+
+        // note: 残りの入力が最小一致長に満たなければ検査せずに失敗とする; 順不同の場合は子要素の繰り返し範囲が変わるため対象外
+        if self.skip_short_groups && !parent_elem_order.is_random() && self.src_len - self.src_i < group.get_effective_min_len_with(loop_range) {
+            self.record_eof_failure();
+
+            for each_elem in group.first_elements() {
+                match each_elem {
+                    RuleElement::Expression(each_expr) => {
+                        self.record_truncated_expr_failure(each_expr);
+                        self.record_expected_expr(each_expr);
+                    },
+                    RuleElement::Group(_) => (),
+                }
+            }
+
+            return GroupStart::Finished(None);
+        }
+
+        // note: 総称引数・テンプレート引数の中身は呼び出しごとに異なるが、メモ化のキーは引数によらずグループの UUID となるため引数の解決中は対象外とする
+        // spec: 規則の %memo / %nomemo 指定があれば設定より優先する; 後方参照を含む文法では %memo 指定があってもメモ化しない
+        let is_memoizable = match group.memoization {
+            Some(enable_memoization) => enable_memoization && !self.rule_map.has_capture_refs,
+            None => self.enable_memoization,
+        } && arg_map_len == 0;
+
+        if is_memoizable {
+            match self.memoized_map.find(&group.uuid, self.src_i) {
+                Some((src_len, result)) => {
+                    self.stats.memo_hit_count += 1;
+                    self.update_rule_memo_stats(|stats| stats.hit_count += 1);
+                    self.record_coverage(group.index, result.is_some());
+                    self.src_i += src_len;
+                    return GroupStart::Finished(result);
+                },
+                None => {
+                    self.stats.memo_miss_count += 1;
+                    self.update_rule_memo_stats(|stats| stats.miss_count += 1);
+                },
+            }
+        }
+
+        return GroupStart::Started(GroupState {
+            is_memoizable: is_memoizable,
+            start_src_i: self.src_i,
+            capture_lens: self.get_capture_lens(),
+        });
+    }
+
+    // spec: グループの結果に応じて捕捉を更新し、メモ化の対象であれば結果を記録する
+    fn end_group(&mut self, group: &RuleGroup, state: GroupState, result: &Option<Vec<SyntaxNodeElement>>) {
+        self.update_captures(&group.capture_name, state.start_src_i, state.capture_lens, result.is_some());
+        self.record_coverage(group.index, result.is_some());
+
+        if state.is_memoizable {
+            if self.src_i != state.start_src_i {
+                self.memoized_map.push(group.uuid.clone(), state.start_src_i, self.src_i - state.start_src_i, result.clone());
+                self.update_rule_memo_stats(|stats| stats.entry_count += 1);
+            }
+        }
+    }
+
+    // spec: グループを検査したことと、is_matched が真であれば一致したことを記録する; 記録が有効でなければ何もしない
+    // note: 後戻りで破棄される分岐内での検査・一致も記録する
+    fn record_coverage(&mut self, group_index: usize, is_matched: bool) {
+        match &mut self.coverage {
+            Some(coverage) => {
+                coverage.attempted.insert(group_index);
+
+                if is_matched {
+                    coverage.matched.insert(group_index);
+                }
+            },
+            None => (),
+        }
+    }
+
+    // spec: 規則スタックの先頭の規則のメモ化の統計情報を f で更新する; 収集が有効でなければ何もしない
+    fn update_rule_memo_stats(&mut self, f: fn(&mut RuleMemoStats)) {
+        if !self.collect_rule_memo_stats {
+            return;
+        }
+
+        let rule_id = match self.rule_stack.last() {
+            Some((_, v)) => v,
+            None => return,
+        };
+
+        match self.rule_memo_stats.get_mut(rule_id) {
+            Some(stats) => f(stats),
+            None => {
+                let mut stats = RuleMemoStats::default();
+                f(&mut stats);
+                self.rule_memo_stats.insert(rule_id.clone(), stats);
+            },
+        }
+    }
+
+    fn parse_lookahead_group(&mut self, parent_elem_order: &RuleElementOrder, group: &Box<RuleGroup>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
+        return if group.lookahead_kind.is_none() {
+            self.parse_loop_group(parent_elem_order, group)
+        } else {
+            let snapshot = self.checkpoint();
+            let is_lookahead_positive = group.lookahead_kind == RuleElementLookaheadKind::Positive;
+
+            self.lookahead_depth += 1;
+            let result = self.parse_loop_group(parent_elem_order, group);
+            self.lookahead_depth -= 1;
+            let result = result?;
+            self.restore(snapshot);
+
+            if result.is_some() == is_lookahead_positive {
+                Ok(Some(Vec::new()))
+            } else {
+                Ok(None)
+            }
+        };
+    }
+
+    fn parse_loop_group(&mut self, parent_elem_order: &RuleElementOrder, group: &Box<RuleGroup>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
+        let (min_count, max_count) = self.check_loop_range(&group.loop_range)?;
+        let start_src_i = self.src_i;
+        let mut children = Vec::<SyntaxNodeElement>::new();
+        let mut loop_count = 0isize;
+
+        while self.src_i < self.src_len {
+            if loop_count > self.loop_limit as isize {
+                self.append_too_long_repetition_log(&group.loop_range, Some(group.uuid.clone()));
+                return Err(());
+            }
+
+            match self.parse_loop_group_iteration(parent_elem_order, group, loop_count)? {
+                LoopIteration::Matched(mut node_elems) => {
+                    children.append(&mut node_elems);
+                    loop_count += 1;
+
+                    if max_count != -1 && loop_count == max_count {
+                        return Ok(self.end_loop_group(group, children, loop_count, (min_count, max_count), start_src_i));
+                    }
+                },
+                LoopIteration::TrailingSeparator(mut node_elems) => {
+                    children.append(&mut node_elems);
+                    break;
+                },
+                LoopIteration::Unmatched => break,
+            }
+        }
+
+        return Ok(self.end_loop_group(group, children, loop_count, (min_count, max_count), start_src_i));
+    }
+
+    // ret: (最小回数, 最大回数); 最大回数が -1 であれば上限なし
+    fn check_loop_range(&mut self, loop_range: &RuleElementLoopRange) -> ConsoleResult<(usize, isize)> {
+        let (min_count, max_count) = loop_range.to_tuple();
+
+        if max_count != -1 && min_count as isize > max_count {
+            self.append_log(SyntaxParsingLog::InvalidLoopRange {
+                msg: format!("invalid loop range {{{},{}}} was detected", min_count, max_count),
+            });
+
+            return Err(());
+        }
+
+        return Ok((min_count, max_count));
+    }
+
+    // ret: 繰り返し回数が範囲内であればグループの結果; 範囲外であれば None
+    fn end_loop_group(&self, group: &RuleGroup, children: Vec<SyntaxNodeElement>, loop_count: isize, loop_range: (usize, isize), start_src_i: usize) -> Option<Vec<SyntaxNodeElement>> {
+        let (min_count, max_count) = loop_range;
+
+        return if loop_count >= min_count as isize && (max_count == -1 || loop_count <= max_count) {
+            Some(SyntaxParser::to_presence_node_elems(group, children, loop_count, (start_src_i, self.src_i - start_src_i)))
+        } else {
+            None
+        };
+    }
+
+    // spec: 2 回目以降の繰り返しでは区切りを先に検査する; 後続の繰り返しがない区切りは末尾の区切りが許される場合のみ消費し、それ以外は区切りの前まで戻す
+    fn parse_loop_group_iteration(&mut self, parent_elem_order: &RuleElementOrder, group: &Box<RuleGroup>, loop_count: isize) -> ConsoleResult<LoopIteration> {
+        let mut state = self.begin_loop_group_iteration();
+
+        match &group.separator {
+            Some(separator) if loop_count != 0 => {
+                match self.parse_separator(separator)? {
+                    Some(node_elems) => {
+                        state.separator_elems = node_elems;
+                        state.is_separator_matched = true;
+                    },
+                    None => return Ok(self.unmatch_loop_group_separator(&state)),
+                }
+            },
+            _ => (),
+        }
+
+        self.begin_loop_group_item(&mut state);
+        let result = self.parse_element_order_group(parent_elem_order, group)?;
+        return Ok(self.end_loop_group_iteration(group, state, result));
+    }
+
+    fn begin_loop_group_iteration(&self) -> LoopIterationState {
+        let snapshot = self.checkpoint();
+
+        return LoopIterationState {
+            separator_snapshot: snapshot,
+            separator_capture_lens: self.get_capture_lens(),
+            separator_elems: Vec::new(),
+            is_separator_matched: false,
+            item_snapshot: snapshot,
+            item_capture_lens: self.get_capture_lens(),
+        };
+    }
+
+    fn begin_loop_group_item(&self, state: &mut LoopIterationState) {
+        state.item_snapshot = self.checkpoint();
+        state.item_capture_lens = self.get_capture_lens();
+    }
+
+    // spec: 区切りに一致しなければ区切りの検査前まで戻す
+    fn unmatch_loop_group_separator(&mut self, state: &LoopIterationState) -> LoopIteration {
+        self.update_captures(&None, state.separator_snapshot.src_i, state.separator_capture_lens, false);
+        self.restore(state.separator_snapshot);
+        return LoopIteration::Unmatched;
+    }
+
+    // note: 空のノードは結果に含めない
+    // spec: 繰り返しの要素が途中まで一致して失敗した場合も、要素内で記録された捕捉と文脈変数を破棄する
+    fn end_loop_group_iteration(&mut self, group: &RuleGroup, state: LoopIterationState, result: Option<Vec<SyntaxNodeElement>>) -> LoopIteration {
+        return match result {
+            Some(node_elems) => {
+                let children = state.separator_elems.into_iter().chain(node_elems).filter(|each_elem| !each_elem.is_empty_node()).collect::<Vec<SyntaxNodeElement>>();
+                LoopIteration::Matched(children)
+            },
+            None if state.is_separator_matched && group.allows_trailing_separator => {
+                self.update_captures(&None, state.item_snapshot.src_i, state.item_capture_lens, false);
+                self.restore(state.item_snapshot);
+                let children = state.separator_elems.into_iter().filter(|each_elem| !each_elem.is_empty_node()).collect::<Vec<SyntaxNodeElement>>();
+                LoopIteration::TrailingSeparator(children)
+            },
+            None => {
+                self.update_captures(&None, state.separator_snapshot.src_i, state.separator_capture_lens, false);
+
+                if state.is_separator_matched {
+                    self.restore(state.separator_snapshot);
+                }
+
+                LoopIteration::Unmatched
+            },
+        };
+    }
+
+    fn parse_separator(&mut self, separator: &RuleElement) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
+        return match separator {
+            RuleElement::Group(separator_group) => self.parse_group(&RuleElementOrder::Sequential, separator_group),
+            RuleElement::Expression(separator_expr) => self.parse_expr(separator_expr),
+        };
+    }
+
+    // note: 存在マーカー付きグループが一致した場合は子要素がなくても反映名のノードを生成する; 一致しなければ何も生成しない
+    // spec: 生成したノードは親グループでそのまま子要素として扱われる
+    fn to_presence_node_elems(group: &RuleGroup, children: Vec<SyntaxNodeElement>, loop_count: isize, src_range: (usize, usize)) -> Vec<SyntaxNodeElement> {
+        return if group.has_presence_marker && loop_count != 0 {
+            let mut presence_node = SyntaxNodeElement::from_presence_node_args(children, group.ast_reflection_style.clone());
+            presence_node.set_src_range(Some(src_range));
+            vec![presence_node]
+        } else {
+            children
+        };
+    }
+
+    fn parse_element_order_group(&mut self, parent_elem_order: &RuleElementOrder, group: &Box<RuleGroup>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
+        let mut children = Vec::<SyntaxNodeElement>::new();
+
+        return match parent_elem_order {
+            RuleElementOrder::Random(random_order_loop_range) => {
+                let tar_elems = self.get_random_order_elems(group)?;
+                let random_order_snapshot = self.checkpoint();
+                let mut is_each_subgroup_matched = vec![false; tar_elems.len()];
+                let mut subgroup_i = 0usize;
+
+                for _ in 0..tar_elems.len() {
+                    let elem_snapshot = self.checkpoint();
+                    for subelem in tar_elems {
+                        match subelem {
+                            RuleElement::Group(subgroup) => {
+                                let mut conved_subgroup = subgroup.clone();
+                                conved_subgroup.loop_range = random_order_loop_range.clone();
+
+                                match self.parse_group(&RuleElementOrder::Sequential, &conved_subgroup)? {
+                                    Some(node_elems) => {
                                         if is_each_subgroup_matched[subgroup_i] {
                                             subgroup_i += 1;
                                             continue;
                                         }
 
-                                        for each_elem in node_elems {
-                                            match &each_elem {
-                                                SyntaxNodeElement::Node(node) => {
-                                                    if node.sub_elems.len() != 0 {
-                                                        children.push(each_elem);
-                                                    }
-                                                },
-                                                _ => children.push(each_elem),
-                                            }
-                                        }
+                                        SyntaxParser::push_nonempty_elems(&mut children, node_elems);
+                                        is_each_subgroup_matched[subgroup_i] = true;
+                                        break;
+                                    },
+                                    None => self.restore(elem_snapshot),
+                                }
+                            },
+                            _ => (),
+                        }
+
+                        subgroup_i += 1;
+                    }
+
+                    if is_each_subgroup_matched.iter().find(|v| !**v).is_none() {
+                        return Ok(Some(children));
+                    }
+
+                    subgroup_i = 0;
+                }
+
+                self.restore(random_order_snapshot);
+                Ok(None)
+            },
+            RuleElementOrder::Sequential => self.parse_raw_group(group),
+        };
+    }
+
+    // ret: 順不同グループの選択肢となる要素
+    fn get_random_order_elems<'a>(&mut self, group: &'a RuleGroup) -> ConsoleResult<&'a Vec<RuleElement>> {
+        return match group.sub_elems.get(0) {
+            Some(tar_parent_elem) => {
+                match tar_parent_elem {
+                    RuleElement::Group(tar_parent_group) => Ok(&tar_parent_group.sub_elems),
+                    _ => {
+                        self.append_log(SyntaxParsingLog::InvalidRuleElementStructure {
+                            uuid: group.uuid.clone(),
+                            msg: "child element of random order group must be a group".to_string(),
+                        });
+
+                        Err(())
+                    },
+                }
+            },
+            None => {
+                self.append_log(SyntaxParsingLog::InvalidRuleElementStructure {
+                    uuid: group.uuid.clone(),
+                    msg: "random order group must have a child group".to_string(),
+                });
+
+                Err(())
+            },
+        };
+    }
+
+    fn parse_raw_group(&mut self, group: &Box<RuleGroup>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
+        let mut children = Vec::<SyntaxNodeElement>::new();
+        let is_single_elem = group.sub_elems.len() == 1;
+
+        for each_elem in &group.sub_elems {
+            let elem_src_i = self.src_i;
+
+            match self.parse_sequence_elem(each_elem, is_single_elem)? {
+                Some(mut v) => children.append(&mut v),
+                None => {
+                    self.record_partial_elems(elem_src_i, &children);
+                    return Ok(None);
+                },
+            }
+        }
+
+        return Ok(Some(children));
+    }
+
+    // ret: 連接の要素 1 つ分の子要素; 一致しなければ None
+    // spec: 連接の要素が 1 つのみであればグループの結果をそのまま返し、そうでなければグループの反映方式に応じたノードを生成する
+    fn parse_sequence_elem(&mut self, elem: &RuleElement, is_single_elem: bool) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
+        let mut children = Vec::<SyntaxNodeElement>::new();
+        let snapshot = self.checkpoint();
+
+        match elem {
+            RuleElement::Group(each_group) => {
+                let (result_group, result) = match each_group.kind {
+                    RuleGroupKind::Choice => {
+                        let choice_result = if each_group.choice_mode.is_longest_match() {
+                            self.parse_longest_match_choice(each_group)?
+                        } else {
+                            self.parse_ordered_choice(each_group)?
+                        };
+
+                        match choice_result {
+                            Some((each_sub_group, v)) => (each_sub_group, v),
+                            None => return Ok(None),
+                        }
+                    },
+                    RuleGroupKind::Sequence => {
+                        match self.parse_group(&each_group.elem_order, each_group)? {
+                            Some(v) => (each_group, v),
+                            None => {
+                                self.restore(snapshot);
+                                return Ok(None);
+                            },
+                        }
+                    },
+                };
+
+                return Ok(Some(self.to_sequence_group_elems(result_group, result, snapshot.src_i, is_single_elem)));
+            },
+            RuleElement::Expression(each_expr) => {
+                match self.parse_expr(each_expr)? {
+                    Some(node_elems) => SyntaxParser::push_nonempty_elems(&mut children, node_elems),
+                    None => {
+                        self.restore(snapshot);
+                        return Ok(None);
+                    },
+                }
+            },
+        }
+
+        return Ok(Some(children));
+    }
+
+    // ret: 連接の要素となったグループの結果から生成した子要素
+    fn to_sequence_group_elems(&self, result_group: &RuleGroup, result: Vec<SyntaxNodeElement>, start_src_i: usize, is_single_elem: bool) -> Vec<SyntaxNodeElement> {
+        if result_group.has_presence_marker || is_single_elem {
+            return result;
+        }
+
+        let mut new_child = SyntaxNodeElement::from_node_args(result, result_group.ast_reflection_style.clone());
+        new_child.set_src_range(Some((start_src_i, self.src_i - start_src_i)));
+
+        return match new_child {
+            SyntaxNodeElement::Node(node) if node.sub_elems.len() == 0 => Vec::new(),
+            SyntaxNodeElement::Node(mut new_node) if new_node.ast_reflection_style.is_expandable() => {
+                new_node.ast_reflection_style.apply_to_expanded_elems(std::mem::take(&mut new_node.sub_elems))
+            },
+            _ => vec![new_child],
+        };
+    }
+
+    fn push_nonempty_elems(children: &mut Vec<SyntaxNodeElement>, node_elems: Vec<SyntaxNodeElement>) {
+        for each_elem in node_elems {
+            if !each_elem.is_empty_node() {
+                children.push(each_elem);
+            }
+        }
+    }
+
+    fn parse_expr(&mut self, expr: &Box<RuleExpression>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
+        let start_src_i = self.src_i;
+        let capture_lens = self.get_capture_lens();
+        let result = self.parse_lookahead_expr(expr)?;
+        self.update_captures(&expr.capture_name, start_src_i, capture_lens, result.is_some());
+        return Ok(result);
+    }
+
+    // spec: 成功した要素に捕捉名があれば一致した入力文字列を捕捉する; 失敗した場合は要素内で行われた捕捉を破棄する
+    // ret: 最初に成功した選択肢とその結果; いずれも失敗すれば入力位置を戻して None
+    fn parse_ordered_choice<'a>(&mut self, choice: &'a Box<RuleGroup>) -> ConsoleResult<Option<(&'a Box<RuleGroup>, Vec<SyntaxNodeElement>)>> {
+        let snapshot = self.checkpoint();
+
+        for each_sub_elem in &choice.sub_elems {
+            match each_sub_elem {
+                RuleElement::Group(each_sub_group) => {
+                    match self.parse_group(&choice.elem_order, each_sub_group)? {
+                        Some(v) => return Ok(Some((each_sub_group, v))),
+                        None => self.restore(snapshot),
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        return Ok(None);
+    }
+
+    // spec: すべての選択肢を同じ開始位置から検査し、入力位置を最も進めた選択肢を採用する; 同じ位置であれば先に記述された選択肢を優先する
+    // note: 採用されなかった選択肢での捕捉は破棄する; 成功した選択肢があっても検査を打ち切らないため選択肢の数だけ検査が行われる
+    fn parse_longest_match_choice<'a>(&mut self, choice: &'a Box<RuleGroup>) -> ConsoleResult<Option<(&'a Box<RuleGroup>, Vec<SyntaxNodeElement>)>> {
+        let snapshot = self.checkpoint();
+        let capture_lens = self.get_capture_lens();
+        let mut longest_match = Option::<LongestMatch<'a>>::None;
+
+        for each_sub_elem in &choice.sub_elems {
+            match each_sub_elem {
+                RuleElement::Group(each_sub_group) => {
+                    match self.parse_group(&choice.elem_order, each_sub_group)? {
+                        Some(v) => self.update_longest_match(&mut longest_match, each_sub_group, v, capture_lens),
+                        None => (),
+                    }
+
+                    self.restore(snapshot);
+                },
+                _ => (),
+            }
+        }
+
+        return Ok(self.end_longest_match_choice(longest_match));
+    }
+
+    // spec: 成功した選択肢がそれまでの最長一致より入力位置を進めていれば置き換える; 選択肢内での捕捉と文脈変数はいずれの場合も取り除く
+    fn update_longest_match<'a>(&mut self, longest_match: &mut Option<LongestMatch<'a>>, sub_group: &'a Box<RuleGroup>, result: Vec<SyntaxNodeElement>, capture_lens: (usize, usize)) {
+        let (capture_len, context_len) = capture_lens;
+
+        let is_longer = match longest_match {
+            Some((_, _, end_snapshot, _, _)) => self.src_i > end_snapshot.src_i,
+            None => true,
+        };
+
+        let captures = self.capture_map.split_off(capture_len);
+        let contexts = self.context_map.split_off(context_len);
+
+        if is_longer {
+            *longest_match = Some((sub_group, result, self.checkpoint(), captures, contexts));
+        }
+    }
+
+    // ret: 最長一致した選択肢とその結果; 選択肢の終了時点の状態と捕捉を戻す
+    fn end_longest_match_choice<'a>(&mut self, longest_match: Option<LongestMatch<'a>>) -> Option<(&'a Box<RuleGroup>, Vec<SyntaxNodeElement>)> {
+        return match longest_match {
+            Some((sub_group, v, end_snapshot, captures, contexts)) => {
+                self.restore(end_snapshot);
+                self.capture_map.append(captures);
+                self.context_map.append(contexts);
+                Some((sub_group, v))
+            },
+            None => None,
+        };
+    }
+
+    pub fn checkpoint(&self) -> ParserCheckpoint {
+        return ParserCheckpoint {
+            src_i: self.src_i,
+            rule_stack_len: self.rule_stack.len(),
+        };
+    }
+
+    // spec: 入力位置を戻し、保存後に呼び出されて失敗した規則を規則スタックから取り除く
+    // note: 捕捉と文脈変数は成功時の記録も伴うため update_captures() で別に戻す
+    pub fn restore(&mut self, checkpoint: ParserCheckpoint) {
+        self.src_i = checkpoint.src_i;
+        self.rule_stack.truncate(checkpoint.rule_stack_len);
+    }
+
+    // ret: 入力位置の文字; 入力の終端 (EOF 用のヌル文字の位置以降) であれば None
+    pub fn peek_char(&self) -> Option<char> {
+        if self.src_i + 1 >= self.src_len {
+            return None;
+        }
+
+        return self.substring_src_content(self.src_i, 1).chars().next();
+    }
+
+    // spec: 入力位置の文字を返して入力位置を 1 文字進める; 入力の終端であれば進めずに None を返す
+    pub fn consume_char(&mut self) -> Option<char> {
+        let tar_char = self.peek_char()?;
+        self.src_i += 1;
+        return Some(tar_char);
+    }
+
+    // spec: 入力位置から value が続けば入力位置を value の長さだけ進めて true を返し、続かなければ進めずに false を返す
+    // note: バイト列モードでは文字列の表現字句と同じく value を UTF-8 のバイト列として照合する
+    pub fn consume_str(&mut self, value: &str) -> bool {
+        let value = if self.is_byte_mode {
+            to_byte_chars(value)
+        } else {
+            value.to_string()
+        };
+
+        let value_len = value.chars().count();
+
+        if self.src_i + value_len >= self.src_len || self.substring_src_content(self.src_i, value_len) != value {
+            return false;
+        }
+
+        self.add_source_index_by_string(&value);
+        return true;
+    }
+
+    // ret: (捕捉の数, 文脈変数の数); 失敗した要素内での記録を破棄するために用いる
+    fn get_capture_lens(&self) -> (usize, usize) {
+        return (self.capture_map.len(), self.context_map.len());
+    }
+
+    fn update_captures(&mut self, capture_name: &Option<String>, start_src_i: usize, capture_lens: (usize, usize), is_successful: bool) {
+        if !is_successful {
+            let (capture_len, context_len) = capture_lens;
+            self.capture_map.truncate(capture_len);
+            self.context_map.truncate(context_len);
+            return;
+        }
+
+        match capture_name {
+            Some(name) => {
+                let value = self.substring_src_content(start_src_i, self.src_i - start_src_i);
+                self.capture_map.push(name.clone(), value);
+            },
+            None => (),
+        }
+    }
+
+    fn parse_lookahead_expr(&mut self, expr: &Box<RuleExpression>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
+        return if expr.lookahead_kind.is_none() {
+            self.parse_loop_expr(expr)
+        } else {
+            let snapshot = self.checkpoint();
+            let is_lookahead_positive = expr.lookahead_kind == RuleElementLookaheadKind::Positive;
+
+            self.lookahead_depth += 1;
+            let result = self.parse_loop_expr(expr);
+            self.lookahead_depth -= 1;
+            let result = result?;
+            self.restore(snapshot);
+
+            if result.is_some() == is_lookahead_positive {
+                Ok(Some(Vec::new()))
+            } else {
+                Ok(None)
+            }
+        }
+    }
+
+    fn parse_loop_expr(&mut self, expr: &Box<RuleExpression>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
+        // note: 空文字列は入力を消費しないため繰り返しの範囲によらず 1 度だけ検査し、入力の終端でも成功させる
+        match expr.kind {
+            RuleExpressionKind::Epsilon => return self.parse_raw_expr(expr),
+            _ => (),
+        }
+
+        let (min_count, max_count) = self.check_loop_range(&expr.loop_range)?;
+        let mut children = Vec::<SyntaxNodeElement>::new();
+        let mut loop_count = 0usize;
+        let is_coalescible = self.is_coalescible_expr(expr);
+
+        while self.src_i < self.src_len {
+            if loop_count > self.loop_limit {
+                self.append_too_long_repetition_log(&expr.loop_range, None);
+                return Err(());
+            }
+
+            match self.parse_raw_expr(expr)? {
+                Some(node) => {
+                    SyntaxParser::push_loop_expr_elems(&mut children, node, is_coalescible);
+                    loop_count += 1;
+
+                    if max_count != -1 && loop_count as isize == max_count {
+                        return Ok(Some(children));
+                    }
+                },
+                None => {
+                    self.record_expected_expr(expr);
+                    break;
+                },
+            }
+        }
+
+        return Ok(self.end_loop_expr(children, loop_count, (min_count, max_count)));
+    }
+
+    fn is_coalescible_expr(&self, expr: &RuleExpression) -> bool {
+        return self.coalesce_repeated_leaves && match expr.kind {
+            RuleExpressionKind::CharClass | RuleExpressionKind::String => true,
+            _ => false,
+        };
+    }
+
+    fn push_loop_expr_elems(children: &mut Vec<SyntaxNodeElement>, node_elems: Vec<SyntaxNodeElement>, is_coalescible: bool) {
+        for each_node in node_elems {
+            if each_node.is_empty_node() {
+                continue;
+            }
+
+            // spec: AST 反映方式が等しく位置の連続するリーフのみをまとめる; まとめたリーフは先頭のリーフの位置を持つ
+            // note: 解析中のリーフの値はバイト列モードでも 1 バイトが 1 文字となるため文字数で位置を求める
+            if is_coalescible {
+                match (children.last_mut(), &each_node) {
+                    (Some(SyntaxNodeElement::Leaf(last_leaf)), SyntaxNodeElement::Leaf(leaf)) if last_leaf.ast_reflection_style == leaf.ast_reflection_style && last_leaf.pos.index + last_leaf.value.chars().count() == leaf.pos.index => {
+                        last_leaf.append(leaf);
+                        continue;
+                    },
+                    _ => (),
+                }
+            }
+
+            children.push(each_node);
+        }
+    }
+
+    // ret: 繰り返し回数が範囲内であれば子要素; 範囲外であれば入力の終端での失敗を記録して None
+    fn end_loop_expr(&mut self, children: Vec<SyntaxNodeElement>, loop_count: usize, loop_range: (usize, isize)) -> Option<Vec<SyntaxNodeElement>> {
+        let (min_count, max_count) = loop_range;
+
+        return if loop_count >= min_count && (max_count == -1 || loop_count as isize <= max_count) {
+            Some(children)
+        } else {
+            self.record_eof_failure();
+            None
+        };
+    }
+
+    // spec: 入力をすべて消費した位置 (EOF 用のヌル文字の位置以降) で要素が失敗したことを記録する
+    // note: メモ化の有無で結果が変わらないよう最初のもののみ記録する
+    fn record_eof_failure(&mut self) {
+        if self.eof_rule_id.is_some() || self.src_i + 1 < self.src_len {
+            return;
+        }
+
+        self.eof_rule_id = match self.rule_stack.last() {
+            Some((_, rule_id)) => Some(rule_id.clone()),
+            None => None,
+        };
+    }
+
+    // spec: 文字列が残りの入力 (EOF 用のヌル文字を除く) より長く、残りの入力が文字列の先頭と一致すれば、入力をすべて消費した位置で失敗したものとして記録する
+    // note: 例として "abc" に対する入力 "ab" は途中で終わった入力とみなす
+    fn record_truncated_string_failure(&mut self, value: &str) {
+        if self.eof_rule_id.is_some() {
+            return;
+        }
+
+        let remaining_len = self.src_len.saturating_sub(self.src_i + 1);
+
+        if value.chars().count() <= remaining_len || !value.starts_with(&self.substring_src_content(self.src_i, remaining_len)) {
+            return;
+        }
+
+        self.eof_rule_id = match self.rule_stack.last() {
+            Some((_, rule_id)) => Some(rule_id.clone()),
+            None => None,
+        };
+    }
+
+    // spec: 文字列・文字列の選択の表現字句の各値について record_truncated_string_failure() を行う
+    fn record_truncated_expr_failure(&mut self, expr: &Box<RuleExpression>) {
+        let values = match &expr.kind {
+            RuleExpressionKind::String => vec![expr.value.clone()],
+            RuleExpressionKind::AnyOf { values, value_indexes: _, value_lens: _ } => values.clone(),
+            _ => return,
+        };
+
+        for each_value in values {
+            let each_value = if self.is_byte_mode {
+                to_byte_chars(&each_value)
+            } else {
+                each_value
+            };
+
+            self.record_truncated_string_failure(&each_value);
+        }
+    }
+
+    // spec: 先読みの外で表現字句が失敗したことを記録する; 最も先の入力位置で失敗したもののみを保持する
+    // note: 同じ表現字句は位置と値で判別し、一度のみ記録する
+    fn record_expected_expr(&mut self, expr: &Box<RuleExpression>) {
+        if self.lookahead_depth != 0 || self.src_i < self.expected_src_i {
+            return;
+        }
+
+        if self.src_i > self.expected_src_i || self.expected_exprs.len() == 0 {
+            self.expected_src_i = self.src_i;
+            self.expected_exprs.clear();
+            self.expected_rule_stack = *self.rule_stack.clone();
+        }
+
+        if self.expected_exprs.iter().any(|(each_expr, _)| each_expr.pos == expr.pos && each_expr.value == expr.value) {
+            return;
+        }
+
+        let rule_id = match self.rule_stack.last() {
+            Some((_, v)) => v.clone(),
+            None => String::new(),
+        };
+
+        self.expected_exprs.push((expr.clone(), rule_id));
+    }
+
+    fn parse_raw_expr(&mut self, expr: &Box<RuleExpression>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
+        self.stats.expr_eval_count += 1;
+
+        match expr.kind {
+            // note: 空文字列は入力の終端でも成功する
+            RuleExpressionKind::Epsilon => (),
+            _ if self.src_i >= self.src_len => return Ok(None),
+            _ => (),
+        }
+
+        match &expr.kind {
+            // note: 選択と同じく記述順で最初に一致する値を採用するため、一致した値のうちインデックスが最小のものを選ぶ
+            RuleExpressionKind::AnyOf { values, value_indexes, value_lens } => {
+                let matched_value = if self.is_byte_mode {
+                    // note: バイト列モードでは値の文字数が変わるため記述順に比較する
+                    values.iter().find_map(|each_value| self.match_str_value(each_value))
+                } else {
+                    let mut matched_value = Option::<(usize, String)>::None;
+
+                    for each_len in value_lens {
+                        if self.src_i + each_len > self.src_len {
+                            continue;
+                        }
+
+                        let each_value = self.substring_src_content(self.src_i, *each_len);
+
+                        match value_indexes.get(&each_value) {
+                            Some(value_i) if matched_value.as_ref().map_or(true, |(matched_i, _)| value_i < matched_i) => matched_value = Some((*value_i, each_value)),
+                            _ => (),
+                        }
+                    }
+
+                    matched_value.map(|(_, value)| value)
+                };
+
+                return match matched_value {
+                    Some(value) => {
+                        let new_leaf = SyntaxNodeElement::from_leaf_args(self.get_char_position(), value.clone(), expr.ast_reflection_style.clone());
+                        self.add_source_index_by_string(&value);
+                        Ok(Some(vec![new_leaf]))
+                    },
+                    None => {
+                        self.record_truncated_expr_failure(expr);
+                        Ok(None)
+                    },
+                };
+            },
+            RuleExpressionKind::ArgId => {
+                let mut generics_group = Option::<Box<RuleGroup>>::None;
+
+                for each_arg_map in &*self.arg_maps {
+                    match each_arg_map.generics_group.get(&expr.value) {
+                        Some(v) => {
+                            generics_group = Some(v.clone());
+                            break;
+                        },
+                        None => (),
+                    };
+                }
+
+                let result = match &generics_group {
+                    Some(v) => self.parse_group(&RuleElementOrder::Sequential, &v),
+                    None => {
+                        self.append_branch_error(SyntaxParsingLog::UnknownGenericsArgumentID {
+                            arg_id: expr.value.clone(),
+                        });
+
+                        return Ok(None);
+                    },
+                };
+
+                return Ok(SyntaxParser::to_arg_id_expr_elems(expr, result?));
+            },
+            RuleExpressionKind::CharClass => {
+                if self.src_len < self.src_i + 1 {
+                    return Ok(None);
+                }
+
+                if self.is_byte_mode {
+                    // note: バイト列モードでは EOF 用のヌル文字を入力の 0x00 と区別する
+                    if self.is_at_eof_char() {
+                        return Ok(None);
+                    }
+
+                    return self.parse_byte_char_class_expr(expr);
+                }
+
+                // note: Regex パターンが見つからない場合は新しく追加する
+                let pattern = match self.regex_map.get(&expr.value) {
+                    Some(v) => v,
+                    None => {
+                        let pattern = match self.compile_char_class(&expr.value) {
+                            Ok(v) => v,
+                            Err(e) => {
+                                let log = self.to_char_class_error_log(&expr.value, &expr.pos, e);
+                                self.append_branch_error(log);
+
+                                return Ok(None);
+                            },
+                        };
+
+                        self.regex_map.insert(expr.value.clone(), pattern);
+                        self.regex_map.get(&expr.value).unwrap()
+                    },
+                };
+
+                let tar_char = self.substring_src_content(self.src_i, 1);
+
+                if pattern.is_match(&tar_char) {
+                    let new_leaf = SyntaxNodeElement::from_leaf_args(self.get_char_position(), tar_char.clone(), expr.ast_reflection_style.clone());
+                    self.add_source_index_by_string(&tar_char);
+
+                    return Ok(Some(vec![new_leaf]));
+                } else {
+                    return Ok(None);
+                }
+            },
+            RuleExpressionKind::Id => self.parse_id_expr(expr),
+            RuleExpressionKind::IdWithArgs { generics_args, template_args } => {
+                let rule_id = &expr.value;
+
+                match rule_id.as_str() {
+                    "CAPTURE" => return self.parse_capture_primitive(expr, generics_args, template_args),
+                    "MATCH_CAPTURED" => return self.parse_match_captured_primitive(expr, generics_args, template_args),
+                    "INTO" => return self.parse_into_primitive(expr, generics_args, template_args),
+                    "SCAN" => return self.parse_scan_primitive(expr, generics_args, template_args),
+                    // note: 引数のない EPSILON は読み込み時に RuleExpressionKind::Epsilon へ置き換えられるため、ここには引数を持つ場合のみ到達する
+                    "EPSILON" => {
+                        if generics_args.len() != 0 {
+                            self.append_branch_error(SyntaxParsingLog::InvalidGenericsArgumentLength {
+                                pos: expr.pos.clone(),
+                                expected_arg_len: 0,
+                                decl: None,
+                            });
+                        } else {
+                            self.append_branch_error(SyntaxParsingLog::InvalidTemplateArgumentLength {
+                                pos: expr.pos.clone(),
+                                expected_arg_len: 0,
+                                decl: None,
+                            });
+                        }
+
+                        return Ok(None);
+                    },
+                    "JOIN" => {
+                        if !self.check_primitive_argument_lengths(expr, generics_args, template_args, 1) {
+                            return Ok(None);
+                        }
+
+                        // note: 結合したリーフの位置は引数の開始位置とする
+                        let start_pos = self.get_char_position();
+
+                        return match self.parse_group(&RuleElementOrder::Sequential, &generics_args[0])? {
+                            Some(result_elems) => Ok(Some(SyntaxParser::to_joined_leaf_elems(expr, start_pos, result_elems))),
+                            None => Ok(None),
+                        };
+                    },
+                    _ => {
+                        if PRIMITIVE_RULE_NAMES.contains(&rule_id.as_str()) {
+                            self.append_branch_error(SyntaxParsingLog::UncoveredPrimitiveRule {
+                                pos: expr.pos.clone(),
+                                rule_name: rule_id.clone(),
+                            });
+
+                            return Ok(None);
+                        }
+                    },
+                }
+
+                let rule_map = self.rule_map.clone();
+
+                let rule = match self.find_called_rule(&rule_map, expr) {
+                    Some(v) => v,
+                    None => return Ok(None),
+                };
+
+                if !self.check_rule_arguments(expr, rule, generics_args, template_args) {
+                    return Ok(None);
+                }
+
+                // note: 引数のない呼び出しで空の引数マップを積むと呼び出し先の規則がメモ化の対象外となるため積まない
+                if generics_args.len() == 0 && template_args.len() == 0 {
+                    return self.parse_id_expr(expr);
+                }
+
+                let mut new_arg_map = ArgumentMap::new();
+
+                for (each_arg_id, each_arg_group) in rule.generics_arg_ids.iter().zip(generics_args) {
+                    new_arg_map.generics_group.insert(each_arg_id.clone(), each_arg_group.clone());
+                }
+
+                for (each_arg_id, each_arg_group) in rule.template_arg_ids.iter().zip(template_args) {
+                    new_arg_map.template_group.insert(each_arg_id.clone(), each_arg_group.clone());
+                }
+
+                self.arg_maps.push(new_arg_map);
+                let result = self.parse_id_expr(expr);
+                self.arg_maps.pop();
+                return result;
+            },
+            RuleExpressionKind::String => {
+                return match self.match_str_value(&expr.value) {
+                    Some(value) => {
+                        let new_leaf = SyntaxNodeElement::from_leaf_args(self.get_char_position(), value.clone(), expr.ast_reflection_style.clone());
+                        self.add_source_index_by_string(&value);
+                        Ok(Some(vec![new_leaf]))
+                    },
+                    None => {
+                        self.record_truncated_expr_failure(expr);
+                        Ok(None)
+                    },
+                };
+            },
+            // note: 入力から捕捉された値を外部の値より優先する; いずれにもない名前への後方参照は一致しない
+            RuleExpressionKind::CaptureRef => {
+                let captured_value = match self.capture_map.find(&expr.value) {
+                    Some(v) => v.clone(),
+                    None => match self.context.extern_captures.get(&expr.value) {
+                        Some(v) => v.clone(),
+                        None => return Ok(None),
+                    },
+                };
+
+                return Ok(self.parse_captured_value(&captured_value, expr));
+            },
+            RuleExpressionKind::Epsilon => return Ok(Some(Vec::new())),
+            RuleExpressionKind::Wildcard => {
+                if self.src_len < self.src_i + 1 || (self.is_byte_mode && self.is_at_eof_char()) {
+                    return Ok(None);
+                }
+
+                let expr_value = self.substring_src_content(self.src_i, 1);
+                let new_leaf = SyntaxNodeElement::from_leaf_args(self.get_char_position(), expr_value.clone(), expr.ast_reflection_style.clone());
+                self.add_source_index_by_string(&expr_value);
+
+                return Ok(Some(vec![new_leaf]));
+            },
+        }
+    }
+
+    // ret: 入力位置から文字列表現の値が続けば一致した入力の文字列; バイト列モードでは値を UTF-8 のバイト列として照合する
+    // spec: バイト列モードでは \z が EOF 用のヌル文字のみと一致し、その他の文字は EOF 用のヌル文字と一致しない; 文字列モードでは \z と \0 はいずれも U+0000 と一致する
+    fn match_str_value(&self, value: &str) -> Option<String> {
+        let value = if self.is_byte_mode {
+            to_byte_chars(value)
+        } else {
+            value.to_string()
+        };
+
+        let value_len = value.chars().count();
+
+        if self.src_len < self.src_i + value_len {
+            return None;
+        }
+
+        let src_value = self.substring_src_content(self.src_i, value_len);
+
+        let is_matched = src_value.chars().zip(value.chars()).enumerate().all(|(char_i, (src_char, value_char))| {
+            let is_eof_char = self.src_i + char_i + 1 == self.src_len;
+
+            match value_char {
+                EOF_ESCAPE_CHAR if self.is_byte_mode => is_eof_char,
+                EOF_ESCAPE_CHAR => src_char == '\0',
+                _ => src_char == value_char && !(self.is_byte_mode && is_eof_char),
+            }
+        });
+
+        return if is_matched {
+            Some(src_value)
+        } else {
+            None
+        };
+    }
+
+    // ret: 入力位置が EOF 用のヌル文字の位置であれば真
+    fn is_at_eof_char(&self) -> bool {
+        return self.src_i + 1 == self.src_len;
+    }
+
+    // spec: 入力が捕捉された文字列と一致すれば成功し、その文字列のリーフを生成する
+    fn parse_captured_value(&mut self, captured_value: &String, expr: &Box<RuleExpression>) -> Option<Vec<SyntaxNodeElement>> {
+        if self.src_len < self.src_i + captured_value.chars().count() {
+            return None;
+        }
+
+        if self.substring_src_content(self.src_i, captured_value.chars().count()) == *captured_value {
+            let new_leaf = SyntaxNodeElement::from_leaf_args(self.get_char_position(), captured_value.clone(), expr.ast_reflection_style.clone());
+            self.add_source_index_by_string(captured_value);
+
+            return Some(vec![new_leaf]);
+        } else {
+            return None;
+        }
+    }
+
+    // spec: CAPTURE<名前, グループ> はグループに一致した入力文字列を文脈変数として記録し、その文字列のリーフを生成する
+    fn parse_capture_primitive(&mut self, expr: &Box<RuleExpression>, generics_args: &Vec<Box<RuleGroup>>, template_args: &Vec<Box<RuleGroup>>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
+        let (name, start_pos) = match self.begin_capture_primitive(expr, generics_args, template_args)? {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+
+        return match self.parse_group(&RuleElementOrder::Sequential, &generics_args[1])? {
+            Some(_) => Ok(Some(self.end_capture_primitive(expr, name, start_pos))),
+            None => Ok(None),
+        };
+    }
+
+    // ret: (文脈変数名, 引数の開始位置); 引数の数が正しくなければ None
+    fn begin_capture_primitive(&mut self, expr: &RuleExpression, generics_args: &Vec<Box<RuleGroup>>, template_args: &Vec<Box<RuleGroup>>) -> ConsoleResult<Option<(String, CharacterPosition)>> {
+        if !self.check_primitive_argument_lengths(expr, generics_args, template_args, 2) {
+            return Ok(None);
+        }
+
+        let name = self.get_context_name(&generics_args[0])?;
+        // note: リーフの位置は JOIN と同じく引数の開始位置とする
+        return Ok(Some((name, self.get_char_position())));
+    }
+
+    fn end_capture_primitive(&mut self, expr: &RuleExpression, name: String, start_pos: CharacterPosition) -> Vec<SyntaxNodeElement> {
+        let value = self.substring_src_content(start_pos.index, self.src_i - start_pos.index);
+        self.context_map.push(name, value.clone());
+
+        let new_leaf = SyntaxNodeElement::from_leaf_args(start_pos, value, expr.ast_reflection_style.clone());
+        return vec![new_leaf];
+    }
+
+    // spec: MATCH_CAPTURED<名前> は入力が文脈変数の値と一致すれば成功する; 現在のスコープから参照できない名前はエラーとなる
+    fn parse_match_captured_primitive(&mut self, expr: &Box<RuleExpression>, generics_args: &Vec<Box<RuleGroup>>, template_args: &Vec<Box<RuleGroup>>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
+        if !self.check_primitive_argument_lengths(expr, generics_args, template_args, 1) {
+            return Ok(None);
+        }
+
+        let name = self.get_context_name(&generics_args[0])?;
+
+        let context_value = match self.context_map.find(&name) {
+            Some(v) => v.clone(),
+            None => {
+                self.append_branch_error(SyntaxParsingLog::UnknownContextName {
+                    pos: expr.pos.clone(),
+                    name: name,
+                });
+
+                return Ok(None);
+            },
+        };
+
+        return Ok(self.parse_captured_value(&context_value, expr));
+    }
+
+    // spec: INTO<グループ, "変換名"> はグループの結果の要素を登録された変換に渡し、その戻り値のリーフを生成する
+    // note: 変換名の検査はグループの検査より先に行い、入力によらずエラーとする
+    fn parse_into_primitive(&mut self, expr: &Box<RuleExpression>, generics_args: &Vec<Box<RuleGroup>>, template_args: &Vec<Box<RuleGroup>>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
+        let (transform, start_pos) = match self.begin_into_primitive(expr, generics_args, template_args)? {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+
+        return match self.parse_group(&RuleElementOrder::Sequential, &generics_args[0])? {
+            Some(result_elems) => Ok(Some(SyntaxParser::to_transformed_leaf_elems(expr, &transform, start_pos, result_elems))),
+            None => Ok(None),
+        };
+    }
+
+    // spec: SCAN<"走査名"> は登録された走査に構文解析器を渡し、走査が返した値のリーフを生成する
+    // note: リーフの位置は走査の開始位置とする; 走査が失敗した場合は途中で読み進めた分を戻す
+    fn parse_scan_primitive(&mut self, expr: &Box<RuleExpression>, generics_args: &Vec<Box<RuleGroup>>, template_args: &Vec<Box<RuleGroup>>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
+        if !self.check_primitive_argument_lengths(expr, generics_args, template_args, 1) {
+            return Ok(None);
+        }
+
+        let name = match generics_args[0].get_string_value() {
+            Some(v) => v.clone(),
+            None => {
+                self.append_log(SyntaxParsingLog::InvalidRuleElementStructure {
+                    uuid: generics_args[0].uuid.clone(),
+                    msg: "name of scanner must be a string".to_string(),
+                });
+
+                return Err(());
+            },
+        };
+
+        let scanner = match self.primitive_registry.get_scanner(&name) {
+            Some(v) => v.clone(),
+            None => {
+                self.append_branch_error(SyntaxParsingLog::UnknownScannerName {
+                    pos: expr.pos.clone(),
+                    name: name,
+                });
+
+                return Ok(None);
+            },
+        };
+
+        let checkpoint = self.checkpoint();
+        let start_pos = self.get_char_position();
+
+        return match scanner(self) {
+            Some(value) => Ok(Some(vec![SyntaxNodeElement::from_leaf_args(start_pos, value, expr.ast_reflection_style.clone())])),
+            None => {
+                self.restore(checkpoint);
+                Ok(None)
+            },
+        };
+    }
+
+    // ret: (変換, 引数の開始位置); 引数の数が正しくないか変換が登録されていなければ None
+    fn begin_into_primitive(&mut self, expr: &RuleExpression, generics_args: &Vec<Box<RuleGroup>>, template_args: &Vec<Box<RuleGroup>>) -> ConsoleResult<Option<(PrimitiveTransform, CharacterPosition)>> {
+        if !self.check_primitive_argument_lengths(expr, generics_args, template_args, 2) {
+            return Ok(None);
+        }
+
+        let name = match generics_args[1].get_string_value() {
+            Some(v) => v.clone(),
+            None => {
+                self.append_log(SyntaxParsingLog::InvalidRuleElementStructure {
+                    uuid: generics_args[1].uuid.clone(),
+                    msg: "name of transform must be a string".to_string(),
+                });
+
+                return Err(());
+            },
+        };
+
+        let transform = match self.primitive_registry.get(&name) {
+            Some(v) => v.clone(),
+            None => {
+                self.append_branch_error(SyntaxParsingLog::UnknownTransformName {
+                    pos: expr.pos.clone(),
+                    name: name,
+                });
+
+                return Ok(None);
+            },
+        };
+
+        // note: リーフの位置は JOIN と同じく引数の開始位置とする
+        return Ok(Some((transform, self.get_char_position())));
+    }
+
+    fn to_transformed_leaf_elems(expr: &RuleExpression, transform: &PrimitiveTransform, start_pos: CharacterPosition, result_elems: Vec<SyntaxNodeElement>) -> Vec<SyntaxNodeElement> {
+        let new_leaf = SyntaxNodeElement::from_leaf_args(start_pos, transform(result_elems), expr.ast_reflection_style.clone());
+        return vec![new_leaf];
+    }
+
+    // spec: JOIN の引数の結果のうち反映される要素の文字列を結合したリーフを生成する
+    fn to_joined_leaf_elems(expr: &RuleExpression, start_pos: CharacterPosition, result_elems: Vec<SyntaxNodeElement>) -> Vec<SyntaxNodeElement> {
+        let mut joined_str = String::new();
+
+        for each_elem in result_elems {
+            match each_elem {
+                SyntaxNodeElement::Node(node) if node.is_reflectable() => joined_str += &node.join_child_leaf_values(),
+                SyntaxNodeElement::Leaf(leaf) if leaf.is_reflectable() => joined_str += &leaf.value,
+                _ => (),
+            }
+        }
+
+        let new_leaf = SyntaxNodeElement::from_leaf_args(start_pos, joined_str, expr.ast_reflection_style.clone());
+        return vec![new_leaf];
+    }
+
+    // note: 反映されない引数 ID であれば引数グループが生成したすべての要素に反映方式を適用する
+    fn to_arg_id_expr_elems(expr: &RuleExpression, result: Option<Vec<SyntaxNodeElement>>) -> Option<Vec<SyntaxNodeElement>> {
+        if expr.ast_reflection_style.is_reflectable() {
+            return result;
+        }
+
+        return match result {
+            Some(node_elems) => {
+                let new_node_elems = node_elems.into_iter().map(|mut each_node_elem| {
+                    each_node_elem.set_ast_reflection_style(expr.ast_reflection_style.clone());
+                    each_node_elem
+                }).collect::<Vec<SyntaxNodeElement>>();
+
+                Some(new_node_elems)
+            },
+            None => None,
+        };
+    }
+
+    // ret: 引数の数が正しければ true
+    fn check_primitive_argument_lengths(&mut self, expr: &RuleExpression, generics_args: &Vec<Box<RuleGroup>>, template_args: &Vec<Box<RuleGroup>>, expected_generics_arg_len: usize) -> bool {
+        if generics_args.len() != expected_generics_arg_len {
+            self.append_branch_error(SyntaxParsingLog::InvalidGenericsArgumentLength {
+                pos: expr.pos.clone(),
+                expected_arg_len: expected_generics_arg_len,
+                decl: None,
+            });
+
+            return false;
+        }
+
+        if template_args.len() != 0 {
+            self.append_branch_error(SyntaxParsingLog::InvalidTemplateArgumentLength {
+                pos: expr.pos.clone(),
+                expected_arg_len: 0,
+                decl: None,
+            });
+
+            return false;
+        }
+
+        return true;
+    }
+
+    // spec: 文脈変数名は文字列 1 つのみからなる引数で指定する
+    fn get_context_name(&mut self, arg: &Box<RuleGroup>) -> ConsoleResult<String> {
+        return match arg.get_string_value() {
+            Some(v) => Ok(v.clone()),
+            None => {
+                self.append_log(SyntaxParsingLog::InvalidRuleElementStructure {
+                    uuid: arg.uuid.clone(),
+                    msg: "name of context variable must be a string".to_string(),
+                });
+
+                Err(())
+            },
+        };
+    }
+
+    // spec: 文法中のすべての文字クラスを解析前にコンパイルする; キャッシュ済みのパターンは除く
+    // ret: コンパイルに失敗したパターンがあれば位置付きのエラーを出力して false
+    fn precompile_char_classes(&mut self) -> bool {
+        let mut is_successful = true;
+
+        for (each_pattern, each_pos) in self.rule_map.get_char_class_exprs() {
+            let result = if self.is_byte_mode {
+                if self.byte_regex_map.contains_key(&each_pattern) {
+                    continue;
+                }
+
+                self.compile_byte_char_class(&each_pattern).map(|v| {
+                    self.byte_regex_map.insert(each_pattern.clone(), v);
+                })
+            } else {
+                if self.regex_map.contains_key(&each_pattern) {
+                    continue;
+                }
+
+                self.compile_char_class(&each_pattern).map(|v| {
+                    self.regex_map.insert(each_pattern.clone(), v);
+                })
+            };
+
+            match result {
+                Ok(()) => (),
+                Err(e) => {
+                    let log = self.to_char_class_error_log(&each_pattern, &each_pos, e);
+                    self.append_log(log);
+
+                    is_successful = false;
+                },
+            }
+        }
+
+        return is_successful;
+    }
+
+    // ret: 文字クラスのコンパイルに失敗した際のログ; 符号位置として不正なエスケープシーケンスを含めば InvalidUnicodeEscape、それ以外は InvalidCharClassFormat
+    // spec: バイト列モードでは U+00FF を超える値も不正とする
+    fn to_char_class_error_log(&self, pattern: &str, pos: &CharacterPosition, msg: String) -> SyntaxParsingLog {
+        let max_code_point = if self.is_byte_mode {
+            0xff
+        } else {
+            0x10ffff
+        };
+
+        return match find_invalid_unicode_escape(pattern, max_code_point) {
+            Some((char_i, sequence)) => {
+                // note: 文字クラスは 1 行で記述される
+                let mut escape_pos = pos.clone();
+                escape_pos.index += char_i;
+                escape_pos.column += char_i;
+
+                SyntaxParsingLog::InvalidUnicodeEscape {
+                    pos: escape_pos,
+                    sequence: sequence,
+                }
+            },
+            None => SyntaxParsingLog::InvalidCharClassFormat {
+                pos: pos.clone(),
+                value: pattern.to_string(),
+                msg: msg,
+            },
+        };
+    }
+
+    // ret: 失敗時は regex クレートのエラーメッセージ
+    fn compile_char_class(&self, pattern: &str) -> std::result::Result<Regex, String> {
+        return RegexBuilder::new(pattern)
+            .size_limit(self.regex_size_limit)
+            .dfa_size_limit(self.regex_dfa_size_limit)
+            .build()
+            .map_err(|e| e.to_string());
+    }
+
+    fn compile_byte_char_class(&self, pattern: &str) -> std::result::Result<bytes::Regex, String> {
+        return bytes::RegexBuilder::new(&format!("(?-u){}", pattern))
+            .size_limit(self.regex_size_limit)
+            .dfa_size_limit(self.regex_dfa_size_limit)
+            .build()
+            .map_err(|e| e.to_string());
+    }
+
+    // spec: 文字クラスを 1 バイトに対して検査する; パターンは Unicode を無効にしたバイト列用の正規表現として扱う
+    fn parse_byte_char_class_expr(&mut self, expr: &Box<RuleExpression>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
+        // note: Regex パターンが見つからない場合は新しく追加する
+        let pattern = match self.byte_regex_map.get(&expr.value) {
+            Some(v) => v,
+            None => {
+                let pattern = match self.compile_byte_char_class(&expr.value) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        let log = self.to_char_class_error_log(&expr.value, &expr.pos, e);
+                        self.append_branch_error(log);
+
+                        return Ok(None);
+                    },
+                };
+
+                self.byte_regex_map.insert(expr.value.clone(), pattern);
+                self.byte_regex_map.get(&expr.value).unwrap()
+            },
+        };
+
+        let tar_char = self.substring_src_content(self.src_i, 1);
+        let tar_byte = tar_char.chars().next().unwrap() as u32 as u8;
+
+        if pattern.is_match(&[tar_byte]) {
+            let new_leaf = SyntaxNodeElement::from_leaf_args(self.get_char_position(), tar_char.clone(), expr.ast_reflection_style.clone());
+            self.add_source_index_by_string(&tar_char);
+
+            return Ok(Some(vec![new_leaf]));
+        } else {
+            return Ok(None);
+        }
+    }
+
+    // note: 規則が期待する引数の種別 (終端記号/非終端記号) と渡されたグループの種別を照合する
+    // ret: 引数付きの ID 表現字句が呼び出す規則; 規則が存在しなければエラーを記録して None
+    fn find_called_rule<'a>(&mut self, rule_map: &'a RuleMap, expr: &RuleExpression) -> Option<&'a Rule> {
+        return match rule_map.rule_map.get(&expr.value) {
+            Some(v) => Some(v),
+            None => {
+                self.append_branch_error(SyntaxParsingLog::UnknownRuleID {
+                    pos: expr.pos.clone(),
+                    rule_id: expr.value.clone(),
+                });
+
+                None
+            },
+        };
+    }
+
+    // ret: 引数の数と種別が規則の定義と一致すれば true
+    fn check_rule_arguments(&mut self, expr: &RuleExpression, rule: &Rule, generics_args: &Vec<Box<RuleGroup>>, template_args: &Vec<Box<RuleGroup>>) -> bool {
+        if generics_args.len() != rule.generics_arg_ids.len() {
+            self.append_branch_error(SyntaxParsingLog::InvalidGenericsArgumentLength {
+                pos: expr.pos.clone(),
+                expected_arg_len: rule.generics_arg_ids.len(),
+                decl: Some(rule.to_declaration(&rule.generics_arg_ids)),
+            });
+
+            return false;
+        }
+
+        if template_args.len() != rule.template_arg_ids.len() {
+            self.append_branch_error(SyntaxParsingLog::InvalidTemplateArgumentLength {
+                pos: expr.pos.clone(),
+                expected_arg_len: rule.template_arg_ids.len(),
+                decl: Some(rule.to_declaration(&rule.template_arg_ids)),
+            });
+
+            return false;
+        }
+
+        // note: 種別の制約はテンプレート引数のみに適用する
+        for (each_arg_id, each_arg_group) in rule.template_arg_ids.iter().zip(template_args) {
+            if !self.check_argument_kind(&expr.pos, rule, each_arg_id, each_arg_group) {
+                return false;
+            }
+        }
+
+        return true;
+    }
+
+    // ret: 種別が一致しなければエラーを記録して false
+    fn check_argument_kind(&mut self, pos: &CharacterPosition, rule: &Rule, arg_id: &String, arg_group: &RuleGroup) -> bool {
+        let expected_kind = match rule.template_arg_kinds.get(arg_id) {
+            Some(v) => v.clone(),
+            None => return true,
+        };
+
+        let actual_kind = arg_group.get_argument_kind();
+
+        if actual_kind != expected_kind {
+            self.append_branch_error(SyntaxParsingLog::TemplateArgumentTypeMismatch {
+                pos: pos.clone(),
+                arg_id: arg_id.clone(),
+                expected_kind: expected_kind,
+                actual_kind: actual_kind,
+            });
+
+            return false;
+        }
+
+        return true;
+    }
+
+    fn parse_id_expr(&mut self, expr: &Box<RuleExpression>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
+        return match self.parse_rule(&expr.value, &expr.pos)? {
+            Some(node_elem) => Ok(Some(self.to_id_expr_elems(expr, node_elem))),
+            None => Ok(None),
+        };
+    }
+
+    // spec: 規則ノードを ID 表現字句の反映方式で作り直す
+    fn to_id_expr_elems(&self, expr: &RuleExpression, node_elem: SyntaxNodeElement) -> Vec<SyntaxNodeElement> {
+        return match node_elem {
+            SyntaxNodeElement::Node(mut node) => {
+                let sub_ast_reflection_style = if expr.ast_reflection_style.visibility == ASTVisibility::Reflect && expr.ast_reflection_style.name_override.is_none() && !expr.is_ast_reflection_specified {
+                    ASTReflectionStyle::reflection(self.to_rule_reflection_name(&expr.value))
+                } else {
+                    expr.ast_reflection_style.clone()
+                };
+
+                let mut conv_node = SyntaxNodeElement::from_node_args(std::mem::take(&mut node.sub_elems), sub_ast_reflection_style);
+                conv_node.set_src_range(node.src_range);
+
+                if expr.ast_reflection_style.is_expandable() {
+                    match conv_node {
+                        SyntaxNodeElement::Node(mut conv_node) => expr.ast_reflection_style.apply_to_expanded_elems(std::mem::take(&mut conv_node.sub_elems)),
+                        _ => vec![conv_node],
+                    }
+                } else {
+                    vec![conv_node]
+                }
+            },
+            SyntaxNodeElement::Leaf(_) => vec![node_elem],
+        };
+    }
+
+    // spec: 開始規則を反復型の解析器で検査する; 結果とログは parse_rule() と同じとなる
+    // note: エラー時は再帰型の解析器が呼び出しから戻る際と同じく規則の深さ・先読みの深さ・文脈変数を戻す
+    fn parse_rule_iteratively(&mut self, rule_id: &String, pos: &CharacterPosition) -> ConsoleResult<Option<SyntaxNodeElement>> {
+        let rule_map = self.rule_map.clone();
+        let rule_depth = self.rule_depth;
+        let lookahead_depth = self.lookahead_depth;
+        let context_len = self.context_map.len();
+        let result = self.run_frames(&rule_map, rule_id, pos);
+
+        if result.is_err() {
+            self.rule_depth = rule_depth;
+            self.lookahead_depth = lookahead_depth;
+            self.context_map.truncate(context_len);
+        }
+
+        return result;
+    }
+
+    fn run_frames<'a>(&mut self, rule_map: &'a RuleMap, rule_id: &'a String, pos: &'a CharacterPosition) -> ConsoleResult<Option<SyntaxNodeElement>> {
+        let mut frames = vec![ParseFrame::Rule(RuleFrame::new(rule_id, pos))];
+        let mut args = ArgumentStack::new();
+        let mut output = Option::<FrameOutput<'a>>::None;
+
+        loop {
+            let frame = match frames.last_mut() {
+                Some(v) => v,
+                None => return Ok(None),
+            };
+
+            match self.step_frame(rule_map, frame, &mut args, output.take())? {
+                FrameStep::Call(child) => frames.push(child),
+                FrameStep::Return(frame_output) => {
+                    frames.pop();
+
+                    if frames.len() == 0 {
+                        return Ok(frame_output.into_node());
+                    }
+
+                    output = Some(frame_output);
+                },
+            }
+        }
+    }
+
+    // spec: フレームを子フレームの呼び出しもしくはフレームからの戻りまで進める; output は直前に戻った子フレームの戻り値
+    fn step_frame<'a>(&mut self, rule_map: &'a RuleMap, frame: &mut ParseFrame<'a>, args: &mut ArgumentStack<'a>, output: Option<FrameOutput<'a>>) -> ConsoleResult<FrameStep<'a>> {
+        return match frame {
+            ParseFrame::Rule(rule_frame) => self.step_rule_frame(rule_map, rule_frame, output),
+            ParseFrame::Group(group_frame) => self.step_group_frame(group_frame, args, output),
+            ParseFrame::LoopIteration(iteration_frame) => self.step_loop_iteration_frame(iteration_frame, output),
+            ParseFrame::RandomOrder(random_order_frame) => Ok(self.step_random_order_frame(random_order_frame, output)),
+            ParseFrame::RawGroup(raw_group_frame) => Ok(self.step_raw_group_frame(raw_group_frame, output)),
+            ParseFrame::SequenceElem(seq_elem_frame) => Ok(self.step_sequence_elem_frame(seq_elem_frame, output)),
+            ParseFrame::Choice(choice_frame) => Ok(self.step_choice_frame(choice_frame, output)),
+            ParseFrame::Expr(expr_frame) => self.step_expr_frame(expr_frame, output),
+            ParseFrame::NestedExpr(nested_expr_frame) => self.step_nested_expr_frame(rule_map, nested_expr_frame, args, output),
+        };
+    }
+
+    fn step_rule_frame<'a>(&mut self, rule_map: &'a RuleMap, frame: &mut RuleFrame<'a>, output: Option<FrameOutput<'a>>) -> ConsoleResult<FrameStep<'a>> {
+        let output = match output {
+            Some(v) => v,
+            None => {
+                frame.context_len = self.enter_rule()?;
+
+                let (rule_group, alias_rule_id) = match self.begin_raw_rule(rule_map, frame.rule_id, frame.pos) {
+                    Some(v) => v,
+                    None => {
+                        self.leave_rule(frame.rule_id, frame.pos, frame.context_len, false);
+                        return Ok(FrameStep::Return(FrameOutput::Node(None)));
+                    },
+                };
+
+                return match alias_rule_id {
+                    Some(alias_rule_id) => {
+                        frame.state = RuleFrameState::Alias {
+                            alias_rule_id: alias_rule_id,
+                        };
+
+                        Ok(FrameStep::Call(ParseFrame::Rule(RuleFrame::new(alias_rule_id, frame.pos))))
+                    },
+                    None => {
+                        frame.state = RuleFrameState::Group {
+                            rule_group: rule_group,
+                            start_src_i: self.src_i,
+                        };
+
+                        Ok(FrameStep::Call(ParseFrame::Group(GroupFrame::new(&rule_group.elem_order, rule_group, &rule_group.loop_range))))
+                    },
+                };
+            },
+        };
+
+        let result = match frame.state {
+            RuleFrameState::Start => output.into_node(),
+            RuleFrameState::Alias { alias_rule_id } => self.to_alias_rule_node(frame.rule_id, alias_rule_id, output.into_node()),
+            RuleFrameState::Group { rule_group, start_src_i } => match output.into_elems() {
+                Some(v) => Some(self.to_rule_node(frame.rule_id, rule_group, start_src_i, v)),
+                None => None,
+            },
+        };
+
+        self.leave_rule(frame.rule_id, frame.pos, frame.context_len, result.is_some());
+        return Ok(FrameStep::Return(FrameOutput::Node(result)));
+    }
+
+    fn step_group_frame<'a>(&mut self, frame: &mut GroupFrame<'a>, args: &ArgumentStack<'a>, output: Option<FrameOutput<'a>>) -> ConsoleResult<FrameStep<'a>> {
+        match output {
+            Some(output) => {
+                match output.into_iteration() {
+                    LoopIteration::Matched(mut node_elems) => {
+                        frame.children.append(&mut node_elems);
+                        frame.loop_count += 1;
+
+                        let (_, max_count) = frame.loop_counts;
+
+                        if max_count != -1 && frame.loop_count == max_count {
+                            return Ok(self.end_group_frame(frame));
+                        }
+                    },
+                    LoopIteration::TrailingSeparator(mut node_elems) => {
+                        frame.children.append(&mut node_elems);
+                        return Ok(self.end_group_frame(frame));
+                    },
+                    LoopIteration::Unmatched => return Ok(self.end_group_frame(frame)),
+                }
+            },
+            None => {
+                match self.begin_group(frame.parent_elem_order, frame.group, frame.loop_range, args.map_len) {
+                    GroupStart::Started(v) => frame.state = Some(v),
+                    GroupStart::Finished(result) => return Ok(FrameStep::Return(FrameOutput::Elems(result))),
+                }
+
+                frame.lookahead_snapshot = self.checkpoint();
+
+                if !frame.group.lookahead_kind.is_none() {
+                    self.lookahead_depth += 1;
+                }
+
+                frame.loop_counts = self.check_loop_range(frame.loop_range)?;
+            },
+        }
+
+        if self.src_i >= self.src_len {
+            return Ok(self.end_group_frame(frame));
+        }
+
+        if frame.loop_count > self.loop_limit as isize {
+            self.append_too_long_repetition_log(frame.loop_range, Some(frame.group.uuid.clone()));
+            return Err(());
+        }
+
+        let iteration_frame = LoopIterationFrame::new(frame.parent_elem_order, frame.group, frame.loop_count);
+        return Ok(FrameStep::Call(ParseFrame::LoopIteration(iteration_frame)));
+    }
+
+    fn end_group_frame<'a>(&mut self, frame: &mut GroupFrame<'a>) -> FrameStep<'a> {
+        let start_src_i = frame.lookahead_snapshot.src_i;
+        let result = self.end_loop_group(frame.group, std::mem::take(&mut frame.children), frame.loop_count, frame.loop_counts, start_src_i);
+
+        let result = if frame.group.lookahead_kind.is_none() {
+            result
+        } else {
+            self.lookahead_depth -= 1;
+            self.restore(frame.lookahead_snapshot);
+
+            if result.is_some() == (frame.group.lookahead_kind == RuleElementLookaheadKind::Positive) {
+                Some(Vec::new())
+            } else {
+                None
+            }
+        };
 
-                                        is_each_subgroup_matched[subgroup_i] = true;
-                                        break;
-                                    },
-                                    None => self.src_i = elem_start_src_i,
-                                }
-                            },
-                            _ => (),
-                        }
+        match frame.state.take() {
+            Some(state) => self.end_group(frame.group, state, &result),
+            None => (),
+        }
 
-                        subgroup_i += 1;
-                    }
+        return FrameStep::Return(FrameOutput::Elems(result));
+    }
 
-                    if is_each_subgroup_matched.iter().find(|v| !**v).is_none() {
-                        return Ok(Some(children));
-                    }
+    fn step_loop_iteration_frame<'a>(&mut self, frame: &mut LoopIterationFrame<'a>, output: Option<FrameOutput<'a>>) -> ConsoleResult<FrameStep<'a>> {
+        match output {
+            Some(output) if frame.is_item_started => {
+                let state = match frame.state.take() {
+                    Some(v) => v,
+                    None => return Ok(FrameStep::Return(FrameOutput::Iteration(LoopIteration::Unmatched))),
+                };
 
-                    subgroup_i = 0;
+                let iteration = self.end_loop_group_iteration(frame.group, state, output.into_elems());
+                return Ok(FrameStep::Return(FrameOutput::Iteration(iteration)));
+            },
+            Some(output) => {
+                match (frame.state.as_mut(), output.into_elems()) {
+                    (Some(state), Some(node_elems)) => {
+                        state.separator_elems = node_elems;
+                        state.is_separator_matched = true;
+                    },
+                    (Some(state), None) => {
+                        let iteration = self.unmatch_loop_group_separator(state);
+                        return Ok(FrameStep::Return(FrameOutput::Iteration(iteration)));
+                    },
+                    (None, _) => (),
                 }
+            },
+            None => {
+                frame.state = Some(self.begin_loop_group_iteration());
 
-                self.src_i = random_order_start_src_i;
-                Ok(None)
+                match &frame.group.separator {
+                    Some(separator) if frame.loop_count != 0 => return Ok(FrameStep::Call(SyntaxParser::to_separator_frame(separator))),
+                    _ => (),
+                }
             },
-            RuleElementOrder::Sequential => self.parse_raw_group(group),
+        }
+
+        match frame.state.as_mut() {
+            Some(state) => self.begin_loop_group_item(state),
+            None => (),
+        }
+
+        frame.is_item_started = true;
+
+        let item_frame = match frame.parent_elem_order {
+            RuleElementOrder::Random(random_order_loop_range) => {
+                let tar_elems = self.get_random_order_elems(frame.group)?;
+                ParseFrame::RandomOrder(RandomOrderFrame::new(tar_elems, random_order_loop_range, self.checkpoint()))
+            },
+            RuleElementOrder::Sequential => ParseFrame::RawGroup(RawGroupFrame::new(frame.group)),
         };
+
+        return Ok(FrameStep::Call(item_frame));
     }
 
-    fn parse_raw_group(&mut self, group: &Box<RuleGroup>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
-        let mut children = Vec::<SyntaxNodeElement>::new();
+    fn to_separator_frame<'a>(separator: &'a RuleElement) -> ParseFrame<'a> {
+        return match separator {
+            RuleElement::Group(separator_group) => ParseFrame::Group(GroupFrame::new(&RuleElementOrder::Sequential, separator_group, &separator_group.loop_range)),
+            RuleElement::Expression(separator_expr) => ParseFrame::Expr(ExprFrame::new(separator_expr)),
+        };
+    }
 
-        for each_elem in &group.sub_elems {
-            let start_src_i = self.src_i;
+    fn step_random_order_frame<'a>(&mut self, frame: &mut RandomOrderFrame<'a>, output: Option<FrameOutput<'a>>) -> FrameStep<'a> {
+        let mut is_round_finished = false;
 
-            match each_elem {
-                RuleElement::Group(each_group) => {
-                    match each_group.kind {
-                        RuleGroupKind::Choice => {
-                            let mut is_successful = false;
-
-                            for each_sub_elem in &each_group.sub_elems {
-                                match each_sub_elem {
-                                    RuleElement::Group(each_sub_group) => {
-                                        match self.parse_group(&each_group.elem_order, each_sub_group)? {
-                                            Some(v) => {
-                                                if group.sub_elems.len() != 1 {
-                                                    let new_child = SyntaxNodeElement::from_node_args(v, each_sub_group.ast_reflection_style.clone());
-
-                                                    match new_child {
-                                                        SyntaxNodeElement::Node(node) if node.sub_elems.len() == 0 => (),
-                                                        _ => {
-                                                            match new_child {
-                                                                SyntaxNodeElement::Node(new_node) if new_node.ast_reflection_style.is_expandable() => {
-                                                                    children.append(&mut new_node.sub_elems.clone());
-                                                                },
-                                                                _ => children.push(new_child),
-                                                            }
-                                                        },
-                                                    }
-                                                } else {
-                                                    children = v;
-                                                }
-
-                                                is_successful = true;
-                                                break;
-                                            },
-                                            None => {
-                                                self.src_i = start_src_i;
-                                            },
-                                        }
-                                    },
-                                    _ => (),
-                                }
-                            }
+        match output {
+            Some(output) => {
+                match output.into_elems() {
+                    Some(node_elems) => {
+                        if frame.is_each_subgroup_matched[frame.subgroup_i] {
+                            frame.subgroup_i += 1;
+                        } else {
+                            SyntaxParser::push_nonempty_elems(&mut frame.children, node_elems);
+                            frame.is_each_subgroup_matched[frame.subgroup_i] = true;
+                            is_round_finished = true;
+                        }
+                    },
+                    None => {
+                        self.restore(frame.elem_snapshot);
+                        frame.subgroup_i += 1;
+                    },
+                }
+            },
+            None => {
+                if frame.tar_elems.len() == 0 {
+                    self.restore(frame.random_order_snapshot);
+                    return FrameStep::Return(FrameOutput::Elems(None));
+                }
 
-                            if !is_successful {
-                                return Ok(None);
-                            }
-                        },
-                        RuleGroupKind::Sequence => {
-                            match self.parse_group(&each_group.elem_order, each_group)? {
-                                Some(v) => {
-                                    if group.sub_elems.len() != 1 {
-                                        let new_child = SyntaxNodeElement::from_node_args(v, each_group.ast_reflection_style.clone());
-
-                                        match new_child {
-                                            SyntaxNodeElement::Node(node) if node.sub_elems.len() == 0 => (),
-                                            _ => {
-                                                match new_child {
-                                                    SyntaxNodeElement::Node(new_node) if new_node.ast_reflection_style.is_expandable() => {
-                                                        children.append(&mut new_node.sub_elems.clone());
-                                                    },
-                                                    _ => children.push(new_child),
-                                                }
-                                            },
-                                        }
-                                    } else {
-                                        children = v;
-                                    }
-
-                                    continue;
-                                },
-                                None => {
-                                    self.src_i = start_src_i;
-                                    return Ok(None);
-                                },
-                            }
-                        },
+                frame.elem_snapshot = self.checkpoint();
+            },
+        }
+
+        loop {
+            if !is_round_finished {
+                while frame.subgroup_i < frame.tar_elems.len() {
+                    match &frame.tar_elems[frame.subgroup_i] {
+                        RuleElement::Group(subgroup) => return FrameStep::Call(ParseFrame::Group(GroupFrame::new(&RuleElementOrder::Sequential, subgroup, frame.loop_range))),
+                        _ => frame.subgroup_i += 1,
                     }
-                },
-                RuleElement::Expression(each_expr) => {
-                    match self.parse_expr(each_expr)? {
-                        Some(node_elems) => {
-                            for each_elem in node_elems {
-                                match each_elem {
-                                    SyntaxNodeElement::Node(node) if node.sub_elems.len() == 0 => (),
-                                    _ => children.push(each_elem),
-                                }
-                            }
+                }
+            }
 
-                            continue;
-                        },
+            if frame.is_each_subgroup_matched.iter().find(|v| !**v).is_none() {
+                return FrameStep::Return(FrameOutput::Elems(Some(std::mem::take(&mut frame.children))));
+            }
+
+            frame.round_i += 1;
+
+            if frame.round_i >= frame.tar_elems.len() {
+                self.restore(frame.random_order_snapshot);
+                return FrameStep::Return(FrameOutput::Elems(None));
+            }
+
+            frame.subgroup_i = 0;
+            frame.elem_snapshot = self.checkpoint();
+            is_round_finished = false;
+        }
+    }
+
+    fn step_raw_group_frame<'a>(&mut self, frame: &mut RawGroupFrame<'a>, output: Option<FrameOutput<'a>>) -> FrameStep<'a> {
+        match output {
+            Some(output) => {
+                match output.into_elems() {
+                    Some(mut v) => {
+                        frame.children.append(&mut v);
+                        frame.elem_i += 1;
+                    },
+                    None => {
+                        self.record_partial_elems(frame.elem_src_i, &frame.children);
+                        return FrameStep::Return(FrameOutput::Elems(None));
+                    },
+                }
+            },
+            None => (),
+        }
+
+        return match frame.group.sub_elems.get(frame.elem_i) {
+            Some(each_elem) => {
+                frame.elem_src_i = self.src_i;
+                let is_single_elem = frame.group.sub_elems.len() == 1;
+                FrameStep::Call(ParseFrame::SequenceElem(SequenceElemFrame::new(each_elem, is_single_elem, self.checkpoint())))
+            },
+            None => FrameStep::Return(FrameOutput::Elems(Some(std::mem::take(&mut frame.children)))),
+        };
+    }
+
+    fn step_sequence_elem_frame<'a>(&mut self, frame: &mut SequenceElemFrame<'a>, output: Option<FrameOutput<'a>>) -> FrameStep<'a> {
+        let output = match output {
+            Some(v) => v,
+            None => {
+                let child_frame = match frame.elem {
+                    RuleElement::Group(each_group) => match each_group.kind {
+                        RuleGroupKind::Choice => ParseFrame::Choice(ChoiceFrame::new(each_group, frame.snapshot, self.get_capture_lens())),
+                        RuleGroupKind::Sequence => ParseFrame::Group(GroupFrame::new(&each_group.elem_order, each_group, &each_group.loop_range)),
+                    },
+                    RuleElement::Expression(each_expr) => ParseFrame::Expr(ExprFrame::new(each_expr)),
+                };
+
+                return FrameStep::Call(child_frame);
+            },
+        };
+
+        let result = match frame.elem {
+            RuleElement::Group(each_group) => {
+                let (result_group, result) = match each_group.kind {
+                    RuleGroupKind::Choice => match output.into_choice() {
+                        Some(v) => v,
+                        None => return FrameStep::Return(FrameOutput::Elems(None)),
+                    },
+                    RuleGroupKind::Sequence => match output.into_elems() {
+                        Some(v) => (each_group, v),
                         None => {
-                            self.src_i = start_src_i;
-                            return Ok(None);
+                            self.restore(frame.snapshot);
+                            return FrameStep::Return(FrameOutput::Elems(None));
                         },
-                    }
+                    },
+                };
+
+                self.to_sequence_group_elems(result_group, result, frame.snapshot.src_i, frame.is_single_elem)
+            },
+            RuleElement::Expression(_) => match output.into_elems() {
+                Some(node_elems) => {
+                    let mut children = Vec::<SyntaxNodeElement>::new();
+                    SyntaxParser::push_nonempty_elems(&mut children, node_elems);
+                    children
+                },
+                None => {
+                    self.restore(frame.snapshot);
+                    return FrameStep::Return(FrameOutput::Elems(None));
+                },
+            },
+        };
+
+        return FrameStep::Return(FrameOutput::Elems(Some(result)));
+    }
+
+    fn step_choice_frame<'a>(&mut self, frame: &mut ChoiceFrame<'a>, output: Option<FrameOutput<'a>>) -> FrameStep<'a> {
+        let is_longest_match = frame.choice.choice_mode.is_longest_match();
+
+        match (output, frame.sub_group) {
+            (Some(output), Some(sub_group)) => {
+                match output.into_elems() {
+                    Some(v) if is_longest_match => self.update_longest_match(&mut frame.longest_match, sub_group, v, frame.capture_lens),
+                    Some(v) => return FrameStep::Return(FrameOutput::Choice(Some((sub_group, v)))),
+                    None => (),
                 }
+
+                self.restore(frame.snapshot);
+                frame.sub_elem_i += 1;
+            },
+            _ => (),
+        }
+
+        loop {
+            match frame.choice.sub_elems.get(frame.sub_elem_i) {
+                Some(RuleElement::Group(each_sub_group)) => {
+                    frame.sub_group = Some(each_sub_group);
+                    return FrameStep::Call(ParseFrame::Group(GroupFrame::new(&frame.choice.elem_order, each_sub_group, &each_sub_group.loop_range)));
+                },
+                Some(RuleElement::Expression(_)) => frame.sub_elem_i += 1,
+                None => break,
             }
         }
 
-        return Ok(Some(children));
+        return if is_longest_match {
+            FrameStep::Return(FrameOutput::Choice(self.end_longest_match_choice(frame.longest_match.take())))
+        } else {
+            FrameStep::Return(FrameOutput::Choice(None))
+        };
     }
 
-    fn parse_expr(&mut self, expr: &Box<RuleExpression>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
-        return self.parse_lookahead_expr(expr);
+    fn step_expr_frame<'a>(&mut self, frame: &mut ExprFrame<'a>, output: Option<FrameOutput<'a>>) -> ConsoleResult<FrameStep<'a>> {
+        match output {
+            Some(output) => {
+                match self.update_loop_expr_frame(frame, output.into_elems()) {
+                    Some(result) => return Ok(self.end_expr_frame(frame, result)),
+                    None => (),
+                }
+            },
+            None => {
+                frame.start_src_i = self.src_i;
+                frame.capture_lens = self.get_capture_lens();
+                frame.lookahead_snapshot = self.checkpoint();
+
+                if !frame.expr.lookahead_kind.is_none() {
+                    self.lookahead_depth += 1;
+                }
+
+                // note: 空文字列は入力を消費しないため繰り返しの範囲によらず 1 度だけ検査する
+                match frame.expr.kind {
+                    RuleExpressionKind::Epsilon => {
+                        let result = self.parse_raw_expr(frame.expr)?;
+                        return Ok(self.end_expr_frame(frame, result));
+                    },
+                    _ => (),
+                }
+
+                frame.loop_counts = self.check_loop_range(&frame.expr.loop_range)?;
+                frame.is_coalescible = self.is_coalescible_expr(frame.expr);
+            },
+        }
+
+        while self.src_i < self.src_len {
+            if frame.loop_count > self.loop_limit {
+                self.append_too_long_repetition_log(&frame.expr.loop_range, None);
+                return Err(());
+            }
+
+            if SyntaxParser::is_nested_expr(frame.expr) {
+                return Ok(FrameStep::Call(ParseFrame::NestedExpr(NestedExprFrame::new(frame.expr))));
+            }
+
+            let result = self.parse_raw_expr(frame.expr)?;
+
+            match self.update_loop_expr_frame(frame, result) {
+                Some(result) => return Ok(self.end_expr_frame(frame, result)),
+                None => (),
+            }
+        }
+
+        let result = self.end_loop_expr(std::mem::take(&mut frame.children), frame.loop_count, frame.loop_counts);
+        return Ok(self.end_expr_frame(frame, result));
     }
 
-    fn parse_lookahead_expr(&mut self, expr: &Box<RuleExpression>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
-        return if expr.lookahead_kind.is_none() {
-            self.parse_loop_expr(expr)
-        } else {
-            let start_src_i = self.src_i;
-            let is_lookahead_positive = expr.lookahead_kind == RuleElementLookaheadKind::Positive;
+    // ret: 繰り返しが終われば Some(繰り返しの結果); 続けて検査する場合は None
+    fn update_loop_expr_frame(&mut self, frame: &mut ExprFrame, result: Option<Vec<SyntaxNodeElement>>) -> Option<Option<Vec<SyntaxNodeElement>>> {
+        return match result {
+            Some(node_elems) => {
+                SyntaxParser::push_loop_expr_elems(&mut frame.children, node_elems, frame.is_coalescible);
+                frame.loop_count += 1;
 
-            let result = self.parse_loop_expr(expr)?;
-            self.src_i = start_src_i;
+                let (_, max_count) = frame.loop_counts;
 
-            if result.is_some() == is_lookahead_positive {
-                Ok(Some(Vec::new()))
+                if max_count != -1 && frame.loop_count as isize == max_count {
+                    Some(Some(std::mem::take(&mut frame.children)))
+                } else {
+                    None
+                }
+            },
+            None => {
+                self.record_expected_expr(frame.expr);
+                Some(self.end_loop_expr(std::mem::take(&mut frame.children), frame.loop_count, frame.loop_counts))
+            },
+        };
+    }
+
+    fn end_expr_frame<'a>(&mut self, frame: &mut ExprFrame<'a>, result: Option<Vec<SyntaxNodeElement>>) -> FrameStep<'a> {
+        let result = if frame.expr.lookahead_kind.is_none() {
+            result
+        } else {
+            self.lookahead_depth -= 1;
+            self.restore(frame.lookahead_snapshot);
+
+            if result.is_some() == (frame.expr.lookahead_kind == RuleElementLookaheadKind::Positive) {
+                Some(Vec::new())
             } else {
-                Ok(None)
+                None
+            }
+        };
+
+        self.update_captures(&frame.expr.capture_name, frame.start_src_i, frame.capture_lens, result.is_some());
+        return FrameStep::Return(FrameOutput::Elems(result));
+    }
+
+    // ret: 規則もしくはグループを検査する表現字句であれば true; MATCH_CAPTURED や引数付きの EPSILON 等はその場で検査する
+    fn is_nested_expr(expr: &RuleExpression) -> bool {
+        return match &expr.kind {
+            RuleExpressionKind::ArgId | RuleExpressionKind::Id => true,
+            RuleExpressionKind::IdWithArgs { generics_args: _, template_args: _ } => match expr.value.as_str() {
+                "CAPTURE" | "INTO" | "JOIN" => true,
+                rule_id => !PRIMITIVE_RULE_NAMES.contains(&rule_id),
+            },
+            _ => false,
+        };
+    }
+
+    fn step_nested_expr_frame<'a>(&mut self, rule_map: &'a RuleMap, frame: &mut NestedExprFrame<'a>, args: &mut ArgumentStack<'a>, output: Option<FrameOutput<'a>>) -> ConsoleResult<FrameStep<'a>> {
+        let output = match output {
+            Some(v) => v,
+            None => {
+                self.stats.expr_eval_count += 1;
+                return self.begin_nested_expr_frame(rule_map, frame, args);
+            },
+        };
+
+        let expr = frame.expr;
+
+        let result = match std::mem::replace(&mut frame.state, NestedExprState::Start) {
+            NestedExprState::Start => None,
+            NestedExprState::ArgId => SyntaxParser::to_arg_id_expr_elems(expr, output.into_elems()),
+            NestedExprState::Id => match output.into_node() {
+                Some(node_elem) => Some(self.to_id_expr_elems(expr, node_elem)),
+                None => None,
+            },
+            NestedExprState::Capture { name, start_pos } => match output.into_elems() {
+                Some(_) => Some(self.end_capture_primitive(expr, name, start_pos)),
+                None => None,
+            },
+            NestedExprState::Into { transform, start_pos } => match output.into_elems() {
+                Some(result_elems) => Some(SyntaxParser::to_transformed_leaf_elems(expr, &transform, start_pos, result_elems)),
+                None => None,
+            },
+            NestedExprState::Join { start_pos } => match output.into_elems() {
+                Some(result_elems) => Some(SyntaxParser::to_joined_leaf_elems(expr, start_pos, result_elems)),
+                None => None,
+            },
+            NestedExprState::RuleCall { generics_arg_len, has_args } => {
+                if has_args {
+                    args.generics_args.truncate(generics_arg_len);
+                    args.map_len -= 1;
+                }
+
+                match output.into_node() {
+                    Some(node_elem) => Some(self.to_id_expr_elems(expr, node_elem)),
+                    None => None,
+                }
+            },
+        };
+
+        return Ok(FrameStep::Return(FrameOutput::Elems(result)));
+    }
+
+    fn begin_nested_expr_frame<'a>(&mut self, rule_map: &'a RuleMap, frame: &mut NestedExprFrame<'a>, args: &mut ArgumentStack<'a>) -> ConsoleResult<FrameStep<'a>> {
+        let expr = frame.expr;
+
+        let (generics_args, template_args) = match &expr.kind {
+            RuleExpressionKind::ArgId => {
+                return match args.generics_args.iter().find(|(each_arg_id, _)| **each_arg_id == expr.value) {
+                    Some((_, arg_group)) => {
+                        frame.state = NestedExprState::ArgId;
+                        Ok(FrameStep::Call(ParseFrame::Group(GroupFrame::new(&RuleElementOrder::Sequential, arg_group, &arg_group.loop_range))))
+                    },
+                    None => {
+                        self.append_branch_error(SyntaxParsingLog::UnknownGenericsArgumentID {
+                            arg_id: expr.value.clone(),
+                        });
+
+                        Ok(FrameStep::Return(FrameOutput::Elems(None)))
+                    },
+                };
+            },
+            RuleExpressionKind::Id => {
+                frame.state = NestedExprState::Id;
+                return Ok(FrameStep::Call(ParseFrame::Rule(RuleFrame::new(&expr.value, &expr.pos))));
+            },
+            RuleExpressionKind::IdWithArgs { generics_args, template_args } => (generics_args, template_args),
+            _ => return Ok(FrameStep::Return(FrameOutput::Elems(self.parse_raw_expr(expr)?))),
+        };
+
+        let arg_group = match expr.value.as_str() {
+            "CAPTURE" => match self.begin_capture_primitive(expr, generics_args, template_args)? {
+                Some((name, start_pos)) => {
+                    frame.state = NestedExprState::Capture {
+                        name: name,
+                        start_pos: start_pos,
+                    };
+
+                    &generics_args[1]
+                },
+                None => return Ok(FrameStep::Return(FrameOutput::Elems(None))),
+            },
+            "INTO" => match self.begin_into_primitive(expr, generics_args, template_args)? {
+                Some((transform, start_pos)) => {
+                    frame.state = NestedExprState::Into {
+                        transform: transform,
+                        start_pos: start_pos,
+                    };
+
+                    &generics_args[0]
+                },
+                None => return Ok(FrameStep::Return(FrameOutput::Elems(None))),
+            },
+            "JOIN" => {
+                if !self.check_primitive_argument_lengths(expr, generics_args, template_args, 1) {
+                    return Ok(FrameStep::Return(FrameOutput::Elems(None)));
+                }
+
+                frame.state = NestedExprState::Join {
+                    start_pos: self.get_char_position(),
+                };
+
+                &generics_args[0]
+            },
+            _ => {
+                let rule = match self.find_called_rule(rule_map, expr) {
+                    Some(v) => v,
+                    None => return Ok(FrameStep::Return(FrameOutput::Elems(None))),
+                };
+
+                if !self.check_rule_arguments(expr, rule, generics_args, template_args) {
+                    return Ok(FrameStep::Return(FrameOutput::Elems(None)));
+                }
+
+                // note: 引数のない呼び出しはメモ化の対象外とならないよう引数を積まない
+                let has_args = generics_args.len() != 0 || template_args.len() != 0;
+                let generics_arg_len = args.generics_args.len();
+
+                if has_args {
+                    args.generics_args.extend(rule.generics_arg_ids.iter().zip(generics_args.iter()));
+                    args.map_len += 1;
+                }
+
+                frame.state = NestedExprState::RuleCall {
+                    generics_arg_len: generics_arg_len,
+                    has_args: has_args,
+                };
+
+                return Ok(FrameStep::Call(ParseFrame::Rule(RuleFrame::new(&expr.value, &expr.pos))));
+            },
+        };
+
+        return Ok(FrameStep::Call(ParseFrame::Group(GroupFrame::new(&RuleElementOrder::Sequential, arg_group, &arg_group.loop_range))));
+    }
+
+    // ret: qualify_rule_names が偽であれば規則 ID から名前空間 (ファイルエイリアス名とブロック名) を除いた規則名
+    // note: 別名の規則 ID であれば参照先の規則の名前とし、別名を経由しても同じ構文木となるようにする
+    fn to_rule_reflection_name(&self, rule_id: &str) -> String {
+        let rule_id = if self.rule_map.has_aliases {
+            match self.rule_map.rule_map.get(rule_id) {
+                Some(rule) => rule.get_canonical_id().as_str(),
+                None => rule_id,
+            }
+        } else {
+            rule_id
+        };
+
+        if self.qualify_rule_names {
+            return rule_id.to_string();
+        }
+
+        return match rule_id.rfind(".") {
+            Some(i) => rule_id[i + 1..].to_string(),
+            None => rule_id.to_string(),
+        };
+    }
+
+    fn substring_src_content(&self, start_i: usize, len: usize) -> String {
+        if self.is_ascii_src {
+            let end_i = start_i.saturating_add(len).min(self.src_content.len());
+            return self.src_content[start_i.min(end_i)..end_i].to_string();
+        }
+
+        return self.src_content.chars().skip(start_i).take(len).collect::<String>();
+    }
+
+    fn add_source_index_by_string(&mut self, expr_str: &String) {
+        self.src_i += expr_str.chars().count();
+    }
+
+    fn get_line_start_indexes(src_content: &String) -> Vec<usize> {
+        let mut line_start_indexes = vec![0usize];
+
+        for (char_i, each_char) in src_content.chars().enumerate() {
+            if each_char == '\n' {
+                line_start_indexes.push(char_i + 1);
             }
         }
+
+        return line_start_indexes;
     }
 
-    fn parse_loop_expr(&mut self, expr: &Box<RuleExpression>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
-        let (min_count, max_count) = expr.loop_range.to_tuple();
+    // ret: EOF 用のヌル文字を除いた入力の末尾の位置
+    fn get_end_of_input_position(&self) -> CharacterPosition {
+        let mut end_pos = self.get_char_position();
 
-        if max_count != -1 && min_count as isize > max_count {
-            self.cons.borrow_mut().append_log(SyntaxParsingLog::InvalidLoopRange {
-                msg: format!("invalid loop range {{{},{}}} was detected", min_count, max_count),
-            }.get_log());
+        for each_char in self.src_content.chars().skip(self.src_i).take(self.src_len.saturating_sub(self.src_i + 1)) {
+            end_pos.index += 1;
 
-            return Err(());
+            if each_char == '\n' {
+                end_pos.line += 1;
+                end_pos.column = 0;
+            } else {
+                end_pos.column += 1;
+            }
         }
 
-        let mut children = Vec::<SyntaxNodeElement>::new();
-        let mut loop_count = 0usize;
+        return end_pos;
+    }
 
-        while self.src_i < self.src_content.chars().count() {
-            if loop_count > self.loop_limit {
-                self.cons.borrow_mut().append_log(SyntaxParsingLog::TooLongRepetition {
-                    loop_limit: self.loop_limit as usize
-                }.get_log());
+    fn get_char_position(&self) -> CharacterPosition {
+        return self.get_char_position_at(self.src_i);
+    }
 
-                return Err(());
-            }
+    fn get_char_position_at(&self, src_i: usize) -> CharacterPosition {
+        let line = self.line_start_indexes.partition_point(|each_line_start_i| *each_line_start_i <= src_i) - 1;
+        let column = src_i - self.line_start_indexes[line];
 
-            match self.parse_raw_expr(expr)? {
-                Some(node) => {
-                    for each_node in node {
-                        match each_node {
-                            SyntaxNodeElement::Node(node) if node.sub_elems.len() == 0 => (),
-                            _ => children.push(each_node),
-                        }
-                    }
+        return CharacterPosition::new(Some(self.src_path.clone()), src_i, line, column);
+    }
+}
 
-                    loop_count += 1;
+// spec: SyntaxParser::parse_iter() が返すイテレータ; 開始規則の連接の要素を 1 つずつ解析し、ルートノードの子要素となる要素を返す
+// spec: 解析に失敗した場合はログを出力して Err を返し、以降は何も返さない
+// note: 返した要素は保持せず、開始規則の直下の繰り返しを 1 回検査するごとにメモ化結果を破棄するため、メモリ使用量は入力の大きさによらない
+pub struct SyntaxNodeIter {
+    parser: SyntaxParser,
+    start_rule_id: String,
+    // note: 開始規則の連接の要素; 逐次的に解析できない規則であれば None とし、入力全体を解析してから子要素を返す
+    seq_elems: Option<Vec<RuleElement>>,
+    elem_i: usize,
+    loop_count: isize,
+    pending_elems: VecDeque<SyntaxNodeElement>,
+    is_started: bool,
+    is_finished: bool,
+}
 
-                    if max_count != -1 && loop_count as isize == max_count {
-                        return Ok(Some(children));
-                    }
-                },
-                None => {
-                    return if loop_count >= min_count && (max_count == -1 || loop_count as isize <= max_count) {
-                        Ok(Some(children))
-                    } else {
-                        Ok(None)
-                    }
-                },
-            }
-        }
+impl SyntaxNodeIter {
+    fn new(parser: SyntaxParser) -> SyntaxNodeIter {
+        return SyntaxNodeIter {
+            parser: parser,
+            start_rule_id: String::new(),
+            seq_elems: None,
+            elem_i: 0,
+            loop_count: 0,
+            pending_elems: VecDeque::new(),
+            is_started: false,
+            is_finished: false,
+        };
+    }
 
-        return if loop_count >= min_count && (max_count == -1 || loop_count as isize <= max_count) {
-            Ok(Some(children))
-        } else {
-            Ok(None)
-        }
+    // spec: ログや統計情報の取得に用いる
+    pub fn get_parser(&self) -> &SyntaxParser {
+        return &self.parser;
     }
 
-    fn parse_raw_expr(&mut self, expr: &Box<RuleExpression>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
-        if self.src_i >= self.src_content.chars().count() {
-            return Ok(None);
+    // ret: 規則が選択を持たず、修飾のない連接のみからなる場合はその要素
+    fn get_streamable_elems(rule: &Rule) -> Option<Vec<RuleElement>> {
+        if rule.generics_arg_ids.len() != 0 || rule.template_arg_ids.len() != 0 || rule.group.get_alias_rule_id().is_some() {
+            return None;
         }
 
-        match &expr.kind {
-            RuleExpressionKind::ArgId => {
-                let mut generics_group = Option::<Box<RuleGroup>>::None;
-
-                for each_arg_map in &*self.arg_maps {
-                    match each_arg_map.generics_group.get(&expr.value) {
-                        Some(v) => {
-                            generics_group = Some(v.clone());
-                            break;
-                        },
-                        None => (),
-                    };
-                }
-
-                let result = match &generics_group {
-                    Some(v) => self.parse_group(&RuleElementOrder::Sequential, &v),
-                    None => {
-                        self.cons.borrow_mut().append_log(SyntaxParsingLog::UnknownGenericsArgumentID {
-                            arg_id: expr.value.clone(),
-                        }.get_log());
+        let mut group = &rule.group;
 
-                        return Err(());
-                    },
-                };
+        // note: 規則のグループは (規則全体, 選択もしくは連接, 連接) の 3 段からなる
+        for _ in 0..2 {
+            if !SyntaxNodeIter::is_plain_sequence(group) {
+                return None;
+            }
 
-                return if !expr.ast_reflection_style.is_reflectable() {
-                    match &result {
-                        Ok(v) => {
-                            match v {
-                                Some(node_elems) => {
-                                    match node_elems.get(0) {
-                                        Some(each_node_elem) => {
-                                            let mut new_node_elem = each_node_elem.clone();
-                                            new_node_elem.set_ast_reflection_style(expr.ast_reflection_style.clone());
-                                            Ok(Some(vec![new_node_elem]))
-                                        },
-                                        _ => result,
-                                    }
-                                },
-                                None => result,
-                            }
-                        },
-                        Err(()) => result,
-                    }
-                } else {
-                    result
-                };
-            },
-            RuleExpressionKind::CharClass => {
-                if self.src_content.chars().count() < self.src_i + 1 {
-                    return Ok(None);
-                }
+            group = match group.sub_elems.as_slice() {
+                [RuleElement::Group(sub_group)] => sub_group,
+                _ => return None,
+            };
+        }
 
-                // note: Regex パターンが見つからない場合は新しく追加する
-                let pattern = match self.regex_map.get(&expr.value) {
-                    Some(v) => v,
-                    None => {
-                        let pattern = match Regex::new(&expr.value.clone()) {
-                            Ok(v) => v,
-                            Err(_) => {
-                                self.cons.borrow_mut().append_log(SyntaxParsingLog::InvalidCharClassFormat {
-                                    value: expr.to_string(),
-                                }.get_log());
+        return if SyntaxNodeIter::is_plain_sequence(group) {
+            Some(group.sub_elems.clone())
+        } else {
+            None
+        };
+    }
 
-                                return Err(());
-                            },
-                        };
+    fn is_plain_sequence(group: &RuleGroup) -> bool {
+        return group.kind == RuleGroupKind::Sequence && group.lookahead_kind.is_none() && group.loop_range.is_single_loop() && !group.elem_order.is_random() && !group.has_presence_marker && group.capture_name.is_none() && group.separator.is_none();
+    }
 
-                        self.regex_map.insert(expr.value.clone(), pattern);
-                        self.regex_map.get(&expr.value).unwrap()
-                    },
-                };
+    // ret: 繰り返しを 1 回ずつ返せる要素であれば真
+    // note: 繰り返し全体が 1 つのノードとなるグループ (反映的な連接の要素) は対象外とする
+    fn is_streamable_elem(elem: &RuleElement, is_single_elem: bool) -> bool {
+        return match elem {
+            RuleElement::Group(group) => group.kind == RuleGroupKind::Sequence && group.lookahead_kind.is_none() && !group.loop_range.is_single_loop() && !group.elem_order.is_random() && !group.has_presence_marker && group.capture_name.is_none() && (is_single_elem || group.ast_reflection_style.is_expandable()),
+            RuleElement::Expression(expr) => expr.lookahead_kind.is_none() && !expr.loop_range.is_single_loop() && expr.capture_name.is_none(),
+        };
+    }
 
-                let tar_char = self.substring_src_content(self.src_i, 1);
+    fn start(&mut self) -> ConsoleResult<()> {
+        let (start_rule_id, _) = self.parser.get_start_rule()?;
 
-                if pattern.is_match(&tar_char) {
-                    let new_leaf = SyntaxNodeElement::from_leaf_args(self.get_char_position(), tar_char.clone(), expr.ast_reflection_style.clone());
-                    self.add_source_index_by_string(&tar_char);
+        self.seq_elems = match self.parser.rule_map.rule_map.get(&start_rule_id) {
+            Some(rule) => SyntaxNodeIter::get_streamable_elems(rule),
+            None => None,
+        };
 
-                    return Ok(Some(vec![new_leaf]));
-                } else {
-                    return Ok(None);
+        match self.seq_elems {
+            Some(_) => {
+                if !self.parser.precompile_char_classes() {
+                    return Err(());
                 }
-            },
-            RuleExpressionKind::Id => self.parse_id_expr(expr),
-            RuleExpressionKind::IdWithArgs { generics_args, template_args } => {
-                let rule_id = &expr.value;
-                let mut new_arg_map = ArgumentMap::new();
-
-                match rule_id.as_str() {
-                    "JOIN" => {
-                        match generics_args.get(0) {
-                            Some(tar_arg) if generics_args.len() == 1 => {
-                                if template_args.len() != 0 {
-                                    self.cons.borrow_mut().append_log(SyntaxParsingLog::InvalidTemplateArgumentLength {
-                                        pos: expr.pos.clone(),
-                                        expected_arg_len: 0,
-                                    }.get_log());
-
-                                    return Err(());
-                                }
 
-                                return match self.parse_group(&RuleElementOrder::Sequential, tar_arg)? {
-                                    Some(result_elems) => {
-                                        let mut joined_str = String::new();
+                // note: 開始規則の呼び出しを parse_rule() と同様に記録する
+                let rule_pos = self.parser.get_char_position();
+                self.parser.rule_stack.push((rule_pos, start_rule_id.clone()));
+                self.parser.stats.rule_invocation_count += 1;
+                self.parser.rule_depth += 1;
+                self.parser.stats.peak_rule_depth = self.parser.stats.peak_rule_depth.max(self.parser.rule_depth);
+            },
+            None => {
+                let tree = self.parser.run()?;
 
-                                        for each_elem in result_elems {
-                                            match each_elem {
-                                                SyntaxNodeElement::Node(node) if node.is_reflectable() => joined_str += &node.join_child_leaf_values(),
-                                                SyntaxNodeElement::Leaf(leaf) if leaf.is_reflectable() => joined_str += &leaf.value,
-                                                _ => (),
-                                            }
-                                        }
+                match tree.get_child_ref() {
+                    SyntaxNodeElement::Node(root_node) => self.pending_elems.extend(root_node.sub_elems.iter().cloned()),
+                    SyntaxNodeElement::Leaf(_) => (),
+                }
 
-                                        let new_leaf = SyntaxNodeElement::from_leaf_args(self.get_char_position(), joined_str, expr.ast_reflection_style.clone());
-                                        Ok(Some(vec![new_leaf]))
-                                    },
-                                    None => Ok(None),
-                                };
-                            },
-                            _ => {
-                                self.cons.borrow_mut().append_log(SyntaxParsingLog::InvalidGenericsArgumentLength {
-                                    pos: expr.pos.clone(),
-                                    expected_arg_len: 1,
-                                }.get_log());
+                self.is_finished = true;
+            },
+        }
 
-                                return Err(());
-                            },
-                        }
-                    },
-                    _ => {
-                        if PRIMITIVE_RULE_NAMES.contains(&rule_id.as_str()) {
-                            self.cons.borrow_mut().append_log(SyntaxParsingLog::UncoveredPrimitiveRule {
-                                pos: expr.pos.clone(),
-                                rule_name: rule_id.clone(),
-                            }.get_log());
+        self.start_rule_id = start_rule_id;
+        return Ok(());
+    }
 
-                            return Err(());
-                        }
-                    },
-                }
+    // spec: 要素もしくは繰り返しの 1 回分を解析して結果を pending_elems に加える
+    // ret: すべての要素を解析し終えれば偽
+    fn advance(&mut self) -> ConsoleResult<bool> {
+        let seq_elems = match self.seq_elems.take() {
+            Some(v) => v,
+            None => return Ok(false),
+        };
 
-                let (generics_arg_ids, template_arg_ids) = match self.rule_map.rule_map.get(rule_id) {
-                    Some(rule) => (&rule.generics_arg_ids, &rule.template_arg_ids),
-                    None => {
-                        self.cons.borrow_mut().append_log(SyntaxParsingLog::UnknownRuleID {
-                            pos: expr.pos.clone(),
-                            rule_id: rule_id.clone(),
-                        }.get_log());
+        let result = self.advance_elem(&seq_elems);
+        self.seq_elems = Some(seq_elems);
+        let is_continued = result?;
 
-                        return Err(());
-                    },
-                };
+        // note: 分岐内で記録したエラーがあればその時点で失敗とする
+        if self.parser.has_branch_error {
+            return Err(());
+        }
 
-                if generics_args.len() != generics_arg_ids.len() {
-                    self.cons.borrow_mut().append_log(SyntaxParsingLog::InvalidGenericsArgumentLength {
-                        pos: expr.pos.clone(),
-                        expected_arg_len: generics_arg_ids.len(),
-                    }.get_log());
+        return Ok(is_continued);
+    }
 
+    fn advance_elem(&mut self, seq_elems: &Vec<RuleElement>) -> ConsoleResult<bool> {
+        let elem = match seq_elems.get(self.elem_i) {
+            Some(v) => v,
+            None => {
+                // note: 入力位置が length を超えると失敗
+                if self.parser.src_i < self.parser.src_len {
+                    self.parser.append_unconsumed_input_log();
                     return Err(());
                 }
 
-                if template_args.len() != template_arg_ids.len() {
-                    self.cons.borrow_mut().append_log(SyntaxParsingLog::InvalidTemplateArgumentLength {
-                        pos: expr.pos.clone(),
-                        expected_arg_len: template_arg_ids.len(),
-                    }.get_log());
+                self.parser.rule_stack.pop();
+                self.parser.rule_depth -= 1;
+                return Ok(false);
+            },
+        };
 
-                    return Err(());
-                }
+        let is_single_elem = seq_elems.len() == 1;
 
-                for i in 0..generics_arg_ids.len() {
-                    let new_arg_id = match generics_arg_ids.get(i) {
-                        Some(v) => v,
-                        None => {
-                            self.cons.borrow_mut().append_log(SyntaxParsingLog::UnknownGenericsArgumentID {
-                                arg_id: format!("[{}]", i),
-                            }.get_log());
+        if !SyntaxNodeIter::is_streamable_elem(elem, is_single_elem) {
+            match self.parser.parse_sequence_elem(elem, is_single_elem)? {
+                Some(node_elems) => self.pending_elems.extend(node_elems),
+                None => {
+                    self.parser.append_unmatched_log(&self.start_rule_id);
+                    return Err(());
+                },
+            }
 
-                            return Err(());
-                        },
-                    };
+            self.elem_i += 1;
+            self.parser.memoized_map.clear();
+            return Ok(true);
+        }
 
-                    let new_arg_group = match generics_args.get(i) {
-                        Some(v) => v,
-                        None => {
-                            self.cons.borrow_mut().append_log(SyntaxParsingLog::UnknownGenericsArgumentID {
-                                arg_id: format!("[{}]", i),
-                            }.get_log());
+        let loop_range = match elem {
+            RuleElement::Group(group) => &group.loop_range,
+            RuleElement::Expression(expr) => &expr.loop_range,
+        };
 
-                            return Err(());
-                        }
-                    };
+        let (min_count, max_count) = loop_range.to_tuple();
+        let is_max_reached = max_count != -1 && self.loop_count >= max_count;
+        let mut is_loop_finished = true;
 
-                    new_arg_map.generics_group.insert(new_arg_id.clone(), new_arg_group.clone());
-                }
+        if !is_max_reached && self.parser.src_i < self.parser.src_len {
+            if self.loop_count > self.parser.loop_limit as isize {
+                let group_uuid = match elem {
+                    RuleElement::Group(group) => Some(group.uuid.clone()),
+                    RuleElement::Expression(_) => None,
+                };
 
-                for i in 0..template_arg_ids.len() {
-                    let new_arg_id = match template_arg_ids.get(i) {
-                        Some(v) => v,
-                        None => {
-                            self.cons.borrow_mut().append_log(SyntaxParsingLog::UnknownTemplateArgumentID {
-                                arg_id: format!("[{}]", i),
-                            }.get_log());
+                self.parser.append_too_long_repetition_log(loop_range, group_uuid);
+                return Err(());
+            }
 
-                            return Err(());
+            match elem {
+                RuleElement::Group(group) => {
+                    match self.parser.parse_loop_group_iteration(&group.elem_order, group, self.loop_count)? {
+                        LoopIteration::Matched(node_elems) => {
+                            self.push_loop_elems(group, node_elems, is_single_elem);
+                            self.loop_count += 1;
+                            is_loop_finished = false;
                         },
-                    };
+                        LoopIteration::TrailingSeparator(node_elems) => self.push_loop_elems(group, node_elems, is_single_elem),
+                        LoopIteration::Unmatched => (),
+                    }
+                },
+                RuleElement::Expression(expr) => {
+                    let capture_lens = self.parser.get_capture_lens();
 
-                    let new_arg_group = match template_args.get(i) {
-                        Some(v) => v,
-                        None => {
-                            self.cons.borrow_mut().append_log(SyntaxParsingLog::UnknownTemplateArgumentID {
-                                arg_id: format!("[{}]", i),
-                            }.get_log());
+                    match self.parser.parse_raw_expr(expr)? {
+                        Some(node_elems) => {
+                            self.pending_elems.extend(node_elems.into_iter().filter(|each_elem| !each_elem.is_empty_node()));
+                            self.loop_count += 1;
+                            is_loop_finished = false;
+                        },
+                        None => self.parser.update_captures(&None, self.parser.src_i, capture_lens, false),
+                    }
+                },
+            }
+        }
 
-                            return Err(());
-                        }
-                    };
+        if is_loop_finished {
+            if self.loop_count < min_count as isize {
+                self.parser.append_unmatched_log(&self.start_rule_id);
+                return Err(());
+            }
 
-                    new_arg_map.template_group.insert(new_arg_id.clone(), new_arg_group.clone());
-                }
+            self.elem_i += 1;
+            self.loop_count = 0;
+        }
 
-                self.arg_maps.push(new_arg_map);
-                let result = self.parse_id_expr(expr);
-                self.arg_maps.pop();
-                return result;
-            },
-            RuleExpressionKind::String => {
-                if self.src_content.chars().count() < self.src_i + expr.value.chars().count() {
-                    return Ok(None);
-                }
+        // note: 完了した繰り返しより前の位置は再び検査されない
+        self.parser.memoized_map.clear();
+        return Ok(true);
+    }
 
-                if self.substring_src_content(self.src_i, expr.value.chars().count()) == expr.value {
-                    let new_leaf = SyntaxNodeElement::from_leaf_args(self.get_char_position(), expr.value.clone(), expr.ast_reflection_style.clone());
-                    self.add_source_index_by_string(&expr.value);
+    fn push_loop_elems(&mut self, group: &Box<RuleGroup>, node_elems: Vec<SyntaxNodeElement>, is_single_elem: bool) {
+        if is_single_elem {
+            self.pending_elems.extend(node_elems);
+        } else {
+            self.pending_elems.extend(group.ast_reflection_style.apply_to_expanded_elems(node_elems));
+        }
+    }
+}
 
-                    return Ok(Some(vec![new_leaf]));
-                } else {
-                    return Ok(None);
-                }
-            },
-            RuleExpressionKind::Wildcard => {
-                if self.src_content.chars().count() < self.src_i + 1 {
-                    return Ok(None);
-                }
+impl Iterator for SyntaxNodeIter {
+    type Item = ConsoleResult<SyntaxNodeElement>;
 
-                let expr_value = self.substring_src_content(self.src_i, 1);
-                let new_leaf = SyntaxNodeElement::from_leaf_args(self.get_char_position(), expr_value.clone(), expr.ast_reflection_style.clone());
-                self.add_source_index_by_string(&expr_value);
+    fn next(&mut self) -> Option<ConsoleResult<SyntaxNodeElement>> {
+        if !self.is_started {
+            self.is_started = true;
 
-                return Ok(Some(vec![new_leaf]));
-            },
+            match self.start() {
+                Ok(()) => (),
+                Err(()) => {
+                    self.is_finished = true;
+                    return Some(Err(()));
+                },
+            }
         }
-    }
-
-    fn parse_id_expr(&mut self, expr: &Box<RuleExpression>) -> ConsoleResult<Option<Vec<SyntaxNodeElement>>> {
-        match self.parse_rule(&expr.value, &expr.pos)? {
-            Some(node_elem) => {
-                let conv_node_elems = match &node_elem {
-                    SyntaxNodeElement::Node(node) => {
-                        let sub_ast_reflection_style = match &expr.ast_reflection_style {
-                            ASTReflectionStyle::Reflection(elem_name) => {
-                                let conv_elem_name = if elem_name == "" {
-                                    expr.value.clone()
-                                } else {
-                                    elem_name.clone()
-                                };
-
-                                ASTReflectionStyle::Reflection(conv_elem_name)
-                            },
-                            _ => expr.ast_reflection_style.clone(),
-                        };
 
-                        let node = SyntaxNodeElement::from_node_args(node.sub_elems.clone(), sub_ast_reflection_style);
+        loop {
+            match self.pending_elems.pop_front() {
+                Some(v) => return Some(Ok(v)),
+                None => (),
+            }
 
-                        if expr.ast_reflection_style.is_expandable() {
-                            match node {
-                                SyntaxNodeElement::Node(node) => node.sub_elems,
-                                _ => vec![node],
-                            }
-                        } else {
-                            vec![node]
-                        }
-                    },
-                    SyntaxNodeElement::Leaf(_) => vec![node_elem],
-                };
+            if self.is_finished {
+                return None;
+            }
 
-                return Ok(Some(conv_node_elems));
-            },
-            None => {
-                return Ok(None);
-            },
-        };
+            match self.advance() {
+                Ok(true) => (),
+                Ok(false) => self.is_finished = true,
+                Err(()) => {
+                    self.is_finished = true;
+                    self.pending_elems.clear();
+                    return Some(Err(()));
+                },
+            }
+        }
     }
+}
 
-    fn substring_src_content(&self, start_i: usize, len: usize) -> String {
-        return self.src_content.chars().skip(start_i).take(len).collect::<String>();
+// ret: 正規表現のエスケープシーケンス (\xHH, \x{...}, \uHHHH, \u{...}, \UHHHHHHHH) が表す符号位置; 16 進数として読めなければ None
+// note: u32 に収まらない値は u32::MAX とする
+fn to_escaped_code_point(sequence: &str) -> Option<u32> {
+    let digits = match sequence.get(2..) {
+        Some(v) if v.starts_with('{') && v.ends_with('}') && v.len() >= 2 => &v[1..v.len() - 1],
+        Some(v) => v,
+        None => return None,
+    };
+
+    if digits.len() == 0 || !digits.chars().all(|each_char| each_char.is_ascii_hexdigit()) {
+        return None;
     }
 
-    fn add_source_index_by_string(&mut self, expr_str: &String) {
-        let mut new_line_indexes = Vec::<usize>::new();
-        let mut char_i = 0usize;
-
-        for each_char in expr_str.chars().rev() {
-            if each_char == '\n' {
-                new_line_indexes.push(char_i);
+    return Some(u32::from_str_radix(digits, 16).unwrap_or(u32::MAX));
+}
 
-                if new_line_indexes.len() >= 2 {
-                    break;
-                }
-            }
+// ret: (パターン中の文字インデックス, エスケープシーケンス); 16 進数として読めるが max_code_point を超えるかサロゲートである最初のエスケープシーケンス
+// spec: 16 進数として読めないものは正規表現の書式の誤りとして扱うため対象外とする
+fn find_invalid_unicode_escape(pattern: &str, max_code_point: u32) -> Option<(usize, String)> {
+    let chars = pattern.chars().collect::<Vec<char>>();
+    let mut char_i = 0;
 
+    while char_i < chars.len() {
+        if chars[char_i] != '\\' {
             char_i += 1;
+            continue;
         }
 
-        match new_line_indexes.pop() {
-            Some(latest_new_line_i) => {
-                self.src_line += expr_str.match_indices("\n").count();
-                self.src_latest_line_i = match new_line_indexes.last() {
-                    Some(second_latest_new_line_i) => self.src_i + latest_new_line_i - second_latest_new_line_i + 1,
-                    None => self.src_i + latest_new_line_i + 1,
-                };
+        // note: 波括弧のない形式は桁数が固定される
+        let seq_len = match (chars.get(char_i + 1), chars.get(char_i + 2)) {
+            (Some('x'), Some('{')) | (Some('u'), Some('{')) | (Some('U'), Some('{')) => match chars[char_i + 2..].iter().position(|each_char| *each_char == '}') {
+                Some(closing_i) => closing_i + 3,
+                None => return None,
             },
-            None => (),
+            (Some('x'), _) => 4,
+            (Some('u'), _) => 6,
+            (Some('U'), _) => 10,
+            // note: \\ などのエスケープされた文字は読み飛ばす
+            _ => {
+                char_i += 2;
+                continue;
+            },
+        };
+
+        let seq_end_i = (char_i + seq_len).min(chars.len());
+        let sequence = chars[char_i..seq_end_i].iter().collect::<String>();
+
+        match to_escaped_code_point(&sequence) {
+            Some(code_point) if code_point > max_code_point || (0xd800..=0xdfff).contains(&code_point) => return Some((char_i, sequence)),
+            _ => (),
         }
 
-        self.src_i += expr_str.chars().count();
+        char_i = seq_end_i;
     }
 
-    fn get_char_position(&self) -> CharacterPosition {
-        // note: 検査に失敗すると src_i < src_latest_line_i になる; その場合は src_latest_line_i の値を使用する
-        let column = match self.src_i.checked_sub(self.src_latest_line_i) {
-            Some(v) => v,
-            None => self.src_latest_line_i,
-        };
+    return None;
+}
+
+// spec: バイト列モードの入力と比較できる形式に変換する; U+0000 ~ U+00FF の文字は同じ値の 1 バイト、それ以外の文字は UTF-8 のバイト列を表す
+// note: \z (EOF_ESCAPE_CHAR) は EOF 用のヌル文字と照合するためそのまま残す
+fn to_byte_chars(s: &str) -> String {
+    let mut byte_chars = String::new();
+
+    for each_char in s.chars() {
+        if (each_char as u32) <= 0xff || each_char == EOF_ESCAPE_CHAR {
+            byte_chars.push(each_char);
+        } else {
+            let mut buf = [0u8; 4];
 
-        return CharacterPosition::new(Some(self.src_path.clone()), self.src_i, self.src_line, column);
+            for each_byte in each_char.encode_utf8(&mut buf).bytes() {
+                byte_chars.push(each_byte as char);
+            }
+        }
     }
+
+    return byte_chars;
 }
@@ -0,0 +1,334 @@
+use std::collections::*;
+use std::fmt::*;
+
+use crate::tree::*;
+
+use rustnutlib::*;
+use rustnutlib::console::*;
+
+// note: index はパターン文字列における文字単位の位置
+#[derive(Clone, Debug, PartialEq)]
+pub enum PatternParseError {
+    DuplicateBindingName { index: usize, name: String },
+    UnexpectedCharacter { index: usize, found: char, expected: String },
+    UnexpectedEnd { expected: String },
+}
+
+impl ConsoleLogger for PatternParseError {
+    fn get_log(&self) -> ConsoleLog {
+        return match self {
+            PatternParseError::DuplicateBindingName { index, name: _ } => log!(Error, self.to_string(), format!("at:\t{}", index)),
+            PatternParseError::UnexpectedCharacter { index, found: _, expected: _ } => log!(Error, self.to_string(), format!("at:\t{}", index)),
+            PatternParseError::UnexpectedEnd { expected: _ } => log!(Error, self.to_string()),
+        };
+    }
+}
+
+impl Display for PatternParseError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let s = match self {
+            PatternParseError::DuplicateBindingName { index: _, name } => format!("duplicate binding name '{}'", name),
+            PatternParseError::UnexpectedCharacter { index: _, found, expected } => format!("unexpected character '{}'; expected {}", found, expected),
+            PatternParseError::UnexpectedEnd { expected } => format!("unexpected end of pattern; expected {}", expected),
+        };
+
+        return write!(f, "{}", s);
+    }
+}
+
+// spec: 構文ノードの構造に対するパターン; name が None であればワイルドカード (*)
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodePattern {
+    pub name: Option<String>,
+    pub children: Vec<ChildPattern>,
+}
+
+impl NodePattern {
+    pub fn new(name: Option<String>, children: Vec<ChildPattern>) -> NodePattern {
+        return NodePattern {
+            name: name,
+            children: children,
+        };
+    }
+
+    pub fn is_wildcard(&self) -> bool {
+        return self.name.is_none();
+    }
+}
+
+impl Display for NodePattern {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let name = match &self.name {
+            Some(v) => v.as_str(),
+            None => "*",
+        };
+
+        if self.children.len() == 0 {
+            return write!(f, "{}", name);
+        }
+
+        return write!(f, "{} {{ {} }}", name, self.children.iter().map(|each_child| each_child.to_string()).collect::<Vec<String>>().join(", "));
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChildPattern {
+    // note: 一致した子ノードを束縛する名前
+    pub binding: Option<String>,
+    pub pattern: NodePattern,
+    pub is_optional: bool,
+}
+
+impl Display for ChildPattern {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let binding = match &self.binding {
+            Some(v) => format!("{}: ", v),
+            None => String::new(),
+        };
+
+        let optional_marker = if self.is_optional {
+            "?"
+        } else {
+            ""
+        };
+
+        return write!(f, "{}{}{}", binding, self.pattern, optional_marker);
+    }
+}
+
+// spec: パターンの構文は以下のとおり; 空白は無視される
+// code: Pattern <- Name ("{" Child ("," Child)* ","? "}")?,
+// code: Child <- (ID ":")? Pattern "?"?,
+// code: Name <- "*" : [a-zA-Z0-9_.]+,
+pub fn parse_node_pattern(s: &str) -> std::result::Result<NodePattern, PatternParseError> {
+    let mut parser = NodePatternParser {
+        chars: s.chars().collect::<Vec<char>>(),
+        index: 0,
+        binding_names: HashSet::new(),
+    };
+
+    let pattern = parser.parse_pattern()?;
+    parser.skip_spaces();
+
+    return match parser.peek() {
+        Some(c) => Err(PatternParseError::UnexpectedCharacter {
+            index: parser.index,
+            found: c,
+            expected: "end of pattern".to_string(),
+        }),
+        None => Ok(pattern),
+    };
+}
+
+struct NodePatternParser {
+    chars: Vec<char>,
+    index: usize,
+    binding_names: HashSet<String>,
+}
+
+impl NodePatternParser {
+    fn peek(&self) -> Option<char> {
+        return self.chars.get(self.index).cloned();
+    }
+
+    fn skip_spaces(&mut self) {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => self.index += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn is_name_char(c: char) -> bool {
+        return c.is_ascii_alphanumeric() || c == '_' || c == '.';
+    }
+
+    // ret: 空白を飛ばした後の文字が c であれば読み進めて true
+    fn consume(&mut self, c: char) -> bool {
+        self.skip_spaces();
+
+        if self.peek() == Some(c) {
+            self.index += 1;
+            return true;
+        }
+
+        return false;
+    }
+
+    fn get_unexpected_error(&self, expected: &str) -> PatternParseError {
+        return match self.peek() {
+            Some(c) => PatternParseError::UnexpectedCharacter {
+                index: self.index,
+                found: c,
+                expected: expected.to_string(),
+            },
+            None => PatternParseError::UnexpectedEnd {
+                expected: expected.to_string(),
+            },
+        };
+    }
+
+    fn parse_name(&mut self) -> std::result::Result<String, PatternParseError> {
+        self.skip_spaces();
+        let start_i = self.index;
+
+        loop {
+            match self.peek() {
+                Some(c) if NodePatternParser::is_name_char(c) => self.index += 1,
+                _ => break,
+            }
+        }
+
+        if start_i == self.index {
+            return Err(self.get_unexpected_error("node name or '*'"));
+        }
+
+        return Ok(self.chars[start_i..self.index].iter().collect::<String>());
+    }
+
+    fn parse_pattern(&mut self) -> std::result::Result<NodePattern, PatternParseError> {
+        let name = if self.consume('*') {
+            None
+        } else {
+            Some(self.parse_name()?)
+        };
+
+        let mut children = Vec::<ChildPattern>::new();
+
+        if self.consume('{') {
+            loop {
+                // note: 末尾のカンマを許容する
+                if self.consume('}') {
+                    break;
+                }
+
+                children.push(self.parse_child()?);
+
+                if self.consume(',') {
+                    continue;
+                }
+
+                if self.consume('}') {
+                    break;
+                }
+
+                self.skip_spaces();
+                return Err(self.get_unexpected_error("',' or '}'"));
+            }
+        }
+
+        return Ok(NodePattern::new(name, children));
+    }
+
+    fn parse_child(&mut self) -> std::result::Result<ChildPattern, PatternParseError> {
+        self.skip_spaces();
+        let start_i = self.index;
+
+        // note: 束縛名とノード名は区別できないため ':' の有無で判断する
+        let binding = if self.consume('*') {
+            self.index = start_i;
+            None
+        } else {
+            let name = self.parse_name()?;
+
+            if self.consume(':') {
+                if !self.binding_names.insert(name.clone()) {
+                    return Err(PatternParseError::DuplicateBindingName {
+                        index: start_i,
+                        name: name,
+                    });
+                }
+
+                Some(name)
+            } else {
+                self.index = start_i;
+                None
+            }
+        };
+
+        let pattern = self.parse_pattern()?;
+        let is_optional = self.consume('?');
+
+        return Ok(ChildPattern {
+            binding: binding,
+            pattern: pattern,
+            is_optional: is_optional,
+        });
+    }
+}
+
+impl SyntaxNode {
+    // spec: ノード名がパターン名と一致し、子パターンそれぞれに異なる反映的な子ノードが一致すれば真; パターンにない子ノードは無視する
+    pub fn matches_pattern(&self, pattern: &NodePattern) -> bool {
+        return self.match_pattern(pattern).is_some();
+    }
+
+    // ret: 一致すれば <束縛名, 子ノード>; 省略可能な子パターンが一致しなかった場合その束縛名は含まれない
+    pub fn match_pattern<'a>(&'a self, pattern: &NodePattern) -> Option<HashMap<String, &'a SyntaxNode>> {
+        match &pattern.name {
+            Some(name) if !self.ast_reflection_style.is_reflected_as(name) => return None,
+            _ => (),
+        }
+
+        let child_nodes = self.get_reflectable_children().into_iter().filter_map(|each_elem| {
+            match each_elem {
+                SyntaxNodeElement::Node(node) => Some(&**node),
+                SyntaxNodeElement::Leaf(_) => None,
+            }
+        }).collect::<Vec<&SyntaxNode>>();
+
+        let mut is_used = vec![false; child_nodes.len()];
+        let mut bindings = HashMap::<String, &SyntaxNode>::new();
+
+        return if SyntaxNode::match_child_patterns(&pattern.children, &child_nodes, &mut is_used, &mut bindings) {
+            Some(bindings)
+        } else {
+            None
+        };
+    }
+
+    // note: ワイルドカードが後続のパターンに必要な子ノードを先に使用する場合に備えて割り当てを後戻りする
+    fn match_child_patterns<'a>(child_patterns: &[ChildPattern], child_nodes: &Vec<&'a SyntaxNode>, is_used: &mut Vec<bool>, bindings: &mut HashMap<String, &'a SyntaxNode>) -> bool {
+        let (child_pattern, rest_child_patterns) = match child_patterns.split_first() {
+            Some(v) => v,
+            None => return true,
+        };
+
+        for (i, each_child_node) in child_nodes.iter().enumerate() {
+            if is_used[i] {
+                continue;
+            }
+
+            let sub_bindings = match each_child_node.match_pattern(&child_pattern.pattern) {
+                Some(v) => v,
+                None => continue,
+            };
+
+            is_used[i] = true;
+            let mut new_bindings = bindings.clone();
+            new_bindings.extend(sub_bindings);
+
+            match &child_pattern.binding {
+                Some(binding) => {
+                    new_bindings.insert(binding.clone(), each_child_node);
+                },
+                None => (),
+            }
+
+            if SyntaxNode::match_child_patterns(rest_child_patterns, child_nodes, is_used, &mut new_bindings) {
+                *bindings = new_bindings;
+                return true;
+            }
+
+            is_used[i] = false;
+        }
+
+        // note: 省略可能な子パターンは一致する子ノードがなくても次のパターンへ進む
+        if child_pattern.is_optional {
+            return SyntaxNode::match_child_patterns(rest_child_patterns, child_nodes, is_used, bindings);
+        }
+
+        return false;
+    }
+}
@@ -1,47 +1,424 @@
+use std::cell::RefCell;
 use std::collections::*;
 use std::fmt::*;
+use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::block::*;
+use crate::diagnostic::*;
+use crate::file::*;
+use crate::parser::*;
+use crate::terminal::*;
 use crate::tree::*;
 
-use rustnutlib::console::ConsoleResult;
+use rustnutlib::*;
+use rustnutlib::console::*;
 
 use uuid::Uuid;
 
+pub enum RuleMapLog {
+    CyclicRuleReference { pos: CharacterPosition, rule_ids: Vec<String> },
+    // note: original_pos は位置の順で先に現れる定義; 読み込み順によらない
+    DuplicateRuleID { rule_id: String, original_pos: CharacterPosition, duplicate_pos: CharacterPosition },
+}
+
+pub enum EmbeddedTestLog {
+    TestFailed { result: EmbeddedTestResult },
+    TestPassed { result: EmbeddedTestResult },
+    TestSummary { passed_count: usize, failed_count: usize },
+}
+
+impl ConsoleLogger for EmbeddedTestLog {
+    fn get_log(&self) -> ConsoleLog {
+        return match self {
+            EmbeddedTestLog::TestFailed { result } => {
+                let detail = match result.test.expectation {
+                    EmbeddedTestExpectation::Accept => result.diagnostics.iter().map(|each_diag| format!("{}:{}: {}", each_diag.range.start.line + 1, each_diag.range.start.character + 1, each_diag.message)).collect::<Vec<String>>().join("\n"),
+                    EmbeddedTestExpectation::Reject => "input was accepted unexpectedly".to_string(),
+                };
+
+                log!(Error, format!("{} embedded test '{}' {}", to_failure_text("FAILED"), result.test.rule_id, result.test.expectation), format!("at:\t{}", result.test.pos), format!("input:\t{:?}", result.test.input), to_secondary_text(&detail))
+            },
+            EmbeddedTestLog::TestPassed { result } => log!(Note, format!("{} embedded test '{}' {}", to_success_text("ok"), result.test.rule_id, result.test.expectation), format!("at:\t{}", result.test.pos)),
+            EmbeddedTestLog::TestSummary { passed_count, failed_count } => {
+                let marker = if *failed_count == 0 {
+                    to_success_text("ok")
+                } else {
+                    to_failure_text("FAILED")
+                };
+
+                log!(Note, format!("embedded test result: {}", marker), format!("passed:\t{}", passed_count), format!("failed:\t{}", failed_count))
+            },
+        };
+    }
+}
+
+impl ConsoleLogger for RuleMapLog {
+    fn get_log(&self) -> ConsoleLog {
+        return match self {
+            RuleMapLog::CyclicRuleReference { pos, rule_ids } => log!(Error, "cyclic rule reference", format!("at:\t{}", pos), format!("cycle:\t{}", rule_ids.join(" -> "))),
+            RuleMapLog::DuplicateRuleID { rule_id, original_pos, duplicate_pos } => log!(Error, format!("duplicate rule id '{}'", rule_id), format!("at:\t{}", duplicate_pos), format!("original:\t{}", original_pos), "help:\tmark exactly one definition with 'override' to replace the others"),
+        };
+    }
+}
+
+// note: 文字列から読み込んだ文法の仮想ファイルパス; ログの位置に表示される
+pub const INLINE_GRAMMAR_FILE_PATH: &'static str = "<inline>";
+
 #[derive(Clone)]
 pub struct RuleMap {
     pub rule_map: HashMap<String, Box<Rule>>,
+    // note: <定数 ID, 展開済みの値>; 定数 ID は規則 ID と同じ形式
+    pub const_map: HashMap<String, String>,
     pub start_rule_pos: CharacterPosition,
     pub start_rule_id: String,
+    // note: 開始規則を宣言した start 命令の位置; 命令がなく既定の開始規則を用いる場合は空の位置
+    pub start_cmd_pos: CharacterPosition,
+    // note: entry 命令で宣言された規則 ID; 開始規則以外の解析の起点として用いる
+    pub entry_rule_ids: Vec<String>,
+    // note: %test 命令で記述された用例; 位置順
+    pub embedded_tests: Vec<EmbeddedTest>,
+    // note: 後方参照の結果は捕捉の状態に依存するため、後方参照があればメモ化を行わない
+    pub has_capture_refs: bool,
+    // note: 別名があるか; なければ構文解析器は規則名の解決を省く
+    pub has_aliases: bool,
+    // note: deprecated 指定のある規則・別名があるか; なければ構文解析器は非推奨の検査を省く
+    pub has_deprecations: bool,
+    // note: 規則グループの連番 (RuleGroup::index) の数
+    pub group_count: usize,
 }
 
 impl RuleMap {
-    pub fn new(block_map: Vec<BlockMap>, start_rule_id: String) -> ConsoleResult<RuleMap> {
-        let raw_rule_map = RuleMap::to_rule_map(block_map)?;
+    pub fn new(cons: &Rc<RefCell<Console>>, block_map: Vec<BlockMap>, start_rule_id: String) -> ConsoleResult<RuleMap> {
+        let const_map = RuleMap::to_const_map(&block_map);
+        let embedded_tests = RuleMap::to_embedded_tests(&block_map);
+        let mut raw_rule_map = RuleMap::to_rule_map(cons, block_map)?;
+        RuleMap::register_aliases(cons, &mut raw_rule_map)?;
+
+        for each_rule in raw_rule_map.values_mut() {
+            each_rule.group.desugar_string_choices();
+            let memoization = each_rule.memoization;
+            each_rule.group.set_memoization(memoization);
+        }
+
+        RuleMap::compute_min_lens(&mut raw_rule_map);
+        RuleMap::compute_template_arg_kinds(&mut raw_rule_map);
+        let group_count = RuleMap::set_group_indexes(&mut raw_rule_map);
 
         let start_rule_pos = match raw_rule_map.get(&start_rule_id) {
             Some(v) => v.pos.clone(),
             None => CharacterPosition::get_empty(),
         };
 
+        let has_capture_refs = raw_rule_map.values().any(|each_rule| each_rule.group.has_capture_refs());
+        let has_aliases = raw_rule_map.values().any(|each_rule| each_rule.is_alias());
+        let has_deprecations = raw_rule_map.values().any(|each_rule| each_rule.deprecation.is_some());
+
         let rule_map = RuleMap {
             rule_map: raw_rule_map,
+            const_map: const_map,
             start_rule_pos: start_rule_pos,
             start_rule_id: start_rule_id,
+            start_cmd_pos: CharacterPosition::get_empty(),
+            entry_rule_ids: Vec::new(),
+            embedded_tests: embedded_tests,
+            has_capture_refs: has_capture_refs,
+            has_aliases: has_aliases,
+            has_deprecations: has_deprecations,
+            group_count: group_count,
         };
 
         return Ok(rule_map);
     }
 
-    fn to_rule_map(block_maps: Vec<BlockMap>) -> ConsoleResult<HashMap<String, Box<Rule>>> {
+    // spec: 文字列の文法をファイルを介さずに読み込む; ファイルパスは INLINE_GRAMMAR_FILE_PATH とする
+    // spec: 他ファイルの参照は解決できないため、単一ファイルで完結する文法にのみ用いる
+    pub fn from_str(cons: &Rc<RefCell<Console>>, grammar_src: &str) -> ConsoleResult<RuleMap> {
+        let mut fcpeg_file_map = FCPEGFileMap::load_from_str(cons.clone(), INLINE_GRAMMAR_FILE_PATH.to_string(), grammar_src.to_string())?;
+        let rule_map = BlockParser::get_rule_map(cons.clone(), &mut fcpeg_file_map, true)?;
+
+        // note: 生成直後の規則マップは共有されていないため複製せずに取り出せる
+        return match Arc::try_unwrap(rule_map) {
+            Ok(v) => Ok(*v),
+            Err(v) => Ok((**v).clone()),
+        };
+    }
+
+    // ret: 規則の説明コメント; 規則が存在しないか説明がなければ None
+    pub fn get_rule_doc(&self, rule_id: &str) -> Option<&str> {
+        return match self.rule_map.get(rule_id) {
+            Some(rule) => rule.doc.as_ref().map(|doc| doc.as_str()),
+            None => None,
+        };
+    }
+
+    // ret: 規則を宣言した位置; 別名であれば alias 指定の位置、規則が存在しなければ None
+    pub fn get_rule_pos(&self, rule_id: &str) -> Option<&CharacterPosition> {
+        return match self.rule_map.get(rule_id) {
+            Some(rule) => Some(&rule.pos),
+            None => None,
+        };
+    }
+
+    // ret: 表現字句の位置で期待される終端記号の説明 (重複なし; 出現順)
+    // spec: 文字列は記述どおり、文字クラスはパターン、ワイルドカードは "." とする; 規則 ID は参照先の先頭の要素 (first_elements()) に展開する
+    // spec: 展開できない要素 (引数 ID, 後方参照, MATCH_CAPTURED, 引数を持つ規則の呼び出し) は規則名とする; context_rule_id は表現字句を含む規則
+    pub fn get_expected_terminals(&self, expr: &RuleExpression, context_rule_id: &str) -> Vec<String> {
+        let mut descs = Vec::<String>::new();
+        let mut visited_rule_ids = Vec::<String>::new();
+
+        if !self.collect_expected_terminals(expr, &mut descs, &mut visited_rule_ids) {
+            self.collect_rule_name(context_rule_id, &mut descs);
+        }
+
+        return descs;
+    }
+
+    // ret: 展開できない要素を含まなければ true
+    fn collect_expected_terminals(&self, expr: &RuleExpression, descs: &mut Vec<String>, visited_rule_ids: &mut Vec<String>) -> bool {
+        let mut new_descs = match &expr.kind {
+            RuleExpressionKind::AnyOf { values, value_indexes: _, value_lens: _ } => values.iter().map(|each_value| GrammarFormatter::format_string(each_value)).collect::<Vec<String>>(),
+            RuleExpressionKind::ArgId | RuleExpressionKind::CaptureRef => return false,
+            RuleExpressionKind::CharClass => vec![expr.value.clone()],
+            // note: 常に成功するため失敗位置で期待されることはない
+            RuleExpressionKind::Epsilon => Vec::new(),
+            RuleExpressionKind::Id => return self.collect_expected_rule_terminals(&expr.value, descs, visited_rule_ids),
+            RuleExpressionKind::IdWithArgs { generics_args, template_args } => {
+                let arg_i = match expr.value.as_str() {
+                    "CAPTURE" => 1,
+                    "INTO" | "JOIN" => 0,
+                    "MATCH_CAPTURED" | "SCAN" => return false,
+                    _ if generics_args.len() == 0 && template_args.len() == 0 => return self.collect_expected_rule_terminals(&expr.value, descs, visited_rule_ids),
+                    // note: 引数を持つ規則の呼び出しは引数によって先頭の要素が変わるため展開しない
+                    _ => return self.collect_rule_name(&expr.value, descs),
+                };
+
+                let mut arg_descs = Vec::<String>::new();
+
+                match generics_args.get(arg_i) {
+                    Some(arg_group) if self.collect_expected_group_terminals(arg_group, &mut arg_descs, visited_rule_ids) => arg_descs,
+                    _ => return false,
+                }
+            },
+            RuleExpressionKind::String => vec![GrammarFormatter::format_string(&expr.value)],
+            RuleExpressionKind::Wildcard => vec![".".to_string()],
+        };
+
+        RuleMap::append_unique_descs(descs, &mut new_descs);
+        return true;
+    }
+
+    fn collect_expected_rule_terminals(&self, rule_id: &String, descs: &mut Vec<String>, visited_rule_ids: &mut Vec<String>) -> bool {
+        // note: 左再帰等で展開中の規則は既に一覧に含まれる要素のみからなる
+        if visited_rule_ids.contains(rule_id) {
+            return true;
+        }
+
+        let rule = match self.rule_map.get(rule_id) {
+            Some(v) => v,
+            None => return false,
+        };
+
+        visited_rule_ids.push(rule_id.clone());
+        let mut rule_descs = Vec::<String>::new();
+
+        if !self.collect_expected_group_terminals(&rule.group, &mut rule_descs, visited_rule_ids) {
+            return self.collect_rule_name(rule_id, descs);
+        }
+
+        RuleMap::append_unique_descs(descs, &mut rule_descs);
+        return true;
+    }
+
+    fn collect_expected_group_terminals(&self, group: &RuleGroup, descs: &mut Vec<String>, visited_rule_ids: &mut Vec<String>) -> bool {
+        for each_elem in group.first_elements() {
+            match each_elem {
+                RuleElement::Expression(each_expr) => {
+                    if !self.collect_expected_terminals(each_expr, descs, visited_rule_ids) {
+                        return false;
+                    }
+                },
+                RuleElement::Group(_) => (),
+            }
+        }
+
+        return true;
+    }
+
+    fn collect_rule_name(&self, rule_id: &str, descs: &mut Vec<String>) -> bool {
+        RuleMap::append_unique_descs(descs, &mut vec![self.get_rule_name(rule_id)]);
+        return true;
+    }
+
+    fn append_unique_descs(descs: &mut Vec<String>, new_descs: &mut Vec<String>) {
+        for each_desc in new_descs.drain(..) {
+            if !descs.contains(&each_desc) {
+                descs.push(each_desc);
+            }
+        }
+    }
+
+    // ret: 規則が存在しなければ規則 ID をそのまま返す
+    fn get_rule_name(&self, rule_id: &str) -> String {
+        return match self.rule_map.get(rule_id) {
+            Some(rule) => rule.name.clone(),
+            None => rule_id.to_string(),
+        };
+    }
+
+    // ret: 定義された規則の ID (別名を除く; 規則 ID 順)
+    pub fn get_defined_rule_ids(&self) -> Vec<&String> {
+        let mut rule_ids = self.rule_map.iter().filter(|(_, each_rule)| !each_rule.is_alias()).map(|(each_rule_id, _)| each_rule_id).collect::<Vec<&String>>();
+        rule_ids.sort();
+        return rule_ids;
+    }
+
+    // ret: (別名の規則 ID, 参照先の規則 ID) の一覧 (別名の規則 ID 順)
+    pub fn get_rule_aliases(&self) -> Vec<(&String, &String)> {
+        let mut aliases = self.rule_map.iter().filter_map(|(each_rule_id, each_rule)| each_rule.alias_of.as_ref().map(|canonical_id| (each_rule_id, canonical_id))).collect::<Vec<(&String, &String)>>();
+        aliases.sort();
+        return aliases;
+    }
+
+    pub fn is_alias(&self, rule_id: &str) -> bool {
+        return match self.rule_map.get(rule_id) {
+            Some(rule) => rule.is_alias(),
+            None => false,
+        };
+    }
+
+    // ret: 解析の起点として指定できる規則 ID; 開始規則が先頭で、以降は entry 命令の記述順
+    pub fn get_entry_rule_ids(&self) -> Vec<String> {
+        let mut entry_rule_ids = vec![self.start_rule_id.clone()];
+
+        for each_rule_id in &self.entry_rule_ids {
+            if !entry_rule_ids.contains(each_rule_id) {
+                entry_rule_ids.push(each_rule_id.clone());
+            }
+        }
+
+        return entry_rule_ids;
+    }
+
+    fn to_const_map(block_maps: &Vec<BlockMap>) -> HashMap<String, String> {
+        let mut const_map = HashMap::<String, String>::new();
+
+        for each_block_map in block_maps {
+            for each_block in each_block_map.values() {
+                for each_cmd in &each_block.cmds {
+                    match each_cmd {
+                        BlockCommand::Const { pos: _, id, value } => {
+                            const_map.insert(id.clone(), value.clone());
+                        },
+                        _ => (),
+                    }
+                }
+            }
+        }
+
+        return const_map;
+    }
+
+    fn to_embedded_tests(block_maps: &Vec<BlockMap>) -> Vec<EmbeddedTest> {
+        let mut embedded_tests = Vec::<EmbeddedTest>::new();
+
+        for each_block_map in block_maps {
+            for each_block in each_block_map.values() {
+                for each_cmd in &each_block.cmds {
+                    match each_cmd {
+                        BlockCommand::Test { pos, rule_id, expectation, input } => {
+                            embedded_tests.push(EmbeddedTest::new(pos.clone(), rule_id.clone(), *expectation, input.clone()));
+                        },
+                        _ => (),
+                    }
+                }
+            }
+        }
+
+        // note: ブロックマップは順序を持たないため位置で並べる
+        embedded_tests.sort_by(|a, b| (&a.pos.file_path, a.pos.index).cmp(&(&b.pos.file_path, b.pos.index)));
+        return embedded_tests;
+    }
+
+    // spec: 各用例を指定の規則を開始規則として解析し、期待どおりに受理・拒否されるかを検査する; 受理には用例全体との一致を要する
+    // spec: config の開始規則の指定は用例ごとに上書きされる
+    // note: 拒否を期待する用例の解析エラーは結果の一部であるため、解析時のログは cons ではなく結果の診断情報に残す
+    pub fn run_embedded_tests(self: &Arc<Box<RuleMap>>, cons: Rc<RefCell<Console>>, config: &ParseConfig) -> TestReport {
+        // note: 別のコンソールを用意できなければ呼び出し元のコンソールに出力し、ログを消去しない
+        let (parse_cons, is_separate_cons) = match Console::load(None, ConsoleLogLimit::NoLimit) {
+            Ok(v) => (Rc::new(RefCell::new(v)), true),
+            Err(_) => (cons, false),
+        };
+
+        let mut results = Vec::<EmbeddedTestResult>::new();
+
+        for each_test in &self.embedded_tests {
+            let test_config = config.clone().with_start_rule_id(each_test.rule_id.clone());
+            let mut parser = SyntaxParser::new(parse_cons.clone(), self.clone(), String::new(), Box::new(each_test.input.clone()), test_config.enable_memoization).with_config(&test_config);
+            let is_accepted = match parser.run() {
+                Ok(_) => true,
+                // note: 用例の末尾に "\z" を要求しないよう、EOF 用のヌル文字のみが残った場合は受理とみなす
+                Err(()) => parser.get_logs().iter().any(|each_log| RuleMap::is_eof_only_unconsumed_log(each_log)) && parser.get_logs().iter().all(|each_log| match each_log {
+                    SyntaxParsingLog::DeprecatedRuleUse { pos: _, rule_id: _, alias_of: _, msg: _ } => true,
+                    _ => RuleMap::is_eof_only_unconsumed_log(each_log),
+                }),
+            };
+
+            let (passed, diagnostics) = match each_test.expectation {
+                EmbeddedTestExpectation::Accept => (is_accepted, if is_accepted { Vec::new() } else { parser.drain_diagnostics() }),
+                EmbeddedTestExpectation::Reject => (!is_accepted, Vec::new()),
+            };
+
+            results.push(EmbeddedTestResult {
+                test: each_test.clone(),
+                passed: passed,
+                diagnostics: diagnostics,
+            });
+
+            if is_separate_cons {
+                parse_cons.borrow_mut().clear_log();
+            }
+        }
+
+        return TestReport::new(results);
+    }
+
+    fn is_eof_only_unconsumed_log(log: &SyntaxParsingLog) -> bool {
+        return match log {
+            SyntaxParsingLog::UnconsumedInput { pos: _, end_pos: _, remaining_preview: _, remaining_len } => *remaining_len == 0,
+            _ => false,
+        };
+    }
+
+    // spec: 同じ規則 ID の定義が複数あれば override 指定のある定義のみを採用する; 指定が一方のみでなければエラーとする
+    // note: ブロックマップ・ブロックの順序に結果が依存しないよう、重複は読み込み順ではなく override 指定と位置から判断する
+    fn to_rule_map(cons: &Rc<RefCell<Console>>, block_maps: Vec<BlockMap>) -> ConsoleResult<HashMap<String, Box<Rule>>> {
         let mut rule_map = HashMap::<String, Box<Rule>>::new();
+        // note: <規則 ID, 採用されなかった定義の一覧>
+        let mut duplicate_rule_map = HashMap::<String, Vec<Box<Rule>>>::new();
 
         for each_block_map in block_maps {
             for (_, each_block) in each_block_map {
                 for each_cmd in each_block.cmds {
                     match each_cmd {
                         BlockCommand::Define { pos: _, rule } => {
-                            rule_map.insert(rule.id.clone(), Box::new(rule));
+                            let new_rule = Box::new(rule);
+
+                            match rule_map.remove(&new_rule.id) {
+                                Some(existing_rule) => {
+                                    let (adopted_rule, other_rule) = if new_rule.is_override && !existing_rule.is_override {
+                                        (new_rule, existing_rule)
+                                    } else {
+                                        (existing_rule, new_rule)
+                                    };
+
+                                    duplicate_rule_map.entry(adopted_rule.id.clone()).or_insert(Vec::new()).push(other_rule);
+                                    rule_map.insert(adopted_rule.id.clone(), adopted_rule);
+                                },
+                                None => {
+                                    rule_map.insert(new_rule.id.clone(), new_rule);
+                                },
+                            }
                         },
                         _ => (),
                     }
@@ -49,7 +426,334 @@ impl RuleMap {
             }
         }
 
-        return Ok(rule_map);
+        let mut duplicate_rule_ids = duplicate_rule_map.keys().cloned().collect::<Vec<String>>();
+        duplicate_rule_ids.sort();
+        let mut has_duplicate = false;
+
+        for each_rule_id in duplicate_rule_ids {
+            let adopted_rule = &rule_map[&each_rule_id];
+            let mut rules = vec![adopted_rule];
+            rules.extend(duplicate_rule_map[&each_rule_id].iter());
+
+            // note: override 指定のある定義が 1 つのみであれば他の定義をすべて置き換える
+            if rules.iter().filter(|each_rule| each_rule.is_override).count() == 1 {
+                continue;
+            }
+
+            rules.sort_by(|a, b| (&a.pos.file_path, a.pos.index).cmp(&(&b.pos.file_path, b.pos.index)));
+
+            for each_duplicate_rule in &rules[1..] {
+                cons.borrow_mut().append_log(RuleMapLog::DuplicateRuleID {
+                    rule_id: each_rule_id.clone(),
+                    original_pos: rules[0].pos.clone(),
+                    duplicate_pos: each_duplicate_rule.pos.clone(),
+                }.get_log());
+            }
+
+            has_duplicate = true;
+        }
+
+        return if has_duplicate {
+            Err(())
+        } else {
+            Ok(rule_map)
+        };
+    }
+
+    // spec: alias 指定の別名を参照先の規則の複製として規則マップに登録する; 既存の規則 ID もしくは他の別名と重複すればエラーとする
+    fn register_aliases(cons: &Rc<RefCell<Console>>, rule_map: &mut HashMap<String, Box<Rule>>) -> ConsoleResult<()> {
+        // note: 重複のログを決定的にするため規則 ID 順に登録する
+        let mut rule_ids = rule_map.iter().filter(|(_, each_rule)| each_rule.aliases.len() != 0).map(|(each_rule_id, _)| each_rule_id.clone()).collect::<Vec<String>>();
+        rule_ids.sort();
+
+        let mut has_duplicate = false;
+
+        for each_rule_id in rule_ids {
+            let rule = rule_map[&each_rule_id].clone();
+
+            for each_alias in &rule.aliases {
+                match rule_map.get(&each_alias.id) {
+                    Some(existing_rule) => {
+                        cons.borrow_mut().append_log(RuleMapLog::DuplicateRuleID {
+                            rule_id: each_alias.id.clone(),
+                            original_pos: existing_rule.pos.clone(),
+                            duplicate_pos: each_alias.pos.clone(),
+                        }.get_log());
+
+                        has_duplicate = true;
+                    },
+                    None => {
+                        rule_map.insert(each_alias.id.clone(), Box::new(rule.to_alias_rule(each_alias)));
+                    },
+                }
+            }
+        }
+
+        return if has_duplicate {
+            Err(())
+        } else {
+            Ok(())
+        };
+    }
+
+    // spec: 各グループの最小一致長を 0 から始めて値が変化しなくなるまで更新する
+    // note: 更新途中の値も真の最小一致長の下限であるため、無限に伸びる再帰規則は反復回数を制限して打ち切る
+    // ret: 連番の数
+    // note: 番号を決定的にするため規則 ID 順に設定する; 別名の項目は参照先の規則と同じ番号となる
+    fn set_group_indexes(rule_map: &mut HashMap<String, Box<Rule>>) -> usize {
+        let mut rule_ids = rule_map.keys().cloned().collect::<Vec<String>>();
+        rule_ids.sort();
+
+        let mut indexes = HashMap::<Uuid, usize>::new();
+
+        for each_rule_id in &rule_ids {
+            rule_map.get_mut(each_rule_id).unwrap().group.set_indexes(&mut indexes);
+        }
+
+        return indexes.len();
+    }
+
+    fn compute_min_lens(rule_map: &mut HashMap<String, Box<Rule>>) {
+        let mut rule_min_lens = HashMap::<String, usize>::new();
+
+        for _ in 0..(rule_map.len() + 1) {
+            let mut new_rule_min_lens = HashMap::<String, usize>::new();
+
+            for (each_rule_id, each_rule) in rule_map.iter_mut() {
+                each_rule.group.update_min_len(&rule_min_lens);
+
+                let rule_min_len = if each_rule.group.elem_order.is_random() {
+                    0
+                } else {
+                    each_rule.group.get_effective_min_len()
+                };
+
+                new_rule_min_lens.insert(each_rule_id.clone(), rule_min_len);
+            }
+
+            if new_rule_min_lens == rule_min_lens {
+                break;
+            }
+
+            rule_min_lens = new_rule_min_lens;
+        }
+    }
+
+    // spec: 規則の呼び出しごとにグループを辿らないよう、テンプレート引数の種別の制約を規則ごとに求めておく
+    fn compute_template_arg_kinds(rule_map: &mut HashMap<String, Box<Rule>>) {
+        for each_rule in rule_map.values_mut() {
+            let template_arg_kinds = each_rule.template_arg_ids.iter().filter_map(|each_arg_id| {
+                each_rule.group.get_expected_argument_kind(each_arg_id).map(|kind| (each_arg_id.clone(), kind))
+            }).collect::<HashMap<String, RuleArgumentKind>>();
+
+            each_rule.template_arg_kinds = template_arg_kinds;
+        }
+    }
+
+    // spec: メインファイル (ファイルエイリアス名が空) の規則をブロック名・規則名順に並べた再読み込み可能な文法を出力する
+    // spec: 定数は読み込み時に文字列へ展開済みのため文字列として出力する; 規則の説明以外のコメントは保持されない
+    // spec: %test 命令の用例は対象の規則のブロックに出力する
+    pub fn format_grammar(&self) -> String {
+        // note: <ブロック名, 規則一覧>
+        let mut block_rules = BTreeMap::<String, Vec<&Rule>>::new();
+
+        // note: 別名は参照先の規則の alias 指定として出力する
+        for each_rule in self.rule_map.values().filter(|each_rule| !each_rule.is_alias()) {
+            match RuleMap::split_rule_id(&each_rule.id) {
+                Some((file_alias_name, block_name, _)) if file_alias_name == "" => {
+                    block_rules.entry(block_name.to_string()).or_insert(Vec::new()).push(each_rule);
+                },
+                _ => (),
+            }
+        }
+
+        // note: 開始規則が既定と異なれば Main ブロックに start 命令を出力する
+        let mut main_cmd_texts = Vec::<String>::new();
+
+        match RuleMap::split_rule_id(&self.start_rule_id) {
+            Some(rule_id_elems) if self.start_rule_id != DEFAULT_START_RULE_ID => main_cmd_texts.push(format!("+ start {},", RuleMap::to_cmd_rule_id(rule_id_elems))),
+            _ => (),
+        }
+
+        for each_rule_id in &self.entry_rule_ids {
+            match RuleMap::split_rule_id(each_rule_id) {
+                Some(rule_id_elems) => main_cmd_texts.push(format!("+ entry {},", RuleMap::to_cmd_rule_id(rule_id_elems))),
+                None => (),
+            }
+        }
+
+        if main_cmd_texts.len() != 0 && !block_rules.contains_key("Main") {
+            block_rules.insert("Main".to_string(), Vec::new());
+        }
+
+        // note: 用例は対象の規則と同じブロックの末尾に出力する; メインファイル以外の規則の用例は出力しない
+        // note: <ブロック名, 用例一覧>
+        let mut block_tests = BTreeMap::<String, Vec<&EmbeddedTest>>::new();
+
+        for each_test in &self.embedded_tests {
+            match RuleMap::split_rule_id(&each_test.rule_id) {
+                Some((file_alias_name, block_name, _)) if file_alias_name == "" && block_rules.contains_key(block_name) => {
+                    block_tests.entry(block_name.to_string()).or_insert(Vec::new()).push(each_test);
+                },
+                _ => (),
+            }
+        }
+
+        let mut block_texts = Vec::<String>::new();
+
+        for (each_block_name, each_rules) in &mut block_rules {
+            each_rules.sort_by(|a, b| a.name.cmp(&b.name));
+
+            let mut formatter = GrammarFormatter::new(String::new(), each_block_name.clone());
+            let mut rule_texts = each_rules.iter().map(|each_rule| formatter.format_rule(each_rule)).collect::<Vec<String>>();
+
+            match block_tests.get(each_block_name) {
+                Some(tests) => rule_texts.extend(tests.iter().map(|each_test| format!("%test {} {} {},", formatter.format_rule_id(&each_test.rule_id), each_test.expectation, GrammarFormatter::format_string(&each_test.input)))),
+                None => (),
+            }
+
+            let mut cmd_texts = formatter.used_block_names.iter().map(|each_name| format!("+ use {},", each_name)).collect::<Vec<String>>();
+
+            if each_block_name == "Main" {
+                cmd_texts.append(&mut main_cmd_texts.clone());
+            }
+
+            let mut lines = cmd_texts.iter().map(|each_text| format!("    {}", each_text)).collect::<Vec<String>>();
+
+            if cmd_texts.len() != 0 && rule_texts.len() != 0 {
+                lines.push(String::new());
+            }
+
+            lines.append(&mut rule_texts.iter().flat_map(|each_text| each_text.split("\n")).map(|each_line| format!("    {}", each_line)).collect::<Vec<String>>());
+            block_texts.push(format!("[{}]{{\n{}\n}}\n", each_block_name, lines.join("\n")));
+        }
+
+        return block_texts.join("\n");
+    }
+
+    // ret: start 命令等の引数の表記; メインファイルの規則であればファイルエイリアス名を省略する
+    fn to_cmd_rule_id((file_alias_name, block_name, rule_name): (&str, &str, &str)) -> String {
+        return if file_alias_name == "" {
+            format!("{}.{}", block_name, rule_name)
+        } else {
+            format!("{}.{}.{}", file_alias_name, block_name, rule_name)
+        };
+    }
+
+    // ret: (ファイルエイリアス名, ブロック名, 規則名); プリミティブ規則等で 3 要素に分割できなければ None
+    fn split_rule_id(rule_id: &str) -> Option<(&str, &str, &str)> {
+        let tokens = rule_id.split(".").collect::<Vec<&str>>();
+
+        return match tokens.as_slice() {
+            [file_alias_name, block_name, rule_name] => Some((file_alias_name, block_name, rule_name)),
+            _ => None,
+        };
+    }
+
+    // spec: すべての規則 ID の参照 (ジェネリクス・テンプレート引数内を含む) が定義済みの規則もしくはプリミティブ規則を指すかを解析前に検査する
+    // ret: 未定義の規則を参照する (表現字句の位置, 規則 ID) の一覧; 参照元の規則 ID 順かつ規則内では出現順
+    pub fn validate_completeness(&self) -> Vec<(CharacterPosition, String)> {
+        // note: 別名のグループは参照先の規則の複製であるため、同じ参照を重複して報告しないよう除く
+        let rule_ids = self.get_defined_rule_ids();
+
+        let mut undefined_refs = Vec::<(CharacterPosition, String)>::new();
+
+        for each_rule_id in rule_ids {
+            self.rule_map[each_rule_id].group.collect_undefined_rule_refs(&self.rule_map, &mut undefined_refs);
+        }
+
+        return undefined_refs;
+    }
+
+    pub fn get_char_class_patterns(&self) -> HashSet<String> {
+        return self.get_char_class_exprs().into_iter().map(|(each_pattern, _)| each_pattern).collect::<HashSet<String>>();
+    }
+
+    // ret: <文字クラスのパターン, 最初に出現した表現字句の位置>; 規則 ID 順に探索する
+    pub fn get_char_class_exprs(&self) -> BTreeMap<String, CharacterPosition> {
+        let rule_ids = self.get_defined_rule_ids();
+
+        let mut patterns = BTreeMap::<String, CharacterPosition>::new();
+
+        for each_rule_id in rule_ids {
+            self.rule_map[each_rule_id].group.collect_char_class_patterns(&mut patterns);
+        }
+
+        return patterns;
+    }
+
+    // spec: 参照される規則が参照元の規則より前に並ぶ; 開始規則から到達できない規則も含む; 別名は含めず、別名への参照は参照先の規則への参照とみなす
+    // ret: 循環参照がある場合はすべての循環をログに出力して Err
+    pub fn topological_sort(&self, cons: Rc<RefCell<Console>>) -> ConsoleResult<Vec<String>> {
+        // note: 出力を決定的にするため規則 ID 順に探索する
+        let rule_ids = self.get_defined_rule_ids().into_iter().cloned().collect::<Vec<String>>();
+
+        let mut sorted_rule_ids = Vec::<String>::new();
+        // note: <規則 ID, 探索済みか否か>; false は探索中を表す
+        let mut visit_states = HashMap::<String, bool>::new();
+        let mut rule_id_stack = Vec::<String>::new();
+        let mut has_cycle = false;
+
+        for each_rule_id in &rule_ids {
+            if self.visit_rule_on_sort(&cons, each_rule_id, &mut visit_states, &mut rule_id_stack, &mut sorted_rule_ids) {
+                has_cycle = true;
+            }
+        }
+
+        if has_cycle {
+            return Err(());
+        }
+
+        return Ok(sorted_rule_ids);
+    }
+
+    // ret: 循環参照を検出したか
+    fn visit_rule_on_sort(&self, cons: &Rc<RefCell<Console>>, rule_id: &String, visit_states: &mut HashMap<String, bool>, rule_id_stack: &mut Vec<String>, sorted_rule_ids: &mut Vec<String>) -> bool {
+        match visit_states.get(rule_id) {
+            Some(true) => return false,
+            Some(false) => {
+                let cycle_start_i = rule_id_stack.iter().position(|each_id| each_id == rule_id).unwrap_or(0);
+                let mut cycle_rule_ids = rule_id_stack[cycle_start_i..].to_vec();
+                cycle_rule_ids.push(rule_id.clone());
+
+                let pos = match self.rule_map.get(rule_id_stack.last().unwrap_or(rule_id)) {
+                    Some(rule) => rule.pos.clone(),
+                    None => CharacterPosition::get_empty(),
+                };
+
+                cons.borrow_mut().append_log(RuleMapLog::CyclicRuleReference {
+                    pos: pos,
+                    rule_ids: cycle_rule_ids,
+                }.get_log());
+
+                return true;
+            },
+            None => (),
+        }
+
+        let rule = match self.rule_map.get(rule_id) {
+            Some(v) if v.is_alias() => return self.visit_rule_on_sort(cons, v.get_canonical_id(), visit_states, rule_id_stack, sorted_rule_ids),
+            Some(v) => v,
+            // note: プリミティブ規則や引数 ID は依存対象に含めない
+            None => return false,
+        };
+
+        visit_states.insert(rule_id.clone(), false);
+        rule_id_stack.push(rule_id.clone());
+
+        let mut has_cycle = false;
+
+        for each_ref_id in rule.group.get_referenced_rule_ids() {
+            if self.visit_rule_on_sort(cons, &each_ref_id, visit_states, rule_id_stack, sorted_rule_ids) {
+                has_cycle = true;
+            }
+        }
+
+        rule_id_stack.pop();
+        visit_states.insert(rule_id.clone(), true);
+        sorted_rule_ids.push(rule_id.clone());
+
+        return has_cycle;
     }
 }
 
@@ -57,7 +761,7 @@ impl Display for RuleMap {
     fn fmt(&self, f: &mut Formatter) -> Result {
         let mut rule_text_lines = Vec::<String>::new();
 
-        for each_rule in self.rule_map.values() {
+        for each_rule in self.rule_map.values().filter(|each_rule| !each_rule.is_alias()) {
             rule_text_lines.push(each_rule.to_string());
         }
 
@@ -65,6 +769,100 @@ impl Display for RuleMap {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EmbeddedTestExpectation {
+    Accept,
+    Reject,
+}
+
+impl Display for EmbeddedTestExpectation {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let s = match self {
+            EmbeddedTestExpectation::Accept => "accepts",
+            EmbeddedTestExpectation::Reject => "rejects",
+        };
+
+        return write!(f, "{}", s);
+    }
+}
+
+// spec: %test 命令で記述された規則の用例
+#[derive(Clone)]
+pub struct EmbeddedTest {
+    pub pos: CharacterPosition,
+    pub rule_id: String,
+    pub expectation: EmbeddedTestExpectation,
+    pub input: String,
+}
+
+impl EmbeddedTest {
+    pub fn new(pos: CharacterPosition, rule_id: String, expectation: EmbeddedTestExpectation, input: String) -> EmbeddedTest {
+        return EmbeddedTest {
+            pos: pos,
+            rule_id: rule_id,
+            expectation: expectation,
+            input: input,
+        };
+    }
+}
+
+#[derive(Clone)]
+pub struct EmbeddedTestResult {
+    pub test: EmbeddedTest,
+    pub passed: bool,
+    // note: 受理を期待する用例が拒否された場合のみ解析時の診断情報を持つ
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+// spec: RuleMap::run_embedded_tests() の結果; 用例の位置順
+#[derive(Clone)]
+pub struct TestReport {
+    pub results: Vec<EmbeddedTestResult>,
+}
+
+impl TestReport {
+    pub fn new(results: Vec<EmbeddedTestResult>) -> TestReport {
+        return TestReport {
+            results: results,
+        };
+    }
+
+    pub fn passed_count(&self) -> usize {
+        return self.results.iter().filter(|each_result| each_result.passed).count();
+    }
+
+    pub fn failed_count(&self) -> usize {
+        return self.results.len() - self.passed_count();
+    }
+
+    // ret: すべての用例が期待どおりであれば真; 用例がなければ真
+    pub fn is_success(&self) -> bool {
+        return self.failed_count() == 0;
+    }
+
+    pub fn get_failures(&self) -> Vec<&EmbeddedTestResult> {
+        return self.results.iter().filter(|each_result| !each_result.passed).collect::<Vec<&EmbeddedTestResult>>();
+    }
+
+    // spec: 用例ごとの結果と件数をコンソールに追加する; 出力は呼び出し元が行う
+    pub fn print(&self, cons: &Rc<RefCell<Console>>) {
+        for each_result in &self.results {
+            let log = if each_result.passed {
+                EmbeddedTestLog::TestPassed { result: each_result.clone() }
+            } else {
+                EmbeddedTestLog::TestFailed { result: each_result.clone() }
+            };
+
+            cons.borrow_mut().append_log(log.get_log());
+        }
+
+        cons.borrow_mut().append_log(EmbeddedTestLog::TestSummary {
+            passed_count: self.passed_count(),
+            failed_count: self.failed_count(),
+        }.get_log());
+    }
+}
+
 #[derive(Clone, PartialEq, PartialOrd)]
 pub enum RuleElementLookaheadKind {
     None,
@@ -95,29 +893,131 @@ impl Display for RuleElementLookaheadKind {
             RuleElementLookaheadKind::Negative => "!",
         };
 
-        return write!(f, "{}", s);
+        return write!(f, "{}", s);
+    }
+}
+
+#[derive(Clone)]
+pub struct Rule {
+    pub pos: CharacterPosition,
+    pub id: String,
+    pub name: String,
+    pub generics_arg_ids: Vec<String>,
+    pub template_arg_ids: Vec<String>,
+    pub group: Box<RuleGroup>,
+    // note: 規則定義の直前に連続するコメント命令の内容; 複数行は改行で連結する
+    pub doc: Option<String>,
+    // note: %memo 指定であれば Some(true), %nomemo 指定であれば Some(false); 指定がなければ None (解析器の設定に従う)
+    pub memoization: Option<bool>,
+    // note: 同じ規則 ID の他の定義を置き換えるか
+    pub is_override: bool,
+    // note: deprecated 指定の説明; 指定がなければ None
+    pub deprecation: Option<String>,
+    // note: alias 指定で宣言された別名の一覧 (記述順)
+    pub aliases: Vec<RuleAlias>,
+    // note: 別名として規則マップに登録された複製であれば参照先の規則 ID; 定義された規則であれば None
+    pub alias_of: Option<String>,
+    // note: 種別の制約があるテンプレート引数 ID ごとの期待する引数の種別; RuleMap::new() で計算する
+    pub template_arg_kinds: HashMap<String, RuleArgumentKind>,
+}
+
+impl Rule {
+    pub fn new(pos: CharacterPosition, id: String, name: String, generics_arg_ids: Vec<String>, template_arg_ids: Vec<String>, group: Box<RuleGroup>) -> Rule {
+        return Rule {
+            pos: pos,
+            id: id,
+            name: name,
+            generics_arg_ids: generics_arg_ids,
+            template_arg_ids: template_arg_ids,
+            group: group,
+            doc: None,
+            memoization: None,
+            is_override: false,
+            deprecation: None,
+            aliases: Vec::new(),
+            alias_of: None,
+            template_arg_kinds: HashMap::new(),
+        };
+    }
+
+    pub fn is_alias(&self) -> bool {
+        return self.alias_of.is_some();
+    }
+
+    // ret: 別名であれば参照先の規則 ID; そうでなければ自身の規則 ID
+    pub fn get_canonical_id(&self) -> &String {
+        return match &self.alias_of {
+            Some(v) => v,
+            None => &self.id,
+        };
+    }
+
+    // spec: 診断情報に付記する宣言を生成する; arg_ids は不一致のあった種類 (ジェネリクス・テンプレート) の引数 ID
+    pub fn to_declaration(&self, arg_ids: &Vec<String>) -> RuleDeclaration {
+        return RuleDeclaration::new(self.pos.clone(), self.id.clone(), arg_ids.clone());
+    }
+
+    // spec: 別名の ID・名前・位置と非推奨の説明を持つ複製を生成する; グループは UUID を含めて複製するためメモ化の結果は参照先の規則と共有される
+    // note: 別名自体に deprecated 指定がなければ参照先の規則の指定を引き継ぐ
+    fn to_alias_rule(&self, alias: &RuleAlias) -> Rule {
+        let mut alias_rule = self.clone();
+        alias_rule.pos = alias.pos.clone();
+        alias_rule.id = alias.id.clone();
+        alias_rule.name = alias.name.clone();
+        alias_rule.is_override = false;
+        alias_rule.deprecation = alias.deprecation.clone().or(self.deprecation.clone());
+        alias_rule.aliases = Vec::new();
+        alias_rule.alias_of = Some(self.id.clone());
+        return alias_rule;
+    }
+}
+
+// spec: 引数の不一致などの診断情報に付記する、呼び出された規則の宣言
+#[derive(Clone)]
+pub struct RuleDeclaration {
+    pub pos: CharacterPosition,
+    pub rule_id: String,
+    // note: 記述順の引数 ID ($ を含まない)
+    pub arg_ids: Vec<String>,
+}
+
+impl RuleDeclaration {
+    pub fn new(pos: CharacterPosition, rule_id: String, arg_ids: Vec<String>) -> RuleDeclaration {
+        return RuleDeclaration {
+            pos: pos,
+            rule_id: rule_id,
+            arg_ids: arg_ids,
+        };
+    }
+
+    // ret: ログの補足行; kind は引数の種類の名前、opening と closing は引数の一覧を囲む記号
+    pub fn to_note_text(&self, kind: &str, opening: &str, closing: &str) -> String {
+        if self.arg_ids.len() == 0 {
+            return format!("note:\trule '{}' declared here takes no {} arguments", self.rule_id, kind);
+        }
+
+        let arg_ids_text = self.arg_ids.iter().map(|each_arg_id| format!("${}", each_arg_id)).collect::<Vec<String>>().join(", ");
+        return format!("note:\trule '{}' declared here takes {} {} argument(s) {}{}{}", self.rule_id, self.arg_ids.len(), kind, opening, arg_ids_text, closing);
     }
 }
 
+// spec: 規則定義の alias 指定; 参照先の規則と同じブロックに属する
 #[derive(Clone)]
-pub struct Rule {
+pub struct RuleAlias {
     pub pos: CharacterPosition,
     pub id: String,
     pub name: String,
-    pub generics_arg_ids: Vec<String>,
-    pub template_arg_ids: Vec<String>,
-    pub group: Box<RuleGroup>,
+    // note: 別名の deprecated 指定の説明; 指定がなければ None
+    pub deprecation: Option<String>,
 }
 
-impl Rule {
-    pub fn new(pos: CharacterPosition, id: String, name: String, generics_arg_ids: Vec<String>, template_arg_ids: Vec<String>, group: Box<RuleGroup>) -> Rule {
-        return Rule {
+impl RuleAlias {
+    pub fn new(pos: CharacterPosition, id: String, name: String, deprecation: Option<String>) -> RuleAlias {
+        return RuleAlias {
             pos: pos,
             id: id,
             name: name,
-            generics_arg_ids: generics_arg_ids,
-            template_arg_ids: template_arg_ids,
-            group: group,
+            deprecation: deprecation,
         };
     }
 }
@@ -202,114 +1102,634 @@ impl RuleElementLoopRange {
             return prefix.to_string();
         }
 
-        if is_loop_count {
-            match self.to_symbol_string() {
-                Some(v) => return v,
-                None => (),
+        if is_loop_count {
+            match self.to_symbol_string() {
+                Some(v) => return v,
+                None => (),
+            }
+        }
+
+        let min_count = if self.min == 0 {
+            String::new()
+        } else {
+            self.min.to_string()
+        };
+
+        let max_count = match self.max {
+            Infinitable::Finite(max_num) => max_num.to_string(),
+            Infinitable::Infinite => String::new(),
+        };
+
+        return format!("{}{}{}{}{}{}", prefix, opening, min_count, separator, max_count, closing);
+    }
+
+    pub fn to_tuple(&self) -> (usize, isize) {
+        let max_num = match self.max {
+            Infinitable::Finite(num) => num as isize,
+            Infinitable::Infinite => -1,
+        };
+
+        return (self.min, max_num)
+    }
+}
+
+#[derive(Clone, PartialEq, PartialOrd)]
+pub enum RuleElementOrder {
+    Random(RuleElementLoopRange),
+    Sequential,
+}
+
+impl RuleElementOrder {
+    pub fn is_random(&self) -> bool {
+        return *self != RuleElementOrder::Sequential;
+    }
+}
+
+impl Display for RuleElementOrder {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let s = match self {
+            RuleElementOrder::Random(loop_range) => format!("{}", loop_range.to_string(false, "^", "[", "-", "]")),
+            RuleElementOrder::Sequential => format!(""),
+        };
+
+        return write!(f, "{}", s);
+    }
+}
+
+// spec: 選択グループの選択肢の採用方法
+#[derive(Clone, PartialEq, PartialOrd)]
+pub enum RuleChoiceMode {
+    // note: 左から順に検査し、最初に成功した選択肢を採用する (PEG の順序付き選択)
+    Ordered,
+    // note: すべての選択肢を検査し、最も入力を消費した選択肢を採用する; 同じ長さであれば先に記述されたものを優先する
+    LongestMatch,
+}
+
+impl RuleChoiceMode {
+    pub fn is_longest_match(&self) -> bool {
+        return *self == RuleChoiceMode::LongestMatch;
+    }
+}
+
+impl Display for RuleChoiceMode {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let s = match self {
+            RuleChoiceMode::Ordered => "",
+            RuleChoiceMode::LongestMatch => "|",
+        };
+
+        return write!(f, "{}", s);
+    }
+}
+
+#[derive(Clone)]
+pub enum RuleElement {
+    Group(Box<RuleGroup>),
+    Expression(Box<RuleExpression>),
+}
+
+impl Display for RuleElement {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        return match self {
+            RuleElement::Group(group) => write!(f, "{}", group),
+            RuleElement::Expression(expr) => write!(f, "{}", expr),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, PartialOrd)]
+pub enum RuleArgumentKind {
+    NonTerminal,
+    Terminal,
+}
+
+impl Display for RuleArgumentKind {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let s = match self {
+            RuleArgumentKind::NonTerminal => "non-terminal",
+            RuleArgumentKind::Terminal => "terminal",
+        };
+
+        return write!(f, "{}", s);
+    }
+}
+
+#[derive(Clone, PartialEq, PartialOrd)]
+pub enum RuleGroupKind {
+    Choice,
+    Sequence,
+}
+
+impl Display for RuleGroupKind {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let s = match self {
+            RuleGroupKind::Choice => "Choice",
+            RuleGroupKind::Sequence => "Sequence",
+        };
+
+        return write!(f, "{}", s);
+    }
+}
+
+#[derive(Clone)]
+pub struct RuleGroup {
+    pub uuid: Uuid,
+    pub kind: RuleGroupKind,
+    pub sub_elems: Vec<RuleElement>,
+    pub ast_reflection_style: ASTReflectionStyle,
+    // note: 文法で反映方式が指定されたか; 反映名のない反映方式 (#_) と指定なしを区別する
+    pub is_ast_reflection_specified: bool,
+    pub lookahead_kind: RuleElementLookaheadKind,
+    pub loop_range: RuleElementLoopRange,
+    pub elem_order: RuleElementOrder,
+    // note: 選択グループでのみ参照される
+    pub choice_mode: RuleChoiceMode,
+    // note: 一致した場合は子要素がなくてもノードを生成する
+    pub has_presence_marker: bool,
+    // note: 一致した入力文字列を捕捉する名前
+    pub capture_name: Option<String>,
+    // note: 繰り返しの間に検査する区切り; 区切りの子要素は区切り自体の反映方式に従う (表現は非反映)
+    pub separator: Option<RuleElement>,
+    // note: 最後の繰り返しの後に区切りが続くことを許すか
+    pub allows_trailing_separator: bool,
+    // note: 先読みと繰り返しを考慮しない 1 回分の最小一致長 (文字数); 規則マップの生成時に計算される
+    pub min_len: usize,
+    // note: 所属する規則の %memo / %nomemo 指定; 規則マップの生成時に設定され、None であれば解析器の設定に従う
+    pub memoization: Option<bool>,
+    // note: 規則マップ内の連番 (0 ~ RuleMap::group_count - 1); 規則マップの生成時に設定され、網羅状況の記録に用いる
+    pub index: usize,
+}
+
+impl RuleGroup {
+    pub fn new(kind: RuleGroupKind) -> RuleGroup {
+        return RuleGroup {
+            uuid: Uuid::new_v4(),
+            kind: kind,
+            sub_elems: Vec::new(),
+            lookahead_kind: RuleElementLookaheadKind::None,
+            loop_range: RuleElementLoopRange::get_single_loop(),
+            ast_reflection_style: ASTReflectionStyle::reflection(String::new()),
+            is_ast_reflection_specified: false,
+            elem_order: RuleElementOrder::Sequential,
+            choice_mode: RuleChoiceMode::Ordered,
+            has_presence_marker: false,
+            capture_name: None,
+            separator: None,
+            allows_trailing_separator: false,
+            min_len: 0,
+            memoization: None,
+            index: 0,
+        };
+    }
+
+    // ret: 先読みと繰り返しを考慮した最小一致長
+    pub fn get_effective_min_len(&self) -> usize {
+        return self.get_effective_min_len_with(&self.loop_range);
+    }
+
+    // ret: 繰り返し範囲を loop_range に置き換えた場合の最小一致長; 順不同グループの子要素の検査に用いる
+    pub fn get_effective_min_len_with(&self, loop_range: &RuleElementLoopRange) -> usize {
+        if !self.lookahead_kind.is_none() {
+            return 0;
+        }
+
+        return self.min_len.saturating_mul(loop_range.min);
+    }
+
+    // ret: サブ要素と区切りを順に返すイテレータ; 区切りは規則参照等の走査のみに用いる
+    fn iter_elems(&self) -> impl Iterator<Item = &RuleElement> {
+        return self.sub_elems.iter().chain(self.separator.iter());
+    }
+
+    fn iter_elems_mut(&mut self) -> impl Iterator<Item = &mut RuleElement> {
+        return self.sub_elems.iter_mut().chain(self.separator.iter_mut());
+    }
+
+    // spec: 自身と子孫のグループ (区切りと引数を含む) にメモ化の指定を設定する; 規則参照の先の規則には及ばない
+    fn set_memoization(&mut self, memoization: Option<bool>) {
+        self.memoization = memoization;
+
+        for each_elem in self.iter_elems_mut() {
+            match each_elem {
+                RuleElement::Group(each_group) => each_group.set_memoization(memoization),
+                RuleElement::Expression(each_expr) => {
+                    match &mut each_expr.kind {
+                        RuleExpressionKind::IdWithArgs { generics_args, template_args } => {
+                            for each_arg in generics_args.iter_mut().chain(template_args.iter_mut()) {
+                                each_arg.set_memoization(memoization);
+                            }
+                        },
+                        _ => (),
+                    }
+                },
+            }
+        }
+    }
+
+    // spec: 自身と子孫のグループ (区切りと引数を含む) に連番を設定する; UUID が同じグループ (別名の規則の複製) には同じ番号を設定する
+    // note: indexes は <UUID, 連番>
+    fn set_indexes(&mut self, indexes: &mut HashMap<Uuid, usize>) {
+        let new_index = indexes.len();
+        self.index = *indexes.entry(self.uuid).or_insert(new_index);
+
+        for each_elem in self.iter_elems_mut() {
+            match each_elem {
+                RuleElement::Group(each_group) => each_group.set_indexes(indexes),
+                RuleElement::Expression(each_expr) => {
+                    match &mut each_expr.kind {
+                        RuleExpressionKind::IdWithArgs { generics_args, template_args } => {
+                            for each_arg in generics_args.iter_mut().chain(template_args.iter_mut()) {
+                                each_arg.set_indexes(indexes);
+                            }
+                        },
+                        _ => (),
+                    }
+                },
+            }
+        }
+    }
+
+    // note: 要素順が順不同であるグループは子要素の繰り返し範囲が置き換えられるため 0 とみなす
+    fn update_min_len(&mut self, rule_min_lens: &HashMap<String, usize>) {
+        let mut min_len = 0usize;
+
+        for each_elem in &mut self.sub_elems {
+            let elem_min_len = match each_elem {
+                RuleElement::Group(each_group) => {
+                    each_group.update_min_len(rule_min_lens);
+
+                    match each_group.kind {
+                        // note: 選択グループ自体の先読みと繰り返しは解析時に参照されない
+                        RuleGroupKind::Choice => {
+                            let mut choice_min_len = None;
+
+                            for each_sub_elem in &each_group.sub_elems {
+                                match each_sub_elem {
+                                    RuleElement::Group(each_sub_group) => {
+                                        let sub_min_len = each_sub_group.get_effective_min_len();
+
+                                        choice_min_len = match choice_min_len {
+                                            Some(v) if v <= sub_min_len => Some(v),
+                                            _ => Some(sub_min_len),
+                                        };
+                                    },
+                                    _ => (),
+                                }
+                            }
+
+                            match choice_min_len {
+                                Some(v) if !each_group.elem_order.is_random() => v,
+                                _ => 0,
+                            }
+                        },
+                        RuleGroupKind::Sequence => {
+                            if each_group.elem_order.is_random() {
+                                0
+                            } else {
+                                each_group.get_effective_min_len()
+                            }
+                        },
+                    }
+                },
+                RuleElement::Expression(each_expr) => each_expr.update_min_len(rule_min_lens),
+            };
+
+            min_len = min_len.saturating_add(elem_min_len);
+        }
+
+        // note: 区切りは繰り返しの間にのみ現れるため 1 回分の最小一致長には含めない
+        match &mut self.separator {
+            Some(RuleElement::Group(separator_group)) => separator_group.update_min_len(rule_min_lens),
+            Some(RuleElement::Expression(separator_expr)) => {
+                separator_expr.update_min_len(rule_min_lens);
+            },
+            None => (),
+        }
+
+        self.min_len = min_len;
+    }
+
+    pub fn child_count(&self) -> usize {
+        return self.sub_elems.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.sub_elems.is_empty();
+    }
+
+    // ret: グループの先頭で入力を消費しうる表現字句要素の一覧 (出現順; FIRST 集合の計算用)
+    // spec: 連接では最小一致長が 0 の要素に続く要素も含め、順不同のグループではすべての要素を含める
+    // spec: 規則 ID は参照先を展開せずにそのまま返す; 入力を消費しない先読みの要素は含めない
+    pub fn first_elements(&self) -> Vec<&RuleElement> {
+        let mut elems = Vec::<&RuleElement>::new();
+        self.collect_first_elements(&mut elems);
+        return elems;
+    }
+
+    // ret: 先読みと繰り返しを考慮せずに空の入力に一致しうるか
+    // note: 最小一致長は真の値を超えないため、空に一致しうると判定された要素の後続も含めることで一覧は過大側に近似される
+    fn collect_first_elements<'a>(&'a self, elems: &mut Vec<&'a RuleElement>) -> bool {
+        return match self.kind {
+            RuleGroupKind::Choice => {
+                let mut is_nullable = false;
+
+                for each_elem in &self.sub_elems {
+                    if RuleGroup::collect_first_elements_of_elem(each_elem, elems) {
+                        is_nullable = true;
+                    }
+                }
+
+                is_nullable
+            },
+            RuleGroupKind::Sequence => {
+                let is_random_order = self.elem_order.is_random();
+
+                for each_elem in &self.sub_elems {
+                    if !RuleGroup::collect_first_elements_of_elem(each_elem, elems) && !is_random_order {
+                        return false;
+                    }
+                }
+
+                true
+            },
+        };
+    }
+
+    fn collect_first_elements_of_elem<'a>(elem: &'a RuleElement, elems: &mut Vec<&'a RuleElement>) -> bool {
+        return match elem {
+            RuleElement::Group(group) => {
+                if !group.lookahead_kind.is_none() {
+                    return true;
+                }
+
+                let is_nullable = group.collect_first_elements(elems);
+                is_nullable || group.loop_range.min == 0
+            },
+            RuleElement::Expression(expr) => {
+                if !expr.lookahead_kind.is_none() {
+                    return true;
+                }
+
+                elems.push(elem);
+                expr.get_effective_min_len() == 0
+            },
+        };
+    }
+
+    // ret: 出現順かつ重複なしの被参照規則 ID 一覧; ジェネリクス・テンプレート引数内の参照も含む
+    pub fn get_referenced_rule_ids(&self) -> Vec<String> {
+        let mut rule_ids = Vec::<String>::new();
+        self.collect_referenced_rule_ids(&mut rule_ids);
+        return rule_ids;
+    }
+
+    // spec: 規則 ID を含むグループは非終端記号; プリミティブ規則は単一のリーフを生成するため終端記号として扱う
+    // spec: 引数 ID は呼び出し時点で種別が確定しないため終端記号として扱う
+    pub fn get_argument_kind(&self) -> RuleArgumentKind {
+        for each_elem in self.iter_elems() {
+            let is_non_terminal = match each_elem {
+                RuleElement::Group(each_group) => each_group.get_argument_kind() == RuleArgumentKind::NonTerminal,
+                RuleElement::Expression(each_expr) => {
+                    match &each_expr.kind {
+                        RuleExpressionKind::Id => true,
+                        RuleExpressionKind::IdWithArgs { generics_args: _, template_args: _ } => !PRIMITIVE_RULE_NAMES.contains(&each_expr.value.as_str()),
+                        _ => false,
+                    }
+                },
+            };
+
+            if is_non_terminal {
+                return RuleArgumentKind::NonTerminal;
+            }
+        }
+
+        return RuleArgumentKind::Terminal;
+    }
+
+    // spec: プリミティブ規則 JOIN の引数内で使われる引数は終端記号のみを受け付ける
+    // ret: 種別の制約がなければ None
+    pub fn get_expected_argument_kind(&self, arg_id: &String) -> Option<RuleArgumentKind> {
+        return if self.is_arg_id_used_in_primitive_rule(arg_id, false) {
+            Some(RuleArgumentKind::Terminal)
+        } else {
+            None
+        };
+    }
+
+    fn is_arg_id_used_in_primitive_rule(&self, arg_id: &String, is_in_primitive_rule: bool) -> bool {
+        for each_elem in self.iter_elems() {
+            let is_used = match each_elem {
+                RuleElement::Group(each_group) => each_group.is_arg_id_used_in_primitive_rule(arg_id, is_in_primitive_rule),
+                RuleElement::Expression(each_expr) => {
+                    match &each_expr.kind {
+                        RuleExpressionKind::ArgId => is_in_primitive_rule && each_expr.value == *arg_id,
+                        RuleExpressionKind::IdWithArgs { generics_args, template_args } => {
+                            let is_primitive_rule = is_in_primitive_rule || PRIMITIVE_RULE_NAMES.contains(&each_expr.value.as_str());
+                            generics_args.iter().chain(template_args.iter()).any(|each_arg| each_arg.is_arg_id_used_in_primitive_rule(arg_id, is_primitive_rule))
+                        },
+                        _ => false,
+                    }
+                },
+            };
+
+            if is_used {
+                return true;
+            }
+        }
+
+        return false;
+    }
+
+    // ret: グループが先読み・繰り返し・AST 反映指定のない単一の規則 ID のみからなる場合はその規則 ID
+    pub fn get_alias_rule_id(&self) -> Option<&String> {
+        if !self.lookahead_kind.is_none() || !self.loop_range.is_single_loop() || self.elem_order.is_random() || self.ast_reflection_style != ASTReflectionStyle::reflection(String::new()) || self.is_ast_reflection_specified || self.capture_name.is_some() || self.sub_elems.len() != 1 {
+            return None;
+        }
+
+        return match &self.sub_elems[0] {
+            RuleElement::Group(sub_group) => sub_group.get_alias_rule_id(),
+            RuleElement::Expression(expr) => {
+                if !expr.lookahead_kind.is_none() || !expr.loop_range.is_single_loop() || expr.ast_reflection_style != ASTReflectionStyle::reflection(String::new()) || expr.is_ast_reflection_specified || expr.capture_name.is_some() {
+                    return None;
+                }
+
+                match &expr.kind {
+                    RuleExpressionKind::Id => Some(&expr.value),
+                    RuleExpressionKind::IdWithArgs { generics_args, template_args } if generics_args.len() == 0 && template_args.len() == 0 && !PRIMITIVE_RULE_NAMES.contains(&expr.value.as_str()) => Some(&expr.value),
+                    _ => None,
+                }
+            },
+        };
+    }
+
+    // spec: 文字列 1 つのみからなる選択肢が 2 つ以上並ぶ選択グループの選択肢を、AnyOf 表現 1 つのみからなる選択肢に置き換える
+    // note: 選択肢同士・文字列同士の反映方式が揃っている場合のみ置き換えるため構文木は変わらない
+    fn desugar_string_choices(&mut self) {
+        for each_elem in self.iter_elems_mut() {
+            match each_elem {
+                RuleElement::Group(each_group) => each_group.desugar_string_choices(),
+                RuleElement::Expression(each_expr) => {
+                    match &mut each_expr.kind {
+                        RuleExpressionKind::IdWithArgs { generics_args, template_args } => {
+                            for each_arg in generics_args.iter_mut().chain(template_args.iter_mut()) {
+                                each_arg.desugar_string_choices();
+                            }
+                        },
+                        _ => (),
+                    }
+                },
             }
         }
 
-        let min_count = if self.min == 0 {
-            String::new()
-        } else {
-            self.min.to_string()
-        };
+        // note: AnyOf は記述順で優先するため最長一致の選択は置き換えない
+        if self.kind != RuleGroupKind::Choice || self.elem_order.is_random() || self.choice_mode.is_longest_match() || self.sub_elems.len() < 2 {
+            return;
+        }
 
-        let max_count = match self.max {
-            Infinitable::Finite(max_num) => max_num.to_string(),
-            Infinitable::Infinite => String::new(),
+        let (first_choice, first_expr) = match self.sub_elems.get(0) {
+            Some(RuleElement::Group(choice)) => match choice.get_plain_string_expr() {
+                Some(expr) => (choice, expr),
+                None => return,
+            },
+            _ => return,
         };
 
-        return format!("{}{}{}{}{}{}", prefix, opening, min_count, separator, max_count, closing);
-    }
+        let mut values = Vec::<String>::new();
 
-    pub fn to_tuple(&self) -> (usize, isize) {
-        let max_num = match self.max {
-            Infinitable::Finite(num) => num as isize,
-            Infinitable::Infinite => -1,
-        };
+        for each_elem in &self.sub_elems {
+            match each_elem {
+                RuleElement::Group(each_choice) if each_choice.ast_reflection_style == first_choice.ast_reflection_style => {
+                    match each_choice.get_plain_string_expr() {
+                        // note: \z を含む値は入力位置によって一致するかが変わるため、値による索引で検査する AnyOf には置き換えない
+                        Some(expr) if expr.ast_reflection_style == first_expr.ast_reflection_style && !expr.value.contains(EOF_ESCAPE_CHAR) => values.push(expr.value.clone()),
+                        _ => return,
+                    }
+                },
+                _ => return,
+            }
+        }
 
-        return (self.min, max_num)
+        let mut new_expr = first_expr.clone();
+        new_expr.kind = RuleExpressionKind::any_of(values);
+        new_expr.value = String::new();
+
+        let mut new_choice = first_choice.clone();
+        new_choice.sub_elems = vec![RuleElement::Expression(Box::new(new_expr))];
+        self.sub_elems = vec![RuleElement::Group(new_choice)];
     }
-}
 
-#[derive(Clone, PartialEq, PartialOrd)]
-pub enum RuleElementOrder {
-    Random(RuleElementLoopRange),
-    Sequential,
-}
+    // ret: 先読み・繰り返しを持たない文字列表現 1 つのみからなるグループであればその値; プリミティブ規則の名前引数に用いる
+    pub fn get_string_value(&self) -> Option<&String> {
+        if !self.lookahead_kind.is_none() || !self.loop_range.is_single_loop() || self.elem_order.is_random() || self.sub_elems.len() != 1 {
+            return None;
+        }
 
-impl RuleElementOrder {
-    pub fn is_random(&self) -> bool {
-        return *self != RuleElementOrder::Sequential;
+        return match &self.sub_elems[0] {
+            RuleElement::Group(sub_group) => sub_group.get_string_value(),
+            RuleElement::Expression(expr) => match expr.kind {
+                RuleExpressionKind::String if expr.lookahead_kind.is_none() && expr.loop_range.is_single_loop() => Some(&expr.value),
+                _ => None,
+            },
+        };
     }
-}
 
-impl Display for RuleElementOrder {
-    fn fmt(&self, f: &mut Formatter) -> Result {
-        let s = match self {
-            RuleElementOrder::Random(loop_range) => format!("{}", loop_range.to_string(false, "^", "[", "-", "]")),
-            RuleElementOrder::Sequential => format!(""),
-        };
+    // ret: 先読み・繰り返し・捕捉等の修飾を持たない文字列表現 1 つのみからなる連接グループであればその表現
+    fn get_plain_string_expr(&self) -> Option<&RuleExpression> {
+        if self.kind != RuleGroupKind::Sequence || !self.lookahead_kind.is_none() || !self.loop_range.is_single_loop() || self.elem_order.is_random() || self.has_presence_marker || self.capture_name.is_some() {
+            return None;
+        }
 
-        return write!(f, "{}", s);
+        return match self.sub_elems.as_slice() {
+            [RuleElement::Expression(expr)] => match expr.kind {
+                RuleExpressionKind::String if expr.lookahead_kind.is_none() && expr.loop_range.is_single_loop() && expr.capture_name.is_none() => Some(expr),
+                _ => None,
+            },
+            _ => None,
+        };
     }
-}
 
-#[derive(Clone)]
-pub enum RuleElement {
-    Group(Box<RuleGroup>),
-    Expression(Box<RuleExpression>),
-}
+    fn has_capture_refs(&self) -> bool {
+        return self.iter_elems().any(|each_elem| {
+            match each_elem {
+                RuleElement::Group(each_group) => each_group.has_capture_refs(),
+                RuleElement::Expression(each_expr) => {
+                    match &each_expr.kind {
+                        RuleExpressionKind::CaptureRef => true,
+                        // note: 文脈変数の参照も後方参照と同様に解析中の状態に依存する
+                        RuleExpressionKind::IdWithArgs { generics_args: _, template_args: _ } if each_expr.value == "MATCH_CAPTURED" => true,
+                        RuleExpressionKind::IdWithArgs { generics_args, template_args } => generics_args.iter().chain(template_args.iter()).any(|each_arg| each_arg.has_capture_refs()),
+                        _ => false,
+                    }
+                },
+            }
+        });
+    }
 
-impl Display for RuleElement {
-    fn fmt(&self, f: &mut Formatter) -> Result {
-        return match self {
-            RuleElement::Group(group) => write!(f, "{}", group),
-            RuleElement::Expression(expr) => write!(f, "{}", expr),
+    fn collect_char_class_patterns(&self, patterns: &mut BTreeMap<String, CharacterPosition>) {
+        for each_elem in self.iter_elems() {
+            match each_elem {
+                RuleElement::Group(each_group) => each_group.collect_char_class_patterns(patterns),
+                RuleElement::Expression(each_expr) => {
+                    match &each_expr.kind {
+                        RuleExpressionKind::CharClass => {
+                            patterns.entry(each_expr.value.clone()).or_insert(each_expr.pos.clone());
+                        },
+                        RuleExpressionKind::IdWithArgs { generics_args, template_args } => {
+                            for each_arg in generics_args.iter().chain(template_args.iter()) {
+                                each_arg.collect_char_class_patterns(patterns);
+                            }
+                        },
+                        _ => (),
+                    }
+                },
+            }
         }
     }
-}
-
-#[derive(Clone, PartialEq, PartialOrd)]
-pub enum RuleGroupKind {
-    Choice,
-    Sequence,
-}
 
-impl Display for RuleGroupKind {
-    fn fmt(&self, f: &mut Formatter) -> Result {
-        let s = match self {
-            RuleGroupKind::Choice => "Choice",
-            RuleGroupKind::Sequence => "Sequence",
-        };
+    fn collect_undefined_rule_refs(&self, rule_map: &HashMap<String, Box<Rule>>, undefined_refs: &mut Vec<(CharacterPosition, String)>) {
+        for each_elem in self.iter_elems() {
+            match each_elem {
+                RuleElement::Group(each_group) => each_group.collect_undefined_rule_refs(rule_map, undefined_refs),
+                RuleElement::Expression(each_expr) => {
+                    match &each_expr.kind {
+                        RuleExpressionKind::Id => (),
+                        RuleExpressionKind::IdWithArgs { generics_args, template_args } => {
+                            for each_arg in generics_args.iter().chain(template_args.iter()) {
+                                each_arg.collect_undefined_rule_refs(rule_map, undefined_refs);
+                            }
+                        },
+                        _ => continue,
+                    }
 
-        return write!(f, "{}", s);
+                    if !rule_map.contains_key(&each_expr.value) && !PRIMITIVE_RULE_NAMES.contains(&each_expr.value.as_str()) {
+                        undefined_refs.push((each_expr.pos.clone(), each_expr.value.clone()));
+                    }
+                },
+            }
+        }
     }
-}
 
-#[derive(Clone)]
-pub struct RuleGroup {
-    pub uuid: Uuid,
-    pub kind: RuleGroupKind,
-    pub sub_elems: Vec<RuleElement>,
-    pub ast_reflection_style: ASTReflectionStyle,
-    pub lookahead_kind: RuleElementLookaheadKind,
-    pub loop_range: RuleElementLoopRange,
-    pub elem_order: RuleElementOrder,
-}
+    fn collect_referenced_rule_ids(&self, rule_ids: &mut Vec<String>) {
+        for each_elem in self.iter_elems() {
+            match each_elem {
+                RuleElement::Group(each_group) => each_group.collect_referenced_rule_ids(rule_ids),
+                RuleElement::Expression(each_expr) => {
+                    match &each_expr.kind {
+                        RuleExpressionKind::Id => (),
+                        RuleExpressionKind::IdWithArgs { generics_args, template_args } => {
+                            for each_arg in generics_args.iter().chain(template_args.iter()) {
+                                each_arg.collect_referenced_rule_ids(rule_ids);
+                            }
+                        },
+                        _ => continue,
+                    }
 
-impl RuleGroup {
-    pub fn new(kind: RuleGroupKind) -> RuleGroup {
-        return RuleGroup {
-            uuid: Uuid::new_v4(),
-            kind: kind,
-            sub_elems: Vec::new(),
-            lookahead_kind: RuleElementLookaheadKind::None,
-            loop_range: RuleElementLoopRange::get_single_loop(),
-            ast_reflection_style: ASTReflectionStyle::Reflection(String::new()),
-            elem_order: RuleElementOrder::Sequential,
-        };
+                    if !rule_ids.contains(&each_expr.value) {
+                        rule_ids.push(each_expr.value.clone());
+                    }
+                },
+            }
+        }
     }
 }
 
@@ -338,16 +1758,33 @@ impl Display for RuleGroup {
             RuleGroupKind::Sequence => " ",
         };
 
-        let loop_text = self.loop_range.to_string(true, "", "{", ",", "}");
+        // note: 区切りを持つ場合は繰り返し範囲の代わりに区切りを表示する
+        let loop_text = match &self.separator {
+            Some(separator) => {
+                let separator_kind = if self.loop_range.min == 0 { "**" } else { "++" };
+                let trailing_separator_text = if self.allows_trailing_separator { "?" } else { "" };
+                format!(" {} {}{}", separator_kind, separator, trailing_separator_text)
+            },
+            None => self.loop_range.to_string(true, "", "{", ",", "}"),
+        };
+
+        let presence_marker_text = if self.has_presence_marker { "~" } else { "" };
+        let capture_text = to_capture_text(&self.capture_name);
 
-        return write!(f, "{}", format!("{}({}){}{}{}", self.lookahead_kind, seq_text.join(separator), loop_text, self.elem_order, self.ast_reflection_style));
+        return write!(f, "{}", format!("{}({}){}{}{}{}{}{}", self.lookahead_kind, seq_text.join(separator), loop_text, self.elem_order, self.choice_mode, presence_marker_text, self.ast_reflection_style, capture_text));
     }
 }
 
 #[derive(Clone)]
 pub enum RuleExpressionKind {
+    // note: 文字列のみからなる選択を読み込み時に置き換えたもの; values は記述順, value_indexes は <値, 最初に出現したインデックス>, value_lens は値の文字数 (重複なし)
+    AnyOf { values: Vec<String>, value_indexes: HashMap<String, usize>, value_lens: Vec<usize> },
     ArgId,
+    // note: 値は捕捉名
+    CaptureRef,
     CharClass,
+    // note: 入力を消費せず常に成功する空文字列 (ε); リーフを生成しない
+    Epsilon,
     Id,
     IdWithArgs { generics_args: Vec<Box<RuleGroup>>, template_args: Vec<Box<RuleGroup>> },
     String,
@@ -357,8 +1794,11 @@ pub enum RuleExpressionKind {
 impl Display for RuleExpressionKind {
     fn fmt(&self, f: &mut Formatter) -> Result {
         let s = match self {
+            RuleExpressionKind::AnyOf { values: _, value_indexes: _, value_lens: _ } => "AnyOf",
             RuleExpressionKind::ArgId => "ArgID",
+            RuleExpressionKind::CaptureRef => "CaptureRef",
             RuleExpressionKind::CharClass => "CharClass",
+            RuleExpressionKind::Epsilon => "Epsilon",
             RuleExpressionKind::Id => "ID",
             RuleExpressionKind::IdWithArgs { generics_args: _, template_args: _ } => "ID",
             RuleExpressionKind::String => "String",
@@ -369,14 +1809,41 @@ impl Display for RuleExpressionKind {
     }
 }
 
+impl RuleExpressionKind {
+    pub fn any_of(values: Vec<String>) -> RuleExpressionKind {
+        let mut value_indexes = HashMap::<String, usize>::new();
+        let mut value_lens = Vec::<usize>::new();
+
+        for (i, each_value) in values.iter().enumerate() {
+            value_indexes.entry(each_value.clone()).or_insert(i);
+            let value_len = each_value.chars().count();
+
+            if !value_lens.contains(&value_len) {
+                value_lens.push(value_len);
+            }
+        }
+
+        return RuleExpressionKind::AnyOf {
+            values: values,
+            value_indexes: value_indexes,
+            value_lens: value_lens,
+        };
+    }
+}
+
 #[derive(Clone)]
 pub struct RuleExpression {
     pub pos: CharacterPosition,
     pub kind: RuleExpressionKind,
     pub value: String,
     pub ast_reflection_style: ASTReflectionStyle,
+    // note: 文法で反映方式が指定されたか; 規則 ID 表現字句で反映名のない反映方式 (#_) と指定なしを区別する
+    pub is_ast_reflection_specified: bool,
     pub lookahead_kind: RuleElementLookaheadKind,
     pub loop_range: RuleElementLoopRange,
+    pub capture_name: Option<String>,
+    // note: 先読みと繰り返しを考慮しない 1 回分の最小一致長 (文字数); 規則マップの生成時に計算される
+    pub min_len: usize,
 }
 
 impl RuleExpression {
@@ -385,10 +1852,56 @@ impl RuleExpression {
             pos: pos,
             kind: kind,
             value: value,
-            ast_reflection_style: ASTReflectionStyle::NoReflection,
+            ast_reflection_style: ASTReflectionStyle::no_reflection(),
+            is_ast_reflection_specified: false,
             lookahead_kind: RuleElementLookaheadKind::None,
             loop_range: RuleElementLoopRange::get_single_loop(),
+            capture_name: None,
+            min_len: 0,
+        }
+    }
+
+    // ret: 先読みと繰り返しを考慮した最小一致長
+    pub fn get_effective_min_len(&self) -> usize {
+        if !self.lookahead_kind.is_none() {
+            return 0;
         }
+
+        return self.min_len.saturating_mul(self.loop_range.min);
+    }
+
+    // ret: 先読みと繰り返しを考慮した最小一致長; 引数グループの最小一致長も更新する
+    // spec: 引数 ID は呼び出し時点まで内容が確定しないため 0 とみなす
+    fn update_min_len(&mut self, rule_min_lens: &HashMap<String, usize>) -> usize {
+        let raw_min_len = match &mut self.kind {
+            RuleExpressionKind::AnyOf { values: _, value_indexes: _, value_lens } => value_lens.iter().min().cloned().unwrap_or(0),
+            RuleExpressionKind::ArgId | RuleExpressionKind::CaptureRef | RuleExpressionKind::Epsilon => 0,
+            RuleExpressionKind::CharClass | RuleExpressionKind::Wildcard => 1,
+            RuleExpressionKind::Id => *rule_min_lens.get(&self.value).unwrap_or(&0),
+            RuleExpressionKind::IdWithArgs { generics_args, template_args } => {
+                for each_arg in generics_args.iter_mut().chain(template_args.iter_mut()) {
+                    each_arg.update_min_len(rule_min_lens);
+                }
+
+                match self.value.as_str() {
+                    "CAPTURE" => match generics_args.get(1) {
+                        Some(tar_arg) => tar_arg.get_effective_min_len(),
+                        None => 0,
+                    },
+                    "INTO" | "JOIN" => match generics_args.get(0) {
+                        Some(tar_arg) => tar_arg.get_effective_min_len(),
+                        None => 0,
+                    },
+                    // note: 文脈変数の値や走査が読み進める長さは解析時まで確定しない
+                    "MATCH_CAPTURED" | "SCAN" => 0,
+                    _ => *rule_min_lens.get(&self.value).unwrap_or(&0),
+                }
+            },
+            RuleExpressionKind::String => self.value.chars().count(),
+        };
+
+        self.min_len = raw_min_len;
+        return self.get_effective_min_len();
     }
 }
 
@@ -396,8 +1909,11 @@ impl Display for RuleExpression {
     fn fmt(&self, f: &mut Formatter) -> Result {
         let loop_text = self.loop_range.to_string(true, "", "{", ",", "}");
         let value_text = match self.kind.clone() {
+            RuleExpressionKind::AnyOf { values, value_indexes: _, value_lens: _ } => values.iter().map(|each_value| format!("\"{}\"", each_value)).collect::<Vec<String>>().join(" : "),
             RuleExpressionKind::ArgId => format!("${}", self.value),
+            RuleExpressionKind::CaptureRef => format!("={}", self.value),
             RuleExpressionKind::CharClass => self.value.clone(),
+            RuleExpressionKind::Epsilon => EPSILON_RULE_NAME.to_string(),
             RuleExpressionKind::Id => self.value.clone(),
             RuleExpressionKind::IdWithArgs { generics_args, template_args } => {
                 let generics_text = {
@@ -422,8 +1938,269 @@ impl Display for RuleExpression {
             },
             RuleExpressionKind::String => format!("\"{}\"", self.value),
             RuleExpressionKind::Wildcard => ".".to_string(),
-        }.replace("\0", "\\0").replace("\n", "\\n");
+        }.replace("\0", "\\0").replace(EOF_ESCAPE_CHAR, "\\z").replace("\n", "\\n");
+
+        return write!(f, "{}{}{}{}{}", self.lookahead_kind, value_text, loop_text, self.ast_reflection_style, to_capture_text(&self.capture_name));
+    }
+}
+
+fn to_capture_text(capture_name: &Option<String>) -> String {
+    return match capture_name {
+        Some(v) => format!("->{}", v),
+        None => String::new(),
+    };
+}
+
+// note: 規則 ID を記述元のブロックからの相対表記に変換しつつ文法の文字列表現を生成する
+struct GrammarFormatter {
+    file_alias_name: String,
+    block_name: String,
+    // note: use 命令が必要な同一ファイル内の他ブロック名; 名前順
+    used_block_names: Vec<String>,
+}
+
+impl GrammarFormatter {
+    fn new(file_alias_name: String, block_name: String) -> GrammarFormatter {
+        return GrammarFormatter {
+            file_alias_name: file_alias_name,
+            block_name: block_name,
+            used_block_names: Vec::new(),
+        };
+    }
+
+    fn format_rule(&mut self, rule: &Rule) -> String {
+        let generics_text = if rule.generics_arg_ids.len() != 0 {
+            format!("<{}>", rule.generics_arg_ids.iter().map(|v| format!("${}", v)).collect::<Vec<String>>().join(", "))
+        } else {
+            String::new()
+        };
+
+        let template_text = if rule.template_arg_ids.len() != 0 {
+            format!("({})", rule.template_arg_ids.iter().map(|v| format!("${}", v)).collect::<Vec<String>>().join(", "))
+        } else {
+            String::new()
+        };
+
+        // note: 規則のグループは純粋な選択グループを 1 つだけ持つ
+        let choice_text = match rule.group.sub_elems.as_slice() {
+            [RuleElement::Group(pure_choice)] => self.format_pure_choice(pure_choice, false),
+            _ => self.format_pure_choice(&rule.group, false),
+        };
+
+        // note: 説明はコメント命令として規則の直前に出力する; コメント内のカンマは ",," と表記する
+        let doc_text = match &rule.doc {
+            Some(doc) => doc.split("\n").map(|each_line| format!("% {},\n", each_line.replace(",", ",,"))).collect::<Vec<String>>().join(""),
+            None => String::new(),
+        };
+
+        let memoization_text = match rule.memoization {
+            Some(true) => "%memo ",
+            Some(false) => "%nomemo ",
+            None => "",
+        };
+
+        let override_text = if rule.is_override {
+            "override "
+        } else {
+            ""
+        };
+
+        let deprecation_text = match &rule.deprecation {
+            Some(msg) => format!("deprecated({}) ", GrammarFormatter::format_string(msg)),
+            None => String::new(),
+        };
+
+        let alias_text = rule.aliases.iter().map(|each_alias| {
+            match &each_alias.deprecation {
+                Some(msg) => format!(" alias {} deprecated({})", each_alias.name, GrammarFormatter::format_string(msg)),
+                None => format!(" alias {}", each_alias.name),
+            }
+        }).collect::<Vec<String>>().join("");
+
+        return format!("{}{}{}{}{}{}{}{} <- {},", doc_text, memoization_text, override_text, deprecation_text, rule.name, generics_text, template_text, alias_text, choice_text);
+    }
+
+    fn format_pure_choice(&mut self, group: &RuleGroup, is_random_order: bool) -> String {
+        let separator = if is_random_order {
+            ", "
+        } else {
+            " : "
+        };
+
+        return group.sub_elems.iter().map(|each_elem| self.format_seq(each_elem)).collect::<Vec<String>>().join(separator);
+    }
+
+    fn format_seq(&mut self, elem: &RuleElement) -> String {
+        return match elem {
+            RuleElement::Group(seq) => seq.sub_elems.iter().map(|each_elem| self.format_seq_elem(each_elem)).collect::<Vec<String>>().join(" "),
+            RuleElement::Expression(_) => self.format_seq_elem(elem),
+        };
+    }
+
+    fn format_seq_elem(&mut self, elem: &RuleElement) -> String {
+        return match elem {
+            RuleElement::Group(group) => {
+                // note: 表現の区切り付き繰り返しは読み込み時に展開方式のグループで囲まれるため、元の表現として出力する
+                match (&group.separator, group.sub_elems.as_slice()) {
+                    (Some(_), [RuleElement::Expression(expr)]) => {
+                        let expr_text = self.format_expr(expr);
+                        return format!("{}{}{}{}{}", group.lookahead_kind, expr_text, self.format_separator(group), GrammarFormatter::format_ast_reflection_style(&expr.ast_reflection_style, expr.is_ast_reflection_specified), to_capture_text(&group.capture_name));
+                    },
+                    _ => (),
+                }
+
+                let choice_text = match group.sub_elems.as_slice() {
+                    [RuleElement::Group(pure_choice)] => self.format_pure_choice(pure_choice, group.elem_order.is_random()),
+                    _ => self.format_pure_choice(group, group.elem_order.is_random()),
+                };
+
+                let elem_order_text = match &group.elem_order {
+                    RuleElementOrder::Random(loop_range) => GrammarFormatter::format_loop_range(loop_range, "^", "[", "-", "]"),
+                    RuleElementOrder::Sequential => String::new(),
+                };
+
+                // note: 最長一致の指定は括弧の中の選択グループが持つ
+                let choice_mode = match group.sub_elems.as_slice() {
+                    [RuleElement::Group(pure_choice)] => &pure_choice.choice_mode,
+                    _ => &group.choice_mode,
+                };
+
+                let loop_text = match &group.separator {
+                    Some(_) => self.format_separator(group),
+                    None => GrammarFormatter::format_loop_range(&group.loop_range, "", "{", ",", "}"),
+                };
+
+                let presence_marker_text = if group.has_presence_marker { "~" } else { "" };
+
+                format!("{}({}){}{}{}{}{}{}", group.lookahead_kind, choice_text, loop_text, elem_order_text, choice_mode, presence_marker_text, GrammarFormatter::format_ast_reflection_style(&group.ast_reflection_style, group.is_ast_reflection_specified), to_capture_text(&group.capture_name))
+            },
+            RuleElement::Expression(expr) => {
+                // note: AnyOf は置き換え前の選択肢の並びとして出力する; 先読み・繰り返し・捕捉を持たないため反映方式のみ各文字列に付ける
+                match &expr.kind {
+                    RuleExpressionKind::AnyOf { values, value_indexes: _, value_lens: _ } => {
+                        let ast_reflection_text = GrammarFormatter::format_ast_reflection_style(&expr.ast_reflection_style, expr.is_ast_reflection_specified);
+                        return values.iter().map(|each_value| format!("{}{}", GrammarFormatter::format_string(each_value), ast_reflection_text)).collect::<Vec<String>>().join(" : ");
+                    },
+                    _ => (),
+                }
+
+                let expr_text = self.format_expr(expr);
+                format!("{}{}{}{}{}", expr.lookahead_kind, expr_text, GrammarFormatter::format_loop_range(&expr.loop_range, "", "{", ",", "}"), GrammarFormatter::format_ast_reflection_style(&expr.ast_reflection_style, expr.is_ast_reflection_specified), to_capture_text(&expr.capture_name))
+            },
+        };
+    }
+
+    // spec: 区切りの反映方式は読み込み時に決まるため出力しない; 繰り返し範囲は ** (0 回以上) もしくは ++ (1 回以上) のみ
+    fn format_separator(&mut self, group: &RuleGroup) -> String {
+        let separator_text = match &group.separator {
+            Some(RuleElement::Group(separator_group)) => {
+                let choice_text = match separator_group.sub_elems.as_slice() {
+                    [RuleElement::Group(pure_choice)] => self.format_pure_choice(pure_choice, false),
+                    _ => self.format_pure_choice(separator_group, false),
+                };
+
+                format!("({})", choice_text)
+            },
+            Some(RuleElement::Expression(separator_expr)) => self.format_expr(separator_expr),
+            None => return String::new(),
+        };
+
+        let separator_kind = if group.loop_range.min == 0 { "**" } else { "++" };
+        let trailing_separator_text = if group.allows_trailing_separator { "?" } else { "" };
+
+        return format!(" {} {}{}", separator_kind, separator_text, trailing_separator_text);
+    }
+
+    fn format_expr(&mut self, expr: &RuleExpression) -> String {
+        return match &expr.kind {
+            RuleExpressionKind::AnyOf { values, value_indexes: _, value_lens: _ } => values.iter().map(|each_value| GrammarFormatter::format_string(each_value)).collect::<Vec<String>>().join(" : "),
+            RuleExpressionKind::ArgId => format!("${}", expr.value),
+            RuleExpressionKind::CaptureRef => format!("={}", expr.value),
+            RuleExpressionKind::CharClass => expr.value.clone(),
+            RuleExpressionKind::Epsilon => EPSILON_RULE_NAME.to_string(),
+            RuleExpressionKind::Id => self.format_rule_id(&expr.value),
+            RuleExpressionKind::IdWithArgs { generics_args, template_args } => {
+                let generics_text = if generics_args.len() != 0 {
+                    let arg_texts = generics_args.iter().map(|each_arg| self.format_seq(&RuleElement::Group(each_arg.clone()))).collect::<Vec<String>>();
+                    format!("<{}>", arg_texts.join(", "))
+                } else {
+                    String::new()
+                };
+
+                let template_text = if template_args.len() != 0 {
+                    let arg_texts = template_args.iter().map(|each_arg| self.format_seq(&RuleElement::Group(each_arg.clone()))).collect::<Vec<String>>();
+                    format!("({})", arg_texts.join(", "))
+                } else {
+                    String::new()
+                };
+
+                format!("{}{}{}", self.format_rule_id(&expr.value), generics_text, template_text)
+            },
+            RuleExpressionKind::String => GrammarFormatter::format_string(&expr.value),
+            RuleExpressionKind::Wildcard => ".".to_string(),
+        };
+    }
+
+    fn format_string(value: &str) -> String {
+        let mut escaped_value = String::new();
+
+        for each_char in value.chars() {
+            match each_char {
+                '\\' => escaped_value += "\\\\",
+                '"' => escaped_value += "\\\"",
+                '\n' => escaped_value += "\\n",
+                '\t' => escaped_value += "\\t",
+                '\r' => escaped_value += "\\r",
+                EOF_ESCAPE_CHAR => escaped_value += "\\z",
+                '\0' => escaped_value += "\\0",
+                // note: その他の制御文字は \xNN で出力する
+                _ if each_char.is_control() && (each_char as u32) <= 0xff => escaped_value += &format!("\\x{:02x}", each_char as u32),
+                _ => escaped_value.push(each_char),
+            }
+        }
+
+        return format!("\"{}\"", escaped_value);
+    }
+
+    // spec: 同一ブロックであれば規則名のみ、同一ファイルの他ブロックであれば ブロック名.規則名 (use 命令を追加)、他ファイルであれば完全な規則 ID
+    fn format_rule_id(&mut self, rule_id: &str) -> String {
+        return match RuleMap::split_rule_id(rule_id) {
+            Some((file_alias_name, block_name, rule_name)) => {
+                if file_alias_name != self.file_alias_name {
+                    rule_id.to_string()
+                } else if block_name == self.block_name {
+                    rule_name.to_string()
+                } else {
+                    if !self.used_block_names.iter().any(|v| v == block_name) {
+                        self.used_block_names.push(block_name.to_string());
+                        self.used_block_names.sort();
+                    }
+
+                    format!("{}.{}", block_name, rule_name)
+                }
+            },
+            None => rule_id.to_string(),
+        };
+    }
+
+    // note: 最小回数と最大回数が等しい場合は {n} の形式にする
+    fn format_loop_range(loop_range: &RuleElementLoopRange, prefix: &str, opening: &str, separator: &str, closing: &str) -> String {
+        return match &loop_range.max {
+            Infinitable::Finite(max_num) if *max_num == loop_range.min && !loop_range.is_single_loop() => format!("{}{}{}{}", prefix, opening, max_num, closing),
+            _ => loop_range.to_string(prefix == "", prefix, opening, separator, closing),
+        };
+    }
 
-        return write!(f, "{}{}{}{}", self.lookahead_kind, value_text, loop_text, self.ast_reflection_style);
+    // spec: 反映名のない反映方式は指定があれば #_ として出力する
+    fn format_ast_reflection_style(ast_reflection_style: &ASTReflectionStyle, is_ast_reflection_specified: bool) -> String {
+        return match &ast_reflection_style.visibility {
+            ASTVisibility::Reflect => match &ast_reflection_style.name_override {
+                Some(elem_name) => format!("#{}", elem_name),
+                None if is_ast_reflection_specified => format!("#{}", ANONYMOUS_REFLECTION_NAME),
+                None => String::new(),
+            },
+            ASTVisibility::Hide => "#".to_string(),
+            ASTVisibility::Expand => format!("##{}", ast_reflection_style.get_name()),
+        };
     }
 }
@@ -0,0 +1,223 @@
+use crate::tree::*;
+
+use uuid::Uuid;
+
+// spec: リーフの値を入力の範囲として保持する構文木; 値は入力への参照から必要な時に取り出す
+// note: 構文解析器は所有する文字列のリーフを生成するため、解析後に変換する (解析中の使用メモリは減らない)
+pub struct SpanTree<'src> {
+    src: &'src str,
+    // note: 各リーフの位置は file_path を持たず、木がまとめて保持する
+    file_path: Option<String>,
+    child: SpanNodeElement,
+}
+
+impl<'src> SpanTree<'src> {
+    // spec: src は構文解析器に渡した入力; 入力の範囲と値が一致しないリーフ (JOIN や INTO の結果, EOF 用のヌル文字等) は値を所有する
+    // note: バイト列モードの構文木には用いない (元のバイト列は保持されない)
+    pub fn from_tree(src: &'src str, tree: SyntaxTree) -> SpanTree<'src> {
+        let byte_indexes = SourceByteIndexes::new(src);
+        let mut file_path = None;
+        let child = SpanTree::to_span_elem(src, &byte_indexes, tree.into_child(), &mut file_path);
+
+        return SpanTree {
+            src: src,
+            file_path: file_path,
+            child: child,
+        };
+    }
+
+    fn to_span_elem(src: &str, byte_indexes: &SourceByteIndexes, elem: SyntaxNodeElement, file_path: &mut Option<String>) -> SpanNodeElement {
+        return match elem {
+            SyntaxNodeElement::Node(node) => {
+                let mut node = *node;
+                let mut sub_elems = Vec::<SpanNodeElement>::new();
+
+                // note: SyntaxNode は Drop を実装するためフィールドを取り出して移動する
+                for each_elem in std::mem::take(&mut node.sub_elems) {
+                    sub_elems.push(SpanTree::to_span_elem(src, byte_indexes, each_elem, file_path));
+                }
+
+                SpanNodeElement::Node(Box::new(SpanNode {
+                    sub_elems: sub_elems,
+                    ast_reflection_style: node.ast_reflection_style.clone(),
+                    uuid: node.uuid,
+                    is_presence_marker: node.is_presence_marker,
+                    src_range: node.src_range,
+                }))
+            },
+            SyntaxNodeElement::Leaf(leaf) => {
+                let mut leaf = *leaf;
+
+                if file_path.is_none() {
+                    *file_path = leaf.pos.file_path.clone();
+                }
+
+                leaf.pos.file_path = None;
+
+                let value = match byte_indexes.get_byte_range(leaf.pos.index, leaf.value.chars().count()) {
+                    Some((start_byte_i, end_byte_i)) if src[start_byte_i..end_byte_i] == leaf.value => SpanLeafValue::Span(start_byte_i, end_byte_i),
+                    _ => SpanLeafValue::Owned(leaf.value),
+                };
+
+                SpanNodeElement::Leaf(Box::new(SpanLeaf {
+                    pos: leaf.pos,
+                    value: value,
+                    ast_reflection_style: leaf.ast_reflection_style,
+                    uuid: leaf.uuid,
+                }))
+            },
+        };
+    }
+
+    pub fn get_src(&self) -> &'src str {
+        return self.src;
+    }
+
+    pub fn get_child_ref(&self) -> &SpanNodeElement {
+        return &self.child;
+    }
+
+    pub fn get_leaf_value<'a>(&'a self, leaf: &'a SpanLeaf) -> &'a str {
+        return match &leaf.value {
+            SpanLeafValue::Span(start_byte_i, end_byte_i) => &self.src[*start_byte_i..*end_byte_i],
+            SpanLeafValue::Owned(value) => value,
+        };
+    }
+
+    pub fn get_leaf_position(&self, leaf: &SpanLeaf) -> CharacterPosition {
+        let mut pos = leaf.pos.clone();
+        pos.file_path = self.file_path.clone();
+        return pos;
+    }
+
+    // spec: SyntaxNode::join_child_leaf_values() と同じく反映的なリーフの値のみを連結する
+    pub fn join_child_leaf_values(&self, node: &SpanNode) -> String {
+        let mut s = String::new();
+
+        for each_elem in &node.sub_elems {
+            match each_elem {
+                SpanNodeElement::Node(node) => s += &self.join_child_leaf_values(node),
+                SpanNodeElement::Leaf(leaf) => {
+                    match leaf.ast_reflection_style.visibility {
+                        ASTVisibility::Reflect => s += self.get_leaf_value(leaf),
+                        _ => (),
+                    }
+                },
+            }
+        }
+
+        return s;
+    }
+
+    // spec: 入力への参照を持たない構文木に変換する; UUID と位置は変換前の構文木と同じとなる
+    pub fn to_owned_tree(&self) -> SyntaxTree {
+        return SyntaxTree::from_node(self.to_owned_elem(&self.child));
+    }
+
+    fn to_owned_elem(&self, elem: &SpanNodeElement) -> SyntaxNodeElement {
+        return match elem {
+            SpanNodeElement::Node(node) => {
+                let sub_elems = node.sub_elems.iter().map(|each_elem| self.to_owned_elem(each_elem)).collect::<Vec<SyntaxNodeElement>>();
+                let mut new_node = SyntaxNode::new(sub_elems, node.ast_reflection_style.clone(), node.uuid.clone());
+                new_node.is_presence_marker = node.is_presence_marker;
+                new_node.src_range = node.src_range;
+                SyntaxNodeElement::Node(Box::new(new_node))
+            },
+            SpanNodeElement::Leaf(leaf) => {
+                let new_leaf = SyntaxLeaf::new(self.get_leaf_position(leaf), self.get_leaf_value(leaf).to_string(), leaf.ast_reflection_style.clone(), leaf.uuid.clone());
+                SyntaxNodeElement::Leaf(Box::new(new_leaf))
+            },
+        };
+    }
+
+    // ret: (入力の範囲を参照するリーフの数, 値を所有するリーフの数)
+    pub fn count_leaves(&self) -> (usize, usize) {
+        let mut counts = (0, 0);
+        SpanTree::count_leaves_in(&self.child, &mut counts);
+        return counts;
+    }
+
+    fn count_leaves_in(elem: &SpanNodeElement, counts: &mut (usize, usize)) {
+        match elem {
+            SpanNodeElement::Node(node) => {
+                for each_elem in &node.sub_elems {
+                    SpanTree::count_leaves_in(each_elem, counts);
+                }
+            },
+            SpanNodeElement::Leaf(leaf) => {
+                match leaf.value {
+                    SpanLeafValue::Span(_, _) => counts.0 += 1,
+                    SpanLeafValue::Owned(_) => counts.1 += 1,
+                }
+            },
+        }
+    }
+}
+
+pub enum SpanNodeElement {
+    Node(Box<SpanNode>),
+    Leaf(Box<SpanLeaf>),
+}
+
+pub struct SpanNode {
+    pub sub_elems: Vec<SpanNodeElement>,
+    pub ast_reflection_style: ASTReflectionStyle,
+    pub uuid: Uuid,
+    pub is_presence_marker: bool,
+    pub src_range: Option<(usize, usize)>,
+}
+
+pub enum SpanLeafValue {
+    // note: 入力のバイト範囲 (開始, 終了)
+    Span(usize, usize),
+    Owned(String),
+}
+
+pub struct SpanLeaf {
+    // note: file_path は常に None; SpanTree::get_leaf_position() で補う
+    pub pos: CharacterPosition,
+    pub value: SpanLeafValue,
+    pub ast_reflection_style: ASTReflectionStyle,
+    pub uuid: Uuid,
+}
+
+// spec: 構文解析器の文字インデックス (0x0d を数えない) から入力のバイトインデックスを求める
+struct SourceByteIndexes {
+    src_len: usize,
+    // note: 各文字の開始バイトインデックス; 入力が 0x0d を含まない ASCII であれば一致するため保持しない
+    byte_indexes: Option<Vec<usize>>,
+}
+
+impl SourceByteIndexes {
+    fn new(src: &str) -> SourceByteIndexes {
+        let byte_indexes = if src.is_ascii() && !src.contains('\r') {
+            None
+        } else {
+            Some(src.char_indices().filter(|(_, each_char)| *each_char != '\r').map(|(byte_i, _)| byte_i).collect::<Vec<usize>>())
+        };
+
+        return SourceByteIndexes {
+            src_len: src.len(),
+            byte_indexes: byte_indexes,
+        };
+    }
+
+    fn get_byte_index(&self, char_i: usize) -> Option<usize> {
+        return match &self.byte_indexes {
+            Some(byte_indexes) => {
+                match byte_indexes.get(char_i) {
+                    Some(v) => Some(*v),
+                    None if char_i == byte_indexes.len() => Some(self.src_len),
+                    None => None,
+                }
+            },
+            None if char_i <= self.src_len => Some(char_i),
+            None => None,
+        };
+    }
+
+    // ret: 範囲が入力の終端を超えれば None
+    fn get_byte_range(&self, char_i: usize, char_len: usize) -> Option<(usize, usize)> {
+        return Some((self.get_byte_index(char_i)?, self.get_byte_index(char_i + char_len)?));
+    }
+}
@@ -0,0 +1,51 @@
+#[cfg(feature = "terminal")]
+use std::io::IsTerminal;
+
+#[cfg(feature = "terminal")]
+use colored::*;
+
+#[cfg(feature = "terminal")]
+use once_cell::sync::Lazy;
+
+// note: 標準出力の判定はプロセス中で変わらないため一度のみ行う
+#[cfg(feature = "terminal")]
+static IS_COLOR_ENABLED: Lazy<bool> = Lazy::new(|| std::io::stdout().is_terminal());
+
+// spec: terminal フィーチャが有効かつ標準出力が端末であれば真
+pub fn is_color_enabled() -> bool {
+    #[cfg(feature = "terminal")]
+    return *IS_COLOR_ENABLED;
+
+    #[cfg(not(feature = "terminal"))]
+    return false;
+}
+
+// spec: ログの補足情報など目立たせない文字列; 色付けが無効であればそのまま返す
+pub fn to_secondary_text(text: &str) -> String {
+    #[cfg(feature = "terminal")]
+    if is_color_enabled() {
+        return text.bright_black().to_string();
+    }
+
+    return text.to_string();
+}
+
+// spec: 成功を示す文字列; 色付けが無効であればそのまま返す
+pub fn to_success_text(text: &str) -> String {
+    #[cfg(feature = "terminal")]
+    if is_color_enabled() {
+        return text.green().to_string();
+    }
+
+    return text.to_string();
+}
+
+// spec: 失敗を示す文字列; 色付けが無効であればそのまま返す
+pub fn to_failure_text(text: &str) -> String {
+    #[cfg(feature = "terminal")]
+    if is_color_enabled() {
+        return text.red().to_string();
+    }
+
+    return text.to_string();
+}
@@ -0,0 +1,209 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::*;
+use crate::parser::*;
+use crate::rule::*;
+use crate::tree::*;
+
+use rustnutlib::console::*;
+
+// note: テスト用の仮想ファイルパス
+const TEST_GRAMMAR_FILE_PATH: &'static str = "<test grammar>";
+const TEST_INPUT_FILE_PATH: &'static str = "<test input>";
+
+// spec: start_rule は規則 ID で指定する (例: ".Main.Expr")
+pub fn assert_parse_tree(grammar_src: &str, start_rule: &str, input: &str, expected_sexp: &str) {
+    let cons = new_console();
+    let rule_map = load_rule_map_with_start_rule(&cons, grammar_src, start_rule);
+
+    let memoized_sexp = parse_to_sexp(&cons, rule_map.clone(), input, true);
+    let unmemoized_sexp = parse_to_sexp(&cons, rule_map, input, false);
+
+    assert_sexp_eq(&memoized_sexp, &unmemoized_sexp, expected_sexp);
+}
+
+fn assert_sexp_eq(memoized_sexp: &String, unmemoized_sexp: &String, expected_sexp: &str) {
+    if memoized_sexp != unmemoized_sexp {
+        panic!("parse results differ between memoization enabled and disabled\n{}", get_sexp_diff("memoized", memoized_sexp, "unmemoized", unmemoized_sexp));
+    }
+
+    let expected_sexp = normalize_sexp(expected_sexp);
+
+    if *memoized_sexp != expected_sexp {
+        panic!("syntax tree mismatch\n{}", get_sexp_diff("expected", &expected_sexp, "actual", memoized_sexp));
+    }
+}
+
+pub fn assert_parse_fails(grammar_src: &str, input: &str, expected_error_substring: &str) {
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, grammar_src);
+
+    for enable_memoization in vec![true, false] {
+        let mut parser = SyntaxParser::new(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new(input.to_string()), enable_memoization);
+
+        match parser.run() {
+            Ok(tree) => panic!("parsing unexpectedly succeeded (memoization: {})\ntree:\t{}", enable_memoization, tree.to_sexp(true)),
+            Err(()) => (),
+        }
+
+        let messages = parser.get_logs().iter().map(|each_log| each_log.to_string()).collect::<Vec<String>>();
+
+        if !messages.iter().any(|each_msg| each_msg.contains(expected_error_substring)) {
+            panic!("no error message contains '{}' (memoization: {})\nmessages:{}", expected_error_substring, enable_memoization, messages.iter().map(|each_msg| format!("\n\t{}", each_msg)).collect::<Vec<String>>().join(""));
+        }
+
+        cons.borrow_mut().clear_log();
+    }
+}
+
+fn new_console() -> Rc<RefCell<Console>> {
+    return match Console::load(None, ConsoleLogLimit::NoLimit) {
+        Ok(v) => Rc::new(RefCell::new(v)),
+        Err(_) => panic!("failed to load console"),
+    };
+}
+
+fn load_rule_map(cons: &Rc<RefCell<Console>>, grammar_src: &str) -> Arc<Box<RuleMap>> {
+    return match FCPEGGrammar::from_str(cons.clone(), TEST_GRAMMAR_FILE_PATH, grammar_src) {
+        Ok(v) => {
+            cons.borrow_mut().clear_log();
+            v
+        },
+        Err(()) => {
+            cons.borrow().print_all();
+            panic!("failed to load the grammar");
+        },
+    };
+}
+
+fn load_rule_map_with_start_rule(cons: &Rc<RefCell<Console>>, grammar_src: &str, start_rule: &str) -> Arc<Box<RuleMap>> {
+    return set_start_rule(load_rule_map(cons, grammar_src), start_rule);
+}
+
+fn set_start_rule(rule_map: Arc<Box<RuleMap>>, start_rule: &str) -> Arc<Box<RuleMap>> {
+    let mut rule_map = (**rule_map).clone();
+
+    rule_map.start_rule_pos = match rule_map.rule_map.get(start_rule) {
+        Some(rule) => rule.pos.clone(),
+        None => panic!("start rule '{}' is not defined in the grammar", start_rule),
+    };
+
+    rule_map.start_rule_id = start_rule.to_string();
+    return Arc::new(Box::new(rule_map));
+}
+
+// spec: 文法を読み込み start_rule から入力を解析した構文木を返す; 読み込みや解析に失敗した場合はログを出力してパニックする
+fn parse_tree(grammar_src: &str, start_rule: &str, input: &str, config: ParseConfig) -> SyntaxTree {
+    let cons = new_console();
+    let rule_map = load_rule_map_with_start_rule(&cons, grammar_src, start_rule);
+
+    return match SyntaxParser::parse(cons.clone(), rule_map, TEST_INPUT_FILE_PATH.to_string(), Box::new(input.to_string()), config) {
+        Ok(v) => v,
+        Err(()) => {
+            cons.borrow().print_all();
+            panic!("failed to parse the input");
+        },
+    };
+}
+
+fn parse_to_sexp(cons: &Rc<RefCell<Console>>, rule_map: Arc<Box<RuleMap>>, input: &str, enable_memoization: bool) -> String {
+    let mut parser = SyntaxParser::new(cons.clone(), rule_map, TEST_INPUT_FILE_PATH.to_string(), Box::new(input.to_string()), enable_memoization);
+
+    return match parser.run() {
+        Ok(tree) => {
+            assert_tree_invariants(&tree);
+            assert_leaf_positions(&parser, &tree);
+            tree.to_sexp(true)
+        },
+        Err(()) => {
+            cons.borrow().print_all();
+            panic!("failed to parse the input (memoization: {})", enable_memoization);
+        },
+    };
+}
+
+fn assert_leaf_positions(parser: &SyntaxParser, tree: &SyntaxTree) {
+    let logs = parser.validate_positions(tree);
+
+    if logs.len() != 0 {
+        panic!("leaf positions mismatch the source:{}\ntree:\t{}", logs.iter().map(|each_log| format!("\n\t{} at {}", each_log, each_log.get_position().unwrap())).collect::<Vec<String>>().join(""), tree.to_sexp(false));
+    }
+}
+
+// note: リリースビルドでは SyntaxTree::verify_invariants() を使用できないため検査しない
+#[cfg(debug_assertions)]
+fn assert_tree_invariants(tree: &SyntaxTree) {
+    let violations = tree.verify_invariants();
+
+    if violations.len() != 0 {
+        panic!("syntax tree violates invariants:{}\ntree:\t{}", violations.iter().map(|each_violation| format!("\n\t{}", each_violation)).collect::<Vec<String>>().join(""), tree.to_sexp(false));
+    }
+}
+
+#[cfg(not(debug_assertions))]
+fn assert_tree_invariants(_tree: &SyntaxTree) {}
+
+// note: 文字列リテラル外の連続する空白を 1 つの空白にまとめる
+fn normalize_sexp(sexp: &str) -> String {
+    let mut s = String::new();
+    let mut is_in_str = false;
+    let mut is_escaped = false;
+    let mut is_space_pending = false;
+
+    for each_char in sexp.trim().chars() {
+        if is_in_str {
+            s.push(each_char);
+
+            if is_escaped {
+                is_escaped = false;
+            } else if each_char == '\\' {
+                is_escaped = true;
+            } else if each_char == '"' {
+                is_in_str = false;
+            }
+
+            continue;
+        }
+
+        if each_char.is_whitespace() {
+            is_space_pending = true;
+            continue;
+        }
+
+        // note: 括弧の内側の空白は捨てる
+        if is_space_pending && !s.ends_with('(') && each_char != ')' {
+            s.push(' ');
+        }
+
+        is_space_pending = false;
+
+        if each_char == '"' {
+            is_in_str = true;
+        }
+
+        s.push(each_char);
+    }
+
+    return s;
+}
+
+// note: 最初に異なる文字の位置を示す
+fn get_sexp_diff(left_name: &str, left: &String, right_name: &str, right: &String) -> String {
+    let left_chars = left.chars().collect::<Vec<char>>();
+    let right_chars = right.chars().collect::<Vec<char>>();
+    let mut diff_i = 0usize;
+
+    while diff_i < left_chars.len() && diff_i < right_chars.len() && left_chars[diff_i] == right_chars[diff_i] {
+        diff_i += 1;
+    }
+
+    let name_len = left_name.len().max(right_name.len());
+    let caret_line = format!("{}  {}^", " ".repeat(name_len), " ".repeat(diff_i));
+
+    return format!("{:>width$}: {}\n{:>width$}: {}\n{}\nfirst difference at character {}", left_name, left, right_name, right, caret_line, diff_i, width = name_len);
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,166 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::*;
+use crate::annotation::*;
+use crate::block::*;
+use crate::coverage::*;
+use crate::diagnostic::*;
+use crate::logging::*;
+use crate::parser::*;
+use crate::pattern::*;
+use crate::rule::*;
+use crate::span::*;
+use crate::tree::*;
+
+use super::*;
+
+use rustnutlib::file::*;
+
+mod engine;
+mod failure;
+mod grammar;
+mod memoization;
+mod tree;
+
+// spec: メモ化の回帰検査に用いる (文法, 開始規則 ID, 入力リスト) の組; 失敗する入力や後戻りの多い入力を含む
+const MEMOIZATION_TEST_CORPUS: &'static [(&'static str, &'static str, &'static [&'static str])] = &[
+    (
+        r##"[Main]{
+            Expr <- Space# Sum Space# "\z"#,
+            Sum <- Prod (Space# ("+" : "-") Space# Prod)*##,
+            Prod <- Unary (Space# ("*" : "/") Space# Unary)*##,
+            Unary <- "-"? Atom,
+            Atom <- Num : "("# Space# Sum Space# ")"#,
+            Num <- JOIN<[0-9]+>,
+            Space <- (" " : "\n")*,
+        }"##,
+        ".Main.Expr",
+        &["1", "1+2*3", "-(1 -\n 2)\n* 34 / 5", "((((1))))", "1 + (2 * (3 - 4)) * 5", "1+", "(1", ""],
+    ),
+    (
+        r##"[Main]{
+            Json <- Space# Value Space# "\z"#,
+            Value <- Object : Array : Str : Num : Bool : Null,
+            Object <- "{"# Space# (Member (Space# ","# Space# Member)*##)?## Space# "}"#,
+            Member <- Str Space# ":"# Space# Value,
+            Array <- "["# Space# (Value (Space# ","# Space# Value)*##)?## Space# "]"#,
+            Str <- "\""# (!"\"" .)*## "\""#,
+            Num <- "-"? [0-9]+ ("." [0-9]+)?,
+            Bool <- "true" : "false",
+            Null <- "null",
+            Space <- (" " : "\n" : "\t")*,
+        }"##,
+        ".Main.Json",
+        &["{}", "[1, -2.5, true, null]", "{\"a\": [1, {\"b\": \"c\"}],\n\t\"d\": false}", "[[], [[]], {}]", "{\"a\": }", "[1, 2"],
+    ),
+    (
+        r##"[Main]{
+            Main <- List<Item> "\z"#,
+            List<$T> <- "["# ($T (","# $T)*##)?## "]"#,
+            Item <- Keyword : Ident : Paren<Ident> : Paren<Keyword>,
+            Paren<$U> <- "("# $U ")"#,
+            Keyword <- ("let" : "fn") ![a-z],
+            Ident <- !Keyword JOIN<[a-z]+>,
+        }"##,
+        ".Main.Main",
+        &["[]", "[let,letter,fn,fnx]", "[(a),(let),(fn)]", "[(let)]", "[let", "[(]"],
+    ),
+    (
+        r##"[Main]{
+            Main <- Tok (" "# Tok)*## "\z"#,
+            Tok <- ("<" : "<=" : "<<" : "<<=")|#Op : ("if"#Kw : JOIN<[a-z]+>#Ident)|,
+        }"##,
+        ".Main.Main",
+        &["<= <<= < <<", "if iffy i", "<<= if <", "<> if", "if<"],
+    ),
+    (
+        r##"[Main]{
+            Main <- Heredoc ("\n"# Heredoc)*## "\z"#,
+            Heredoc <- "<<"# CAPTURE<"delim", [A-Z]+>#Delim "\n"# Line* MATCH_CAPTURED<"delim">#,
+            Line <- !MATCH_CAPTURED<"delim"> JOIN<(!"\n" .)*> "\n"#,
+        }"##,
+        ".Main.Main",
+        &["<<EOF\nabc\n  EOFx\nEOF", "<<A\nB\nA\n<<B\nA\nB", "<<EOF\nEOF", "<<EOF\nabc\nEND", "<<A\nA\n<<B\nA"],
+    ),
+    (
+        r##"[Main]{
+            Main <- (List : Tuple)## "\z"#,
+            List <- "["# Item ** "," "]"#,
+            Tuple <- "("# Item ++ (","#Comma : ";")? ")"#,
+            Item <- JOIN<[a-z]+>,
+        }"##,
+        ".Main.Main",
+        &["[]", "[a]", "[a,bc,d]", "[a,]", "[,a]", "()", "(a)", "(a,b;c)", "(a,b;)", "(a,,b)"],
+    ),
+];
+
+// note: path には根から異なる要素までの「子要素のインデックス:反映名」を積む
+// ret: 異なる要素があればその理由
+fn get_divergent_path(left: &SyntaxNodeElement, right: &SyntaxNodeElement, path: &mut Vec<String>) -> Option<String> {
+    return match (left, right) {
+        (SyntaxNodeElement::Node(left_node), SyntaxNodeElement::Node(right_node)) => {
+            if left_node.ast_reflection_style != right_node.ast_reflection_style {
+                return Some(format!("reflection style: {} / {}", left_node.ast_reflection_style.to_sexp_name(), right_node.ast_reflection_style.to_sexp_name()));
+            }
+
+            if left_node.is_presence_marker != right_node.is_presence_marker {
+                return Some(format!("presence marker: {} / {}", left_node.is_presence_marker, right_node.is_presence_marker));
+            }
+
+            if left_node.src_range != right_node.src_range {
+                return Some(format!("matched range: {:?} / {:?}", left_node.src_range, right_node.src_range));
+            }
+
+            if left_node.sub_elems.len() != right_node.sub_elems.len() {
+                return Some(format!("child count: {} / {}", left_node.sub_elems.len(), right_node.sub_elems.len()));
+            }
+
+            for (i, (each_left, each_right)) in left_node.sub_elems.iter().zip(right_node.sub_elems.iter()).enumerate() {
+                let name = match each_left {
+                    SyntaxNodeElement::Node(node) => node.ast_reflection_style.to_sexp_name(),
+                    SyntaxNodeElement::Leaf(leaf) => format!("{:?}", leaf.value),
+                };
+
+                path.push(format!("{}:{}", i, name));
+
+                match get_divergent_path(each_left, each_right, path) {
+                    Some(reason) => return Some(reason),
+                    None => (),
+                }
+
+                path.pop();
+            }
+
+            None
+        },
+        (SyntaxNodeElement::Leaf(left_leaf), SyntaxNodeElement::Leaf(right_leaf)) => {
+            if left_leaf.value != right_leaf.value || left_leaf.raw_value != right_leaf.raw_value {
+                Some(format!("value: {:?} / {:?}", left_leaf.value, right_leaf.value))
+            } else if left_leaf.ast_reflection_style != right_leaf.ast_reflection_style {
+                Some(format!("reflection style: {} / {}", left_leaf.ast_reflection_style.to_sexp_name(), right_leaf.ast_reflection_style.to_sexp_name()))
+            } else if left_leaf.pos.index != right_leaf.pos.index || left_leaf.pos.line != right_leaf.pos.line || left_leaf.pos.column != right_leaf.pos.column {
+                Some(format!("position: {} (index {}) / {} (index {})", left_leaf.pos, left_leaf.pos.index, right_leaf.pos, right_leaf.pos.index))
+            } else {
+                None
+            }
+        },
+        _ => Some("node and leaf".to_string()),
+    };
+}
+
+// note: 位置は 1 行目の index 列目とし、入力のパスを TEST_INPUT_FILE_PATH とする
+fn test_leaf(value: &str, index: usize, ast_reflection_style: ASTReflectionStyle, uuid: uuid::Uuid) -> SyntaxNodeElement {
+    return SyntaxNodeElement::Leaf(Box::new(SyntaxLeaf::new(CharacterPosition::new(Some(TEST_INPUT_FILE_PATH.to_string()), index, 0, index), value.to_string(), ast_reflection_style, uuid)));
+}
+
+fn test_node(sub_elems: Vec<SyntaxNodeElement>, ast_reflection_style: ASTReflectionStyle, uuid: uuid::Uuid) -> SyntaxNodeElement {
+    return SyntaxNodeElement::Node(Box::new(SyntaxNode::new(sub_elems, ast_reflection_style, uuid)));
+}
+
+// note: 反映名のない Reflectable なリーフ "a" のみを子要素に持つノード
+fn test_leaf_node(ast_reflection_style: ASTReflectionStyle, uuid: uuid::Uuid) -> SyntaxNodeElement {
+    return test_node(vec![test_leaf("a", 0, ASTReflectionStyle::reflection(String::new()), uuid::Uuid::new_v4())], ast_reflection_style, uuid);
+}
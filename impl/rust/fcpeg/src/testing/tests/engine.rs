@@ -0,0 +1,331 @@
+use super::*;
+
+// spec: 各入力を parse_iter() と parse() で解析し、成否と parse_iter() が返す要素の列がルートノードの子要素 (UUID を除く) と一致することを確認する
+fn assert_parse_iter_consistent(grammar_src: &str, start_rule: &str, inputs: &[&str]) {
+    let cons = new_console();
+    let rule_map = load_rule_map_with_start_rule(&cons, grammar_src, start_rule);
+
+    for each_input in inputs {
+        let tree = SyntaxParser::parse(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new(each_input.to_string()), ParseConfig::default());
+        let streamed_elems = SyntaxParser::parse_iter(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new(each_input.to_string()), ParseConfig::default()).collect::<ConsoleResult<Vec<SyntaxNodeElement>>>();
+        cons.borrow_mut().clear_log();
+
+        match (tree, streamed_elems) {
+            (Ok(tree), Ok(streamed_elems)) => {
+                // note: ルートノードの反映方式と一致範囲は比較の対象外とする
+                let mut streamed_root = SyntaxNodeElement::from_node_args(streamed_elems, tree.get_child_ref().get_ast_reflection_style());
+
+                match tree.get_child_ref() {
+                    SyntaxNodeElement::Node(root_node) => streamed_root.set_src_range(root_node.src_range),
+                    SyntaxNodeElement::Leaf(_) => (),
+                }
+
+                let mut path = Vec::<String>::new();
+
+                match get_divergent_path(tree.get_child_ref(), &streamed_root, &mut path) {
+                    Some(reason) => panic!("streamed elements differ from the syntax tree\ninput:\t{:?}\npath:\t{}\nreason:\t{}\ntree:\t{}\nstreamed:\t{}", each_input, path.join("/"), reason, tree.to_sexp(false), SyntaxTree::from_node(streamed_root).to_sexp(false)),
+                    None => (),
+                }
+            },
+            (Err(()), Err(())) => (),
+            (Ok(_), Err(())) => panic!("parsing succeeded only without streaming\ninput:\t{:?}", each_input),
+            (Err(()), Ok(_)) => panic!("parsing succeeded only with streaming\ninput:\t{:?}", each_input),
+        }
+    }
+}
+
+#[test]
+fn parse_iter_corpus_consistent() {
+    for (each_grammar_src, each_start_rule, each_inputs) in MEMOIZATION_TEST_CORPUS {
+        assert_parse_iter_consistent(each_grammar_src, each_start_rule, each_inputs);
+    }
+}
+
+// spec: 各入力を再帰型と反復型の解析器でメモ化の有効時と無効時のそれぞれについて解析し、成否・構文木・ログ・統計情報・部分木が一致することを確認する
+fn assert_engines_consistent(grammar_src: &str, start_rule: &str, inputs: &[&str]) {
+    assert_engines_consistent_with_config(grammar_src, start_rule, inputs, ParseConfig::default());
+}
+
+fn assert_engines_consistent_with_config(grammar_src: &str, start_rule: &str, inputs: &[&str], config: ParseConfig) {
+    let cons = new_console();
+    let rule_map = load_rule_map_with_start_rule(&cons, grammar_src, start_rule);
+
+    for each_input in inputs {
+        for enable_memoization in vec![true, false] {
+            let recursive_config = config.clone().with_memoization(enable_memoization).with_engine(ParseEngine::Recursive);
+            let iterative_config = config.clone().with_memoization(enable_memoization).with_engine(ParseEngine::Iterative);
+            let recursive_result = parse_with_engine(&cons, rule_map.clone(), each_input, recursive_config);
+            let iterative_result = parse_with_engine(&cons, rule_map.clone(), each_input, iterative_config);
+            let (recursive_tree, recursive_logs, recursive_stats, recursive_partial_sexp) = recursive_result;
+            let (iterative_tree, iterative_logs, iterative_stats, iterative_partial_sexp) = iterative_result;
+
+            match (recursive_tree, iterative_tree) {
+                (Ok(recursive_tree), Ok(iterative_tree)) => {
+                    let mut path = Vec::<String>::new();
+
+                    match get_divergent_path(recursive_tree.get_child_ref(), iterative_tree.get_child_ref(), &mut path) {
+                        Some(reason) => panic!("parse results differ between the recursive and iterative engines\ninput:\t{:?}\nmemoization:\t{}\npath:\t{}\nreason:\t{}\nrecursive:\t{}\niterative:\t{}", each_input, enable_memoization, path.join("/"), reason, recursive_tree.to_sexp(false), iterative_tree.to_sexp(false)),
+                        None => (),
+                    }
+                },
+                (Err(()), Err(())) => (),
+                (Ok(_), Err(())) => panic!("parsing succeeded only with the recursive engine\ninput:\t{:?}\nmemoization:\t{}\nlogs:\t{:?}", each_input, enable_memoization, iterative_logs),
+                (Err(()), Ok(_)) => panic!("parsing succeeded only with the iterative engine\ninput:\t{:?}\nmemoization:\t{}\nlogs:\t{:?}", each_input, enable_memoization, recursive_logs),
+            }
+
+            assert_eq!(recursive_logs, iterative_logs, "logs differ between the recursive and iterative engines (input: {:?}, memoization: {})", each_input, enable_memoization);
+            assert_eq!(recursive_stats, iterative_stats, "stats differ between the recursive and iterative engines (input: {:?}, memoization: {})", each_input, enable_memoization);
+            assert_eq!(recursive_partial_sexp, iterative_partial_sexp, "partial trees differ between the recursive and iterative engines (input: {:?}, memoization: {})", each_input, enable_memoization);
+        }
+    }
+}
+
+// ret: (解析結果, ログの (文字列, 位置) の一覧, 統計情報, 部分木の S 式表現)
+fn parse_with_engine(cons: &Rc<RefCell<Console>>, rule_map: Arc<Box<RuleMap>>, input: &str, config: ParseConfig) -> (ConsoleResult<SyntaxTree>, Vec<(String, Option<usize>)>, ParseStats, Option<String>) {
+    let mut parser = SyntaxParser::new(cons.clone(), rule_map, TEST_INPUT_FILE_PATH.to_string(), Box::new(input.to_string()), config.enable_memoization).with_config(&config);
+    let result = parser.run();
+    let logs = parser.get_logs().iter().map(|each_log| (each_log.to_string(), each_log.get_position().map(|pos| pos.index))).collect::<Vec<(String, Option<usize>)>>();
+    let partial_sexp = parser.get_partial_tree().map(|tree| tree.to_sexp(true));
+    cons.borrow_mut().clear_log();
+    return (result, logs, parser.get_stats(), partial_sexp);
+}
+
+// note: 順不同・先読み・反映されない引数 ID・分岐内のエラー等、MEMOIZATION_TEST_CORPUS にない要素を扱う
+const ENGINE_TEST_CORPUS: &'static [(&'static str, &'static str, &'static [&'static str])] = &[
+    (
+        r##"[Main]{
+            Main <- ("a" : "b"#B : "c"+)^ "\z"#,
+        }"##,
+        ".Main.Main",
+        &["abc", "cba", "bccca", "ab", "abb", "aabc", ""],
+    ),
+    (
+        r##"[Main]{
+            Main <- (Word (" "# Word)*##)? "\z"#,
+            Word <- !("end" ![a-z]) &[a-z] JOIN<[a-z]+> ("!")?#Bang,
+        }"##,
+        ".Main.Main",
+        &["ab cd", "ab end", "endless x!", "a!! b", "x  y"],
+    ),
+    (
+        r##"[Main]{
+            Main <- Wrap<Item> ("," Wrap<Item#>)*## "\z"#,
+            Wrap<$T> <- "<"# $T# ">"# : $T##,
+            Item <- CAPTURE<"q", ("'" : "\"")>#Open JOIN<(!MATCH_CAPTURED<"q"> .)*> MATCH_CAPTURED<"q">#,
+        }"##,
+        ".Main.Main",
+        &["'a'", "<'a'>,\"b\"", "'a\"", "<'a',\"b'\">", "'a','"],
+    ),
+    (
+        r##"[Main]{
+            Main <- (Known : Transformed : EPSILON<"e">) "\z"#,
+            Known <- "k",
+            Transformed <- INTO<"t", "upper">,
+        }"##,
+        ".Main.Main",
+        &["k", "t", "x"],
+    ),
+    (
+        r##"[Main]{
+            Main <- Item ","# Item#Key ","# Pick "\z"#,
+            Item <- Word,
+            Word <- Letters,
+            Letters <- [a-z]+,
+            Pick <- Hidden,
+            Hidden <- [0-9]#,
+        }"##,
+        ".Main.Main",
+        &["ab,c,1", "ab,,1", "a,b,c"],
+    ),
+];
+
+#[test]
+fn engines_corpus_consistent() {
+    for (each_grammar_src, each_start_rule, each_inputs) in MEMOIZATION_TEST_CORPUS.iter().chain(ENGINE_TEST_CORPUS) {
+        assert_engines_consistent(each_grammar_src, each_start_rule, each_inputs);
+    }
+
+    // note: 左再帰による入れ子の上限超過; 各解析器で同じ失敗となることのみを確認するため上限を下げる
+    let left_recursion_grammar_src = r##"[Main]{
+        Main <- Left "\z"#,
+        Left <- Left "a" : "a",
+    }"##;
+
+    assert_engines_consistent_with_config(left_recursion_grammar_src, ".Main.Main", &["a", "aa"], ParseConfig::default().with_max_depth(32));
+}
+
+// spec: 各入力を最小一致長に満たないグループの検査を省く場合と省かない場合で解析し、成否と構文木が一致し、省く場合に表現字句の評価回数が増えないことを確認する
+// ret: (省く場合, 省かない場合) の表現字句の評価回数の合計
+fn assert_short_group_skip_consistent(grammar_src: &str, start_rule: &str, inputs: &[&str]) -> (usize, usize) {
+    let cons = new_console();
+    let rule_map = load_rule_map_with_start_rule(&cons, grammar_src, start_rule);
+    let mut expr_eval_counts = (0, 0);
+
+    for each_input in inputs {
+        for each_engine in vec![ParseEngine::Recursive, ParseEngine::Iterative] {
+            let config = ParseConfig::default().with_engine(each_engine);
+            let (skipped_tree, _, skipped_stats, _) = parse_with_engine(&cons, rule_map.clone(), each_input, config.clone());
+            let (unskipped_tree, _, unskipped_stats, _) = parse_with_engine(&cons, rule_map.clone(), each_input, config.with_short_group_skip(false));
+
+            match (skipped_tree, unskipped_tree) {
+                (Ok(skipped_tree), Ok(unskipped_tree)) => {
+                    let mut path = Vec::<String>::new();
+
+                    match get_divergent_path(skipped_tree.get_child_ref(), unskipped_tree.get_child_ref(), &mut path) {
+                        Some(reason) => panic!("skipping short groups changed the parse result\ninput:\t{:?}\nengine:\t{:?}\npath:\t{}\nreason:\t{}\nskipped:\t{}\nunskipped:\t{}", each_input, each_engine, path.join("/"), reason, skipped_tree.to_sexp(false), unskipped_tree.to_sexp(false)),
+                        None => (),
+                    }
+                },
+                (Err(()), Err(())) => (),
+                (Ok(_), Err(())) => panic!("parsing succeeded only with skipping short groups\ninput:\t{:?}\nengine:\t{:?}", each_input, each_engine),
+                (Err(()), Ok(_)) => panic!("parsing succeeded only without skipping short groups\ninput:\t{:?}\nengine:\t{:?}", each_input, each_engine),
+            }
+
+            assert!(skipped_stats.expr_eval_count <= unskipped_stats.expr_eval_count, "skipping short groups increased expression evaluations (input: {:?}, engine: {:?}, skipped: {}, unskipped: {})", each_input, each_engine, skipped_stats.expr_eval_count, unskipped_stats.expr_eval_count);
+            expr_eval_counts.0 += skipped_stats.expr_eval_count;
+            expr_eval_counts.1 += unskipped_stats.expr_eval_count;
+        }
+    }
+
+    return expr_eval_counts;
+}
+
+// note: コーパス全体では入力の末尾で省かれるグループがあるため、評価回数は真に減る
+#[test]
+fn short_group_skip_corpus_consistent() {
+    let mut skipped_count = 0;
+    let mut unskipped_count = 0;
+
+    for (each_grammar_src, each_start_rule, each_inputs) in MEMOIZATION_TEST_CORPUS.iter().chain(ENGINE_TEST_CORPUS) {
+        let (each_skipped_count, each_unskipped_count) = assert_short_group_skip_consistent(each_grammar_src, each_start_rule, each_inputs);
+        skipped_count += each_skipped_count;
+        unskipped_count += each_unskipped_count;
+    }
+
+    assert!(skipped_count < unskipped_count, "skipping short groups did not reduce expression evaluations over the corpus (skipped: {}, unskipped: {})", skipped_count, unskipped_count);
+}
+
+// note: 既定の上限の範囲で解析できるべき入れ子の深さ; Main と各 Item (Expr / Term / Factor と Num) の規則の呼び出しが DEFAULT_MAX_DEPTH を超えない
+const DEFAULT_DEPTH_TEST_LEN: usize = DEFAULT_MAX_DEPTH - 1;
+const DEFAULT_DEPTH_TEST_NESTING: usize = (DEFAULT_MAX_DEPTH - 5) / 3;
+
+// spec: 右再帰で DEFAULT_DEPTH_TEST_LEN 個の要素を読む文法と、DEFAULT_DEPTH_TEST_NESTING 重の括弧を Expr / Term / Factor で読む文法が既定の設定の再帰型の解析器で解析でき、上限を超える左再帰はスタックを溢れさせずに TooDeepRuleNesting となることを確認する
+// note: 既定のスタックサイズのスレッド (テストのスレッド) で解析する
+#[test]
+fn default_max_depth_accepts_deep_inputs() {
+    let item_grammar_src = r##"[Main]{
+        Main <- Item "\z"#,
+        Item <- "a"# Item?,
+    }"##;
+
+    let item_sexp = format!("(Main {}{})", "(Item ".repeat(DEFAULT_DEPTH_TEST_LEN), ")".repeat(DEFAULT_DEPTH_TEST_LEN));
+    assert_parse_tree(item_grammar_src, ".Main.Main", &"a".repeat(DEFAULT_DEPTH_TEST_LEN), &item_sexp);
+
+    let expr_grammar_src = r##"[Main]{
+        Main <- Expr "\z"#,
+        Expr <- Term (("+" : "-") Term)*##,
+        Term <- Factor (("*" : "/") Factor)*##,
+        Factor <- Num : "("# Expr ")"#,
+        Num <- JOIN<[0-9]+>,
+    }"##;
+
+    let input = "(".repeat(DEFAULT_DEPTH_TEST_NESTING) + "1" + &")".repeat(DEFAULT_DEPTH_TEST_NESTING);
+    let expr_sexp = format!("(Main {}(Expr (Term (Factor (Num \"1\")))){})", "(Expr (Term (Factor ".repeat(DEFAULT_DEPTH_TEST_NESTING), ")))".repeat(DEFAULT_DEPTH_TEST_NESTING));
+    assert_parse_tree(expr_grammar_src, ".Main.Main", &input, &expr_sexp);
+
+    let left_recursion_grammar_src = r##"[Main]{
+        Main <- Left "\z"#,
+        Left <- Left "a" : "a",
+    }"##;
+
+    assert_parse_fails(left_recursion_grammar_src, "aa", &format!("too deep rule nesting over {}", DEFAULT_MAX_DEPTH));
+}
+
+const DEEP_NESTING_TEST_DEPTH: usize = 100000;
+
+// spec: DEEP_NESTING_TEST_DEPTH 重の括弧を既定のスタックサイズのスレッドで反復型の解析器により解析できることを確認する
+// note: S 式表現は再帰的に処理されるため、深さと一致範囲のみを確認する
+#[test]
+fn deep_nesting_with_iterative_engine() {
+    let grammar_src = r##"[Main]{
+        Main <- Paren "\z"#,
+        Paren <- "("# Paren? ")"#,
+    }"##;
+
+    let handle = std::thread::spawn(move || {
+        let cons = new_console();
+        let rule_map = load_rule_map(&cons, grammar_src);
+        let input = "(".repeat(DEEP_NESTING_TEST_DEPTH) + &")".repeat(DEEP_NESTING_TEST_DEPTH);
+        // note: メモ化は既定のまま有効とし、メモ化の結果の複製でもスタックが溢れないことを確認する
+        let config = ParseConfig::default().with_max_depth(DEEP_NESTING_TEST_DEPTH * 2).with_engine(ParseEngine::Iterative);
+
+        let tree = match SyntaxParser::parse(cons.clone(), rule_map, TEST_INPUT_FILE_PATH.to_string(), Box::new(input.clone()), config) {
+            Ok(v) => v,
+            Err(()) => {
+                cons.borrow().print_all();
+                panic!("failed to parse {} nested parentheses", DEEP_NESTING_TEST_DEPTH);
+            },
+        };
+
+        let root_range = match tree.get_child_ref() {
+            SyntaxNodeElement::Node(node) => node.matched_range(),
+            SyntaxNodeElement::Leaf(_) => None,
+        };
+
+        // note: 一致範囲は "\z" が消費する EOF 用のヌル文字を含む
+        (tree.depth(), root_range, input.chars().count() + 1)
+    });
+
+    let (depth, root_range, input_len) = match handle.join() {
+        Ok(v) => v,
+        Err(_) => panic!("parser thread panicked"),
+    };
+
+    assert!(depth >= DEEP_NESTING_TEST_DEPTH, "unexpected tree depth {} for {} nested parentheses", depth, DEEP_NESTING_TEST_DEPTH);
+    assert_eq!(root_range, Some((0, input_len)), "unexpected matched range of the root node");
+}
+
+const STREAMING_TEST_RECORD_COUNT: usize = 100000;
+
+// spec: STREAMING_TEST_RECORD_COUNT 件のレコードからなる入力を parse_iter() で解析し、レコードが入力順にすべて返されることを確認する
+// note: 繰り返しの上限はレコード数に合わせて引き上げる
+#[test]
+fn parse_iter_record_count() {
+    let grammar_src = r##"[Main]{
+        Main <- Record* "\z"#,
+        Record <- JOIN<[a-z]+>#Name ","# JOIN<[0-9]+>#Num "\n"#,
+    }"##;
+
+    let cons = new_console();
+    let rule_map = load_rule_map_with_start_rule(&cons, grammar_src, ".Main.Main");
+    let input = (0..STREAMING_TEST_RECORD_COUNT).map(|i| format!("rec,{}\n", i)).collect::<String>();
+    let config = ParseConfig::default().with_loop_limit(STREAMING_TEST_RECORD_COUNT + 1);
+    let mut record_count = 0usize;
+
+    for each_result in SyntaxParser::parse_iter(cons.clone(), rule_map, TEST_INPUT_FILE_PATH.to_string(), Box::new(input), config) {
+        let each_elem = match each_result {
+            Ok(v) => v,
+            Err(()) => {
+                cons.borrow().print_all();
+                panic!("failed to parse record {}", record_count);
+            },
+        };
+
+        if !each_elem.is_reflectable() {
+            continue;
+        }
+
+        let expected_sexp = format!("(Record Name:\"rec\" Num:\"{}\")", record_count);
+        let actual_sexp = each_elem.to_sexp(true).unwrap_or_default();
+
+        if actual_sexp != expected_sexp {
+            panic!("record mismatch\n{}", get_sexp_diff("expected", &expected_sexp, "actual", &actual_sexp));
+        }
+
+        record_count += 1;
+    }
+
+    if record_count != STREAMING_TEST_RECORD_COUNT {
+        panic!("expected {} records but got {}", STREAMING_TEST_RECORD_COUNT, record_count);
+    }
+}
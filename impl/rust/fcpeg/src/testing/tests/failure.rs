@@ -0,0 +1,614 @@
+use super::*;
+
+// spec: assert_parse_fails() と同様だが、指定の部分文字列を含むログの位置 (文字インデックス) が expected_index_range に含まれることも確認する
+fn assert_parse_fails_at(grammar_src: &str, input: &str, expected_error_substring: &str, expected_index_range: std::ops::Range<usize>) {
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, grammar_src);
+
+    for enable_memoization in vec![true, false] {
+        let mut parser = SyntaxParser::new(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new(input.to_string()), enable_memoization);
+
+        match parser.run() {
+            Ok(tree) => panic!("parsing unexpectedly succeeded (memoization: {})\ntree:\t{}", enable_memoization, tree.to_sexp(true)),
+            Err(()) => (),
+        }
+
+        let matched_log = match parser.get_logs().iter().find(|each_log| each_log.to_string().contains(expected_error_substring)) {
+            Some(v) => v,
+            None => panic!("no error message contains '{}' (memoization: {})", expected_error_substring, enable_memoization),
+        };
+
+        match matched_log.get_position() {
+            Some(pos) if expected_index_range.contains(&pos.index) => (),
+            Some(pos) => panic!("error position {} (index {}) is out of {:?} (memoization: {})\nmessage:\t{}", pos, pos.index, expected_index_range, enable_memoization, matched_log),
+            None => panic!("error has no position (memoization: {})\nmessage:\t{}", enable_memoization, matched_log),
+        }
+
+        cons.borrow_mut().clear_log();
+    }
+}
+
+// spec: 解析が失敗し、失敗した位置の手前までに一致した部分の構文木 (SyntaxParser::get_partial_tree()) の S 式表現が期待値と等しいことを確認する
+fn assert_partial_tree(grammar_src: &str, start_rule: &str, input: &str, expected_sexp: &str) {
+    let cons = new_console();
+    let rule_map = load_rule_map_with_start_rule(&cons, grammar_src, start_rule);
+    let mut partial_sexps = Vec::<String>::new();
+
+    for enable_memoization in vec![true, false] {
+        let mut parser = SyntaxParser::new(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new(input.to_string()), enable_memoization);
+
+        match parser.run() {
+            Ok(tree) => panic!("parsing unexpectedly succeeded (memoization: {})\ntree:\t{}", enable_memoization, tree.to_sexp(true)),
+            Err(()) => (),
+        }
+
+        match parser.get_partial_tree() {
+            Some(tree) => partial_sexps.push(tree.to_sexp(true)),
+            None => panic!("no partial tree is available (memoization: {})", enable_memoization),
+        }
+
+        cons.borrow_mut().clear_log();
+    }
+
+    assert_sexp_eq(&partial_sexps[0], &partial_sexps[1], expected_sexp);
+}
+
+// spec: 四則演算の文法で "1+2garbage" を解析し、未消費の入力が "1+2" の直後から "garbage" として報告され、"1+2" の構文木が得られることを確認する
+#[test]
+fn unconsumed_input_report() {
+    let grammar_src = r##"[Main]{
+        Main <- Num ("+"# Num)*##,
+        Num <- JOIN<[0-9]+>,
+    }"##;
+
+    assert_parse_fails_at(grammar_src, "1+2garbage", "unconsumed input \"garbage\" (7 character(s))", 3..4);
+    assert_partial_tree(grammar_src, ".Main.Main", "1+2garbage", "(Main (Num \"1\") (Num \"2\"))");
+}
+
+// spec: 3 つ目の関数定義に構文の誤りがある入力で開始規則が一致せずに解析が失敗し、前の 2 つの関数定義を持つ構文木が得られることを確認する
+#[test]
+fn partial_tree_on_failure() {
+    let grammar_src = r##"[Main]{
+        Main <- Space# Func* "\z"#,
+        Func <- "fn"# Space# Name Space# "("# Space# ")"# Space# "{"# Space# Stmt* "}"# Space#,
+        Stmt <- Name Space# ";"# Space#,
+        Name <- JOIN<[a-z]+>,
+        Space <- (" " : "\n")*,
+    }"##;
+
+    let input = "fn first() {\n    a;\n}\n\nfn second() {\n    b;\n    c;\n}\n\nfn third() {\n    d\n}\n";
+
+    assert_parse_fails(grammar_src, input, "no succeeded rule");
+    assert_partial_tree(grammar_src, ".Main.Main", input, "(Main (Func (Name \"first\") (Stmt (Name \"a\"))) (Func (Name \"second\") (Stmt (Name \"b\")) (Stmt (Name \"c\"))))");
+
+    // note: 開始規則の最初の要素で失敗した場合は一致した部分がない
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, "[Main]{\n    Main <- \"fn\" \"\\z\"#,\n}");
+    let mut parser = SyntaxParser::new(cons.clone(), rule_map, TEST_INPUT_FILE_PATH.to_string(), Box::new("let".to_string()), true);
+    assert!(parser.run().is_err(), "parsing unexpectedly succeeded");
+    assert!(parser.get_partial_tree().is_none(), "partial tree is available without matched elements");
+    cons.borrow_mut().clear_log();
+}
+
+// spec: 開始規則が存在しない・要素を持たない文法が入力によらず文法の誤りとして報告され、空の入力も開始規則で検査されることを確認する
+#[test]
+fn start_rule_validation() {
+    let cons = new_console();
+
+    // ret: (解析の成否, 出力されたログの (コード, 位置の行))
+    let parse_logs = |rule_map: Arc<Box<RuleMap>>, input: &str, config: ParseConfig| {
+        let mut parser = SyntaxParser::new(cons.clone(), rule_map, TEST_INPUT_FILE_PATH.to_string(), Box::new(input.to_string()), config.enable_memoization).with_config(&config);
+        let is_succeeded = parser.run().is_ok();
+        let logs = parser.get_logs().iter().map(|each_log| (each_log.get_code(), each_log.get_position().map(|pos| pos.line))).collect::<Vec<(&'static str, Option<usize>)>>();
+        cons.borrow_mut().clear_log();
+        (is_succeeded, logs)
+    };
+
+    // note: start 命令がなければ既定の開始規則 .Main.Main を用いる
+    let no_main_rule_map = load_rule_map(&cons, "[Main]{\n    Expr <- [a-z]* \"\\z\"#,\n}");
+
+    for each_input in vec!["", "abc"] {
+        assert_eq!(parse_logs(no_main_rule_map.clone(), each_input, ParseConfig::default()), (false, vec![("StartRuleNotFound", Some(0))]), "missing default start rule is not reported (input: {:?})", each_input);
+    }
+
+    assert_eq!(parse_logs(no_main_rule_map.clone(), "", ParseConfig::default().with_start_rule_id(".Main.Missing".to_string())), (false, vec![("StartRuleNotFound", Some(0))]));
+    assert_eq!(parse_logs(no_main_rule_map.clone(), "", ParseConfig::default().with_start_rule_id(".Main.Expr".to_string())), (true, vec![]));
+
+    // note: 宣言した規則が後から取り除かれた場合は start 命令の位置を示す
+    let declared_rule_map = load_rule_map(&cons, "[Main]{\n    + start Main.Expr,\n    Expr <- [a-z]+ \"\\z\"#,\n}");
+    let mut removed_rule_map = (**declared_rule_map).clone();
+    removed_rule_map.rule_map.remove(".Main.Expr");
+    assert_eq!(parse_logs(Arc::new(Box::new(removed_rule_map)), "abc", ParseConfig::default()), (false, vec![("StartRuleNotFound", Some(1))]));
+
+    let mut empty_rule_map = (**declared_rule_map).clone();
+
+    match empty_rule_map.rule_map.get_mut(".Main.Expr") {
+        Some(rule) => rule.group.sub_elems.clear(),
+        None => panic!("start rule '.Main.Expr' is not defined in the grammar"),
+    }
+
+    assert_eq!(parse_logs(Arc::new(Box::new(empty_rule_map)), "abc", ParseConfig::default()), (false, vec![("InvalidRuleElementStructure", None)]));
+
+    // note: 空の入力でも 1 文字以上を要求する開始規則は失敗し、空を許す開始規則は成功する
+    let (is_succeeded, _) = parse_logs(declared_rule_map.clone(), "", ParseConfig::default());
+    assert!(!is_succeeded, "empty input is accepted by a start rule requiring a token");
+    assert_parse_tree("[Main]{\n    Main <- [a-z]* \"\\z\"#,\n}", ".Main.Main", "", "(Main)");
+}
+
+// spec: 解析が失敗し、NoSucceededRule のログが持つ期待される字句 (SyntaxParsingLog::get_expected_tokens()) が期待値と等しいことを確認する
+// spec: expected_found が None であれば入力の終端で失敗したことを表す
+fn assert_expected_tokens(grammar_src: &str, input: &str, expected_tokens: &[&str], expected_found: Option<&str>, expected_index: usize) {
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, grammar_src);
+
+    for enable_memoization in vec![true, false] {
+        let mut parser = SyntaxParser::new(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new(input.to_string()), enable_memoization);
+
+        match parser.run() {
+            Ok(tree) => panic!("parsing unexpectedly succeeded (memoization: {})\ntree:\t{}", enable_memoization, tree.to_sexp(true)),
+            Err(()) => (),
+        }
+
+        let expected = match parser.get_logs().iter().find_map(|each_log| each_log.get_expected_tokens()) {
+            Some(v) => v.clone(),
+            None => panic!("no error has expected tokens (memoization: {})\nmessages:{}", enable_memoization, parser.get_logs().iter().map(|each_log| format!("\n\t{}", each_log)).collect::<Vec<String>>().join("")),
+        };
+
+        if expected.tokens != expected_tokens || expected.found.as_ref().map(|v| v.as_str()) != expected_found || expected.pos.index != expected_index {
+            panic!("expected tokens mismatched (memoization: {})\nexpected:\t{:?}, found: {:?} at index {}\nactual:\t\t{:?}, found: {:?} at index {}", enable_memoization, expected_tokens, expected_found, expected_index, expected.tokens, expected.found, expected.pos.index);
+        }
+
+        cons.borrow_mut().clear_log();
+    }
+}
+
+// spec: 小さな言語の文法で 3 箇所の失敗位置 (文の先頭, 式の先頭, 括弧の内側) における期待される字句を確認する
+// spec: 期待される字句が EXPECTED_TOKENS_MAX_LEN を超えればメッセージでは省略されることも確認する
+#[test]
+fn expected_token_suggestions() {
+    let grammar_src = r##"[Main]{
+        Main <- Stmt* "\z"#,
+        Stmt <- (Let : Print) ";"# "\n"*#,
+        Let <- "let"# " "# Ident " "# "="# " "# Expr,
+        Print <- "print"# " "# Expr,
+        Expr <- Term (" "# ("+" : "-") " "# Term)*##,
+        Term <- Num : Ident : "("# Expr ")"#,
+        Num <- JOIN<[0-9]+>,
+        Ident <- JOIN<[a-z]+>,
+    }"##;
+
+    assert_expected_tokens(grammar_src, "let x = 1;\nlex y = 2;", &["\"\\n\"", "\"let\"", "\"print\"", "\"\\z\""], Some("l"), 11);
+    assert_expected_tokens(grammar_src, "let x = ;", &["[0-9]", "[a-z]", "\"(\""], Some(";"), 8);
+    assert_expected_tokens(grammar_src, "print (1 + x;", &["[a-z]", "\" \"", "\")\""], Some(";"), 12);
+
+    let keyword_grammar_src = r##"[Main]{
+        Main <- ("a" : "b" : "c" : "d" : "e" : "f" : "g" : "h" : "i" : "j") "\z"#,
+    }"##;
+
+    let keyword_tokens = ["\"a\"", "\"b\"", "\"c\"", "\"d\"", "\"e\"", "\"f\"", "\"g\"", "\"h\"", "\"i\"", "\"j\""];
+    assert_expected_tokens(keyword_grammar_src, "z", &keyword_tokens, Some("z"), 0);
+
+    let expected = ExpectedTokens {
+        pos: CharacterPosition::new(Some(TEST_INPUT_FILE_PATH.to_string()), 0, 1, 1),
+        tokens: keyword_tokens.iter().map(|each_token| each_token.to_string()).collect::<Vec<String>>(),
+        found: Some("z".to_string()),
+    };
+
+    assert_eq!(expected.to_string(), format!("expected: \"a\", \"b\", \"c\", \"d\", \"e\", \"f\", \"g\", \"h\" and 2 more, found: 'z' at {}", expected.pos));
+}
+
+// spec: 規則の呼び出しでジェネリクス引数の数が合わない場合、InvalidGenericsArgumentLength が呼び出し位置に加えて呼び出された規則の宣言位置と引数 ID を持つことを確認する
+#[test]
+fn argument_declaration_notes() {
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, "[Main]{\n    Main <- List<\"a\", \",\"> \"\\z\"#,\n    List<$Elem> <- $Elem (\",\"# $Elem)*,\n}");
+
+    let list_pos = match rule_map.get_rule_pos(".Main.List") {
+        Some(v) => v.clone(),
+        None => panic!("position of rule '.Main.List' is not found"),
+    };
+
+    for enable_memoization in vec![true, false] {
+        let mut parser = SyntaxParser::new(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new("a,a".to_string()), enable_memoization);
+
+        match parser.run() {
+            Ok(tree) => panic!("parsing unexpectedly succeeded (memoization: {})\ntree:\t{}", enable_memoization, tree.to_sexp(true)),
+            Err(()) => (),
+        }
+
+        let (call_pos, decl) = match parser.get_logs().iter().find(|each_log| each_log.get_code() == "InvalidGenericsArgumentLength") {
+            Some(SyntaxParsingLog::InvalidGenericsArgumentLength { pos, expected_arg_len: 1, decl: Some(decl) }) => (pos.clone(), decl.clone()),
+            Some(log) => panic!("unexpected argument length error (memoization: {}):\t{}", enable_memoization, log),
+            None => panic!("no argument length error is reported (memoization: {})", enable_memoization),
+        };
+
+        // note: 呼び出し位置は Main の定義中、宣言位置は List の定義の行にある
+        assert_eq!(call_pos.line, 1, "call site is not reported (memoization: {})", enable_memoization);
+        assert_eq!((decl.pos.line, decl.pos.index), (list_pos.line, list_pos.index), "declaration position is not reported (memoization: {})", enable_memoization);
+        assert_eq!(decl.pos.line, 2, "declaration position points at another rule (memoization: {})", enable_memoization);
+        assert_eq!(decl.rule_id, ".Main.List");
+        assert_eq!(decl.arg_ids, vec!["Elem".to_string()]);
+        assert_eq!(decl.to_note_text("generics", "<", ">"), "note:\trule '.Main.List' declared here takes 1 generics argument(s) <$Elem>");
+
+        cons.borrow_mut().clear_log();
+    }
+}
+
+// spec: 構文解析器が生成した構文木のリーフの位置が入力と一致し、手で組み立てた位置のずれを SyntaxParser::validate_positions() が PositionMismatch として検出することを確認する
+#[test]
+fn position_mismatch_detection() {
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, "[Main]{\n    Main <- Word (\"\\n\"# Word)* \"\\z\"#,\n    Word <- JOIN<[a-zä]+>,\n}");
+    let mut parser = SyntaxParser::new(cons.clone(), rule_map, TEST_INPUT_FILE_PATH.to_string(), Box::new("äb\ncd".to_string()), true);
+
+    let parsed_tree = match parser.run() {
+        Ok(v) => v,
+        Err(()) => {
+            cons.borrow().print_all();
+            panic!("failed to parse the input");
+        },
+    };
+
+    assert_leaf_positions(&parser, &parsed_tree);
+
+    let leaf = |index: usize, value: &str| SyntaxNodeElement::from_leaf_args(CharacterPosition::new(Some(TEST_INPUT_FILE_PATH.to_string()), index, 0, index), value.to_string(), ASTReflectionStyle::reflection(String::new()));
+
+    // note: (説明, リーフ, 検出されるべき (文字インデックス, 値, 入力))
+    let cases = vec![
+        ("matched non-ascii leaf", leaf(0, "äb"), None),
+        ("matched eof leaf", leaf(5, "\0"), None),
+        ("shifted leaf", leaf(2, "cd"), Some((2, "cd", "\nc"))),
+        ("leaf over the end of source", leaf(5, "cd"), Some((5, "cd", "\0"))),
+        ("leaf out of source", leaf(10, "cd"), Some((10, "cd", ""))),
+    ];
+
+    for (each_desc, each_leaf, each_expected) in cases {
+        let tree = SyntaxTree::from_node_args(vec![each_leaf], ASTReflectionStyle::reflection("Main".to_string()));
+
+        let actual = parser.validate_positions(&tree).iter().map(|each_log| match each_log {
+            SyntaxParsingLog::PositionMismatch { pos, value, found } => (pos.index, value.clone(), found.clone()),
+            _ => panic!("unexpected log ({}): {}", each_desc, each_log),
+        }).collect::<Vec<(usize, String, String)>>();
+
+        let expected = each_expected.iter().map(|(index, value, found)| (*index, value.to_string(), found.to_string())).collect::<Vec<(usize, String, String)>>();
+        assert_eq!(actual, expected, "unexpected position mismatches ({})", each_desc);
+    }
+}
+
+// spec: 共有するコンソールに 2 つの解析のログが交互に出力されても、LogRecorder が各ログを入力のパスと段階で区別して記録することを確認する
+// note: 入力 a.txt の解析中に、INTO の変換の中で入力 b.txt を解析する
+#[test]
+fn scoped_logs() {
+    let cons = new_console();
+    let recorder = LogRecorder::new();
+    let inner_rule_map = load_rule_map(&cons, r##"[Main]{
+        Main <- [0-9]+ "\z"#,
+    }"##);
+
+    let outer_rule_map = load_rule_map(&cons, r##"[Main]{
+        Main <- INTO<Word, "check"> "\z"#,
+        Word <- [a-z]+,
+    }"##);
+
+    let inner_cons = cons.clone();
+    let transform_rule_map = inner_rule_map.clone();
+    let inner_recorder = recorder.clone();
+    let inner_codes = Rc::new(RefCell::new(Vec::<&'static str>::new()));
+    let transform_inner_codes = inner_codes.clone();
+    let mut registry = PrimitiveRegistry::new();
+
+    registry.register("check", Arc::new(move |elems: Vec<SyntaxNodeElement>| {
+        let mut parser = SyntaxParser::new(inner_cons.clone(), transform_rule_map.clone(), "b.txt".to_string(), Box::new("x".to_string()), true).with_config(&ParseConfig::default().with_log_recorder(inner_recorder.clone()));
+        assert!(parser.run().is_err(), "inner input was unexpectedly accepted");
+        *transform_inner_codes.borrow_mut() = parser.get_logs().iter().map(|each_log| each_log.get_code()).collect::<Vec<&'static str>>();
+
+        elems.iter().map(|each_elem| match each_elem {
+            SyntaxNodeElement::Node(node) => node.join_child_leaf_values(),
+            SyntaxNodeElement::Leaf(leaf) => leaf.value.clone(),
+        }).collect::<String>()
+    }));
+
+    let config = ParseConfig { primitive_registry: registry, ..ParseConfig::default() }.with_log_recorder(recorder.clone());
+    let mut parser = SyntaxParser::new(cons.clone(), outer_rule_map.clone(), "a.txt".to_string(), Box::new("ab!".to_string()), true).with_config(&config);
+    assert!(parser.run().is_err(), "outer input was unexpectedly accepted");
+
+    let to_codes = |logs: &Vec<ScopedLog>| logs.iter().map(|each_log| match &each_log.log {
+        ScopedLogKind::Parsing(log) => log.get_code(),
+        ScopedLogKind::Tree(log) => panic!("unexpected tree log '{}' in scope {}", log, each_log.scope),
+    }).collect::<Vec<&'static str>>();
+
+    let outer_logs = recorder.get_logs_in(Some(LogPhase::Parsing), Some("a.txt"));
+    let inner_logs = recorder.get_logs_in(Some(LogPhase::Parsing), Some("b.txt"));
+    assert!(!inner_logs.is_empty() && !outer_logs.is_empty(), "logs of either parse were not recorded");
+    assert_eq!(to_codes(&inner_logs), *inner_codes.borrow(), "logs of the inner parse were misattributed");
+    assert_eq!(to_codes(&outer_logs), parser.get_logs().iter().map(|each_log| each_log.get_code()).collect::<Vec<&'static str>>(), "logs of the outer parse were misattributed");
+    assert_eq!(recorder.len(), inner_logs.len() + outer_logs.len());
+    assert_eq!(recorder.get_logs()[0].scope, inner_logs[0].scope, "logs are not kept in the appended order");
+    assert!(outer_logs.iter().all(|each_log| each_log.scope == parser.get_log_scope()));
+
+    // note: 構文解析器を生成する前のエラーも入力のパスで記録する
+    assert!(SyntaxParser::parse_with_entry(cons.clone(), outer_rule_map.clone(), ".Main.Word", "c.txt".to_string(), Box::new("ab".to_string()), ParseConfig::default().with_log_recorder(recorder.clone())).is_err());
+    assert_eq!(to_codes(&recorder.get_logs_in(None, Some("c.txt"))), vec!["UndeclaredEntryRule"]);
+
+    let tree = match SyntaxParser::parse(cons.clone(), inner_rule_map.clone(), "d.txt".to_string(), Box::new("12".to_string()), ParseConfig::default()) {
+        Ok(v) => v,
+        Err(()) => {
+            cons.borrow().print_all();
+            panic!("failed to parse the input for tree access");
+        },
+    };
+
+    let tree_log = match tree.get_child_ref().try_get_leaf() {
+        Ok(_) => panic!("root of the tree is unexpectedly a leaf"),
+        Err(e) => e,
+    };
+
+    assert_eq!(tree_log.get_src_path(), Some(&"d.txt".to_string()), "tree log does not carry the input path");
+    recorder.record_tree_log(&tree_log);
+    assert_eq!(recorder.get_logs_in(Some(LogPhase::TreeAccess), None).len(), 1);
+    assert!(recorder.get_logs_in(Some(LogPhase::Parsing), Some("d.txt")).is_empty(), "successful parse recorded logs");
+    cons.borrow_mut().clear_log();
+}
+
+// spec: 文字列の途中で入力が終わった場合に NoSucceededRule ではなく UnexpectedEOF として報告されることを確認する
+// note: 文字列が入力の残りより長くても、残りの入力が文字列の先頭と一致しなければ NoSucceededRule とする
+#[test]
+fn truncated_input_eof_report() {
+    let grammar_src = r##"[Main]{
+        Main <- "abc" "\z"#,
+    }"##;
+
+    assert_parse_fails_at(grammar_src, "ab", "unexpected EOF while parsing rule '.Main.Main'", 0..3);
+    assert_parse_fails(grammar_src, "", "unexpected EOF while parsing rule '.Main.Main'");
+    assert_parse_fails(grammar_src, "xy", "no succeeded rule '.Main.Main'");
+
+    let group_grammar_src = r##"[Main]{
+        Main <- Item ("," Item)* "\z"#,
+        Item <- ("key" : "value"),
+    }"##;
+
+    assert_parse_fails(group_grammar_src, "key,val", "unexpected EOF while parsing rule '.Main.Item'");
+    assert_parse_fails(group_grammar_src, "key,ke", "unexpected EOF while parsing rule '.Main.Item'");
+}
+
+// spec: regex_size_limit を超える文字クラスが解析の開始時に 1 件の InvalidCharClassFormat として文字クラスの位置と正規表現のエラー文とともに報告され、上限内の文字クラスは報告されないことを確認する
+// note: 上限の既定値では同じ文法で解析できる
+#[test]
+fn char_class_size_limit() {
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, "[Main]{\n    Main <- [a-z]+ [\\x{80}-\\x{10FFFF}]+ \"\\z\"#,\n}");
+
+    for enable_memoization in vec![true, false] {
+        let config = ParseConfig::default().with_memoization(enable_memoization).with_regex_size_limits(1024, DEFAULT_REGEX_DFA_SIZE_LIMIT);
+        let mut parser = SyntaxParser::new(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new("a\u{100}".to_string()), enable_memoization).with_config(&config);
+        assert!(parser.run().is_err(), "char class over the size limit was unexpectedly compiled (memoization: {})", enable_memoization);
+
+        let logs = parser.get_logs();
+        assert_eq!(logs.len(), 1, "unexpected logs (memoization: {}):{}", enable_memoization, logs.iter().map(|each_log| format!("\n\t{}", each_log)).collect::<Vec<String>>().join(""));
+
+        match &logs[0] {
+            SyntaxParsingLog::InvalidCharClassFormat { pos, value, msg } => {
+                assert_eq!((pos.index, pos.line, pos.column), (27, 1, 19), "char class is reported at {}", pos);
+                assert_eq!(value, "[\\x{80}-\\x{10FFFF}]");
+                assert!(msg.contains("size limit"), "regex error is not included: {}", msg);
+            },
+            each_log => panic!("unexpected log: {}", each_log),
+        }
+
+        cons.borrow_mut().clear_log();
+    }
+
+    assert!(SyntaxParser::parse(cons.clone(), rule_map, TEST_INPUT_FILE_PATH.to_string(), Box::new("a\u{100}".to_string()), ParseConfig::default()).is_ok(), "char class was rejected under the default size limit");
+}
+
+// spec: 繰り返し回数の上限を超えた表現字句・グループの TooLongRepetition の位置が、両方の解析エンジンで繰り返した入力の範囲 ("x" の直後から入力の末尾まで) を指すことを確認する
+#[test]
+fn too_long_repetition_position() {
+    let cons = new_console();
+    let input = "xababababab";
+    let repeated_range = 1..input.len();
+
+    // note: (文法, 繰り返すものがグループか)
+    let cases = vec![
+        ("[Main]{\n    Main <- \"x\" [a-z]* \"\\z\"#,\n}", false),
+        ("[Main]{\n    Main <- \"x\" (\"a\" \"b\")* \"\\z\"#,\n}", true),
+    ];
+
+    for (each_grammar_src, each_is_group) in cases {
+        let rule_map = load_rule_map(&cons, each_grammar_src);
+
+        for each_engine in vec![ParseEngine::Recursive, ParseEngine::Iterative] {
+            let config = ParseConfig::default().with_loop_limit(3).with_engine(each_engine);
+            let mut parser = SyntaxParser::new(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new(input.to_string()), true).with_config(&config);
+            assert!(parser.run().is_err(), "repetition over the loop limit was unexpectedly accepted ({:?})", each_engine);
+
+            match parser.get_logs().iter().find(|each_log| each_log.get_code() == "TooLongRepetition") {
+                Some(SyntaxParsingLog::TooLongRepetition { pos, rule_id, group_uuid, loop_range: _, loop_limit }) => {
+                    assert!(repeated_range.contains(&pos.index), "position {} (index {}) is out of the repeated region {:?} ({:?})", pos, pos.index, repeated_range, each_engine);
+                    assert_eq!((rule_id.as_str(), group_uuid.is_some(), *loop_limit), (".Main.Main", each_is_group, 3), "unexpected log ({:?})", each_engine);
+                },
+                _ => panic!("TooLongRepetition is not reported ({:?})", each_engine),
+            }
+
+            cons.borrow_mut().clear_log();
+        }
+    }
+}
+
+// spec: 2 つの入口規則を宣言した文法で、同じ入力を各入口規則から解析でき、宣言されていない規則 ID (存在する規則を含む) を指定すると UndeclaredEntryRule が指定可能な規則 ID の一覧とともに出力されて失敗することを確認する
+#[test]
+fn undeclared_entry_rule() {
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, r##"[Main]{
+        + entry Main.ExprEntry,
+        + entry Main.NumEntry,
+
+        Main <- Expr ";"# "\z"#,
+        ExprEntry <- Expr "\z"#,
+        NumEntry <- Num "\z"#,
+        Expr <- Num ("+"# Num)*##,
+        Num <- JOIN<[0-9]+>,
+    }"##);
+
+    for (each_entry, each_expected_sexp) in vec![(".Main.ExprEntry", "(ExprEntry (Expr (Num \"12\")))"), (".Main.NumEntry", "(NumEntry (Num \"12\"))")] {
+        match SyntaxParser::parse_with_entry(cons.clone(), rule_map.clone(), each_entry, TEST_INPUT_FILE_PATH.to_string(), Box::new("12".to_string()), ParseConfig::default()) {
+            Ok(tree) => assert_eq!(tree.to_sexp(true), each_expected_sexp, "unexpected tree from entry rule '{}'", each_entry),
+            Err(()) => {
+                cons.borrow().print_all();
+                panic!("failed to parse the input from entry rule '{}'", each_entry);
+            },
+        }
+    }
+
+    let expected_entry_rule_ids = vec![".Main.Main", ".Main.ExprEntry", ".Main.NumEntry"];
+
+    for each_entry in vec![".Main.Num", ".Main.Missing"] {
+        let recorder = LogRecorder::new();
+        assert!(SyntaxParser::parse_with_entry(cons.clone(), rule_map.clone(), each_entry, TEST_INPUT_FILE_PATH.to_string(), Box::new("12".to_string()), ParseConfig::default().with_log_recorder(recorder.clone())).is_err(), "undeclared entry rule '{}' was unexpectedly accepted", each_entry);
+
+        let logs = recorder.get_logs();
+        assert_eq!(logs.len(), 1, "unexpected logs for entry rule '{}':{}", each_entry, logs.iter().map(|each_log| format!("\n\t{}", each_log.log)).collect::<Vec<String>>().join(""));
+
+        match &logs[0].log {
+            ScopedLogKind::Parsing(SyntaxParsingLog::UndeclaredEntryRule { rule_id, entry_rule_ids }) => {
+                assert_eq!(rule_id, each_entry);
+                assert_eq!(entry_rule_ids, &expected_entry_rule_ids, "valid entries are not listed for entry rule '{}'", each_entry);
+            },
+            each_log => panic!("unexpected log for entry rule '{}': {}", each_entry, each_log),
+        }
+
+        cons.borrow_mut().clear_log();
+    }
+}
+
+// spec: 規則定義の直前に連続するコメント命令が規則の説明として RuleMap から取得でき、include_rule_docs が真の場合のみ NoSucceededRule が失敗した規則の説明を持つことを確認する
+// note: 規則定義以外の命令を挟んだコメントは説明とならない
+#[test]
+fn rule_docs_in_failure_logs() {
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, r##"[Main]{
+        % 符号のない整数,
+        Main <- Num "\z"#,
+        % 1 桁以上の数字,
+        % 先頭の 0 も許す,,
+        Num <- JOIN<[0-9]+>,
+        % 使われないコメント,
+        + const Sign = "-",
+        Unused <- "x",
+    }"##);
+
+    assert_eq!(rule_map.get_rule_doc(".Main.Main"), Some("符号のない整数"));
+    assert_eq!(rule_map.get_rule_doc(".Main.Num"), Some("1 桁以上の数字\n先頭の 0 も許す,"));
+    assert_eq!(rule_map.get_rule_doc(".Main.Unused"), None);
+    assert_eq!(rule_map.get_rule_doc(".Main.Missing"), None);
+
+    for include_rule_docs in vec![true, false] {
+        let config = ParseConfig::default().with_rule_docs(include_rule_docs);
+        let mut parser = SyntaxParser::new(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new("x".to_string()), true).with_config(&config);
+        assert!(parser.run().is_err(), "input without digits was unexpectedly accepted");
+
+        match parser.get_logs().iter().find(|each_log| each_log.get_code() == "NoSucceededRule") {
+            Some(SyntaxParsingLog::NoSucceededRule { pos: _, rule_id: _, rule_stack: _, rule_doc, expected: _ }) => {
+                let expected_rule_doc = if include_rule_docs {
+                    Some(("Num".to_string(), "1 桁以上の数字\n先頭の 0 も許す,".to_string()))
+                } else {
+                    None
+                };
+
+                assert_eq!(rule_doc, &expected_rule_doc, "unexpected rule doc (include_rule_docs: {})", include_rule_docs);
+            },
+            _ => panic!("NoSucceededRule is not reported (include_rule_docs: {})", include_rule_docs),
+        }
+
+        cons.borrow_mut().clear_log();
+    }
+}
+
+// spec: 構文解析のログから変換した診断情報の範囲・重大度・コード・メッセージと、drain_diagnostics() による取り出し、UTF-16 の列数への変換を確認する
+#[test]
+fn parsing_log_diagnostics() {
+    let pos = CharacterPosition::new(None, 3, 1, 2);
+    let end_pos = CharacterPosition::new(None, 5, 1, 4);
+
+    let unconsumed_log = SyntaxParsingLog::UnconsumedInput { pos: pos.clone(), end_pos: end_pos, remaining_preview: "xy".to_string(), remaining_len: 2 };
+    let diagnostic = Diagnostic::from(&unconsumed_log);
+    assert_eq!(diagnostic.range, DiagnosticRange::new(DiagnosticPosition::new(1, 2), DiagnosticPosition::new(1, 4)));
+    assert_eq!(diagnostic.severity, DiagnosticSeverity::Error);
+    assert_eq!(diagnostic.code, "UnconsumedInput");
+    assert_eq!(diagnostic.message, unconsumed_log.to_string());
+    assert_eq!(diagnostic.source, "fcpeg");
+    assert_eq!(diagnostic.position_encoding, DiagnosticPositionEncoding::Utf32);
+
+    // note: 終了位置が不明なログは開始位置を終了位置とする
+    let diagnostic = Diagnostic::from(&SyntaxParsingLog::DeprecatedRuleUse { pos: pos.clone(), rule_id: ".Main.Old".to_string(), alias_of: None, msg: "use New".to_string() });
+    assert_eq!(diagnostic.range, DiagnosticRange::new(DiagnosticPosition::new(1, 2), DiagnosticPosition::new(1, 2)));
+    assert_eq!(diagnostic.severity, DiagnosticSeverity::Warning);
+
+    // note: エスケープシーケンスの終了位置は記述どおりの長さから求める
+    let diagnostic = Diagnostic::from(&SyntaxParsingLog::InvalidUnicodeEscape { pos: pos.clone(), sequence: "\\u{zz}".to_string() });
+    assert_eq!(diagnostic.range.end, DiagnosticPosition::new(1, 8));
+
+    // note: 位置を持たないログは文書の先頭とする
+    let diagnostic = Diagnostic::from(&SyntaxParsingLog::TooDeepRuleNesting { max_depth: 8 });
+    assert_eq!(diagnostic.range, DiagnosticRange::new(DiagnosticPosition::new(0, 0), DiagnosticPosition::new(0, 0)));
+    assert_eq!(diagnostic.code, "TooDeepRuleNesting");
+
+    let cons = new_console();
+    let input = "aé😀b";
+    let mut parser = SyntaxParser::new(cons.clone(), load_rule_map(&cons, "[Main]{\n    Main <- \"a\",\n}"), TEST_INPUT_FILE_PATH.to_string(), Box::new(input.to_string()), true);
+    assert!(parser.run().is_err(), "input with unconsumed characters was unexpectedly accepted");
+
+    let diagnostics = parser.drain_diagnostics();
+    assert_eq!(parser.get_logs().len(), 0, "drained logs remain in the parser");
+
+    let unconsumed_diagnostic = match diagnostics.iter().find(|each_diagnostic| each_diagnostic.code == "UnconsumedInput") {
+        Some(v) => v,
+        None => panic!("unconsumed input is not reported: {:?}", diagnostics),
+    };
+
+    assert_eq!(unconsumed_diagnostic.range, DiagnosticRange::new(DiagnosticPosition::new(0, 1), DiagnosticPosition::new(0, 4)));
+
+    // note: U+1F600 は UTF-16 では 2 コード単位となる
+    let utf16_diagnostic = unconsumed_diagnostic.to_utf16(input);
+    assert_eq!(utf16_diagnostic.range, DiagnosticRange::new(DiagnosticPosition::new(0, 1), DiagnosticPosition::new(0, 5)));
+    assert_eq!(utf16_diagnostic.position_encoding, DiagnosticPositionEncoding::Utf16);
+    assert_eq!(utf16_diagnostic.to_utf16(input), utf16_diagnostic, "converted diagnostic was converted again");
+    assert_eq!(DiagnosticPositionEncoding::Utf16.get_lsp_name(), "utf-16");
+
+    cons.borrow_mut().clear_log();
+}
+
+// spec: SyntaxParsingLog::to_lsp_diagnostic() が LSP の Diagnostic オブジェクトの各フィールドを持ち、write_lsp_diagnostics() が解析器のログを同じオブジェクトの配列として書き出すことを確認する
+#[cfg(feature = "lsp")]
+#[test]
+fn lsp_diagnostics() {
+    let pos = CharacterPosition::new(None, 3, 1, 2);
+    let end_pos = CharacterPosition::new(None, 5, 1, 4);
+    let unconsumed_log = SyntaxParsingLog::UnconsumedInput { pos: pos, end_pos: end_pos, remaining_preview: "xy".to_string(), remaining_len: 2 };
+
+    let expected_json = serde_json::json!({
+        "range": {
+            "start": { "line": 1, "character": 2 },
+            "end": { "line": 1, "character": 4 },
+        },
+        "severity": 1,
+        "code": "UnconsumedInput",
+        "source": "fcpeg",
+        "message": unconsumed_log.to_string(),
+    });
+
+    assert_eq!(unconsumed_log.to_lsp_diagnostic(), expected_json);
+
+    let cons = new_console();
+    let mut parser = SyntaxParser::new(cons.clone(), load_rule_map(&cons, "[Main]{\n    Main <- \"a\",\n}"), TEST_INPUT_FILE_PATH.to_string(), Box::new("ab".to_string()), true);
+    assert!(parser.run().is_err(), "input with unconsumed characters was unexpectedly accepted");
+
+    let mut buf = Vec::<u8>::new();
+    parser.write_lsp_diagnostics(&mut buf).unwrap();
+    let written_json = serde_json::from_slice::<serde_json::Value>(&buf).unwrap();
+    let expected_json = serde_json::Value::Array(parser.get_logs().iter().map(|each_log| each_log.to_lsp_diagnostic()).collect::<Vec<serde_json::Value>>());
+
+    assert!(parser.get_logs().len() > 0, "parser has no logs");
+    assert_eq!(written_json, expected_json);
+    cons.borrow_mut().clear_log();
+}
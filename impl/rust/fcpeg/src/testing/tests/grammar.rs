@@ -0,0 +1,1053 @@
+use super::*;
+
+// spec: 入力をバイト列モードで解析する; 期待値のリーフの値は UTF-8 として解釈した文字列で記述する
+fn assert_parse_bytes_tree(grammar_src: &str, start_rule: &str, input: &[u8], expected_sexp: &str) {
+    let cons = new_console();
+    let rule_map = load_rule_map_with_start_rule(&cons, grammar_src, start_rule);
+
+    let memoized_sexp = parse_bytes_to_sexp(&cons, rule_map.clone(), input, Encoding::Binary, true);
+    let unmemoized_sexp = parse_bytes_to_sexp(&cons, rule_map, input, Encoding::Binary, false);
+
+    assert_sexp_eq(&memoized_sexp, &unmemoized_sexp, expected_sexp);
+}
+
+// spec: 入力を encoding に従って文字列に変換して解析する点以外は assert_parse_tree と同じ
+fn assert_parse_encoded_tree(grammar_src: &str, start_rule: &str, input: &[u8], encoding: Encoding, expected_sexp: &str) {
+    let cons = new_console();
+    let rule_map = load_rule_map_with_start_rule(&cons, grammar_src, start_rule);
+
+    let memoized_sexp = parse_bytes_to_sexp(&cons, rule_map.clone(), input, encoding, true);
+    let unmemoized_sexp = parse_bytes_to_sexp(&cons, rule_map, input, encoding, false);
+
+    assert_sexp_eq(&memoized_sexp, &unmemoized_sexp, expected_sexp);
+}
+
+// spec: 構成ファイルの内容 (例: "ASTReflection: reversed,") を指定して文法を読み込む点以外は assert_parse_tree と同じ
+fn assert_parse_tree_with_config(grammar_src: &str, config_src: &str, start_rule: &str, input: &str, expected_sexp: &str) {
+    let cons = new_console();
+    let rule_map = set_start_rule(load_rule_map_with_config(&cons, grammar_src, config_src), start_rule);
+
+    let memoized_sexp = parse_to_sexp(&cons, rule_map.clone(), input, true);
+    let unmemoized_sexp = parse_to_sexp(&cons, rule_map, input, false);
+
+    assert_sexp_eq(&memoized_sexp, &unmemoized_sexp, expected_sexp);
+}
+
+// spec: 反映方式の指定のない規則・要素が構成ファイルの ASTReflection (省略, normal, reversed) に従って反映されることを確認する
+// note: 指定のある要素 (#, #名前) は反転の影響を受けない; 開始規則のノードは常に反映される
+#[test]
+fn default_reflection_tree_shapes() {
+    let grammar_src = r##"[Main]{
+        Main <- Item* Pair#Key "\z"#,
+        Item <- Word ","#,
+        Word <- JOIN<[a-z]+>,
+        Pair <- "=" Num,
+        Num <- [0-9],
+    }"##;
+
+    let normal_sexp = "(Main (Item (Word \"ab\")) (Item (Word \"c\")) (Key \"=\" (Num \"1\")))";
+    assert_parse_tree(grammar_src, ".Main.Main", "ab,c,=1", normal_sexp);
+    assert_parse_tree_with_config(grammar_src, "ASTReflection: normal,", ".Main.Main", "ab,c,=1", normal_sexp);
+    assert_parse_tree_with_config(grammar_src, "ASTReflection: reversed,", ".Main.Main", "ab,c,=1", "(Main (Key))");
+}
+
+// spec: 規則ノードの反映名が指定なしでは規則名、#名前 では指定の名前、#_ では無名となることを確認する
+// note: #_ は指定のある要素であるため反転の影響を受けず、規則の別名ともみなされない
+#[test]
+fn rule_reflection_names() {
+    let grammar_src = r##"[Main]{
+    Main <- Item Item#Key Item#_ Wrap "\z"#,
+    Item <- [a-z],
+    Wrap <- Item#_,
+}"##;
+
+    let normal_sexp = "(Main (Item \"a\") (Key \"b\") (_ \"c\") (Wrap (_ \"d\")))";
+    assert_parse_tree(grammar_src, ".Main.Main", "abcd", normal_sexp);
+    assert_parse_tree_with_config(grammar_src, "ASTReflection: reversed,", ".Main.Main", "abcd", "(Main (Key) (_))");
+
+    let cons = new_console();
+    let formatted_grammar_src = load_rule_map(&cons, grammar_src).format_grammar();
+    assert!(formatted_grammar_src.contains("Item Item#Key Item#_ Wrap"), "formatted grammar lost the anonymous reflection:\n{}", formatted_grammar_src);
+    assert_parse_tree(&formatted_grammar_src, ".Main.Main", "abcd", normal_sexp);
+}
+
+// spec: 他規則の別名にすぎない規則 (A <- B) は参照先の規則ノードを別名の規則名で置き換え、中間ノードを生成しないことを確認する
+// note: 別名が連鎖する場合や開始規則が別名である場合も呼び出し元の規則名となる
+#[test]
+fn alias_rule_inlining() {
+    let grammar_src = r##"[Main]{
+        Start <- Main,
+        Main <- Item ","# Item#Key ","# Pick "\z"#,
+        Item <- Word,
+        Word <- Letters,
+        Letters <- [a-z]+,
+        Pick <- Hidden,
+        Hidden <- [0-9]#,
+    }"##;
+
+    assert_parse_tree(grammar_src, ".Main.Main", "ab,c,1", "(Main (Item \"a\" \"b\") (Key \"c\") (Pick))");
+    assert_parse_tree(grammar_src, ".Main.Start", "ab,c,1", "(Start (Item \"a\" \"b\") (Key \"c\") (Pick))");
+}
+
+// spec: EPSILON (RuleExpressionKind::Epsilon) が入力を消費せずに成功し、リーフを生成しないことを確認する
+#[test]
+fn epsilon_matches() {
+    let grammar_src = r##"[Main]{
+        Main <- Opt Opt "\z"#,
+        Opt <- "a" : EPSILON,
+        Rep <- EPSILON* EPSILON{2,3} "b" "\z"#,
+    }"##;
+
+    assert_parse_tree(grammar_src, ".Main.Main", "aa", "(Main (Opt \"a\") (Opt \"a\"))");
+    // note: 空文字列のみに一致した規則のノードは子要素を持たないため構文木に含まれない
+    assert_parse_tree(grammar_src, ".Main.Main", "a", "(Main (Opt \"a\"))");
+    assert_parse_tree(grammar_src, ".Main.Main", "", "(Main)");
+    assert_parse_tree(grammar_src, ".Main.Rep", "b", "(Rep \"b\")");
+    assert_parse_fails(grammar_src, "aaa", "no succeeded rule");
+
+    // note: 否定的先読みでは常に失敗する
+    assert_parse_fails(r##"[Main]{
+        Main <- !EPSILON "c" "\z"#,
+    }"##, "c", "no succeeded rule");
+
+    assert_parse_fails(r##"[Main]{
+        Main <- EPSILON<"a"> "\z"#,
+    }"##, "", "invalid generics argument length");
+}
+
+// spec: alias 指定の別名を経由した解析が参照先の規則による解析と同じ構文木となり、deprecated 指定の警告が解析ごとに 1 度だけ参照元の表現字句の位置を示して出力されることを確認する
+#[test]
+fn rule_alias_deprecation() {
+    let canonical_grammar_src = r##"[Main]{
+    Main <- Item (","# Item)* "\z"#,
+    Item alias OldItem deprecated("use Item") alias Element <- JOIN<[a-z]+>,
+}"##;
+
+    let alias_grammar_src = canonical_grammar_src.replacen("Item (\",\"# Item)*", "OldItem (\",\"# OldItem)*", 1);
+    let input = "ab,c,de";
+
+    let cons = new_console();
+    let canonical_rule_map = load_rule_map(&cons, canonical_grammar_src);
+    let alias_rule_map = load_rule_map(&cons, &alias_grammar_src);
+
+    for enable_memoization in vec![true, false] {
+        let (canonical_sexp, canonical_logs) = parse_with_deprecation_logs(&cons, canonical_rule_map.clone(), input, enable_memoization);
+        let (alias_sexp, alias_logs) = parse_with_deprecation_logs(&cons, alias_rule_map.clone(), input, enable_memoization);
+
+        if alias_sexp != canonical_sexp {
+            panic!("syntax tree via alias mismatched (memoization: {})\nalias:\t\t{}\ncanonical:\t{}", enable_memoization, alias_sexp, canonical_sexp);
+        }
+
+        if canonical_logs.len() != 0 {
+            panic!("canonical rule unexpectedly reported deprecation (memoization: {})\nmessages:\t{:?}", enable_memoization, canonical_logs);
+        }
+
+        // note: 最初に一致した参照は 2 行目の "OldItem" (0 始まりで 1 行目, 12 列目)
+        match alias_logs.as_slice() {
+            [(msg, line, column)] if msg == "use of deprecated alias '.Main.OldItem' of rule '.Main.Item'" && *line == 1 && *column == 12 => (),
+            _ => panic!("expected exactly one deprecation warning at 2:13 (memoization: {})\nactual:\t{:?}", enable_memoization, alias_logs),
+        }
+    }
+
+    let expected_aliases = vec![(".Main.Element".to_string(), ".Main.Item".to_string()), (".Main.OldItem".to_string(), ".Main.Item".to_string())];
+    let actual_aliases = alias_rule_map.get_rule_aliases().into_iter().map(|(alias_id, canonical_id)| (alias_id.clone(), canonical_id.clone())).collect::<Vec<(String, String)>>();
+    assert_eq!(actual_aliases, expected_aliases);
+    assert_eq!(alias_rule_map.get_defined_rule_ids(), vec![".Main.Item", ".Main.Main"]);
+    assert!(alias_rule_map.format_grammar().contains("Item alias OldItem deprecated(\"use Item\") alias Element <- "), "formatted grammar lost the alias:\n{}", alias_rule_map.format_grammar());
+
+    let deprecated_rule_grammar_src = r##"[Main]{
+    Main <- Legacy Legacy "\z"#,
+    deprecated("removed in the next version") Legacy <- "x",
+}"##;
+
+    let (_, deprecated_rule_logs) = parse_with_deprecation_logs(&cons, load_rule_map(&cons, deprecated_rule_grammar_src), "xx", true);
+
+    match deprecated_rule_logs.as_slice() {
+        [(msg, 1, 12)] if msg == "use of deprecated rule '.Main.Legacy'" => (),
+        _ => panic!("expected exactly one deprecation warning of rule at 2:13\nactual:\t{:?}", deprecated_rule_logs),
+    }
+}
+
+// ret: (構文木の S 式表現, DeprecatedRuleUse の (見出し, 行, 列) の一覧)
+fn parse_with_deprecation_logs(cons: &Rc<RefCell<Console>>, rule_map: Arc<Box<RuleMap>>, input: &str, enable_memoization: bool) -> (String, Vec<(String, usize, usize)>) {
+    let mut parser = SyntaxParser::new(cons.clone(), rule_map, TEST_INPUT_FILE_PATH.to_string(), Box::new(input.to_string()), enable_memoization);
+
+    let sexp = match parser.run() {
+        Ok(tree) => tree.to_sexp(true),
+        Err(()) => {
+            cons.borrow().print_all();
+            panic!("failed to parse the input (memoization: {})", enable_memoization);
+        },
+    };
+
+    let logs = parser.get_logs().iter().filter(|each_log| each_log.get_code() == "DeprecatedRuleUse").map(|each_log| {
+        let (line, column) = match each_log.get_position() {
+            Some(pos) => (pos.line, pos.column),
+            None => panic!("deprecation warning has no position"),
+        };
+
+        (each_log.to_string(), line, column)
+    }).collect::<Vec<(String, usize, usize)>>();
+
+    cons.borrow_mut().clear_log();
+    return (sexp, logs);
+}
+
+// spec: 文法の文字列リテラルのエスケープシーケンスが読み込み時に文字へ変換され、不正なものは読み込みに失敗することを確認する
+#[test]
+fn string_escapes() {
+    // note: (文字列リテラル内の記法, 一致する入力, S 式でのリーフの値)
+    let escapes: &[(&str, &str, &str)] = &[
+        ("\\n", "\n", "\\n"),
+        ("\\t", "\t", "\\t"),
+        ("\\\\", "\\", "\\\\"),
+        ("\\\"", "\"", "\\\""),
+        ("\\0", "\0", "\\0"),
+        ("\\x41", "A", "A"),
+        ("\\u{41}", "A", "A"),
+        ("\\u{e9}", "\u{e9}", "\u{e9}"),
+        ("\\u{1F600}", "\u{1F600}", "\u{1F600}"),
+    ];
+
+    for (each_esc_seq, each_input, each_sexp_value) in escapes {
+        let grammar_src = format!("[Main]{{\n    Main <- \"<{}>\" \"\\z\"#,\n}}", each_esc_seq);
+        assert_parse_tree(&grammar_src, ".Main.Main", &format!("<{}>", each_input), &format!("(Main \"<{}>\")", each_sexp_value));
+    }
+
+    // note: 文字列の入力からは 0x0d が取り除かれるため、\r はバイト列モードで確認する
+    assert_parse_bytes_tree("[Main]{\n    Main <- \"<\\r>\" \"\\z\"#,\n}", ".Main.Main", b"<\r>", "(Main \"<\r>\")");
+
+    // note: (文字列リテラル内の記法, 不正である理由に含まれる部分文字列)
+    let invalid_escapes: &[(&str, &str)] = &[
+        ("\\q", "unknown escape sequence character 'q'"),
+        ("\\x4", "2 hexadecimal digits"),
+        ("\\u{12", "unterminated unicode escape sequence"),
+        ("\\u{}", "1 to 6 hexadecimal digits"),
+        ("\\u{110000}", "U+110000 is not a unicode scalar value"),
+        ("\\u{D800}", "surrogate"),
+    ];
+
+    let cons = new_console();
+
+    for (each_esc_seq, each_reason) in invalid_escapes {
+        let grammar_src = format!("[Main]{{\n    Main <- \"<{}>\" \"\\z\"#,\n}}", each_esc_seq);
+
+        match FCPEGGrammar::from_str(cons.clone(), TEST_GRAMMAR_FILE_PATH, &grammar_src) {
+            Ok(_) => panic!("grammar with invalid escape sequence was unexpectedly loaded\nliteral:\t\"<{}>\"", each_esc_seq),
+            Err(()) => cons.borrow_mut().clear_log(),
+        }
+
+        match BlockParser::to_escaped_char(&each_esc_seq[1..]) {
+            Err(msg) if msg.contains(each_reason) => (),
+            Err(msg) => panic!("reason of invalid escape sequence '{}' does not contain '{}'\nactual:\t{}", each_esc_seq, each_reason, msg),
+            Ok(v) => panic!("invalid escape sequence '{}' was unexpectedly decoded to {:?}", each_esc_seq, v),
+        }
+    }
+}
+
+// spec: %test 命令の用例が RuleMap に位置順で集められ、意図的に失敗させた用例が失敗として報告されることを確認する
+#[test]
+fn embedded_tests() {
+    let grammar_src = r##"[Main]{
+    Main <- Expr "\z"#,
+    Expr <- Term ("+" Term)*,
+    Term <- Num ("*" Num)*,
+    Num <- [0-9]+,
+
+    %test Expr accepts "1+2*3",
+    %test Expr rejects "1+*",
+    %test Num accepts "42",
+    % 以下の 2 件は意図的に失敗させる,
+    %test Term accepts "2+3",
+    %test Num rejects "7",
+}"##;
+
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, grammar_src);
+
+    // note: (規則 ID, 期待, 入力, 0 始まりの行)
+    let expected_tests = vec![
+        (".Main.Expr", EmbeddedTestExpectation::Accept, "1+2*3", 6),
+        (".Main.Expr", EmbeddedTestExpectation::Reject, "1+*", 7),
+        (".Main.Num", EmbeddedTestExpectation::Accept, "42", 8),
+        (".Main.Term", EmbeddedTestExpectation::Accept, "2+3", 10),
+        (".Main.Num", EmbeddedTestExpectation::Reject, "7", 11),
+    ];
+
+    let actual_tests = rule_map.embedded_tests.iter().map(|each_test| (each_test.rule_id.as_str(), each_test.expectation, each_test.input.as_str(), each_test.pos.line)).collect::<Vec<(&str, EmbeddedTestExpectation, &str, usize)>>();
+    assert_eq!(actual_tests, expected_tests);
+
+    for enable_memoization in vec![true, false] {
+        let report = rule_map.run_embedded_tests(cons.clone(), &ParseConfig::default().with_memoization(enable_memoization));
+
+        assert_eq!((report.passed_count(), report.failed_count()), (3, 2), "unexpected test counts (memoization: {})", enable_memoization);
+        assert!(!report.is_success());
+
+        match report.get_failures().as_slice() {
+            [accept_failure, reject_failure] => {
+                assert_eq!((accept_failure.test.rule_id.as_str(), accept_failure.test.pos.line), (".Main.Term", 10));
+
+                // note: "2+3" の "+3" が Term に一致せず残る
+                match accept_failure.diagnostics.as_slice() {
+                    [diag] if diag.message.starts_with("unconsumed input \"+3\"") && diag.range.start.character == 1 => (),
+                    _ => panic!("unexpected diagnostics of failed accepting test (memoization: {})\nactual:\t{:?}", enable_memoization, accept_failure.diagnostics),
+                }
+
+                assert_eq!((reject_failure.test.rule_id.as_str(), reject_failure.test.pos.line), (".Main.Num", 11));
+                assert!(reject_failure.diagnostics.len() == 0);
+            },
+            failures => panic!("expected 2 failures (memoization: {})\nactual:\t{}", enable_memoization, failures.len()),
+        }
+
+        report.print(&cons);
+        cons.borrow_mut().clear_log();
+    }
+
+    // note: 整形後の文法を読み込み直しても用例が保たれる
+    let formatted_grammar_src = rule_map.format_grammar();
+    assert!(formatted_grammar_src.contains("%test Expr accepts \"1+2*3\","), "formatted grammar lost the embedded test:\n{}", formatted_grammar_src);
+    assert_eq!(load_rule_map(&cons, &formatted_grammar_src).embedded_tests.len(), expected_tests.len());
+
+    let unknown_rule_grammar_src = r##"[Main]{
+    Main <- "a",
+    %test Missing accepts "a",
+}"##;
+
+    assert!(FCPEGGrammar::from_str(cons.clone(), TEST_GRAMMAR_FILE_PATH, unknown_rule_grammar_src).is_err(), "embedded test for unknown rule was unexpectedly loaded");
+    cons.borrow_mut().clear_log();
+}
+
+// spec: RuleMap::from_str() がファイル経由の読み込みと同じ規則マップを返し、位置に仮想ファイルパスを持つことを確認する
+#[test]
+fn rule_map_from_str() {
+    let grammar_src = r##"[Main]{
+    + start Main.Sum,
+    Sum <- Num ("+" Num)* "\z"#,
+    Num <- [0-9]+,
+}"##;
+
+    let cons = new_console();
+
+    let rule_map = match RuleMap::from_str(&cons, grammar_src) {
+        Ok(v) => v,
+        Err(()) => {
+            cons.borrow().print_all();
+            panic!("failed to load the inline grammar");
+        },
+    };
+
+    let loaded_rule_map = load_rule_map(&cons, grammar_src);
+    assert_eq!(rule_map.format_grammar(), loaded_rule_map.format_grammar(), "inline grammar differs from the grammar loaded from the file map");
+    assert_eq!(rule_map.start_rule_id, ".Main.Sum");
+
+    match rule_map.rule_map.get(".Main.Num") {
+        Some(rule) => assert_eq!(rule.pos.file_path, Some(INLINE_GRAMMAR_FILE_PATH.to_string())),
+        None => panic!("rule '.Main.Num' is not defined in the inline grammar"),
+    }
+
+    assert_eq!(parse_to_sexp(&cons, Arc::new(Box::new(rule_map)), "1+23", true), "(Sum (Num \"1\") (_ \"+\" (Num \"2\" \"3\")))");
+
+    assert!(RuleMap::from_str(&cons, "[Main]{\n    Main <- Missing,\n}").is_err(), "inline grammar with unknown rule was unexpectedly loaded");
+    cons.borrow_mut().clear_log();
+}
+
+// spec: 2 つのファイルがインクルードガードを持つ共通のファイルを同じエイリアス名で参照する場合に共通のファイルが 1 度だけ読み込まれ、インクルードガードがない場合や同じエイリアス名で異なるファイルを参照する場合はエラーとなることを確認する
+#[test]
+fn shared_file_imports() {
+    let cons = new_console();
+    let mut src_map = HashMap::<String, String>::new();
+    src_map.insert("main.fcpeg".to_string(), "[Main]{\n    Main <- B.Lib.Left C.Lib.Right \"\\z\"#,\n}".to_string());
+    src_map.insert("main.cfg".to_string(), "FileAliases:\n||B: b.fcpeg,\n||C: c.fcpeg,\n".to_string());
+    src_map.insert("b.fcpeg".to_string(), "[Lib]{\n    Left <- Base.Digits.Digit,\n}".to_string());
+    src_map.insert("b.cfg".to_string(), "FileAliases:\n||Base: base.fcpeg,\n".to_string());
+    src_map.insert("c.fcpeg".to_string(), "[Lib]{\n    Right <- Base.Digits.Digit,\n}".to_string());
+    src_map.insert("c.cfg".to_string(), "FileAliases:\n||Base: base.fcpeg,\n".to_string());
+    src_map.insert("base.fcpeg".to_string(), "#pragma once\n\n[Digits]{\n    Digit <- [0-9],\n}".to_string());
+    src_map.insert("other.fcpeg".to_string(), "[Digits]{\n    Digit <- [a-z],\n}".to_string());
+
+    let rule_map = match FCPEGGrammar::from_str_map(cons.clone(), "main.fcpeg", src_map.clone()) {
+        Ok(v) => v,
+        Err(()) => {
+            cons.borrow().print_all();
+            panic!("failed to load the grammar importing a shared file");
+        },
+    };
+
+    let mut rule_ids = rule_map.rule_map.keys().map(|each_rule_id| each_rule_id.as_str()).collect::<Vec<&str>>();
+    rule_ids.sort();
+    assert_eq!(rule_ids, vec![".Main.Main", "B.Lib.Left", "Base.Digits.Digit", "C.Lib.Right"], "shared file is not loaded exactly once");
+    assert_eq!(parse_to_sexp(&cons, rule_map, "12", true), "(Main (Left \"1\") (Right \"2\"))");
+
+    let mut unguarded_src_map = src_map.clone();
+    unguarded_src_map.insert("base.fcpeg".to_string(), "[Digits]{\n    Digit <- [0-9],\n}".to_string());
+    assert!(FCPEGGrammar::from_str_map(cons.clone(), "main.fcpeg", unguarded_src_map).is_err(), "shared file without include guard was unexpectedly accepted");
+    cons.borrow_mut().clear_log();
+
+    src_map.insert("c.cfg".to_string(), "FileAliases:\n||Base: other.fcpeg,\n".to_string());
+    assert!(FCPEGGrammar::from_str_map(cons.clone(), "main.fcpeg", src_map).is_err(), "alias name referring to different files was unexpectedly accepted");
+    cons.borrow_mut().clear_log();
+}
+
+// spec: 網羅状況がセッション中の解析間で累積し、呼び出されなかった規則と一致しなかった選択肢が両方の解析エンジンで同じく報告されることを確認する
+#[test]
+fn grammar_coverage() {
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, "[Main]{\n    Main <- Item+ \"\\z\"#,\n    Item <- Num : Word : \"@\" Never : (\"(\" Item \")\"),\n    Num <- [0-9]+,\n    Word <- [a-z]+,\n    Never <- \"~\",\n    Unused <- \"u\",\n}");
+
+    // ret: (規則 ID, 選択肢のインデックス, 検査されたか) の一覧
+    let to_alternatives = |entries: Vec<&CoverageEntry>| entries.iter().map(|each_entry| match &each_entry.kind {
+        CoverageEntryKind::Alternative { choice_uuid: _, alternative_i } => (each_entry.rule_id.clone(), *alternative_i, each_entry.is_attempted),
+        _ => panic!("non-alternative entry in rule '{}' was returned as an alternative", each_entry.rule_id),
+    }).collect::<Vec<(String, usize, bool)>>();
+
+    let to_rule_ids = |entries: Vec<&CoverageEntry>| entries.iter().map(|each_entry| each_entry.rule_id.clone()).collect::<Vec<String>>();
+
+    let collect_coverage = |engine: ParseEngine, inputs: &Vec<&str>, collect_coverage: bool| {
+        let mut session = ParserSession::new(cons.clone(), rule_map.clone()).with_config(ParseConfig::default().with_engine(engine).with_coverage(collect_coverage));
+
+        for each_input in inputs {
+            if session.parse(TEST_INPUT_FILE_PATH.to_string(), Box::new(each_input.to_string())).is_err() {
+                cons.borrow().print_all();
+                panic!("failed to parse '{}' while collecting coverage", each_input);
+            }
+        }
+
+        session.take_coverage()
+    };
+
+    let mut coverages = Vec::<GrammarCoverage>::new();
+
+    for each_engine in vec![ParseEngine::Recursive, ParseEngine::Iterative] {
+        let partial_coverage = collect_coverage(each_engine, &vec!["12ab"], true);
+        assert_eq!(to_alternatives(partial_coverage.get_unmatched_alternatives()), vec![(".Main.Item".to_string(), 2, true), (".Main.Item".to_string(), 3, true)]);
+
+        let coverage = collect_coverage(each_engine, &vec!["12ab", "(a)"], true);
+        assert_eq!(to_rule_ids(coverage.get_unexercised_rules()), vec![".Main.Never".to_string(), ".Main.Unused".to_string()], "unexercised rules mismatch");
+        assert_eq!(to_alternatives(coverage.get_unmatched_alternatives()), vec![(".Main.Item".to_string(), 2, true)], "coverage is not accumulated across parses");
+        coverages.push(coverage);
+
+        let disabled_coverage = collect_coverage(each_engine, &vec!["12ab"], false);
+        assert!(disabled_coverage.entries.iter().all(|each_entry| !each_entry.is_attempted && !each_entry.is_matched), "coverage was recorded although it is disabled");
+    }
+
+    let to_flags = |coverage: &GrammarCoverage| coverage.entries.iter().map(|each_entry| (each_entry.index, each_entry.is_attempted, each_entry.is_matched)).collect::<Vec<(usize, bool, bool)>>();
+    assert_eq!(to_flags(&coverages[0]), to_flags(&coverages[1]), "coverage differs between parse engines");
+    cons.borrow_mut().clear_log();
+}
+
+// spec: 文字クラス中の符号位置として不正なエスケープシーケンスが InvalidUnicodeEscape としてその位置で報告され、16 進数として読めないものは InvalidCharClassFormat のままであることを確認する
+#[test]
+fn invalid_unicode_escapes() {
+    let cons = new_console();
+
+    // ret: (ログのコード, 列, 見出し) の一覧
+    let parse_logs = |char_class: &str, is_byte_mode: bool| {
+        let rule_map = load_rule_map(&cons, &format!("[Main]{{\n    Main <- {}* \"\\z\"#,\n}}", char_class));
+
+        let mut parser = if is_byte_mode {
+            SyntaxParser::new_bytes(cons.clone(), rule_map, TEST_INPUT_FILE_PATH.to_string(), b"a".to_vec(), true)
+        } else {
+            SyntaxParser::new(cons.clone(), rule_map, TEST_INPUT_FILE_PATH.to_string(), Box::new("a".to_string()), true)
+        };
+
+        let is_succeeded = parser.run().is_ok();
+        let logs = parser.get_logs().iter().map(|each_log| (each_log.get_code(), each_log.get_position().map(|pos| pos.column), each_log.to_string())).collect::<Vec<(&'static str, Option<usize>, String)>>();
+        cons.borrow_mut().clear_log();
+        (is_succeeded, logs)
+    };
+
+    // note: 文字クラスは "    Main <- " の直後の 12 列目から始まる
+    let invalid_escapes = vec![
+        ("[a-\\u{D800}]", false, 15, "invalid Unicode escape sequence '\\u{D800}' (U+D800) in character class"),
+        ("[\\x{110000}]", false, 13, "invalid Unicode escape sequence '\\x{110000}' (U+110000) in character class"),
+        ("[\\\\\\U00110000]", false, 15, "invalid Unicode escape sequence '\\U00110000' (U+110000) in character class"),
+        ("[\\uDFFF]", false, 13, "invalid Unicode escape sequence '\\uDFFF' (U+DFFF) in character class"),
+        ("[\\x{100}]", true, 13, "invalid Unicode escape sequence '\\x{100}' (U+0100) in character class"),
+    ];
+
+    for (each_char_class, is_byte_mode, expected_column, expected_msg) in invalid_escapes {
+        assert_eq!(parse_logs(each_char_class, is_byte_mode), (false, vec![("InvalidUnicodeEscape", Some(expected_column), expected_msg.to_string())]), "unexpected logs for {} (byte mode: {})", each_char_class, is_byte_mode);
+    }
+
+    let (is_succeeded, logs) = parse_logs("[\\x{GG}]", false);
+    assert!(!is_succeeded);
+    assert_eq!(logs.iter().map(|(code, column, _)| (*code, *column)).collect::<Vec<(&'static str, Option<usize>)>>(), vec![("InvalidCharClassFormat", Some(12))]);
+
+    for (each_char_class, is_byte_mode) in vec![("[\\u{FFFF}a]", false), ("[\\x{10FFFF}a]", false), ("[\\xFFa]", true), ("[\\\\u{D800}a]", false)] {
+        assert_eq!(parse_logs(each_char_class, is_byte_mode), (true, vec![]), "valid escape is reported in {} (byte mode: {})", each_char_class, is_byte_mode);
+    }
+}
+
+// spec: 同じバイト列が文字コードの指定に応じて異なる文字として解析され、UTF-8 として不正なバイト列の入力は解析前に失敗することを確認する
+#[test]
+fn input_encodings() {
+    let grammar_src = r##"[Main]{
+    Main <- Word ("\n"# Word)*## "\z"#,
+    Word <- JOIN<[a-z\xe0-\xff]+>,
+}"##;
+
+    // note: 0xe9 は Latin-1 の 'é'; UTF-8 としては不正なバイト
+    let latin1_input = b"caf\xe9\nna\xefve";
+    assert_parse_encoded_tree(grammar_src, ".Main.Main", latin1_input, Encoding::Latin1, "(Main (Word \"café\") (Word \"naïve\"))");
+    assert_parse_encoded_tree(grammar_src, ".Main.Main", "café\nnaïve".as_bytes(), Encoding::Utf8, "(Main (Word \"café\") (Word \"naïve\"))");
+
+    // note: 文字列の入力と同様に 0x0d は取り除かれる
+    assert_parse_encoded_tree(grammar_src, ".Main.Main", b"caf\xe9\r\nna\xefve", Encoding::Latin1, "(Main (Word \"café\") (Word \"naïve\"))");
+
+    // note: バイト列モードでは文字クラスが 1 バイトを検査するため同じ入力に一致し、リーフの値は UTF-8 として不正なバイトを置換した文字列となる
+    assert_parse_encoded_tree(grammar_src, ".Main.Main", latin1_input, Encoding::Binary, "(Main (Word \"caf\u{FFFD}\") (Word \"na\u{FFFD}ve\"))");
+
+    assert_eq!(Encoding::Utf8.decode(b"ok\nab\xff"), Err("ok\nab".to_string()));
+    assert_eq!(Encoding::Latin1.decode(b"ab\xff"), Ok("ab\u{ff}".to_string()));
+
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, grammar_src);
+
+    for enable_memoization in vec![true, false] {
+        match SyntaxParser::parse_bytes(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), latin1_input, Encoding::Utf8, ParseConfig::default().with_memoization(enable_memoization)) {
+            Ok(tree) => panic!("invalid UTF-8 input was unexpectedly parsed (memoization: {})\ntree:\t{}", enable_memoization, tree.to_sexp(true)),
+            Err(()) => cons.borrow_mut().clear_log(),
+        }
+    }
+}
+
+// spec: 2 要素の連接を束縛した総称引数を呼び出し先で非反映とした場合に、引数が生成したすべての要素が欠落せずに非反映となり、反映する場合はすべての要素が残ることを確認する
+#[test]
+fn hidden_generic_sequence_argument() {
+    let grammar_src = r##"[Main]{
+        Main <- Shown<"a" "b"> Hidden<"c" "d"> "\z"#,
+        Shown<$T> <- "("# $T ")"#,
+        Hidden<$T> <- "["# $T# "]"#,
+    }"##;
+
+    assert_parse_tree(grammar_src, ".Main.Main", "(ab)[cd]", "(Main (Shown \"a\" \"b\") (Hidden))");
+
+    let tree = parse_tree(grammar_src, ".Main.Main", "(ab)[cd]", ParseConfig::default());
+
+    // note: 非反映的な要素を含めた木では、非反映とした引数の要素が 2 つとも残る
+    assert_eq!(tree.to_sexp(false), normalize_sexp(r##"(Main (Shown #:"(" "a" "b" #:")") (Hidden #:"[" #:"c" #:"d" #:"]") #:"\0")"##));
+}
+
+// spec: 型・長さ・値からなる TLV 形式のバイト列をバイト列モードで解析し、UTF-8 として不正な値のバイトが raw_value に保持され、位置がバイト単位で数えられることを確認する
+// note: 長さ 0 から 3 までの値を長さのバイトごとの選択肢で表す
+#[test]
+fn tlv_byte_mode() {
+    let grammar_src = r##"[Main]{
+        Main <- Record* "\z"#,
+        Record <- Tag Value,
+        Tag <- JOIN<[\x01-\x7f]>,
+        Value <- "\x00"# : "\x01"# JOIN<Byte> : "\x02"# JOIN<Byte{2}> : "\x03"# JOIN<Byte{3}>,
+        Byte <- [\x00-\xff],
+    }"##;
+
+    let input = [0x41u8, 0x03, b'a', b'b', b'c', 0x42, 0x02, 0xff, 0x00, 0x43, 0x00];
+    assert_parse_bytes_tree(grammar_src, ".Main.Main", &input, "(Main (Record (Tag \"A\") (Value \"abc\")) (Record (Tag \"B\") (Value \"\u{FFFD}\\0\")) (Record (Tag \"C\") (Value)))");
+
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, grammar_src);
+
+    let tree = match SyntaxParser::parse_bytes(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), &input, Encoding::Binary, ParseConfig::default()) {
+        Ok(v) => v,
+        Err(()) => {
+            cons.borrow().print_all();
+            panic!("failed to parse the TLV input");
+        },
+    };
+
+    // note: (バイト単位の位置, 元のバイト列); 長さ 0 の値はリーフを持たない
+    let value_leaves = tree.to_token_stream(true).iter().filter(|each_leaf| each_leaf.pos.index == 2 || each_leaf.pos.index == 7).map(|each_leaf| (each_leaf.pos.index, each_leaf.raw_value.clone())).collect::<Vec<(usize, Option<Vec<u8>>)>>();
+    assert_eq!(value_leaves, vec![(2, Some(b"abc".to_vec())), (7, Some(vec![0xff, 0x00]))]);
+
+    // note: 入力末尾の値の 0x00 は EOF 用のヌル文字と区別する
+    assert_parse_bytes_tree(grammar_src, ".Main.Main", &[0x43, 0x01, 0x00], "(Main (Record (Tag \"C\") (Value \"\\0\")))");
+
+    // note: 長さより短い値で終わる入力や、\z の位置に 0x00 が続く入力は受理しない
+    for each_input in vec![vec![0x41u8, 0x03, b'a'], vec![0x43, 0x01], vec![0x43, 0x00, 0x00]] {
+        match SyntaxParser::parse_bytes(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), &each_input, Encoding::Binary, ParseConfig::default()) {
+            Ok(tree) => panic!("invalid TLV input was unexpectedly parsed\ninput:\t{:?}\ntree:\t{}", each_input, tree.to_sexp(true)),
+            Err(()) => cons.borrow_mut().clear_log(),
+        }
+    }
+
+    // note: ワイルドカードは EOF 用のヌル文字と一致しない
+    assert_parse_bytes_tree("[Main]{\n    Main <- JOIN<.*> \"\\z\"#,\n}", ".Main.Main", &[0x00, 0x00], "(Main \"\\0\\0\")");
+}
+
+// spec: 同じファイルの同じブロック内の規則名の重複は override の有無によらずエラーとなり、ブロックマップ間の規則 ID の重複は override 指定のある定義が 1 つのみであれば順序によらずその定義が採用されることを確認する
+#[test]
+fn duplicate_rule_definitions() {
+    let cons = new_console();
+
+    for each_grammar_src in vec!["[Main]{\n    Main <- Item,\n    Item <- \"a\",\n    Item <- \"b\",\n}", "[Main]{\n    Main <- Item,\n    Item <- \"a\",\n    override Item <- \"b\",\n}"] {
+        assert!(FCPEGGrammar::from_str(cons.clone(), TEST_GRAMMAR_FILE_PATH, each_grammar_src).is_err(), "duplicate rule name in a block was unexpectedly accepted\ngrammar:\t{}", each_grammar_src);
+        cons.borrow_mut().clear_log();
+    }
+
+    // note: 別に読み込んだ文法の規則を 1 つのブロックマップとする
+    let to_block_map = |grammar_src: &str| -> BlockMap {
+        let cmds = load_rule_map(&cons, grammar_src).rule_map.values().map(|each_rule| BlockCommand::Define { pos: each_rule.pos.clone(), rule: (**each_rule).clone() }).collect::<Vec<BlockCommand>>();
+        let mut block_map = BlockMap::new();
+        block_map.insert("Main".to_string(), Box::new(Block::new("Main".to_string(), cmds)));
+        return block_map;
+    };
+
+    let base_block_map = to_block_map("[Main]{\n    Main <- Item \"\\z\"#,\n    Item <- \"a\",\n}");
+    let plain_block_map = to_block_map("[Main]{\n    Item <- \"b\",\n}");
+    let override_block_map = to_block_map("[Main]{\n    override Item <- \"b\",\n}");
+
+    for each_block_maps in vec![vec![base_block_map.clone(), plain_block_map.clone()], vec![override_block_map.clone(), override_block_map.clone()]] {
+        assert!(RuleMap::new(&cons, each_block_maps, DEFAULT_START_RULE_ID.to_string()).is_err(), "duplicate rule id across block maps was unexpectedly accepted");
+        cons.borrow_mut().clear_log();
+    }
+
+    for each_block_maps in vec![vec![base_block_map.clone(), override_block_map.clone()], vec![override_block_map.clone(), base_block_map.clone()]] {
+        let rule_map = match RuleMap::new(&cons, each_block_maps, DEFAULT_START_RULE_ID.to_string()) {
+            Ok(v) => Arc::new(Box::new(v)),
+            Err(()) => {
+                cons.borrow().print_all();
+                panic!("overriding rule definition was rejected");
+            },
+        };
+
+        assert!(rule_map.rule_map[".Main.Item"].is_override, "overriding rule definition was not adopted");
+        assert_eq!(parse_to_sexp(&cons, rule_map, "b", true), "(Main (Item \"b\"))");
+    }
+}
+
+// spec: 2 つのブロックが同名の規則を定義する文法で、規則 ID がブロック名で区別され、ノード名は既定では規則名、qualify_rule_names では規則 ID となることを確認する
+// note: 他のブロックの総称規則に別のブロックの規則を渡す; 開始規則には規則 ID を指定でき、失敗時の規則スタックにも規則 ID が出力される
+#[test]
+fn namespaced_rule_ids() {
+    let grammar_src = "[Main]{\n    + use Lhs,\n    + use Rhs,\n\n    Main <- Lhs.Item \"=\"# Lhs.Wrap<Rhs.Item> \"\\z\"#,\n}\n\n[Lhs]{\n    Item <- JOIN<[a-z]+>,\n    Wrap<$T> <- \"(\"# $T \")\"#,\n}\n\n[Rhs]{\n    Entry <- Item \"\\z\"#,\n    Item <- JOIN<[0-9]+>,\n}";
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, grammar_src);
+
+    let mut rule_ids = rule_map.rule_map.keys().map(|each_rule_id| each_rule_id.as_str()).collect::<Vec<&str>>();
+    rule_ids.sort();
+    assert_eq!(rule_ids, vec![".Lhs.Item", ".Lhs.Wrap", ".Main.Main", ".Rhs.Entry", ".Rhs.Item"]);
+
+    assert_parse_tree(grammar_src, ".Main.Main", "ab=(12)", "(Main (Item \"ab\") (Wrap (Item \"12\")))");
+    assert_parse_tree(grammar_src, ".Rhs.Entry", "12", "(Entry (Item \"12\"))");
+
+    let config = ParseConfig::default().with_qualified_rule_names(true);
+
+    match SyntaxParser::parse(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new("ab=(12)".to_string()), config.clone()) {
+        Ok(tree) => assert_eq!(tree.to_sexp(true), "(.Main.Main (.Lhs.Item \"ab\") (.Lhs.Wrap (.Rhs.Item \"12\")))"),
+        Err(()) => {
+            cons.borrow().print_all();
+            panic!("failed to parse the input with qualified rule names");
+        },
+    }
+
+    match SyntaxParser::parse(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new("12".to_string()), config.with_start_rule_id(".Rhs.Entry".to_string())) {
+        Ok(tree) => assert_eq!(tree.to_sexp(true), "(.Rhs.Entry (.Rhs.Item \"12\"))"),
+        Err(()) => {
+            cons.borrow().print_all();
+            panic!("failed to parse the input from the fully qualified start rule");
+        },
+    }
+
+    let mut parser = SyntaxParser::new(cons.clone(), rule_map, TEST_INPUT_FILE_PATH.to_string(), Box::new("ab=(x)".to_string()), true);
+    assert!(parser.run().is_err(), "input with a letter in the digits was unexpectedly accepted");
+
+    match parser.get_logs().iter().find(|each_log| each_log.get_code() == "NoSucceededRule") {
+        Some(SyntaxParsingLog::NoSucceededRule { pos: _, rule_id, rule_stack, rule_doc: _, expected: _ }) => {
+            let rule_stack_ids = rule_stack.iter().map(|(_, each_rule_id)| each_rule_id.as_str()).collect::<Vec<&str>>();
+            assert_eq!((rule_id.as_str(), rule_stack_ids), (".Main.Main", vec![".Main.Main", ".Lhs.Wrap", ".Rhs.Item"]));
+        },
+        _ => panic!("NoSucceededRule is not reported"),
+    }
+
+    cons.borrow_mut().clear_log();
+}
+
+// spec: ->名前 で捕捉した文字列に =名前 の後方参照が一致してリーフとなり、失敗した選択肢での捕捉は参照できないことを確認する
+#[test]
+fn capture_back_references() {
+    let grammar_src = r##"[Main]{
+        Main <- Quote->q JOIN<(!=q .)*>#Body =q#End "\z"#,
+        Quote <- "'" : "\"",
+    }"##;
+
+    assert_parse_tree(grammar_src, ".Main.Main", "'ab\"c'", "(Main (Quote \"'\") Body:\"ab\\\"c\" End:\"'\")");
+    assert_parse_tree(grammar_src, ".Main.Main", "\"\"", "(Main (Quote \"\\\"\") Body:\"\" End:\"\\\"\")");
+    assert_parse_fails(grammar_src, "'ab\"", "'.Main.Main'");
+
+    let choice_grammar_src = r##"[Main]{
+        Main <- ("a"->x "b" : "a")# =x "\z"#,
+    }"##;
+
+    assert_parse_tree(choice_grammar_src, ".Main.Main", "aba", "(Main \"a\")");
+    assert_parse_fails(choice_grammar_src, "aa", "'.Main.Main'");
+
+    // note: 整形後の文法でも捕捉の記法が保たれる
+    let cons = new_console();
+    let formatted_grammar_src = load_rule_map(&cons, grammar_src).format_grammar();
+    assert!(formatted_grammar_src.contains("Quote->q"), "formatted grammar lost the capture:\n{}", formatted_grammar_src);
+}
+
+// spec: 繰り返しの要素が途中まで一致して失敗した場合に、要素内で記録された文脈変数・捕捉が破棄されて後続の参照に残らないことを再帰型・反復型の解析器と parse_iter() で確認する
+#[test]
+fn failed_loop_iteration_discards_captures() {
+    // note: (文法, 失敗時のエラーメッセージに含まれる部分文字列)
+    let grammar_srcs = vec![
+        (r##"[Main]{
+            Main <- (CAPTURE<"q", "a"> "b")*# MATCH_CAPTURED<"q"> "\z"#,
+        }"##, "unknown context name 'q'"),
+        (r##"[Main]{
+            Main <- ("a"->q "b")*# =q "\z"#,
+        }"##, "'.Main.Main'"),
+    ];
+
+    let cons = new_console();
+
+    for (each_grammar_src, each_error_substring) in grammar_srcs {
+        // note: 1 回目の繰り返しで記録された値は 2 回目の失敗後も残る
+        assert_parse_tree(each_grammar_src, ".Main.Main", "aba", "(Main \"a\")");
+        assert_parse_fails(each_grammar_src, "a", each_error_substring);
+
+        let rule_map = load_rule_map(&cons, each_grammar_src);
+
+        for each_engine in vec![ParseEngine::Recursive, ParseEngine::Iterative] {
+            match SyntaxParser::parse(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new("a".to_string()), ParseConfig::default().with_engine(each_engine)) {
+                Ok(tree) => panic!("capture of failed iteration leaked (engine: {:?})\ntree:\t{}", each_engine, tree.to_sexp(true)),
+                Err(()) => cons.borrow_mut().clear_log(),
+            }
+        }
+
+        let streamed_elems = SyntaxParser::parse_iter(cons.clone(), rule_map, TEST_INPUT_FILE_PATH.to_string(), Box::new("a".to_string()), ParseConfig::default()).collect::<ConsoleResult<Vec<SyntaxNodeElement>>>();
+        assert!(streamed_elems.is_err(), "capture of failed iteration leaked while streaming");
+        cons.borrow_mut().clear_log();
+    }
+}
+
+// spec: まだ捕捉されていない名前への後方参照が ParseContext の外部の捕捉と一致し、入力から捕捉された値が外部の値より優先されることを再帰型・反復型の解析器で確認する
+#[test]
+fn extern_capture_back_references() {
+    let cons = new_console();
+    let extern_rule_map = load_rule_map(&cons, "[Main]{\n    Main <- \"v\" =ver \"\\z\"#,\n}");
+    let captured_rule_map = load_rule_map(&cons, "[Main]{\n    Main <- [0-9]->ver \":\"# =ver \"\\z\"#,\n}");
+    let context = ParseContext::new().with_extern_capture("ver", "2");
+
+    // note: (規則マップ, 入力, 文脈, 期待される S 式; 失敗する場合は None)
+    let cases = vec![
+        (extern_rule_map.clone(), "v2", context.clone(), Some("(Main \"v\" \"2\")")),
+        (extern_rule_map.clone(), "v3", context.clone(), None),
+        (extern_rule_map.clone(), "v2", ParseContext::new(), None),
+        (captured_rule_map.clone(), "1:1", context.clone(), Some("(Main \"1\" \"1\")")),
+        (captured_rule_map.clone(), "1:2", context.clone(), None),
+    ];
+
+    for (each_rule_map, each_input, each_context, each_expected_sexp) in cases {
+        for each_engine in vec![ParseEngine::Recursive, ParseEngine::Iterative] {
+            let config = ParseConfig::default().with_engine(each_engine).with_context(each_context.clone());
+            let actual_sexp = match SyntaxParser::parse(cons.clone(), each_rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new(each_input.to_string()), config) {
+                Ok(tree) => Some(tree.to_sexp(true)),
+                Err(()) => {
+                    cons.borrow_mut().clear_log();
+                    None
+                },
+            };
+
+            assert_eq!(actual_sexp, each_expected_sexp.map(|v| v.to_string()), "unexpected result of '{}' (engine: {:?}, context: {:?})", each_input, each_engine, each_context);
+        }
+    }
+}
+
+// spec: 存在マーカー ~ を付けた省略可能なグループが、空に一致した場合は子要素のないノードを、一致しなかった場合は何も反映せず、~ がなければ両者とも何も反映しないことを確認する
+// note: 否定的先読みにより、"[]" では空に一致し、"[-]" では一致しない
+#[test]
+fn presence_marker() {
+    let to_grammar_src = |presence_marker: &str| format!("[Main]{{\n    Main <- \"[\"# (!\"-\" [a-z]*)?{}#Body \"-\"?# \"]\"# \"\\z\"#,\n}}", presence_marker);
+
+    // note: (入力, 存在マーカーがある場合の構文木, ない場合の構文木)
+    let cases = vec![
+        ("[ab]", "(Main (Body \"a\" \"b\"))", "(Main (Body \"a\" \"b\"))"),
+        ("[]", "(Main (Body))", "(Main)"),
+        ("[-]", "(Main)", "(Main)"),
+    ];
+
+    for (each_input, each_marked_sexp, each_unmarked_sexp) in cases {
+        assert_parse_tree(&to_grammar_src("~"), ".Main.Main", each_input, each_marked_sexp);
+        assert_parse_tree(&to_grammar_src(""), ".Main.Main", each_input, each_unmarked_sexp);
+    }
+
+    // note: 一致したノードは子要素が空であっても空のノードとして残る
+    let tree = parse_tree(&to_grammar_src("~"), ".Main.Main", "[]", ParseConfig::default());
+
+    assert_eq!(tree.to_sexp(false), normalize_sexp(r##"(Main #:"[" (Body) #:"]" #:"\0")"##));
+}
+
+// spec: + const で定義した定数が定義位置によらず @名前 の参照で連結した文字列に置換されて解析され、未定義の定数・循環参照・重複定義が読み込み時のエラーとなることを確認する
+#[test]
+fn constant_substitution() {
+    let grammar_src = r##"[Main]{
+        Main <- @Version "."# Name "\z"#,
+        + const Prefix = "org.example.",
+        + const Version = @Prefix "v1",
+        Name <- JOIN<[a-z]+> : @Sep,
+        + const Sep = ";" "-",
+    }"##;
+
+    assert_parse_tree(grammar_src, ".Main.Main", "org.example.v1.core", "(Main \"org.example.v1\" (Name \"core\"))");
+    assert_parse_tree(grammar_src, ".Main.Main", "org.example.v1.;-", "(Main \"org.example.v1\" (Name \";-\"))");
+    assert_parse_fails(grammar_src, "org.example.core", "no succeeded rule '.Main.Main'");
+
+    // note: 整形後の文法では定数が文字列として出力される
+    let cons = new_console();
+    let formatted_grammar_src = load_rule_map(&cons, grammar_src).format_grammar();
+    assert!(formatted_grammar_src.contains("\"org.example.v1\""), "formatted grammar lost the substituted constant:\n{}", formatted_grammar_src);
+
+    let invalid_grammar_srcs = vec![
+        "[Main]{\n    Main <- @Missing \"\\z\"#,\n}",
+        "[Main]{\n    + const A = @Missing,\n    Main <- @A \"\\z\"#,\n}",
+        "[Main]{\n    + const A = @B \"a\",\n    + const B = @A,\n    Main <- @A \"\\z\"#,\n}",
+        "[Main]{\n    + const A = @A,\n    Main <- \"\\z\"#,\n}",
+        "[Main]{\n    + const A = \"a\",\n    + const A = \"b\",\n    Main <- @A \"\\z\"#,\n}",
+    ];
+
+    for each_grammar_src in invalid_grammar_srcs {
+        assert!(FCPEGGrammar::from_str(cons.clone(), TEST_GRAMMAR_FILE_PATH, each_grammar_src).is_err(), "invalid constant was unexpectedly accepted\ngrammar:\t{}", each_grammar_src);
+        cons.borrow_mut().clear_log();
+    }
+}
+
+// spec: 区切り付き繰り返し ** / ++ が 0 個・1 個・複数個の要素に一致して要素のみを反映し、末尾の区切りは ? を付けた場合のみ受理されることを確認する
+#[test]
+fn separated_repetitions() {
+    let to_grammar_src = |repetition: &str| format!("[Main]{{\n    Main <- \"(\"# {} \")\"# \"\\z\"#,\n    Item <- JOIN<[a-z]+>,\n}}", repetition);
+
+    let zero_or_more_grammar_src = to_grammar_src("Item ** \",\"");
+    assert_parse_tree(&zero_or_more_grammar_src, ".Main.Main", "()", "(Main)");
+    assert_parse_tree(&zero_or_more_grammar_src, ".Main.Main", "(a)", "(Main (Item \"a\"))");
+    assert_parse_tree(&zero_or_more_grammar_src, ".Main.Main", "(a,bc,d)", "(Main (Item \"a\") (Item \"bc\") (Item \"d\"))");
+
+    for each_input in vec!["(a,)", "(,a)", "(a,,b)", "(,)"] {
+        assert_parse_fails(&zero_or_more_grammar_src, each_input, "no succeeded rule '.Main.Main'");
+    }
+
+    let one_or_more_grammar_src = to_grammar_src("Item ++ \",\"");
+    assert_parse_tree(&one_or_more_grammar_src, ".Main.Main", "(a)", "(Main (Item \"a\"))");
+    assert_parse_tree(&one_or_more_grammar_src, ".Main.Main", "(a,bc,d)", "(Main (Item \"a\") (Item \"bc\") (Item \"d\"))");
+
+    for each_input in vec!["()", "(a,)", "(,a)"] {
+        assert_parse_fails(&one_or_more_grammar_src, each_input, "no succeeded rule '.Main.Main'");
+    }
+
+    // note: 末尾の区切りを許す場合も、区切りのみの入力や区切りの連続は受理しない
+    for each_repetition in vec!["Item ** \",\"?", "Item ++ \",\"?"] {
+        let trailing_grammar_src = to_grammar_src(each_repetition);
+        assert_parse_tree(&trailing_grammar_src, ".Main.Main", "(a,)", "(Main (Item \"a\"))");
+        assert_parse_tree(&trailing_grammar_src, ".Main.Main", "(a,b,)", "(Main (Item \"a\") (Item \"b\"))");
+        assert_parse_tree(&trailing_grammar_src, ".Main.Main", "(a,b)", "(Main (Item \"a\") (Item \"b\"))");
+
+        for each_input in vec!["(,)", "(a,,)"] {
+            assert_parse_fails(&trailing_grammar_src, each_input, "no succeeded rule '.Main.Main'");
+        }
+    }
+
+    assert_parse_tree(&to_grammar_src("Item ** \",\"?"), ".Main.Main", "()", "(Main)");
+    assert_parse_fails(&to_grammar_src("Item ++ \",\"?"), "()", "no succeeded rule '.Main.Main'");
+}
+
+// spec: CAPTURE で記録した区切り文字列で終わるヒアドキュメントが MATCH_CAPTURED で閉じられ、異なる区切り文字列では閉じられず、スコープ外の文脈変数の参照が UnknownContextName となることを確認する
+#[test]
+fn heredoc_context_variables() {
+    let grammar_src = r##"[Main]{
+        Main <- Heredoc ("\n"# Heredoc)*## "\z"#,
+        Heredoc <- "<<"# CAPTURE<"delim", [A-Z]+>#Delim "\n"# Line* MATCH_CAPTURED<"delim">#,
+        Line <- !MATCH_CAPTURED<"delim"> JOIN<(!"\n" .)*> "\n"#,
+    }"##;
+
+    assert_parse_tree(grammar_src, ".Main.Main", "<<EOF\nabc\n  EOFx\nEOF", "(Main (Heredoc Delim:\"EOF\" (Line \"abc\") (Line \"  EOFx\")))");
+    assert_parse_tree(grammar_src, ".Main.Main", "<<A\nB\nA\n<<B\nA\nB", "(Main (Heredoc Delim:\"A\" (Line \"B\")) (Heredoc Delim:\"B\" (Line \"A\")))");
+    // note: 閉じる区切り文字列が異なれば、途中の行または入力の終端で失敗する
+    assert_parse_fails(grammar_src, "<<EOF\nabc\nEND", "'.Main.");
+    assert_parse_fails(grammar_src, "<<A\nA\n<<B\nA", "'.Main.");
+
+    // note: 呼び出し先の規則で記録された文脈変数は規則から戻る際に破棄される
+    let scope_grammar_src = r##"[Main]{
+        Main <- Open MATCH_CAPTURED<"delim"> "\z"#,
+        Open <- CAPTURE<"delim", "a">,
+    }"##;
+
+    assert_parse_fails(scope_grammar_src, "aa", "unknown context name 'delim'");
+    assert_parse_fails("[Main]{\n    Main <- MATCH_CAPTURED<\"delim\"> \"\\z\"#,\n}", "a", "unknown context name 'delim'");
+}
+
+// spec: 最長一致の選択で記述順によらず最も長く一致した選択肢が採用され、同じ長さで一致した選択肢が複数あれば左にあるものが採用されることを確認する
+#[test]
+fn longest_match_choices() {
+    let grammar_src = r##"[Main]{
+        Main <- Tok (" "# Tok)*## "\z"#,
+        Tok <- Op : Word,
+        Op <- ("<" : "<=" : "<<" : "<<=")|##,
+        Word <- ("if"#Kw : JOIN<[a-z]+>#Ident)|##,
+        RevWord <- (JOIN<[a-z]+>#Ident : "if"#Kw)|## "\z"#,
+    }"##;
+
+    assert_parse_tree(grammar_src, ".Main.Main", "<= <<= < <<", "(Main (Tok (Op \"<=\")) (Tok (Op \"<<=\")) (Tok (Op \"<\")) (Tok (Op \"<<\")))");
+    assert_parse_tree(grammar_src, ".Main.Main", "if iffy i", "(Main (Tok (Word Kw:\"if\")) (Tok (Word Ident:\"iffy\")) (Tok (Word Ident:\"i\")))");
+    assert_parse_tree(grammar_src, ".Main.RevWord", "if", "(RevWord Ident:\"if\")");
+    assert_parse_fails(grammar_src, "<>", "'.Main.Main'");
+}
+
+// spec: JOIN の引数内で使われるテンプレート引数は終端記号のみを受け付け、総称引数には種別の制約がないことを確認する
+#[test]
+fn template_argument_kinds() {
+    let grammar_src = r##"[Main]{
+        Main <- Tok(Letter) "\z"#,
+        Term <- Tok("ab") "\z"#,
+        Generic <- Wrap<Letter> "\z"#,
+        Tok($T) <- JOIN<$T> ";"#,
+        Wrap<$T> <- JOIN<$T>,
+        Letter <- [a-z],
+    }"##;
+
+    assert_parse_tree(grammar_src, ".Main.Term", "ab;", "(Term (Tok \"ab\"))");
+    assert_parse_tree(grammar_src, ".Main.Generic", "a", "(Generic (Wrap \"a\"))");
+    assert_parse_fails(grammar_src, "a;", "argument '$T' expects terminal expression but non-terminal expression is passed");
+}
+
+// spec: from_str_with_diagnostics() が文法の構文解析の失敗のみを診断情報として追加し、読み込みに成功した場合やブロック・規則の検査で失敗した場合は追加しないことを確認する
+#[test]
+fn grammar_load_diagnostics() {
+    let cons = new_console();
+    let load = |grammar_src: &str| {
+        let mut diagnostics = Vec::<Diagnostic>::new();
+        let result = FCPEGGrammar::from_str_with_diagnostics(cons.clone(), TEST_GRAMMAR_FILE_PATH, grammar_src, &mut diagnostics);
+        cons.borrow_mut().clear_log();
+        (result.is_ok(), diagnostics)
+    };
+
+    let (is_loaded, diagnostics) = load("[Main]{\n    Main <- \"a\" \"\\z\"#,\n}");
+    assert!(is_loaded && diagnostics.len() == 0, "valid grammar was rejected or reported diagnostics: {:?}", diagnostics);
+
+    let (is_loaded, diagnostics) = load("[Main]{\n    Main <- ,\n}");
+    assert!(!is_loaded, "grammar with a syntax error was unexpectedly loaded");
+    assert!(diagnostics.iter().any(|each_diagnostic| each_diagnostic.severity == DiagnosticSeverity::Error), "syntax error of the grammar was not reported as a diagnostic: {:?}", diagnostics);
+
+    let (is_loaded, diagnostics) = load("[Main]{\n    Main <- Unknown,\n}");
+    assert!(!is_loaded, "grammar referring to an unknown rule was unexpectedly loaded");
+    assert_eq!(diagnostics, Vec::new(), "block error was unexpectedly reported as a diagnostic");
+}
+
+// spec: GRAMMAR_MAX_DEPTH を超えて括弧を入れ子にした文法がスタックを溢れさせずに読み込みのエラーとなり、浅い入れ子の文法は読み込めることを確認する
+// note: 既定のスタックサイズのスレッド (テストのスレッド) で読み込む
+#[test]
+fn deeply_nested_grammar_is_rejected() {
+    let cons = new_console();
+    let to_nested_grammar_src = |nesting: usize| format!("[Main]{{\n    Main <- {}\"a\"{} \"\\z\"#,\n}}", "(".repeat(nesting), ")".repeat(nesting));
+
+    if FCPEGGrammar::from_str(cons.clone(), TEST_GRAMMAR_FILE_PATH, &to_nested_grammar_src(8)).is_err() {
+        cons.borrow().print_all();
+        panic!("failed to load the shallowly nested grammar");
+    }
+
+    assert!(FCPEGGrammar::from_str(cons.clone(), TEST_GRAMMAR_FILE_PATH, &to_nested_grammar_src(GRAMMAR_MAX_DEPTH)).is_err(), "grammar nested over the depth limit was unexpectedly loaded");
+    cons.borrow_mut().clear_log();
+}
+
+// spec: RuleGroup::first_elements() が先頭で入力を消費しうる表現字句要素を出現順に返し、最小一致長が 0 の要素の後続を含め、先読みの要素を除くことを確認する
+// spec: child_count() と is_empty() がサブ要素の数と有無を返すことを確認する
+#[test]
+fn group_first_elements() {
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, "[Main]{\n    Main <- Seq Choice Nested Ref \"\\z\"#,\n    Seq <- \"a\"? \"b\"* \"c\" \"d\",\n    Choice <- &\"p\" \"q\" : \"r\" \"s\",\n    Nested <- (\"x\" \"y\")? \"z\" \"w\",\n    Ref <- Opt \"k\",\n    Opt <- \"o\"?,\n}");
+
+    // note: 規則 ID は末尾の規則名を <> で囲む
+    let to_first_values = |rule_id: &str| {
+        let rule = match rule_map.rule_map.get(rule_id) {
+            Some(v) => v,
+            None => panic!("rule '{}' is not defined", rule_id),
+        };
+
+        return rule.group.first_elements().iter().map(|each_elem| match each_elem {
+            RuleElement::Expression(expr) => match &expr.kind {
+                RuleExpressionKind::Id => format!("<{}>", expr.value.rsplit('.').next().unwrap()),
+                _ => expr.value.clone(),
+            },
+            RuleElement::Group(_) => panic!("first element of rule '{}' is a group", rule_id),
+        }).collect::<Vec<String>>();
+    };
+
+    assert_eq!(to_first_values(".Main.Seq"), vec!["a", "b", "c"]);
+    assert_eq!(to_first_values(".Main.Choice"), vec!["q", "r"], "lookahead element is included");
+    assert_eq!(to_first_values(".Main.Nested"), vec!["x", "z"]);
+    assert_eq!(to_first_values(".Main.Ref"), vec!["<Opt>", "k"], "element following a nullable rule reference is not included");
+    assert_eq!(to_first_values(".Main.Opt"), vec!["o"]);
+
+    let mut group = RuleGroup::new(RuleGroupKind::Sequence);
+    assert!(group.is_empty());
+    assert_eq!(group.child_count(), 0);
+
+    let to_string_elem = |value: &str| RuleElement::Expression(Box::new(RuleExpression::new(CharacterPosition::get_empty(), RuleExpressionKind::String, value.to_string())));
+    group.sub_elems.push(to_string_elem("a"));
+    group.sub_elems.push(to_string_elem("b"));
+    assert!(!group.is_empty());
+    assert_eq!(group.child_count(), 2);
+}
+
+// spec: RuleMap::validate_completeness() が読み込んだ規則マップでは空となり、規則を取り除いた規則マップではジェネリクス引数内を含む未定義の規則の参照を出現順に返し、プリミティブ規則と引数 ID を報告しないことを確認する
+#[test]
+fn rule_map_completeness() {
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, "[Main]{\n    Main <- Item Wrap<Item> JOIN<Item> \"\\z\"#,\n    Wrap<$T> <- \"(\"# $T \")\"#,\n    Item <- [a-z]+,\n}");
+    // note: (行, 列, 規則 ID) の一覧
+    let to_refs = |undefined_refs: Vec<(CharacterPosition, String)>| undefined_refs.iter().map(|(each_pos, each_rule_id)| (each_pos.line, each_pos.column, each_rule_id.clone())).collect::<Vec<(usize, usize, String)>>();
+    assert_eq!(to_refs(rule_map.validate_completeness()), Vec::new(), "loaded rule map has undefined rule references");
+
+    let mut incomplete_rule_map = (**rule_map).clone();
+    assert!(incomplete_rule_map.rule_map.remove(".Main.Item").is_some(), "rule '.Main.Item' is not defined");
+
+    let undefined_refs = to_refs(incomplete_rule_map.validate_completeness());
+    let undefined_rule_ids = undefined_refs.iter().map(|(_, _, each_rule_id)| each_rule_id.as_str()).collect::<Vec<&str>>();
+    assert_eq!(undefined_rule_ids, vec![".Main.Item", ".Main.Item", ".Main.Item"], "unexpected undefined rule references: {:?}", undefined_refs);
+
+    // note: いずれも Main 規則の行にあり、出現順に並ぶ
+    assert!(undefined_refs.iter().all(|(each_line, _, _)| *each_line == 1), "unexpected positions of undefined rule references: {:?}", undefined_refs);
+    assert!(undefined_refs.windows(2).all(|each_pair| each_pair[0].1 < each_pair[1].1), "undefined rule references are not in order of appearance: {:?}", undefined_refs);
+}
+
+// spec: 構成ファイルは文法と同じ仮想ファイルパスで拡張子を cfg としたものとして読み込まれる
+fn load_rule_map_with_config(cons: &Rc<RefCell<Console>>, grammar_src: &str, config_src: &str) -> Arc<Box<RuleMap>> {
+    let mut src_map = HashMap::<String, String>::new();
+    src_map.insert(TEST_GRAMMAR_FILE_PATH.to_string(), grammar_src.to_string());
+    src_map.insert(FileMan::rename_ext(&TEST_GRAMMAR_FILE_PATH.to_string(), "cfg"), config_src.to_string());
+
+    return match FCPEGGrammar::from_str_map(cons.clone(), TEST_GRAMMAR_FILE_PATH, src_map) {
+        Ok(v) => {
+            cons.borrow_mut().clear_log();
+            v
+        },
+        Err(()) => {
+            cons.borrow().print_all();
+            panic!("failed to load the grammar with the configuration");
+        },
+    };
+}
+
+// note: 変換に失敗する入力は SyntaxParser::parse_bytes() を直接呼び出して確認する
+fn parse_bytes_to_sexp(cons: &Rc<RefCell<Console>>, rule_map: Arc<Box<RuleMap>>, input: &[u8], encoding: Encoding, enable_memoization: bool) -> String {
+    let parser = match encoding {
+        Encoding::Binary => SyntaxParser::new_bytes(cons.clone(), rule_map, TEST_INPUT_FILE_PATH.to_string(), input.to_vec(), enable_memoization),
+        Encoding::Latin1 | Encoding::Utf8 => match encoding.decode(input) {
+            Ok(src_content) => SyntaxParser::new(cons.clone(), rule_map, TEST_INPUT_FILE_PATH.to_string(), Box::new(src_content), enable_memoization),
+            Err(_) => panic!("failed to decode the input bytes (encoding: {})", encoding),
+        },
+    };
+
+    let mut parser = parser.with_config(&ParseConfig::default().with_memoization(enable_memoization));
+
+    return match parser.run() {
+        Ok(tree) => {
+            assert_tree_invariants(&tree);
+            assert_leaf_positions(&parser, &tree);
+            tree.to_sexp(true)
+        },
+        Err(()) => {
+            cons.borrow().print_all();
+            panic!("failed to parse the input bytes (encoding: {}, memoization: {})", encoding, enable_memoization);
+        },
+    };
+}
@@ -0,0 +1,256 @@
+use super::*;
+
+// spec: 各入力をメモ化の有効時と無効時で解析し、成否と構文木の構造 (UUID を除く) が一致することを確認する; 不一致の場合は最初に異なる要素のパスを示す
+// note: 非反映的な要素・リーフの位置・ノードの一致範囲も比較する
+fn assert_memoization_consistent(grammar_src: &str, start_rule: &str, inputs: &[&str]) {
+    let cons = new_console();
+    let rule_map = load_rule_map_with_start_rule(&cons, grammar_src, start_rule);
+
+    for each_input in inputs {
+        let memoized_tree = SyntaxParser::parse(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new(each_input.to_string()), ParseConfig::default().with_memoization(true));
+        let unmemoized_tree = SyntaxParser::parse(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new(each_input.to_string()), ParseConfig::default().with_memoization(false));
+        cons.borrow_mut().clear_log();
+
+        match (memoized_tree, unmemoized_tree) {
+            (Ok(memoized_tree), Ok(unmemoized_tree)) => {
+                let mut path = Vec::<String>::new();
+
+                match get_divergent_path(memoized_tree.get_child_ref(), unmemoized_tree.get_child_ref(), &mut path) {
+                    Some(reason) => panic!("parse results differ between memoization enabled and disabled\ninput:\t{:?}\npath:\t{}\nreason:\t{}\nmemoized:\t{}\nunmemoized:\t{}", each_input, path.join("/"), reason, memoized_tree.to_sexp(false), unmemoized_tree.to_sexp(false)),
+                    None => (),
+                }
+            },
+            (Err(()), Err(())) => (),
+            (Ok(_), Err(())) => panic!("parsing succeeded only with memoization enabled\ninput:\t{:?}", each_input),
+            (Err(()), Ok(_)) => panic!("parsing succeeded only with memoization disabled\ninput:\t{:?}", each_input),
+        }
+    }
+}
+
+#[test]
+fn memoization_corpus_consistent() {
+    for (each_grammar_src, each_start_rule, each_inputs) in MEMOIZATION_TEST_CORPUS {
+        assert_memoization_consistent(each_grammar_src, each_start_rule, each_inputs);
+    }
+}
+
+// spec: 文法の各規則に %memo / %nomemo を付けた複数の組み合わせについて、メモ化の有効時と無効時のいずれでも指定のない文法をメモ化なしで解析した結果と成否・構文木が一致することを確認する
+// note: 規則定義は行頭 (インデントを除く) から "規則名 ... <-" の形式で記述されている必要がある
+fn assert_memo_annotations_consistent(grammar_src: &str, start_rule: &str, inputs: &[&str]) {
+    let cons = new_console();
+    let base_rule_map = load_rule_map_with_start_rule(&cons, grammar_src, start_rule);
+    let annotations = ["%memo ", "%nomemo ", ""];
+
+    for offset in 0..annotations.len() {
+        let mut rule_i = 0;
+
+        let annotated_grammar_src = grammar_src.split("\n").map(|each_line| {
+            let trimmed_line = each_line.trim_start();
+
+            if trimmed_line.starts_with(|c: char| c.is_ascii_uppercase()) && trimmed_line.contains("<-") {
+                let annotation = annotations[(rule_i + offset) % annotations.len()];
+                rule_i += 1;
+                format!("{}{}{}", &each_line[..each_line.len() - trimmed_line.len()], annotation, trimmed_line)
+            } else {
+                each_line.to_string()
+            }
+        }).collect::<Vec<String>>().join("\n");
+
+        let annotated_rule_map = load_rule_map_with_start_rule(&cons, &annotated_grammar_src, start_rule);
+
+        for each_input in inputs {
+            let base_tree = SyntaxParser::parse(cons.clone(), base_rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new(each_input.to_string()), ParseConfig::default().with_memoization(false));
+
+            for enable_memoization in vec![true, false] {
+                let annotated_tree = SyntaxParser::parse(cons.clone(), annotated_rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new(each_input.to_string()), ParseConfig::default().with_memoization(enable_memoization));
+
+                match (&base_tree, annotated_tree) {
+                    (Ok(base_tree), Ok(annotated_tree)) => {
+                        let mut path = Vec::<String>::new();
+
+                        match get_divergent_path(base_tree.get_child_ref(), annotated_tree.get_child_ref(), &mut path) {
+                            Some(reason) => panic!("parse results differ with memoization annotations (memoization: {})\ninput:\t{:?}\ngrammar:\n{}\npath:\t{}\nreason:\t{}\nbase:\t{}\nannotated:\t{}", enable_memoization, each_input, annotated_grammar_src, path.join("/"), reason, base_tree.to_sexp(false), annotated_tree.to_sexp(false)),
+                            None => (),
+                        }
+                    },
+                    (Err(()), Err(())) => (),
+                    (Ok(_), Err(())) => panic!("parsing succeeded only without memoization annotations (memoization: {})\ninput:\t{:?}\ngrammar:\n{}", enable_memoization, each_input, annotated_grammar_src),
+                    (Err(()), Ok(_)) => panic!("parsing succeeded only with memoization annotations (memoization: {})\ninput:\t{:?}\ngrammar:\n{}", enable_memoization, each_input, annotated_grammar_src),
+                }
+            }
+
+            cons.borrow_mut().clear_log();
+        }
+    }
+}
+
+#[test]
+fn memo_annotations_corpus_consistent() {
+    for (each_grammar_src, each_start_rule, each_inputs) in MEMOIZATION_TEST_CORPUS {
+        assert_memo_annotations_consistent(each_grammar_src, each_start_rule, each_inputs);
+    }
+}
+
+// spec: %memo / %nomemo 指定が設定より優先されることを規則ごとのメモ化の統計情報で確認し、format_grammar() が指定を保つことを確認する
+#[test]
+fn rule_memo_stats() {
+    // note: Call が Name の後で失敗すると、選択の次の候補で同じ位置の Name が再び試される
+    let grammar_src = |name_annotation: &str| format!("[Main]{{\n    Main <- (Call : Name) \"\\z\"#,\n    Call <- Name \"(\"# Name \")\"#,\n    {}Name <- JOIN<[a-z]+>,\n}}", name_annotation);
+
+    // note: (Name の指定, 設定のメモ化の有無, 統計情報を持つべき規則 ID)
+    let cases = vec![
+        ("%memo ", false, vec![".Main.Name"]),
+        ("%nomemo ", true, vec![".Main.Call", ".Main.Main"]),
+        ("", true, vec![".Main.Call", ".Main.Main", ".Main.Name"]),
+        ("", false, vec![]),
+    ];
+
+    for (each_annotation, each_enable_memoization, each_expected_rule_ids) in cases {
+        let cons = new_console();
+        let rule_map = load_rule_map(&cons, &grammar_src(each_annotation));
+
+        if each_annotation != "" {
+            assert!(rule_map.format_grammar().contains(&format!("{}Name <-", each_annotation)), "format_grammar() drops annotation '{}':\n{}", each_annotation, rule_map.format_grammar());
+        }
+
+        let config = ParseConfig::default().with_memoization(each_enable_memoization).with_rule_memo_stats(true);
+        // note: with_config() の設定が SyntaxParser::new() のメモ化の指定より優先されることも確認する
+        let mut parser = SyntaxParser::new(cons.clone(), rule_map, TEST_INPUT_FILE_PATH.to_string(), Box::new("abc".to_string()), true).with_config(&config);
+
+        match parser.run() {
+            Ok(_) => (),
+            Err(()) => {
+                cons.borrow().print_all();
+                panic!("failed to parse the input (annotation: '{}')", each_annotation);
+            },
+        }
+
+        let rule_memo_stats = parser.get_rule_memo_stats();
+        let mut actual_rule_ids = rule_memo_stats.keys().map(|each_rule_id| each_rule_id.as_str()).collect::<Vec<&str>>();
+        actual_rule_ids.sort();
+        assert_eq!(actual_rule_ids, each_expected_rule_ids, "unexpected rules in memoization stats (annotation: '{}', memoization: {})", each_annotation, each_enable_memoization);
+
+        let hit_count = rule_memo_stats.values().map(|each_stats| each_stats.hit_count).sum::<usize>();
+        let miss_count = rule_memo_stats.values().map(|each_stats| each_stats.miss_count).sum::<usize>();
+        assert_eq!((hit_count, miss_count), (parser.get_stats().memo_hit_count, parser.get_stats().memo_miss_count), "per-rule memoization stats mismatch the totals (annotation: '{}')", each_annotation);
+
+        match rule_memo_stats.get(".Main.Name") {
+            Some(name_stats) => assert!(name_stats.hit_count > 0 && name_stats.entry_count > 0, "Name is expected to be reused from memoization: {:?}", name_stats),
+            None => (),
+        }
+    }
+}
+
+// note: 共有メモ化の検査に用いる入力のおおよその大きさ (バイト)
+const SHARED_MEMOIZATION_TEST_INPUT_LEN: usize = 200 * 1024;
+
+// spec: SHARED_MEMOIZATION_TEST_INPUT_LEN の入力を 3 つの入口規則で解析し、SharedMemoization を共有した場合としない場合で構文木が一致し、共有した場合にメモ化の結果がより多く再利用されることを確認する
+#[test]
+fn shared_memoization_consistent() {
+    // note: いずれの入口規則も同じ位置で Entry を呼び出すため、2 つ目以降の解析では Entry の結果が再利用される
+    let grammar_src = r##"[Main]{
+        + entry Main.Flat,
+        + entry Main.Keys,
+        Main <- Entry* "\z"#,
+        Flat <- Entry*## "\z"#,
+        Keys <- (Entry#)* "\z"#,
+        Entry <- Key ":"# " "# Value "\n"#,
+        Key <- JOIN<[a-z]+>,
+        Value <- (Num : Str : List)|,
+        Num <- JOIN<"-"? [0-9]+ ("." [0-9]+)?>,
+        Str <- "\""# JOIN<(!"\"" .)*> "\""#,
+        List <- "["# Value (","# Value)*## "]"#,
+    }"##;
+
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, grammar_src);
+    let mut input = String::new();
+
+    while input.len() < SHARED_MEMOIZATION_TEST_INPUT_LEN {
+        let i = input.len();
+        input += &format!("key: [{},\"s{}\",[-{}.5,\"t\",[{}]]]\n", i, i, i, i % 7);
+    }
+
+    let entry_rule_ids = vec![".Main.Main", ".Main.Flat", ".Main.Keys"];
+    let config = ParseConfig::default().with_loop_limit(SHARED_MEMOIZATION_TEST_INPUT_LEN);
+    let shared_memoization = SharedMemoization::new();
+    let mut sexps = Vec::<Vec<String>>::new();
+    let mut memo_hit_counts = Vec::<usize>::new();
+
+    for each_config in vec![config.clone(), config.with_shared_memoization(shared_memoization.clone())] {
+        let mut each_sexps = Vec::<String>::new();
+        let mut each_memo_hit_count = 0;
+
+        for each_entry in &entry_rule_ids {
+            match SyntaxParser::parse_with_stats(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new(input.clone()), each_config.clone().with_start_rule_id(each_entry.to_string())) {
+                Ok(output) => {
+                    each_sexps.push(output.tree.to_sexp(true));
+                    each_memo_hit_count += output.stats.memo_hit_count;
+                },
+                Err(()) => {
+                    cons.borrow().print_all();
+                    panic!("failed to parse the input from entry rule '{}'", each_entry);
+                },
+            }
+        }
+
+        sexps.push(each_sexps);
+        memo_hit_counts.push(each_memo_hit_count);
+    }
+
+    assert!(memo_hit_counts[1] > memo_hit_counts[0], "shared memoization did not increase memo hits (unshared: {}, shared: {})", memo_hit_counts[0], memo_hit_counts[1]);
+
+    for (each_entry, (unshared_sexp, shared_sexp)) in entry_rule_ids.iter().zip(sexps[0].iter().zip(sexps[1].iter())) {
+        if unshared_sexp != shared_sexp {
+            panic!("shared memoization changed the tree of entry rule '{}'\n{}", each_entry, get_sexp_diff("unshared", unshared_sexp, "shared", shared_sexp));
+        }
+    }
+}
+
+// spec: ParserSession::reload_grammar() で文法を置き換えた後に同じ入力を解析し、置き換え前の文法のメモ化結果を用いずに新しい文法を新たに読み込んだ場合と同じ構文木となることを確認する
+// spec: あわせて、両方の文法に現れる文字クラスの正規表現のみがキャッシュに残ることを確認する
+#[test]
+fn session_grammar_reload() {
+    let old_grammar_src = r##"[Main]{
+        Main <- (Word : Num)+## "\z"#,
+        Word <- JOIN<[a-z]+>,
+        Num <- JOIN<[0-9]+>,
+    }"##;
+
+    let new_grammar_src = r##"[Main]{
+        Main <- (Num : Ident)+## "\z"#,
+        Ident <- JOIN<[a-z_]+>,
+        Num <- JOIN<[0-9]+>,
+    }"##;
+
+    let input = "ab12cd3";
+    let cons = new_console();
+
+    for enable_memoization in vec![true, false] {
+        let config = ParseConfig::default().with_memoization(enable_memoization);
+        let mut session = ParserSession::new(cons.clone(), load_rule_map(&cons, old_grammar_src)).with_config(config.clone());
+        let parse_sexp = |session: &mut ParserSession| match session.parse(TEST_INPUT_FILE_PATH.to_string(), Box::new(input.to_string())) {
+            Ok(tree) => tree.to_sexp(true),
+            Err(()) => {
+                cons.borrow().print_all();
+                panic!("failed to parse the input in the session (memoization: {})", enable_memoization);
+            },
+        };
+
+        assert_eq!(parse_sexp(&mut session), "(Main (Word \"ab\") (Num \"12\") (Word \"cd\") (Num \"3\"))");
+        assert_eq!(session.get_cached_char_class_patterns(), vec!["[0-9]", "[a-z]"]);
+
+        session.reload_grammar(load_rule_map(&cons, new_grammar_src));
+        assert_eq!(session.get_cached_char_class_patterns(), vec!["[0-9]"], "regex cache entries of the old grammar were kept (memoization: {})", enable_memoization);
+
+        let reloaded_sexp = parse_sexp(&mut session);
+        let fresh_sexp = parse_sexp(&mut ParserSession::new(cons.clone(), load_rule_map(&cons, new_grammar_src)).with_config(config));
+
+        if reloaded_sexp != fresh_sexp {
+            panic!("results of the old grammar leaked into the reloaded session (memoization: {})\n{}", enable_memoization, get_sexp_diff("reloaded", &reloaded_sexp, "fresh", &fresh_sexp));
+        }
+
+        assert_eq!(reloaded_sexp, "(Main (Ident \"ab\") (Num \"12\") (Ident \"cd\") (Num \"3\"))");
+        assert_eq!(session.get_cached_char_class_patterns(), vec!["[0-9]", "[a-z_]"]);
+    }
+}
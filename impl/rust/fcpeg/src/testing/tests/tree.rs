@@ -0,0 +1,1413 @@
+use super::*;
+
+// spec: すべてのノードについて、一致範囲で切り出した入力が配下のすべてのリーフ (非反映的なものを含む) の値を連結したものと等しいことを確認する
+// note: JOIN は非反映的な要素を結合しないため、JOIN の引数に非反映的な要素を含む文法には用いない
+fn assert_matched_ranges(grammar_src: &str, start_rule: &str, input: &str) {
+    for enable_memoization in vec![true, false] {
+        let tree = parse_tree(grammar_src, start_rule, input, ParseConfig::default().with_memoization(enable_memoization));
+
+        match tree.get_child_ref() {
+            SyntaxNodeElement::Node(node) => assert_node_matched_ranges(&tree, node, input, enable_memoization),
+            SyntaxNodeElement::Leaf(_) => (),
+        }
+    }
+}
+
+fn assert_node_matched_ranges(tree: &SyntaxTree, node: &SyntaxNode, input: &str, enable_memoization: bool) {
+    let (start_i, len) = match node.matched_range() {
+        Some(v) => v,
+        None => panic!("node {} has no matched range (memoization: {})", node.ast_reflection_style.to_sexp_name(), enable_memoization),
+    };
+
+    let mut expected = String::new();
+    collect_all_leaf_values(node, &mut expected);
+
+    // note: 入力の終端を超える範囲は EOF 用のヌル文字に一致した分であり、入力には含まれない
+    if start_i + len > input.chars().count() {
+        let _ = expected.pop();
+    }
+
+    let actual = tree.get_matched_str(input, &node.uuid);
+
+    if actual != Some(expected.as_str()) {
+        panic!("matched range {}..{} of node {} mismatches its leaves (memoization: {})\nexpected: {:?}\n  actual: {:?}", start_i, start_i + len, node.ast_reflection_style.to_sexp_name(), enable_memoization, expected, actual);
+    }
+
+    for each_elem in node.get_children() {
+        match each_elem {
+            SyntaxNodeElement::Node(sub_node) => assert_node_matched_ranges(tree, sub_node, input, enable_memoization),
+            SyntaxNodeElement::Leaf(_) => (),
+        }
+    }
+}
+
+fn collect_all_leaf_values(node: &SyntaxNode, s: &mut String) {
+    for each_elem in node.get_children() {
+        match each_elem {
+            SyntaxNodeElement::Node(sub_node) => collect_all_leaf_values(sub_node, s),
+            SyntaxNodeElement::Leaf(leaf) => *s += leaf.value.as_str(),
+        }
+    }
+}
+
+// spec: 空白を読み飛ばす四則演算の文法で、括弧の入れ子や改行を含む入力の各ノードの一致範囲を確認する
+#[test]
+fn matched_ranges() {
+    let grammar_src = r##"[Main]{
+        Expr <- Space# Sum Space# "\z"#,
+        Sum <- Prod (Space# ("+" : "-") Space# Prod)*##,
+        Prod <- Atom (Space# ("*" : "/") Space# Atom)*##,
+        Atom <- Num : "("# Space# Sum Space# ")"#,
+        Num <- JOIN<[0-9]+>,
+        Space <- (" " : "\n")*,
+    }"##;
+
+    for each_input in vec!["1", " 1 + 2*3 ", "(1 -\n (23 / 4)) * 5"] {
+        assert_matched_ranges(grammar_src, ".Main.Expr", each_input);
+    }
+}
+
+// spec: 構文解析器が生成した構文木に不整合がなく、手で組み立てた各種の不整合を SyntaxTree::verify_invariants() が検出することを確認する
+#[cfg(debug_assertions)]
+#[test]
+fn tree_invariant_violations() {
+    let parsed_tree = parse_tree("[Main]{\n    Main <- (Item : Pair)+ \"\\z\"#,\n    Item <- [a-z],\n    Pair <- \"(\"# Item##Left Item##Right \")\"#,\n}", ".Main.Main", "a(bc)d", ParseConfig::default());
+
+    assert_eq!(parsed_tree.verify_invariants(), Vec::new(), "parsed tree unexpectedly violates invariants:\n{}", parsed_tree.to_sexp(false));
+
+    let uuids = (0..4).map(|_| uuid::Uuid::new_v4()).collect::<Vec<uuid::Uuid>>();
+    let leaf = |index: usize, uuid: uuid::Uuid| SyntaxNodeElement::Leaf(Box::new(SyntaxLeaf::new(CharacterPosition::new(Some(TEST_INPUT_FILE_PATH.to_string()), index, 0, index), "a".to_string(), ASTReflectionStyle::reflection(String::new()), uuid)));
+    let node = |sub_elems: Vec<SyntaxNodeElement>, ast_reflection_style: ASTReflectionStyle, uuid: uuid::Uuid| SyntaxNodeElement::Node(Box::new(SyntaxNode::new(sub_elems, ast_reflection_style, uuid)));
+    let root = |sub_elems: Vec<SyntaxNodeElement>| SyntaxTree::from_node_args(sub_elems, ASTReflectionStyle::reflection("Main".to_string()));
+
+    // note: (説明, 構文木, 検出されるべき不整合)
+    let cases = vec![
+        ("valid", root(vec![leaf(0, uuids[0]), node(vec![leaf(1, uuids[1])], ASTReflectionStyle::reflection(String::new()), uuids[2])]), vec![]),
+        ("duplicate uuid", root(vec![leaf(0, uuids[0]), leaf(1, uuids[0])]), vec![InvariantViolation::DuplicateUuid { uuid: uuids[0] }]),
+        ("empty expansion node", root(vec![node(Vec::new(), ASTReflectionStyle::expansion(), uuids[0])]), vec![InvariantViolation::EmptyExpansionNode { uuid: uuids[0] }]),
+        ("decreasing leaf position", root(vec![leaf(2, uuids[0]), leaf(1, uuids[1])]), vec![InvariantViolation::LeafPositionDecreased { uuid: uuids[1], prev_index: 2, index: 1 }]),
+        ("self reference", SyntaxTree::from_node(node(vec![node(vec![leaf(0, uuids[0])], ASTReflectionStyle::reflection(String::new()), uuids[3])], ASTReflectionStyle::reflection("Main".to_string()), uuids[0])), vec![InvariantViolation::SelfReference { uuid: uuids[0] }]),
+    ];
+
+    for (each_desc, each_tree, each_expected) in cases {
+        let actual = each_tree.verify_invariants();
+
+        if actual != each_expected {
+            panic!("unexpected invariant violations ({})\nexpected:\t{:?}\nactual:\t\t{:?}", each_desc, each_expected, actual);
+        }
+    }
+}
+
+// spec: SyntaxTree::to_token_stream() がリーフを入力順に返し、include_hidden が偽であれば非反映的なリーフと非反映的なノード以下のリーフを除くことを確認する
+#[test]
+fn token_streams() {
+    let tree = parse_tree("[Main]{\n    Main <- Word (Space# (Word : Op))* \"\\z\"#,\n    Word <- JOIN<[a-z]+>,\n    Op <- \"=\" \";\"#,\n    Space <- \" \"+,\n}", ".Main.Main", "ab  =; cd", ParseConfig::default());
+
+    let to_tokens = |include_hidden: bool| tree.to_token_stream(include_hidden).iter().map(|each_leaf| (each_leaf.pos.index, each_leaf.value.clone())).collect::<Vec<(usize, String)>>();
+    let to_expected = |tokens: Vec<(usize, &str)>| tokens.iter().map(|(index, value)| (*index, value.to_string())).collect::<Vec<(usize, String)>>();
+
+    assert_eq!(to_tokens(false), to_expected(vec![(0, "ab"), (4, "="), (7, "cd")]), "unexpected token stream:\n{}", tree.to_sexp(false));
+    assert_eq!(to_tokens(true), to_expected(vec![(0, "ab"), (2, " "), (3, " "), (4, "="), (5, ";"), (6, " "), (7, "cd"), (9, "\0")]), "unexpected token stream including hidden leaves:\n{}", tree.to_sexp(false));
+
+    let leaf = |value: &str| SyntaxNodeElement::from_leaf_args(CharacterPosition::get_empty(), value.to_string(), ASTReflectionStyle::reflection(String::new()));
+    let hidden_root = SyntaxTree::from_node_args(vec![leaf("a")], ASTReflectionStyle::no_reflection());
+    assert_eq!(hidden_root.to_token_stream(false).len(), 0);
+    assert_eq!(hidden_root.to_token_stream(true).len(), 1);
+
+    let leaf_root = SyntaxTree::from_node(leaf("a"));
+    assert_eq!(leaf_root.to_token_stream(false).iter().map(|each_leaf| each_leaf.value.as_str()).collect::<Vec<&str>>(), vec!["a"]);
+}
+
+// note: 指定の反映名のノードの反映名を置き換える
+struct RenameNodeTransform {
+    elem_name: &'static str,
+    new_elem_name: &'static str,
+}
+
+impl TreeTransform for RenameNodeTransform {
+    fn transform_node(&self, node: &SyntaxNode) -> Option<SyntaxNodeElement> {
+        let mut new_node = node.clone();
+
+        if node.ast_reflection_style.is_reflected_as(self.elem_name) {
+            new_node.ast_reflection_style = ASTReflectionStyle::reflection(self.new_elem_name.to_string());
+        }
+
+        return Some(SyntaxNodeElement::Node(Box::new(new_node)));
+    }
+}
+
+// note: 指定の反映名のノードを取り除く
+struct RemoveNodeTransform {
+    elem_name: &'static str,
+}
+
+impl TreeTransform for RemoveNodeTransform {
+    fn transform_node(&self, node: &SyntaxNode) -> Option<SyntaxNodeElement> {
+        return if node.ast_reflection_style.is_reflected_as(self.elem_name) {
+            None
+        } else {
+            Some(SyntaxNodeElement::Node(Box::new(node.clone())))
+        };
+    }
+}
+
+// note: 子要素がすべて Reflectable なリーフであるノードを、値を連結した 1 つのリーフで置き換える
+struct JoinLeavesTransform;
+
+impl TreeTransform for JoinLeavesTransform {
+    fn transform_node(&self, node: &SyntaxNode) -> Option<SyntaxNodeElement> {
+        let is_joinable = node.sub_elems.len() != 0 && node.sub_elems.iter().all(|each_elem| !each_elem.is_node() && each_elem.is_reflectable());
+
+        return if is_joinable {
+            let pos = node.sub_elems[0].first_leaf_position().unwrap();
+            Some(SyntaxNodeElement::from_leaf_args(pos, node.join_child_leaf_values(), node.ast_reflection_style.clone()))
+        } else {
+            Some(SyntaxNodeElement::Node(Box::new(node.clone())))
+        };
+    }
+}
+
+// spec: 独立した変換を SyntaxTree::apply_transform() で子から親の順に適用し、ComposeTransform で合成した変換が記述順に適用されることを確認する
+#[test]
+fn tree_transforms() {
+    let tree = parse_tree("[Main]{\n    Main <- Item (\",\"# Item)* \"\\z\"#,\n    Item <- Word : Num,\n    Word <- [a-z]+,\n    Num <- [0-9]+,\n}", ".Main.Main", "ab,12,c", ParseConfig::default());
+
+    let original_sexp = "(Main (Item (Word \"a\" \"b\")) (_ #:\",\" (Item (Num \"1\" \"2\")) #:\",\" (Item (Word \"c\"))) #:\"\\0\")";
+    assert_eq!(tree.to_sexp(false), original_sexp);
+
+    let rename_word = || RenameNodeTransform { elem_name: "Word", new_elem_name: "Num" };
+    let remove_num = || RemoveNodeTransform { elem_name: "Num" };
+
+    assert_eq!(tree.apply_transform(&rename_word()).to_sexp(false), "(Main (Item (Num \"a\" \"b\")) (_ #:\",\" (Item (Num \"1\" \"2\")) #:\",\" (Item (Num \"c\"))) #:\"\\0\")");
+    assert_eq!(tree.apply_transform(&remove_num()).to_sexp(false), "(Main (Item (Word \"a\" \"b\")) (_ #:\",\" (Item) #:\",\" (Item (Word \"c\"))) #:\"\\0\")");
+
+    // note: 子から親の順に適用するため、リーフに置き換えた子要素のみを持つ親ノードも続けて置き換わる
+    assert_eq!(tree.apply_transform(&JoinLeavesTransform).to_sexp(false), "(Main Item:\"ab\" (_ #:\",\" Item:\"12\" #:\",\" Item:\"c\") #:\"\\0\")");
+
+    // note: 合成した変換は記述順に適用される
+    assert_eq!(tree.apply_transform(&ComposeTransform(rename_word(), remove_num())).to_sexp(false), "(Main (Item) (_ #:\",\" (Item) #:\",\" (Item)) #:\"\\0\")");
+    assert_eq!(tree.apply_transform(&ComposeTransform(remove_num(), rename_word())).to_sexp(false), "(Main (Item (Num \"a\" \"b\")) (_ #:\",\" (Item) #:\",\" (Item (Num \"c\"))) #:\"\\0\")");
+    assert_eq!(tree.apply_transform(&ComposeTransform(JoinLeavesTransform, rename_word())).to_sexp(false), tree.apply_transform(&JoinLeavesTransform).to_sexp(false));
+
+    // note: ルートノードは取り除かれない
+    assert_eq!(tree.apply_transform(&RemoveNodeTransform { elem_name: "Main" }).to_sexp(false), original_sexp);
+    assert_eq!(tree.to_sexp(false), original_sexp, "original tree is modified by transforms");
+}
+
+// spec: SyntaxTree::simplify() の各簡約の結果の形を確認し、いずれの組み合わせでも Reflectable なリーフの値の連結が変わらないことを確認する
+#[test]
+fn tree_simplification() {
+    let grammar_src = r##"[Main]{
+    Main <- Stmt (Sep Stmt)* "\z"#,
+    Stmt <- Space# (Word : Sign) Space#,
+    Word <- [a-z]+,
+    Sign <- "+" : "-",
+    Sep <- ";"#,
+    Space <- " "*,
+}"##;
+
+    let tree = parse_tree(grammar_src, ".Main.Main", " ab ;+; cd", ParseConfig::default());
+
+    let original_sexp = "(Main (Stmt (# \" \") (_ (Word \"a\" \"b\")) (# \" \")) (_ (Sep #:\";\") (Stmt (_ (Sign \"+\"))) (Sep #:\";\") (Stmt (# \" \") (_ (Word \"c\" \"d\")))) #:\"\\0\")";
+    assert_eq!(tree.to_sexp(false), original_sexp);
+
+    let golden_sexps = vec![
+        (SimplifyOptions::default(), original_sexp),
+        (SimplifyOptions::default().with_drop_hidden_elems(true), "(Main (Stmt (_ (Word \"a\" \"b\"))) (_ (Sep) (Stmt (_ (Sign \"+\"))) (Sep) (Stmt (_ (Word \"c\" \"d\")))))"),
+        (SimplifyOptions::default().with_remove_empty_nodes(true), "(Main (Stmt (# \" \") (_ (Word \"a\" \"b\")) (# \" \")) (_ (Stmt (_ (Sign \"+\"))) (Stmt (# \" \") (_ (Word \"c\" \"d\")))) #:\"\\0\")"),
+        (SimplifyOptions::default().with_collapse_single_child_nodes(true), "(Main (Stmt (# \" \") (Word \"a\" \"b\") (# \" \")) (_ (Sep #:\";\") (Stmt (Sign \"+\")) (Sep #:\";\") (Stmt (# \" \") (Word \"c\" \"d\"))) #:\"\\0\")"),
+        (SimplifyOptions::default().with_merge_adjacent_leaves(true), "(Main (Stmt (# \" \") (_ (Word \"ab\")) (# \" \")) (_ (Sep #:\";\") (Stmt (_ (Sign \"+\"))) (Sep #:\";\") (Stmt (# \" \") (_ (Word \"cd\")))) #:\"\\0\")"),
+        (SimplifyOptions::all(), "(Main (Stmt (Word \"ab\")) (_ (Stmt (Sign \"+\")) (Stmt (Word \"cd\"))))"),
+    ];
+
+    for (each_options, each_expected_sexp) in golden_sexps {
+        assert_eq!(tree.simplify(each_options).to_sexp(false), each_expected_sexp, "unexpected simplified tree ({:?})", each_options);
+    }
+
+    assert_eq!(tree.to_sexp(false), original_sexp, "original tree is modified by simplification");
+
+    let to_reflectable_text = |tree: &SyntaxTree| tree.to_token_stream(false).iter().map(|each_leaf| each_leaf.value.as_str()).collect::<String>();
+    let to_joined_text = |tree: &SyntaxTree| match tree.get_child_ref().try_get_node() {
+        Ok(node) => node.join_child_leaf_values(),
+        Err(_) => panic!("root of the simplified tree is not a node:\n{}", tree.to_sexp(false)),
+    };
+
+    for each_bits in 0..16 {
+        let options = SimplifyOptions::default()
+            .with_drop_hidden_elems(each_bits & 1 != 0)
+            .with_remove_empty_nodes(each_bits & 2 != 0)
+            .with_collapse_single_child_nodes(each_bits & 4 != 0)
+            .with_merge_adjacent_leaves(each_bits & 8 != 0);
+
+        let simplified_tree = tree.simplify(options);
+        assert_eq!(to_reflectable_text(&simplified_tree), to_reflectable_text(&tree), "reflectable leaves are changed ({:?}):\n{}", options, simplified_tree.to_sexp(false));
+
+        // note: 非反映的なノードの下のリーフも結合されるため、要素を取り除かない組み合わせでのみ比較する
+        if !options.drop_hidden_elems && !options.remove_empty_nodes {
+            assert_eq!(to_joined_text(&simplified_tree), to_joined_text(&tree), "joined leaf values are changed ({:?}):\n{}", options, simplified_tree.to_sexp(false));
+        }
+
+        #[cfg(debug_assertions)]
+        assert_eq!(simplified_tree.verify_invariants(), Vec::<InvariantViolation>::new(), "simplified tree has invariant violations ({:?})", options);
+    }
+
+    // note: 反映名の等しい入れ子のノードと反映名のないルートは畳み込み、存在マーカーと非反映的なノードは残す
+    let leaf = |value: &str| SyntaxNodeElement::from_leaf_args(CharacterPosition::get_empty(), value.to_string(), ASTReflectionStyle::reflection(String::new()));
+    let node = |sub_elems: Vec<SyntaxNodeElement>, elem_name: &str| SyntaxNodeElement::from_node_args(sub_elems, ASTReflectionStyle::reflection(elem_name.to_string()));
+    let collapse_options = SimplifyOptions::default().with_collapse_single_child_nodes(true);
+
+    let nested_tree = SyntaxTree::from_node(node(vec![node(vec![node(vec![leaf("1")], "Expr")], "Expr"), node(vec![leaf("2")], "Num")], "Main"));
+    assert_eq!(nested_tree.simplify(collapse_options).to_sexp(false), "(Main (Expr \"1\") (Num \"2\"))");
+
+    let unnamed_root_tree = SyntaxTree::from_node(node(vec![leaf("a")], ""));
+    assert_eq!(unnamed_root_tree.simplify(collapse_options).to_sexp(false), "\"a\"");
+
+    let kept_tree = SyntaxTree::from_node(node(vec![
+        SyntaxNodeElement::from_presence_node_args(vec![leaf("p")], ASTReflectionStyle::reflection(String::new())),
+        SyntaxNodeElement::from_node_args(vec![leaf("h")], ASTReflectionStyle::no_reflection()),
+    ], "Main"));
+    assert_eq!(kept_tree.simplify(collapse_options).to_sexp(false), kept_tree.to_sexp(false));
+}
+
+// spec: 字句の並びが固定された規則のリーフの値を、子ノードと非反映的なリーフを飛ばした位置で取得できることを確認する
+#[test]
+fn nth_leaf_values() {
+    let tree = parse_tree("[Main]{\n    Main <- Let \"\\z\"#,\n    Let <- \"let\" Space# Id Space# \"=\" Space# Num \";\"#,\n    Id <- JOIN<[a-z]+>,\n    Num <- JOIN<[0-9]+>,\n    Space <- \" \"+,\n}", ".Main.Main", "let x = 42;", ParseConfig::default());
+
+    let let_node = match tree.get_child_ref().try_get_node() {
+        Ok(v) => v.child_named("Let"),
+        Err(_) => panic!("root of the tree is not a node:\n{}", tree.to_sexp(false)),
+    };
+
+    assert_eq!(let_node.get_nth_leaf_value(0), Some("let"), "unexpected leaf values:\n{}", tree.to_sexp(false));
+    assert_eq!(let_node.get_nth_leaf_value(1), Some("="), "unexpected leaf values:\n{}", tree.to_sexp(false));
+    assert_eq!(let_node.get_nth_leaf_value(2), None, "hidden leaf is counted:\n{}", tree.to_sexp(false));
+    assert_eq!(let_node.nth_leaf_value(1), "=");
+    assert_eq!(let_node.child_named("Num").nth_leaf_value(0), "42");
+
+    let missing_leaf_result = std::panic::catch_unwind(|| let_node.nth_leaf_value(2).to_string());
+    assert!(missing_leaf_result.is_err(), "nth_leaf_value() returned a value for a missing leaf");
+}
+
+// spec: 非反映的な子要素を含むノードについて、SyntaxNode::index_of_child() が get_child_at_checked() のインデックスを返し、非反映的な子要素と子要素でない要素には None を返すことを確認する
+#[test]
+fn child_indexes() {
+    let tree = parse_tree("[Main]{\n    Main <- Let \"\\z\"#,\n    Let <- \"let\" Space# Id Space# \"=\" Space# Num \";\"#,\n    Id <- JOIN<[a-z]+>,\n    Num <- JOIN<[0-9]+>,\n    Space <- \" \"+,\n}", ".Main.Main", "let x = 42;", ParseConfig::default());
+
+    let root_node = match tree.get_child_ref().try_get_node() {
+        Ok(v) => v,
+        Err(_) => panic!("root of the tree is not a node:\n{}", tree.to_sexp(false)),
+    };
+
+    let let_node = root_node.child_named("Let");
+    let mut reflectable_count = 0;
+
+    for each_elem in &let_node.sub_elems {
+        let uuid = match each_elem {
+            SyntaxNodeElement::Node(node) => node.uuid,
+            SyntaxNodeElement::Leaf(leaf) => leaf.uuid,
+        };
+
+        if !each_elem.is_reflectable() {
+            assert_eq!(let_node.index_of_child(&uuid), None, "non-reflectable child {} has an index:\n{}", uuid, tree.to_sexp(false));
+            continue;
+        }
+
+        assert_eq!(let_node.index_of_child(&uuid), Some(reflectable_count), "unexpected index of child {}:\n{}", uuid, tree.to_sexp(false));
+
+        match let_node.get_child_at_checked(reflectable_count as i64) {
+            Some(SyntaxNodeElement::Node(node)) => assert_eq!(node.uuid, uuid, "index of child {} does not point to itself", uuid),
+            Some(SyntaxNodeElement::Leaf(leaf)) => assert_eq!(leaf.uuid, uuid, "index of child {} does not point to itself", uuid),
+            None => panic!("index of child {} is out of range", uuid),
+        }
+
+        reflectable_count += 1;
+    }
+
+    assert!(reflectable_count < let_node.sub_elems.len(), "test input has no non-reflectable children:\n{}", tree.to_sexp(false));
+    assert_eq!(let_node.index_of_child(&let_node.uuid), None, "node itself is found as its child");
+    assert_eq!(root_node.index_of_child(&let_node.child_named("Id").uuid), None, "grandchild is found as a direct child");
+}
+
+// spec: SyntaxNode::depth() が子要素のないノードで 0, リーフのみのノードで 1 となり、深い構文木でもスタックを溢れさせずに数えることを確認する
+#[test]
+fn tree_depths() {
+    let leaf = || SyntaxNodeElement::from_leaf_args(CharacterPosition::get_empty(), "a".to_string(), ASTReflectionStyle::reflection(String::new()));
+    let node = |sub_elems: Vec<SyntaxNodeElement>| SyntaxNodeElement::from_node_args(sub_elems, ASTReflectionStyle::reflection(String::new()));
+
+    // note: (説明, 子要素, 期待される深さ)
+    let cases = vec![
+        ("no children", Vec::new(), 0),
+        ("leaves only", vec![leaf(), leaf()], 1),
+        ("empty child node", vec![node(Vec::new())], 1),
+        ("uneven subtrees", vec![leaf(), node(vec![leaf()]), node(vec![node(vec![leaf()]), leaf()])], 3),
+        ("hidden child", vec![SyntaxNodeElement::from_node_args(vec![leaf()], ASTReflectionStyle::no_reflection())], 2),
+    ];
+
+    for (each_desc, each_sub_elems, each_expected) in cases {
+        let tree = SyntaxTree::from_node_args(each_sub_elems, ASTReflectionStyle::reflection("Main".to_string()));
+        assert_eq!(tree.depth(), each_expected, "unexpected depth ({})", each_desc);
+    }
+
+    // note: 再帰的に数えるとスタックが溢れる深さ
+    let mut deep_elem = leaf();
+
+    for _ in 0..100000 {
+        deep_elem = node(vec![deep_elem]);
+    }
+
+    let deep_tree = SyntaxTree::from_node_args(vec![deep_elem], ASTReflectionStyle::reflection("Main".to_string()));
+    assert_eq!(deep_tree.depth(), 100001);
+
+    // note: 複製と破棄もスタックを溢れさせない
+    drop(deep_tree.clone());
+    drop(deep_tree);
+
+    let tree = parse_tree("[Main]{\n    Main <- Pair \"\\z\"#,\n    Pair <- \"(\"# (Pair : [a-z]) \")\"#,\n}", ".Main.Main", "((a))", ParseConfig::default());
+
+    // note: 選択のグループもノードとなるため Main, Pair, (選択), Pair, (選択), "a" の 5 段となる
+    assert_eq!(tree.depth(), 5, "unexpected depth of parsed tree:\n{}", tree.to_sexp(false));
+}
+
+// spec: total_text_length() が非反映的なリーフを含めた値の長さを、位置と同じ単位で返すことを確認する
+#[test]
+fn total_text_lengths() {
+    let input = "let x = 42;";
+    let tree = parse_tree("[Main]{\n    Main <- Let \"\\z\"#,\n    Let <- \"let\" Space# Id Space# \"=\" Space# Num \";\"#,\n    Id <- JOIN<[a-z]+>,\n    Num <- JOIN<[0-9]+>,\n    Space <- \" \"+,\n}", ".Main.Main", input, ParseConfig::default());
+
+    let root_node = match tree.get_child_ref().try_get_node() {
+        Ok(v) => v,
+        Err(_) => panic!("root of the tree is not a node:\n{}", tree.to_sexp(false)),
+    };
+
+    // note: ルートノードは "\z" に一致した EOF 用のヌル文字を含む
+    assert_eq!(root_node.total_text_length(), input.chars().count() + 1, "total text length of the root node differs from the input length");
+
+    let let_node = root_node.child_named("Let");
+    assert_eq!(let_node.total_text_length(), input.chars().count());
+    assert!(let_node.total_text_length() > let_node.join_child_leaf_values().chars().count(), "non-reflectable leaves are not counted:\n{}", tree.to_sexp(false));
+
+    // note: 先読みを含まない文法のため、各ノードの長さは一致した入力の範囲の文字数と等しい
+    let mut nodes = vec![root_node];
+
+    while let Some(each_node) = nodes.pop() {
+        match each_node.matched_range() {
+            Some((_, len)) => assert_eq!(each_node.total_text_length(), len, "total text length of node{} differs from its matched range", each_node.ast_reflection_style),
+            None => panic!("node{} has no matched range", each_node.ast_reflection_style),
+        }
+
+        for each_elem in &each_node.sub_elems {
+            match each_elem {
+                SyntaxNodeElement::Node(node) => nodes.push(node),
+                SyntaxNodeElement::Leaf(_) => (),
+            }
+        }
+    }
+
+    // note: 文字列は文字数、バイト列モードはバイト数となる
+    let cons = new_console();
+    let char_rule_map = load_rule_map(&cons, "[Main]{\n    Main <- (!\"\\z\" .)+ \"\\z\"#,\n}");
+    let multibyte_input = "\u{3042}\u{3044}";
+
+    let get_total_text_length = |tree: ConsoleResult<SyntaxTree>| match tree {
+        Ok(v) => match v.get_child_ref().try_get_node() {
+            Ok(node) => node.total_text_length(),
+            Err(_) => panic!("root of the tree is not a node:\n{}", v.to_sexp(false)),
+        },
+        Err(()) => {
+            cons.borrow().print_all();
+            panic!("failed to parse the multibyte input");
+        },
+    };
+
+    assert_eq!(get_total_text_length(SyntaxParser::parse(cons.clone(), char_rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new(multibyte_input.to_string()), ParseConfig::default())), 3);
+    assert_eq!(get_total_text_length(SyntaxParser::parse_bytes(cons.clone(), char_rule_map, TEST_INPUT_FILE_PATH.to_string(), multibyte_input.as_bytes(), Encoding::Binary, ParseConfig::default())), 7);
+    cons.borrow_mut().clear_log();
+}
+
+// spec: annotate() が元の構文木と同じ構造の木を返し、すべての要素の注釈を UUID から引けることを確認する
+#[test]
+fn tree_annotations() {
+    let tree = parse_tree("[Main]{\n    Main <- Let \"\\z\"#,\n    Let <- \"let\" Space# Id Space# \"=\" Space# Num \";\"#,\n    Id <- JOIN<[a-z]+>,\n    Num <- JOIN<[0-9]+>,\n    Space <- \" \"+,\n}", ".Main.Main", "let x = 42;", ParseConfig::default());
+
+    let get_uuid = |elem: &SyntaxNodeElement| match elem {
+        SyntaxNodeElement::Node(node) => node.uuid,
+        SyntaxNodeElement::Leaf(leaf) => leaf.uuid,
+    };
+
+    // note: 注釈は要素が覆う入力の長さとする
+    let visited_uuids = RefCell::new(Vec::<uuid::Uuid>::new());
+
+    let annotated_tree = tree.annotate(|elem| {
+        visited_uuids.borrow_mut().push(get_uuid(elem));
+
+        match elem {
+            SyntaxNodeElement::Node(node) => node.total_text_length(),
+            SyntaxNodeElement::Leaf(leaf) => leaf.get_value_len(),
+        }
+    });
+
+    let preorder_uuids = tree.fold(Vec::<uuid::Uuid>::new(), TraversalOrder::PreOrder, |mut uuids, each_elem| {
+        uuids.push(get_uuid(each_elem));
+        uuids
+    });
+
+    assert_eq!(*visited_uuids.borrow(), preorder_uuids, "annotation function was not called in pre-order");
+    assert_eq!(annotated_tree.len(), preorder_uuids.len(), "some elements have no annotation");
+    assert_eq!(annotated_tree.get_annotation_checked(&uuid::Uuid::new_v4()), None);
+
+    // note: 元の構文木と注釈の木を並行して辿り、構造と注釈を比較する
+    let mut elem_pairs = vec![(tree.get_child_ref(), annotated_tree.get_child_ref())];
+
+    while let Some((each_elem, each_annotated_elem)) = elem_pairs.pop() {
+        let uuid = get_uuid(each_elem);
+        assert_eq!(*each_annotated_elem.get_uuid(), uuid, "annotated tree does not mirror the original tree:\n{}", tree.to_sexp(false));
+        assert_eq!(annotated_tree.get_annotation(&uuid), each_annotated_elem.get_annotation(), "annotation looked up by UUID differs from the attached one");
+
+        match (each_elem, each_annotated_elem) {
+            (SyntaxNodeElement::Node(node), AnnotatedNodeElement::Node(annotated_node)) => {
+                assert_eq!(annotated_node.sub_elems.len(), node.sub_elems.len(), "child count of node{} differs", node.ast_reflection_style);
+                assert_eq!(annotated_node.annotation, node.total_text_length());
+                elem_pairs.extend(node.sub_elems.iter().zip(annotated_node.sub_elems.iter()));
+            },
+            (SyntaxNodeElement::Leaf(leaf), AnnotatedNodeElement::Leaf(annotated_leaf)) => {
+                assert_eq!(annotated_leaf.value, leaf.value);
+                assert_eq!(annotated_leaf.annotation, leaf.get_value_len());
+            },
+            _ => panic!("element kind of {} differs in the annotated tree", uuid),
+        }
+    }
+}
+
+// spec: 複数行にわたる先読み・選択の失敗で入力位置を戻した後に一致したリーフの行と列が、入力の先頭から数えたものと一致することを確認する
+// note: 行と列は入力位置から求めるため、後戻りで戻すのは入力位置のみでよい (ParserCheckpoint)
+#[test]
+fn positions_after_multiline_backtracking() {
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, r##"[Main]{
+        Main <- !(Lines "x") Alt "\z"#,
+        Alt <- &(Lines "q") Lines "q" : Lines "e",
+        Lines <- ([a-z]+ "\n")+,
+    }"##);
+
+    let input = "ab\ncd\ne";
+
+    for each_engine in vec![ParseEngine::Recursive, ParseEngine::Iterative] {
+        for each_enable_memoization in vec![true, false] {
+            let config = ParseConfig::default().with_engine(each_engine).with_memoization(each_enable_memoization);
+            let mut parser = SyntaxParser::new(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new(input.to_string()), each_enable_memoization).with_config(&config);
+
+            let tree = match parser.run() {
+                Ok(v) => v,
+                Err(()) => {
+                    cons.borrow().print_all();
+                    panic!("failed to parse the input after multi-line backtracking");
+                },
+            };
+
+            assert_leaf_positions(&parser, &tree);
+
+            for each_leaf in tree.to_token_stream(true) {
+                let preceding_chars = input.chars().take(each_leaf.pos.index).collect::<String>();
+                let expected_line = preceding_chars.matches('\n').count();
+
+                let expected_column = match preceding_chars.rfind('\n') {
+                    Some(line_end_i) => preceding_chars[line_end_i + 1..].chars().count(),
+                    None => preceding_chars.chars().count(),
+                };
+
+                assert_eq!((each_leaf.pos.line, each_leaf.pos.column), (expected_line, expected_column), "line and column of leaf {:?} at index {} are corrupted after backtracking ({:?}, memoization: {})", each_leaf.value, each_leaf.pos.index, each_engine, each_enable_memoization);
+            }
+
+            let last_leaf = match tree.to_token_stream(false).last() {
+                Some(v) => (*v).clone(),
+                None => panic!("tree has no leaves:\n{}", tree.to_sexp(false)),
+            };
+
+            assert_eq!((last_leaf.value.as_str(), last_leaf.pos.line, last_leaf.pos.column), ("e", 2, 0), "unexpected last leaf:\n{}", tree.to_sexp(false));
+        }
+    }
+
+    cons.borrow_mut().clear_log();
+}
+
+// spec: JOIN で結合したリーフが引数の終了位置ではなく開始位置を持つことを確認する
+// note: 範囲をリーフの位置と値から求める print_with_source() 等は開始位置であることを前提とする
+#[test]
+fn join_leaf_positions() {
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, "[Main]{\n    Main <- \"x\"# JOIN<[a-z]+> \"\\n\"# JOIN<[a-z]+> \"\\z\"#,\n}");
+    let input = "xab\ncd";
+
+    for each_engine in vec![ParseEngine::Recursive, ParseEngine::Iterative] {
+        for each_enable_memoization in vec![true, false] {
+            let config = ParseConfig::default().with_engine(each_engine).with_memoization(each_enable_memoization);
+            let mut parser = SyntaxParser::new(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new(input.to_string()), each_enable_memoization).with_config(&config);
+
+            let tree = match parser.run() {
+                Ok(v) => v,
+                Err(()) => {
+                    cons.borrow().print_all();
+                    panic!("failed to parse the input ({:?}, memoization: {})", each_engine, each_enable_memoization);
+                },
+            };
+
+            assert_leaf_positions(&parser, &tree);
+
+            let tokens = tree.to_token_stream(false).iter().map(|each_leaf| (each_leaf.value.clone(), each_leaf.pos.index, each_leaf.pos.line, each_leaf.pos.column)).collect::<Vec<(String, usize, usize, usize)>>();
+            assert_eq!(tokens, vec![("ab".to_string(), 1, 0, 1), ("cd".to_string(), 4, 1, 0)], "unexpected joined leaf positions ({:?}, memoization: {})", each_engine, each_enable_memoization);
+        }
+    }
+
+    cons.borrow_mut().clear_log();
+}
+
+// spec: SCAN の走査が ParserCheckpoint で複数行にわたる読み進めを戻した後も、後続のリーフの行と列が入力の先頭から数えたものと一致することを確認する
+// note: dangling は入力を読み進めたまま失敗し、走査前の位置に戻すことを構文解析器に委ねる
+#[test]
+fn scanner_checkpoints() {
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, r##"[Main]{
+        Main <- Item+ "\z"#,
+        Item <- SCAN<"dangling"> : Block : Text,
+        Block <- SCAN<"block">,
+        Text <- [a-z] : "{" : "}" : "\n",
+    }"##);
+
+    let mut registry = PrimitiveRegistry::new();
+
+    registry.register_scanner("dangling", Arc::new(|parser: &mut SyntaxParser| {
+        while parser.consume_char().is_some() {}
+        None
+    }));
+
+    // note: 入れ子の波括弧を読み進め、閉じられていなければ開始位置に戻して失敗する
+    registry.register_scanner("block", Arc::new(|parser: &mut SyntaxParser| {
+        let checkpoint = parser.checkpoint();
+
+        if !parser.consume_str("{") {
+            return None;
+        }
+
+        let mut value = "{".to_string();
+        let mut depth = 1usize;
+
+        while depth != 0 {
+            let tar_char = match parser.consume_char() {
+                Some(v) => v,
+                None => {
+                    parser.restore(checkpoint);
+                    return None;
+                },
+            };
+
+            match tar_char {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => (),
+            }
+
+            value.push(tar_char);
+        }
+
+        Some(value)
+    }));
+
+    let input = "a{b\n{c}\n}d\n{e\nf";
+    let expected_sexp = normalize_sexp(r##"(Main (Item (Text "a")) (Item (Block "{b\n{c}\n}")) (Item (Text "d")) (Item (Text "\n"))
+        (Item (Text "{")) (Item (Text "e")) (Item (Text "\n")) (Item (Text "f")))"##);
+
+    for each_engine in vec![ParseEngine::Recursive, ParseEngine::Iterative] {
+        for each_enable_memoization in vec![true, false] {
+            let config = ParseConfig::default().with_engine(each_engine).with_memoization(each_enable_memoization).with_primitive_registry(registry.clone());
+            let mut parser = SyntaxParser::new(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new(input.to_string()), each_enable_memoization).with_config(&config);
+
+            let tree = match parser.run() {
+                Ok(v) => v,
+                Err(()) => {
+                    cons.borrow().print_all();
+                    panic!("failed to parse the input with scanners ({:?}, memoization: {})", each_engine, each_enable_memoization);
+                },
+            };
+
+            assert_eq!(tree.to_sexp(true), expected_sexp, "unexpected tree ({:?}, memoization: {})", each_engine, each_enable_memoization);
+            assert_leaf_positions(&parser, &tree);
+
+            let leaf_positions = tree.to_token_stream(true).iter().map(|each_leaf| (each_leaf.pos.index, each_leaf.pos.line, each_leaf.pos.column)).collect::<Vec<(usize, usize, usize)>>();
+            assert_eq!(leaf_positions, vec![(0, 0, 0), (1, 0, 1), (9, 2, 1), (10, 2, 2), (11, 3, 0), (12, 3, 1), (13, 3, 2), (14, 4, 0), (15, 4, 1)], "leaf positions are corrupted after the scanners backtracked ({:?}, memoization: {})", each_engine, each_enable_memoization);
+        }
+    }
+
+    assert_parse_fails(r##"[Main]{
+        Main <- SCAN<"unknown"> "\z"#,
+    }"##, "", "unknown scanner name 'unknown'");
+}
+
+// spec: with_ast_reflection_style() が AST 反映方式のみを置き換え、UUID・子要素・一致範囲を保持することを確認する
+#[test]
+fn node_reflection_style_replacement() {
+    let tree = parse_tree("[Main]{\n    Main <- Let \"\\z\"#,\n    Let <- \"let\" Space# Id Space# \"=\" Space# Num \";\"#,\n    Id <- JOIN<[a-z]+>,\n    Num <- JOIN<[0-9]+>,\n    Space <- \" \"+,\n}", ".Main.Main", "let x = 42;", ParseConfig::default());
+
+    let root_node = match tree.get_child_ref().try_get_node() {
+        Ok(v) => v,
+        Err(_) => panic!("root of the tree is not a node:\n{}", tree.to_sexp(false)),
+    };
+
+    let let_node = root_node.child_named("Let");
+    let renamed_node = let_node.clone().with_ast_reflection_style(ASTReflectionStyle::reflection("Binding".to_string()));
+
+    assert!(renamed_node.ast_reflection_style.is_reflected_as("Binding"), "reflection style was not replaced: {}", renamed_node.ast_reflection_style);
+    assert_eq!(renamed_node.uuid, let_node.uuid, "UUID was not preserved");
+    assert_eq!(renamed_node.matched_range(), let_node.matched_range(), "matched range was not preserved");
+    assert_eq!(renamed_node.is_presence_marker, let_node.is_presence_marker);
+    assert_eq!(renamed_node.total_text_length(), let_node.total_text_length());
+
+    let to_child_sexp = |node: &SyntaxNode| node.sub_elems.iter().map(|each_elem| SyntaxTree::from_node(each_elem.clone()).to_sexp(false)).collect::<Vec<String>>();
+    assert_eq!(to_child_sexp(&renamed_node), to_child_sexp(let_node), "children were not preserved");
+
+    let expanded_node = renamed_node.with_ast_reflection_style(ASTReflectionStyle::expansion());
+    assert!(expanded_node.ast_reflection_style.is_expandable(), "expansion style was not applied: {}", expanded_node.ast_reflection_style);
+    assert!(let_node.ast_reflection_style.is_reflected_as("Let"), "original node was modified: {}", let_node.ast_reflection_style);
+}
+
+// spec: SyntaxNode::serialize_compact() の出力を deserialize_compact() で復元した要素が元の要素と一致し、不正なバイト列の読み込みが位置を含むエラーとなることを確認する
+#[test]
+fn compact_serialization() {
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, "[Main]{\n    Main <- Item (\",\"# Item)*## \"\\z\"#,\n    Item <- \"[\"# ([a-z]*)?~#Body \"]\"#,\n}");
+    let byte_rule_map = load_rule_map(&cons, "[Main]{\n    Main <- [a-z\\x80-\\xff]+ \"\\z\"#,\n}");
+
+    let mut parsers = vec![
+        SyntaxParser::new(cons.clone(), rule_map, TEST_INPUT_FILE_PATH.to_string(), Box::new("[ab],[]".to_string()), true),
+        SyntaxParser::new_bytes(cons.clone(), byte_rule_map, TEST_INPUT_FILE_PATH.to_string(), "abé".as_bytes().to_vec(), true),
+    ];
+
+    for each_parser in &mut parsers {
+        let tree = match each_parser.run() {
+            Ok(v) => v,
+            Err(()) => {
+                cons.borrow().print_all();
+                panic!("failed to parse the input");
+            },
+        };
+
+        let node = match tree.get_child_ref().try_get_node() {
+            Ok(v) => v,
+            Err(_) => panic!("root of the tree is not a node:\n{}", tree.to_sexp(false)),
+        };
+
+        let bytes = node.serialize_compact();
+        assert_eq!(bytes[0], COMPACT_FORMAT_VERSION);
+
+        let elem = match SyntaxNode::deserialize_compact(&bytes) {
+            Ok(v) => v,
+            Err(e) => panic!("failed to deserialize the tree: {} at {}\ntree:\t{}", e, e.get_index(), tree.to_sexp(false)),
+        };
+
+        // note: 出力はすべてのフィールドを含むため、再度書き込んだバイト列が一致すれば復元された要素も一致する
+        let deserialized_node = elem.try_get_node().unwrap();
+        assert_eq!(deserialized_node.serialize_compact(), bytes, "re-serialized bytes differ:\n{}", tree.to_sexp(false));
+        assert_eq!(elem.to_sexp(false), tree.get_child_ref().to_sexp(false));
+        assert_eq!(SyntaxTree::from_node(elem.clone()).compute_hash(), tree.compute_hash());
+        assert_eq!((deserialized_node.uuid, deserialized_node.src_range), (node.uuid, node.src_range));
+
+        let to_leaf_fields = |tree: &SyntaxTree| tree.to_token_stream(true).iter().map(|each_leaf| (each_leaf.uuid, each_leaf.pos.to_string(), each_leaf.get_bytes().to_vec())).collect::<Vec<(uuid::Uuid, String, Vec<u8>)>>();
+        assert_eq!(to_leaf_fields(&SyntaxTree::from_node(elem)), to_leaf_fields(&tree));
+
+        for each_len in 0..bytes.len() {
+            assert_eq!(SyntaxNode::deserialize_compact(&bytes[..each_len]).err(), Some(DeserializeError::UnexpectedEnd { index: each_len }), "truncated bytes are deserialized (length: {})", each_len);
+        }
+
+        let mut trailing_bytes = bytes.clone();
+        trailing_bytes.push(0);
+        assert_eq!(SyntaxNode::deserialize_compact(&trailing_bytes).err(), Some(DeserializeError::TrailingBytes { index: bytes.len() }));
+    }
+
+    // note: 手で組み立てたノードのバイト列は 版 (0), タグ (1), 内容の長さ (2..6), 反映方式 (6), 反映名の有無 (7) と続く
+    let leaf = SyntaxNodeElement::from_leaf_args(CharacterPosition::get_empty(), "é".to_string(), ASTReflectionStyle::reflection(String::new()));
+    let bytes = SyntaxNode::new(vec![leaf], ASTReflectionStyle::reflection("Node".to_string()), uuid::Uuid::new_v4()).serialize_compact();
+
+    let corrupt = |index: usize, byte: u8| {
+        let mut corrupted_bytes = bytes.clone();
+        corrupted_bytes[index] = byte;
+        SyntaxNode::deserialize_compact(&corrupted_bytes).err()
+    };
+
+    assert_eq!(corrupt(0, COMPACT_FORMAT_VERSION + 1), Some(DeserializeError::UnsupportedVersion { index: 0, version: COMPACT_FORMAT_VERSION + 1 }));
+    assert_eq!(corrupt(1, 9), Some(DeserializeError::UnknownTag { index: 1, tag: 9 }));
+    assert_eq!(corrupt(6, 3), Some(DeserializeError::UnexpectedByte { index: 6, found: 3 }));
+
+    let content_len = bytes.len() - 6;
+    assert_eq!(corrupt(2, (content_len - 1) as u8), Some(DeserializeError::ElementLengthMismatch { index: 1, expected_len: content_len - 1, actual_len: content_len }));
+
+    // note: リーフの値の直前の 1 バイトは値の長さ
+    let value_i = bytes.windows(2).position(|each_bytes| each_bytes == "é".as_bytes()).unwrap();
+    assert_eq!(corrupt(value_i, 0xff), Some(DeserializeError::InvalidUtf8 { index: value_i - 1 }));
+
+    // note: リーフの UUID (8..24) とファイルパスの有無 (24) に続く位置のインデックス (25) が u64 に収まらない
+    let mut overflowed_bytes = vec![COMPACT_FORMAT_VERSION, 1, 0, 0, 0, 0, 0, 0];
+    overflowed_bytes.extend_from_slice(&[0; 17]);
+    overflowed_bytes.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]);
+    assert_eq!(SyntaxNode::deserialize_compact(&overflowed_bytes).err(), Some(DeserializeError::InvalidVarint { index: 25 }));
+}
+
+// spec: ParseConfig::with_coalesced_leaves() で文字クラス・文字列の繰り返しのリーフが位置を保ったまままとめられ、既定では構文木が変わらないことを確認する
+#[test]
+fn coalesced_leaves() {
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, "[Main]{\n    Main <- Word (\"\\n\"# Word)*## \"\\n\"# Tag \"\\z\"#,\n    Word <- [a-z]+ \"_\"+# &[a-z]+ [a-z]+,\n    Tag <- \"xy\"+,\n}");
+    let input = "ab__cd\nef_g\nxyxy";
+
+    let parse = |config: ParseConfig| {
+        let enable_memoization = config.enable_memoization;
+        let mut parser = SyntaxParser::new(cons.clone(), rule_map.clone(), TEST_INPUT_FILE_PATH.to_string(), Box::new(input.to_string()), enable_memoization).with_config(&config);
+
+        return match parser.run() {
+            Ok(tree) => {
+                assert_tree_invariants(&tree);
+                assert_leaf_positions(&parser, &tree);
+                tree
+            },
+            Err(()) => {
+                cons.borrow().print_all();
+                panic!("failed to parse the input (memoization: {})", enable_memoization);
+            },
+        };
+    };
+
+    // ret: (インデックス, 行, 列, 値) の一覧
+    let to_tokens = |tree: &SyntaxTree| tree.to_token_stream(true).iter().map(|each_leaf| (each_leaf.pos.index, each_leaf.pos.line, each_leaf.pos.column, each_leaf.value.clone())).collect::<Vec<(usize, usize, usize, String)>>();
+    let to_expected = |tokens: Vec<(usize, usize, usize, &str)>| tokens.iter().map(|(index, line, column, value)| (*index, *line, *column, value.to_string())).collect::<Vec<(usize, usize, usize, String)>>();
+
+    // note: 非表示のリーフ同士はまとめられるが、表示されるリーフとはまとめられない; 先読みはリーフを追加しない
+    let expected_tokens = to_expected(vec![
+        (0, 0, 0, "ab"), (2, 0, 2, "__"), (4, 0, 4, "cd"), (6, 0, 6, "\n"),
+        (7, 1, 0, "ef"), (9, 1, 2, "_"), (10, 1, 3, "g"), (11, 1, 4, "\n"),
+        (12, 2, 0, "xyxy"), (16, 2, 4, "\0"),
+    ]);
+
+    let default_sexp = parse(ParseConfig::default()).to_sexp(true);
+    assert_eq!(default_sexp, "(Main (Word \"a\" \"b\" \"c\" \"d\") (Word \"e\" \"f\" \"g\") (Tag \"xy\" \"xy\"))", "leaves are coalesced by default");
+
+    for enable_memoization in vec![true, false] {
+        let config = ParseConfig::default().with_memoization(enable_memoization);
+        assert_eq!(parse(config.clone().with_coalesced_leaves(false)).to_sexp(true), default_sexp, "tree differs from the default one (memoization: {})", enable_memoization);
+
+        let tree = parse(config.with_coalesced_leaves(true));
+        assert_eq!(tree.to_sexp(true), "(Main (Word \"ab\" \"cd\") (Word \"ef\" \"g\") (Tag \"xyxy\"))", "unexpected coalesced tree (memoization: {})", enable_memoization);
+        assert_eq!(to_tokens(&tree), expected_tokens, "unexpected coalesced token stream (memoization: {})\ntree:\t{}", enable_memoization, tree.to_sexp(false));
+    }
+
+    // note: バイト列モードではまとめたリーフの元のバイト列も連結されるため、複数バイトの文字がリーフをまたがずに復元される
+    let byte_rule_map = load_rule_map(&cons, "[Main]{\n    Main <- [a-z\\x80-\\xff]+ \"_\"# [a-z]+ \"\\z\"#,\n}");
+    let mut parser = SyntaxParser::new_bytes(cons.clone(), byte_rule_map, TEST_INPUT_FILE_PATH.to_string(), "abé_c".as_bytes().to_vec(), true).with_config(&ParseConfig::default().with_coalesced_leaves(true));
+
+    let tree = match parser.run() {
+        Ok(v) => v,
+        Err(()) => {
+            cons.borrow().print_all();
+            panic!("failed to parse the input bytes");
+        },
+    };
+
+    let byte_tokens = tree.to_token_stream(false).iter().map(|each_leaf| (each_leaf.pos.index, each_leaf.value.clone(), each_leaf.get_bytes().to_vec())).collect::<Vec<(usize, String, Vec<u8>)>>();
+    assert_eq!(byte_tokens, vec![(0, "abé".to_string(), "abé".as_bytes().to_vec()), (5, "c".to_string(), b"c".to_vec())], "unexpected coalesced leaves in byte mode:\n{}", tree.to_sexp(false));
+}
+
+// spec: SyntaxTree::write_with_source() が print_to_string() と同じ木構造の各行の右側に、列を揃えて範囲とノードが覆うソース文字列を付記することを確認する
+// note: 複数行にわたるノードのソース文字列は先頭行のみを表示して "…" で省略する
+#[test]
+fn source_annotated_print() {
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, "[Main]{\n    Main <- Pair (\"\\n\"# Pair)*## \"\\z\"#,\n    Pair <- JOIN<[a-z]+> \"=\"# JOIN<[0-9]+>,\n}");
+    let input = "ab=12\ncd=3";
+    let mut parser = SyntaxParser::new(cons.clone(), rule_map, TEST_INPUT_FILE_PATH.to_string(), Box::new(input.to_string()), true);
+
+    let tree = match parser.run() {
+        Ok(v) => v,
+        Err(()) => {
+            cons.borrow().print_all();
+            panic!("failed to parse the input");
+        },
+    };
+
+    let mut buf = Vec::<u8>::new();
+    tree.write_with_source(&mut buf, input, true).unwrap();
+    let annotated_str = String::from_utf8(buf).unwrap();
+
+    let printed_str = tree.print_to_string(true);
+    let printed_lines = printed_str.lines().collect::<Vec<&str>>();
+    let annotated_lines = annotated_str.lines().collect::<Vec<&str>>();
+    assert_eq!(annotated_lines.len(), printed_lines.len(), "line count differs from print_to_string()\nprinted:\n{}\nannotated:\n{}", printed_str, annotated_str);
+
+    let label_width = printed_lines.iter().map(|each_line| each_line.chars().count()).max().unwrap();
+    let mut annotations = Vec::<String>::new();
+
+    for (each_printed_line, each_annotated_line) in printed_lines.iter().zip(&annotated_lines) {
+        assert!(each_annotated_line.starts_with(each_printed_line), "annotated line does not start with the printed line\nprinted:\t{}\nannotated:\t{}", each_printed_line, each_annotated_line);
+
+        // note: 注記はすべての行で最長の行の 2 文字後から始まる
+        let annotation = each_annotated_line.chars().skip(label_width + 2).collect::<String>();
+        assert_eq!(each_annotated_line.chars().take(label_width + 2).collect::<String>().trim_end(), *each_printed_line, "annotation is not aligned:\n{}", annotated_str);
+        annotations.push(annotation);
+    }
+
+    let expected_annotations = vec![
+        "1:1..2:6  \"ab=12…\"",
+        "1:1..1:6  \"ab=12\"",
+        "1:1..1:3",
+        "1:4..1:6",
+        "2:1..2:5  \"cd=3\"",
+        "2:1..2:3",
+        "2:4..2:5",
+    ];
+
+    assert_eq!(annotations, expected_annotations, "unexpected annotations:\n{}", annotated_str);
+    cons.borrow_mut().clear_log();
+}
+
+// spec: パターンのワイルドカード・省略可能な子パターン・束縛名による照合と、束縛名の重複や構文エラーの位置を確認する
+#[test]
+fn node_patterns() {
+    let grammar_src = r##"[Main]{
+        Main <- Func+ "\z"#,
+        Func <- "fn "# Name Params? ";"#,
+        Name <- JOIN<[a-z]+>,
+        Params <- "("# Name (","# Name)*## ")"#,
+    }"##;
+
+    let tree = parse_tree(grammar_src, ".Main.Main", "fn ab(x,y);fn cd;", ParseConfig::default());
+
+    let root = match tree.get_child_ref() {
+        SyntaxNodeElement::Node(node) => node,
+        SyntaxNodeElement::Leaf(_) => panic!("root is not a node:\n{}", tree.to_sexp(false)),
+    };
+
+    let to_pattern = |s: &str| match parse_node_pattern(s) {
+        Ok(v) => v,
+        Err(e) => panic!("failed to parse pattern '{}': {}", s, e),
+    };
+
+    assert!(root.matches_pattern(&to_pattern("Main { Func, Func }")));
+    assert!(!root.matches_pattern(&to_pattern("Main { Func, Func, Func }")), "one child node matched two child patterns");
+    assert!(!root.matches_pattern(&to_pattern("Func")));
+
+    // note: ワイルドカードが先に割り当てた子ノードを後戻りして後続の子パターンに割り当てる
+    let bindings = root.match_pattern(&to_pattern("Main { *, Func { n: Name, Params } }")).expect("pattern with a leading wildcard did not match");
+    assert_eq!(bindings["n"].join_child_leaf_values(), "ab");
+
+    let bindings = root.match_pattern(&to_pattern("Main { first: Func, second: Func }")).expect("pattern with bindings did not match");
+    let (first_func, second_func) = (bindings["first"], bindings["second"]);
+    assert!(first_func.matches_pattern(&to_pattern("* { Name }")), "wildcard did not match a named node");
+    assert!(!second_func.matches_pattern(&to_pattern("Func { Params }")));
+
+    // note: 一致しなかった省略可能な子パターンの束縛名は含まれない
+    let optional_pattern = to_pattern("Func { n: Name, p: Params? }");
+    let first_bindings = first_func.match_pattern(&optional_pattern).expect("optional child pattern did not match");
+    let second_bindings = second_func.match_pattern(&optional_pattern).expect("missing optional child was rejected");
+    assert_eq!(first_bindings["p"].get_reflectable_children().len(), 2);
+    assert_eq!(second_bindings["n"].join_child_leaf_values(), "cd");
+    assert!(!second_bindings.contains_key("p"), "binding of an unmatched optional child pattern exists");
+
+    assert_eq!(to_pattern(" Main{a:Func{Name},b:*?,} ").to_string(), "Main { a: Func { Name }, b: *? }");
+
+    let expected_errors = vec![
+        ("Main { a: Func, a: Func }", PatternParseError::DuplicateBindingName { index: 16, name: "a".to_string() }),
+        ("Main { a: Func { a: Name } }", PatternParseError::DuplicateBindingName { index: 17, name: "a".to_string() }),
+        ("Main { Func Name }", PatternParseError::UnexpectedCharacter { index: 12, found: 'N', expected: "',' or '}'".to_string() }),
+        ("Main { $ }", PatternParseError::UnexpectedCharacter { index: 7, found: '$', expected: "node name or '*'".to_string() }),
+        ("Main }", PatternParseError::UnexpectedCharacter { index: 5, found: '}', expected: "end of pattern".to_string() }),
+        ("Main { Func,", PatternParseError::UnexpectedEnd { expected: "node name or '*'".to_string() }),
+    ];
+
+    for (each_pattern_str, each_expected_error) in expected_errors {
+        assert_eq!(parse_node_pattern(each_pattern_str), Err(each_expected_error), "unexpected result of pattern '{}'", each_pattern_str);
+    }
+
+    assert_eq!(PatternParseError::DuplicateBindingName { index: 0, name: "a".to_string() }.to_string(), "duplicate binding name 'a'");
+}
+
+#[test]
+fn leaf_unescape() {
+    let leaf = |value: &str| SyntaxLeaf::new(CharacterPosition::new(Some(TEST_INPUT_FILE_PATH.to_string()), 0, 0, 0), value.to_string(), ASTReflectionStyle::reflection(String::new()), uuid::Uuid::new_v4());
+
+    let cases = vec![
+        (r"abc", "abc"),
+        (r"a\nb", "a\nb"),
+        (r"\t\r\0", "\t\r\0"),
+        (r#"\\\'\""#, "\\'\""),
+        (r"\x41\xFF", "A\u{FF}"),
+        (r"\u3042", "\u{3042}"),
+        (r"\u{1F600}", "\u{1F600}"),
+        (r"x\uD83D\uDE00y", "x\u{1F600}y"),
+    ];
+
+    for (value, expected) in cases {
+        assert_eq!(leaf(value).unescape(), Ok(expected.to_string()), "value: {}", value);
+    }
+
+    let error_cases = vec![
+        (r"ab\", UnescapeError::UnexpectedEnd { index: 2 }),
+        (r"a\q", UnescapeError::UnknownEscapeCharacter { index: 1, found: 'q' }),
+        (r"\x4G", UnescapeError::InvalidHexDigit { index: 0, found: 'G' }),
+        (r"a\x4", UnescapeError::UnexpectedEnd { index: 1 }),
+        (r"\u{}", UnescapeError::InvalidHexDigit { index: 0, found: '}' }),
+        (r"ab\u{41", UnescapeError::UnexpectedEnd { index: 2 }),
+        (r"\u{110000}", UnescapeError::InvalidCodePoint { index: 0, code_point: 0x110000 }),
+        // note: 対になる下位サロゲートがない上位サロゲート
+        (r"a\uD83D", UnescapeError::InvalidCodePoint { index: 1, code_point: 0xD83D }),
+        (r"\uD83D\u0041", UnescapeError::InvalidCodePoint { index: 0, code_point: 0xD83D }),
+        (r"\uD83D\u{DE00}", UnescapeError::InvalidCodePoint { index: 0, code_point: 0xD83D }),
+        // note: 単独の下位サロゲート
+        (r"\uDE00", UnescapeError::InvalidCodePoint { index: 0, code_point: 0xDE00 }),
+    ];
+
+    for (value, expected) in error_cases {
+        let result = leaf(value).unescape();
+        assert_eq!(result, Err(expected.clone()), "value: {}", value);
+        assert_eq!(result.unwrap_err().get_index(), expected.get_index(), "value: {}", value);
+    }
+}
+
+// spec: SyntaxNode::path_to() が自身から対象の親ノードまでのノード列を返し、非反映的なリーフも探索して、子孫でない要素には None を返すことを確認する
+#[test]
+fn node_paths() {
+    let tree = parse_tree("[Main]{\n    Main <- Pair+ \"\\z\"#,\n    Pair <- Key \"=\"# Value \";\"#,\n    Key <- [a-z],\n    Value <- [0-9],\n}", ".Main.Main", "a=1;b=2;", ParseConfig::default());
+
+    let root_node = match tree.get_child_ref().try_get_node() {
+        Ok(v) => v,
+        Err(_) => panic!("root of the tree is not a node:\n{}", tree.to_sexp(false)),
+    };
+
+    let to_uuids = |path: Option<Vec<&SyntaxNode>>| path.map(|v| v.iter().map(|each_node| each_node.uuid).collect::<Vec<uuid::Uuid>>());
+
+    let first_pair_node = root_node.get_child_at_checked(0).unwrap().try_get_node().unwrap();
+    let second_pair_node = root_node.get_child_at_checked(1).unwrap().try_get_node().unwrap();
+    let value_node = second_pair_node.child_named("Value");
+    let value_leaf = value_node.get_child_at_checked(0).unwrap().try_get_leaf().unwrap();
+
+    let hidden_leaf = match first_pair_node.sub_elems.iter().find(|each_elem| !each_elem.is_reflectable()) {
+        Some(SyntaxNodeElement::Leaf(leaf)) => leaf,
+        _ => panic!("pair has no non-reflectable leaf:\n{}", tree.to_sexp(false)),
+    };
+
+    assert_eq!(to_uuids(root_node.path_to(&root_node.uuid)), Some(Vec::new()), "path to the node itself is not empty");
+    assert_eq!(to_uuids(root_node.path_to(&value_node.uuid)), Some(vec![root_node.uuid, second_pair_node.uuid]), "unexpected path to a node:\n{}", tree.to_sexp(false));
+    assert_eq!(to_uuids(root_node.path_to(&value_leaf.uuid)), Some(vec![root_node.uuid, second_pair_node.uuid, value_node.uuid]), "unexpected path to a leaf:\n{}", tree.to_sexp(false));
+    assert_eq!(to_uuids(root_node.path_to(&hidden_leaf.uuid)), Some(vec![root_node.uuid, first_pair_node.uuid]), "unexpected path to a non-reflectable leaf:\n{}", tree.to_sexp(false));
+    assert_eq!(to_uuids(value_node.path_to(&first_pair_node.uuid)), None, "path to a non-descendant node is found");
+    assert_eq!(to_uuids(root_node.path_to(&uuid::Uuid::new_v4())), None, "path to an unknown element is found");
+}
+
+// spec: SyntaxTree::coverage() が反映されるリーフの値の合計バイト数を入力のバイト数で割った値を返し、入力長が 0 であれば 1.0 を返すことを確認する
+#[test]
+fn tree_coverages() {
+    let grammar_src = "[Main]{\n    Main <- Word (Space# Word)*## \"\\z\"#,\n    Word <- JOIN<[a-zé]+>,\n    Space <- \" \"+,\n}";
+
+    // note: (入力, 反映されるリーフの値の合計バイト数); é は UTF-8 で 2 バイト
+    let cases = vec![
+        ("ab  cd", 4),
+        ("éb cd", 5),
+        ("abc", 3),
+    ];
+
+    for (each_input, each_reflected_len) in cases {
+        let tree = parse_tree(grammar_src, ".Main.Main", each_input, ParseConfig::default());
+
+        let expected = each_reflected_len as f64 / each_input.len() as f64;
+        assert_eq!(tree.coverage(each_input.len()), expected, "unexpected coverage of '{}':\n{}", each_input, tree.to_sexp(false));
+        assert_eq!(tree.coverage(0), 1.0, "coverage of empty input is not 1.0");
+    }
+}
+
+// spec: iter_reflectable()・reflectable_len()・last_child()・get_child_at_checked() が非反映的な子要素を除いて子要素を返し、filter_children() が条件を満たす子要素を返すことを確認する
+#[test]
+fn console_free_child_accessors() {
+    let tree = parse_tree("[Main]{\n    Main <- Let \"\\z\"#,\n    Let <- \"let\" Space# Id Space# \"=\" Space# Num \";\"#,\n    Id <- JOIN<[a-z]+>,\n    Num <- JOIN<[0-9]+>,\n    Space <- \" \"+,\n}", ".Main.Main", "let x = 42;", ParseConfig::default());
+
+    let root_node = match tree.get_child_ref().try_get_node() {
+        Ok(v) => v,
+        Err(_) => panic!("root of the tree is not a node:\n{}", tree.to_sexp(false)),
+    };
+
+    let let_node = root_node.child_named("Let");
+    let to_sexps = |elems: Vec<&SyntaxNodeElement>| elems.iter().map(|each_elem| each_elem.to_sexp(true).unwrap()).collect::<Vec<String>>();
+    let to_sexp = |elem: Option<&SyntaxNodeElement>| elem.map(|v| v.to_sexp(true).unwrap());
+
+    let reflectable_sexps = to_sexps(let_node.iter_reflectable().collect::<Vec<&SyntaxNodeElement>>());
+    assert_eq!(reflectable_sexps, vec!["\"let\"", "(Id \"x\")", "\"=\"", "(Num \"42\")"], "unexpected reflectable children:\n{}", tree.to_sexp(false));
+    assert_eq!(let_node.reflectable_len(), 4);
+    assert_eq!(to_sexp(let_node.last_child()), Some("(Num \"42\")".to_string()), "last child is not the last reflectable one");
+
+    assert_eq!(to_sexp(let_node.get_child_at_checked(1)), Some("(Id \"x\")".to_string()));
+    assert_eq!(to_sexp(let_node.get_child_at_checked(-1)), Some("(Num \"42\")".to_string()));
+    assert_eq!(to_sexp(let_node.get_child_at_checked(-4)), Some("\"let\"".to_string()));
+    assert_eq!(to_sexp(let_node.get_child_at_checked(4)), None, "child is found out of range");
+    assert_eq!(to_sexp(let_node.get_child_at_checked(-5)), None, "child is found out of range from the end");
+
+    let hidden_children = let_node.filter_children(|each_elem| !each_elem.is_reflectable());
+    assert_eq!(hidden_children.len(), let_node.sub_elems.len() - 4, "unexpected non-reflectable children:\n{}", tree.to_sexp(false));
+    assert_eq!(to_sexps(let_node.filter_children(|each_elem| each_elem.is_node() && each_elem.is_reflectable())), vec!["(Id \"x\")", "(Num \"42\")"]);
+}
+
+// spec: child_count() が Reflectable な子要素の数を返し、is_leaf_only() が Reflectable な子要素がすべてリーフであるか子要素がない場合に真となることを確認する
+#[test]
+fn leaf_only_nodes() {
+    // note: (説明, ノード, 子要素の数, リーフのみであるか)
+    let cases = vec![
+        ("empty", test_node(Vec::new(), ASTReflectionStyle::reflection("Main".to_string()), uuid::Uuid::new_v4()), 0, true),
+        ("leaves", test_node(vec![test_leaf("a", 0, ASTReflectionStyle::reflection(String::new()), uuid::Uuid::new_v4()), test_leaf("b", 1, ASTReflectionStyle::reflection(String::new()), uuid::Uuid::new_v4())], ASTReflectionStyle::reflection("Main".to_string()), uuid::Uuid::new_v4()), 2, true),
+        ("hidden leaf", test_node(vec![test_leaf("a", 0, ASTReflectionStyle::reflection(String::new()), uuid::Uuid::new_v4()), test_leaf(" ", 1, ASTReflectionStyle::no_reflection(), uuid::Uuid::new_v4())], ASTReflectionStyle::reflection("Main".to_string()), uuid::Uuid::new_v4()), 1, true),
+        ("hidden node", test_node(vec![test_leaf("a", 0, ASTReflectionStyle::reflection(String::new()), uuid::Uuid::new_v4()), test_leaf_node(ASTReflectionStyle::no_reflection(), uuid::Uuid::new_v4())], ASTReflectionStyle::reflection("Main".to_string()), uuid::Uuid::new_v4()), 1, true),
+        ("reflectable node", test_node(vec![test_leaf("a", 0, ASTReflectionStyle::reflection(String::new()), uuid::Uuid::new_v4()), test_leaf_node(ASTReflectionStyle::reflection("Sub".to_string()), uuid::Uuid::new_v4())], ASTReflectionStyle::reflection("Main".to_string()), uuid::Uuid::new_v4()), 2, false),
+    ];
+
+    for (each_desc, each_elem, each_child_count, each_is_leaf_only) in cases {
+        let each_node = each_elem.try_get_node().unwrap();
+        assert_eq!(each_node.child_count(), each_child_count, "unexpected child count ({})", each_desc);
+        assert_eq!(each_node.is_leaf_only(), each_is_leaf_only, "unexpected leaf-only flag ({})", each_desc);
+    }
+}
+
+// note: 書き込みが常に失敗するライタ
+struct FailingWriter;
+
+impl std::io::Write for FailingWriter {
+    fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, "failing writer"));
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return Ok(());
+    }
+}
+
+// spec: print_to_string() が 1 要素 1 行の木構造を返し、print_to_writer() が同じ内容を書き込んで書き込みのエラーを返すことを確認する
+#[test]
+fn tree_prints() {
+    let uuids = (0..5).map(|_| uuid::Uuid::new_v4()).collect::<Vec<uuid::Uuid>>();
+    let short_uuids = uuids.iter().map(|each_uuid| each_uuid.to_string()[..8].to_string()).collect::<Vec<String>>();
+
+    let word_node = SyntaxNodeElement::Node(Box::new(SyntaxNode::new(vec![test_leaf("b\n", 2, ASTReflectionStyle::reflection(String::new()), uuids[4])], ASTReflectionStyle::reflection("Word".to_string()), uuids[3])));
+    let root_sub_elems = vec![
+        test_leaf("a", 0, ASTReflectionStyle::reflection(String::new()), uuids[1]),
+        test_leaf(" ", 1, ASTReflectionStyle::no_reflection(), uuids[2]),
+        word_node,
+    ];
+    let tree = SyntaxTree::from_node(SyntaxNodeElement::Node(Box::new(SyntaxNode::new(root_sub_elems, ASTReflectionStyle::reflection("Main".to_string()), uuids[0]))));
+
+    let expected_lines = vec![
+        format!("| Main *{}", short_uuids[0]),
+        format!("|   |- \"a\" 1:1 () *{}", short_uuids[1]),
+        format!("|   |- \" \" 1:2 [hidden] *{}", short_uuids[2]),
+        format!("|   | Word *{}", short_uuids[3]),
+        format!("|   |   |- \"b\\n\" 1:3 () *{}", short_uuids[4]),
+    ];
+
+    let to_expected = |line_indexes: Vec<usize>| line_indexes.iter().map(|each_i| format!("{}\n", expected_lines[*each_i])).collect::<String>();
+    assert_eq!(tree.print_to_string(false), to_expected(vec![0, 1, 2, 3, 4]));
+    assert_eq!(tree.print_to_string(true), to_expected(vec![0, 1, 3, 4]), "hidden leaf is printed");
+
+    for ignore_hidden_elems in vec![true, false] {
+        let mut buf = Vec::<u8>::new();
+        tree.print_to_writer(&mut buf, ignore_hidden_elems).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), tree.print_to_string(ignore_hidden_elems), "written tree differs from print_to_string() (ignore_hidden_elems: {})", ignore_hidden_elems);
+        assert!(tree.print_to_writer(&mut FailingWriter, ignore_hidden_elems).is_err(), "write error is not returned (ignore_hidden_elems: {})", ignore_hidden_elems);
+    }
+}
+
+// spec: try_ で始まる取得がコンソールを受け取らずに結果を返し、失敗時は要素の UUID と入力のパスを持つ TreeLog を返すことを確認する
+#[test]
+fn tree_access_logs() {
+    let uuids = (0..5).map(|_| uuid::Uuid::new_v4()).collect::<Vec<uuid::Uuid>>();
+    let src_path = Some(TEST_INPUT_FILE_PATH.to_string());
+
+    let sub_node = SyntaxNodeElement::Node(Box::new(SyntaxNode::new(vec![test_leaf("b", 1, ASTReflectionStyle::reflection(String::new()), uuids[2])], ASTReflectionStyle::reflection("Sub".to_string()), uuids[1])));
+    let root_sub_elems = vec![
+        test_leaf("a", 0, ASTReflectionStyle::reflection(String::new()), uuids[3]),
+        sub_node,
+        test_leaf(";", 2, ASTReflectionStyle::no_reflection(), uuids[4]),
+    ];
+    let root = SyntaxNodeElement::Node(Box::new(SyntaxNode::new(root_sub_elems, ASTReflectionStyle::reflection("Main".to_string()), uuids[0])));
+
+    let root_node = match root.try_get_node() {
+        Ok(v) => v,
+        Err(e) => panic!("root is not a node: {:?}", e),
+    };
+
+    assert_eq!(root_node.uuid, uuids[0]);
+    assert_eq!(root.try_get_leaf().map(|v| v.uuid), Err(TreeLog::ElementNotLeaf { uuid: uuids[0], src_path: src_path.clone() }));
+
+    let first_elem = root_node.try_get_child_at(0).unwrap();
+    assert_eq!(first_elem.try_get_leaf().map(|v| v.uuid), Ok(uuids[3]));
+    assert_eq!(first_elem.try_get_node().map(|v| v.uuid), Err(TreeLog::ElementNotNode { uuid: uuids[3], src_path: src_path.clone() }));
+
+    // note: 非反映的なリーフはインデックスに含まれない
+    assert_eq!(root_node.try_get_child_at(2).map(|_| ()), Err(TreeLog::ReflectableChildNotFound { parent_uuid: uuids[0], index: 2, src_path: src_path.clone() }));
+    assert_eq!(root_node.try_get_child_at(-1).map(|v| v.is_node()), Ok(true));
+
+    assert_eq!(root_node.try_get_node_child_at(1).map(|v| v.uuid), Ok(uuids[1]));
+    assert_eq!(root_node.try_get_node_child_at(0).map(|v| v.uuid), Err(TreeLog::ElementNotNode { uuid: uuids[3], src_path: src_path.clone() }));
+    assert_eq!(root_node.try_get_leaf_child_at(0).map(|v| v.uuid), Ok(uuids[3]));
+    assert_eq!(root_node.try_get_leaf_child_at(1).map(|v| v.uuid), Err(TreeLog::ElementNotLeaf { uuid: uuids[1], src_path: src_path.clone() }));
+
+    assert_eq!(root_node.try_get_position().map(|v| v.index), Ok(0));
+
+    // note: リーフを持たないノードは入力のパスを特定できない
+    let empty_node = SyntaxNode::new(Vec::new(), ASTReflectionStyle::reflection("Empty".to_string()), uuids[4]);
+    assert_eq!(empty_node.try_get_position().map(|v| v.index), Err(TreeLog::CharacterPositionNotFound { uuid: uuids[4], src_path: None }));
+    assert_eq!(empty_node.try_get_child_at(0).map(|_| ()), Err(TreeLog::ReflectableChildNotFound { parent_uuid: uuids[4], index: 0, src_path: None }));
+}
+
+// spec: fold() がルートを含むすべての要素 (非反映的な要素を含む) を TraversalOrder の順に訪れ、累積値を返すことを確認する
+#[test]
+fn tree_folds() {
+    let sub_node = test_node(vec![test_leaf("b", 1, ASTReflectionStyle::reflection(String::new()), uuid::Uuid::new_v4())], ASTReflectionStyle::reflection("Sub".to_string()), uuid::Uuid::new_v4());
+    let tree = SyntaxTree::from_node_args(vec![test_leaf("a", 0, ASTReflectionStyle::reflection(String::new()), uuid::Uuid::new_v4()), sub_node, test_leaf(";", 2, ASTReflectionStyle::no_reflection(), uuid::Uuid::new_v4())], ASTReflectionStyle::reflection("Main".to_string()));
+
+    // note: ノードは反映名、リーフは値を訪れた順に並べる
+    let push_label = |mut labels: Vec<String>, elem: &SyntaxNodeElement| {
+        let label = match elem {
+            SyntaxNodeElement::Node(node) => node.ast_reflection_style.get_name().to_string(),
+            SyntaxNodeElement::Leaf(leaf) => leaf.value.clone(),
+        };
+
+        labels.push(label);
+        return labels;
+    };
+
+    assert_eq!(tree.fold(Vec::new(), TraversalOrder::PreOrder, push_label), vec!["Main", "a", "Sub", "b", ";"]);
+    assert_eq!(tree.fold(Vec::new(), TraversalOrder::PostOrder, push_label), vec!["a", "b", "Sub", ";", "Main"]);
+    assert_eq!(tree.fold(0, TraversalOrder::PreOrder, |leaf_count, elem| if elem.is_node() { leaf_count } else { leaf_count + 1 }), 3, "non-reflectable leaf is not visited");
+
+    let sub_elem = tree.get_child_ref().try_get_node().unwrap().try_get_child_at(1).unwrap();
+    assert_eq!(sub_elem.fold(Vec::new(), TraversalOrder::PostOrder, &push_label), vec!["b", "Sub"]);
+}
+
+// spec: named_children_map() が Reflectable な子要素を反映名ごとに出現順でまとめ、反映名のない子要素を空文字列のキーとし、非反映的な子要素を含めないことを確認する
+#[test]
+fn named_children_maps() {
+    let uuids = (0..6).map(|_| uuid::Uuid::new_v4()).collect::<Vec<uuid::Uuid>>();
+
+    let root_node = SyntaxNode::new(vec![
+        test_leaf_node(ASTReflectionStyle::reflection("Name".to_string()), uuids[0]),
+        test_leaf("=", 1, ASTReflectionStyle::reflection(String::new()), uuids[1]),
+        test_leaf_node(ASTReflectionStyle::reflection("Name".to_string()), uuids[2]),
+        test_leaf_node(ASTReflectionStyle::new(ASTVisibility::Hide, Some("Name".to_string())), uuids[3]),
+        test_leaf_node(ASTReflectionStyle::reflection("Value".to_string()), uuids[4]),
+        test_leaf(";", 5, ASTReflectionStyle::no_reflection(), uuids[5]),
+    ], ASTReflectionStyle::reflection("Main".to_string()), uuid::Uuid::new_v4());
+
+    let to_uuid = |elem: &&SyntaxNodeElement| match elem {
+        SyntaxNodeElement::Node(node) => node.uuid,
+        SyntaxNodeElement::Leaf(leaf) => leaf.uuid,
+    };
+
+    let children_map = root_node.named_children_map();
+    let mut names = children_map.keys().map(|each_name| *each_name).collect::<Vec<&str>>();
+    names.sort();
+    assert_eq!(names, vec!["", "Name", "Value"], "unexpected reflection names");
+
+    let to_uuids = |name: &str| children_map[name].iter().map(to_uuid).collect::<Vec<uuid::Uuid>>();
+    assert_eq!(to_uuids("Name"), vec![uuids[0], uuids[2]], "children of the same name are not in order or include non-reflectable one");
+    assert_eq!(to_uuids(""), vec![uuids[1]], "children without reflection name are not keyed by empty string");
+    assert_eq!(to_uuids("Value"), vec![uuids[4]]);
+
+    let empty_node = SyntaxNode::new(Vec::new(), ASTReflectionStyle::reflection("Main".to_string()), uuid::Uuid::new_v4());
+    assert!(empty_node.named_children_map().is_empty());
+}
+
+// spec: to_lsp_position() と to_lsp_range() が 0 始まりの行と列を返し、ノードの範囲が子孫のリーフの範囲となることを確認する
+#[cfg(feature = "lsp")]
+#[test]
+fn lsp_ranges() {
+    let pos = CharacterPosition::new(None, 7, 2, 3);
+    let end_pos = CharacterPosition::new(None, 9, 2, 5);
+    assert_eq!(pos.to_lsp_position(), (2, 3));
+    assert_eq!(pos.to_lsp_range(&end_pos), ((2, 3), (2, 5)));
+
+    let tree = parse_tree("[Main]{\n    Main <- Line (\"\\n\"# Line)*## \"\\z\"#,\n    Line <- JOIN<[a-z]+>,\n}", ".Main.Main", "ab\ncde", ParseConfig::default());
+
+    let root_node = match tree.get_child_ref().try_get_node() {
+        Ok(v) => v,
+        Err(_) => panic!("root of the tree is not a node:\n{}", tree.to_sexp(false)),
+    };
+
+    let to_line_range = |index: i64| root_node.get_child_at_checked(index).unwrap().try_get_node().unwrap().to_lsp_range();
+    assert_eq!(to_line_range(0), Some(((0, 0), (0, 2))), "unexpected range of the first line:\n{}", tree.to_sexp(false));
+    assert_eq!(to_line_range(1), Some(((1, 0), (1, 3))), "unexpected range of the second line:\n{}", tree.to_sexp(false));
+
+    let empty_node = SyntaxNode::new(Vec::new(), ASTReflectionStyle::reflection("Empty".to_string()), uuid::Uuid::new_v4());
+    assert_eq!(empty_node.to_lsp_range(), None, "node without leaves has a range");
+}
+
+// spec: get_sibling_after() と get_sibling_before() が非反映的な子要素を飛ばして隣の Reflectable な子要素を返し、端の要素や直接の子要素でない要素には None を返すことを確認する
+#[test]
+fn node_siblings() {
+    let uuids = (0..5).map(|_| uuid::Uuid::new_v4()).collect::<Vec<uuid::Uuid>>();
+
+    let root_node = SyntaxNode::new(vec![
+        test_leaf("a", 0, ASTReflectionStyle::reflection(String::new()), uuids[0]),
+        test_leaf(";", 1, ASTReflectionStyle::no_reflection(), uuids[1]),
+        test_node(vec![test_leaf("c", 2, ASTReflectionStyle::reflection(String::new()), uuids[4])], ASTReflectionStyle::reflection("Sub".to_string()), uuids[2]),
+        test_leaf("b", 3, ASTReflectionStyle::reflection(String::new()), uuids[3]),
+    ], ASTReflectionStyle::reflection("Main".to_string()), uuid::Uuid::new_v4());
+
+    let to_uuid = |elem: Option<&SyntaxNodeElement>| elem.map(|v| match v {
+        SyntaxNodeElement::Node(node) => node.uuid,
+        SyntaxNodeElement::Leaf(leaf) => leaf.uuid,
+    });
+
+    assert_eq!(to_uuid(root_node.get_sibling_after(&uuids[0])), Some(uuids[2]), "non-reflectable sibling is returned");
+    assert_eq!(to_uuid(root_node.get_sibling_after(&uuids[1])), Some(uuids[2]));
+    assert_eq!(to_uuid(root_node.get_sibling_after(&uuids[2])), Some(uuids[3]));
+    assert_eq!(to_uuid(root_node.get_sibling_after(&uuids[3])), None, "sibling after the last child is found");
+
+    assert_eq!(to_uuid(root_node.get_sibling_before(&uuids[2])), Some(uuids[0]), "non-reflectable sibling is returned");
+    assert_eq!(to_uuid(root_node.get_sibling_before(&uuids[3])), Some(uuids[2]));
+    assert_eq!(to_uuid(root_node.get_sibling_before(&uuids[0])), None, "sibling before the first child is found");
+
+    // note: 孫要素や未知の UUID は直接の子要素ではない
+    assert_eq!(to_uuid(root_node.get_sibling_after(&uuids[4])), None, "sibling of a grandchild is found");
+    assert_eq!(to_uuid(root_node.get_sibling_before(&uuid::Uuid::new_v4())), None, "sibling of an unknown element is found");
+}
+
+// spec: SyntaxTree::merge_adjacent_leaves() が AST 反映方式の等しい連続するリーフを先頭のリーフの位置と UUID でまとめ、ノードを挟むリーフや反映方式の異なるリーフをまとめないことを確認する
+#[test]
+fn adjacent_leaf_merging() {
+    let uuids = (0..9).map(|_| uuid::Uuid::new_v4()).collect::<Vec<uuid::Uuid>>();
+    let reflectable = || ASTReflectionStyle::reflection(String::new());
+
+    let sub_node = SyntaxNodeElement::Node(Box::new(SyntaxNode::new(vec![test_leaf("d", 5, reflectable(), uuids[5]), test_leaf("e", 6, reflectable(), uuids[6])], ASTReflectionStyle::reflection("Sub".to_string()), uuids[8])));
+    let tree = SyntaxTree::from_node(SyntaxNodeElement::Node(Box::new(SyntaxNode::new(vec![
+        test_leaf("a", 0, reflectable(), uuids[0]),
+        test_leaf("b", 1, reflectable(), uuids[1]),
+        test_leaf(" ", 2, ASTReflectionStyle::no_reflection(), uuids[2]),
+        test_leaf(" ", 3, ASTReflectionStyle::no_reflection(), uuids[3]),
+        test_leaf("c", 4, reflectable(), uuids[4]),
+        sub_node,
+        test_leaf("f", 7, reflectable(), uuids[7]),
+    ], ASTReflectionStyle::reflection("Main".to_string()), uuid::Uuid::new_v4()))));
+
+    let merged_tree = tree.merge_adjacent_leaves();
+    let to_tokens = |tree: &SyntaxTree, include_hidden: bool| tree.to_token_stream(include_hidden).iter().map(|each_leaf| (each_leaf.pos.index, each_leaf.uuid, each_leaf.value.clone())).collect::<Vec<(usize, uuid::Uuid, String)>>();
+
+    let expected_tokens = vec![
+        (0, uuids[0], "ab".to_string()),
+        (2, uuids[2], "  ".to_string()),
+        (4, uuids[4], "c".to_string()),
+        (5, uuids[5], "de".to_string()),
+        (7, uuids[7], "f".to_string()),
+    ];
+
+    assert_eq!(to_tokens(&merged_tree, true), expected_tokens, "unexpected merged leaves:\n{}", merged_tree.to_sexp(false));
+    assert_eq!(to_tokens(&merged_tree, false).len(), 4, "non-reflectable leaves are merged with reflectable ones:\n{}", merged_tree.to_sexp(false));
+    assert_eq!(to_tokens(&tree, true).len(), 8, "original tree is modified");
+
+    // note: バイト列モードのリーフは元のバイト列も連結する
+    let mut byte_leaves = vec![test_leaf("a", 0, reflectable(), uuids[0]), test_leaf("\u{e9}", 1, reflectable(), uuids[1])];
+
+    for (each_leaf, each_raw_value) in byte_leaves.iter_mut().zip(vec![b"a".to_vec(), vec![0xc3, 0xa9]]) {
+        match each_leaf {
+            SyntaxNodeElement::Leaf(leaf) => leaf.raw_value = Some(each_raw_value),
+            SyntaxNodeElement::Node(_) => (),
+        }
+    }
+
+    let merged_byte_tree = SyntaxTree::from_node_args(byte_leaves, ASTReflectionStyle::reflection("Main".to_string())).merge_adjacent_leaves();
+    let merged_byte_leaves = merged_byte_tree.to_token_stream(true);
+    assert_eq!(merged_byte_leaves.len(), 1);
+    assert_eq!(merged_byte_leaves[0].value, "a\u{e9}");
+    assert_eq!(merged_byte_leaves[0].raw_value, Some(vec![b'a', 0xc3, 0xa9]));
+}
+
+// spec: SyntaxParser::parse_spans() が入力の範囲と値が一致するリーフを入力への参照として保持し、to_owned_tree() で元の構文木と同じ UUID・位置・値の木に戻せることを確認する
+// note: 複数バイトの文字を含む入力で文字インデックスからバイト範囲を求める; EOF 用のヌル文字は値を所有する
+#[test]
+fn span_trees() {
+    let grammar_src = "[Main]{\n    Main <- Word (\" \"# Word)*## \"\\z\"#,\n    Word <- JOIN<[a-z\u{e9}]+>,\n}";
+    let src = "\u{e9}a bc";
+    let tree = parse_tree(grammar_src, ".Main.Main", src, ParseConfig::default());
+
+    let span_tree = SpanTree::from_tree(src, tree.clone());
+    assert_eq!(span_tree.get_src(), src);
+    assert_eq!(span_tree.count_leaves(), (3, 1), "unexpected counts of span and owned leaves:\n{}", tree.to_sexp(false));
+
+    // note: (UUID, インデックス, 行, 列, 入力のパス, 値) の一覧
+    let to_tokens = |tree: &SyntaxTree| tree.to_token_stream(true).iter().map(|each_leaf| (each_leaf.uuid, each_leaf.pos.index, each_leaf.pos.line, each_leaf.pos.column, each_leaf.pos.file_path.clone(), each_leaf.value.clone())).collect::<Vec<(uuid::Uuid, usize, usize, usize, Option<String>, String)>>();
+    let owned_tree = span_tree.to_owned_tree();
+    assert_eq!(to_tokens(&owned_tree), to_tokens(&tree), "owned tree differs from the parsed one");
+    assert_eq!(owned_tree.to_sexp(false), tree.to_sexp(false));
+
+    let span_root_node = match span_tree.get_child_ref() {
+        SpanNodeElement::Node(node) => node,
+        SpanNodeElement::Leaf(_) => panic!("root of the span tree is a leaf"),
+    };
+
+    let root_node = tree.get_child_ref().try_get_node().unwrap();
+    assert_eq!(span_tree.join_child_leaf_values(span_root_node), root_node.join_child_leaf_values());
+    assert_eq!(span_tree.join_child_leaf_values(span_root_node), "\u{e9}abc");
+
+    let first_leaf = match &span_root_node.sub_elems[0] {
+        SpanNodeElement::Node(node) => match &node.sub_elems[0] {
+            SpanNodeElement::Leaf(leaf) => leaf,
+            SpanNodeElement::Node(_) => panic!("first element of the word is a node"),
+        },
+        SpanNodeElement::Leaf(_) => panic!("first element of the span tree is a leaf"),
+    };
+
+    assert!(matches!(first_leaf.value, SpanLeafValue::Span(0, 3)), "first leaf does not reference the source bytes");
+    assert_eq!(span_tree.get_leaf_value(first_leaf), "\u{e9}a");
+    assert_eq!(first_leaf.pos.file_path, None, "leaf position of span tree has the input path");
+    assert_eq!(span_tree.get_leaf_position(first_leaf).file_path, Some(TEST_INPUT_FILE_PATH.to_string()));
+
+    let cons = new_console();
+    let rule_map = load_rule_map(&cons, grammar_src);
+
+    let parsed_span_tree = match SyntaxParser::parse_spans(cons.clone(), rule_map, TEST_INPUT_FILE_PATH.to_string(), src, ParseConfig::default()) {
+        Ok(v) => v,
+        Err(()) => {
+            cons.borrow().print_all();
+            panic!("failed to parse the input into span tree");
+        },
+    };
+
+    assert_eq!(parsed_span_tree.count_leaves(), span_tree.count_leaves());
+    assert_eq!(parsed_span_tree.to_owned_tree().to_sexp(false), tree.to_sexp(false), "span tree from parse_spans() differs from the parsed one");
+}
+
+// spec: first_leaf_position() がリーフでは自身の位置を、ノードでは子孫のうち最初のリーフ (非反映的なものを含む) の位置を返し、子孫にリーフがなければ None を返すことを確認する
+#[test]
+fn first_leaf_positions() {
+    let empty_node = test_node(vec![test_node(Vec::new(), ASTReflectionStyle::reflection("Sub".to_string()), uuid::Uuid::new_v4())], ASTReflectionStyle::reflection("Sub".to_string()), uuid::Uuid::new_v4());
+
+    // note: 子孫にリーフのないノードを飛ばし、入れ子のノードの非反映的なリーフを返す
+    let root = test_node(vec![
+        empty_node.clone(),
+        test_node(vec![test_leaf(" ", 3, ASTReflectionStyle::no_reflection(), uuid::Uuid::new_v4()), test_leaf("a", 4, ASTReflectionStyle::reflection(String::new()), uuid::Uuid::new_v4())], ASTReflectionStyle::reflection("Sub".to_string()), uuid::Uuid::new_v4()),
+        test_leaf("b", 5, ASTReflectionStyle::reflection(String::new()), uuid::Uuid::new_v4()),
+    ], ASTReflectionStyle::reflection("Main".to_string()), uuid::Uuid::new_v4());
+
+    assert_eq!(root.first_leaf_position().map(|v| v.index), Some(3));
+    assert_eq!(test_leaf("c", 7, ASTReflectionStyle::reflection(String::new()), uuid::Uuid::new_v4()).first_leaf_position().map(|v| v.index), Some(7));
+    assert_eq!(empty_node.first_leaf_position().map(|v| v.index), None, "node without leaves has a position");
+    assert_eq!(root.first_leaf_position().and_then(|v| v.file_path), Some(TEST_INPUT_FILE_PATH.to_string()));
+}
+
+// spec: reflectable_child_names() が Reflectable な子要素の反映名を重複を含めて出現順に返し、反映名のない子要素と非反映的な子要素を含めないことを確認する
+#[test]
+fn reflectable_child_name_lists() {
+    let root_node = SyntaxNode::new(vec![
+        test_leaf_node(ASTReflectionStyle::reflection("Name".to_string()), uuid::Uuid::new_v4()),
+        test_leaf("a", 1, ASTReflectionStyle::reflection(String::new()), uuid::Uuid::new_v4()),
+        test_leaf("+", 2, ASTReflectionStyle::reflection("Op".to_string()), uuid::Uuid::new_v4()),
+        test_leaf_node(ASTReflectionStyle::new(ASTVisibility::Hide, Some("Value".to_string())), uuid::Uuid::new_v4()),
+        test_leaf_node(ASTReflectionStyle::reflection("Name".to_string()), uuid::Uuid::new_v4()),
+        test_leaf_node(ASTReflectionStyle::reflection(String::new()), uuid::Uuid::new_v4()),
+    ], ASTReflectionStyle::reflection("Main".to_string()), uuid::Uuid::new_v4());
+
+    assert_eq!(root_node.reflectable_child_names(), vec!["Name", "Op", "Name"]);
+
+    let empty_node = SyntaxNode::new(Vec::new(), ASTReflectionStyle::reflection("Main".to_string()), uuid::Uuid::new_v4());
+    assert_eq!(empty_node.reflectable_child_names(), Vec::<&str>::new());
+}
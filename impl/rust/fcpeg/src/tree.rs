@@ -1,34 +1,206 @@
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fmt::*;
-use std::io::*;
+use std::hash::Hasher;
+#[cfg(feature = "terminal")]
+use std::io::{BufWriter, stdout};
 use std::io::Write;
 use std::rc::Rc;
 
+use crate::annotation::*;
 use crate::rule::*;
 
+use fxhash::FxHasher64;
+
 use rustnutlib::*;
 use rustnutlib::console::*;
 
 use uuid::Uuid;
 
+// note: src_path は要素の子孫のリーフの位置から特定した入力のパス; リーフがなければ None
+#[derive(Clone, Debug, PartialEq)]
 pub enum TreeLog {
     Unknown {},
-    CharacterPositionNotFound { uuid: Uuid },
-    ElementNotNode { uuid: Uuid },
-    ElementNotLeaf { uuid: Uuid },
-    NodeChildNotFound { parent_uuid: Uuid, index: usize },
-    ReflectableChildNotFound { parent_uuid: Uuid, index: usize },
+    CharacterPositionNotFound { uuid: Uuid, src_path: Option<String> },
+    ElementNotNode { uuid: Uuid, src_path: Option<String> },
+    ElementNotLeaf { uuid: Uuid, src_path: Option<String> },
+    NodeChildNotFound { parent_uuid: Uuid, index: i64, src_path: Option<String> },
+    ReflectableChildNotFound { parent_uuid: Uuid, index: i64, src_path: Option<String> },
 }
 
 impl ConsoleLogger for TreeLog {
     fn get_log(&self) -> ConsoleLog {
+        let uuid_text = match self {
+            TreeLog::Unknown {} => return log!(Error, self.to_string()),
+            TreeLog::CharacterPositionNotFound { uuid, src_path: _ } => format!("uuid:\t{}", uuid),
+            TreeLog::ElementNotNode { uuid, src_path: _ } => format!("uuid:\t{}", uuid),
+            TreeLog::ElementNotLeaf { uuid, src_path: _ } => format!("uuid:\t{}", uuid),
+            TreeLog::NodeChildNotFound { parent_uuid, index: _, src_path: _ } => format!("parent:\t{}", parent_uuid),
+            TreeLog::ReflectableChildNotFound { parent_uuid, index: _, src_path: _ } => format!("parent:\t{}", parent_uuid),
+        };
+
+        let index = match self {
+            TreeLog::NodeChildNotFound { parent_uuid: _, index, src_path: _ } => Some(index),
+            TreeLog::ReflectableChildNotFound { parent_uuid: _, index, src_path: _ } => Some(index),
+            _ => None,
+        };
+
+        return match (self.get_src_path(), index) {
+            (Some(src_path), Some(index)) => log!(Error, self.to_string(), format!("input:\t{}", src_path), uuid_text, format!("index:\t{}", index)),
+            (Some(src_path), None) => log!(Error, self.to_string(), format!("input:\t{}", src_path), uuid_text),
+            (None, Some(index)) => log!(Error, self.to_string(), uuid_text, format!("index:\t{}", index)),
+            (None, None) => log!(Error, self.to_string(), uuid_text),
+        };
+    }
+}
+
+impl TreeLog {
+    // ret: 要素を含む入力のパス; 特定できなければ None
+    pub fn get_src_path(&self) -> Option<&String> {
+        return match self {
+            TreeLog::Unknown {} => None,
+            TreeLog::CharacterPositionNotFound { uuid: _, src_path } => src_path.as_ref(),
+            TreeLog::ElementNotNode { uuid: _, src_path } => src_path.as_ref(),
+            TreeLog::ElementNotLeaf { uuid: _, src_path } => src_path.as_ref(),
+            TreeLog::NodeChildNotFound { parent_uuid: _, index: _, src_path } => src_path.as_ref(),
+            TreeLog::ReflectableChildNotFound { parent_uuid: _, index: _, src_path } => src_path.as_ref(),
+        };
+    }
+
+    // note: コンソールを受け取る API で map_err に渡す
+    fn append_to(self, cons: &Rc<RefCell<Console>>) {
+        cons.borrow_mut().append_log(self.get_log());
+    }
+}
+
+// note: ログの見出し部分; 詳細情報は get_log() で付加する
+impl Display for TreeLog {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let s = match self {
+            TreeLog::Unknown {} => "unknown error",
+            TreeLog::CharacterPositionNotFound { uuid: _, src_path: _ } => "character position not found",
+            TreeLog::ElementNotNode { uuid: _, src_path: _ } => "element not node",
+            TreeLog::ElementNotLeaf { uuid: _, src_path: _ } => "element not leaf",
+            TreeLog::NodeChildNotFound { parent_uuid: _, index: _, src_path: _ } => "node child not found",
+            TreeLog::ReflectableChildNotFound { parent_uuid: _, index: _, src_path: _ } => "reflectable child not found",
+        };
+
+        return write!(f, "{}", s);
+    }
+}
+
+impl std::error::Error for TreeLog {}
+
+// note: index はリーフの値における不正なエスケープシーケンスの '\' の文字単位の位置
+#[derive(Clone, Debug, PartialEq)]
+pub enum UnescapeError {
+    InvalidCodePoint { index: usize, code_point: u32 },
+    InvalidHexDigit { index: usize, found: char },
+    UnexpectedEnd { index: usize },
+    UnknownEscapeCharacter { index: usize, found: char },
+}
+
+impl UnescapeError {
+    pub fn get_index(&self) -> usize {
+        return match self {
+            UnescapeError::InvalidCodePoint { index, code_point: _ } => *index,
+            UnescapeError::InvalidHexDigit { index, found: _ } => *index,
+            UnescapeError::UnexpectedEnd { index } => *index,
+            UnescapeError::UnknownEscapeCharacter { index, found: _ } => *index,
+        };
+    }
+}
+
+impl ConsoleLogger for UnescapeError {
+    fn get_log(&self) -> ConsoleLog {
+        return log!(Error, self.to_string(), format!("at:\t{}", self.get_index()));
+    }
+}
+
+impl Display for UnescapeError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let s = match self {
+            UnescapeError::InvalidCodePoint { index: _, code_point } => format!("invalid code point U+{:X} in escape sequence", code_point),
+            UnescapeError::InvalidHexDigit { index: _, found } => format!("invalid hex digit '{}' in escape sequence", found),
+            UnescapeError::UnexpectedEnd { index: _ } => "unexpected end of escape sequence".to_string(),
+            UnescapeError::UnknownEscapeCharacter { index: _, found } => format!("unknown escape character '{}'", found),
+        };
+
+        return write!(f, "{}", s);
+    }
+}
+
+// note: index は SyntaxNode::deserialize_compact() に渡したバイト列における不正な箇所の位置
+#[derive(Clone, Debug, PartialEq)]
+pub enum DeserializeError {
+    ElementLengthMismatch { index: usize, expected_len: usize, actual_len: usize },
+    InvalidUtf8 { index: usize },
+    InvalidVarint { index: usize },
+    TrailingBytes { index: usize },
+    UnexpectedByte { index: usize, found: u8 },
+    UnexpectedEnd { index: usize },
+    UnknownTag { index: usize, tag: u8 },
+    UnsupportedVersion { index: usize, version: u8 },
+}
+
+impl DeserializeError {
+    pub fn get_index(&self) -> usize {
+        return match self {
+            DeserializeError::ElementLengthMismatch { index, expected_len: _, actual_len: _ } => *index,
+            DeserializeError::InvalidUtf8 { index } => *index,
+            DeserializeError::InvalidVarint { index } => *index,
+            DeserializeError::TrailingBytes { index } => *index,
+            DeserializeError::UnexpectedByte { index, found: _ } => *index,
+            DeserializeError::UnexpectedEnd { index } => *index,
+            DeserializeError::UnknownTag { index, tag: _ } => *index,
+            DeserializeError::UnsupportedVersion { index, version: _ } => *index,
+        };
+    }
+}
+
+impl ConsoleLogger for DeserializeError {
+    fn get_log(&self) -> ConsoleLog {
+        return log!(Error, self.to_string(), format!("at:\t{}", self.get_index()));
+    }
+}
+
+impl Display for DeserializeError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let s = match self {
+            DeserializeError::ElementLengthMismatch { index: _, expected_len, actual_len } => format!("element length mismatch: expected {} bytes but read {} bytes", expected_len, actual_len),
+            DeserializeError::InvalidUtf8 { index: _ } => "invalid UTF-8 string".to_string(),
+            DeserializeError::InvalidVarint { index: _ } => "invalid variable-length integer".to_string(),
+            DeserializeError::TrailingBytes { index: _ } => "trailing bytes after element".to_string(),
+            DeserializeError::UnexpectedByte { index: _, found } => format!("unexpected byte 0x{:02x}", found),
+            DeserializeError::UnexpectedEnd { index: _ } => "unexpected end of bytes".to_string(),
+            DeserializeError::UnknownTag { index: _, tag } => format!("unknown element tag 0x{:02x}", tag),
+            DeserializeError::UnsupportedVersion { index: _, version } => format!("unsupported compact format version {} (expected {})", version, COMPACT_FORMAT_VERSION),
+        };
+
+        return write!(f, "{}", s);
+    }
+}
+
+impl std::error::Error for DeserializeError {}
+
+// spec: SyntaxTree::verify_invariants() が検出した構文木の不整合
+#[cfg(debug_assertions)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum InvariantViolation {
+    DuplicateUuid { uuid: Uuid },
+    EmptyExpansionNode { uuid: Uuid },
+    LeafPositionDecreased { uuid: Uuid, prev_index: usize, index: usize },
+    SelfReference { uuid: Uuid },
+}
+
+#[cfg(debug_assertions)]
+impl Display for InvariantViolation {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
         return match self {
-            TreeLog::Unknown {} => log!(Error, "unknown error"),
-            TreeLog::CharacterPositionNotFound { uuid } => log!(Error, "character position not found", format!("uuid:\t{}", uuid)),
-            TreeLog::ElementNotNode { uuid } => log!(Error, "element not node", format!("uuid:\t{}", uuid)),
-            TreeLog::ElementNotLeaf { uuid } => log!(Error, "element not leaf", format!("uuid:\t{}", uuid)),
-            TreeLog::NodeChildNotFound { parent_uuid, index } => log!(Error, "node child not found", format!("parent:\t{}", parent_uuid), format!("index:\t{}", index)),
-            TreeLog::ReflectableChildNotFound { parent_uuid, index } => log!(Error, "reflectable child not found", format!("parent:\t{}", parent_uuid), format!("index:\t{}", index)),
+            InvariantViolation::DuplicateUuid { uuid } => write!(f, "duplicate uuid {}", uuid),
+            InvariantViolation::EmptyExpansionNode { uuid } => write!(f, "expansion node {} has no children", uuid),
+            InvariantViolation::LeafPositionDecreased { uuid, prev_index, index } => write!(f, "position of leaf {} decreased from index {} to {}", uuid, prev_index, index),
+            InvariantViolation::SelfReference { uuid } => write!(f, "element {} has the same uuid as its ancestor", uuid),
         };
     }
 }
@@ -59,6 +231,26 @@ impl CharacterPosition {
             column: 0,
         };
     }
+
+    fn write_compact(&self, bytes: &mut Vec<u8>) {
+        write_compact_option_str(bytes, &self.file_path);
+        write_compact_varint(bytes, self.index as u64);
+        write_compact_varint(bytes, self.line as u64);
+        write_compact_varint(bytes, self.column as u64);
+    }
+
+    // ret: LSP の Position に対応する (line, character); いずれも 0 始まり
+    // note: character は UTF-16 のコード単位ではなく文字単位の列数
+    #[cfg(feature = "lsp")]
+    pub fn to_lsp_position(&self) -> (u32, u32) {
+        return (self.line as u32, self.column as u32);
+    }
+
+    // ret: 自身を開始位置とする LSP の Range に対応する (start, end)
+    #[cfg(feature = "lsp")]
+    pub fn to_lsp_range(&self, end_pos: &CharacterPosition) -> ((u32, u32), (u32, u32)) {
+        return (self.to_lsp_position(), end_pos.to_lsp_position());
+    }
 }
 
 impl Display for CharacterPosition {
@@ -72,294 +264,1562 @@ impl Display for CharacterPosition {
     }
 }
 
-#[derive(Clone, PartialEq)]
-pub enum ASTReflectionStyle {
-    // note: AST に反映される
-    Reflection(String),
-    // note: AST に反映されない
-    NoReflection,
-    Expansion,
+// note: 文字列の境界を区別するため長さを先に書き込む
+fn write_str_hash(hasher: &mut FxHasher64, s: &str) {
+    hasher.write_u64(s.len() as u64);
+    hasher.write(s.as_bytes());
 }
 
-impl ASTReflectionStyle {
-    // todo: config データの扱いを修正
-    pub fn from_config(reverse_ast_reflection: bool, is_reflectable: bool, elem_name: String) -> ASTReflectionStyle {
-        return if is_reflectable {
-            if reverse_ast_reflection {
-                ASTReflectionStyle::Reflection(elem_name)
-            } else {
-                ASTReflectionStyle::NoReflection
-            }
-        } else {
-            if reverse_ast_reflection {
-                ASTReflectionStyle::NoReflection
-            } else{
-                ASTReflectionStyle::Reflection(elem_name)
-            }
+// note: SyntaxNode::serialize_compact() の形式の版; 形式を変更した場合は値を増やし、以前の版のキャッシュを UnsupportedVersion として読み込まないようにする
+pub const COMPACT_FORMAT_VERSION: u8 = 1;
+
+const COMPACT_NODE_TAG: u8 = 0;
+const COMPACT_LEAF_TAG: u8 = 1;
+
+// note: 要素の内容の長さは書き込んだ後に埋めるため固定長とする
+const COMPACT_ELEM_LEN_SIZE: usize = 4;
+
+// spec: 符号なし LEB128; 下位から 7 ビットずつ書き込み、続きがあれば最上位ビットを立てる
+fn write_compact_varint(bytes: &mut Vec<u8>, mut n: u64) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+
+        if n == 0 {
+            bytes.push(byte);
+            return;
         }
-    }
 
-    pub fn is_reflectable(&self) -> bool {
-        return *self != ASTReflectionStyle::NoReflection;
+        bytes.push(byte | 0x80);
     }
+}
 
-    pub fn is_expandable(&self) -> bool {
-        return *self == ASTReflectionStyle::Expansion;
+fn write_compact_bytes(bytes: &mut Vec<u8>, value: &[u8]) {
+    write_compact_varint(bytes, value.len() as u64);
+    bytes.extend_from_slice(value);
+}
+
+fn write_compact_option_str(bytes: &mut Vec<u8>, value: &Option<String>) {
+    match value {
+        Some(v) => {
+            bytes.push(1);
+            write_compact_bytes(bytes, v.as_bytes());
+        },
+        None => bytes.push(0),
     }
 }
 
-impl Display for ASTReflectionStyle {
-    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-        let s = match self {
-            ASTReflectionStyle::Reflection(elem_name) => format!("#{}", elem_name.clone()),
-            ASTReflectionStyle::NoReflection => String::new(),
-            ASTReflectionStyle::Expansion => "##".to_string(),
-        };
+// spec: タグ 1 バイト、内容の長さ (u32 リトルエンディアン)、内容の順に書き込む
+// note: 一時的なバッファを確保しないよう、長さの領域を先に確保して内容を書き込んだ後に埋める
+fn write_compact_elem<F: FnOnce(&mut Vec<u8>)>(bytes: &mut Vec<u8>, tag: u8, write_content: F) {
+    bytes.push(tag);
+    let len_i = bytes.len();
+    bytes.extend_from_slice(&[0; COMPACT_ELEM_LEN_SIZE]);
 
-        return write!(f, "{}", s);
-    }
+    write_content(bytes);
+
+    let content_len = (bytes.len() - len_i - COMPACT_ELEM_LEN_SIZE) as u32;
+    bytes[len_i..len_i + COMPACT_ELEM_LEN_SIZE].copy_from_slice(&content_len.to_le_bytes());
 }
 
-#[derive(Clone)]
-pub enum SyntaxNodeElement {
-    Node(Box<SyntaxNode>),
-    Leaf(Box<SyntaxLeaf>),
+// spec: SyntaxNode::serialize_compact() の出力を先頭から読み込む
+struct CompactReader<'a> {
+    bytes: &'a [u8],
+    index: usize,
 }
 
-impl SyntaxNodeElement {
-    pub fn from_node_args(sub_elems: Vec<SyntaxNodeElement>, ast_reflection_style: ASTReflectionStyle) -> SyntaxNodeElement {
-        return SyntaxNodeElement::Node(Box::new(SyntaxNode::new(sub_elems, ast_reflection_style, Uuid::new_v4())));
+impl<'a> CompactReader<'a> {
+    fn new(bytes: &'a [u8]) -> CompactReader<'a> {
+        return CompactReader {
+            bytes: bytes,
+            index: 0,
+        };
     }
 
-    pub fn from_leaf_args(pos: CharacterPosition, value: String, ast_reflection: ASTReflectionStyle) -> SyntaxNodeElement {
-        return SyntaxNodeElement::Leaf(Box::new(SyntaxLeaf::new(pos, value, ast_reflection, Uuid::new_v4())));
+    fn read_slice(&mut self, len: usize) -> std::result::Result<&'a [u8], DeserializeError> {
+        if len > self.bytes.len() - self.index {
+            return Err(DeserializeError::UnexpectedEnd { index: self.bytes.len() });
+        }
+
+        let slice = &self.bytes[self.index..self.index + len];
+        self.index += len;
+        return Ok(slice);
     }
 
-    pub fn get_node(&self, cons: &Rc<RefCell<Console>>) -> ConsoleResult<&SyntaxNode> {
-        return match self {
-            SyntaxNodeElement::Node(node) => Ok(node),
-            SyntaxNodeElement::Leaf(leaf) => {
-                cons.borrow_mut().append_log(TreeLog::ElementNotNode {
-                    uuid: leaf.uuid.clone(),
-                }.get_log());
+    fn read_u8(&mut self) -> std::result::Result<u8, DeserializeError> {
+        return Ok(self.read_slice(1)?[0]);
+    }
 
-                return Err(());
-            },
+    fn read_bool(&mut self) -> std::result::Result<bool, DeserializeError> {
+        let byte_i = self.index;
+
+        return match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            found => Err(DeserializeError::UnexpectedByte { index: byte_i, found: found }),
         };
     }
 
-    pub fn get_leaf(&self, cons: &Rc<RefCell<Console>>) -> ConsoleResult<&SyntaxLeaf> {
-        return match self {
-            SyntaxNodeElement::Node(node) => {
-                cons.borrow_mut().append_log(TreeLog::ElementNotLeaf {
-                    uuid: node.uuid.clone(),
-                }.get_log());
+    fn read_varint(&mut self) -> std::result::Result<u64, DeserializeError> {
+        let varint_i = self.index;
+        let mut n = 0u64;
+        let mut shift = 0u32;
 
-                return Err(());
-            },
-            SyntaxNodeElement::Leaf(leaf) => Ok(leaf),
+        loop {
+            let byte = self.read_u8()?;
+            let bits = (byte & 0x7f) as u64;
+
+            // note: u64 に収まらないビットが立っていれば不正とする
+            if shift >= 64 || (shift != 0 && bits >> (64 - shift) != 0) {
+                return Err(DeserializeError::InvalidVarint { index: varint_i });
+            }
+
+            n |= bits << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+
+            shift += 7;
+        }
+    }
+
+    fn read_usize(&mut self) -> std::result::Result<usize, DeserializeError> {
+        let varint_i = self.index;
+        let n = self.read_varint()?;
+
+        return if n <= usize::MAX as u64 {
+            Ok(n as usize)
+        } else {
+            Err(DeserializeError::InvalidVarint { index: varint_i })
         };
     }
 
-    pub fn is_node(&self) -> bool {
-        return match self {
-            SyntaxNodeElement::Node(_) => true,
-            _ => false,
+    fn read_bytes(&mut self) -> std::result::Result<&'a [u8], DeserializeError> {
+        let len = self.read_usize()?;
+        return self.read_slice(len);
+    }
+
+    fn read_string(&mut self) -> std::result::Result<String, DeserializeError> {
+        let str_i = self.index;
+
+        return match std::str::from_utf8(self.read_bytes()?) {
+            Ok(v) => Ok(v.to_string()),
+            Err(_) => Err(DeserializeError::InvalidUtf8 { index: str_i }),
         };
     }
 
-    pub fn is_reflectable(&self) -> bool {
-        return match self {
-            SyntaxNodeElement::Node(node) => node.is_reflectable(),
-            SyntaxNodeElement::Leaf(leaf) => leaf.is_reflectable(),
+    fn read_option_string(&mut self) -> std::result::Result<Option<String>, DeserializeError> {
+        return if self.read_bool()? {
+            Ok(Some(self.read_string()?))
+        } else {
+            Ok(None)
         };
     }
 
-    pub fn get_ast_reflection_style(&self) -> ASTReflectionStyle {
-        return match self {
-            SyntaxNodeElement::Node(node) => node.ast_reflection_style.clone(),
-            SyntaxNodeElement::Leaf(leaf) => leaf.ast_reflection_style.clone(),
+    fn read_uuid(&mut self) -> std::result::Result<Uuid, DeserializeError> {
+        let mut uuid_bytes = [0u8; 16];
+        uuid_bytes.copy_from_slice(self.read_slice(16)?);
+        return Ok(Uuid::from_bytes(uuid_bytes));
+    }
+
+    fn read_ast_reflection_style(&mut self) -> std::result::Result<ASTReflectionStyle, DeserializeError> {
+        let visibility_i = self.index;
+
+        let visibility = match self.read_u8()? {
+            0 => ASTVisibility::Reflect,
+            1 => ASTVisibility::Hide,
+            2 => ASTVisibility::Expand,
+            found => return Err(DeserializeError::UnexpectedByte { index: visibility_i, found: found }),
         };
+
+        let name_override = self.read_option_string()?;
+        return Ok(ASTReflectionStyle::new(visibility, name_override));
     }
 
-    pub fn set_ast_reflection_style(&mut self, ast_reflection_style: ASTReflectionStyle) {
-        match self {
-            SyntaxNodeElement::Node(node) => node.ast_reflection_style = ast_reflection_style,
-            SyntaxNodeElement::Leaf(leaf) => leaf.ast_reflection_style = ast_reflection_style,
+    fn read_position(&mut self) -> std::result::Result<CharacterPosition, DeserializeError> {
+        let file_path = self.read_option_string()?;
+        let index = self.read_usize()?;
+        let line = self.read_usize()?;
+        let column = self.read_usize()?;
+        return Ok(CharacterPosition::new(file_path, index, line, column));
+    }
+
+    fn read_elem(&mut self) -> std::result::Result<SyntaxNodeElement, DeserializeError> {
+        let tag_i = self.index;
+        let tag = self.read_u8()?;
+
+        let mut len_bytes = [0u8; COMPACT_ELEM_LEN_SIZE];
+        len_bytes.copy_from_slice(self.read_slice(COMPACT_ELEM_LEN_SIZE)?);
+        let expected_len = u32::from_le_bytes(len_bytes) as usize;
+        let content_i = self.index;
+
+        let elem = match tag {
+            COMPACT_NODE_TAG => {
+                let ast_reflection_style = self.read_ast_reflection_style()?;
+                let uuid = self.read_uuid()?;
+                let mut node = SyntaxNode::new(Vec::new(), ast_reflection_style, uuid);
+                node.is_presence_marker = self.read_bool()?;
+
+                node.src_range = if self.read_bool()? {
+                    Some((self.read_usize()?, self.read_usize()?))
+                } else {
+                    None
+                };
+
+                // note: 不正な子要素数で大きな領域を確保しないよう容量は指定しない
+                let child_count = self.read_usize()?;
+
+                for _ in 0..child_count {
+                    node.sub_elems.push(self.read_elem()?);
+                }
+
+                SyntaxNodeElement::Node(Box::new(node))
+            },
+            COMPACT_LEAF_TAG => {
+                let ast_reflection_style = self.read_ast_reflection_style()?;
+                let uuid = self.read_uuid()?;
+                let pos = self.read_position()?;
+                let value = self.read_string()?;
+                let mut leaf = SyntaxLeaf::new(pos, value, ast_reflection_style, uuid);
+
+                leaf.raw_value = if self.read_bool()? {
+                    Some(self.read_bytes()?.to_vec())
+                } else {
+                    None
+                };
+
+                SyntaxNodeElement::Leaf(Box::new(leaf))
+            },
+            _ => return Err(DeserializeError::UnknownTag { index: tag_i, tag: tag }),
+        };
+
+        let actual_len = self.index - content_i;
+
+        if actual_len != expected_len {
+            return Err(DeserializeError::ElementLengthMismatch { index: tag_i, expected_len: expected_len, actual_len: actual_len });
         }
+
+        return Ok(elem);
     }
+}
 
-    pub fn print(&self, ignore_hidden_elems: bool) {
-        self.print_with_details(0, &mut BufWriter::new(stdout().lock()), ignore_hidden_elems)
+// note: print_with_source() で表示するソース文字列の最大文字数
+const SOURCE_EXCERPT_MAX_LEN: usize = 32;
+
+fn format_source_span(start_pos: &CharacterPosition, end_pos: &CharacterPosition) -> String {
+    return format!("{}:{}..{}:{}", start_pos.line + 1, start_pos.column + 1, end_pos.line + 1, end_pos.column + 1);
+}
+
+// spec: 範囲の先頭行のみを表示し、複数行にわたるか最大文字数を超える場合は末尾を "…" で省略する
+fn get_source_excerpt(src: &str, start_pos: &CharacterPosition, end_pos: &CharacterPosition) -> String {
+    let covered_text = src.chars().skip(start_pos.index).take(end_pos.index.saturating_sub(start_pos.index)).collect::<String>();
+
+    let (first_line, is_multiline) = match covered_text.find('\n') {
+        Some(line_end_i) => (&covered_text[..line_end_i], true),
+        None => (covered_text.as_str(), false),
+    };
+
+    let mut excerpt = escape_source_text(&first_line.chars().take(SOURCE_EXCERPT_MAX_LEN).collect::<String>());
+
+    if is_multiline || first_line.chars().count() > SOURCE_EXCERPT_MAX_LEN {
+        excerpt += "…";
     }
 
-    pub fn print_with_details(&self, nest: usize, writer: &mut BufWriter<StdoutLock>, ignore_hidden_elems: bool) {
-        match self {
-            SyntaxNodeElement::Node(node) => node.print_with_details(nest, writer, ignore_hidden_elems),
-            SyntaxNodeElement::Leaf(leaf) => leaf.print_with_details(nest, writer, ignore_hidden_elems),
+    return excerpt;
+}
+
+// note: 構文解析器のログでも用いる
+pub(crate) fn escape_source_text(s: &str) -> String {
+    let mut escaped = String::new();
+
+    for each_char in s.chars() {
+        match each_char {
+            '\\' => escaped += "\\\\",
+            '"' => escaped += "\\\"",
+            '\n' => escaped += "\\n",
+            '\t' => escaped += "\\t",
+            '\r' => escaped += "\\r",
+            '\0' => escaped += "\\0",
+            _ if each_char.is_control() => escaped += &format!("\\u{{{:x}}}", each_char as u32),
+            _ => escaped.push(each_char),
         }
     }
+
+    return escaped;
 }
 
-#[derive(Clone)]
-pub struct SyntaxTree {
-    child: SyntaxNodeElement,
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ASTVisibility {
+    // note: AST に反映される
+    Reflect,
+    // note: AST に反映されない
+    Hide,
+    // note: 子要素を親ノードに展開する
+    Expand,
 }
 
-impl SyntaxTree {
-    pub fn from_node(node: SyntaxNodeElement) -> SyntaxTree {
-        return SyntaxTree {
-            child: node,
+// spec: 反映の有無と反映名を独立して持つ; 展開方式でも反映名を指定すると展開された子要素の反映名を置き換える
+#[derive(Clone, PartialEq)]
+pub struct ASTReflectionStyle {
+    pub visibility: ASTVisibility,
+    // note: None であれば要素ごとの既定名 (規則 ID 等) となる
+    pub name_override: Option<String>,
+}
+
+impl ASTReflectionStyle {
+    pub fn new(visibility: ASTVisibility, name_override: Option<String>) -> ASTReflectionStyle {
+        return ASTReflectionStyle {
+            visibility: visibility,
+            name_override: name_override,
         };
     }
 
-    pub fn from_node_args(sub_elems: Vec<SyntaxNodeElement>, ast_reflection_style: ASTReflectionStyle) -> SyntaxTree {
-        return SyntaxTree {
-            child: SyntaxNodeElement::Node(Box::new(SyntaxNode::new(sub_elems, ast_reflection_style, Uuid::new_v4()))),
+    // note: 旧 Reflection(name) に相当する; 空文字は反映名なしとして扱う
+    pub fn reflection(elem_name: String) -> ASTReflectionStyle {
+        let name_override = if elem_name == "" {
+            None
+        } else {
+            Some(elem_name)
         };
+
+        return ASTReflectionStyle::new(ASTVisibility::Reflect, name_override);
     }
 
-    pub fn print(&self, ignore_hidden_elems: bool) {
-        self.child.print(ignore_hidden_elems)
+    // note: 旧 NoReflection に相当する
+    pub fn no_reflection() -> ASTReflectionStyle {
+        return ASTReflectionStyle::new(ASTVisibility::Hide, None);
     }
 
-    pub fn get_child_ref(&self) -> &SyntaxNodeElement {
-        return &self.child;
+    // note: 旧 Expansion に相当する
+    pub fn expansion() -> ASTReflectionStyle {
+        return ASTReflectionStyle::new(ASTVisibility::Expand, None);
+    }
+
+    // spec: 反映方式が指定されていない要素に用いる; 構成ファイルで反転が指定されていれば非反映とする
+    pub fn get_default(reverse_ast_reflection: bool) -> ASTReflectionStyle {
+        return if reverse_ast_reflection {
+            ASTReflectionStyle::no_reflection()
+        } else {
+            ASTReflectionStyle::reflection(String::new())
+        };
+    }
+
+    // ret: 反映名が指定されていなければ空文字
+    pub fn get_name(&self) -> &str {
+        return match &self.name_override {
+            Some(v) => v,
+            None => "",
+        };
+    }
+
+    pub fn is_reflected_as(&self, elem_name: &str) -> bool {
+        return self.visibility == ASTVisibility::Reflect && self.get_name() == elem_name;
+    }
+
+    pub fn is_reflectable(&self) -> bool {
+        return self.visibility != ASTVisibility::Hide;
+    }
+
+    pub fn is_expandable(&self) -> bool {
+        return self.visibility == ASTVisibility::Expand;
+    }
+
+    // spec: 展開された子要素に適用する; 反映名が指定されていれば反映される子要素の反映名を置き換える
+    pub fn apply_to_expanded_elems(&self, mut elems: Vec<SyntaxNodeElement>) -> Vec<SyntaxNodeElement> {
+        match &self.name_override {
+            Some(elem_name) => {
+                for each_elem in &mut elems {
+                    if each_elem.is_reflectable() {
+                        each_elem.set_ast_reflection_style(ASTReflectionStyle::reflection(elem_name.clone()));
+                    }
+                }
+            },
+            None => (),
+        }
+
+        return elems;
+    }
+
+    pub fn to_sexp_name(&self) -> String {
+        return match (&self.visibility, &self.name_override) {
+            (ASTVisibility::Reflect, Some(elem_name)) => elem_name.clone(),
+            (ASTVisibility::Reflect, None) => "_".to_string(),
+            (ASTVisibility::Hide, _) => "#".to_string(),
+            (ASTVisibility::Expand, _) => format!("##{}", self.get_name()),
+        };
+    }
+
+    // spec: JSON 出力で用いる反映性の名前
+    #[cfg(feature = "json")]
+    fn get_visibility_name(&self) -> &'static str {
+        return match &self.visibility {
+            ASTVisibility::Reflect => "reflect",
+            ASTVisibility::Hide => "hide",
+            ASTVisibility::Expand => "expand",
+        };
+    }
+
+    fn write_compact(&self, bytes: &mut Vec<u8>) {
+        let visibility = match &self.visibility {
+            ASTVisibility::Reflect => 0,
+            ASTVisibility::Hide => 1,
+            ASTVisibility::Expand => 2,
+        };
+
+        bytes.push(visibility);
+        write_compact_option_str(bytes, &self.name_override);
+    }
+
+    // note: 反映名なしの展開方式は以前の列挙型と同じハッシュ値になる
+    pub fn write_hash(&self, hasher: &mut FxHasher64) {
+        match &self.visibility {
+            ASTVisibility::Reflect => {
+                hasher.write_u8(0);
+                write_str_hash(hasher, self.get_name());
+            },
+            ASTVisibility::Hide => hasher.write_u8(1),
+            ASTVisibility::Expand => {
+                hasher.write_u8(2);
+
+                match &self.name_override {
+                    Some(elem_name) => write_str_hash(hasher, elem_name),
+                    None => (),
+                }
+            },
+        }
     }
 }
 
-#[derive(Clone)]
-pub struct SyntaxNode {
-    pub sub_elems: Vec<SyntaxNodeElement>,
-    pub ast_reflection_style: ASTReflectionStyle,
+impl Display for ASTReflectionStyle {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let s = match &self.visibility {
+            ASTVisibility::Reflect => format!("#{}", self.get_name()),
+            ASTVisibility::Hide => String::new(),
+            ASTVisibility::Expand => format!("##{}", self.get_name()),
+        };
+
+        return write!(f, "{}", s);
+    }
+}
+
+#[derive(Clone)]
+pub enum SyntaxNodeElement {
+    Node(Box<SyntaxNode>),
+    Leaf(Box<SyntaxLeaf>),
+}
+
+impl SyntaxNodeElement {
+    pub fn from_node_args(sub_elems: Vec<SyntaxNodeElement>, ast_reflection_style: ASTReflectionStyle) -> SyntaxNodeElement {
+        return SyntaxNodeElement::Node(Box::new(SyntaxNode::new(sub_elems, ast_reflection_style, Uuid::new_v4())));
+    }
+
+    // spec: 存在マーカー付きグループが一致したことを表すノード; 子要素がなくても取り除かれない
+    pub fn from_presence_node_args(sub_elems: Vec<SyntaxNodeElement>, ast_reflection_style: ASTReflectionStyle) -> SyntaxNodeElement {
+        let mut node = SyntaxNode::new(sub_elems, ast_reflection_style, Uuid::new_v4());
+        node.is_presence_marker = true;
+        return SyntaxNodeElement::Node(Box::new(node));
+    }
+
+    pub fn from_leaf_args(pos: CharacterPosition, value: String, ast_reflection: ASTReflectionStyle) -> SyntaxNodeElement {
+        return SyntaxNodeElement::Leaf(Box::new(SyntaxLeaf::new(pos, value, ast_reflection, Uuid::new_v4())));
+    }
+
+    fn decode_byte_leaves(&mut self) {
+        match self {
+            SyntaxNodeElement::Node(node) => {
+                for each_elem in &mut node.sub_elems {
+                    each_elem.decode_byte_leaves();
+                }
+            },
+            SyntaxNodeElement::Leaf(leaf) => leaf.decode_byte_value(),
+        }
+    }
+
+    pub fn get_node(&self, cons: &Rc<RefCell<Console>>) -> ConsoleResult<&SyntaxNode> {
+        return self.try_get_node().map_err(|e| e.append_to(cons));
+    }
+
+    // spec: get_node() と同様だがログを出力せずにエラーを返す
+    pub fn try_get_node(&self) -> std::result::Result<&SyntaxNode, TreeLog> {
+        return match self {
+            SyntaxNodeElement::Node(node) => Ok(node),
+            SyntaxNodeElement::Leaf(leaf) => Err(TreeLog::ElementNotNode {
+                uuid: leaf.uuid.clone(),
+                src_path: leaf.pos.file_path.clone(),
+            }),
+        };
+    }
+
+    pub fn get_leaf(&self, cons: &Rc<RefCell<Console>>) -> ConsoleResult<&SyntaxLeaf> {
+        return self.try_get_leaf().map_err(|e| e.append_to(cons));
+    }
+
+    // spec: get_leaf() と同様だがログを出力せずにエラーを返す
+    pub fn try_get_leaf(&self) -> std::result::Result<&SyntaxLeaf, TreeLog> {
+        return match self {
+            SyntaxNodeElement::Node(node) => Err(TreeLog::ElementNotLeaf {
+                uuid: node.uuid.clone(),
+                src_path: node.get_src_path(),
+            }),
+            SyntaxNodeElement::Leaf(leaf) => Ok(leaf),
+        };
+    }
+
+    // ret: リーフであればその位置、ノードであれば子孫のうち最初のリーフ (非反映的なものを含む) の位置; 子孫にリーフがなければ None
+    // note: SyntaxNode::get_position() と異なり直接の子要素以外のリーフも探し、コンソールを必要としない
+    pub fn first_leaf_position(&self) -> Option<CharacterPosition> {
+        return match self {
+            SyntaxNodeElement::Node(node) => node.sub_elems.iter().find_map(|each_elem| each_elem.first_leaf_position()),
+            SyntaxNodeElement::Leaf(leaf) => Some(leaf.pos.clone()),
+        };
+    }
+
+    // spec: SyntaxTree::fold() と同様; 自身を起点に走査する
+    pub fn fold<A, F: Fn(A, &SyntaxNodeElement) -> A>(&self, init: A, order: TraversalOrder, f: &F) -> A {
+        let mut acc = init;
+
+        if order == TraversalOrder::PreOrder {
+            acc = f(acc, self);
+        }
+
+        match self {
+            SyntaxNodeElement::Node(node) => {
+                for each_elem in &node.sub_elems {
+                    acc = each_elem.fold(acc, order, f);
+                }
+            },
+            SyntaxNodeElement::Leaf(_) => (),
+        }
+
+        if order == TraversalOrder::PostOrder {
+            acc = f(acc, self);
+        }
+
+        return acc;
+    }
+
+    // note: 祖先と同じ UUID は重複としてではなく自己参照として報告する; 位置を持たないリーフ (構文解析器以外が生成したもの) は位置の検査から除く
+    #[cfg(debug_assertions)]
+    fn collect_invariant_violations(&self, uuids: &mut std::collections::HashSet<Uuid>, ancestor_uuids: &mut Vec<Uuid>, last_leaf_index: &mut Option<usize>, violations: &mut Vec<InvariantViolation>) {
+        let uuid = match self {
+            SyntaxNodeElement::Node(node) => node.uuid,
+            SyntaxNodeElement::Leaf(leaf) => leaf.uuid,
+        };
+
+        if ancestor_uuids.contains(&uuid) {
+            violations.push(InvariantViolation::SelfReference { uuid: uuid });
+        } else if !uuids.insert(uuid) {
+            violations.push(InvariantViolation::DuplicateUuid { uuid: uuid });
+        }
+
+        match self {
+            SyntaxNodeElement::Node(node) => {
+                if node.ast_reflection_style.is_expandable() && node.sub_elems.len() == 0 {
+                    violations.push(InvariantViolation::EmptyExpansionNode { uuid: uuid });
+                }
+
+                ancestor_uuids.push(uuid);
+
+                for each_elem in &node.sub_elems {
+                    each_elem.collect_invariant_violations(uuids, ancestor_uuids, last_leaf_index, violations);
+                }
+
+                ancestor_uuids.pop();
+            },
+            SyntaxNodeElement::Leaf(leaf) => {
+                if leaf.pos.file_path.is_some() {
+                    match *last_leaf_index {
+                        Some(prev_index) if leaf.pos.index < prev_index => violations.push(InvariantViolation::LeafPositionDecreased {
+                            uuid: uuid,
+                            prev_index: prev_index,
+                            index: leaf.pos.index,
+                        }),
+                        _ => (),
+                    }
+
+                    *last_leaf_index = Some(leaf.pos.index);
+                }
+            },
+        }
+    }
+
+    // ret: 子要素を持たず存在マーカーでもないノードであれば true
+    pub fn is_empty_node(&self) -> bool {
+        return match self {
+            SyntaxNodeElement::Node(node) => node.sub_elems.len() == 0 && !node.is_presence_marker,
+            SyntaxNodeElement::Leaf(_) => false,
+        };
+    }
+
+    pub fn is_node(&self) -> bool {
+        return match self {
+            SyntaxNodeElement::Node(_) => true,
+            _ => false,
+        };
+    }
+
+    pub fn is_reflectable(&self) -> bool {
+        return match self {
+            SyntaxNodeElement::Node(node) => node.is_reflectable(),
+            SyntaxNodeElement::Leaf(leaf) => leaf.is_reflectable(),
+        };
+    }
+
+    pub fn get_ast_reflection_style(&self) -> ASTReflectionStyle {
+        return match self {
+            SyntaxNodeElement::Node(node) => node.ast_reflection_style.clone(),
+            SyntaxNodeElement::Leaf(leaf) => leaf.ast_reflection_style.clone(),
+        };
+    }
+
+    pub fn set_ast_reflection_style(&mut self, ast_reflection_style: ASTReflectionStyle) {
+        match self {
+            SyntaxNodeElement::Node(node) => node.ast_reflection_style = ast_reflection_style,
+            SyntaxNodeElement::Leaf(leaf) => leaf.ast_reflection_style = ast_reflection_style,
+        }
+    }
+
+    // note: リーフは自身の位置と値を持つため対象外
+    pub fn set_src_range(&mut self, src_range: Option<(usize, usize)>) {
+        match self {
+            SyntaxNodeElement::Node(node) => node.src_range = src_range,
+            SyntaxNodeElement::Leaf(_) => (),
+        }
+    }
+
+    #[cfg(feature = "terminal")]
+    pub fn print(&self, ignore_hidden_elems: bool) {
+        self.print_with_details(0, &mut BufWriter::new(stdout().lock()), ignore_hidden_elems).unwrap();
+    }
+
+    pub fn print_with_details<W: Write>(&self, nest: usize, writer: &mut W, ignore_hidden_elems: bool) -> std::io::Result<()> {
+        return match self {
+            SyntaxNodeElement::Node(node) => node.print_with_details(nest, writer, ignore_hidden_elems),
+            SyntaxNodeElement::Leaf(leaf) => leaf.print_with_details(nest, writer, ignore_hidden_elems),
+        };
+    }
+
+    fn collect_source_lines(&self, nest: usize, src: &str, ignore_hidden_elems: bool, lines: &mut Vec<(String, String)>) {
+        match self {
+            SyntaxNodeElement::Node(node) => node.collect_source_lines(nest, src, ignore_hidden_elems, lines),
+            SyntaxNodeElement::Leaf(leaf) => leaf.collect_source_lines(nest, ignore_hidden_elems, lines),
+        }
+    }
+
+    pub fn write_hash(&self, hasher: &mut FxHasher64) {
+        match self {
+            SyntaxNodeElement::Node(node) => {
+                hasher.write_u8(0);
+                node.write_hash(hasher);
+            },
+            SyntaxNodeElement::Leaf(leaf) => {
+                hasher.write_u8(1);
+                leaf.write_hash(hasher);
+            },
+        }
+    }
+
+    fn write_compact(&self, bytes: &mut Vec<u8>) {
+        match self {
+            SyntaxNodeElement::Node(node) => node.write_compact(bytes),
+            SyntaxNodeElement::Leaf(leaf) => leaf.write_compact(bytes),
+        }
+    }
+
+    fn get_reflected_value_len(&self) -> usize {
+        if !self.is_reflectable() {
+            return 0;
+        }
+
+        return match self {
+            SyntaxNodeElement::Node(node) => node.sub_elems.iter().map(|each_elem| each_elem.get_reflected_value_len()).sum(),
+            SyntaxNodeElement::Leaf(leaf) => leaf.get_bytes().len(),
+        };
+    }
+
+    // ret: ignore_hidden_elems が有効かつ Unreflectable な要素であれば None
+    pub fn to_sexp(&self, ignore_hidden_elems: bool) -> Option<String> {
+        return match self {
+            SyntaxNodeElement::Node(node) => node.to_sexp(ignore_hidden_elems),
+            SyntaxNodeElement::Leaf(leaf) => leaf.to_sexp(ignore_hidden_elems),
+        };
+    }
+
+    // ret: ignore_hidden_elems が有効かつ Unreflectable な要素であれば None
+    #[cfg(feature = "json")]
+    pub fn to_json(&self, ignore_hidden_elems: bool) -> Option<serde_json::Value> {
+        return match self {
+            SyntaxNodeElement::Node(node) => node.to_json(ignore_hidden_elems),
+            SyntaxNodeElement::Leaf(leaf) => leaf.to_json(ignore_hidden_elems),
+        };
+    }
+}
+
+#[derive(Clone)]
+pub struct SyntaxTree {
+    child: SyntaxNodeElement,
+}
+
+impl SyntaxTree {
+    pub fn from_node(node: SyntaxNodeElement) -> SyntaxTree {
+        return SyntaxTree {
+            child: node,
+        };
+    }
+
+    pub fn from_node_args(sub_elems: Vec<SyntaxNodeElement>, ast_reflection_style: ASTReflectionStyle) -> SyntaxTree {
+        return SyntaxTree {
+            child: SyntaxNodeElement::Node(Box::new(SyntaxNode::new(sub_elems, ast_reflection_style, Uuid::new_v4()))),
+        };
+    }
+
+    #[cfg(feature = "terminal")]
+    pub fn print(&self, ignore_hidden_elems: bool) {
+        self.print_to_writer(&mut BufWriter::new(stdout().lock()), ignore_hidden_elems).unwrap();
+    }
+
+    // spec: print() と同じ形式で任意の出力先へ書き込む
+    pub fn print_to_writer<W: Write>(&self, writer: &mut W, ignore_hidden_elems: bool) -> std::io::Result<()> {
+        self.child.print_with_details(0, writer, ignore_hidden_elems)?;
+        return writer.flush();
+    }
+
+    // note: テストやログへの埋め込みなど出力内容を文字列として扱う場合に用いる
+    pub fn print_to_string(&self, ignore_hidden_elems: bool) -> String {
+        let mut buf = Vec::<u8>::new();
+        // note: Vec<u8> への書き込みは失敗しない
+        self.print_to_writer(&mut buf, ignore_hidden_elems).unwrap();
+        return String::from_utf8_lossy(&buf).into_owned();
+    }
+
+    #[cfg(feature = "terminal")]
+    pub fn print_with_source(&self, src: &str, ignore_hidden_elems: bool) {
+        self.write_with_source(&mut BufWriter::new(stdout().lock()), src, ignore_hidden_elems).unwrap();
+    }
+
+    // spec: 各要素の範囲 (行:列..行:列) と、ノードであれば覆っているソース文字列を木構造の右側に列を揃えて出力する
+    pub fn write_with_source<W: Write>(&self, writer: &mut W, src: &str, ignore_hidden_elems: bool) -> std::io::Result<()> {
+        let mut lines = Vec::<(String, String)>::new();
+        self.child.collect_source_lines(0, src, ignore_hidden_elems, &mut lines);
+
+        let label_width = match lines.iter().map(|(label, _)| label.chars().count()).max() {
+            Some(v) => v,
+            None => 0,
+        };
+
+        for (each_label, each_annotation) in lines {
+            let padding = " ".repeat(label_width - each_label.chars().count());
+            writeln!(writer, "{}{}  {}", each_label, padding, each_annotation)?;
+        }
+
+        return writer.flush();
+    }
+
+    pub fn get_child_ref(&self) -> &SyntaxNodeElement {
+        return &self.child;
+    }
+
+    // ret: ルートノードの SyntaxNode::depth(); ルートがリーフであれば 0
+    pub fn depth(&self) -> usize {
+        return match &self.child {
+            SyntaxNodeElement::Node(node) => node.depth(),
+            SyntaxNodeElement::Leaf(_) => 0,
+        };
+    }
+
+    pub fn into_child(self) -> SyntaxNodeElement {
+        return self.child;
+    }
+
+    // spec: 子要素の除去後に Reflectable な子要素を持たないノードを再帰的に取り除く; ルートノードと存在マーカーのノードは除去しない
+    pub fn prune_empty_nodes(&self) -> SyntaxTree {
+        let pruned_child = match &self.child {
+            SyntaxNodeElement::Node(node) => SyntaxNodeElement::Node(Box::new(node.to_pruned_node())),
+            SyntaxNodeElement::Leaf(_) => self.child.clone(),
+        };
+
+        return SyntaxTree::from_node(pruned_child);
+    }
+
+    // spec: 各ノードの子要素のうち、AST 反映方式の等しい連続するリーフを 1 つのリーフにまとめた構文木を返す
+    // note: まとめたリーフは先頭のリーフの位置と UUID を持ち、値は連結したものとなる
+    pub fn merge_adjacent_leaves(&self) -> SyntaxTree {
+        let merged_child = match &self.child {
+            SyntaxNodeElement::Node(node) => SyntaxNodeElement::Node(Box::new(node.to_merged_node())),
+            SyntaxNodeElement::Leaf(_) => self.child.clone(),
+        };
+
+        return SyntaxTree::from_node(merged_child);
+    }
+
+    // spec: options で有効にした簡約を子から親の順に適用した構文木を返す; 自身は変更しない
+    // spec: ルートノードは除去しないが、畳み込みの対象であれば唯一の子要素をルートとする
+    pub fn simplify(&self, options: SimplifyOptions) -> SyntaxTree {
+        let simplified_child = match &self.child {
+            SyntaxNodeElement::Node(node) => node.to_simplified_node(&options).into_simplified_elem(&options),
+            SyntaxNodeElement::Leaf(_) => self.child.clone(),
+        };
+
+        return SyntaxTree::from_node(simplified_child);
+    }
+
+    // spec: 子から親の順に各ノードへ transform を適用した構文木を返す; 親には変換後の子要素を持つノードを渡す
+    // spec: ルートノードは取り除かない; transform が None を返した場合は子要素の変換のみを反映したノードとする
+    pub fn apply_transform<T: TreeTransform>(&self, transform: &T) -> SyntaxTree {
+        let transformed_child = match &self.child {
+            SyntaxNodeElement::Node(node) => {
+                let transformed_node = node.to_transformed_node(transform);
+
+                match transform.transform_node(&transformed_node) {
+                    Some(v) => v,
+                    None => SyntaxNodeElement::Node(Box::new(transformed_node)),
+                }
+            },
+            SyntaxNodeElement::Leaf(_) => self.child.clone(),
+        };
+
+        return SyntaxTree::from_node(transformed_child);
+    }
+
+    // ret: 対象ノードが一致した入力の部分文字列; ノードが木に存在しないか一致範囲を持たなければ None
+    // spec: src は構文解析器に渡した入力; 構文解析器が取り除く 0x0d は文字インデックスとして数えず、範囲の内側にあれば部分文字列に含める
+    // note: 範囲が入力の終端を超える場合 (EOF 用のヌル文字) は終端までとする
+    pub fn get_matched_str<'a>(&self, src: &'a str, target: &Uuid) -> Option<&'a str> {
+        let (start_i, len) = match &self.child {
+            SyntaxNodeElement::Node(node) => node.find_node(target)?.matched_range()?,
+            SyntaxNodeElement::Leaf(_) => return None,
+        };
+
+        let char_byte_ranges = src.char_indices().filter(|(_, each_char)| *each_char != '\r').map(|(byte_i, each_char)| (byte_i, byte_i + each_char.len_utf8())).collect::<Vec<(usize, usize)>>();
+
+        let start_byte_i = match char_byte_ranges.get(start_i) {
+            Some((byte_i, _)) => *byte_i,
+            None => src.len(),
+        };
+
+        let end_byte_i = if len == 0 {
+            start_byte_i
+        } else {
+            match char_byte_ranges.get(start_i + len - 1) {
+                Some((_, byte_i)) => *byte_i,
+                None => src.len(),
+            }
+        };
+
+        return Some(&src[start_byte_i..end_byte_i]);
+    }
+
+    // spec: バイト列モードで解析した構文木のすべてのリーフの値を UTF-8 の文字列と元のバイト列に変換する
+    pub fn decode_byte_leaves(mut self) -> SyntaxTree {
+        self.child.decode_byte_leaves();
+        return self;
+    }
+
+    // note: UUID を含まない決定的な S 式表現; テストでの木の比較に用いる
+    pub fn to_sexp(&self, ignore_hidden_elems: bool) -> String {
+        return match self.child.to_sexp(ignore_hidden_elems) {
+            Some(v) => v,
+            None => String::new(),
+        };
+    }
+
+    // spec: ノードは {"kind": "node", "name", "visibility", "range", "children"}, リーフは {"kind": "leaf", "name", "visibility", "value", "line", "column"} の形式
+    // note: name は反映名がなければ null; range は [先頭の文字インデックス, 文字数] もしくは null; line, column は 0 始まり
+    #[cfg(feature = "json")]
+    pub fn to_json(&self, ignore_hidden_elems: bool) -> serde_json::Value {
+        return match self.child.to_json(ignore_hidden_elems) {
+            Some(v) => v,
+            None => serde_json::Value::Null,
+        };
+    }
+
+    // ret: 反映されるリーフの値の合計長 (バイト数) を入力長で割った値; 入力長が 0 であれば 1.0
+    // spec: 非反映的なノード以下のリーフは含めない; 値が大きく 1.0 を下回る場合は非反映的な空白やコメントの規則が入力の大部分を消費している
+    pub fn coverage(&self, total_input_len: usize) -> f64 {
+        if total_input_len == 0 {
+            return 1.0;
+        }
+
+        return self.child.get_reflected_value_len() as f64 / total_input_len as f64;
+    }
+
+    // spec: 反映名・リーフの値・木構造から決定的なハッシュ値を計算する; ランダムな UUID と文字位置は含めない
+    pub fn compute_hash(&self) -> u64 {
+        let mut hasher = FxHasher64::default();
+        self.child.write_hash(&mut hasher);
+        return hasher.finish();
+    }
+
+    // spec: ルートを含むすべての要素 (非反映的な要素を含む) を指定の順で走査し、累積値を f で更新する
+    // note: 例えばリーフ数は tree.fold(0, TraversalOrder::PreOrder, |n, elem| if elem.is_node() { n } else { n + 1 }) で求まる
+    pub fn fold<A, F: Fn(A, &SyntaxNodeElement) -> A>(&self, init: A, order: TraversalOrder, f: F) -> A {
+        return self.child.fold(init, order, &f);
+    }
+
+    // spec: すべての要素 (非反映的な要素を含む) に f の結果を注釈として付加した、同じ構造の木を返す; f は行きがけ順に呼び出される
+    pub fn annotate<A: Clone, F: Fn(&SyntaxNodeElement) -> A>(&self, f: F) -> AnnotatedTree<A> {
+        return AnnotatedTree::from_tree(self, &f);
+    }
+
+    // ret: すべてのリーフを入力順 (行きがけ順) に並べたもの
+    // spec: include_hidden が偽であれば非反映的なリーフと非反映的なノード以下のリーフを含めない; 深い構文木でもスタックが溢れないよう再帰せずに走査する
+    pub fn to_token_stream(&self, include_hidden: bool) -> Vec<&SyntaxLeaf> {
+        let mut leaves = Vec::<&SyntaxLeaf>::new();
+        let mut elem_stack = vec![&self.child];
+
+        loop {
+            let each_elem = match elem_stack.pop() {
+                Some(v) => v,
+                None => break,
+            };
+
+            if !include_hidden && !each_elem.is_reflectable() {
+                continue;
+            }
+
+            match each_elem {
+                SyntaxNodeElement::Node(node) => elem_stack.extend(node.sub_elems.iter().rev()),
+                SyntaxNodeElement::Leaf(leaf) => leaves.push(leaf),
+            }
+        }
+
+        return leaves;
+    }
+
+    // spec: 構文木を変更する処理の後の不整合を検査する; デバッグビルドでのみ使用できる
+    // spec: UUID が木全体で一意であること, 展開方式のノードが子要素を持つこと, リーフの位置が出現順に減少しないこと, 要素が祖先と同じ UUID を持たないことを検査する
+    // ret: 検出した不整合を走査順 (行きがけ順) に並べたもの; 不整合がなければ空
+    #[cfg(debug_assertions)]
+    pub fn verify_invariants(&self) -> Vec<InvariantViolation> {
+        let mut violations = Vec::<InvariantViolation>::new();
+        let mut uuids = std::collections::HashSet::<Uuid>::new();
+        let mut ancestor_uuids = Vec::<Uuid>::new();
+        let mut last_leaf_index = None;
+        self.child.collect_invariant_violations(&mut uuids, &mut ancestor_uuids, &mut last_leaf_index, &mut violations);
+        return violations;
+    }
+}
+
+// spec: SyntaxTree::apply_transform() で各ノードに適用する変換
+pub trait TreeTransform {
+    // ret: ノードを置き換える要素; None であればノードを取り除く
+    // note: 変更しないノードは複製して返す
+    fn transform_node(&self, node: &SyntaxNode) -> Option<SyntaxNodeElement>;
+}
+
+// spec: 各ノードに 0 番目の変換を適用し、結果がノードであれば続けて 1 番目の変換を適用する
+// note: 結果がリーフであれば 1 番目の変換は適用しない
+pub struct ComposeTransform<T1: TreeTransform, T2: TreeTransform>(pub T1, pub T2);
+
+impl<T1: TreeTransform, T2: TreeTransform> TreeTransform for ComposeTransform<T1, T2> {
+    fn transform_node(&self, node: &SyntaxNode) -> Option<SyntaxNodeElement> {
+        return match self.0.transform_node(node)? {
+            SyntaxNodeElement::Node(transformed_node) => self.1.transform_node(&transformed_node),
+            SyntaxNodeElement::Leaf(leaf) => Some(SyntaxNodeElement::Leaf(leaf)),
+        };
+    }
+}
+
+// spec: SyntaxTree::simplify() で適用する簡約; 各項目は独立して指定できる
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SimplifyOptions {
+    // note: 非反映的な子要素 (ノード以下を含む) を取り除く
+    pub drop_hidden_elems: bool,
+    // note: Reflectable な子要素を持たないノードを取り除く; 存在マーカーのノードは残す
+    pub remove_empty_nodes: bool,
+    // note: 子要素が 1 つのみで、反映名がないか子要素と同じ反映名を持つノードを子要素で置き換える
+    pub collapse_single_child_nodes: bool,
+    // note: 同じ親の下で連続する AST 反映方式の等しいリーフを先頭のリーフの位置で 1 つにまとめる
+    pub merge_adjacent_leaves: bool,
+}
+
+impl SimplifyOptions {
+    // note: すべての簡約を有効にする
+    pub fn all() -> SimplifyOptions {
+        return SimplifyOptions {
+            drop_hidden_elems: true,
+            remove_empty_nodes: true,
+            collapse_single_child_nodes: true,
+            merge_adjacent_leaves: true,
+        };
+    }
+
+    pub fn with_drop_hidden_elems(mut self, drop_hidden_elems: bool) -> SimplifyOptions {
+        self.drop_hidden_elems = drop_hidden_elems;
+        return self;
+    }
+
+    pub fn with_remove_empty_nodes(mut self, remove_empty_nodes: bool) -> SimplifyOptions {
+        self.remove_empty_nodes = remove_empty_nodes;
+        return self;
+    }
+
+    pub fn with_collapse_single_child_nodes(mut self, collapse_single_child_nodes: bool) -> SimplifyOptions {
+        self.collapse_single_child_nodes = collapse_single_child_nodes;
+        return self;
+    }
+
+    pub fn with_merge_adjacent_leaves(mut self, merge_adjacent_leaves: bool) -> SimplifyOptions {
+        self.merge_adjacent_leaves = merge_adjacent_leaves;
+        return self;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TraversalOrder {
+    // note: 親を子より先に訪れる
+    PreOrder,
+    // note: 子を親より先に訪れる
+    PostOrder,
+}
+
+pub struct SyntaxNode {
+    pub sub_elems: Vec<SyntaxNodeElement>,
+    pub ast_reflection_style: ASTReflectionStyle,
     pub uuid: Uuid,
+    // note: 存在マーカー付きグループの一致により生成されたノードか
+    pub is_presence_marker: bool,
+    // note: 一致した入力の範囲 (先頭の文字インデックス, 文字数); 構文解析器が生成したノードでなければ None
+    pub src_range: Option<(usize, usize)>,
 }
 
-impl SyntaxNode {
-    pub fn new(sub_elems: Vec<SyntaxNodeElement>, ast_reflection_style: ASTReflectionStyle, uuid: Uuid) -> SyntaxNode {
-        return SyntaxNode {
-            sub_elems: sub_elems,
-            ast_reflection_style: ast_reflection_style,
-            uuid: uuid,
+impl SyntaxNode {
+    pub fn new(sub_elems: Vec<SyntaxNodeElement>, ast_reflection_style: ASTReflectionStyle, uuid: Uuid) -> SyntaxNode {
+        return SyntaxNode {
+            sub_elems: sub_elems,
+            ast_reflection_style: ast_reflection_style,
+            uuid: uuid,
+            is_presence_marker: false,
+            src_range: None,
+        };
+    }
+
+    // spec: AST 反映方式のみを置き換えたノードを返す; UUID・子要素・存在マーカーの有無・一致範囲は保持する
+    // note: 参照しか持たない場合は複製してから呼び出す
+    pub fn with_ast_reflection_style(mut self, ast_reflection_style: ASTReflectionStyle) -> SyntaxNode {
+        self.ast_reflection_style = ast_reflection_style;
+        return self;
+    }
+
+    // ret: (先頭の文字インデックス, 文字数); 先読みのみに一致した場合の文字数は 0
+    // spec: 非反映的な要素や JOIN で結合された要素も含めて一致した入力全体を表す; バイト列モードではバイト単位
+    pub fn matched_range(&self) -> Option<(usize, usize)> {
+        return self.src_range;
+    }
+
+    pub fn exists_child_node(&self, patterns: Vec<&str>) -> bool {
+        return self.find_first_child_node(patterns).is_some();
+    }
+
+    pub fn filter_children(&self, f: impl Fn(&SyntaxNodeElement) -> bool) -> Vec<&SyntaxNodeElement> {
+        let mut elems = Vec::<&SyntaxNodeElement>::new();
+
+        for each_elem in &self.sub_elems {
+            if f(each_elem) {
+                elems.push(each_elem);
+            }
+        }
+
+        return elems;
+    }
+
+    pub fn get_reflectable_children(&self) -> Vec<&SyntaxNodeElement> {
+        return self.filter_children(|each_elem| each_elem.is_reflectable());
+    }
+
+    pub fn iter_reflectable(&self) -> impl Iterator<Item = &SyntaxNodeElement> {
+        return self.sub_elems.iter().filter(|each_elem| each_elem.is_reflectable());
+    }
+
+    pub fn reflectable_len(&self) -> usize {
+        return self.iter_reflectable().count();
+    }
+
+    // note: reflectable_len() の別名; スキーマ検査などで子要素の数を数える際の名前に合わせる
+    pub fn child_count(&self) -> usize {
+        return self.reflectable_len();
+    }
+
+    // ret: Reflectable な子要素がすべてリーフであれば真; 子要素がなければ真
+    pub fn is_leaf_only(&self) -> bool {
+        return self.iter_reflectable().all(|each_elem| match each_elem {
+            SyntaxNodeElement::Node(_) => false,
+            SyntaxNodeElement::Leaf(_) => true,
+        });
+    }
+
+    // ret: 自身を根とする部分木の深さ; 子要素がなければ 0, リーフのみであれば 1
+    // spec: 非反映的な子要素も数える; 深い構文木でもスタックが溢れないよう再帰せずに走査する
+    pub fn depth(&self) -> usize {
+        let mut max_depth = 0;
+        // note: (ノード, 自身からの深さ)
+        let mut node_stack = vec![(self, 0)];
+
+        loop {
+            let (each_node, each_depth) = match node_stack.pop() {
+                Some(v) => v,
+                None => break,
+            };
+
+            for each_elem in &each_node.sub_elems {
+                max_depth = max_depth.max(each_depth + 1);
+
+                match each_elem {
+                    SyntaxNodeElement::Node(node) => node_stack.push((node, each_depth + 1)),
+                    SyntaxNodeElement::Leaf(_) => (),
+                }
+            }
+        }
+
+        return max_depth;
+    }
+
+    // ret: 最後の Reflectable な子要素
+    pub fn last_child(&self) -> Option<&SyntaxNodeElement> {
+        return self.sub_elems.iter().rev().find(|each_elem| each_elem.is_reflectable());
+    }
+
+    // ret: UUID で指定された子要素の直後にある Reflectable な子要素; 指定された要素が直接の子要素でなければ None
+    pub fn get_sibling_after(&self, uuid: &Uuid) -> Option<&SyntaxNodeElement> {
+        let child_i = self.find_child_index(uuid)?;
+        return self.sub_elems[child_i + 1..].iter().find(|each_elem| each_elem.is_reflectable());
+    }
+
+    // ret: UUID で指定された子要素の直前にある Reflectable な子要素; 指定された要素が直接の子要素でなければ None
+    pub fn get_sibling_before(&self, uuid: &Uuid) -> Option<&SyntaxNodeElement> {
+        let child_i = self.find_child_index(uuid)?;
+        return self.sub_elems[..child_i].iter().rev().find(|each_elem| each_elem.is_reflectable());
+    }
+
+    // ret: UUID で指定された子要素の Reflectable な子要素の中でのインデックス (get_child_at() のインデックス); 指定された要素が直接の子要素でないか非反映的であれば None
+    pub fn index_of_child(&self, uuid: &Uuid) -> Option<usize> {
+        let child_i = self.find_child_index(uuid)?;
+
+        if !self.sub_elems[child_i].is_reflectable() {
+            return None;
+        }
+
+        return Some(self.sub_elems[..child_i].iter().filter(|each_elem| each_elem.is_reflectable()).count());
+    }
+
+    // note: 非反映的な子要素も対象とする
+    fn find_child_index(&self, uuid: &Uuid) -> Option<usize> {
+        return self.sub_elems.iter().position(|each_elem| match each_elem {
+            SyntaxNodeElement::Node(node) => node.uuid == *uuid,
+            SyntaxNodeElement::Leaf(leaf) => leaf.uuid == *uuid,
+        });
+    }
+
+    // ret: 最初にマッチした Reflectable な子ノード
+    pub fn find_first_child_node(&self, patterns: Vec<&str>) -> Option<&SyntaxNode> {
+        for each_elem in &self.sub_elems {
+            match each_elem {
+                SyntaxNodeElement::Node(node) => {
+                    if patterns.iter().any(|s| node.ast_reflection_style.is_reflected_as(s)) {
+                        return Some(node);
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        return None;
+    }
+
+    // ret: すべてのマッチした Reflectable な子ノードの列
+    pub fn find_child_nodes(&self, patterns: Vec<&str>) -> Vec<&SyntaxNode> {
+        let mut nodes = Vec::<&SyntaxNode>::new();
+
+        for each_elem in &self.sub_elems {
+            match each_elem {
+                SyntaxNodeElement::Node(node) => {
+                    if patterns.iter().any(|s| node.ast_reflection_style.is_reflected_as(s)) {
+                        nodes.push(node);
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        return nodes;
+    }
+
+    // spec: 文法上必ず存在する子ノードを取得する; 存在しなければ親ノード名と子要素の一覧を含むメッセージでパニックする
+    pub fn child_named(&self, name: &str) -> &SyntaxNode {
+        return match self.find_first_child_node(vec![name]) {
+            Some(v) => v,
+            None => {
+                let child_names = self.sub_elems.iter().map(|each_elem| {
+                    match each_elem {
+                        SyntaxNodeElement::Node(node) => format!("node{}", node.ast_reflection_style),
+                        SyntaxNodeElement::Leaf(leaf) => format!("leaf{} {:?}", leaf.ast_reflection_style, leaf.value),
+                    }
+                }).collect::<Vec<String>>();
+
+                panic!("child node '{}' not found in node{} (children: [{}])", name, self.ast_reflection_style, child_names.join(", "));
+            },
+        };
+    }
+
+    // ret: 反映名から Reflectable な子要素の一覧へのハッシュマップ; 同名の子要素は出現順に並ぶ
+    // spec: 反映名のない子要素は空文字列をキーとする
+    pub fn named_children_map(&self) -> HashMap<&str, Vec<&SyntaxNodeElement>> {
+        let mut children_map = HashMap::<&str, Vec<&SyntaxNodeElement>>::new();
+
+        for each_elem in self.iter_reflectable() {
+            let elem_name = match each_elem {
+                SyntaxNodeElement::Node(node) => node.ast_reflection_style.get_name(),
+                SyntaxNodeElement::Leaf(leaf) => leaf.ast_reflection_style.get_name(),
+            };
+
+            children_map.entry(elem_name).or_insert_with(Vec::new).push(each_elem);
+        }
+
+        return children_map;
+    }
+
+    // ret: Reflectable な子要素の反映名 (出現順; 重複あり); 反映名のない子要素は含めない
+    pub fn reflectable_child_names(&self) -> Vec<&str> {
+        return self.iter_reflectable().filter_map(|each_elem| {
+            let elem_name = match each_elem {
+                SyntaxNodeElement::Node(node) => node.ast_reflection_style.get_name(),
+                SyntaxNodeElement::Leaf(leaf) => leaf.ast_reflection_style.get_name(),
+            };
+
+            if elem_name.len() == 0 {
+                None
+            } else {
+                Some(elem_name)
+            }
+        }).collect::<Vec<&str>>();
+    }
+
+    // ret: 自身から対象要素の親ノードまでのノード列 (親から子の順); 対象が自身であれば空, 子孫に存在しなければ None
+    // spec: target はノードもしくはリーフの UUID; 非反映的な要素も探索する
+    pub fn path_to(&self, target: &Uuid) -> Option<Vec<&SyntaxNode>> {
+        if self.uuid == *target {
+            return Some(Vec::new());
+        }
+
+        for each_elem in &self.sub_elems {
+            let sub_path = match each_elem {
+                SyntaxNodeElement::Node(node) => node.path_to(target),
+                SyntaxNodeElement::Leaf(leaf) if leaf.uuid == *target => Some(Vec::new()),
+                SyntaxNodeElement::Leaf(_) => None,
+            };
+
+            match sub_path {
+                Some(mut v) => {
+                    v.insert(0, self);
+                    return Some(v);
+                },
+                None => (),
+            }
+        }
+
+        return None;
+    }
+
+    // note: 非反映的なノードも探索する
+    fn find_node(&self, target: &Uuid) -> Option<&SyntaxNode> {
+        if self.uuid == *target {
+            return Some(self);
+        }
+
+        for each_elem in &self.sub_elems {
+            match each_elem {
+                SyntaxNodeElement::Node(node) => match node.find_node(target) {
+                    Some(v) => return Some(v),
+                    None => (),
+                },
+                SyntaxNodeElement::Leaf(_) => (),
+            }
+        }
+
+        return None;
+    }
+
+    // todo: 最初に出現したリーフの位置を返す; Unreflectable なリーフも対象にする
+    pub fn get_position(&self, cons: &Rc<RefCell<Console>>) -> ConsoleResult<CharacterPosition> {
+        return self.try_get_position().map_err(|e| e.append_to(cons));
+    }
+
+    // spec: get_position() と同様だがログを出力せずにエラーを返す
+    pub fn try_get_position(&self) -> std::result::Result<CharacterPosition, TreeLog> {
+        for each_child in self.get_children() {
+            match each_child {
+                SyntaxNodeElement::Leaf(each_leaf) => return Ok(each_leaf.pos.clone()),
+                _ => (),
+            }
+        };
+
+        return Err(TreeLog::CharacterPositionNotFound {
+            uuid: self.uuid.clone(),
+            src_path: self.get_src_path(),
+        });
+    }
+
+    // ret: (開始位置, 終了位置); 終了位置は最後のリーフの直後を指す; 子孫にリーフが存在しなければ None
+    // spec: 非反映的なリーフも範囲に含める
+    pub fn get_source_span(&self) -> Option<(CharacterPosition, CharacterPosition)> {
+        let first_leaf = self.find_edge_leaf(false)?;
+        let last_leaf = self.find_edge_leaf(true)?;
+        return Some((first_leaf.pos.clone(), last_leaf.get_end_position()));
+    }
+
+    // ret: get_source_span() の範囲を LSP の Range に対応する形式に変換したもの
+    #[cfg(feature = "lsp")]
+    pub fn to_lsp_range(&self) -> Option<((u32, u32), (u32, u32))> {
+        return match self.get_source_span() {
+            Some((start_pos, end_pos)) => Some(start_pos.to_lsp_range(&end_pos)),
+            None => None,
+        };
+    }
+
+    // ret: 子孫のうち最初のリーフ (非反映的なものを含む) の位置の入力のパス; TreeLog に付加する
+    fn get_src_path(&self) -> Option<String> {
+        return match self.find_edge_leaf(false) {
+            Some(leaf) => leaf.pos.file_path.clone(),
+            None => None,
         };
     }
 
-    pub fn exists_child_node(&self, patterns: Vec<&str>) -> bool {
-        return self.find_first_child_node(patterns).is_some();
+    fn find_edge_leaf(&self, from_end: bool) -> Option<&SyntaxLeaf> {
+        let sub_elems = if from_end {
+            self.sub_elems.iter().rev().collect::<Vec<&SyntaxNodeElement>>()
+        } else {
+            self.sub_elems.iter().collect::<Vec<&SyntaxNodeElement>>()
+        };
+
+        for each_elem in sub_elems {
+            match each_elem {
+                SyntaxNodeElement::Node(node) => match node.find_edge_leaf(from_end) {
+                    Some(v) => return Some(v),
+                    None => (),
+                },
+                SyntaxNodeElement::Leaf(leaf) => return Some(leaf),
+            }
+        }
+
+        return None;
     }
 
-    pub fn filter_children(&self, f: fn(&SyntaxNodeElement) -> bool) -> Vec<&SyntaxNodeElement> {
-        let mut elems = Vec::<&SyntaxNodeElement>::new();
+    fn to_pruned_node(&self) -> SyntaxNode {
+        let mut pruned_sub_elems = Vec::<SyntaxNodeElement>::new();
 
         for each_elem in &self.sub_elems {
-            if f(each_elem) {
-                elems.push(each_elem);
+            match each_elem {
+                SyntaxNodeElement::Node(node) => {
+                    let pruned_node = node.to_pruned_node();
+
+                    if pruned_node.is_presence_marker || pruned_node.get_reflectable_children().len() != 0 {
+                        pruned_sub_elems.push(SyntaxNodeElement::Node(Box::new(pruned_node)));
+                    }
+                },
+                SyntaxNodeElement::Leaf(_) => pruned_sub_elems.push(each_elem.clone()),
             }
         }
 
-        return elems;
+        let mut pruned_node = SyntaxNode::new(pruned_sub_elems, self.ast_reflection_style.clone(), self.uuid.clone());
+        pruned_node.is_presence_marker = self.is_presence_marker;
+        pruned_node.src_range = self.src_range;
+        return pruned_node;
     }
 
-    pub fn get_reflectable_children(&self) -> Vec<&SyntaxNodeElement> {
-        return self.filter_children(|each_elem| each_elem.is_reflectable());
-    }
+    fn to_merged_node(&self) -> SyntaxNode {
+        let mut merged_sub_elems = Vec::<SyntaxNodeElement>::new();
 
-    // ret: 最初にマッチした Reflectable な子ノード
-    pub fn find_first_child_node(&self, patterns: Vec<&str>) -> Option<&SyntaxNode> {
         for each_elem in &self.sub_elems {
             match each_elem {
-                SyntaxNodeElement::Node(node) => {
-                    match &node.ast_reflection_style {
-                        ASTReflectionStyle::Reflection(name) if patterns.iter().any(|s| s == name) => return Some(node),
+                SyntaxNodeElement::Node(node) => merged_sub_elems.push(SyntaxNodeElement::Node(Box::new(node.to_merged_node()))),
+                SyntaxNodeElement::Leaf(leaf) => {
+                    match merged_sub_elems.last_mut() {
+                        Some(SyntaxNodeElement::Leaf(last_leaf)) if last_leaf.ast_reflection_style == leaf.ast_reflection_style => {
+                            last_leaf.append(leaf);
+                            continue;
+                        },
                         _ => (),
                     }
+
+                    merged_sub_elems.push(each_elem.clone());
                 },
-                _ => (),
             }
         }
 
-        return None;
+        let mut merged_node = SyntaxNode::new(merged_sub_elems, self.ast_reflection_style.clone(), self.uuid.clone());
+        merged_node.is_presence_marker = self.is_presence_marker;
+        merged_node.src_range = self.src_range;
+        return merged_node;
     }
 
-    // ret: すべてのマッチした Reflectable な子ノードの列
-    pub fn find_child_nodes(&self, patterns: Vec<&str>) -> Vec<&SyntaxNode> {
-        let mut nodes = Vec::<&SyntaxNode>::new();
+    // spec: 子要素のノードを再帰的に変換したノードを返す; 自身は変換しない
+    fn to_transformed_node<T: TreeTransform>(&self, transform: &T) -> SyntaxNode {
+        let mut transformed_sub_elems = Vec::<SyntaxNodeElement>::new();
 
         for each_elem in &self.sub_elems {
             match each_elem {
                 SyntaxNodeElement::Node(node) => {
-                    match &node.ast_reflection_style {
-                        ASTReflectionStyle::Reflection(name) if patterns.iter().any(|s| s == name) => nodes.push(node),
-                        _ => (),
+                    match transform.transform_node(&node.to_transformed_node(transform)) {
+                        Some(v) => transformed_sub_elems.push(v),
+                        None => (),
                     }
                 },
-                _ => (),
+                SyntaxNodeElement::Leaf(_) => transformed_sub_elems.push(each_elem.clone()),
             }
         }
 
-        return nodes;
+        let mut transformed_node = SyntaxNode::new(transformed_sub_elems, self.ast_reflection_style.clone(), self.uuid.clone());
+        transformed_node.is_presence_marker = self.is_presence_marker;
+        transformed_node.src_range = self.src_range;
+        return transformed_node;
     }
 
-    // todo: 最初に出現したリーフの位置を返す; Unreflectable なリーフも対象にする
-    pub fn get_position(&self, cons: &Rc<RefCell<Console>>) -> ConsoleResult<CharacterPosition> {
-        for each_child in self.get_children() {
-            match each_child {
-                SyntaxNodeElement::Leaf(each_leaf) => return Ok(each_leaf.pos.clone()),
-                _ => (),
+    // spec: 子要素を再帰的に簡約したノードを返す; 自身の除去と畳み込みは親 (ルートであれば SyntaxTree) が判断する
+    fn to_simplified_node(&self, options: &SimplifyOptions) -> SyntaxNode {
+        let mut simplified_sub_elems = Vec::<SyntaxNodeElement>::new();
+
+        for each_elem in &self.sub_elems {
+            if options.drop_hidden_elems && !each_elem.is_reflectable() {
+                continue;
             }
-        };
 
-        cons.borrow_mut().append_log(TreeLog::CharacterPositionNotFound {
-            uuid: self.uuid.clone(),
-        }.get_log());
+            let simplified_elem = match each_elem {
+                SyntaxNodeElement::Node(node) => {
+                    let simplified_node = node.to_simplified_node(options);
+
+                    if options.remove_empty_nodes && !simplified_node.is_presence_marker && simplified_node.get_reflectable_children().len() == 0 {
+                        continue;
+                    }
+
+                    simplified_node.into_simplified_elem(options)
+                },
+                SyntaxNodeElement::Leaf(_) => each_elem.clone(),
+            };
+
+            // note: 畳み込みにより隣接したリーフもまとめるため、子要素の簡約後に判定する
+            if options.merge_adjacent_leaves {
+                match (simplified_sub_elems.last_mut(), &simplified_elem) {
+                    (Some(SyntaxNodeElement::Leaf(last_leaf)), SyntaxNodeElement::Leaf(leaf)) if last_leaf.ast_reflection_style == leaf.ast_reflection_style => {
+                        last_leaf.append(leaf);
+                        continue;
+                    },
+                    _ => (),
+                }
+            }
+
+            simplified_sub_elems.push(simplified_elem);
+        }
+
+        let mut simplified_node = SyntaxNode::new(simplified_sub_elems, self.ast_reflection_style.clone(), self.uuid.clone());
+        simplified_node.is_presence_marker = self.is_presence_marker;
+        simplified_node.src_range = self.src_range;
+        return simplified_node;
+    }
+
+    // ret: 畳み込みの対象であれば唯一の子要素; そうでなければ自身
+    // note: 非反映的なノードや存在マーカーのノードは子要素の扱いを変えるため畳み込まない
+    fn into_simplified_elem(mut self, options: &SimplifyOptions) -> SyntaxNodeElement {
+        if options.collapse_single_child_nodes && !self.is_presence_marker && self.ast_reflection_style.visibility == ASTVisibility::Reflect && self.sub_elems.len() == 1 {
+            let has_distinct_name = match &self.ast_reflection_style.name_override {
+                Some(elem_name) => !self.sub_elems[0].get_ast_reflection_style().is_reflected_as(elem_name),
+                None => false,
+            };
+
+            if !has_distinct_name {
+                return self.sub_elems.pop().unwrap();
+            }
+        }
 
-        return Err(());
+        return SyntaxNodeElement::Node(Box::new(self));
     }
 
     pub fn get_children(&self) -> &Vec<SyntaxNodeElement> {
         return &self.sub_elems;
     }
 
-    pub fn get_child_at(&self, cons: &Rc<RefCell<Console>>, index: usize) -> ConsoleResult<&SyntaxNodeElement> {
-        let mut elem_i = 0;
-        let mut reflectable_elem_i = 0;
+    // spec: 負のインデックスは末尾の Reflectable な子要素から数える (-1 で最後の子要素)
+    pub fn get_child_at(&self, cons: &Rc<RefCell<Console>>, index: i64) -> ConsoleResult<&SyntaxNodeElement> {
+        return self.try_get_child_at(index).map_err(|e| e.append_to(cons));
+    }
+
+    // spec: get_child_at() と同様だがログを出力せずにエラーを返す
+    pub fn try_get_child_at(&self, index: i64) -> std::result::Result<&SyntaxNodeElement, TreeLog> {
+        return match self.get_child_at_checked(index) {
+            Some(v) => Ok(v),
+            None => Err(TreeLog::ReflectableChildNotFound {
+                parent_uuid: self.uuid,
+                index: index,
+                src_path: self.get_src_path(),
+            }),
+        };
+    }
+
+    // spec: get_child_at() と同様だがログを出力しない
+    pub fn get_child_at_checked(&self, index: i64) -> Option<&SyntaxNodeElement> {
+        return if index < 0 {
+            self.sub_elems.iter().rev().filter(|each_elem| each_elem.is_reflectable()).nth((-(index + 1)) as usize)
+        } else {
+            self.iter_reflectable().nth(index as usize)
+        };
+    }
+
+    pub fn get_node_child_at(&self, cons: &Rc<RefCell<Console>>, index: i64) -> ConsoleResult<&SyntaxNode> {
+        return self.try_get_node_child_at(index).map_err(|e| e.append_to(cons));
+    }
+
+    pub fn try_get_node_child_at(&self, index: i64) -> std::result::Result<&SyntaxNode, TreeLog> {
+        return self.try_get_child_at(index)?.try_get_node();
+    }
+
+    pub fn get_leaf_child_at(&self, cons: &Rc<RefCell<Console>>, index: i64) -> ConsoleResult<&SyntaxLeaf> {
+        return self.try_get_leaf_child_at(index).map_err(|e| e.append_to(cons));
+    }
+
+    pub fn try_get_leaf_child_at(&self, index: i64) -> std::result::Result<&SyntaxLeaf, TreeLog> {
+        return self.try_get_child_at(index)?.try_get_leaf();
+    }
+
+    // note: Reflectable なリーフのみを数えてインデックスを指定する; ノードは飛ばす
+    pub fn get_leaf_at(&self, index: usize) -> Option<&SyntaxLeaf> {
+        let mut leaf_i = 0;
 
         for each_elem in &self.sub_elems {
-            if each_elem.is_reflectable() {
-                if reflectable_elem_i == index {
-                    return match self.sub_elems.get(elem_i) {
-                        Some(v) => Ok(&v),
-                        None => {
-                            cons.borrow_mut().append_log(TreeLog::NodeChildNotFound {
-                                parent_uuid: self.uuid.clone(),
-                                index: index,
-                            }.get_log());
-
-                            return Err(());
-                        },
-                    };
-                }
+            match each_elem {
+                SyntaxNodeElement::Leaf(leaf) if leaf.is_reflectable() => {
+                    if leaf_i == index {
+                        return Some(leaf);
+                    }
 
-                reflectable_elem_i += 1;
+                    leaf_i += 1;
+                },
+                _ => (),
             }
-
-            elem_i += 1;
         }
 
-        cons.borrow_mut().append_log(TreeLog::ReflectableChildNotFound {
-            parent_uuid: self.uuid,
-            index: index,
-        }.get_log());
-
-        return Err(());
+        return None;
     }
 
-    pub fn get_node_child_at(&self, cons: &Rc<RefCell<Console>>, index: usize) -> ConsoleResult<&SyntaxNode> {
-        return self.get_child_at(cons, index)?.get_node(cons);
+    // ret: n 番目の Reflectable なリーフの値; 数え方は get_leaf_at() と同じ
+    pub fn get_nth_leaf_value(&self, n: usize) -> Option<&str> {
+        return self.get_leaf_at(n).map(|leaf| leaf.value.as_str());
     }
 
-    pub fn get_leaf_child_at(&self, cons: &Rc<RefCell<Console>>, index: usize) -> ConsoleResult<&SyntaxLeaf> {
-        return self.get_child_at(cons, index)?.get_leaf(cons);
+    // spec: 文法上必ず存在するリーフの値を取得する; 存在しなければ親ノード名・リーフの数・要求されたインデックスを含むメッセージでパニックする
+    pub fn nth_leaf_value(&self, n: usize) -> &str {
+        return match self.get_nth_leaf_value(n) {
+            Some(v) => v,
+            None => {
+                let leaf_count = self.sub_elems.iter().filter(|each_elem| {
+                    match each_elem {
+                        SyntaxNodeElement::Leaf(leaf) => leaf.is_reflectable(),
+                        SyntaxNodeElement::Node(_) => false,
+                    }
+                }).count();
+
+                panic!("leaf #{} not found in node{} (reflectable leaves: {})", n, self.ast_reflection_style, leaf_count);
+            },
+        };
     }
 
     pub fn is_reflectable(&self) -> bool {
@@ -376,8 +1836,8 @@ impl SyntaxNode {
                     s += node.join_child_leaf_values().as_str();
                 },
                 SyntaxNodeElement::Leaf(leaf) => {
-                    match leaf.ast_reflection_style {
-                        ASTReflectionStyle::Reflection(_) => s += leaf.value.as_ref(),
+                    match leaf.ast_reflection_style.visibility {
+                        ASTVisibility::Reflect => s += leaf.value.as_ref(),
                         _ => (),
                     }
                 },
@@ -387,33 +1847,223 @@ impl SyntaxNode {
         return s;
     }
 
+    // ret: 子孫のリーフの値の長さの合計; join_child_leaf_values() と異なり非反映的なリーフも含める
+    // note: 長さは位置と同じ単位 (バイト列モードではバイト単位) とする
+    pub fn total_text_length(&self) -> usize {
+        let mut len = 0usize;
+
+        for each_elem in &self.sub_elems {
+            match each_elem {
+                SyntaxNodeElement::Node(node) => len += node.total_text_length(),
+                SyntaxNodeElement::Leaf(leaf) => len += leaf.get_value_len(),
+            }
+        }
+
+        return len;
+    }
+
+    #[cfg(feature = "terminal")]
     pub fn print(&self, ignore_hidden_elems: bool) {
-        self.print_with_details(0, &mut BufWriter::new(stdout().lock()), ignore_hidden_elems);
+        self.print_with_details(0, &mut BufWriter::new(stdout().lock()), ignore_hidden_elems).unwrap();
+    }
+
+    pub fn print_with_details<W: Write>(&self, nest: usize, writer: &mut W, ignore_hidden_elems: bool) -> std::io::Result<()> {
+        if ignore_hidden_elems && !self.is_reflectable() {
+            return Ok(());
+        }
+
+        writeln!(writer, "{}", self.get_detail_label(nest))?;
+
+        for each_elem in &self.sub_elems {
+            each_elem.print_with_details(nest + 1, writer, ignore_hidden_elems)?;
+        }
+
+        return Ok(());
     }
 
-    pub fn print_with_details(&self, nest: usize, writer: &mut BufWriter<StdoutLock>, ignore_hidden_elems: bool) {
+    fn collect_source_lines(&self, nest: usize, src: &str, ignore_hidden_elems: bool, lines: &mut Vec<(String, String)>) {
         if ignore_hidden_elems && !self.is_reflectable() {
             return;
         }
 
-        let display_name = match &self.ast_reflection_style {
-            ASTReflectionStyle::Reflection(elem_name) => {
-                if elem_name == "" {
-                    "[noname]".to_string()
-                } else {
-                    elem_name.clone()
-                }
-            },
-            ASTReflectionStyle::NoReflection => "[hidden]".to_string(),
-            ASTReflectionStyle::Expansion => "[expandable]".to_string(),
+        let annotation = match self.get_source_span() {
+            Some((start_pos, end_pos)) => format!("{}  \"{}\"", format_source_span(&start_pos, &end_pos), get_source_excerpt(src, &start_pos, &end_pos)),
+            None => "-".to_string(),
+        };
+
+        lines.push((self.get_detail_label(nest), annotation));
+
+        for each_elem in &self.sub_elems {
+            each_elem.collect_source_lines(nest + 1, src, ignore_hidden_elems, lines);
+        }
+    }
+
+    fn get_detail_label(&self, nest: usize) -> String {
+        let display_name = match (&self.ast_reflection_style.visibility, &self.ast_reflection_style.name_override) {
+            (ASTVisibility::Reflect, Some(elem_name)) => elem_name.clone(),
+            (ASTVisibility::Reflect, None) => "[noname]".to_string(),
+            (ASTVisibility::Hide, _) => "[hidden]".to_string(),
+            (ASTVisibility::Expand, Some(elem_name)) => format!("[expandable] ({})", elem_name),
+            (ASTVisibility::Expand, None) => "[expandable]".to_string(),
         };
 
         let uuid_str = self.uuid.to_string()[..8].to_string();
 
-        writeln!(writer, "|{} {} *{}", "   |".repeat(nest), display_name, uuid_str).unwrap();
+        return format!("|{} {} *{}", "   |".repeat(nest), display_name, uuid_str);
+    }
+
+    pub fn write_hash(&self, hasher: &mut FxHasher64) {
+        self.ast_reflection_style.write_hash(hasher);
+        hasher.write_u64(self.sub_elems.len() as u64);
+
+        for each_elem in &self.sub_elems {
+            each_elem.write_hash(hasher);
+        }
+    }
+
+    // spec: 形式の版 1 バイトに続けて自身を要素として書き込む; UUID・位置・一致範囲・元のバイト列を含め、非反映的な要素も省略しない
+    // note: 解析結果をファイルにキャッシュする際に用いる; 出力全体の Vec のみを確保する
+    pub fn serialize_compact(&self) -> Vec<u8> {
+        let mut bytes = vec![COMPACT_FORMAT_VERSION];
+        self.write_compact(&mut bytes);
+        return bytes;
+    }
+
+    // spec: serialize_compact() の出力を読み込む; 版が異なる場合や末尾に余分なバイトがある場合も失敗する
+    pub fn deserialize_compact(bytes: &[u8]) -> std::result::Result<SyntaxNodeElement, DeserializeError> {
+        let mut reader = CompactReader::new(bytes);
+        let version = reader.read_u8()?;
+
+        if version != COMPACT_FORMAT_VERSION {
+            return Err(DeserializeError::UnsupportedVersion { index: 0, version: version });
+        }
+
+        let elem = reader.read_elem()?;
+
+        if reader.index != bytes.len() {
+            return Err(DeserializeError::TrailingBytes { index: reader.index });
+        }
+
+        return Ok(elem);
+    }
+
+    // spec: AST 反映方式、UUID、存在マーカーか、一致範囲、子要素数、子要素の順に書き込む
+    fn write_compact(&self, bytes: &mut Vec<u8>) {
+        write_compact_elem(bytes, COMPACT_NODE_TAG, |bytes| {
+            self.ast_reflection_style.write_compact(bytes);
+            bytes.extend_from_slice(self.uuid.as_bytes());
+            bytes.push(self.is_presence_marker as u8);
+
+            match self.src_range {
+                Some((start_i, len)) => {
+                    bytes.push(1);
+                    write_compact_varint(bytes, start_i as u64);
+                    write_compact_varint(bytes, len as u64);
+                },
+                None => bytes.push(0),
+            }
+
+            write_compact_varint(bytes, self.sub_elems.len() as u64);
+
+            for each_elem in &self.sub_elems {
+                each_elem.write_compact(bytes);
+            }
+        });
+    }
+
+    // spec: (名前 子要素...) の形式; 名前が空であれば "_", 非反映的であれば "#", 展開的であれば "##"
+    pub fn to_sexp(&self, ignore_hidden_elems: bool) -> Option<String> {
+        if ignore_hidden_elems && !self.is_reflectable() {
+            return None;
+        }
+
+        let mut tokens = vec![self.ast_reflection_style.to_sexp_name()];
 
         for each_elem in &self.sub_elems {
-            each_elem.print_with_details(nest + 1, writer, ignore_hidden_elems);
+            match each_elem.to_sexp(ignore_hidden_elems) {
+                Some(v) => tokens.push(v),
+                None => (),
+            }
+        }
+
+        return Some(format!("({})", tokens.join(" ")));
+    }
+
+    #[cfg(feature = "json")]
+    pub fn to_json(&self, ignore_hidden_elems: bool) -> Option<serde_json::Value> {
+        if ignore_hidden_elems && !self.is_reflectable() {
+            return None;
+        }
+
+        let children = self.sub_elems.iter().filter_map(|each_elem| each_elem.to_json(ignore_hidden_elems)).collect::<Vec<serde_json::Value>>();
+
+        let range = match self.src_range {
+            Some((start_i, len)) => serde_json::json!([start_i, len]),
+            None => serde_json::Value::Null,
+        };
+
+        return Some(serde_json::json!({
+            "kind": "node",
+            "name": self.ast_reflection_style.name_override,
+            "visibility": self.ast_reflection_style.get_visibility_name(),
+            "range": range,
+            "children": children,
+        }));
+    }
+}
+
+// spec: 子孫のノードを再帰せずに複製する; メモ化の結果等で入れ子の深い構文木を複製する際にスタックが溢れないようにする
+impl Clone for SyntaxNode {
+    fn clone(&self) -> SyntaxNode {
+        // note: (複製元のノード, 複製済みの子要素) のスタック; 子要素をすべて複製したノードから親の子要素に加える
+        let mut stack = vec![(self, Vec::with_capacity(self.sub_elems.len()))];
+
+        loop {
+            let (node, cloned_elem_len) = match stack.last() {
+                Some((node, cloned_elems)) => (*node, cloned_elems.len()),
+                None => unreachable!(),
+            };
+
+            match node.sub_elems.get(cloned_elem_len) {
+                Some(SyntaxNodeElement::Node(sub_node)) => stack.push((sub_node.as_ref(), Vec::with_capacity(sub_node.sub_elems.len()))),
+                Some(SyntaxNodeElement::Leaf(leaf)) => match stack.last_mut() {
+                    Some((_, cloned_elems)) => cloned_elems.push(SyntaxNodeElement::Leaf(leaf.clone())),
+                    None => unreachable!(),
+                },
+                None => {
+                    let cloned_elems = match stack.pop() {
+                        Some((_, cloned_elems)) => cloned_elems,
+                        None => unreachable!(),
+                    };
+
+                    let cloned_node = SyntaxNode {
+                        sub_elems: cloned_elems,
+                        ast_reflection_style: node.ast_reflection_style.clone(),
+                        uuid: node.uuid,
+                        is_presence_marker: node.is_presence_marker,
+                        src_range: node.src_range,
+                    };
+
+                    match stack.last_mut() {
+                        Some((_, parent_elems)) => parent_elems.push(SyntaxNodeElement::Node(Box::new(cloned_node))),
+                        None => return cloned_node,
+                    }
+                },
+            }
+        }
+    }
+}
+
+// spec: 子孫のノードを再帰せずに解放する; 入れ子の深い構文木の解放でスタックが溢れないようにする
+impl Drop for SyntaxNode {
+    fn drop(&mut self) {
+        let mut elems = std::mem::take(&mut self.sub_elems);
+
+        while let Some(each_elem) = elems.pop() {
+            match each_elem {
+                SyntaxNodeElement::Node(mut node) => elems.append(&mut node.sub_elems),
+                SyntaxNodeElement::Leaf(_) => (),
+            }
         }
     }
 }
@@ -422,6 +2072,8 @@ impl SyntaxNode {
 pub struct SyntaxLeaf {
     pub pos: CharacterPosition,
     pub value: String,
+    // note: バイト列モードで解析された場合の元のバイト列; value はこれを UTF-8 として解釈した文字列
+    pub raw_value: Option<Vec<u8>>,
     pub ast_reflection_style: ASTReflectionStyle,
     pub uuid: Uuid,
 }
@@ -431,39 +2083,296 @@ impl SyntaxLeaf {
         return SyntaxLeaf {
             pos: pos,
             value: value,
+            raw_value: None,
             ast_reflection_style: ast_reflection_style,
             uuid: uuid,
         };
     }
 
+    pub fn get_bytes(&self) -> &[u8] {
+        return match &self.raw_value {
+            Some(v) => v,
+            None => self.value.as_bytes(),
+        };
+    }
+
+    // spec: 各文字を 1 バイトとして保持した値を UTF-8 の文字列と元のバイト列に変換する
+    fn decode_byte_value(&mut self) {
+        let raw_value = self.value.chars().map(|each_char| each_char as u32 as u8).collect::<Vec<u8>>();
+        self.value = String::from_utf8_lossy(&raw_value).to_string();
+        self.raw_value = Some(raw_value);
+    }
+
+    // note: バイト列モードのリーフ同士であれば元のバイト列も連結する
+    pub(crate) fn append(&mut self, other: &SyntaxLeaf) {
+        self.value += &other.value;
+
+        self.raw_value = match (self.raw_value.take(), &other.raw_value) {
+            (Some(mut raw_value), Some(other_raw_value)) => {
+                raw_value.extend(other_raw_value);
+                Some(raw_value)
+            },
+            _ => None,
+        };
+    }
+
     pub fn is_reflectable(&self) -> bool {
         return self.ast_reflection_style.is_reflectable();
     }
 
+    #[cfg(feature = "terminal")]
     pub fn print(&self, ignore_hidden_elems: bool) {
-        self.print_with_details(0, &mut BufWriter::new(stdout().lock()), ignore_hidden_elems);
+        self.print_with_details(0, &mut BufWriter::new(stdout().lock()), ignore_hidden_elems).unwrap();
+    }
+
+    // spec: 値に含まれるエスケープシーケンス (\n \t \r \0 \\ \' \" \xHH \uXXXX \u{X...}) を対応する文字に置き換える
+    // note: \xHH は U+0000 ~ U+00FF の文字となる; \uXXXX のサロゲートペアは 1 文字に結合する
+    pub fn unescape(&self) -> std::result::Result<String, UnescapeError> {
+        let chars = self.value.chars().collect::<Vec<char>>();
+        let mut unescaped = String::new();
+        let mut char_i = 0usize;
+
+        loop {
+            let each_char = match chars.get(char_i) {
+                Some(v) => *v,
+                None => break,
+            };
+
+            if each_char != '\\' {
+                unescaped.push(each_char);
+                char_i += 1;
+                continue;
+            }
+
+            let escape_i = char_i;
+
+            let esc_char = match chars.get(escape_i + 1) {
+                Some(v) => *v,
+                None => return Err(UnescapeError::UnexpectedEnd { index: escape_i }),
+            };
+
+            char_i = escape_i + 2;
+
+            let new_char = match esc_char {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                '0' => '\0',
+                '\\' => '\\',
+                '\'' => '\'',
+                '"' => '"',
+                'x' => {
+                    let code_point = SyntaxLeaf::read_hex_digits(&chars, &mut char_i, 2, escape_i)?;
+                    SyntaxLeaf::to_escaped_char(code_point, escape_i)?
+                },
+                'u' => {
+                    let code_point = if chars.get(char_i) == Some(&'{') {
+                        char_i += 1;
+                        let digit_len = chars.iter().skip(char_i).take_while(|each_char| each_char.is_ascii_hexdigit()).count();
+
+                        if digit_len == 0 {
+                            return match chars.get(char_i) {
+                                Some(v) => Err(UnescapeError::InvalidHexDigit { index: escape_i, found: *v }),
+                                None => Err(UnescapeError::UnexpectedEnd { index: escape_i }),
+                            };
+                        }
+
+                        let code_point = SyntaxLeaf::read_hex_digits(&chars, &mut char_i, digit_len, escape_i)?;
+
+                        match chars.get(char_i) {
+                            Some('}') => char_i += 1,
+                            Some(v) => return Err(UnescapeError::InvalidHexDigit { index: escape_i, found: *v }),
+                            None => return Err(UnescapeError::UnexpectedEnd { index: escape_i }),
+                        }
+
+                        code_point
+                    } else {
+                        let high_code_point = SyntaxLeaf::read_hex_digits(&chars, &mut char_i, 4, escape_i)?;
+
+                        // note: 上位サロゲートの直後に \uXXXX 形式の下位サロゲートが続く場合はサロゲートペアとして 1 文字に結合する
+                        if (0xD800..=0xDBFF).contains(&high_code_point) && chars.get(char_i) == Some(&'\\') && chars.get(char_i + 1) == Some(&'u') {
+                            let mut low_char_i = char_i + 2;
+                            // note: 下位サロゲートとして読めない場合は上位サロゲート単体の不正なコードポイントとして扱う
+                            let low_code_point = match SyntaxLeaf::read_hex_digits(&chars, &mut low_char_i, 4, char_i) {
+                                Ok(v) => v,
+                                Err(_) => 0,
+                            };
+
+                            if (0xDC00..=0xDFFF).contains(&low_code_point) {
+                                char_i = low_char_i;
+                                0x10000 + ((high_code_point - 0xD800) << 10) + (low_code_point - 0xDC00)
+                            } else {
+                                high_code_point
+                            }
+                        } else {
+                            high_code_point
+                        }
+                    };
+
+                    SyntaxLeaf::to_escaped_char(code_point, escape_i)?
+                },
+                _ => return Err(UnescapeError::UnknownEscapeCharacter { index: escape_i, found: esc_char }),
+            };
+
+            unescaped.push(new_char);
+        }
+
+        return Ok(unescaped);
+    }
+
+    // note: char_i から digit_len 文字の 16 進数を読み進める; 桁数が多すぎる値は範囲外のコードポイントとして扱えるよう飽和させる
+    fn read_hex_digits(chars: &Vec<char>, char_i: &mut usize, digit_len: usize, escape_i: usize) -> std::result::Result<u32, UnescapeError> {
+        let mut code_point = 0u32;
+
+        for _ in 0..digit_len {
+            let each_char = match chars.get(*char_i) {
+                Some(v) => *v,
+                None => return Err(UnescapeError::UnexpectedEnd { index: escape_i }),
+            };
+
+            let digit = match each_char.to_digit(16) {
+                Some(v) => v,
+                None => return Err(UnescapeError::InvalidHexDigit { index: escape_i, found: each_char }),
+            };
+
+            code_point = code_point.saturating_mul(16).saturating_add(digit);
+            *char_i += 1;
+        }
+
+        return Ok(code_point);
+    }
+
+    fn to_escaped_char(code_point: u32, escape_i: usize) -> std::result::Result<char, UnescapeError> {
+        return match std::char::from_u32(code_point) {
+            Some(v) => Ok(v),
+            None => Err(UnescapeError::InvalidCodePoint { index: escape_i, code_point: code_point }),
+        };
+    }
+
+    // ret: 値の長さ; バイト列モードではバイト数、それ以外は文字数
+    pub fn get_value_len(&self) -> usize {
+        return match &self.raw_value {
+            Some(v) => v.len(),
+            None => self.value.chars().count(),
+        };
+    }
+
+    // ret: 値の直後の位置
+    // note: バイト列モードの位置はバイト単位
+    pub fn get_end_position(&self) -> CharacterPosition {
+        let mut end_pos = self.pos.clone();
+
+        let chars = match &self.raw_value {
+            Some(v) => v.iter().map(|each_byte| *each_byte as char).collect::<Vec<char>>(),
+            None => self.value.chars().collect::<Vec<char>>(),
+        };
+
+        for each_char in chars {
+            end_pos.index += 1;
+
+            if each_char == '\n' {
+                end_pos.line += 1;
+                end_pos.column = 0;
+            } else {
+                end_pos.column += 1;
+            }
+        }
+
+        return end_pos;
+    }
+
+    pub fn print_with_details<W: Write>(&self, nest: usize, writer: &mut W, ignore_hidden_elems: bool) -> std::io::Result<()> {
+        if !self.is_reflectable() && ignore_hidden_elems {
+            return Ok(());
+        }
+
+        return writeln!(writer, "{}", self.get_detail_label(nest));
     }
 
-    pub fn print_with_details(&self, nest: usize, writer: &mut BufWriter<StdoutLock>, ignore_hidden_elems: bool) {
+    fn collect_source_lines(&self, nest: usize, ignore_hidden_elems: bool, lines: &mut Vec<(String, String)>) {
         if !self.is_reflectable() && ignore_hidden_elems {
             return;
         }
 
+        lines.push((self.get_detail_label(nest), format_source_span(&self.pos, &self.get_end_position())));
+    }
+
+    fn get_detail_label(&self, nest: usize) -> String {
         let value = self.value
             .replace("\\", "\\\\")
             .replace("\n", "\\n")
             .replace("\t", "\\t");
 
-        let ast_reflection_str = match &self.ast_reflection_style {
-            ASTReflectionStyle::Reflection(elem_name) => format!("({})", elem_name.clone()),
-            ASTReflectionStyle::NoReflection => "[hidden]".to_string(),
-            ASTReflectionStyle::Expansion => "[expandable]".to_string(),
+        let ast_reflection_str = match &self.ast_reflection_style.visibility {
+            ASTVisibility::Reflect => format!("({})", self.ast_reflection_style.get_name()),
+            ASTVisibility::Hide => "[hidden]".to_string(),
+            ASTVisibility::Expand => "[expandable]".to_string(),
         };
 
         let pos_str = format!("{}:{}", self.pos.line + 1, self.pos.column + 1);
         let uuid_str = self.uuid.to_string()[..8].to_string();
 
-        writeln!(writer, "|{}- \"{}\" {} {} *{}", "   |".repeat(nest), value, pos_str, ast_reflection_str, uuid_str).unwrap();
+        return format!("|{}- \"{}\" {} {} *{}", "   |".repeat(nest), value, pos_str, ast_reflection_str, uuid_str);
+    }
+
+    pub fn write_hash(&self, hasher: &mut FxHasher64) {
+        self.ast_reflection_style.write_hash(hasher);
+        write_str_hash(hasher, &self.value);
+    }
+
+    // spec: AST 反映方式、UUID、位置、値、元のバイト列の順に書き込む
+    fn write_compact(&self, bytes: &mut Vec<u8>) {
+        write_compact_elem(bytes, COMPACT_LEAF_TAG, |bytes| {
+            self.ast_reflection_style.write_compact(bytes);
+            bytes.extend_from_slice(self.uuid.as_bytes());
+            self.pos.write_compact(bytes);
+            write_compact_bytes(bytes, self.value.as_bytes());
+
+            match &self.raw_value {
+                Some(v) => {
+                    bytes.push(1);
+                    write_compact_bytes(bytes, v);
+                },
+                None => bytes.push(0),
+            }
+        });
+    }
+
+    // spec: 名前が空であれば "値", そうでなければ 名前:"値" の形式
+    pub fn to_sexp(&self, ignore_hidden_elems: bool) -> Option<String> {
+        if ignore_hidden_elems && !self.is_reflectable() {
+            return None;
+        }
+
+        let value = self.value
+            .replace("\\", "\\\\")
+            .replace("\"", "\\\"")
+            .replace("\n", "\\n")
+            .replace("\t", "\\t")
+            .replace("\0", "\\0");
+
+        let s = match (&self.ast_reflection_style.visibility, &self.ast_reflection_style.name_override) {
+            (ASTVisibility::Reflect, None) => format!("\"{}\"", value),
+            _ => format!("{}:\"{}\"", self.ast_reflection_style.to_sexp_name(), value),
+        };
+
+        return Some(s);
+    }
+
+    #[cfg(feature = "json")]
+    pub fn to_json(&self, ignore_hidden_elems: bool) -> Option<serde_json::Value> {
+        if ignore_hidden_elems && !self.is_reflectable() {
+            return None;
+        }
+
+        return Some(serde_json::json!({
+            "kind": "leaf",
+            "name": self.ast_reflection_style.name_override,
+            "visibility": self.ast_reflection_style.get_visibility_name(),
+            "value": self.value,
+            "line": self.pos.line,
+            "column": self.pos.column,
+        }));
     }
 }
 
@@ -489,8 +2398,13 @@ impl Block {
 #[derive(Clone)]
 pub enum BlockCommand {
     Comment { pos: CharacterPosition, value: String },
+    // note: value は他の定数を展開済みの値
+    Const { pos: CharacterPosition, id: String, value: String },
     Define { pos: CharacterPosition, rule: Rule },
+    Entry { pos: CharacterPosition, file_alias_name: String, block_name: String, rule_name: String },
     Start { pos: CharacterPosition, file_alias_name: String, block_name: String, rule_name: String },
+    // note: rule_id は解決済みの規則 ID
+    Test { pos: CharacterPosition, rule_id: String, expectation: EmbeddedTestExpectation, input: String },
     Use { pos: CharacterPosition, file_alias_name: String, block_name: String, block_alias_name: String },
 }
 
@@ -498,8 +2412,11 @@ impl Display for BlockCommand {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             BlockCommand::Comment { pos, value } => return write!(f, "{}| %{},", pos.line, value),
+            BlockCommand::Const { pos, id, value } => return write!(f, "{}| const '{}' = \"{}\"", pos.line, id, value),
             BlockCommand::Define { pos, rule } => return write!(f, "{}| rule {}", pos.line, rule),
+            BlockCommand::Entry { pos, file_alias_name, block_name, rule_name } => return write!(f, "{}| entry rule '{}.{}.{}'", pos.line, file_alias_name, block_name, rule_name),
             BlockCommand::Start { pos, file_alias_name, block_name, rule_name } => return write!(f, "{}| start rule '{}.{}.{}'", pos.line, file_alias_name, block_name, rule_name),
+            BlockCommand::Test { pos, rule_id, expectation, input } => return write!(f, "{}| test rule '{}' {} {:?}", pos.line, rule_id, expectation, input),
             BlockCommand::Use { pos, file_alias_name, block_name, block_alias_name } => return write!(f, "{}| use block '{}.{}' as '{}'", pos.line, file_alias_name, block_name, block_alias_name),
         }
     }
@@ -0,0 +1,81 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use fcpeg::parser::*;
+
+use rustnutlib::console::*;
+
+#[path = "../benches/scenarios/mod.rs"]
+mod scenarios;
+
+use scenarios::*;
+
+// spec: すべてのベンチマークの場面で、最小の大きさの入力をメモ化の有無に応じて解析でき、入力をすべて消費することを確認する
+#[test]
+fn bench_scenarios_parse() {
+    let cons = match Console::load(None, ConsoleLogLimit::NoLimit) {
+        Ok(v) => Rc::new(RefCell::new(v)),
+        Err(_) => panic!("failed to load console"),
+    };
+
+    let input_len = BENCH_INPUT_LENS[0];
+
+    for each_scenario in BENCH_SCENARIOS {
+        let rule_map = each_scenario.load_rule_map(&cons);
+        let input = (each_scenario.generate_input)(input_len);
+
+        if input.len() < input_len {
+            panic!("generated input of scenario '{}' is shorter than {} bytes", each_scenario.name, input_len);
+        }
+
+        match SyntaxParser::parse_with_stats(cons.clone(), rule_map, BENCH_INPUT_FILE_PATH.to_string(), Box::new(input.clone()), each_scenario.get_config(input.len())) {
+            Ok(output) => assert_eq!(output.stats.consumed_char_count, input.chars().count(), "scenario '{}' did not consume the whole input", each_scenario.name),
+            Err(()) => {
+                cons.borrow().print_all();
+                panic!("failed to parse the input of scenario '{}'", each_scenario.name);
+            },
+        }
+    }
+}
+
+// spec: ベンチマーク (short_group_skip) と同じく入力を行ごとに分けて解析し、最小一致長に満たないグループの検査を省くと表現字句の評価回数が減り、消費する文字数は変わらないことを確認する
+#[test]
+fn short_group_skip_reduces_expr_evals() {
+    let cons = match Console::load(None, ConsoleLogLimit::NoLimit) {
+        Ok(v) => Rc::new(RefCell::new(v)),
+        Err(_) => panic!("failed to load console"),
+    };
+
+    let scenario = match BENCH_SCENARIOS.iter().find(|each_scenario| each_scenario.name == BENCH_SHORT_GROUP_SKIP_SCENARIO_NAME) {
+        Some(v) => v,
+        None => panic!("scenario '{}' is not found", BENCH_SHORT_GROUP_SKIP_SCENARIO_NAME),
+    };
+
+    let rule_map = scenario.load_rule_map(&cons);
+    let input = (scenario.generate_input)(BENCH_INPUT_LENS[0]);
+    // note: (表現字句の評価回数, 消費した文字数)
+    let mut counts = Vec::<(usize, usize)>::new();
+
+    for skip_short_groups in vec![true, false] {
+        let config = scenario.get_config(input.len()).with_short_group_skip(skip_short_groups);
+        let mut each_counts = (0, 0);
+
+        for each_line in split_input_lines(&input) {
+            match SyntaxParser::parse_with_stats(cons.clone(), rule_map.clone(), BENCH_INPUT_FILE_PATH.to_string(), Box::new(each_line.clone()), config.clone()) {
+                Ok(output) => {
+                    each_counts.0 += output.stats.expr_eval_count;
+                    each_counts.1 += output.stats.consumed_char_count;
+                },
+                Err(()) => {
+                    cons.borrow().print_all();
+                    panic!("failed to parse the line {:?} (skip_short_groups: {})", each_line, skip_short_groups);
+                },
+            }
+        }
+
+        counts.push(each_counts);
+    }
+
+    assert!(counts[0].0 < counts[1].0, "skipping short groups did not reduce expression evaluations (skipped: {}, unskipped: {})", counts[0].0, counts[1].0);
+    assert_eq!(counts[0].1, counts[1].1, "skipping short groups changed the consumed characters");
+}
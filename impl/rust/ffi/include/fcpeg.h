@@ -0,0 +1,51 @@
+/* fcpeg C API (generated by cbindgen; do not edit) */
+
+#ifndef FCPEG_H
+#define FCPEG_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+typedef struct ElementHandle ElementHandle;
+
+typedef struct GrammarHandle GrammarHandle;
+
+typedef struct TreeHandle TreeHandle;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+GrammarHandle *fcpeg_load_grammar(const char *src);
+
+TreeHandle *fcpeg_parse(const GrammarHandle *grammar, const char *input);
+
+const char *fcpeg_last_error(void);
+
+const ElementHandle *fcpeg_tree_root(const TreeHandle *tree);
+
+size_t fcpeg_child_count(const ElementHandle *elem);
+
+const ElementHandle *fcpeg_child_at(const ElementHandle *elem, size_t index);
+
+int32_t fcpeg_is_leaf(const ElementHandle *elem);
+
+char *fcpeg_node_name(const ElementHandle *elem);
+
+char *fcpeg_leaf_value(const ElementHandle *elem);
+
+int64_t fcpeg_leaf_line(const ElementHandle *elem);
+
+int64_t fcpeg_leaf_column(const ElementHandle *elem);
+
+void fcpeg_free_grammar(GrammarHandle *grammar);
+
+void fcpeg_free_tree(TreeHandle *tree);
+
+void fcpeg_free_string(char *s);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* FCPEG_H */
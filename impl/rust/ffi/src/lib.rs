@@ -0,0 +1,301 @@
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use fcpeg::*;
+use fcpeg::diagnostic::*;
+use fcpeg::parser::*;
+use fcpeg::rule::*;
+use fcpeg::tree::*;
+
+use rustnutlib::console::*;
+
+// spec: C ABI で文法の読み込み・構文解析・構文木の参照を公開する
+// note: ハンドルは Box::into_raw() で確保し、対応する fcpeg_free_*() で解放する; 解放後のハンドルやそこから得た要素を渡してはならない
+// note: コンソールが Rc を用いるため、ハンドルは作成したスレッド以外から使用しない
+// note: ポインタを受け取る関数は unsafe とする; いずれも null ポインタを受け付け、失敗として扱う (null もしくは -1 を返す); パニックは境界で捕捉して失敗とする
+
+// note: ログの出力先の仮想ファイルパス
+const FFI_GRAMMAR_FILE_PATH: &'static str = "main.fcpeg";
+const FFI_INPUT_FILE_PATH: &'static str = "input";
+
+thread_local! {
+    // note: 最後に失敗した fcpeg_load_grammar() / fcpeg_parse() のエラー; 次に呼び出すまで保持する
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+pub struct GrammarHandle {
+    rule_map: Arc<Box<RuleMap>>,
+}
+
+pub struct TreeHandle {
+    tree: SyntaxTree,
+}
+
+// spec: 構文木の要素 (SyntaxNodeElement) への参照; 所有する TreeHandle を解放すると無効になる
+// note: C 側から中身を参照させないための不透明型で、実体は常に SyntaxNodeElement
+pub struct ElementHandle {
+    _private: [u8; 0],
+}
+
+// spec: NUL 終端の UTF-8 文字列の文法を読み込む; 失敗すれば null を返し、fcpeg_last_error() に文法の構文解析のログを含むエラーを設定する
+#[no_mangle]
+pub unsafe extern "C" fn fcpeg_load_grammar(src: *const c_char) -> *mut GrammarHandle {
+    clear_last_error();
+
+    let result = catch_ffi_panic(|| {
+        let grammar_src = to_str(src, "grammar source")?;
+        let cons = new_console()?;
+        let mut diagnostics = Vec::<Diagnostic>::new();
+
+        return match FCPEGGrammar::from_str_with_diagnostics(cons, FFI_GRAMMAR_FILE_PATH, grammar_src, &mut diagnostics) {
+            Ok(rule_map) => Ok(Box::into_raw(Box::new(GrammarHandle { rule_map: rule_map }))),
+            Err(()) => Err(to_error_json("failed to load grammar", to_lsp_diagnostics(diagnostics, grammar_src))),
+        };
+    });
+
+    return match result {
+        Ok(handle) => handle,
+        Err(error_json) => {
+            set_last_error(error_json);
+            ptr::null_mut()
+        },
+    };
+}
+
+// spec: NUL 終端の UTF-8 文字列の入力を解析する; 失敗すれば null を返し、fcpeg_last_error() にログを含むエラーを設定する
+// note: メモ化は常に有効とする
+#[no_mangle]
+pub unsafe extern "C" fn fcpeg_parse(grammar: *const GrammarHandle, input: *const c_char) -> *mut TreeHandle {
+    clear_last_error();
+
+    let result = catch_ffi_panic(|| {
+        let grammar = match grammar.as_ref() {
+            Some(v) => v,
+            None => return Err(to_error_json("grammar handle is null", Vec::new())),
+        };
+
+        let input = to_str(input, "input")?;
+        let cons = new_console()?;
+        let mut parser = SyntaxParser::new(cons, grammar.rule_map.clone(), FFI_INPUT_FILE_PATH.to_string(), Box::new(input.to_string()), true);
+
+        return match parser.run() {
+            Ok(tree) => Ok(Box::into_raw(Box::new(TreeHandle { tree: tree }))),
+            Err(()) => {
+                Err(to_error_json("failed to parse input", to_lsp_diagnostics(parser.drain_diagnostics(), input)))
+            },
+        };
+    });
+
+    return match result {
+        Ok(handle) => handle,
+        Err(error_json) => {
+            set_last_error(error_json);
+            ptr::null_mut()
+        },
+    };
+}
+
+// spec: 最後に失敗した呼び出しのエラーを {"error": エラー文字列, "diagnostics": LSP の Diagnostic の配列} の JSON 文字列で返す; なければ null
+// note: 返す文字列はライブラリが所有し、同じスレッドで次に fcpeg_load_grammar() / fcpeg_parse() を呼び出すまで有効; 解放してはならない
+#[no_mangle]
+pub extern "C" fn fcpeg_last_error() -> *const c_char {
+    return LAST_ERROR.with(|last_error| {
+        match &*last_error.borrow() {
+            Some(v) => v.as_ptr(),
+            None => ptr::null(),
+        }
+    });
+}
+
+// ret: 構文木の最上位の要素
+#[no_mangle]
+pub unsafe extern "C" fn fcpeg_tree_root(tree: *const TreeHandle) -> *const ElementHandle {
+    return catch_ffi_panic(|| {
+        match tree.as_ref() {
+            Some(tree) => Ok(to_element_handle(tree.tree.get_child_ref())),
+            None => Ok(ptr::null()),
+        }
+    }).unwrap_or(ptr::null());
+}
+
+// ret: ノードの Reflectable な子要素の数; リーフであるか null であれば 0
+#[no_mangle]
+pub unsafe extern "C" fn fcpeg_child_count(elem: *const ElementHandle) -> usize {
+    return catch_ffi_panic(|| {
+        match to_element(elem) {
+            Some(SyntaxNodeElement::Node(node)) => Ok(node.reflectable_len()),
+            _ => Ok(0),
+        }
+    }).unwrap_or(0);
+}
+
+// ret: ノードの index 番目 (0 始まり) の Reflectable な子要素; 範囲外であるかリーフであれば null
+#[no_mangle]
+pub unsafe extern "C" fn fcpeg_child_at(elem: *const ElementHandle, index: usize) -> *const ElementHandle {
+    return catch_ffi_panic(|| {
+        match to_element(elem) {
+            Some(SyntaxNodeElement::Node(node)) => Ok(node.iter_reflectable().nth(index).map_or(ptr::null(), to_element_handle)),
+            _ => Ok(ptr::null()),
+        }
+    }).unwrap_or(ptr::null());
+}
+
+// ret: 要素がリーフであれば 1, ノードであれば 0; null であれば -1
+#[no_mangle]
+pub unsafe extern "C" fn fcpeg_is_leaf(elem: *const ElementHandle) -> i32 {
+    return catch_ffi_panic(|| {
+        match to_element(elem) {
+            Some(SyntaxNodeElement::Node(_)) => Ok(0),
+            Some(SyntaxNodeElement::Leaf(_)) => Ok(1),
+            None => Ok(-1),
+        }
+    }).unwrap_or(-1);
+}
+
+// ret: 要素 (ノード・リーフ) の反映名; 反映名がなければ空文字列, null であれば null
+// note: 返す文字列は fcpeg_free_string() で解放する
+#[no_mangle]
+pub unsafe extern "C" fn fcpeg_node_name(elem: *const ElementHandle) -> *mut c_char {
+    return catch_ffi_panic(|| {
+        match to_element(elem) {
+            Some(elem) => Ok(to_owned_c_string(elem.get_ast_reflection_style().get_name())),
+            None => Ok(ptr::null_mut()),
+        }
+    }).unwrap_or(ptr::null_mut());
+}
+
+// ret: リーフの値; ノードであるか null であれば null
+// note: 値がヌル文字を含む場合 (EOF 用のヌル文字やバイト列モードのリーフ等) は C 文字列で表せないため null を返す; 返す文字列は fcpeg_free_string() で解放する
+#[no_mangle]
+pub unsafe extern "C" fn fcpeg_leaf_value(elem: *const ElementHandle) -> *mut c_char {
+    return catch_ffi_panic(|| {
+        match to_element(elem) {
+            Some(SyntaxNodeElement::Leaf(leaf)) => Ok(to_owned_c_string(&leaf.value)),
+            _ => Ok(ptr::null_mut()),
+        }
+    }).unwrap_or(ptr::null_mut());
+}
+
+// ret: リーフの行番号 (0 始まり); ノードであるか null であれば -1
+#[no_mangle]
+pub unsafe extern "C" fn fcpeg_leaf_line(elem: *const ElementHandle) -> i64 {
+    return catch_ffi_panic(|| {
+        match to_element(elem) {
+            Some(SyntaxNodeElement::Leaf(leaf)) => Ok(leaf.pos.line as i64),
+            _ => Ok(-1),
+        }
+    }).unwrap_or(-1);
+}
+
+// ret: リーフの列番号 (0 始まり; 文字単位); ノードであるか null であれば -1
+#[no_mangle]
+pub unsafe extern "C" fn fcpeg_leaf_column(elem: *const ElementHandle) -> i64 {
+    return catch_ffi_panic(|| {
+        match to_element(elem) {
+            Some(SyntaxNodeElement::Leaf(leaf)) => Ok(leaf.pos.column as i64),
+            _ => Ok(-1),
+        }
+    }).unwrap_or(-1);
+}
+
+// note: null であれば何もしない; 文法から生成した構文木は文法の解放後も使用できる
+#[no_mangle]
+pub unsafe extern "C" fn fcpeg_free_grammar(grammar: *mut GrammarHandle) {
+    if !grammar.is_null() {
+        drop(Box::from_raw(grammar));
+    }
+}
+
+// note: null であれば何もしない; 構文木から得たすべての ElementHandle は無効になる
+#[no_mangle]
+pub unsafe extern "C" fn fcpeg_free_tree(tree: *mut TreeHandle) {
+    if !tree.is_null() {
+        drop(Box::from_raw(tree));
+    }
+}
+
+// spec: fcpeg_node_name() / fcpeg_leaf_value() が返した文字列を解放する; null であれば何もしない
+#[no_mangle]
+pub unsafe extern "C" fn fcpeg_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+// ret: パニックした場合は Err(エラーの JSON 文字列)
+fn catch_ffi_panic<T, F: FnOnce() -> Result<T, String>>(f: F) -> Result<T, String> {
+    return match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(payload) => {
+            let msg = match payload.downcast_ref::<&str>() {
+                Some(v) => v.to_string(),
+                None => match payload.downcast_ref::<String>() {
+                    Some(v) => v.clone(),
+                    None => "unknown panic".to_string(),
+                },
+            };
+
+            Err(to_error_json(&format!("panicked: {}", msg), Vec::new()))
+        },
+    };
+}
+
+fn new_console() -> Result<Rc<RefCell<Console>>, String> {
+    return match Console::load(None, ConsoleLogLimit::NoLimit) {
+        Ok(v) => Ok(Rc::new(RefCell::new(v))),
+        Err(_) => Err(to_error_json("failed to load console", Vec::new())),
+    };
+}
+
+unsafe fn to_str<'a>(s: *const c_char, name: &str) -> Result<&'a str, String> {
+    if s.is_null() {
+        return Err(to_error_json(&format!("{} is null", name), Vec::new()));
+    }
+
+    return match CStr::from_ptr(s).to_str() {
+        Ok(v) => Ok(v),
+        Err(_) => Err(to_error_json(&format!("{} is not valid UTF-8", name), Vec::new())),
+    };
+}
+
+unsafe fn to_element<'a>(elem: *const ElementHandle) -> Option<&'a SyntaxNodeElement> {
+    return (elem as *const SyntaxNodeElement).as_ref();
+}
+
+fn to_element_handle(elem: &SyntaxNodeElement) -> *const ElementHandle {
+    return elem as *const SyntaxNodeElement as *const ElementHandle;
+}
+
+fn to_owned_c_string(s: &str) -> *mut c_char {
+    return match CString::new(s) {
+        Ok(v) => v.into_raw(),
+        Err(_) => ptr::null_mut(),
+    };
+}
+
+// note: 列数は LSP の既定の単位である UTF-16 のコード単位に変換する
+fn to_lsp_diagnostics(diagnostics: Vec<Diagnostic>, src: &str) -> Vec<serde_json::Value> {
+    return diagnostics.iter().map(|each_diagnostic| each_diagnostic.to_utf16(src).to_lsp_json()).collect::<Vec<serde_json::Value>>();
+}
+
+fn to_error_json(msg: &str, diagnostics: Vec<serde_json::Value>) -> String {
+    return serde_json::json!({
+        "error": msg,
+        "diagnostics": diagnostics,
+    }).to_string();
+}
+
+fn set_last_error(error_json: String) {
+    // note: JSON 文字列はヌル文字をエスケープするため変換に失敗しない
+    let error = CString::new(error_json).ok();
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = error);
+}
+
+fn clear_last_error() {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = None);
+}
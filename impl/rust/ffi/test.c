@@ -0,0 +1,115 @@
+/*
+ * fcpeg の C API の動作確認
+ *
+ * cargo build
+ * cc test.c -Iinclude -Ltarget/debug -lfcpeg_ffi -o target/ffi_test
+ * LD_LIBRARY_PATH=target/debug target/ffi_test
+ */
+
+#include <assert.h>
+#include <stdio.h>
+#include <string.h>
+
+#include "fcpeg.h"
+
+static const char *GRAMMAR_SRC =
+    "[Main]{\n"
+    "    Main <- Item* \"\\z\"#,\n"
+    "    Item <- Word \"\\n\"# : Num,\n"
+    "    Word <- JOIN<[a-z]+>,\n"
+    "    Num <- JOIN<[0-9]+>,\n"
+    "}\n";
+
+static void assert_name(const ElementHandle *elem, const char *expected) {
+    char *name = fcpeg_node_name(elem);
+    assert(name != NULL);
+    assert(strcmp(name, expected) == 0);
+    fcpeg_free_string(name);
+}
+
+static void assert_leaf(const ElementHandle *elem, const char *expected_value, int64_t expected_line, int64_t expected_column) {
+    char *value;
+
+    assert(fcpeg_is_leaf(elem) == 1);
+    value = fcpeg_leaf_value(elem);
+    assert(value != NULL);
+    assert(strcmp(value, expected_value) == 0);
+    fcpeg_free_string(value);
+
+    assert(fcpeg_leaf_line(elem) == expected_line);
+    assert(fcpeg_leaf_column(elem) == expected_column);
+}
+
+static void test_tree_access(const GrammarHandle *grammar) {
+    TreeHandle *tree = fcpeg_parse(grammar, "ab\n12");
+    const ElementHandle *root;
+    const ElementHandle *item;
+
+    assert(tree != NULL);
+    assert(fcpeg_last_error() == NULL);
+
+    /* (Main (Item (Word "ab")) (Item (Num "12"))) */
+    root = fcpeg_tree_root(tree);
+    assert(fcpeg_is_leaf(root) == 0);
+    assert_name(root, "Main");
+    assert(fcpeg_child_count(root) == 2);
+    assert(fcpeg_child_at(root, 2) == NULL);
+
+    item = fcpeg_child_at(root, 0);
+    assert_name(item, "Item");
+    assert(fcpeg_child_count(item) == 1);
+    assert_name(fcpeg_child_at(item, 0), "Word");
+    assert_leaf(fcpeg_child_at(fcpeg_child_at(item, 0), 0), "ab", 0, 0);
+
+    item = fcpeg_child_at(root, 1);
+    assert_name(fcpeg_child_at(item, 0), "Num");
+    assert_leaf(fcpeg_child_at(fcpeg_child_at(item, 0), 0), "12", 1, 0);
+
+    /* ノードに対するリーフ用の関数は失敗する */
+    assert(fcpeg_leaf_value(root) == NULL);
+    assert(fcpeg_leaf_line(root) == -1);
+
+    fcpeg_free_tree(tree);
+}
+
+static void test_errors(const GrammarHandle *grammar) {
+    const char *error;
+
+    assert(fcpeg_parse(grammar, "ab!") == NULL);
+    error = fcpeg_last_error();
+    assert(error != NULL);
+    assert(strstr(error, "\"error\":\"failed to parse input\"") != NULL);
+    assert(strstr(error, "\"diagnostics\":[{") != NULL);
+
+    assert(fcpeg_load_grammar("[Main]{ Main <- }") == NULL);
+    error = fcpeg_last_error();
+    assert(strstr(error, "\"error\":\"failed to load grammar\"") != NULL);
+    assert(strstr(error, "\"diagnostics\":[{") != NULL);
+
+    /* null ポインタは失敗として扱う */
+    assert(fcpeg_load_grammar(NULL) == NULL);
+    assert(fcpeg_parse(NULL, "ab") == NULL);
+    assert(strstr(fcpeg_last_error(), "grammar handle is null") != NULL);
+    assert(fcpeg_tree_root(NULL) == NULL);
+    assert(fcpeg_child_count(NULL) == 0);
+    assert(fcpeg_is_leaf(NULL) == -1);
+    assert(fcpeg_node_name(NULL) == NULL);
+    fcpeg_free_tree(NULL);
+    fcpeg_free_string(NULL);
+}
+
+int main(void) {
+    GrammarHandle *grammar = fcpeg_load_grammar(GRAMMAR_SRC);
+
+    if (grammar == NULL) {
+        fprintf(stderr, "%s\n", fcpeg_last_error());
+        return 1;
+    }
+
+    test_tree_access(grammar);
+    test_errors(grammar);
+
+    fcpeg_free_grammar(grammar);
+    puts("ok");
+    return 0;
+}
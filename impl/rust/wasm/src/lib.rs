@@ -0,0 +1,48 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use fcpeg::*;
+use fcpeg::parser::*;
+
+use rustnutlib::console::*;
+
+use wasm_bindgen::prelude::*;
+
+// spec: 文法と入力を受け取り {"tree": 構文木, "diagnostics": 診断情報の配列, "error": エラー文字列} の JSON 文字列を返す
+// note: 解析に失敗した場合 tree は null; 文法の読み込みに失敗した場合は error のみを設定する
+#[wasm_bindgen]
+pub fn parse(grammar_src: &str, input: &str) -> String {
+    let cons = match Console::load(None, ConsoleLogLimit::NoLimit) {
+        Ok(v) => Rc::new(RefCell::new(v)),
+        Err(_) => return to_error_json("failed to load console"),
+    };
+
+    let rule_map = match FCPEGGrammar::from_str(cons.clone(), "main.fcpeg", grammar_src) {
+        Ok(v) => v,
+        Err(_) => return to_error_json("failed to load grammar"),
+    };
+
+    let mut parser = SyntaxParser::new(cons, rule_map, "input".to_string(), Box::new(input.to_string()), true);
+
+    let tree = match parser.run() {
+        Ok(tree) => tree.to_json(true),
+        Err(_) => serde_json::Value::Null,
+    };
+
+    // note: 列数は LSP の既定の単位である UTF-16 のコード単位に変換する
+    let diagnostics = parser.drain_diagnostics().iter().map(|each_diagnostic| each_diagnostic.to_utf16(input).to_lsp_json()).collect::<Vec<serde_json::Value>>();
+
+    return serde_json::json!({
+        "tree": tree,
+        "diagnostics": diagnostics,
+        "error": serde_json::Value::Null,
+    }).to_string();
+}
+
+fn to_error_json(msg: &str) -> String {
+    return serde_json::json!({
+        "tree": serde_json::Value::Null,
+        "diagnostics": Vec::<serde_json::Value>::new(),
+        "error": msg,
+    }).to_string();
+}